@@ -0,0 +1,245 @@
+//! Keyboard connection and depth table.
+//!
+//! A background thread keeps one keyboard open with magnetism reporting on
+//! and stores the latest travel of every key, normalized to 0.0..=1.0 and
+//! indexed by HID usage. It reconnects when the keyboard goes away.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use monsgeek_keyboard::{KeyboardInterface, TimestampedEvent, VendorEvent};
+use monsgeek_transport::protocol::ProtocolFamily;
+use monsgeek_transport::{list_devices_sync, open_device_sync, Transport};
+use tokio::sync::broadcast::{self, error::TryRecvError};
+use tracing::{debug, info, warn};
+
+use crate::keys::hid_usage;
+
+/// Travel reported as full press (1.0) unless `MONSGEEK_ANALOG_TRAVEL_MM`
+/// says otherwise.
+pub const DEFAULT_TRAVEL_MM: f64 = 3.4;
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Identity of the connected keyboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub vid: u16,
+    pub pid: u16,
+    pub name: String,
+    pub id: u64,
+}
+
+/// Connection changes reported to the SDK.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceEvent {
+    Connected,
+    Disconnected,
+}
+
+/// Stable device id from VID, PID and serial (FNV-1a), so the SDK sees the
+/// same id across reconnects.
+pub fn device_id(vid: u16, pid: u16, serial: Option<&str>) -> u64 {
+    let key = format!("{vid:04x}:{pid:04x}:{}", serial.unwrap_or(""));
+    key.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Raw depth → 0.0..=1.0 of `travel_mm`.
+pub fn analog_value(depth_raw: u16, precision_factor: f64, travel_mm: f64) -> f32 {
+    (depth_raw as f64 / precision_factor / travel_mm).clamp(0.0, 1.0) as f32
+}
+
+/// Full-press travel from the environment, or [`DEFAULT_TRAVEL_MM`].
+pub fn travel_mm() -> f64 {
+    std::env::var("MONSGEEK_ANALOG_TRAVEL_MM")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|mm| *mm > 0.0)
+        .unwrap_or(DEFAULT_TRAVEL_MM)
+}
+
+/// State shared between the reader thread and the SDK entry points.
+#[derive(Default)]
+pub struct Shared {
+    /// Non-zero analog values by HID usage
+    pub values: Mutex<HashMap<u16, f32>>,
+    pub device: Mutex<Option<DeviceInfo>>,
+    pub stop: AtomicBool,
+}
+
+/// An open keyboard with magnetism reporting enabled.
+pub struct Connection {
+    keyboard: KeyboardInterface,
+    info: DeviceInfo,
+    precision_factor: f64,
+    events: broadcast::Receiver<TimestampedEvent>,
+}
+
+impl Connection {
+    pub fn info(&self) -> &DeviceInfo {
+        &self.info
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.keyboard.stop_magnetism_report();
+    }
+}
+
+/// Open the first supported keyboard and start depth reports.
+pub fn connect() -> Option<Connection> {
+    let devices = match list_devices_sync() {
+        Ok(d) if !d.is_empty() => d,
+        Ok(_) => {
+            debug!("No supported keyboard found");
+            return None;
+        }
+        Err(e) => {
+            warn!("Failed to list devices: {}", e);
+            return None;
+        }
+    };
+    let transport = match open_device_sync(&devices[0]) {
+        Ok(t) => t,
+        Err(e) => {
+            warn!("Failed to open device: {}", e);
+            return None;
+        }
+    };
+
+    let dev = transport.device_info();
+    let info = DeviceInfo {
+        vid: dev.vid,
+        pid: dev.pid,
+        name: dev
+            .product_name
+            .clone()
+            .unwrap_or_else(|| "MonsGeek keyboard".to_string()),
+        id: device_id(dev.vid, dev.pid, dev.serial.as_deref()),
+    };
+    let protocol = ProtocolFamily::detect(None, dev.pid);
+    let keyboard = KeyboardInterface::new(transport, 0, false, protocol);
+
+    let precision_factor = match keyboard.get_precision() {
+        Ok(precision) => precision.factor(),
+        Err(e) => {
+            warn!("Failed to get precision: {}", e);
+            return None;
+        }
+    };
+    if let Err(e) = keyboard.start_magnetism_report() {
+        warn!("Failed to start magnetism report: {}", e);
+        return None;
+    }
+    let Some(events) = keyboard.subscribe_events() else {
+        warn!("Event subscription not supported (no input endpoint)");
+        return None;
+    };
+    info!(
+        "Analog plugin connected to {} ({:04x}:{:04x})",
+        info.name, info.vid, info.pid
+    );
+
+    Some(Connection {
+        keyboard,
+        info,
+        precision_factor,
+        events,
+    })
+}
+
+/// Reader loop: runs until `shared.stop` is set, starting with `initial`
+/// if the caller already connected. `on_event` sees every connect and
+/// disconnect.
+pub fn run(
+    shared: Arc<Shared>,
+    initial: Option<Connection>,
+    on_event: impl Fn(DeviceEvent, &DeviceInfo),
+) {
+    let travel = travel_mm();
+    let mut next = initial;
+    while !shared.stop.load(Ordering::Relaxed) {
+        let Some(mut conn) = next.take().or_else(connect) else {
+            sleep_unless_stopped(&shared, RETRY_INTERVAL);
+            continue;
+        };
+        *shared.device.lock().unwrap() = Some(conn.info.clone());
+        on_event(DeviceEvent::Connected, &conn.info);
+
+        read_depths(&shared, &mut conn, travel);
+
+        shared.values.lock().unwrap().clear();
+        *shared.device.lock().unwrap() = None;
+        on_event(DeviceEvent::Disconnected, &conn.info);
+        if !shared.stop.load(Ordering::Relaxed) {
+            info!("Analog plugin lost {}, reconnecting...", conn.info.name);
+        }
+    }
+}
+
+/// Apply depth reports until the keyboard disappears or we are stopped.
+fn read_depths(shared: &Shared, conn: &mut Connection, travel: f64) {
+    while !shared.stop.load(Ordering::Relaxed) {
+        match conn.events.try_recv() {
+            Ok(ts) => {
+                let VendorEvent::KeyDepth {
+                    key_index,
+                    depth_raw,
+                } = ts.event
+                else {
+                    continue;
+                };
+                let Some(usage) = hid_usage(key_index) else {
+                    continue;
+                };
+                let value = analog_value(depth_raw, conn.precision_factor, travel);
+                let mut values = shared.values.lock().unwrap();
+                if value > 0.0 {
+                    values.insert(usage, value);
+                } else {
+                    values.remove(&usage);
+                }
+            }
+            Err(TryRecvError::Empty) => thread::sleep(POLL_INTERVAL),
+            Err(TryRecvError::Lagged(n)) => debug!("Depth reader lagged by {} events", n),
+            Err(TryRecvError::Closed) => return,
+        }
+    }
+}
+
+fn sleep_unless_stopped(shared: &Shared, total: Duration) {
+    let step = Duration::from_millis(100);
+    let mut slept = Duration::ZERO;
+    while slept < total && !shared.stop.load(Ordering::Relaxed) {
+        thread::sleep(step);
+        slept += step;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analog_value_is_clamped_fraction_of_travel() {
+        // factor 100 = 0.01mm steps
+        assert_eq!(analog_value(0, 100.0, 3.4), 0.0);
+        assert!((analog_value(170, 100.0, 3.4) - 0.5).abs() < 1e-6);
+        assert_eq!(analog_value(400, 100.0, 3.4), 1.0);
+    }
+
+    #[test]
+    fn device_id_is_stable_and_distinct() {
+        let a = device_id(0x3151, 0x5030, Some("ABC"));
+        assert_eq!(a, device_id(0x3151, 0x5030, Some("ABC")));
+        assert_ne!(a, device_id(0x3151, 0x5030, Some("ABD")));
+        assert_ne!(a, device_id(0x3151, 0x5031, None));
+    }
+}
@@ -0,0 +1,93 @@
+//! Matrix position → HID usage.
+//!
+//! The Analog SDK always hands plugins HID usage codes (it translates
+//! scan codes and virtual keys itself), so depth reports are stored by
+//! the usage of the key's default base-layer function.
+
+use monsgeek_transport::protocol::matrix;
+
+/// HID usage of the key at matrix `index`, `None` for empty slots and Fn.
+pub fn hid_usage(index: u8) -> Option<u16> {
+    let usage = match matrix::key_name(index) {
+        "Esc" => 0x29,
+        "`" => 0x35,
+        "Tab" => 0x2B,
+        "Caps" => 0x39,
+        "LShf" => 0xE1,
+        "LCtl" => 0xE0,
+        "Win" => 0xE3,
+        "LAlt" => 0xE2,
+        "Spc" => 0x2C,
+        "RAlt" => 0xE6,
+        "RCtl" => 0xE4,
+        "RShf" => 0xE5,
+        "IntlBs" => 0x64,
+        "IntlRo" => 0x87,
+        "1" => 0x1E,
+        "2" => 0x1F,
+        "3" => 0x20,
+        "4" => 0x21,
+        "5" => 0x22,
+        "6" => 0x23,
+        "7" => 0x24,
+        "8" => 0x25,
+        "9" => 0x26,
+        "0" => 0x27,
+        "-" => 0x2D,
+        "=" => 0x2E,
+        "[" => 0x2F,
+        "]" => 0x30,
+        "\\" => 0x31,
+        ";" => 0x33,
+        "'" => 0x34,
+        "," => 0x36,
+        "." => 0x37,
+        "/" => 0x38,
+        "Ent" => 0x28,
+        "Bksp" => 0x2A,
+        "Del" => 0x4C,
+        "Home" => 0x4A,
+        "PgUp" => 0x4B,
+        "End" => 0x4D,
+        "PgDn" => 0x4E,
+        "Right" => 0x4F,
+        "Left" => 0x50,
+        "Down" => 0x51,
+        "Up" => 0x52,
+        name => {
+            let bytes = name.as_bytes();
+            match bytes {
+                [c @ b'A'..=b'Z'] => 0x04 + (c - b'A') as u16,
+                [b'F', ..] => 0x3A + name[1..].parse::<u16>().ok()?.checked_sub(1)?,
+                _ => return None,
+            }
+        }
+    };
+    Some(usage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_known_positions() {
+        assert_eq!(hid_usage(0), Some(0x29)); // Esc
+        assert_eq!(hid_usage(9), Some(0x04)); // A
+        assert_eq!(hid_usage(14), Some(0x1A)); // W
+        assert_eq!(hid_usage(6), Some(0x3A)); // F1
+        assert_eq!(hid_usage(72), Some(0x45)); // F12
+        assert_eq!(hid_usage(23), None); // empty slot
+        assert_eq!(hid_usage(65), None); // Fn
+    }
+
+    #[test]
+    fn every_named_key_has_a_usage() {
+        for index in 0..=255u8 {
+            let name = matrix::key_name(index);
+            if name != "?" && name != "Fn" {
+                assert!(hid_usage(index).is_some(), "{name} at {index}");
+            }
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! Wooting Analog SDK plugin for MonsGeek/Akko magnetic keyboards
+//!
+//! Built as a C-ABI plugin: copy `libmonsgeek_analog_plugin.so` into the
+//! SDK's plugin directory (`/usr/local/share/WootingAnalogPlugins/monsgeek/`)
+//! and games using the Analog SDK read key travel from the magnetism depth
+//! stream. Values are 0.0 (released) to 1.0 (full travel, 3.4mm by default,
+//! override with `MONSGEEK_ANALOG_TRAVEL_MM`), keyed by HID usage.
+//!
+//! The plugin holds the keyboard's vendor interface while loaded, so don't
+//! run it next to another client that enables depth reports.
+
+pub mod device;
+pub mod keys;
+
+use std::ffi::{c_char, c_float, c_int, c_uint, c_ushort, c_void, CString};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use device::{DeviceEvent, DeviceInfo, Shared};
+
+/// `WootingAnalogResult` codes used by this plugin.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalogResult {
+    Ok = 1,
+    UnInitialized = -2000,
+    NoDevices = -1999,
+    DeviceDisconnected = -1998,
+    InvalidArgument = -1996,
+}
+
+/// `WootingAnalog_DeviceType`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub enum DeviceType {
+    Keyboard = 1,
+}
+
+/// `WootingAnalog_DeviceEventType`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub enum DeviceEventType {
+    Connected = 1,
+    Disconnected = 2,
+}
+
+/// `WootingAnalog_DeviceInfo_FFI`; strings are owned by the plugin.
+#[repr(C)]
+#[derive(Debug)]
+pub struct DeviceInfoFfi {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub manufacturer_name: *mut c_char,
+    pub device_name: *mut c_char,
+    pub device_id: u64,
+    pub device_type: DeviceType,
+}
+
+type DeviceEventCallback = extern "C" fn(*mut c_void, DeviceEventType, *mut DeviceInfoFfi);
+
+/// FFI view of the connected keyboard; keeps its strings alive.
+struct FfiDevice {
+    info: Box<DeviceInfoFfi>,
+    _manufacturer: CString,
+    _name: CString,
+}
+
+impl FfiDevice {
+    fn new(dev: &DeviceInfo) -> Self {
+        let manufacturer = CString::new("MonsGeek").unwrap();
+        let name = CString::new(dev.name.replace('\0', "")).unwrap();
+        let info = Box::new(DeviceInfoFfi {
+            vendor_id: dev.vid,
+            product_id: dev.pid,
+            manufacturer_name: manufacturer.as_ptr() as *mut c_char,
+            device_name: name.as_ptr() as *mut c_char,
+            device_id: dev.id,
+            device_type: DeviceType::Keyboard,
+        });
+        Self {
+            info,
+            _manufacturer: manufacturer,
+            _name: name,
+        }
+    }
+
+    fn ptr(&mut self) -> *mut DeviceInfoFfi {
+        &mut *self.info
+    }
+}
+
+// SAFETY: the raw pointers only point into the CStrings owned alongside.
+unsafe impl Send for FfiDevice {}
+
+/// The SDK's callback and its opaque data pointer.
+#[derive(Clone, Copy)]
+struct Callback {
+    data: *mut c_void,
+    func: DeviceEventCallback,
+}
+
+// SAFETY: the SDK expects device events from plugin threads.
+unsafe impl Send for Callback {}
+
+struct Plugin {
+    shared: Arc<Shared>,
+    reader: JoinHandle<()>,
+}
+
+static PLUGIN: Mutex<Option<Plugin>> = Mutex::new(None);
+static DEVICE: Mutex<Option<FfiDevice>> = Mutex::new(None);
+
+/// The requested device is ours (0 = any device).
+fn selects(shared: &Shared, device: u64) -> Result<(), AnalogResult> {
+    match shared.device.lock().unwrap().as_ref() {
+        None => Err(AnalogResult::NoDevices),
+        Some(dev) if device != 0 && device != dev.id => Err(AnalogResult::DeviceDisconnected),
+        Some(_) => Ok(()),
+    }
+}
+
+fn with_plugin<T>(f: impl FnOnce(&Shared) -> T) -> Option<T> {
+    PLUGIN.lock().unwrap().as_ref().map(|p| f(&p.shared))
+}
+
+fn on_device_event(callback: Option<Callback>, event: DeviceEvent, dev: &DeviceInfo) {
+    let mut slot = DEVICE.lock().unwrap();
+    // Keep the existing info (and pointers handed out) for the same device
+    if event == DeviceEvent::Connected && slot.as_ref().map(|d| d.info.device_id) != Some(dev.id) {
+        *slot = Some(FfiDevice::new(dev));
+    }
+    // A removed device's info stays alive until the callback has returned
+    let mut removed = match event {
+        DeviceEvent::Disconnected => slot.take(),
+        DeviceEvent::Connected => None,
+    };
+    let info = removed.as_mut().or(slot.as_mut()).map(FfiDevice::ptr);
+    // The callback may call back in (`_device_info` locks DEVICE). Only this
+    // reader thread replaces the slot, so the boxed info doesn't move.
+    drop(slot);
+    if let (Some(cb), Some(info)) = (callback, info) {
+        let kind = match event {
+            DeviceEvent::Connected => DeviceEventType::Connected,
+            DeviceEvent::Disconnected => DeviceEventType::Disconnected,
+        };
+        (cb.func)(cb.data, kind, info);
+    }
+    drop(removed);
+}
+
+/// Plugin name shown by the SDK.
+#[no_mangle]
+pub extern "C" fn _name() -> *const c_char {
+    c"MonsGeek Analog Plugin".as_ptr()
+}
+
+/// Marks this library as a C-ABI plugin.
+#[no_mangle]
+pub extern "C" fn is_c_plugin() -> bool {
+    true
+}
+
+/// Connect to the keyboard and start the reader. Returns the number of
+/// connected devices; later (dis)connects are reported through `callback`.
+#[no_mangle]
+pub extern "C" fn _initialise(
+    callback_data: *mut c_void,
+    callback: Option<DeviceEventCallback>,
+) -> c_int {
+    let mut plugin = PLUGIN.lock().unwrap();
+    if let Some(p) = plugin.as_ref() {
+        return p.shared.device.lock().unwrap().is_some() as c_int;
+    }
+    let callback = callback.map(|func| Callback {
+        data: callback_data,
+        func,
+    });
+
+    let shared = Arc::new(Shared::default());
+    let initial = device::connect();
+    let connected = initial.is_some();
+    if let Some(conn) = &initial {
+        // Publish before returning so the first read already sees it
+        *shared.device.lock().unwrap() = Some(conn.info().clone());
+        *DEVICE.lock().unwrap() = Some(FfiDevice::new(conn.info()));
+    }
+    let reader = {
+        let shared = Arc::clone(&shared);
+        std::thread::spawn(move || {
+            device::run(shared, initial, |event, dev| {
+                on_device_event(callback, event, dev)
+            })
+        })
+    };
+    *plugin = Some(Plugin { shared, reader });
+    connected as c_int
+}
+
+#[no_mangle]
+pub extern "C" fn _is_initialised() -> bool {
+    PLUGIN.lock().unwrap().is_some()
+}
+
+/// Stop the reader and release the keyboard.
+#[no_mangle]
+pub extern "C" fn _unload() {
+    let Some(plugin) = PLUGIN.lock().unwrap().take() else {
+        return;
+    };
+    plugin.shared.stop.store(true, Ordering::Relaxed);
+    let _ = plugin.reader.join();
+}
+
+/// Fill `buffer` with pointers to connected devices' info, valid until
+/// the device disconnects. Returns how many were written.
+///
+/// # Safety
+/// `buffer` must have room for `len` pointers.
+#[no_mangle]
+pub unsafe extern "C" fn _device_info(buffer: *mut *mut DeviceInfoFfi, len: c_uint) -> c_int {
+    if !_is_initialised() {
+        return AnalogResult::UnInitialized as c_int;
+    }
+    if buffer.is_null() {
+        return AnalogResult::InvalidArgument as c_int;
+    }
+    match DEVICE.lock().unwrap().as_mut() {
+        Some(dev) if len > 0 => {
+            *buffer = dev.ptr();
+            1
+        }
+        _ => 0,
+    }
+}
+
+/// Travel of the key with HID usage `code` on `device` (0 = any).
+#[no_mangle]
+pub extern "C" fn _read_analog(code: u16, device: u64) -> c_float {
+    with_plugin(|shared| match selects(shared, device) {
+        Ok(()) => shared
+            .values
+            .lock()
+            .unwrap()
+            .get(&code)
+            .copied()
+            .unwrap_or(0.0),
+        Err(e) => e as i32 as f32,
+    })
+    .unwrap_or(AnalogResult::UnInitialized as i32 as f32)
+}
+
+/// Write every pressed key's HID usage and travel, up to `len` entries.
+/// Returns how many were written.
+///
+/// # Safety
+/// Both buffers must have room for `len` entries.
+#[no_mangle]
+pub unsafe extern "C" fn _read_full_buffer(
+    code_buffer: *mut c_ushort,
+    analog_buffer: *mut c_float,
+    len: c_uint,
+    device: u64,
+) -> c_int {
+    if code_buffer.is_null() || analog_buffer.is_null() {
+        return AnalogResult::InvalidArgument as c_int;
+    }
+    let pressed = with_plugin(|shared| -> Result<Vec<_>, AnalogResult> {
+        selects(shared, device)?;
+        Ok(shared
+            .values
+            .lock()
+            .unwrap()
+            .iter()
+            .take(len as usize)
+            .map(|(&code, &value)| (code, value))
+            .collect())
+    });
+    match pressed {
+        None => AnalogResult::UnInitialized as c_int,
+        Some(Err(e)) => e as c_int,
+        Some(Ok(pressed)) => {
+            for (i, (code, value)) in pressed.iter().enumerate() {
+                *code_buffer.add(i) = *code;
+                *analog_buffer.add(i) = *value;
+            }
+            pressed.len() as c_int
+        }
+    }
+}
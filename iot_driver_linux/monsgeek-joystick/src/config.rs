@@ -5,6 +5,7 @@
 //! with custom serde that serializes as bare key name strings (e.g. `"W"`)
 //! and accepts both the new format and the old `{ key_index, label }` format.
 
+use crate::joystick::AxisRange;
 use monsgeek_transport::protocol::{matrix, KeyRef, Layer};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::path::PathBuf;
@@ -18,6 +19,12 @@ pub enum AxisId {
     RY,
     Z,
     RZ,
+    /// Flight-sim and wheel controls beyond the gamepad's axes
+    Throttle,
+    Rudder,
+    Wheel,
+    Gas,
+    Brake,
 }
 
 impl AxisId {
@@ -30,6 +37,11 @@ impl AxisId {
             AxisId::RY => "RY",
             AxisId::Z => "Z",
             AxisId::RZ => "RZ",
+            AxisId::Throttle => "Throttle",
+            AxisId::Rudder => "Rudder",
+            AxisId::Wheel => "Wheel",
+            AxisId::Gas => "Gas",
+            AxisId::Brake => "Brake",
         }
     }
 
@@ -41,6 +53,11 @@ impl AxisId {
         AxisId::RY,
         AxisId::Z,
         AxisId::RZ,
+        AxisId::Throttle,
+        AxisId::Rudder,
+        AxisId::Wheel,
+        AxisId::Gas,
+        AxisId::Brake,
     ];
 }
 
@@ -87,6 +104,67 @@ fn deserialize_keyref<'de, D: Deserializer<'de>>(d: D) -> Result<KeyRef, D::Erro
     }
 }
 
+/// Serialize an optional `KeyRef` as its key name string.
+fn serialize_opt_keyref<S: Serializer>(key: &Option<KeyRef>, s: S) -> Result<S::Ok, S::Error> {
+    match key {
+        Some(key) => serialize_keyref(key, s),
+        None => s.serialize_none(),
+    }
+}
+
+/// Deserialize an optional `KeyRef` in either format.
+fn deserialize_opt_keyref<'de, D: Deserializer<'de>>(d: D) -> Result<Option<KeyRef>, D::Error> {
+    #[derive(Deserialize)]
+    struct Repr(#[serde(deserialize_with = "deserialize_keyref")] KeyRef);
+
+    Ok(Option::<Repr>::deserialize(d)?.map(|Repr(key)| key))
+}
+
+/// SOCD (simultaneous opposing cardinal directions) cleaning: what a two-key
+/// axis reports while both keys are held
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SocdMode {
+    /// Depths are subtracted; equal presses cancel out
+    #[default]
+    Blend,
+    /// Both held = centered
+    Neutral,
+    /// The key pressed most recently wins (snap tap)
+    LastInput,
+    /// The positive key always wins
+    PositivePriority,
+    /// The negative key always wins
+    NegativePriority,
+    /// The deeper-pressed key wins
+    Deeper,
+}
+
+impl SocdMode {
+    /// Get display name for the mode
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            SocdMode::Blend => "blend",
+            SocdMode::Neutral => "neutral",
+            SocdMode::LastInput => "last input",
+            SocdMode::PositivePriority => "positive wins",
+            SocdMode::NegativePriority => "negative wins",
+            SocdMode::Deeper => "deeper wins",
+        }
+    }
+
+    /// The next mode, for cycling through them in the TUI
+    pub fn next(self) -> Self {
+        match self {
+            SocdMode::Blend => SocdMode::Neutral,
+            SocdMode::Neutral => SocdMode::LastInput,
+            SocdMode::LastInput => SocdMode::PositivePriority,
+            SocdMode::PositivePriority => SocdMode::NegativePriority,
+            SocdMode::NegativePriority => SocdMode::Deeper,
+            SocdMode::Deeper => SocdMode::Blend,
+        }
+    }
+}
+
 /// How an axis is mapped from key(s) to joystick value
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -104,6 +182,9 @@ pub enum AxisMappingMode {
             deserialize_with = "deserialize_keyref"
         )]
         negative_key: KeyRef,
+        /// What to report while both keys are held
+        #[serde(default)]
+        socd: SocdMode,
     },
     /// Single key controls 0 to max (throttle-style)
     SingleKey {
@@ -115,6 +196,48 @@ pub enum AxisMappingMode {
         /// If true, inverts the axis (pressed = 0, released = max)
         invert: bool,
     },
+    /// Single key drives an analog trigger: released = 0, fully pressed = max
+    ///
+    /// Meant for `Z` (left trigger) and `RZ` (right trigger), which games
+    /// read as gamepad LT/RT for accelerate/brake or aim pressure.
+    Trigger {
+        #[serde(
+            serialize_with = "serialize_keyref",
+            deserialize_with = "deserialize_keyref"
+        )]
+        key: KeyRef,
+    },
+    /// Two keys steer like a keyboard racing wheel: right positive, left
+    /// negative, at full axis resolution. The response curve sets the
+    /// linearity. With a `speed_key` (usually the throttle) the steering range
+    /// narrows as that key goes down, to `full_speed_range_percent` at a full
+    /// press, standing in for speed-sensitive steering.
+    Steering {
+        #[serde(
+            serialize_with = "serialize_keyref",
+            deserialize_with = "deserialize_keyref"
+        )]
+        left_key: KeyRef,
+        #[serde(
+            serialize_with = "serialize_keyref",
+            deserialize_with = "deserialize_keyref"
+        )]
+        right_key: KeyRef,
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            serialize_with = "serialize_opt_keyref",
+            deserialize_with = "deserialize_opt_keyref"
+        )]
+        speed_key: Option<KeyRef>,
+        /// Steering range left with the speed key fully pressed (0-100)
+        #[serde(default = "default_full_speed_range")]
+        full_speed_range_percent: f32,
+    },
+}
+
+fn default_full_speed_range() -> f32 {
+    50.0
 }
 
 impl AxisMappingMode {
@@ -124,8 +247,130 @@ impl AxisMappingMode {
             AxisMappingMode::TwoKey {
                 positive_key,
                 negative_key,
+                ..
             } => vec![positive_key.index, negative_key.index],
-            AxisMappingMode::SingleKey { key, .. } => vec![key.index],
+            AxisMappingMode::SingleKey { key, .. } | AxisMappingMode::Trigger { key } => {
+                vec![key.index]
+            }
+            AxisMappingMode::Steering { .. } => self.keys().iter().map(|k| k.index).collect(),
+        }
+    }
+
+    /// Value range the axis is registered with on the virtual device
+    pub fn range(&self) -> AxisRange {
+        match self {
+            AxisMappingMode::Trigger { .. } => AxisRange::Trigger,
+            AxisMappingMode::TwoKey { .. }
+            | AxisMappingMode::SingleKey { .. }
+            | AxisMappingMode::Steering { .. } => AxisRange::Bipolar,
+        }
+    }
+
+    fn keys(&self) -> Vec<&KeyRef> {
+        match self {
+            AxisMappingMode::TwoKey {
+                positive_key,
+                negative_key,
+                ..
+            } => vec![positive_key, negative_key],
+            AxisMappingMode::SingleKey { key, .. } | AxisMappingMode::Trigger { key } => vec![key],
+            AxisMappingMode::Steering {
+                left_key,
+                right_key,
+                speed_key,
+                ..
+            } => [Some(left_key), Some(right_key), speed_key.as_ref()]
+                .into_iter()
+                .flatten()
+                .collect(),
+        }
+    }
+
+    fn keys_mut(&mut self) -> Vec<&mut KeyRef> {
+        match self {
+            AxisMappingMode::TwoKey {
+                positive_key,
+                negative_key,
+                ..
+            } => vec![positive_key, negative_key],
+            AxisMappingMode::SingleKey { key, .. } | AxisMappingMode::Trigger { key } => vec![key],
+            AxisMappingMode::Steering {
+                left_key,
+                right_key,
+                speed_key,
+                ..
+            } => [Some(left_key), Some(right_key), speed_key.as_mut()]
+                .into_iter()
+                .flatten()
+                .collect(),
+        }
+    }
+}
+
+/// Shape of an axis response: how key travel (after the deadzone) maps to
+/// deflection, both 0.0-1.0
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ResponseCurve {
+    /// Deflection follows travel
+    Linear,
+    /// travel ^ `curve_exponent` (>1 = finer control near rest)
+    #[default]
+    Exponential,
+    /// Straight lines through `[travel, deflection]` control points; (0, 0)
+    /// and (1, 1) are implied, so a full press always reaches max deflection
+    Custom { points: Vec<[f32; 2]> },
+}
+
+impl ResponseCurve {
+    /// Get display name for the curve type
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ResponseCurve::Linear => "Linear",
+            ResponseCurve::Exponential => "Exponential",
+            ResponseCurve::Custom { .. } => "Custom",
+        }
+    }
+
+    /// The next curve type, for cycling through them in the TUI
+    ///
+    /// A new custom curve starts out precise near rest, like exponent 2.
+    pub fn next(&self) -> Self {
+        match self {
+            ResponseCurve::Linear => ResponseCurve::Exponential,
+            ResponseCurve::Exponential => ResponseCurve::Custom {
+                points: vec![[0.25, 0.1], [0.5, 0.25], [0.75, 0.55]],
+            },
+            ResponseCurve::Custom { .. } => ResponseCurve::Linear,
+        }
+    }
+
+    /// Deflection for `travel` (both 0.0-1.0)
+    pub fn apply(&self, travel: f32, exponent: f32) -> f32 {
+        let travel = travel.clamp(0.0, 1.0);
+        match self {
+            ResponseCurve::Linear => travel,
+            ResponseCurve::Exponential => travel.powf(exponent),
+            ResponseCurve::Custom { points } => {
+                let mut knots: Vec<[f32; 2]> = points
+                    .iter()
+                    .map(|&[x, y]| [x.clamp(0.0, 1.0), y.clamp(0.0, 1.0)])
+                    .collect();
+                knots.sort_by(|a, b| a[0].total_cmp(&b[0]));
+                knots.insert(0, [0.0, 0.0]);
+                knots.push([1.0, 1.0]);
+
+                let i = knots
+                    .windows(2)
+                    .position(|w| travel <= w[1][0])
+                    .unwrap_or(knots.len() - 2);
+                let ([x0, y0], [x1, y1]) = (knots[i], knots[i + 1]);
+                if x1 <= x0 {
+                    return y1;
+                }
+                let t = (travel - x0) / (x1 - x0);
+                y0 * (1.0 - t) + y1 * t
+            }
         }
     }
 }
@@ -145,6 +390,9 @@ pub struct AxisCalibration {
     /// Response curve exponent (1.0 = linear, >1 = less sensitive in center)
     #[serde(default = "default_curve")]
     pub curve_exponent: f32,
+    /// Response curve type (`curve_exponent` applies to `Exponential`)
+    #[serde(default)]
+    pub curve: ResponseCurve,
 }
 
 fn default_max_travel() -> f32 {
@@ -164,10 +412,18 @@ impl Default for AxisCalibration {
             max_travel_mm: default_max_travel(),
             deadzone_percent: default_deadzone(),
             curve_exponent: default_curve(),
+            curve: ResponseCurve::default(),
         }
     }
 }
 
+impl AxisCalibration {
+    /// Deflection (0.0-1.0) for key travel past the deadzone (0.0-1.0)
+    pub fn response(&self, travel: f32) -> f32 {
+        self.curve.apply(travel, self.curve_exponent)
+    }
+}
+
 /// Complete configuration for a single axis
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AxisConfig {
@@ -187,15 +443,93 @@ fn default_true() -> bool {
     true
 }
 
+impl AxisConfig {
+    /// Two-key axis on base-layer matrix positions, default calibration
+    fn two_key(id: AxisId, positive: u8, negative: u8) -> Self {
+        Self {
+            id,
+            enabled: true,
+            mapping: AxisMappingMode::TwoKey {
+                positive_key: KeyRef::new(positive, Layer::Base),
+                negative_key: KeyRef::new(negative, Layer::Base),
+                socd: SocdMode::default(),
+            },
+            calibration: AxisCalibration::default(),
+        }
+    }
+}
+
+/// Name of the top-level axes when switching profiles
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// A named set of axes to use instead of the top-level ones, e.g. per game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JoystickProfile {
+    pub name: String,
+    /// Switch to this profile when one of these applications gets focus:
+    /// case-insensitive substrings of the app id (Wayland `app_id`, X11
+    /// `WM_CLASS`)
+    #[serde(default)]
+    pub apps: Vec<String>,
+    /// Also require the window title to contain this (case-insensitive)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub axes: Vec<AxisConfig>,
+}
+
+impl JoystickProfile {
+    /// Whether the profile's rule covers a focused window
+    pub fn matches_window(&self, app_id: &str, title: &str) -> bool {
+        let app_id = app_id.to_lowercase();
+        let title_matches = self
+            .title
+            .as_ref()
+            .is_none_or(|t| title.to_lowercase().contains(&t.to_lowercase()));
+        !app_id.is_empty()
+            && title_matches
+            && self
+                .apps
+                .iter()
+                .any(|pattern| app_id.contains(&pattern.to_lowercase()))
+    }
+}
+
+/// What the virtual device presents itself as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputMode {
+    /// A plain joystick with only the enabled axes
+    #[default]
+    Generic,
+    /// A wired Xbox 360 controller (IDs, buttons and axis ranges), for games
+    /// that ignore other controllers
+    Xbox360,
+}
+
 /// Complete joystick mapper configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JoystickConfig {
     /// Name for the virtual joystick device
     #[serde(default = "default_device_name")]
     pub device_name: String,
-    /// Axis configurations
+    /// What the virtual device presents itself as
+    #[serde(default)]
+    pub output: OutputMode,
+    /// Keep keys bound to enabled axes from typing while `iot_driver
+    /// joystick` runs (it grabs the keyboard and replays the other keys)
+    #[serde(default)]
+    pub suppress_keys: bool,
+    /// Axis configurations (of the active profile while one is switched to)
     #[serde(default)]
     pub axes: Vec<AxisConfig>,
+    /// Named alternatives to the top-level axes; the first whose rule matches
+    /// the focused window is switched to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<JoystickProfile>,
+    /// Index of the profile whose axes are in `axes`; that profile holds the
+    /// top-level axes meanwhile
+    #[serde(skip)]
+    active_profile: Option<usize>,
 }
 
 fn default_device_name() -> String {
@@ -206,6 +540,8 @@ impl Default for JoystickConfig {
     fn default() -> Self {
         Self {
             device_name: default_device_name(),
+            output: OutputMode::default(),
+            suppress_keys: false,
             axes: vec![
                 // Default WASD mapping for X/Y axes
                 AxisConfig {
@@ -214,6 +550,7 @@ impl Default for JoystickConfig {
                     mapping: AxisMappingMode::TwoKey {
                         positive_key: KeyRef::new(21, Layer::Base),
                         negative_key: KeyRef::new(9, Layer::Base),
+                        socd: SocdMode::default(),
                     },
                     calibration: AxisCalibration::default(),
                 },
@@ -223,15 +560,91 @@ impl Default for JoystickConfig {
                     mapping: AxisMappingMode::TwoKey {
                         positive_key: KeyRef::new(14, Layer::Base),
                         negative_key: KeyRef::new(15, Layer::Base),
+                        socd: SocdMode::default(),
                     },
                     calibration: AxisCalibration::default(),
                 },
             ],
+            profiles: Vec::new(),
+            active_profile: None,
         }
     }
 }
 
+/// Names accepted by [`JoystickConfig::preset`]
+pub const PRESETS: &[&str] = &["flight-sim", "racing"];
+
 impl JoystickConfig {
+    /// A ready-made config to start from (see [`PRESETS`]), or `None` for
+    /// an unknown name
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "flight-sim" => Some(Self::flight_sim()),
+            "racing" => Some(Self::racing()),
+            _ => None,
+        }
+    }
+
+    /// WASD stick, Q/E rudder and R/F throttle; prop (Wheel, T/G) and
+    /// mixture (Gas, Y/H) are there to enable. Forward (W) is stick forward,
+    /// which is -Y.
+    fn flight_sim() -> Self {
+        let mut prop = AxisConfig::two_key(AxisId::Wheel, 32, 33);
+        prop.enabled = false;
+        let mut mixture = AxisConfig::two_key(AxisId::Gas, 38, 39);
+        mixture.enabled = false;
+        Self {
+            device_name: "MonsGeek Flight Controls".to_string(),
+            axes: vec![
+                AxisConfig::two_key(AxisId::X, 21, 9),         // D / A
+                AxisConfig::two_key(AxisId::Y, 15, 14),        // S / W
+                AxisConfig::two_key(AxisId::Rudder, 20, 8),    // E / Q
+                AxisConfig::two_key(AxisId::Throttle, 26, 27), // R / F
+                prop,
+                mixture,
+            ],
+            ..Self::default()
+        }
+    }
+
+    /// A/D steering that narrows as W goes down, W/S throttle and brake on
+    /// the trigger axes (RZ/Z, as on a gamepad) and Q as clutch on Y
+    fn racing() -> Self {
+        let pedal = |id, key| AxisConfig {
+            id,
+            enabled: true,
+            mapping: AxisMappingMode::Trigger {
+                key: KeyRef::new(key, Layer::Base),
+            },
+            calibration: AxisCalibration::default(),
+        };
+        let steering = AxisConfig {
+            id: AxisId::X,
+            enabled: true,
+            mapping: AxisMappingMode::Steering {
+                left_key: KeyRef::new(9, Layer::Base),         // A
+                right_key: KeyRef::new(21, Layer::Base),       // D
+                speed_key: Some(KeyRef::new(14, Layer::Base)), // W
+                full_speed_range_percent: default_full_speed_range(),
+            },
+            calibration: AxisCalibration {
+                deadzone_percent: 2.0,
+                curve_exponent: 1.5,
+                ..AxisCalibration::default()
+            },
+        };
+        Self {
+            device_name: "MonsGeek Racing Wheel".to_string(),
+            axes: vec![
+                steering,
+                pedal(AxisId::RZ, 14), // W: throttle
+                pedal(AxisId::Z, 15),  // S: brake
+                pedal(AxisId::Y, 8),   // Q: clutch
+            ],
+            ..Self::default()
+        }
+    }
+
     /// Get the default config file path
     pub fn default_path() -> PathBuf {
         dirs::config_dir()
@@ -257,11 +670,55 @@ impl JoystickConfig {
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
-        let content = toml::to_string_pretty(self)?;
+        // Write the axes back where they belong, whichever profile is active
+        let mut config = self.clone();
+        config.switch_profile(None).ok();
+        let content = toml::to_string_pretty(&config)?;
         std::fs::write(path, content)?;
         Ok(())
     }
 
+    /// Name of the active profile ([`DEFAULT_PROFILE`] for the top-level axes)
+    pub fn active_profile(&self) -> &str {
+        self.active_profile
+            .map_or(DEFAULT_PROFILE, |i| self.profiles[i].name.as_str())
+    }
+
+    /// The first profile whose rule matches a focused window
+    pub fn profile_for_window(&self, app_id: &str, title: &str) -> Option<&str> {
+        self.profiles
+            .iter()
+            .find(|p| p.matches_window(app_id, title))
+            .map(|p| p.name.as_str())
+    }
+
+    /// Make a profile's axes the active ones (`None` or [`DEFAULT_PROFILE`]
+    /// for the top-level axes). Returns whether the active profile changed.
+    pub fn switch_profile(&mut self, name: Option<&str>) -> Result<bool, String> {
+        let target = match name {
+            None => None,
+            Some(name) if name.eq_ignore_ascii_case(DEFAULT_PROFILE) => None,
+            Some(name) => Some(
+                self.profiles
+                    .iter()
+                    .position(|p| p.name.eq_ignore_ascii_case(name))
+                    .ok_or_else(|| format!("no joystick profile named \"{name}\""))?,
+            ),
+        };
+        if target == self.active_profile {
+            return Ok(false);
+        }
+        // Put the active profile's axes back, then bring in the new ones
+        if let Some(i) = self.active_profile {
+            std::mem::swap(&mut self.axes, &mut self.profiles[i].axes);
+        }
+        if let Some(i) = target {
+            std::mem::swap(&mut self.axes, &mut self.profiles[i].axes);
+        }
+        self.active_profile = target;
+        Ok(true)
+    }
+
     /// Get axis config by ID
     pub fn get_axis(&self, id: AxisId) -> Option<&AxisConfig> {
         self.axes.iter().find(|a| a.id == id)
@@ -272,6 +729,66 @@ impl JoystickConfig {
         self.axes.iter_mut().find(|a| a.id == id)
     }
 
+    /// Look every mapped key up again by name in a board's key name table
+    /// (`matrix::key_names_for`).
+    ///
+    /// The config is read before a keyboard is connected, so names resolve
+    /// against the default M1 V5 layout; boards that place keys elsewhere
+    /// need this once their device ID is known. Names the board doesn't have
+    /// keep their index.
+    pub fn resolve_keys(&mut self, names: &'static [&'static str]) {
+        let profile_axes = self.profiles.iter_mut().flat_map(|p| p.axes.iter_mut());
+        for axis in self.axes.iter_mut().chain(profile_axes) {
+            for key in axis.mapping.keys_mut() {
+                let found = names
+                    .iter()
+                    .position(|&n| n != "?" && n.eq_ignore_ascii_case(key.position));
+                if let Some(index) = found {
+                    key.index = index as u8;
+                    key.position = names[index];
+                }
+            }
+        }
+    }
+
+    /// Names of the keys enabled axes are bound to, in any profile (what
+    /// `suppress_keys` holds back)
+    pub fn bound_key_names(&self) -> Vec<&'static str> {
+        let profile_axes = self.profiles.iter().flat_map(|p| p.axes.iter());
+        let mut names: Vec<&'static str> = self
+            .axes
+            .iter()
+            .chain(profile_axes)
+            .filter(|a| a.enabled)
+            .flat_map(|a| a.mapping.keys())
+            .map(|key| key.position)
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Mapped keys a board with this key name table has no key at (the
+    /// default WASD on a numpad, say).
+    pub fn keys_missing_from(&self, names: &[&str]) -> Vec<&'static str> {
+        self.axes
+            .iter()
+            .filter(|a| a.enabled)
+            .flat_map(|a| a.mapping.keys())
+            .filter(|key| matches!(names.get(key.index as usize), None | Some(&"?")))
+            .map(|key| key.position)
+            .collect()
+    }
+
+    /// Enabled axes and their ranges, for creating the virtual device
+    pub fn enabled_axes(&self) -> Vec<(AxisId, AxisRange)> {
+        self.axes
+            .iter()
+            .filter(|a| a.enabled)
+            .map(|a| (a.id, a.mapping.range()))
+            .collect()
+    }
+
     /// Get all key indices that are currently mapped
     pub fn mapped_key_indices(&self) -> Vec<u8> {
         self.axes
@@ -308,6 +825,7 @@ mod tests {
         if let AxisMappingMode::TwoKey {
             positive_key,
             negative_key,
+            ..
         } = &parsed.axes[0].mapping
         {
             assert_eq!(positive_key.index, 21); // D
@@ -349,6 +867,7 @@ curve_exponent = 1.0
         if let AxisMappingMode::TwoKey {
             positive_key,
             negative_key,
+            ..
         } = &config.axes[0].mapping
         {
             assert_eq!(positive_key.index, 21);
@@ -385,6 +904,7 @@ curve_exponent = 1.0
         if let AxisMappingMode::TwoKey {
             positive_key,
             negative_key,
+            ..
         } = &config.axes[0].mapping
         {
             assert_eq!(positive_key.index, 21);
@@ -396,6 +916,317 @@ curve_exponent = 1.0
         }
     }
 
+    #[test]
+    fn test_resolve_keys_for_another_layout() {
+        let toml_str = r#"
+[[axes]]
+id = "Z"
+
+[axes.mapping]
+type = "SingleKey"
+key = "Del"
+invert = false
+"#;
+        let mut config: JoystickConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.mapped_key_indices(), vec![78]); // M1 V5 position
+
+        config.resolve_keys(matrix::key_names_for(matrix::M3_V5_HE));
+        assert_eq!(config.mapped_key_indices(), vec![86]);
+
+        // WASD sits in the same place on both boards
+        let mut config = JoystickConfig::default();
+        config.resolve_keys(matrix::key_names_for(matrix::M3_V5_HE));
+        assert_eq!(config.mapped_key_indices(), vec![21, 9, 14, 15]);
+    }
+
+    #[test]
+    fn test_keys_missing_from_a_small_board() {
+        let config = JoystickConfig::default();
+        assert!(config
+            .keys_missing_from(matrix::key_names_for(matrix::M3_V5_HE))
+            .is_empty());
+
+        // A numpad-sized table: only the first column has keys
+        let names = ["Esc", "KP-", "KP6", "?", "?", "?"];
+        let mut missing = config.keys_missing_from(&names);
+        missing.sort_unstable();
+        assert_eq!(missing, vec!["A", "D", "S", "W"]);
+    }
+
+    #[test]
+    fn test_socd_mode() {
+        let toml_str = r#"
+[[axes]]
+id = "X"
+
+[axes.mapping]
+type = "TwoKey"
+positive_key = "D"
+negative_key = "A"
+socd = "LastInput"
+
+[[axes]]
+id = "Y"
+
+[axes.mapping]
+type = "TwoKey"
+positive_key = "W"
+negative_key = "S"
+"#;
+        let config: JoystickConfig = toml::from_str(toml_str).unwrap();
+        let socd: Vec<_> = config
+            .axes
+            .iter()
+            .map(|a| match a.mapping {
+                AxisMappingMode::TwoKey { socd, .. } => socd,
+                _ => panic!("Expected TwoKey mapping"),
+            })
+            .collect();
+        // Configs from before SOCD modes keep the old blending
+        assert_eq!(socd, vec![SocdMode::LastInput, SocdMode::Blend]);
+
+        let resaved = toml::to_string_pretty(&config).unwrap();
+        assert!(resaved.contains("socd = \"LastInput\""));
+    }
+
+    #[test]
+    fn test_response_curves() {
+        let linear = ResponseCurve::Linear;
+        assert_eq!(linear.apply(0.3, 2.0), 0.3);
+        assert_eq!(ResponseCurve::Exponential.apply(0.5, 2.0), 0.25);
+
+        // Control points out of order; the ends are implied
+        let custom = ResponseCurve::Custom {
+            points: vec![[0.8, 0.5], [0.4, 0.1]],
+        };
+        assert_eq!(custom.apply(0.0, 1.0), 0.0);
+        assert!((custom.apply(0.2, 1.0) - 0.05).abs() < 1e-6);
+        assert!((custom.apply(0.6, 1.0) - 0.3).abs() < 1e-6);
+        assert_eq!(custom.apply(1.0, 1.0), 1.0);
+        assert_eq!(custom.apply(1.5, 1.0), 1.0);
+    }
+
+    #[test]
+    fn test_custom_curve_roundtrip() {
+        let toml_str = r#"
+[[axes]]
+id = "X"
+
+[axes.mapping]
+type = "SingleKey"
+key = "W"
+invert = false
+
+[axes.calibration.curve]
+type = "Custom"
+points = [[0.5, 0.2]]
+"#;
+        let config: JoystickConfig = toml::from_str(toml_str).unwrap();
+        let cal = &config.axes[0].calibration;
+        assert_eq!(
+            cal.curve,
+            ResponseCurve::Custom {
+                points: vec![[0.5, 0.2]]
+            }
+        );
+        // The rest of the calibration keeps its defaults
+        assert_eq!(cal.max_travel_mm, 4.0);
+
+        let resaved = toml::to_string_pretty(&config).unwrap();
+        let parsed: JoystickConfig = toml::from_str(&resaved).unwrap();
+        assert_eq!(parsed.axes[0].calibration.curve, cal.curve);
+
+        // Configs without a curve type keep the exponent curve
+        let config = JoystickConfig::default();
+        assert_eq!(config.axes[0].calibration.curve, ResponseCurve::Exponential);
+    }
+
+    #[test]
+    fn test_trigger_mapping() {
+        let toml_str = r#"
+[[axes]]
+id = "X"
+
+[axes.mapping]
+type = "TwoKey"
+positive_key = "D"
+negative_key = "A"
+
+[[axes]]
+id = "RZ"
+
+[axes.mapping]
+type = "Trigger"
+key = "W"
+"#;
+        let config: JoystickConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            config.enabled_axes(),
+            vec![
+                (AxisId::X, AxisRange::Bipolar),
+                (AxisId::RZ, AxisRange::Trigger)
+            ]
+        );
+        assert_eq!(config.mapped_key_indices(), vec![21, 9, 14]);
+
+        let resaved = toml::to_string_pretty(&config).unwrap();
+        assert!(resaved.contains("type = \"Trigger\""));
+        assert!(resaved.contains("key = \"W\""));
+    }
+
+    const PROFILES_TOML: &str = r#"
+[[axes]]
+id = "X"
+
+[axes.mapping]
+type = "TwoKey"
+positive_key = "D"
+negative_key = "A"
+
+[[profiles]]
+name = "racing"
+apps = ["assettocorsa", "BeamNG"]
+
+[[profiles.axes]]
+id = "RZ"
+
+[profiles.axes.mapping]
+type = "Trigger"
+key = "W"
+
+[[profiles]]
+name = "flight"
+apps = ["steam_app"]
+title = "Flight"
+"#;
+
+    #[test]
+    fn test_profile_switching() {
+        let mut config: JoystickConfig = toml::from_str(PROFILES_TOML).unwrap();
+        assert_eq!(config.active_profile(), DEFAULT_PROFILE);
+
+        assert_eq!(config.switch_profile(Some("Racing")), Ok(true));
+        assert_eq!(config.active_profile(), "racing");
+        assert_eq!(config.mapped_key_indices(), vec![14]);
+        assert_eq!(config.switch_profile(Some("racing")), Ok(false));
+
+        // Going from one profile to another restores the first one's axes
+        assert_eq!(config.switch_profile(Some("flight")), Ok(true));
+        assert!(config.axes.is_empty());
+        assert_eq!(config.switch_profile(Some("default")), Ok(true));
+        assert_eq!(config.mapped_key_indices(), vec![21, 9]);
+        assert_eq!(config.profiles[0].axes.len(), 1);
+
+        assert!(config.switch_profile(Some("nope")).is_err());
+        assert_eq!(config.active_profile(), DEFAULT_PROFILE);
+    }
+
+    #[test]
+    fn test_profile_for_window() {
+        let config: JoystickConfig = toml::from_str(PROFILES_TOML).unwrap();
+        assert_eq!(
+            config.profile_for_window("BeamNG.drive.x64", ""),
+            Some("racing")
+        );
+        assert_eq!(
+            config.profile_for_window("steam_app_1250410", "Microsoft Flight Simulator"),
+            Some("flight")
+        );
+        assert_eq!(
+            config.profile_for_window("steam_app_1250410", "Other"),
+            None
+        );
+        assert_eq!(config.profile_for_window("", "BeamNG"), None);
+    }
+
+    #[test]
+    fn test_save_while_switched() {
+        let mut config: JoystickConfig = toml::from_str(PROFILES_TOML).unwrap();
+        config.switch_profile(Some("racing")).unwrap();
+        config.axes[0].enabled = false; // edited while active
+
+        let path = std::env::temp_dir().join(format!(
+            "monsgeek-joystick-test-{}.toml",
+            std::process::id()
+        ));
+        config.save(&path).unwrap();
+        let saved = JoystickConfig::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(saved.active_profile(), DEFAULT_PROFILE);
+        assert_eq!(saved.axes[0].id, AxisId::X);
+        assert_eq!(saved.profiles[0].axes[0].id, AxisId::RZ);
+        assert!(!saved.profiles[0].axes[0].enabled);
+    }
+
+    #[test]
+    fn test_flight_sim_preset() {
+        let config = JoystickConfig::preset("flight-sim").unwrap();
+        let ids: Vec<AxisId> = config.enabled_axes().iter().map(|&(id, _)| id).collect();
+        assert_eq!(
+            ids,
+            vec![AxisId::X, AxisId::Y, AxisId::Rudder, AxisId::Throttle]
+        );
+        assert_eq!(config.axes.len(), 6);
+
+        // Keys are saved by name
+        let saved = toml::to_string_pretty(&config).unwrap();
+        assert!(saved.contains("id = \"Rudder\""));
+        assert!(saved.contains("positive_key = \"E\""));
+        assert!(saved.contains("negative_key = \"Q\""));
+
+        assert!(JoystickConfig::preset("space-sim").is_none());
+        for name in PRESETS {
+            assert!(JoystickConfig::preset(name).is_some());
+        }
+    }
+
+    #[test]
+    fn test_steering_round_trip() {
+        let config = JoystickConfig::preset("racing").unwrap();
+        let saved = toml::to_string_pretty(&config).unwrap();
+        assert!(saved.contains("type = \"Steering\""));
+        assert!(saved.contains("speed_key = \"W\""));
+
+        let loaded: JoystickConfig = toml::from_str(&saved).unwrap();
+        assert_eq!(loaded.axes[0].mapping.key_indices(), vec![9, 21, 14]);
+        assert_eq!(loaded.axes[0].mapping.range(), AxisRange::Bipolar);
+
+        // Without a speed key, only the two steering keys
+        let plain: AxisMappingMode = toml::from_str(
+            r#"
+type = "Steering"
+left_key = "A"
+right_key = "D"
+"#,
+        )
+        .unwrap();
+        assert_eq!(plain.key_indices(), vec![9, 21]);
+        assert!(!toml::to_string(&plain).unwrap().contains("speed_key"));
+    }
+
+    #[test]
+    fn test_bound_key_names() {
+        let mut config = JoystickConfig::preset("racing").unwrap();
+        assert_eq!(config.bound_key_names(), vec!["A", "D", "Q", "S", "W"]);
+        config.axes[3].enabled = false; // clutch
+        assert_eq!(config.bound_key_names(), vec!["A", "D", "S", "W"]);
+
+        // Profiles count whichever is active
+        let mut config: JoystickConfig = toml::from_str(PROFILES_TOML).unwrap();
+        assert_eq!(config.bound_key_names(), vec!["A", "D", "W"]);
+        config.switch_profile(Some("racing")).unwrap();
+        assert_eq!(config.bound_key_names(), vec!["A", "D", "W"]);
+    }
+
+    #[test]
+    fn test_output_mode() {
+        let config: JoystickConfig = toml::from_str("output = \"Xbox360\"").unwrap();
+        assert_eq!(config.output, OutputMode::Xbox360);
+        let config: JoystickConfig = toml::from_str("").unwrap();
+        assert_eq!(config.output, OutputMode::Generic);
+    }
+
     #[test]
     fn test_old_format_resaves_as_new() {
         let old_toml = r#"
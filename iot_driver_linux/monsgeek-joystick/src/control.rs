@@ -0,0 +1,277 @@
+//! Control socket for switching joystick profiles
+//!
+//! A running mapper listens on a Unix socket for one-line commands:
+//! `profile <name>` switches to a profile and holds it there, `auto` goes
+//! back to following the focused window, and `focus <app id>\t<title>`
+//! reports a focus change. Every line is answered with `ok` or
+//! `error: <reason>`. `iot_driver joystick` forwards focus changes here, and
+//! `--profile` sends a switch to the running mapper.
+
+use crate::config::JoystickConfig;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+
+/// How long a client waits for the mapper to answer
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// One control socket command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlCommand {
+    /// Switch to a profile and stay there until `Auto`
+    Profile(String),
+    /// Switch by focused window again
+    Auto,
+    /// The focused window changed (empty app id: no window)
+    Focus { app_id: String, title: String },
+}
+
+impl ControlCommand {
+    /// Parse one command line
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (verb, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match verb {
+            "profile" if !rest.trim().is_empty() => Ok(Self::Profile(rest.trim().to_string())),
+            "auto" => Ok(Self::Auto),
+            "focus" => {
+                let (app_id, title) = rest.split_once('\t').unwrap_or((rest, ""));
+                Ok(Self::Focus {
+                    app_id: app_id.to_string(),
+                    title: title.to_string(),
+                })
+            }
+            _ => Err(format!("unknown command \"{line}\"")),
+        }
+    }
+
+    /// The command as a socket line (without the newline)
+    pub fn to_line(&self) -> String {
+        // Tabs and newlines would end the field or the command early
+        let clean = |s: &str| s.replace(['\t', '\n', '\r'], " ");
+        match self {
+            Self::Profile(name) => format!("profile {}", clean(name)),
+            Self::Auto => "auto".to_string(),
+            Self::Focus { app_id, title } => {
+                format!("focus {}\t{}", clean(app_id), clean(title))
+            }
+        }
+    }
+}
+
+/// A command and where its answer goes
+pub type ControlRequest = (ControlCommand, oneshot::Sender<Result<(), String>>);
+
+/// `$XDG_RUNTIME_DIR/monsgeek/joystick.sock`, or the config directory
+/// without a runtime directory
+pub fn socket_path() -> PathBuf {
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime) => PathBuf::from(runtime).join("monsgeek"),
+        None => JoystickConfig::default_path()
+            .parent()
+            .map(PathBuf::from)
+            .unwrap_or_default(),
+    };
+    dir.join("joystick.sock")
+}
+
+/// Send one command to the running mapper.
+pub fn send(command: &ControlCommand) -> Result<(), String> {
+    let path = socket_path();
+    let mut stream = std::os::unix::net::UnixStream::connect(&path)
+        .map_err(|e| format!("joystick mapper not running ({}: {e})", path.display()))?;
+    let io_err = |e: io::Error| e.to_string();
+    stream
+        .set_read_timeout(Some(CLIENT_TIMEOUT))
+        .map_err(io_err)?;
+    writeln!(stream, "{}", command.to_line()).map_err(io_err)?;
+
+    let mut answer = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut answer)
+        .map_err(io_err)?;
+    match answer.trim_end() {
+        "ok" => Ok(()),
+        answer => Err(answer.strip_prefix("error: ").unwrap_or(answer).to_string()),
+    }
+}
+
+/// Listen on the control socket, passing each command to `tx`.
+///
+/// Fails if another mapper is already listening.
+pub fn listen(tx: mpsc::Sender<ControlRequest>) -> io::Result<PathBuf> {
+    let path = socket_path();
+    if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("another joystick mapper is listening on {}", path.display()),
+        ));
+    }
+    // Left behind by a mapper that didn't exit cleanly
+    let _ = std::fs::remove_file(&path);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let listener = tokio::net::UnixListener::bind(&path)?;
+
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    debug!("Control socket accept failed: {}", e);
+                    continue;
+                }
+            };
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let (read, mut write) = stream.into_split();
+                let mut lines = tokio::io::BufReader::new(read).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let result = match ControlCommand::parse(&line) {
+                        Ok(command) => {
+                            let (reply, answer) = oneshot::channel();
+                            if tx.send((command, reply)).await.is_err() {
+                                return;
+                            }
+                            answer
+                                .await
+                                .unwrap_or_else(|_| Err("mapper stopped".into()))
+                        }
+                        Err(e) => Err(e),
+                    };
+                    let answer = match result {
+                        Ok(()) => "ok\n".to_string(),
+                        Err(e) => format!("error: {e}\n"),
+                    };
+                    if write.write_all(answer.as_bytes()).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    Ok(path)
+}
+
+/// Picks the active profile from focus changes and explicit switches
+#[derive(Debug, Default)]
+pub struct ProfileSwitcher {
+    /// An explicit switch is holding the profile
+    held: bool,
+    /// Last focused window (app id, title)
+    focused: Option<(String, String)>,
+}
+
+impl ProfileSwitcher {
+    /// Apply a command to `config`. Returns whether the active profile changed.
+    pub fn handle(
+        &mut self,
+        config: &mut JoystickConfig,
+        command: &ControlCommand,
+    ) -> Result<bool, String> {
+        match command {
+            ControlCommand::Profile(name) => {
+                let changed = config.switch_profile(Some(name))?;
+                self.held = true;
+                Ok(changed)
+            }
+            ControlCommand::Auto => {
+                self.held = false;
+                self.follow_focus(config)
+            }
+            ControlCommand::Focus { app_id, title } => {
+                self.focused = Some((app_id.clone(), title.clone()));
+                if self.held {
+                    return Ok(false);
+                }
+                self.follow_focus(config)
+            }
+        }
+    }
+
+    /// Switch to the profile for the last focused window
+    fn follow_focus(&self, config: &mut JoystickConfig) -> Result<bool, String> {
+        let name = self
+            .focused
+            .as_ref()
+            .and_then(|(app_id, title)| config.profile_for_window(app_id, title))
+            .map(str::to_string);
+        config.switch_profile(name.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_PROFILE;
+
+    #[test]
+    fn commands_round_trip_through_lines() {
+        for command in [
+            ControlCommand::Profile("racing".into()),
+            ControlCommand::Auto,
+            ControlCommand::Focus {
+                app_id: "BeamNG.drive.x64".into(),
+                title: "BeamNG.drive - 0.32".into(),
+            },
+            ControlCommand::Focus {
+                app_id: String::new(),
+                title: String::new(),
+            },
+        ] {
+            assert_eq!(ControlCommand::parse(&command.to_line()), Ok(command));
+        }
+        assert!(ControlCommand::parse("profile").is_err());
+        assert!(ControlCommand::parse("reboot").is_err());
+    }
+
+    #[test]
+    fn explicit_switches_hold_until_auto() {
+        let mut config: JoystickConfig = toml::from_str(
+            r#"
+[[profiles]]
+name = "racing"
+apps = ["beamng"]
+
+[[profiles]]
+name = "fps"
+"#,
+        )
+        .unwrap();
+        let mut switcher = ProfileSwitcher::default();
+        let focus = |app_id: &str| ControlCommand::Focus {
+            app_id: app_id.into(),
+            title: String::new(),
+        };
+
+        assert_eq!(
+            switcher.handle(&mut config, &focus("BeamNG.drive")),
+            Ok(true)
+        );
+        assert_eq!(config.active_profile(), "racing");
+        assert_eq!(switcher.handle(&mut config, &focus("firefox")), Ok(true));
+        assert_eq!(config.active_profile(), DEFAULT_PROFILE);
+
+        let fps = ControlCommand::Profile("fps".into());
+        assert_eq!(switcher.handle(&mut config, &fps), Ok(true));
+        assert_eq!(
+            switcher.handle(&mut config, &focus("BeamNG.drive")),
+            Ok(false)
+        );
+        assert_eq!(config.active_profile(), "fps");
+
+        // Back to the focused window's profile
+        assert_eq!(
+            switcher.handle(&mut config, &ControlCommand::Auto),
+            Ok(true)
+        );
+        assert_eq!(config.active_profile(), "racing");
+
+        let unknown = ControlCommand::Profile("nope".into());
+        assert!(switcher.handle(&mut config, &unknown).is_err());
+    }
+}
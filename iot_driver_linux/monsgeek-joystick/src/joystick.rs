@@ -1,12 +1,13 @@
 //! Virtual joystick device using evdev/uinput
 //!
 //! Creates a virtual gamepad device that appears as a standard joystick
-//! to games and applications.
+//! to games and applications, or as an Xbox 360 controller for games that
+//! only accept that (see [`OutputMode`]).
 
-use crate::config::AxisId;
+use crate::config::{AxisId, OutputMode};
 use evdev::{
     uinput::{VirtualDevice, VirtualDeviceBuilder},
-    AbsInfo, AbsoluteAxisType, AttributeSet, InputEvent, Key, UinputAbsSetup,
+    AbsInfo, AbsoluteAxisType, AttributeSet, BusType, InputEvent, InputId, Key, UinputAbsSetup,
 };
 use std::collections::HashMap;
 use thiserror::Error;
@@ -15,6 +16,54 @@ use thiserror::Error;
 pub const AXIS_MIN: i32 = -32767;
 pub const AXIS_MAX: i32 = 32767;
 
+/// Trigger axis maximum (the range the Xbox One controller driver reports for LT/RT)
+pub const TRIGGER_MAX: i32 = 1023;
+
+/// The Xbox 360 mode copies the IDs, name, buttons and ranges the kernel's
+/// xpad driver gives a wired Xbox 360 controller
+const XBOX360_VENDOR: u16 = 0x045e;
+const XBOX360_PRODUCT: u16 = 0x028e;
+const XBOX360_VERSION: u16 = 0x0110;
+const XBOX360_NAME: &str = "Microsoft X-Box 360 pad";
+const XBOX360_BUTTONS: &[Key] = &[
+    Key::BTN_SOUTH,
+    Key::BTN_EAST,
+    Key::BTN_NORTH,
+    Key::BTN_WEST,
+    Key::BTN_TL,
+    Key::BTN_TR,
+    Key::BTN_SELECT,
+    Key::BTN_START,
+    Key::BTN_MODE,
+    Key::BTN_THUMBL,
+    Key::BTN_THUMBR,
+];
+
+/// Value range of an axis on the virtual device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AxisRange {
+    /// Stick axis, centered at 0: [AXIS_MIN, AXIS_MAX]
+    Bipolar,
+    /// Analog trigger, 0 at rest: [0, TRIGGER_MAX]
+    Trigger,
+}
+
+impl AxisRange {
+    pub fn min(self) -> i32 {
+        match self {
+            AxisRange::Bipolar => AXIS_MIN,
+            AxisRange::Trigger => 0,
+        }
+    }
+
+    pub fn max(self) -> i32 {
+        match self {
+            AxisRange::Bipolar => AXIS_MAX,
+            AxisRange::Trigger => TRIGGER_MAX,
+        }
+    }
+}
+
 /// Errors from virtual joystick operations
 #[derive(Debug, Error)]
 pub enum JoystickError {
@@ -31,60 +80,147 @@ pub struct VirtualJoystick {
     device: VirtualDevice,
     /// Current axis values (for change detection)
     axis_values: HashMap<AxisId, i32>,
+    /// How mapper values of each axis reach the device
+    outputs: HashMap<AxisId, AxisOutput>,
+}
+
+/// Mapper range of an axis and the range it is registered with
+#[derive(Debug, Clone, Copy)]
+struct AxisOutput {
+    source: AxisRange,
+    min: i32,
+    max: i32,
+}
+
+impl AxisOutput {
+    /// Device value for a mapper value; rescaled only when the mapper range
+    /// doesn't fit the device's (a trigger on the Xbox 360's 0-255, say)
+    fn to_device(self, value: i32) -> i32 {
+        let (src_min, src_max) = (self.source.min(), self.source.max());
+        let value = value.clamp(src_min, src_max);
+        if src_min >= self.min && src_max <= self.max {
+            return value;
+        }
+        let span = (src_max - src_min) as i64;
+        let scaled = ((value - src_min) as i64 * (self.max - self.min) as i64 + span / 2) / span;
+        self.min + scaled as i32
+    }
 }
 
 impl VirtualJoystick {
     /// Create a new virtual joystick device
     ///
     /// # Arguments
-    /// * `name` - Device name (shown in `evtest` and game controller settings)
-    /// * `axes` - Which axes to enable on the device
-    pub fn new(name: &str, axes: &[AxisId]) -> Result<Self, JoystickError> {
-        let mut builder = VirtualDeviceBuilder::new()
-            .map_err(JoystickError::CreateDevice)?
-            .name(name);
-
-        // Add gamepad buttons for Steam Input compatibility
+    /// * `name` - Device name (shown in `evtest` and game controller settings;
+    ///   the Xbox 360 mode uses the real controller's name)
+    /// * `axes` - Which axes to drive, and their mapper ranges
+    /// * `output` - What the device presents itself as
+    pub fn new(
+        name: &str,
+        axes: &[(AxisId, AxisRange)],
+        output: OutputMode,
+    ) -> Result<Self, JoystickError> {
+        let mut builder = VirtualDeviceBuilder::new().map_err(JoystickError::CreateDevice)?;
         let mut keys = AttributeSet::<Key>::new();
-        keys.insert(Key::BTN_SOUTH);
-        keys.insert(Key::BTN_EAST);
-        keys.insert(Key::BTN_NORTH);
-        keys.insert(Key::BTN_WEST);
+        // Registered axes: (code, info), and the configured ones' outputs
+        let mut abs: Vec<(AbsoluteAxisType, AbsInfo)> = Vec::new();
+        let mut outputs = HashMap::new();
+
+        match output {
+            OutputMode::Generic => {
+                builder = builder.name(name);
+
+                // Add gamepad buttons for Steam Input compatibility
+                keys.insert(Key::BTN_SOUTH);
+                keys.insert(Key::BTN_EAST);
+                keys.insert(Key::BTN_NORTH);
+                keys.insert(Key::BTN_WEST);
+
+                // Add requested absolute axes
+                for &(axis_id, range) in axes {
+                    let info = AbsInfo::new(0, range.min(), range.max(), 0, 0, 1);
+                    abs.push((axis_id_to_code(axis_id), info));
+                }
+            }
+            OutputMode::Xbox360 => {
+                builder = builder.name(XBOX360_NAME).input_id(InputId::new(
+                    BusType::BUS_USB,
+                    XBOX360_VENDOR,
+                    XBOX360_PRODUCT,
+                    XBOX360_VERSION,
+                ));
+                for &key in XBOX360_BUTTONS {
+                    keys.insert(key);
+                }
+                // The full pad, whichever axes are configured
+                for &axis_id in AxisId::ALL {
+                    if let Some(info) = xbox360_abs_info(axis_id) {
+                        abs.push((axis_id_to_code(axis_id), info));
+                    }
+                }
+                let hat = AbsInfo::new(0, -1, 1, 0, 0, 0);
+                abs.push((AbsoluteAxisType::ABS_HAT0X, hat));
+                abs.push((AbsoluteAxisType::ABS_HAT0Y, hat));
+            }
+        }
+
+        // Axes the device doesn't have (flight-sim axes on the pad) are ignored
+        for &(axis_id, source) in axes {
+            let Some(&(_, info)) = abs
+                .iter()
+                .find(|(code, _)| *code == axis_id_to_code(axis_id))
+            else {
+                continue;
+            };
+            outputs.insert(
+                axis_id,
+                AxisOutput {
+                    source,
+                    min: info.minimum(),
+                    max: info.maximum(),
+                },
+            );
+        }
+
         builder = builder
             .with_keys(&keys)
             .map_err(JoystickError::CreateDevice)?;
-
-        // Add requested absolute axes
-        for &axis_id in axes {
-            let code = axis_id_to_code(axis_id);
-            let abs_setup = UinputAbsSetup::new(code, AbsInfo::new(0, AXIS_MIN, AXIS_MAX, 0, 0, 1));
+        for (code, info) in abs {
             builder = builder
-                .with_absolute_axis(&abs_setup)
+                .with_absolute_axis(&UinputAbsSetup::new(code, info))
                 .map_err(JoystickError::CreateDevice)?;
         }
 
         let device = builder.build().map_err(JoystickError::CreateDevice)?;
 
-        let mut axis_values = HashMap::new();
-        for &axis_id in axes {
-            axis_values.insert(axis_id, 0);
-        }
+        let axis_values = outputs.keys().map(|&axis_id| (axis_id, 0)).collect();
 
         Ok(Self {
             device,
             axis_values,
+            outputs,
         })
     }
 
+    /// Device value for a mapper value of `axis`, if the device has it
+    fn to_device(&self, axis: AxisId, value: i32) -> Option<i32> {
+        self.outputs
+            .get(&axis)
+            .map(|output| output.to_device(value))
+    }
+
     /// Set an axis value
     ///
-    /// Only emits events if the value has changed.
+    /// Only emits events if the value has changed; axes the device doesn't have
+    /// are ignored.
     ///
     /// # Arguments
     /// * `axis` - Which axis to update
-    /// * `value` - Value in range [-32767, 32767]
+    /// * `value` - Value in range [-32767, 32767] ([0, 1023] for triggers)
     pub fn set_axis(&mut self, axis: AxisId, value: i32) -> Result<(), JoystickError> {
-        let clamped = value.clamp(AXIS_MIN, AXIS_MAX);
+        let Some(clamped) = self.to_device(axis, value) else {
+            return Ok(());
+        };
 
         // Only emit if changed
         if self.axis_values.get(&axis) == Some(&clamped) {
@@ -108,7 +244,9 @@ impl VirtualJoystick {
         let mut events = Vec::new();
 
         for &(axis, value) in values {
-            let clamped = value.clamp(AXIS_MIN, AXIS_MAX);
+            let Some(clamped) = self.to_device(axis, value) else {
+                continue;
+            };
 
             // Only include if changed
             if self.axis_values.get(&axis) != Some(&clamped) {
@@ -140,12 +278,24 @@ impl VirtualJoystick {
             .ok()
     }
 
-    /// Get current axis value
+    /// Get current axis value (as sent to the device)
     pub fn get_axis(&self, axis: AxisId) -> i32 {
         self.axis_values.get(&axis).copied().unwrap_or(0)
     }
 }
 
+/// Axis range of a wired Xbox 360 controller: sticks on X/Y/RX/RY, the
+/// triggers on Z (LT) and RZ (RT)
+fn xbox360_abs_info(axis: AxisId) -> Option<AbsInfo> {
+    match axis {
+        AxisId::X | AxisId::Y | AxisId::RX | AxisId::RY => {
+            Some(AbsInfo::new(0, -32768, 32767, 16, 128, 0))
+        }
+        AxisId::Z | AxisId::RZ => Some(AbsInfo::new(0, 0, 255, 0, 0, 0)),
+        AxisId::Throttle | AxisId::Rudder | AxisId::Wheel | AxisId::Gas | AxisId::Brake => None,
+    }
+}
+
 /// Convert our AxisId to evdev AbsoluteAxisType
 fn axis_id_to_code(axis: AxisId) -> AbsoluteAxisType {
     match axis {
@@ -155,6 +305,11 @@ fn axis_id_to_code(axis: AxisId) -> AbsoluteAxisType {
         AxisId::RY => AbsoluteAxisType::ABS_RY,
         AxisId::Z => AbsoluteAxisType::ABS_Z,
         AxisId::RZ => AbsoluteAxisType::ABS_RZ,
+        AxisId::Throttle => AbsoluteAxisType::ABS_THROTTLE,
+        AxisId::Rudder => AbsoluteAxisType::ABS_RUDDER,
+        AxisId::Wheel => AbsoluteAxisType::ABS_WHEEL,
+        AxisId::Gas => AbsoluteAxisType::ABS_GAS,
+        AxisId::Brake => AbsoluteAxisType::ABS_BRAKE,
     }
 }
 
@@ -165,8 +320,44 @@ mod tests {
     #[test]
     #[ignore] // Requires uinput access (run with: cargo test -- --ignored)
     fn test_create_joystick() {
-        let axes = vec![AxisId::X, AxisId::Y];
-        let joystick = VirtualJoystick::new("Test Joystick", &axes);
+        let axes = vec![
+            (AxisId::X, AxisRange::Bipolar),
+            (AxisId::Y, AxisRange::Bipolar),
+            (AxisId::RZ, AxisRange::Trigger),
+        ];
+        let joystick = VirtualJoystick::new("Test Joystick", &axes, OutputMode::Generic);
         assert!(joystick.is_ok());
+        let pad = VirtualJoystick::new("Test Joystick", &axes, OutputMode::Xbox360);
+        assert!(pad.is_ok());
+    }
+
+    #[test]
+    fn test_values_rescale_only_when_they_dont_fit() {
+        let stick = AxisOutput {
+            source: AxisRange::Bipolar,
+            min: -32768,
+            max: 32767,
+        };
+        assert_eq!(stick.to_device(0), 0);
+        assert_eq!(stick.to_device(AXIS_MIN), AXIS_MIN);
+        assert_eq!(stick.to_device(40000), AXIS_MAX);
+
+        let trigger = AxisOutput {
+            source: AxisRange::Trigger,
+            min: 0,
+            max: 255,
+        };
+        assert_eq!(trigger.to_device(0), 0);
+        assert_eq!(trigger.to_device(TRIGGER_MAX / 2), 127);
+        assert_eq!(trigger.to_device(TRIGGER_MAX), 255);
+
+        // A stick mapping on a trigger axis covers the trigger's range
+        let stick_on_trigger = AxisOutput {
+            source: AxisRange::Bipolar,
+            min: 0,
+            max: 255,
+        };
+        assert_eq!(stick_on_trigger.to_device(AXIS_MIN), 0);
+        assert_eq!(stick_on_trigger.to_device(AXIS_MAX), 255);
     }
 }
@@ -4,10 +4,14 @@
 //! with a TUI for configuration and live visualization.
 
 pub mod config;
+pub mod control;
 pub mod joystick;
 pub mod mapper;
 pub mod tui;
 
-pub use config::{AxisCalibration, AxisConfig, AxisId, AxisMappingMode, JoystickConfig};
-pub use joystick::{JoystickError, VirtualJoystick, AXIS_MAX, AXIS_MIN};
+pub use config::{
+    AxisCalibration, AxisConfig, AxisId, AxisMappingMode, JoystickConfig, JoystickProfile,
+    OutputMode, ResponseCurve, SocdMode,
+};
+pub use joystick::{AxisRange, JoystickError, VirtualJoystick, AXIS_MAX, AXIS_MIN, TRIGGER_MAX};
 pub use mapper::AxisMapper;
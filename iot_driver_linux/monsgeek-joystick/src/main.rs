@@ -17,16 +17,18 @@ use std::collections::HashMap;
 use std::io::stdout;
 use std::path::PathBuf;
 use std::time::Duration;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, info, warn};
 
-use monsgeek_joystick::config::JoystickConfig;
+use monsgeek_joystick::config::{JoystickConfig, PRESETS};
+use monsgeek_joystick::control::{self, ControlCommand, ControlRequest, ProfileSwitcher};
 use monsgeek_joystick::joystick::VirtualJoystick;
 use monsgeek_joystick::mapper::AxisMapper;
-use monsgeek_joystick::tui::app::{App, AppMode, JoystickStatus, KeyboardStatus};
+use monsgeek_joystick::tui::app::{App, AppMode, JoystickStatus, KeyboardStatus, SelectedElement};
 use monsgeek_joystick::tui::render;
 
 use monsgeek_keyboard::KeyboardInterface;
+use monsgeek_transport::protocol::matrix;
 use monsgeek_transport::{
     list_devices_sync, open_device_sync, TimestampedEvent, Transport, VendorEvent,
 };
@@ -46,12 +48,40 @@ struct Cli {
     /// Log level (error, warn, info, debug, trace)
     #[arg(long, default_value = "info")]
     log_level: String,
+
+    /// Switch the running mapper to this profile and exit ('auto' follows
+    /// the focused window again, 'default' is the top-level axes)
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Start from a built-in config instead of the config file (flight-sim, racing);
+    /// saving from the TUI writes it to the config file
+    #[arg(long, value_name = "NAME")]
+    preset: Option<String>,
+}
+
+/// Pages for the whole factory key matrix (126 positions × 4 bytes).
+const KEYMATRIX_PAGES: usize = 8;
+
+/// `names` with the positions the board has no key at blanked out, going by
+/// its base-layer key matrix, so small boards (numpads) show only their keys.
+fn board_layout(names: &'static [&'static str], keymatrix: &[u8]) -> Vec<&'static str> {
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, &name)| match keymatrix.get(i * 4..i * 4 + 4) {
+            Some([0, 0, 0, 0]) => "?",
+            _ => name,
+        })
+        .collect()
 }
 
 /// Encapsulates a live keyboard connection with event subscription.
 struct KeyboardConnection {
     keyboard: KeyboardInterface,
     precision_factor: f64,
+    /// Firmware device ID, which selects the key matrix layout
+    device_id: Option<u32>,
     event_rx: broadcast::Receiver<TimestampedEvent>,
 }
 
@@ -95,6 +125,17 @@ async fn connect_keyboard() -> Option<KeyboardConnection> {
     // axis mapping (that comes from the config file). Use safe defaults.
     let keyboard = KeyboardInterface::new(transport, 0, false, protocol);
 
+    // Boards other than the M1 V5 HE place some keys elsewhere in the matrix
+    let device_id = keyboard.get_device_id().ok();
+    if let Some(id) = device_id {
+        info!("Device ID: {}", id);
+        match keyboard.get_keymatrix(0, KEYMATRIX_PAGES) {
+            Ok(data) => matrix::install_layout(id, board_layout(matrix::key_names_for(id), &data)),
+            Err(e) => warn!("Failed to read key matrix: {}", e),
+        }
+        matrix::select_layout(id);
+    }
+
     let precision_factor = match keyboard.get_precision() {
         Ok(precision) => {
             let factor = precision.factor();
@@ -124,6 +165,7 @@ async fn connect_keyboard() -> Option<KeyboardConnection> {
     Some(KeyboardConnection {
         keyboard,
         precision_factor,
+        device_id,
         event_rx,
     })
 }
@@ -132,6 +174,15 @@ async fn connect_keyboard() -> Option<KeyboardConnection> {
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    if let Some(name) = cli.profile {
+        let command = if name == "auto" {
+            ControlCommand::Auto
+        } else {
+            ControlCommand::Profile(name)
+        };
+        return control::send(&command).map_err(|e| anyhow::anyhow!(e));
+    }
+
     // Initialize logging
     let filter = tracing_subscriber::EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(&cli.log_level));
@@ -142,8 +193,22 @@ async fn main() -> Result<()> {
 
     // Load config
     let config_path = cli.config.unwrap_or_else(JoystickConfig::default_path);
-    info!("Loading config from {:?}", config_path);
-    let config = JoystickConfig::load(&config_path)?;
+    let config = match cli.preset.as_deref() {
+        Some(name) => {
+            info!("Using preset {}", name);
+            JoystickConfig::preset(name).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "unknown preset \"{}\" (available: {})",
+                    name,
+                    PRESETS.join(", ")
+                )
+            })?
+        }
+        None => {
+            info!("Loading config from {:?}", config_path);
+            JoystickConfig::load(&config_path)?
+        }
+    };
 
     if cli.headless {
         run_headless(config, config_path).await
@@ -189,19 +254,62 @@ fn apply_depths(pending_depths: &HashMap<u8, u16>, precision_factor: f64, mapper
     }
 }
 
+/// Start the control socket; profile switches arrive on the returned channel.
+fn start_control() -> mpsc::Receiver<ControlRequest> {
+    let (tx, rx) = mpsc::channel(8);
+    match control::listen(tx) {
+        Ok(path) => info!("Control socket: {}", path.display()),
+        Err(e) => warn!("Profile switching unavailable: {}", e),
+    }
+    rx
+}
+
+/// Answer a control request. Returns whether the active profile changed.
+fn handle_control(
+    request: ControlRequest,
+    switcher: &mut ProfileSwitcher,
+    config: &mut JoystickConfig,
+) -> bool {
+    let (command, reply) = request;
+    let result = switcher.handle(config, &command);
+    let changed = result == Ok(true);
+    if changed {
+        info!(
+            "Switched to joystick profile \"{}\"",
+            config.active_profile()
+        );
+    }
+    let _ = reply.send(result.map(|_| ()));
+    changed
+}
+
+/// Apply a control request in headless mode, recreating the virtual
+/// joystick when the new profile uses other axes.
+fn handle_control_headless(
+    request: ControlRequest,
+    switcher: &mut ProfileSwitcher,
+    config: &mut JoystickConfig,
+    joystick: &mut VirtualJoystick,
+) {
+    let axes = config.enabled_axes();
+    if handle_control(request, switcher, config) && config.enabled_axes() != axes {
+        match VirtualJoystick::new(&config.device_name, &config.enabled_axes(), config.output) {
+            Ok(js) => *joystick = js,
+            Err(e) => warn!("Failed to recreate joystick for the new profile: {}", e),
+        }
+    }
+}
+
 /// Run in headless mode (no TUI) with event-driven depth.
-async fn run_headless(config: JoystickConfig, _config_path: PathBuf) -> Result<()> {
+async fn run_headless(mut config: JoystickConfig, _config_path: PathBuf) -> Result<()> {
     info!("Running in headless mode");
+    let mut control_rx = start_control();
+    let mut switcher = ProfileSwitcher::default();
 
     // Create virtual joystick
-    let enabled_axes: Vec<_> = config
-        .axes
-        .iter()
-        .filter(|a| a.enabled)
-        .map(|a| a.id)
-        .collect();
+    let enabled_axes = config.enabled_axes();
 
-    let mut joystick = VirtualJoystick::new(&config.device_name, &enabled_axes)?;
+    let mut joystick = VirtualJoystick::new(&config.device_name, &enabled_axes, config.output)?;
     info!("Created virtual joystick: {}", config.device_name);
 
     if let Some(path) = joystick.device_path() {
@@ -218,15 +326,40 @@ async fn run_headless(config: JoystickConfig, _config_path: PathBuf) -> Result<(
             if let Some(conn) = connect_keyboard().await {
                 break conn;
             }
-            tokio::time::sleep(Duration::from_secs(2)).await;
+            // Keep answering profile switches while waiting
+            let retry = tokio::time::sleep(Duration::from_secs(2));
+            tokio::pin!(retry);
+            loop {
+                tokio::select! {
+                    _ = &mut retry => break,
+                    Some(request) = control_rx.recv() => {
+                        handle_control_headless(request, &mut switcher, &mut config, &mut joystick);
+                    }
+                }
+            }
         };
 
+        if let Some(id) = conn.device_id {
+            let names = matrix::key_names_for(id);
+            config.resolve_keys(names);
+            let missing = config.keys_missing_from(names);
+            if !missing.is_empty() {
+                warn!("Mapped keys not on this keyboard: {}", missing.join(", "));
+            }
+        }
         let mut event_rx = conn.event_rx;
         let precision_factor = conn.precision_factor;
 
         // Event loop: recv + coalescing drain
         loop {
-            match event_rx.recv().await {
+            let result = tokio::select! {
+                result = event_rx.recv() => result,
+                Some(request) = control_rx.recv() => {
+                    handle_control_headless(request, &mut switcher, &mut config, &mut joystick);
+                    continue;
+                }
+            };
+            match result {
                 Ok(ts) => {
                     let mut pending_depths: HashMap<u8, u16> = HashMap::new();
 
@@ -282,6 +415,8 @@ async fn run_tui(config: JoystickConfig, config_path: PathBuf) -> Result<()> {
 
     // Create app state
     let mut app = App::new(config, config_path);
+    let mut control_rx = start_control();
+    let mut switcher = ProfileSwitcher::default();
 
     // Create virtual joystick
     let mut joystick: Option<VirtualJoystick> = None;
@@ -405,10 +540,23 @@ async fn run_tui(config: JoystickConfig, config_path: PathBuf) -> Result<()> {
                     Ok(Some(conn)) => {
                         app.keyboard_status = KeyboardStatus::Connected;
                         app.precision_factor = conn.precision_factor;
-                        app.status_message = Some(format!(
-                            "Keyboard connected (precision factor: {:.0})",
-                            conn.precision_factor
-                        ));
+                        let mut missing = Vec::new();
+                        if let Some(id) = conn.device_id {
+                            let names = matrix::key_names_for(id);
+                            app.config.resolve_keys(names);
+                            missing = app.config.keys_missing_from(names);
+                        }
+                        app.status_message = Some(if missing.is_empty() {
+                            format!(
+                                "Keyboard connected (precision factor: {:.0})",
+                                conn.precision_factor
+                            )
+                        } else {
+                            format!(
+                                "Keyboard connected; mapped keys not on this keyboard: {}",
+                                missing.join(", ")
+                            )
+                        });
                         event_rx = Some(conn.event_rx);
                         // conn.keyboard is dropped — that's fine, the transport
                         // and its reader thread continue running independently
@@ -430,6 +578,29 @@ async fn run_tui(config: JoystickConfig, config_path: PathBuf) -> Result<()> {
                 }
             }
 
+            // Profile switches from the control socket
+            Some(request) = control_rx.recv() => {
+                let axes = app.config.enabled_axes();
+                if handle_control(request, &mut switcher, &mut app.config) {
+                    app.selected = SelectedElement::AxisList(0);
+                    app.status_message =
+                        Some(format!("Profile: {}", app.config.active_profile()));
+                    if app.config.enabled_axes() != axes {
+                        joystick = None;
+                        match create_joystick(&app.config) {
+                            Ok(js) => {
+                                app.joystick_status = JoystickStatus::Active;
+                                joystick = Some(js);
+                            }
+                            Err(e) => {
+                                app.joystick_status = JoystickStatus::Error;
+                                app.status_message = Some(format!("Joystick error: {}", e));
+                            }
+                        }
+                    }
+                }
+            }
+
             // Tick timeout for UI refresh
             _ = tokio::time::sleep(timeout) => {
                 // Periodic joystick update (for smooth display even without new events)
@@ -461,18 +632,13 @@ async fn run_tui(config: JoystickConfig, config_path: PathBuf) -> Result<()> {
 
 /// Create virtual joystick from config
 fn create_joystick(config: &JoystickConfig) -> Result<VirtualJoystick, anyhow::Error> {
-    let enabled_axes: Vec<_> = config
-        .axes
-        .iter()
-        .filter(|a| a.enabled)
-        .map(|a| a.id)
-        .collect();
+    let enabled_axes = config.enabled_axes();
 
     if enabled_axes.is_empty() {
         return Err(anyhow::anyhow!("No axes enabled"));
     }
 
-    let joystick = VirtualJoystick::new(&config.device_name, &enabled_axes)?;
+    let joystick = VirtualJoystick::new(&config.device_name, &enabled_axes, config.output)?;
     Ok(joystick)
 }
 
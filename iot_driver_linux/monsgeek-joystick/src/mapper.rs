@@ -2,8 +2,10 @@
 //!
 //! Converts raw key depth readings (in mm) to joystick axis values.
 
-use crate::config::{AxisCalibration, AxisConfig, AxisId, AxisMappingMode, JoystickConfig};
-use crate::joystick::{AXIS_MAX, AXIS_MIN};
+use crate::config::{
+    AxisCalibration, AxisConfig, AxisId, AxisMappingMode, JoystickConfig, SocdMode,
+};
+use crate::joystick::{AXIS_MAX, AXIS_MIN, TRIGGER_MAX};
 use std::collections::HashMap;
 
 /// Stores current key depth values and computes axis outputs
@@ -12,6 +14,39 @@ pub struct AxisMapper {
     key_depths: HashMap<u8, f32>,
     /// Computed axis values (cached for display)
     axis_values: HashMap<AxisId, i32>,
+    /// Which keys of each two-key axis are held, for SOCD cleaning
+    socd_states: HashMap<AxisId, SocdState>,
+}
+
+/// One key of a two-key axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Positive,
+    Negative,
+}
+
+/// Held keys of a two-key axis and which one went down last
+#[derive(Debug, Clone, Copy, Default)]
+struct SocdState {
+    positive_held: bool,
+    negative_held: bool,
+    last: Option<Side>,
+}
+
+impl SocdState {
+    fn update(&mut self, positive_held: bool, negative_held: bool) {
+        let positive_new = positive_held && !self.positive_held;
+        let negative_new = negative_held && !self.negative_held;
+        match (positive_new, negative_new) {
+            (true, false) => self.last = Some(Side::Positive),
+            (false, true) => self.last = Some(Side::Negative),
+            // Both went down in the same report: neither came last
+            (true, true) => self.last = None,
+            (false, false) => {}
+        }
+        self.positive_held = positive_held;
+        self.negative_held = negative_held;
+    }
 }
 
 impl AxisMapper {
@@ -20,6 +55,7 @@ impl AxisMapper {
         Self {
             key_depths: HashMap::new(),
             axis_values: HashMap::new(),
+            socd_states: HashMap::new(),
         }
     }
 
@@ -58,20 +94,49 @@ impl AxisMapper {
     }
 
     /// Compute a single axis value
-    fn compute_axis(&self, config: &AxisConfig) -> i32 {
+    fn compute_axis(&mut self, config: &AxisConfig) -> i32 {
         match &config.mapping {
             AxisMappingMode::TwoKey {
                 positive_key,
                 negative_key,
+                socd,
             } => {
+                let cal = &config.calibration;
                 let pos_depth = self.get_key_depth(positive_key.index);
                 let neg_depth = self.get_key_depth(negative_key.index);
-                map_two_key(pos_depth, neg_depth, &config.calibration)
+                let state = self.socd_states.entry(config.id).or_default();
+                state.update(is_held(pos_depth, cal), is_held(neg_depth, cal));
+                let state = *state;
+                map_two_key(pos_depth, neg_depth, *socd, state.last, cal)
             }
             AxisMappingMode::SingleKey { key, invert } => {
                 let depth = self.get_key_depth(key.index);
                 map_single_key(depth, *invert, &config.calibration)
             }
+            AxisMappingMode::Trigger { key } => {
+                let depth = self.get_key_depth(key.index);
+                map_trigger(depth, &config.calibration)
+            }
+            AxisMappingMode::Steering {
+                left_key,
+                right_key,
+                speed_key,
+                full_speed_range_percent,
+            } => {
+                let cal = &config.calibration;
+                let right_depth = self.get_key_depth(right_key.index);
+                let left_depth = self.get_key_depth(left_key.index);
+                let speed = speed_key.as_ref().map_or(0.0, |key| {
+                    normalize_depth(self.get_key_depth(key.index), cal)
+                });
+                map_steering(
+                    right_depth,
+                    left_depth,
+                    speed,
+                    *full_speed_range_percent,
+                    cal,
+                )
+            }
         }
     }
 
@@ -95,14 +160,25 @@ impl Default for AxisMapper {
 /// Map two keys to a bipolar axis value (-32767 to +32767)
 ///
 /// Positive key increases value, negative key decreases it.
-/// If both are pressed, they can cancel out.
-fn map_two_key(pos_depth_mm: f32, neg_depth_mm: f32, cal: &AxisCalibration) -> i32 {
+/// If both are pressed, `socd` decides; `last` is the key that went down
+/// most recently, for [`SocdMode::LastInput`].
+fn map_two_key(
+    pos_depth_mm: f32,
+    neg_depth_mm: f32,
+    socd: SocdMode,
+    last: Option<Side>,
+    cal: &AxisCalibration,
+) -> i32 {
     // Normalize each key's depth to 0.0-1.0
     let pos_norm = normalize_depth(pos_depth_mm, cal);
     let neg_norm = normalize_depth(neg_depth_mm, cal);
 
     // Combine: positive pushes up, negative pushes down
-    let combined = pos_norm - neg_norm; // Range: -1.0 to +1.0
+    let combined = if is_held(pos_depth_mm, cal) && is_held(neg_depth_mm, cal) {
+        clean_socd(pos_norm, neg_norm, socd, last)
+    } else {
+        pos_norm - neg_norm
+    }; // Range: -1.0 to +1.0
 
     // Apply deadzone (as a fraction of the combined range)
     let deadzone = cal.deadzone_percent / 100.0;
@@ -116,17 +192,84 @@ fn map_two_key(pos_depth_mm: f32, neg_depth_mm: f32, cal: &AxisCalibration) -> i
     };
 
     // Apply response curve
-    let curved = apply_curve(with_deadzone, cal.curve_exponent);
+    let curved = apply_curve(with_deadzone, cal);
 
     // Scale to axis range
     (curved * AXIS_MAX as f32) as i32
 }
 
+/// Combined value of a two-key axis while both keys are held
+fn clean_socd(pos_norm: f32, neg_norm: f32, socd: SocdMode, last: Option<Side>) -> f32 {
+    match socd {
+        SocdMode::Blend => pos_norm - neg_norm,
+        SocdMode::Neutral => 0.0,
+        SocdMode::LastInput => match last {
+            Some(Side::Positive) => pos_norm,
+            Some(Side::Negative) => -neg_norm,
+            None => 0.0,
+        },
+        SocdMode::PositivePriority => pos_norm,
+        SocdMode::NegativePriority => -neg_norm,
+        SocdMode::Deeper => {
+            if pos_norm >= neg_norm {
+                pos_norm
+            } else {
+                -neg_norm
+            }
+        }
+    }
+}
+
+/// Map steering keys to a bipolar axis value (-32767 to +32767)
+///
+/// Like a two-key axis (right positive), scaled down from full range at
+/// `speed` 0.0 to `full_speed_range_percent` at `speed` 1.0.
+fn map_steering(
+    right_depth_mm: f32,
+    left_depth_mm: f32,
+    speed: f32,
+    full_speed_range_percent: f32,
+    cal: &AxisCalibration,
+) -> i32 {
+    let value = map_two_key(right_depth_mm, left_depth_mm, SocdMode::Blend, None, cal);
+    let full_speed_range = (full_speed_range_percent / 100.0).clamp(0.0, 1.0);
+    let range = 1.0 - speed.clamp(0.0, 1.0) * (1.0 - full_speed_range);
+    (value as f32 * range) as i32
+}
+
+/// Whether a key is pressed past the axis deadzone
+fn is_held(depth_mm: f32, cal: &AxisCalibration) -> bool {
+    let norm = normalize_depth(depth_mm, cal);
+    norm > 0.0 && norm >= cal.deadzone_percent / 100.0
+}
+
 /// Map a single key to a unipolar axis value (-32767 to +32767)
 ///
 /// 0mm = -32767 (or +32767 if inverted)
 /// max_travel = +32767 (or -32767 if inverted)
 fn map_single_key(depth_mm: f32, invert: bool, cal: &AxisCalibration) -> i32 {
+    let curved = single_key_travel(depth_mm, cal);
+
+    // Convert to full axis range (-32767 to +32767)
+    // 0.0 -> -32767, 1.0 -> +32767
+    let scaled = AXIS_MIN as f32 + curved * (AXIS_MAX - AXIS_MIN) as f32;
+
+    if invert {
+        -scaled as i32
+    } else {
+        scaled as i32
+    }
+}
+
+/// Map a single key to a trigger value (0 to 1023)
+///
+/// 0mm = 0 (trigger released), max_travel = TRIGGER_MAX
+fn map_trigger(depth_mm: f32, cal: &AxisCalibration) -> i32 {
+    (single_key_travel(depth_mm, cal) * TRIGGER_MAX as f32) as i32
+}
+
+/// Travel of one key as 0.0-1.0, after deadzone and response curve
+fn single_key_travel(depth_mm: f32, cal: &AxisCalibration) -> f32 {
     // Normalize to 0.0-1.0
     let norm = normalize_depth(depth_mm, cal);
 
@@ -139,17 +282,7 @@ fn map_single_key(depth_mm: f32, invert: bool, cal: &AxisCalibration) -> i32 {
     };
 
     // Apply response curve
-    let curved = apply_curve(with_deadzone, cal.curve_exponent);
-
-    // Convert to full axis range (-32767 to +32767)
-    // 0.0 -> -32767, 1.0 -> +32767
-    let scaled = AXIS_MIN as f32 + curved * (AXIS_MAX - AXIS_MIN) as f32;
-
-    if invert {
-        -scaled as i32
-    } else {
-        scaled as i32
-    }
+    apply_curve(with_deadzone, cal)
 }
 
 /// Normalize depth to 0.0-1.0 based on calibration
@@ -161,17 +294,18 @@ fn normalize_depth(depth_mm: f32, cal: &AxisCalibration) -> f32 {
     ((depth_mm - cal.min_travel_mm) / range).clamp(0.0, 1.0)
 }
 
-/// Apply response curve (exponent)
+/// Apply the axis response curve to a value in -1.0 to +1.0
 ///
-/// Exponent > 1.0 makes the center less sensitive
-/// Exponent < 1.0 makes the center more sensitive
-fn apply_curve(value: f32, exponent: f32) -> f32 {
-    value.signum() * value.abs().powf(exponent)
+/// Curves that stay below linear near rest (exponent > 1.0) make the center
+/// less sensitive; curves above it make it more sensitive
+fn apply_curve(value: f32, cal: &AxisCalibration) -> f32 {
+    value.signum() * cal.response(value.abs())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::ResponseCurve;
 
     fn default_cal() -> AxisCalibration {
         AxisCalibration {
@@ -179,6 +313,7 @@ mod tests {
             max_travel_mm: 4.0,
             deadzone_percent: 0.0,
             curve_exponent: 1.0,
+            curve: ResponseCurve::Linear,
         }
     }
 
@@ -186,14 +321,14 @@ mod tests {
     fn test_two_key_neutral() {
         let cal = default_cal();
         // Both keys released
-        assert_eq!(map_two_key(0.0, 0.0, &cal), 0);
+        assert_eq!(map_two_key(0.0, 0.0, SocdMode::Blend, None, &cal), 0);
     }
 
     #[test]
     fn test_two_key_positive_full() {
         let cal = default_cal();
         // Positive key fully pressed
-        let val = map_two_key(4.0, 0.0, &cal);
+        let val = map_two_key(4.0, 0.0, SocdMode::Blend, None, &cal);
         assert_eq!(val, AXIS_MAX);
     }
 
@@ -201,7 +336,7 @@ mod tests {
     fn test_two_key_negative_full() {
         let cal = default_cal();
         // Negative key fully pressed
-        let val = map_two_key(0.0, 4.0, &cal);
+        let val = map_two_key(0.0, 4.0, SocdMode::Blend, None, &cal);
         assert_eq!(val, AXIS_MIN);
     }
 
@@ -209,7 +344,7 @@ mod tests {
     fn test_two_key_both_pressed() {
         let cal = default_cal();
         // Both keys pressed equally -> cancel out
-        let val = map_two_key(2.0, 2.0, &cal);
+        let val = map_two_key(2.0, 2.0, SocdMode::Blend, None, &cal);
         assert_eq!(val, 0);
     }
 
@@ -219,10 +354,73 @@ mod tests {
         cal.deadzone_percent = 10.0;
 
         // Small press should be absorbed by deadzone
-        let val = map_two_key(0.2, 0.0, &cal); // 5% travel
+        let val = map_two_key(0.2, 0.0, SocdMode::Blend, None, &cal); // 5% travel
         assert_eq!(val, 0);
     }
 
+    #[test]
+    fn test_socd_modes() {
+        let cal = default_cal();
+        let both = |socd, last| map_two_key(3.0, 2.0, socd, last, &cal);
+        assert_eq!(both(SocdMode::Blend, None), AXIS_MAX / 4);
+        assert_eq!(both(SocdMode::Neutral, Some(Side::Positive)), 0);
+        assert_eq!(
+            both(SocdMode::LastInput, Some(Side::Negative)),
+            AXIS_MIN / 2
+        );
+        assert_eq!(both(SocdMode::LastInput, None), 0);
+        assert_eq!(both(SocdMode::PositivePriority, None), AXIS_MAX * 3 / 4);
+        assert_eq!(both(SocdMode::NegativePriority, None), AXIS_MIN / 2);
+        assert_eq!(
+            both(SocdMode::Deeper, Some(Side::Negative)),
+            AXIS_MAX * 3 / 4
+        );
+
+        // One key alone is never cleaned
+        assert_eq!(
+            map_two_key(0.0, 2.0, SocdMode::Neutral, None, &cal),
+            AXIS_MIN / 2
+        );
+    }
+
+    #[test]
+    fn test_last_input_follows_presses() {
+        let mut config = JoystickConfig::default();
+        if let AxisMappingMode::TwoKey { socd, .. } = &mut config.axes[0].mapping {
+            *socd = SocdMode::LastInput;
+        }
+        config.axes[0].calibration.deadzone_percent = 0.0;
+        let mut mapper = AxisMapper::new();
+        let x = |mapper: &mut AxisMapper| mapper.compute_axes(&config)[0].1;
+
+        mapper.update_key_depth(9, 4.0); // A
+        assert_eq!(x(&mut mapper), AXIS_MIN);
+        mapper.update_key_depth(21, 4.0); // D, pressed later, wins
+        assert_eq!(x(&mut mapper), AXIS_MAX);
+        mapper.update_key_depth(21, 0.0); // back to A
+        assert_eq!(x(&mut mapper), AXIS_MIN);
+        mapper.update_key_depth(21, 4.0);
+        assert_eq!(x(&mut mapper), AXIS_MAX);
+        mapper.update_key_depth(9, 0.0);
+        assert_eq!(x(&mut mapper), AXIS_MAX);
+        mapper.update_key_depth(9, 4.0); // A re-pressed
+        assert_eq!(x(&mut mapper), AXIS_MIN);
+    }
+
+    #[test]
+    fn test_custom_curve_keeps_full_deflection() {
+        let mut cal = default_cal();
+        cal.curve = ResponseCurve::Custom {
+            points: vec![[0.5, 0.1]],
+        };
+        // Half travel only gives a tenth of the stick, full travel all of it
+        let half = map_two_key(2.0, 0.0, SocdMode::Blend, None, &cal);
+        assert_eq!(half, (0.1 * AXIS_MAX as f32) as i32);
+        assert_eq!(map_two_key(4.0, 0.0, SocdMode::Blend, None, &cal), AXIS_MAX);
+        assert_eq!(map_two_key(0.0, 4.0, SocdMode::Blend, None, &cal), AXIS_MIN);
+        assert_eq!(map_trigger(4.0, &cal), TRIGGER_MAX);
+    }
+
     #[test]
     fn test_single_key_neutral() {
         let cal = default_cal();
@@ -243,4 +441,33 @@ mod tests {
         let val = map_single_key(4.0, true, &cal);
         assert_eq!(val, AXIS_MIN);
     }
+
+    #[test]
+    fn test_trigger_range() {
+        let cal = default_cal();
+        assert_eq!(map_trigger(0.0, &cal), 0);
+        assert_eq!(map_trigger(2.0, &cal), TRIGGER_MAX / 2);
+        assert_eq!(map_trigger(4.0, &cal), TRIGGER_MAX);
+    }
+
+    #[test]
+    fn test_steering_narrows_with_speed() {
+        let cal = default_cal();
+        assert_eq!(map_steering(4.0, 0.0, 0.0, 50.0, &cal), AXIS_MAX);
+        assert_eq!(map_steering(0.0, 4.0, 0.0, 50.0, &cal), AXIS_MIN);
+        // Full throttle: half the lock
+        assert_eq!(map_steering(4.0, 0.0, 1.0, 50.0, &cal), AXIS_MAX / 2);
+        assert_eq!(map_steering(0.0, 4.0, 0.5, 50.0, &cal), -(AXIS_MAX * 3 / 4));
+        // 100% keeps the full range at any speed
+        assert_eq!(map_steering(4.0, 0.0, 1.0, 100.0, &cal), AXIS_MAX);
+    }
+
+    #[test]
+    fn test_trigger_deadzone() {
+        let mut cal = default_cal();
+        cal.deadzone_percent = 10.0;
+        // Resting finger on the key doesn't creep the trigger
+        assert_eq!(map_trigger(0.2, &cal), 0);
+        assert_eq!(map_trigger(4.0, &cal), TRIGGER_MAX);
+    }
 }
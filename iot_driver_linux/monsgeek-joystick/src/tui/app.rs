@@ -1,6 +1,6 @@
 //! TUI application state
 
-use crate::config::{AxisId, JoystickConfig};
+use crate::config::{AxisId, AxisMappingMode, JoystickConfig, ResponseCurve};
 use crate::mapper::AxisMapper;
 use std::path::PathBuf;
 
@@ -33,8 +33,17 @@ pub enum AxisProperty {
     MappingMode,
     PositiveKey,
     NegativeKey,
+    /// What a two-key axis reports while both keys are held
+    Socd,
+    /// Steering range left at full speed-key travel
+    SpeedRange,
     Deadzone,
+    /// Response curve type
+    CurveType,
+    /// Exponent of an exponential curve
     Curve,
+    /// Selected control point of a custom curve
+    CurvePoint,
 }
 
 /// Keyboard connection status
@@ -84,6 +93,8 @@ pub struct App {
     pub should_quit: bool,
     /// Precision factor for depth conversion (depth_raw / precision_factor → mm)
     pub precision_factor: f64,
+    /// Control point being edited on a custom response curve
+    pub curve_point: usize,
 }
 
 impl App {
@@ -104,6 +115,7 @@ impl App {
             show_help: false,
             should_quit: false,
             precision_factor: 100.0,
+            curve_point: 0,
         }
     }
 
@@ -223,9 +235,26 @@ impl App {
     pub fn toggle_current(&mut self) {
         if let SelectedElement::AxisProperty(idx, prop) = self.selected {
             if let Some(axis) = self.config.axes.get_mut(idx) {
-                if prop == AxisProperty::Enabled {
-                    axis.enabled = !axis.enabled;
-                    self.mark_dirty();
+                match (prop, &mut axis.mapping) {
+                    (AxisProperty::Enabled, _) => {
+                        axis.enabled = !axis.enabled;
+                        self.mark_dirty();
+                    }
+                    (AxisProperty::Socd, AxisMappingMode::TwoKey { socd, .. }) => {
+                        *socd = socd.next();
+                        self.mark_dirty();
+                    }
+                    (AxisProperty::CurveType, _) => {
+                        axis.calibration.curve = axis.calibration.curve.next();
+                        self.curve_point = 0;
+                        self.mark_dirty();
+                    }
+                    (AxisProperty::CurvePoint, _) => {
+                        if let ResponseCurve::Custom { points } = &axis.calibration.curve {
+                            self.curve_point = (self.curve_point + 1) % points.len().max(1);
+                        }
+                    }
+                    _ => {}
                 }
             }
         }
@@ -241,11 +270,30 @@ impl App {
                             (axis.calibration.deadzone_percent + delta).clamp(0.0, 50.0);
                         self.mark_dirty();
                     }
+                    AxisProperty::SpeedRange => {
+                        if let AxisMappingMode::Steering {
+                            full_speed_range_percent,
+                            ..
+                        } = &mut axis.mapping
+                        {
+                            *full_speed_range_percent =
+                                (*full_speed_range_percent + delta * 5.0).clamp(0.0, 100.0);
+                            self.mark_dirty();
+                        }
+                    }
                     AxisProperty::Curve => {
                         axis.calibration.curve_exponent =
                             (axis.calibration.curve_exponent + delta * 0.1).clamp(0.5, 3.0);
                         self.mark_dirty();
                     }
+                    AxisProperty::CurvePoint => {
+                        if let ResponseCurve::Custom { points } = &mut axis.calibration.curve {
+                            if let Some(point) = points.get_mut(self.curve_point) {
+                                point[1] = (point[1] + delta * 0.05).clamp(0.0, 1.0);
+                                self.mark_dirty();
+                            }
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -260,7 +308,11 @@ fn axis_properties() -> Vec<AxisProperty> {
         AxisProperty::MappingMode,
         AxisProperty::PositiveKey,
         AxisProperty::NegativeKey,
+        AxisProperty::Socd,
+        AxisProperty::SpeedRange,
         AxisProperty::Deadzone,
+        AxisProperty::CurveType,
         AxisProperty::Curve,
+        AxisProperty::CurvePoint,
     ]
 }
@@ -12,8 +12,9 @@ use ratatui::text::Span;
 use ratatui::widgets::{Block, Borders, Paragraph};
 use ratatui::Frame;
 
-/// Number of columns in the visual keyboard layout (cols 0-14, no media keys)
-const LAYOUT_COLS: usize = 15;
+/// Number of columns in the visual keyboard layout. Column 15 holds the
+/// M1 V5's encoder (unnamed, so skipped) or the M3 V5's nav cluster.
+const LAYOUT_COLS: usize = 16;
 /// Number of rows in the keyboard layout
 const LAYOUT_ROWS: usize = 6;
 
@@ -38,7 +39,7 @@ pub fn render_keyboard_layout(frame: &mut Frame, app: &App, area: Rect) {
     // Get mapped key indices for highlighting
     let mapped_keys = app.config.mapped_key_indices();
 
-    // Render each key (columns 0-14 only, skip media columns 90-95)
+    // Render each named key of the active layout
     for pos in 0..(LAYOUT_COLS * LAYOUT_ROWS) {
         let col = pos / LAYOUT_ROWS;
         let row = pos % LAYOUT_ROWS;
@@ -1,13 +1,13 @@
 //! TUI rendering logic
 
-use crate::config::{AxisConfig, AxisMappingMode};
-use crate::joystick::{AXIS_MAX, AXIS_MIN};
+use crate::config::{AxisCalibration, AxisConfig, AxisMappingMode, ResponseCurve};
+use crate::joystick::{AXIS_MAX, AXIS_MIN, TRIGGER_MAX};
 use crate::tui::app::{App, AppMode, JoystickStatus, KeyboardStatus, SelectedElement};
 use crate::tui::keyboard_layout::render_keyboard_layout;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, Paragraph, Tabs};
+use ratatui::widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph, Tabs};
 use ratatui::Frame;
 
 /// Render the entire application UI
@@ -118,8 +118,16 @@ fn render_axis_row(frame: &mut Frame, app: &App, axis: &AxisConfig, area: Rect)
         AxisMappingMode::TwoKey {
             positive_key,
             negative_key,
+            ..
         } => format!("{}/{}", positive_key.position, negative_key.position),
-        AxisMappingMode::SingleKey { key, .. } => key.position.to_string(),
+        AxisMappingMode::SingleKey { key, .. } | AxisMappingMode::Trigger { key } => {
+            key.position.to_string()
+        }
+        AxisMappingMode::Steering {
+            left_key,
+            right_key,
+            ..
+        } => format!("{}/{}", left_key.position, right_key.position),
     };
 
     let label = format!("{} ({}) ", axis.id.display_name(), keys_str);
@@ -154,9 +162,13 @@ fn render_axis_row(frame: &mut Frame, app: &App, axis: &AxisConfig, area: Rect)
         Color::DarkGray
     };
 
-    // For bipolar axis, show gauge from center
+    // For bipolar axis, show gauge from center; triggers fill from the left
     // We'll use a custom rendering approach
-    render_bipolar_gauge(frame, value, chunks[1], gauge_color);
+    if let AxisMappingMode::Trigger { .. } = axis.mapping {
+        render_trigger_gauge(frame, value, chunks[1], gauge_color);
+    } else {
+        render_bipolar_gauge(frame, value, chunks[1], gauge_color);
+    }
 
     // Value
     let value_str = format!("{:>6}", value);
@@ -196,6 +208,26 @@ fn render_bipolar_gauge(frame: &mut Frame, value: i32, area: Rect, color: Color)
     frame.render_widget(widget, area);
 }
 
+/// Render a trigger gauge (left = released, right = fully pressed)
+fn render_trigger_gauge(frame: &mut Frame, value: i32, area: Rect, color: Color) {
+    let width = area.width as usize;
+    let normalized = (value as f64 / TRIGGER_MAX as f64).clamp(0.0, 1.0);
+    let fill_width = (normalized * width as f64) as usize;
+
+    let chars: Vec<Span> = (0..width)
+        .map(|i| {
+            if i < fill_width {
+                Span::styled("=", Style::default().fg(color))
+            } else {
+                Span::styled("-", Style::default().fg(Color::DarkGray))
+            }
+        })
+        .collect();
+
+    let widget = Paragraph::new(Line::from(chars));
+    frame.render_widget(widget, area);
+}
+
 /// Render configuration view
 fn render_configure_view(frame: &mut Frame, app: &App, area: Rect) {
     let chunks = Layout::default()
@@ -288,6 +320,8 @@ fn render_axis_details(frame: &mut Frame, app: &App, area: Rect) {
                 "Single Key"
             }
         }
+        AxisMappingMode::Trigger { .. } => "Trigger",
+        AxisMappingMode::Steering { .. } => "Steering",
     };
     lines.push(Line::from(format!("Mode: {}", mode_str)));
 
@@ -296,6 +330,7 @@ fn render_axis_details(frame: &mut Frame, app: &App, area: Rect) {
         AxisMappingMode::TwoKey {
             positive_key,
             negative_key,
+            socd,
         } => {
             lines.push(Line::from(format!(
                 "Positive: {} (idx:{})",
@@ -305,13 +340,32 @@ fn render_axis_details(frame: &mut Frame, app: &App, area: Rect) {
                 "Negative: {} (idx:{})",
                 negative_key.position, negative_key.index
             )));
+            lines.push(Line::from(format!("Both held: {}", socd.display_name())));
         }
-        AxisMappingMode::SingleKey { key, .. } => {
+        AxisMappingMode::SingleKey { key, .. } | AxisMappingMode::Trigger { key } => {
             lines.push(Line::from(format!(
                 "Key: {} (idx:{})",
                 key.position, key.index
             )));
         }
+        AxisMappingMode::Steering {
+            left_key,
+            right_key,
+            speed_key,
+            full_speed_range_percent,
+        } => {
+            lines.push(Line::from(format!(
+                "Left: {} (idx:{})  Right: {} (idx:{})",
+                left_key.position, left_key.index, right_key.position, right_key.index
+            )));
+            match speed_key {
+                Some(key) => lines.push(Line::from(format!(
+                    "Speed key: {} ({:.0}% range at full press)",
+                    key.position, full_speed_range_percent
+                ))),
+                None => lines.push(Line::from("Speed key: none")),
+            }
+        }
     }
 
     // Calibration
@@ -320,13 +374,94 @@ fn render_axis_details(frame: &mut Frame, app: &App, area: Rect) {
         "Deadzone: {:.0}%",
         axis.calibration.deadzone_percent
     )));
-    lines.push(Line::from(format!(
-        "Curve: {:.1}",
-        axis.calibration.curve_exponent
-    )));
+    let cal = &axis.calibration;
+    match &cal.curve {
+        ResponseCurve::Linear => lines.push(Line::from("Curve: Linear")),
+        ResponseCurve::Exponential => lines.push(Line::from(format!(
+            "Curve: Exponential ({:.1})",
+            cal.curve_exponent
+        ))),
+        ResponseCurve::Custom { points } => {
+            lines.push(Line::from(format!(
+                "Curve: Custom ({} points)",
+                points.len()
+            )));
+            if let Some([x, y]) = points.get(app.curve_point) {
+                lines.push(Line::from(format!(
+                    "Point {}: {:.0}% travel -> {:.0}%",
+                    app.curve_point + 1,
+                    x * 100.0,
+                    y * 100.0
+                )));
+            }
+        }
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(lines.len() as u16),
+            Constraint::Min(6), // Curve preview
+        ])
+        .split(inner);
 
     let widget = Paragraph::new(lines);
-    frame.render_widget(widget, inner);
+    frame.render_widget(widget, chunks[0]);
+    render_curve_preview(frame, app, cal, chunks[1]);
+}
+
+/// Plot the response curve (travel past the deadzone -> deflection)
+fn render_curve_preview(frame: &mut Frame, app: &App, cal: &AxisCalibration, area: Rect) {
+    const SAMPLES: usize = 64;
+    let curve: Vec<(f64, f64)> = (0..=SAMPLES)
+        .map(|i| {
+            let travel = i as f32 / SAMPLES as f32;
+            (travel as f64, cal.response(travel) as f64)
+        })
+        .collect();
+
+    let points: Vec<(f64, f64)> = match &cal.curve {
+        ResponseCurve::Custom { points } => {
+            points.iter().map(|&[x, y]| (x as f64, y as f64)).collect()
+        }
+        _ => Vec::new(),
+    };
+    let selected: Vec<(f64, f64)> = points.get(app.curve_point).copied().into_iter().collect();
+
+    let datasets = vec![
+        Dataset::default()
+            .marker(ratatui::symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Cyan))
+            .data(&curve),
+        Dataset::default()
+            .marker(ratatui::symbols::Marker::Dot)
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(Color::White))
+            .data(&points),
+        Dataset::default()
+            .marker(ratatui::symbols::Marker::Block)
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(Color::Yellow))
+            .data(&selected),
+    ];
+
+    let chart = Chart::new(datasets)
+        .x_axis(
+            Axis::default()
+                .title("Travel")
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds([0.0, 1.0]),
+        )
+        .y_axis(
+            Axis::default()
+                .title("Output")
+                .style(Style::default().fg(Color::DarkGray))
+                .labels(vec![Span::raw("0"), Span::raw("max")])
+                .bounds([0.0, 1.0]),
+        );
+
+    frame.render_widget(chart, area);
 }
 
 /// Render calibration view
@@ -407,7 +542,7 @@ fn render_help_overlay(frame: &mut Frame, _app: &App) {
         Line::from("Arrow keys   Navigate"),
         Line::from("Enter        Select/Edit"),
         Line::from("Escape       Go back"),
-        Line::from("Space        Toggle enabled"),
+        Line::from("Space        Toggle / cycle (SOCD, curve type, curve point)"),
         Line::from("+/-          Adjust value"),
         Line::from("s            Save config"),
         Line::from("q            Quit"),
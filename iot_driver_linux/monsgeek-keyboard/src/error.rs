@@ -7,25 +7,37 @@ use thiserror::Error;
 /// Errors from keyboard operations
 #[derive(Error, Debug)]
 pub enum KeyboardError {
-    /// Transport layer error
+    /// Transport layer error not covered by a more specific variant
     #[error("Transport error: {0}")]
-    Transport(#[from] TransportError),
+    Transport(TransportError),
 
     /// Invalid parameter value
     #[error("Invalid parameter: {0}")]
     InvalidParameter(String),
 
     /// Feature not supported by this device
-    #[error("Feature not supported: {0}")]
-    NotSupported(String),
+    #[error("Feature not supported: {feature}{}", cmd_suffix(.cmd))]
+    NotSupported {
+        feature: String,
+        /// Command that would have been sent, if the device defines one
+        cmd: Option<u8>,
+    },
 
-    /// Device returned unexpected response
-    #[error("Unexpected response: {0}")]
-    UnexpectedResponse(String),
+    /// Device answered with a response that does not match the protocol
+    #[error("Protocol mismatch{}: {reason}", cmd_suffix(.cmd))]
+    ProtocolMismatch { cmd: Option<u8>, reason: String },
 
-    /// Operation timed out
-    #[error("Operation timed out")]
-    Timeout,
+    /// No response within the retry budget
+    #[error("Operation timed out{}", cmd_suffix(.cmd))]
+    Timeout { cmd: Option<u8> },
+
+    /// Device or dongle temporarily unable to accept the command
+    #[error("Device busy{}", cmd_suffix(.cmd))]
+    Busy { cmd: Option<u8> },
+
+    /// Device went away (unplugged, or the transport worker stopped)
+    #[error("Device disconnected")]
+    Disconnected,
 
     /// Device is offline (wireless only)
     #[error("Device is offline")]
@@ -34,6 +46,88 @@ pub enum KeyboardError {
     /// Device not found
     #[error("Device not found: {0}")]
     NotFound(String),
+
+    /// A settings transaction failed part-way
+    #[error("Settings transaction failed at {step}: {reason} ({})", rollback_note(.rolled_back))]
+    Transaction {
+        step: &'static str,
+        reason: String,
+        /// Whether every applied step was restored to its previous value
+        rolled_back: bool,
+    },
+}
+
+fn cmd_suffix(cmd: &Option<u8>) -> String {
+    cmd.map(|c| format!(" (cmd 0x{c:02X})")).unwrap_or_default()
+}
+
+fn rollback_note(rolled_back: &bool) -> &'static str {
+    if *rolled_back {
+        "previous settings restored"
+    } else {
+        "rollback incomplete"
+    }
+}
+
+impl KeyboardError {
+    /// Shorthand for a response that failed validation for `cmd`.
+    pub fn mismatch(cmd: u8, reason: impl Into<String>) -> Self {
+        Self::ProtocolMismatch {
+            cmd: Some(cmd),
+            reason: reason.into(),
+        }
+    }
+
+    /// Shorthand for a feature this device does not have.
+    pub fn unsupported(feature: impl Into<String>) -> Self {
+        Self::NotSupported {
+            feature: feature.into(),
+            cmd: None,
+        }
+    }
+
+    /// Whether repeating the same operation may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Timeout { .. } | Self::Busy { .. } | Self::Offline => true,
+            Self::Transport(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// Command byte of the request that failed, if known.
+    pub fn command(&self) -> Option<u8> {
+        match self {
+            Self::NotSupported { cmd, .. }
+            | Self::ProtocolMismatch { cmd, .. }
+            | Self::Timeout { cmd }
+            | Self::Busy { cmd } => *cmd,
+            Self::Transport(e) => e.command(),
+            _ => None,
+        }
+    }
+}
+
+/// Lift the transport conditions callers act on into first-class variants;
+/// everything else stays wrapped.
+impl From<TransportError> for KeyboardError {
+    fn from(e: TransportError) -> Self {
+        match e {
+            TransportError::Timeout { cmd } => Self::Timeout { cmd },
+            TransportError::Busy { cmd } => Self::Busy { cmd },
+            TransportError::Disconnected => Self::Disconnected,
+            TransportError::KeyboardOffline => Self::Offline,
+            TransportError::NotSupported { cmd } => Self::NotSupported {
+                feature: "Command (not in this firmware)".into(),
+                cmd: Some(cmd),
+            },
+            TransportError::ProtocolMismatch { cmd, reason } => Self::ProtocolMismatch {
+                cmd: Some(cmd),
+                reason,
+            },
+            other => Self::Transport(other),
+        }
+    }
 }
 
 impl From<KeyMatrixBoundsError> for KeyboardError {
@@ -41,3 +135,41 @@ impl From<KeyMatrixBoundsError> for KeyboardError {
         Self::InvalidParameter(e.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transport_errors_are_lifted() {
+        let e: KeyboardError = TransportError::Timeout { cmd: Some(0x87) }.into();
+        assert!(matches!(e, KeyboardError::Timeout { cmd: Some(0x87) }));
+        assert!(e.is_retryable());
+        assert_eq!(e.command(), Some(0x87));
+
+        let e: KeyboardError = TransportError::Disconnected.into();
+        assert!(matches!(e, KeyboardError::Disconnected));
+        assert!(!e.is_retryable());
+
+        // Unlifted variants keep their classification through the wrapper
+        let e: KeyboardError = TransportError::InvalidResponse {
+            expected: 0x8F,
+            actual: 0x87,
+        }
+        .into();
+        assert!(matches!(e, KeyboardError::Transport(_)));
+        assert!(e.is_retryable());
+        assert_eq!(e.command(), Some(0x8F));
+    }
+
+    #[test]
+    fn mismatch_is_not_retryable() {
+        let e = KeyboardError::mismatch(0x87, "Invalid profile response");
+        assert!(!e.is_retryable());
+        assert_eq!(e.command(), Some(0x87));
+        assert_eq!(
+            e.to_string(),
+            "Protocol mismatch (cmd 0x87): Invalid profile response"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Keep a wireless keyboard awake while the host drives it continuously.
+//!
+//! Host-to-keyboard traffic (LED streaming, audio visualizer frames) does not
+//! reset the firmware's idle timer, so over the dongle or Bluetooth the
+//! keyboard drops into light sleep after its idle timeout and the stream dies.
+//! [`KeepAwake`] disables the wireless sleep timers for as long as it lives and
+//! writes the user's original timeouts back when dropped.
+//!
+//! Wired connections never sleep, so on USB the guard does nothing.
+
+use crate::error::KeyboardError;
+use crate::settings::SleepTimeSettings;
+use crate::KeyboardInterface;
+
+/// Guard that keeps sleep disabled until dropped (or [`restore`](Self::restore)d).
+///
+/// Obtain one with [`KeyboardInterface::keep_awake`].
+pub struct KeepAwake<'a> {
+    kb: &'a KeyboardInterface,
+    /// Sleep settings to write back; `None` when nothing was overridden.
+    saved: Option<SleepTimeSettings>,
+}
+
+impl<'a> KeepAwake<'a> {
+    pub(crate) fn engage(kb: &'a KeyboardInterface) -> Result<Self, KeyboardError> {
+        let mut guard = Self { kb, saved: None };
+        if !kb.is_wireless() {
+            return Ok(guard);
+        }
+
+        let current = match kb.get_sleep_time() {
+            Ok(s) => s,
+            // No sleep-time command on this model: nothing we can override.
+            Err(KeyboardError::NotSupported { .. }) => return Ok(guard),
+            Err(e) => return Err(e),
+        };
+        let disabled = SleepTimeSettings::uniform(0, 0);
+        if current == disabled {
+            return Ok(guard);
+        }
+
+        kb.set_sleep_time(&disabled)?;
+        guard.saved = Some(current);
+        Ok(guard)
+    }
+
+    /// True if sleep was actually overridden (wireless link, timers enabled).
+    pub fn is_active(&self) -> bool {
+        self.saved.is_some()
+    }
+
+    /// Write the original sleep timeouts back, reporting any failure.
+    ///
+    /// Dropping the guard does the same but can only log errors.
+    pub fn restore(mut self) -> Result<(), KeyboardError> {
+        self.restore_inner()
+    }
+
+    fn restore_inner(&mut self) -> Result<(), KeyboardError> {
+        match self.saved.take() {
+            Some(saved) => self.kb.set_sleep_time(&saved),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for KeepAwake<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.restore_inner() {
+            tracing::warn!("failed to restore sleep timeouts: {e}");
+        }
+    }
+}
@@ -9,7 +9,7 @@ use monsgeek_transport::command::{
 };
 
 /// LED parameters
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LedParams {
     /// Current mode
     pub mode: LedMode,
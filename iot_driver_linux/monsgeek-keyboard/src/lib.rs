@@ -1,26 +1,33 @@
 //! High-level keyboard interface for MonsGeek/Akko keyboards
 //!
 //! This crate provides a convenient API for interacting with keyboard features
-//! on top of any transport layer (HID wired, dongle, Bluetooth, etc.)
+//! on top of any transport layer (HID wired, dongle, Bluetooth, etc.), plus a
+//! minimal [`MouseInterface`] for the vendor's mice.
 
 pub mod error;
 pub mod hid_codes;
+pub mod keep_awake;
 pub mod led;
 pub mod magnetism;
+pub mod mouse;
 pub mod settings;
 pub mod sync;
+pub mod transaction;
 
 pub use error::KeyboardError;
+pub use keep_awake::KeepAwake;
 pub use led::{LedMode, LedParams, RgbColor};
 pub use magnetism::{
     DksAction, DksBinding, DksCombo, DksConfig, DksPhase, KeyDepthEvent, KeyMode,
     KeyTriggerSettings, KeyTriggerSettingsDetail, ModeByte, TravelDepth, TriggerSettings,
 };
+pub use mouse::{DpiLevel, DpiSettings, MouseInterface};
 pub use settings::{
-    BatteryInfo, FeatureList, FirmwareVersion, KeyboardOptions, PollingRate, Precision,
-    SleepTimeSettings,
+    BatteryInfo, DeviceCapabilities, FeatureList, FirmwareVersion, KeyboardOptions, PollingRate,
+    Precision, SleepTimeSettings,
 };
 pub use sync::list_keyboards;
+pub use transaction::{SettingsStep, SettingsTransaction, TriggerChanges};
 
 pub use monsgeek_transport::protocol::ProtocolFamily;
 
@@ -84,7 +91,7 @@ pub use monsgeek_transport::{TimestampedEvent, VendorEvent};
 use std::sync::Arc;
 
 use monsgeek_transport::protocol::{cmd, magnetism as mag_cmd, CommandTable};
-use monsgeek_transport::{ChecksumType, FlowControlTransport, Transport};
+use monsgeek_transport::{ChecksumType, FlowControlTransport, Quirks, Transport};
 // Typed commands
 use monsgeek_transport::command::{
     GetFnData, GetKeyMatrixData, GetMacroData, GetMultiMagnetismData, HidCommand,
@@ -114,6 +121,10 @@ pub const SNAPTAP_UNBOUND: u8 = 0xFF;
 /// the written key. Measured floor is ~200 ms; this carries margin.
 const MAGNETISM_SETTLE_MS: u64 = 250;
 
+/// Settle time after switching profile before per-profile settings are
+/// read or written against the new profile.
+const PROFILE_SETTLE_MS: u64 = 100;
+
 /// High-level keyboard interface using any transport
 ///
 /// Provides convenient methods for keyboard features like LED control,
@@ -133,6 +144,8 @@ pub struct KeyboardInterface {
     protocol: ProtocolFamily,
     /// Command table for the active protocol family.
     commands: &'static CommandTable,
+    /// Hardware flags from the device database; unknown by default.
+    capabilities: DeviceCapabilities,
 }
 
 impl KeyboardInterface {
@@ -158,6 +171,7 @@ impl KeyboardInterface {
             polling_rates: Vec::new(),
             protocol,
             commands: protocol.commands(),
+            capabilities: DeviceCapabilities::default(),
         }
     }
 
@@ -186,6 +200,45 @@ impl KeyboardInterface {
         self.polling_rates = rates;
     }
 
+    /// Set the model's hardware flags from the device database.
+    ///
+    /// A known magnetism flag also replaces the one given to [`Self::new`].
+    pub fn set_capabilities(&mut self, capabilities: DeviceCapabilities) {
+        if let Some(has_magnetism) = capabilities.has_magnetism {
+            self.has_magnetism = has_magnetism;
+        }
+        self.capabilities = capabilities;
+    }
+
+    /// Hardware flags of this model (all unknown unless set)
+    pub fn capabilities(&self) -> &DeviceCapabilities {
+        &self.capabilities
+    }
+
+    /// Refuse `cmd` when the database says the board lacks `feature`.
+    fn require(&self, flag: Option<bool>, feature: &str, cmd: u8) -> Result<(), KeyboardError> {
+        if DeviceCapabilities::lacks(flag) {
+            return Err(KeyboardError::NotSupported {
+                feature: format!("{feature} (not on this model)"),
+                cmd: Some(cmd),
+            });
+        }
+        Ok(())
+    }
+
+    /// Sleep timers only exist on boards with a wireless radio.
+    fn require_wireless(&self, cmd: u8) -> Result<(), KeyboardError> {
+        if self.is_wireless() {
+            return Ok(());
+        }
+        self.require(self.capabilities.wireless, "Sleep time", cmd)
+    }
+
+    /// Screen color streaming and the screen sync mode need a screen.
+    fn require_screen(&self, cmd: u8) -> Result<(), KeyboardError> {
+        self.require(self.capabilities.has_screen, "Screen sync", cmd)
+    }
+
     /// Check if a matrix position is non-analog (GPIO/encoder, not a magnetic switch).
     pub fn is_non_analog(&self, position: usize) -> bool {
         self.non_analog_positions.contains(&(position as u8))
@@ -251,8 +304,9 @@ impl KeyboardInterface {
             .query_command(cmd::GET_USB_VERSION, &[], ChecksumType::Bit7)?;
 
         if resp.len() < 5 || resp[0] != cmd::GET_USB_VERSION {
-            return Err(KeyboardError::UnexpectedResponse(
-                "Invalid device ID response".into(),
+            return Err(KeyboardError::mismatch(
+                cmd::GET_USB_VERSION,
+                "Invalid device ID response",
             ));
         }
 
@@ -260,6 +314,14 @@ impl KeyboardInterface {
         Ok(device_id)
     }
 
+    /// Firmware quirks of the connected board (see `monsgeek_transport::quirks`).
+    ///
+    /// Read once per connection by the transport, which also refuses the
+    /// commands the firmware lacks.
+    pub fn quirks(&self) -> Quirks {
+        self.transport.quirks()
+    }
+
     /// Get firmware version
     pub fn get_version(&self) -> Result<FirmwareVersion, KeyboardError> {
         // Use GET_USB_VERSION which returns device_id and version
@@ -268,8 +330,9 @@ impl KeyboardInterface {
             .query_command(cmd::GET_USB_VERSION, &[], ChecksumType::Bit7)?;
 
         if resp.len() < 9 || resp[0] != cmd::GET_USB_VERSION {
-            return Err(KeyboardError::UnexpectedResponse(
-                "Invalid version response".into(),
+            return Err(KeyboardError::mismatch(
+                cmd::GET_USB_VERSION,
+                "Invalid version response",
             ));
         }
 
@@ -310,6 +373,9 @@ impl KeyboardInterface {
 
     /// Set LED parameters
     pub fn set_led_params(&self, params: &LedParams) -> Result<(), KeyboardError> {
+        if params.mode == LedMode::ScreenSync {
+            self.require_screen(cmd::SET_LEDPARAM)?;
+        }
         self.transport.send(&params.to_transport_cmd())?;
         Ok(())
     }
@@ -321,9 +387,7 @@ impl KeyboardInterface {
         let cmd = self.commands.get_profile;
         let resp = self.transport.query_command(cmd, &[], ChecksumType::Bit7)?;
         if resp.is_empty() || resp[0] != cmd {
-            return Err(KeyboardError::UnexpectedResponse(
-                "Invalid profile response".into(),
-            ));
+            return Err(KeyboardError::mismatch(cmd, "Invalid profile response"));
         }
         Ok(resp[1])
     }
@@ -340,34 +404,73 @@ impl KeyboardInterface {
         Ok(())
     }
 
+    /// Run `f` with `profile` temporarily active, then switch back.
+    ///
+    /// Trigger (magnetism) commands carry no profile field and always act on
+    /// the active profile, so targeting another profile means switching to
+    /// it, applying, and restoring. If `profile` is already active `f` runs
+    /// directly. The original profile is restored even when `f` fails; an
+    /// error from `f` takes precedence over a failed restore.
+    pub fn with_profile<T, E>(
+        &self,
+        profile: u8,
+        f: impl FnOnce(&Self) -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: From<KeyboardError>,
+    {
+        let active = self.get_profile()?;
+        if active == profile {
+            return f(self);
+        }
+
+        self.set_profile(profile)?;
+        std::thread::sleep(std::time::Duration::from_millis(PROFILE_SETTLE_MS));
+        let result = f(self);
+        let restored = self.set_profile(active);
+        std::thread::sleep(std::time::Duration::from_millis(PROFILE_SETTLE_MS));
+
+        let value = result?;
+        restored?;
+        Ok(value)
+    }
+
     /// Get polling rate (RY5088-only, uses GET_REPORT)
     pub fn get_polling_rate(&self) -> Result<PollingRate, KeyboardError> {
         let cmd_byte = self.commands.get_report.ok_or_else(|| {
-            KeyboardError::NotSupported("Polling rate not available on this device".into())
+            KeyboardError::unsupported("Polling rate not available on this device")
         })?;
         let resp = self
             .transport
             .query_command(cmd_byte, &[], ChecksumType::Bit7)?;
         if resp.len() < POLLING_RATE_FRAME_OFFSET + 1 || resp[0] != cmd_byte {
-            return Err(KeyboardError::UnexpectedResponse(
-                "Invalid polling rate response".into(),
+            return Err(KeyboardError::mismatch(
+                cmd_byte,
+                "Invalid polling rate response",
             ));
         }
         let code = resp[POLLING_RATE_FRAME_OFFSET];
         PollingRate::from_protocol(code).ok_or_else(|| {
-            KeyboardError::UnexpectedResponse(format!("Unknown polling rate: 0x{code:02X}"))
+            KeyboardError::mismatch(cmd_byte, format!("Unknown polling rate: 0x{code:02X}"))
         })
     }
 
     /// Set polling rate (RY5088-only, uses SET_REPORT)
     pub fn set_polling_rate(&self, rate: PollingRate) -> Result<(), KeyboardError> {
         let cmd_byte = self.commands.set_report.ok_or_else(|| {
-            KeyboardError::NotSupported("Polling rate not available on this device".into())
+            KeyboardError::unsupported("Polling rate not available on this device")
         })?;
         let hz = rate.to_hz();
-        if !self.polling_rates.is_empty() && !self.polling_rates.contains(&hz) {
-            let max = self.polling_rates.iter().max().copied().unwrap_or(0);
-            return Err(KeyboardError::NotSupported(format!(
+        let listed = self.polling_rates.is_empty() || self.polling_rates.contains(&hz);
+        if !listed || self.capabilities.exceeds_polling(hz) {
+            let max = self
+                .polling_rates
+                .iter()
+                .max()
+                .copied()
+                .or(self.capabilities.max_polling_hz)
+                .unwrap_or(0);
+            return Err(KeyboardError::unsupported(format!(
                 "{hz} Hz is above this device's maximum of {max} Hz"
             )));
         }
@@ -386,8 +489,9 @@ impl KeyboardInterface {
             .transport
             .query_command(cmd_byte, &[], ChecksumType::Bit7)?;
         if resp.is_empty() || resp[0] != cmd_byte {
-            return Err(KeyboardError::UnexpectedResponse(
-                "Invalid debounce response".into(),
+            return Err(KeyboardError::mismatch(
+                cmd_byte,
+                "Invalid debounce response",
             ));
         }
         Ok(resp[1])
@@ -412,15 +516,18 @@ impl KeyboardInterface {
     /// Returns idle and deep sleep timeouts for both Bluetooth and 2.4GHz.
     /// All values are in seconds.
     pub fn get_sleep_time(&self) -> Result<SleepTimeSettings, KeyboardError> {
-        let cmd_byte = self.commands.get_sleeptime.ok_or_else(|| {
-            KeyboardError::NotSupported("Sleep time not available on this device".into())
-        })?;
+        let cmd_byte = self
+            .commands
+            .get_sleeptime
+            .ok_or_else(|| KeyboardError::unsupported("Sleep time not available on this device"))?;
+        self.require_wireless(cmd_byte)?;
         let resp = self
             .transport
             .query_command(cmd_byte, &[], ChecksumType::Bit7)?;
         if resp.len() < 16 || resp[0] != cmd_byte {
-            return Err(KeyboardError::UnexpectedResponse(
-                "Invalid sleep time response".into(),
+            return Err(KeyboardError::mismatch(
+                cmd_byte,
+                "Invalid sleep time response",
             ));
         }
         Ok(SleepTimeSettings {
@@ -436,9 +543,11 @@ impl KeyboardInterface {
     /// Sets idle and deep sleep timeouts for both Bluetooth and 2.4GHz.
     /// All values are in seconds. Set to 0 to disable a particular timeout.
     pub fn set_sleep_time(&self, settings: &SleepTimeSettings) -> Result<(), KeyboardError> {
-        let cmd_byte = self.commands.set_sleeptime.ok_or_else(|| {
-            KeyboardError::NotSupported("Sleep time not available on this device".into())
-        })?;
+        let cmd_byte = self
+            .commands
+            .set_sleeptime
+            .ok_or_else(|| KeyboardError::unsupported("Sleep time not available on this device"))?;
+        self.require_wireless(cmd_byte)?;
         // Build data with same layout as SetSleepTime::to_data()
         let mut data = vec![0u8; 15];
         data[7..9].copy_from_slice(&settings.idle_bt.to_le_bytes());
@@ -454,16 +563,18 @@ impl KeyboardInterface {
 
     /// Get keyboard options (OS mode, Fn layer, etc.)
     pub fn get_kb_options(&self) -> Result<KeyboardOptions, KeyboardError> {
-        let cmd_byte = self.commands.get_kboption.ok_or_else(|| {
-            KeyboardError::NotSupported("KB options not available on this device".into())
-        })?;
+        let cmd_byte = self
+            .commands
+            .get_kboption
+            .ok_or_else(|| KeyboardError::unsupported("KB options not available on this device"))?;
         let resp = self
             .transport
             .query_command(cmd_byte, &[], ChecksumType::Bit7)?;
 
         if resp.len() < 9 || resp[0] != cmd_byte {
-            return Err(KeyboardError::UnexpectedResponse(
-                "Invalid KB options response".into(),
+            return Err(KeyboardError::mismatch(
+                cmd_byte,
+                "Invalid KB options response",
             ));
         }
 
@@ -472,9 +583,10 @@ impl KeyboardInterface {
 
     /// Set keyboard options
     pub fn set_kb_options(&self, options: &KeyboardOptions) -> Result<(), KeyboardError> {
-        let cmd_byte = self.commands.set_kboption.ok_or_else(|| {
-            KeyboardError::NotSupported("KB options not available on this device".into())
-        })?;
+        let cmd_byte = self
+            .commands
+            .set_kboption
+            .ok_or_else(|| KeyboardError::unsupported("KB options not available on this device"))?;
         self.transport
             .send_command(cmd_byte, &options.to_bytes(), ChecksumType::Bit7)?;
 
@@ -490,14 +602,31 @@ impl KeyboardInterface {
             .query_command(cmd::GET_FEATURE_LIST, &[], ChecksumType::Bit7)?;
 
         if resp.is_empty() || resp[0] != cmd::GET_FEATURE_LIST {
-            return Err(KeyboardError::UnexpectedResponse(
-                "Invalid feature list response".into(),
+            return Err(KeyboardError::mismatch(
+                cmd::GET_FEATURE_LIST,
+                "Invalid feature list response",
             ));
         }
 
         Ok(FeatureList::from_bytes(&resp[1..]))
     }
 
+    /// Disable wireless sleep until the returned guard is dropped.
+    ///
+    /// Use around long-running streaming loops (per-key RGB, audio or screen
+    /// modes) so the keyboard does not idle into sleep mid-stream. No-op on
+    /// wired connections.
+    pub fn keep_awake(&self) -> Result<KeepAwake<'_>, KeyboardError> {
+        KeepAwake::engage(self)
+    }
+
+    /// Start staging several setting changes to apply all-or-nothing.
+    ///
+    /// See [`SettingsTransaction`] for ordering, verification and rollback.
+    pub fn transaction(&self) -> SettingsTransaction<'_> {
+        SettingsTransaction::new(self)
+    }
+
     /// Get precision level for travel/trigger settings
     ///
     /// This method tries to get precision from the feature list first.
@@ -514,22 +643,31 @@ impl KeyboardInterface {
             }
         }
 
-        // Fall back to firmware version
-        let version = self.get_version()?;
-        Ok(version.precision())
+        // Fall back to the firmware's travel steps
+        Ok(settings::Precision::from_steps_per_mm(
+            self.quirks().travel_steps_per_mm,
+        ))
     }
 
     // === Side LED (Sidelight) ===
 
     /// Get side LED parameters
+    ///
+    /// Refused without a query on models the database lists without a side light.
     pub fn get_side_led_params(&self) -> Result<LedParams, KeyboardError> {
+        self.require(
+            self.capabilities.has_side_led,
+            "Side LED",
+            cmd::GET_SLEDPARAM,
+        )?;
         let resp = self
             .transport
             .query_command(cmd::GET_SLEDPARAM, &[], ChecksumType::Bit7)?;
 
         if resp.len() < 8 || resp[0] != cmd::GET_SLEDPARAM {
-            return Err(KeyboardError::UnexpectedResponse(
-                "Invalid side LED params response".into(),
+            return Err(KeyboardError::mismatch(
+                cmd::GET_SLEDPARAM,
+                "Invalid side LED params response",
             ));
         }
 
@@ -546,6 +684,11 @@ impl KeyboardInterface {
 
     /// Set side LED parameters
     pub fn set_side_led_params(&self, params: &LedParams) -> Result<(), KeyboardError> {
+        self.require(
+            self.capabilities.has_side_led,
+            "Side LED",
+            cmd::SET_SLEDPARAM,
+        )?;
         // Protocol format: [mode, speed, brightness, option, r, g, b]
         // Note: Side LED speed is NOT inverted (unlike main LED)
         let data = [
@@ -582,6 +725,7 @@ impl KeyboardInterface {
     ///
     /// Uses the SET_USERPIC (0x0C) bulk protocol: 7 pages of 56/42 bytes.
     pub fn upload_userpic(&self, slot: u8, data: &[u8]) -> Result<(), KeyboardError> {
+        self.require(self.capabilities.has_key_leds, "Userpic", cmd::SET_USERPIC)?;
         if slot > 4 {
             return Err(KeyboardError::InvalidParameter(
                 "Userpic slot must be 0-4".into(),
@@ -638,6 +782,7 @@ impl KeyboardInterface {
     /// Returns 384 bytes in column-major format (6 blocks × 64 bytes).
     /// Uses GET_USERPIC (0x8C) block read protocol.
     pub fn download_userpic(&self, slot: u8) -> Result<Vec<u8>, KeyboardError> {
+        self.require(self.capabilities.has_key_leds, "Userpic", cmd::GET_USERPIC)?;
         if slot > 4 {
             return Err(KeyboardError::InvalidParameter(
                 "Userpic slot must be 0-4".into(),
@@ -665,8 +810,8 @@ impl KeyboardInterface {
     /// Start magnetism (key depth) reporting
     pub fn start_magnetism_report(&self) -> Result<(), KeyboardError> {
         if !self.has_magnetism {
-            return Err(KeyboardError::NotSupported(
-                "Device does not have Hall Effect switches".into(),
+            return Err(KeyboardError::unsupported(
+                "Device does not have Hall Effect switches",
             ));
         }
         self.transport.send(&SetMagnetismReport::enable())?;
@@ -720,7 +865,7 @@ impl KeyboardInterface {
     pub fn poll_notification(&self, timeout_ms: u32) -> Result<Option<VendorEvent>, KeyboardError> {
         self.transport
             .read_event(timeout_ms)
-            .map_err(KeyboardError::Transport)
+            .map_err(KeyboardError::from)
     }
 
     /// Get trigger settings for a specific key.
@@ -750,10 +895,11 @@ impl KeyboardInterface {
     /// (it belongs to a different chip family), so writes through it never landed.
     pub fn set_key_trigger(&self, settings: &KeyTriggerSettings) -> Result<(), KeyboardError> {
         if !self.has_magnetism {
-            return Err(KeyboardError::NotSupported(
-                "Device does not have Hall Effect switches".into(),
+            return Err(KeyboardError::unsupported(
+                "Device does not have Hall Effect switches",
             ));
         }
+        self.require_magnetism_firmware()?;
 
         let key = settings.key_index;
         self.set_magnetism_simple(
@@ -807,10 +953,12 @@ impl KeyboardInterface {
     /// Get all trigger settings
     pub fn get_all_triggers(&self) -> Result<TriggerSettings, KeyboardError> {
         if !self.has_magnetism {
-            return Err(KeyboardError::NotSupported(
-                "Device does not have Hall Effect switches".into(),
+            return Err(KeyboardError::unsupported(
+                "Device does not have Hall Effect switches",
             ));
         }
+        self.require_magnetism_firmware()?;
+        let top_deadzone = self.quirks().top_deadzone;
 
         // Calculate pages needed based on key count (64 bytes per page)
         let pages_u8 = (self.key_count as usize).div_ceil(64); // 1 byte per key
@@ -827,13 +975,16 @@ impl KeyboardInterface {
         let rt_press = self.get_magnetism(mag_cmd::RT_PRESS, pages_u16)?;
         let rt_lift = self.get_magnetism(mag_cmd::RT_LIFT, pages_u16)?;
 
-        // Deadzones - may fail on older firmware
+        // Deadzones - may fail on older firmware; the top one only exists from v4.00
         let bottom_dz = self
             .get_magnetism(mag_cmd::BOTTOM_DEADZONE, pages_u16)
             .unwrap_or_default();
-        let top_dz = self
-            .get_magnetism(mag_cmd::TOP_DEADZONE, pages_u16)
-            .unwrap_or_default();
+        let top_dz = if top_deadzone {
+            self.get_magnetism(mag_cmd::TOP_DEADZONE, pages_u16)
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
 
         Ok(TriggerSettings {
             key_count: kc,
@@ -851,9 +1002,18 @@ impl KeyboardInterface {
 
     /// Set magnetism values for all keys (u16 version, used by newer firmware)
     ///
-    /// Sends values in pages of 56 bytes each.
+    /// Sends values in pages (56 bytes each unless the firmware's quirks say otherwise).
     /// Format: [sub_cmd, flag=1, page, commit, 0, 0, 0, data...]
     fn set_magnetism_u16(&self, sub_cmd: u8, values: &[u16]) -> Result<(), KeyboardError> {
+        self.require_magnetism_firmware()?;
+        let quirks = self.quirks();
+        if sub_cmd == mag_cmd::TOP_DEADZONE && !quirks.top_deadzone {
+            return Err(KeyboardError::NotSupported {
+                feature: "Top deadzone (firmware v4.00 or later)".to_string(),
+                cmd: Some(cmd::SET_MULTI_MAGNETISM),
+            });
+        }
+
         // Convert u16 values to bytes (little-endian)
         let bytes: Vec<u8> = values
             .iter()
@@ -861,11 +1021,10 @@ impl KeyboardInterface {
             .flat_map(|&v| v.to_le_bytes())
             .collect();
 
-        // Send in pages (56 bytes per page)
-        const PAGE_SIZE: usize = 56;
-        let num_pages = bytes.len().div_ceil(PAGE_SIZE);
+        let page_size = quirks.magnetism_page_size;
+        let num_pages = bytes.len().div_ceil(page_size);
 
-        for (page, chunk) in bytes.chunks(PAGE_SIZE).enumerate() {
+        for (page, chunk) in bytes.chunks(page_size).enumerate() {
             let is_last = page == num_pages - 1;
             let cmd = SetMultiMagnetismCommand {
                 header: SetMultiMagnetismHeader {
@@ -880,14 +1039,26 @@ impl KeyboardInterface {
                 payload: chunk.to_vec(),
             };
 
-            self.transport.send_with_delay(&cmd, 30)?;
+            self.transport
+                .send_with_delay(&cmd, quirks.magnetism_page_delay_ms)?;
         }
 
         Ok(())
     }
 
+    /// Refuse trigger commands on firmware that predates them.
+    fn require_magnetism_firmware(&self) -> Result<(), KeyboardError> {
+        if self.quirks().magnetism {
+            return Ok(());
+        }
+        Err(KeyboardError::unsupported(
+            "Trigger settings need firmware v2.00 or later",
+        ))
+    }
+
     /// Set magnetism values for all keys (u8 version, legacy)
     fn set_magnetism_u8(&self, sub_cmd: u8, values: &[u8]) -> Result<(), KeyboardError> {
+        self.require_magnetism_firmware()?;
         let mut data = vec![sub_cmd];
         data.extend_from_slice(&values[..self.key_count as usize]);
         self.transport
@@ -921,6 +1092,42 @@ impl KeyboardInterface {
         self.set_magnetism_u16(mag_cmd::RT_LIFT, &values)
     }
 
+    /// Check a per-key table covers every key before a bulk write; a short
+    /// table would commit with the trailing keys' pages never sent.
+    fn check_per_key_len(&self, len: usize) -> Result<(), KeyboardError> {
+        if len < self.key_count as usize {
+            return Err(KeyboardError::InvalidParameter(format!(
+                "expected {} per-key values, got {len}",
+                self.key_count
+            )));
+        }
+        Ok(())
+    }
+
+    /// Set actuation points per key (u16 raw values, indexed by matrix position)
+    pub fn set_actuation_per_key_u16(&self, travel: &[u16]) -> Result<(), KeyboardError> {
+        self.check_per_key_len(travel.len())?;
+        self.set_magnetism_u16(mag_cmd::PRESS_TRAVEL, travel)
+    }
+
+    /// Set release points per key (u16 raw values, indexed by matrix position)
+    pub fn set_release_per_key_u16(&self, travel: &[u16]) -> Result<(), KeyboardError> {
+        self.check_per_key_len(travel.len())?;
+        self.set_magnetism_u16(mag_cmd::LIFT_TRAVEL, travel)
+    }
+
+    /// Set Rapid Trigger press sensitivity per key (u16 raw values)
+    pub fn set_rt_press_per_key_u16(&self, sensitivity: &[u16]) -> Result<(), KeyboardError> {
+        self.check_per_key_len(sensitivity.len())?;
+        self.set_magnetism_u16(mag_cmd::RT_PRESS, sensitivity)
+    }
+
+    /// Set Rapid Trigger release sensitivity per key (u16 raw values)
+    pub fn set_rt_lift_per_key_u16(&self, sensitivity: &[u16]) -> Result<(), KeyboardError> {
+        self.check_per_key_len(sensitivity.len())?;
+        self.set_magnetism_u16(mag_cmd::RT_LIFT, sensitivity)
+    }
+
     /// Enable/disable the Rapid-Trigger flag (`0x80`) for all keys, preserving
     /// each key's base mode (read-modify-write of the KEY_MODE bytes).
     pub fn set_rapid_trigger_all(&self, enable: bool) -> Result<(), KeyboardError> {
@@ -1266,6 +1473,9 @@ impl KeyboardInterface {
         dazzle: bool,
         layer: u8,
     ) -> Result<(), KeyboardError> {
+        if mode == LedMode::ScreenSync as u8 {
+            self.require_screen(cmd::SET_LEDPARAM)?;
+        }
         let (option, r_val, g_val, b_val) = if mode == 13 {
             // For UserPicture mode: option = layer << 4, RGB = (0, 200, 200)
             (layer << 4, 0u8, 200u8, 200u8)
@@ -1387,6 +1597,11 @@ impl KeyboardInterface {
 
     /// Start/stop minimum position calibration (keys released)
     pub fn calibrate_min(&self, start: bool) -> Result<(), KeyboardError> {
+        self.require(
+            self.capabilities.has_magnetism,
+            "Calibration",
+            cmd::SET_MAGNETISM_CAL,
+        )?;
         self.transport.send_command(
             cmd::SET_MAGNETISM_CAL,
             &[if start { 1 } else { 0 }],
@@ -1397,6 +1612,11 @@ impl KeyboardInterface {
 
     /// Start/stop maximum position calibration (keys pressed)
     pub fn calibrate_max(&self, start: bool) -> Result<(), KeyboardError> {
+        self.require(
+            self.capabilities.has_magnetism,
+            "Calibration",
+            cmd::SET_MAGNETISM_MAX_CAL,
+        )?;
         self.transport.send_command(
             cmd::SET_MAGNETISM_MAX_CAL,
             &[if start { 1 } else { 0 }],
@@ -1416,6 +1636,11 @@ impl KeyboardInterface {
     /// # Returns
     /// Vector of 16-bit calibration values for up to 32 keys
     pub fn get_calibration_progress(&self, page: u8) -> Result<Vec<u16>, KeyboardError> {
+        self.require(
+            self.capabilities.has_magnetism,
+            "Calibration",
+            cmd::GET_MULTI_MAGNETISM,
+        )?;
         let query = GetMultiMagnetismData {
             sub_cmd: mag_cmd::CALIBRATION,
             flag: 1,
@@ -1437,6 +1662,29 @@ impl KeyboardInterface {
         Ok(values)
     }
 
+    /// Stored per-key calibration values (the sensor reading each switch
+    /// was calibrated to at the bottom of its travel).
+    pub fn get_calibration(&self) -> Result<Vec<u16>, KeyboardError> {
+        self.require(
+            self.capabilities.has_magnetism,
+            "Calibration",
+            cmd::GET_MULTI_MAGNETISM,
+        )?;
+        let kc = self.key_count as usize;
+        let data = self.get_magnetism(mag_cmd::CALIBRATION, (kc * 2).div_ceil(64))?;
+        Ok(TriggerSettings::decode_u16_values(&data, kc))
+    }
+
+    /// Write calibration values back, as read by [`get_calibration`](Self::get_calibration).
+    pub fn set_calibration(&self, values: &[u16]) -> Result<(), KeyboardError> {
+        self.require(
+            self.capabilities.has_magnetism,
+            "Calibration",
+            cmd::SET_MULTI_MAGNETISM,
+        )?;
+        self.set_magnetism_u16(mag_cmd::CALIBRATION, values)
+    }
+
     // === Factory Reset ===
 
     /// Factory reset the keyboard
@@ -1466,6 +1714,9 @@ impl KeyboardInterface {
 
     /// Send raw command without expecting response
     pub fn send_raw_cmd(&self, cmd_byte: u8, data: &[u8]) -> Result<(), KeyboardError> {
+        if cmd_byte == cmd::SET_SCREEN_COLOR {
+            self.require_screen(cmd_byte)?;
+        }
         self.transport
             .send_command(cmd_byte, data, ChecksumType::Bit7)?;
         Ok(())
@@ -1480,6 +1731,9 @@ impl KeyboardInterface {
     ///
     /// [`send_raw_cmd`]: Self::send_raw_cmd
     pub fn send_raw_cmd_fast(&self, cmd_byte: u8, data: &[u8]) -> Result<(), KeyboardError> {
+        if cmd_byte == cmd::SET_SCREEN_COLOR {
+            self.require_screen(cmd_byte)?;
+        }
         self.transport
             .send_command_with_delay(cmd_byte, data, ChecksumType::Bit7, 0)?;
         Ok(())
@@ -1531,9 +1785,9 @@ impl KeyboardInterface {
         }
 
         if all_data.is_empty() {
-            Err(KeyboardError::UnexpectedResponse(
-                "No keymatrix data".into(),
-            ))
+            Err(KeyboardError::Timeout {
+                cmd: Some(self.commands.get_keymatrix),
+            })
         } else {
             Ok(all_data)
         }
@@ -1576,9 +1830,9 @@ impl KeyboardInterface {
         }
 
         if all_data.is_empty() {
-            Err(KeyboardError::UnexpectedResponse(
-                "GET_FN returned no data".into(),
-            ))
+            Err(KeyboardError::Timeout {
+                cmd: Some(cmd::GET_FN),
+            })
         } else {
             Ok(all_data)
         }
@@ -1634,7 +1888,20 @@ impl KeyboardInterface {
     ///
     /// Sets the key to "disabled" which causes the firmware to use the default.
     pub fn reset_key(&self, layer: u8, key_index: u8) -> Result<(), KeyboardError> {
-        self.set_key_config(0, key_index, layer, [0, 0, 0, 0])
+        self.reset_key_in_profile(0, layer, key_index)
+    }
+
+    /// Like [`reset_key`](Self::reset_key) but for an explicit profile (0-3).
+    ///
+    /// Keymatrix writes carry the profile in the packet, so no profile switch
+    /// is needed.
+    pub fn reset_key_in_profile(
+        &self,
+        profile: u8,
+        layer: u8,
+        key_index: u8,
+    ) -> Result<(), KeyboardError> {
+        self.set_key_config(profile, key_index, layer, [0, 0, 0, 0])
     }
 
     /// Swap two keys
@@ -1692,7 +1959,9 @@ impl KeyboardInterface {
         }
 
         if all_data.is_empty() {
-            Err(KeyboardError::UnexpectedResponse("No macro data".into()))
+            Err(KeyboardError::Timeout {
+                cmd: Some(cmd::GET_MACRO),
+            })
         } else if all_data.iter().all(|&b| b == 0xFF) {
             // Uninitialized slot — treat as empty
             Ok(vec![0, 0]) // repeat_count=0, no events
@@ -1790,25 +2059,7 @@ impl KeyboardInterface {
         delay_ms: u16,
         repeat: u16,
     ) -> Result<(), KeyboardError> {
-        use crate::hid_codes::char_to_hid;
-
-        const LSHIFT: u8 = 0xE1; // Left Shift HID code
-        let mut events = Vec::new();
-
-        for ch in text.chars() {
-            if let Some((keycode, needs_shift)) = char_to_hid(ch) {
-                if needs_shift {
-                    events.push((LSHIFT, true, 0u16)); // Shift down
-                    events.push((keycode, true, delay_ms)); // Key down
-                    events.push((keycode, false, 0u16)); // Key up
-                    events.push((LSHIFT, false, delay_ms)); // Shift up
-                } else {
-                    events.push((keycode, true, delay_ms)); // Key down
-                    events.push((keycode, false, delay_ms)); // Key up
-                }
-            }
-        }
-
+        let events = text_macro_events(text, delay_ms);
         self.set_macro(macro_index, &events, repeat)
     }
 
@@ -2143,6 +2394,32 @@ pub struct MacroEvent {
     pub delay_ms: u16,
 }
 
+/// Expand `text` into the macro events [`KeyboardInterface::set_text_macro`] writes.
+///
+/// Characters without a HID mapping are skipped.
+pub fn text_macro_events(text: &str, delay_ms: u16) -> Vec<(u8, bool, u16)> {
+    use crate::hid_codes::char_to_hid;
+
+    const LSHIFT: u8 = 0xE1; // Left Shift HID code
+    let mut events = Vec::new();
+
+    for ch in text.chars() {
+        if let Some((keycode, needs_shift)) = char_to_hid(ch) {
+            if needs_shift {
+                events.push((LSHIFT, true, 0u16)); // Shift down
+                events.push((keycode, true, delay_ms)); // Key down
+                events.push((keycode, false, 0u16)); // Key up
+                events.push((LSHIFT, false, delay_ms)); // Shift up
+            } else {
+                events.push((keycode, true, delay_ms)); // Key down
+                events.push((keycode, false, delay_ms)); // Key up
+            }
+        }
+    }
+
+    events
+}
+
 /// Parse raw macro data into repeat count and structured events.
 ///
 /// Input `data` should be the full macro data (starting with 2-byte LE repeat count).
@@ -0,0 +1,236 @@
+//! Minimal interface for the vendor's mice
+//!
+//! Mice use the keyboard framing over the same wired and dongle transports,
+//! so battery and device ID come from the same places. DPI is per profile:
+//! a list of levels (separate X/Y values and an indicator color) and the
+//! index of the active one.
+
+use std::sync::Arc;
+
+use monsgeek_transport::protocol::{cmd, mouse as mouse_cmd};
+use monsgeek_transport::{ChecksumType, FlowControlTransport, Transport};
+
+use crate::error::KeyboardError;
+use crate::led::RgbColor;
+use crate::settings::BatteryInfo;
+
+/// Frame offsets of the DPI tables (response and request alike)
+const DPI_X_OFFSET: usize = 8;
+const DPI_Y_OFFSET: usize = 24;
+const DPI_COLOR_OFFSET: usize = 40;
+const DPI_FRAME_SIZE: usize = DPI_COLOR_OFFSET + mouse_cmd::MAX_DPI_LEVELS * 3;
+
+/// One DPI level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DpiLevel {
+    pub x: u16,
+    pub y: u16,
+    /// Indicator color shown while this level is active
+    pub color: RgbColor,
+}
+
+/// DPI levels of one profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DpiSettings {
+    /// Index of the active level
+    pub current: u8,
+    pub levels: Vec<DpiLevel>,
+}
+
+impl DpiSettings {
+    /// Parse a GET_DPI response (command echo at byte 0).
+    pub fn from_response(resp: &[u8]) -> Result<Self, KeyboardError> {
+        if resp.len() < DPI_FRAME_SIZE || resp[0] != mouse_cmd::GET_DPI {
+            return Err(KeyboardError::mismatch(
+                mouse_cmd::GET_DPI,
+                "Invalid DPI response",
+            ));
+        }
+        let count = (resp[3] as usize).min(mouse_cmd::MAX_DPI_LEVELS);
+        let word = |at: usize| u16::from_le_bytes([resp[at], resp[at + 1]]);
+        let levels = (0..count)
+            .map(|i| {
+                let c = DPI_COLOR_OFFSET + i * 3;
+                DpiLevel {
+                    x: word(DPI_X_OFFSET + i * 2),
+                    y: word(DPI_Y_OFFSET + i * 2),
+                    color: RgbColor::new(resp[c], resp[c + 1], resp[c + 2]),
+                }
+            })
+            .collect();
+        Ok(Self {
+            current: resp[2],
+            levels,
+        })
+    }
+
+    /// SET_DPI payload for `profile` (everything after the command byte).
+    pub fn to_payload(&self, profile: u8) -> Result<Vec<u8>, KeyboardError> {
+        if self.levels.is_empty() || self.levels.len() > mouse_cmd::MAX_DPI_LEVELS {
+            return Err(KeyboardError::InvalidParameter(format!(
+                "a profile holds 1-{} DPI levels, got {}",
+                mouse_cmd::MAX_DPI_LEVELS,
+                self.levels.len()
+            )));
+        }
+        if self.current as usize >= self.levels.len() {
+            return Err(KeyboardError::InvalidParameter(format!(
+                "DPI level {} out of range (0-{})",
+                self.current,
+                self.levels.len() - 1
+            )));
+        }
+        // Offsets are frame offsets; the payload starts after the command byte
+        let mut frame = [0u8; DPI_FRAME_SIZE];
+        frame[1] = profile;
+        frame[2] = self.current;
+        frame[3] = self.levels.len() as u8;
+        for (i, level) in self.levels.iter().enumerate() {
+            let x = DPI_X_OFFSET + i * 2;
+            frame[x..x + 2].copy_from_slice(&level.x.to_le_bytes());
+            let y = DPI_Y_OFFSET + i * 2;
+            frame[y..y + 2].copy_from_slice(&level.y.to_le_bytes());
+            let c = DPI_COLOR_OFFSET + i * 3;
+            frame[c..c + 3].copy_from_slice(&[level.color.r, level.color.g, level.color.b]);
+        }
+        Ok(frame[1..].to_vec())
+    }
+
+    /// The active level, if the index is in range
+    pub fn current_level(&self) -> Option<&DpiLevel> {
+        self.levels.get(self.current as usize)
+    }
+}
+
+/// High-level mouse interface using any transport
+pub struct MouseInterface {
+    transport: Arc<FlowControlTransport>,
+}
+
+impl MouseInterface {
+    pub fn new(transport: Arc<FlowControlTransport>) -> Self {
+        Self { transport }
+    }
+
+    /// Open a specific discovered device.
+    pub fn open_device(
+        device: &monsgeek_transport::DiscoveredDevice,
+    ) -> Result<Self, KeyboardError> {
+        let transport = monsgeek_transport::open_device_sync(device)?;
+        Ok(Self::new(transport))
+    }
+
+    /// Get the underlying transport
+    pub fn transport(&self) -> &Arc<FlowControlTransport> {
+        &self.transport
+    }
+
+    /// Get device ID (GET_USB_VERSION, as on keyboards)
+    pub fn get_device_id(&self) -> Result<u32, KeyboardError> {
+        let resp = self
+            .transport
+            .query_command(cmd::GET_USB_VERSION, &[], ChecksumType::Bit7)?;
+        if resp.len() < 5 || resp[0] != cmd::GET_USB_VERSION {
+            return Err(KeyboardError::mismatch(
+                cmd::GET_USB_VERSION,
+                "Invalid device ID response",
+            ));
+        }
+        Ok(u32::from_le_bytes([resp[1], resp[2], resp[3], resp[4]]))
+    }
+
+    /// Get battery info (dongle/wireless only; wired reports full)
+    pub fn get_battery(&self) -> Result<BatteryInfo, KeyboardError> {
+        let (level, online, idle) = self.transport.get_battery_status()?;
+        Ok(BatteryInfo {
+            level,
+            online,
+            charging: false, // Not available via dongle protocol
+            idle,
+        })
+    }
+
+    /// Get the DPI levels of `profile`
+    pub fn get_dpi(&self, profile: u8) -> Result<DpiSettings, KeyboardError> {
+        let resp =
+            self.transport
+                .query_command(mouse_cmd::GET_DPI, &[profile], ChecksumType::Bit7)?;
+        DpiSettings::from_response(&resp)
+    }
+
+    /// Replace the DPI levels of `profile`
+    pub fn set_dpi(&self, profile: u8, dpi: &DpiSettings) -> Result<(), KeyboardError> {
+        let payload = dpi.to_payload(profile)?;
+        self.transport
+            .send_command(mouse_cmd::SET_DPI, &payload, ChecksumType::Bit7)?;
+        Ok(())
+    }
+
+    /// Switch `profile` to DPI level `index`, keeping the levels themselves
+    pub fn set_dpi_level(&self, profile: u8, index: u8) -> Result<(), KeyboardError> {
+        let mut dpi = self.get_dpi(profile)?;
+        dpi.current = index;
+        self.set_dpi(profile, &dpi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(current: u8, levels: &[(u16, u16, [u8; 3])]) -> Vec<u8> {
+        let mut resp = vec![0u8; 64];
+        resp[0] = mouse_cmd::GET_DPI;
+        resp[2] = current;
+        resp[3] = levels.len() as u8;
+        for (i, (x, y, rgb)) in levels.iter().enumerate() {
+            resp[8 + i * 2..10 + i * 2].copy_from_slice(&x.to_le_bytes());
+            resp[24 + i * 2..26 + i * 2].copy_from_slice(&y.to_le_bytes());
+            resp[40 + i * 3..43 + i * 3].copy_from_slice(rgb);
+        }
+        resp
+    }
+
+    #[test]
+    fn dpi_round_trips_through_the_wire_layout() {
+        let resp = response(1, &[(800, 800, [255, 0, 0]), (1600, 1200, [0, 0, 255])]);
+        let dpi = DpiSettings::from_response(&resp).unwrap();
+        assert_eq!(dpi.current, 1);
+        assert_eq!(dpi.levels.len(), 2);
+        assert_eq!(
+            dpi.current_level(),
+            Some(&DpiLevel {
+                x: 1600,
+                y: 1200,
+                color: RgbColor::new(0, 0, 255),
+            })
+        );
+
+        // The request carries the same layout, minus the command byte
+        let payload = dpi.to_payload(2).unwrap();
+        assert_eq!(payload[0], 2);
+        assert_eq!(&payload[1..], &resp[2..DPI_FRAME_SIZE]);
+    }
+
+    #[test]
+    fn dpi_rejects_bad_tables() {
+        let mut resp = response(0, &[(800, 800, [0; 3])]);
+        resp[0] = cmd::GET_USB_VERSION;
+        assert!(DpiSettings::from_response(&resp).is_err());
+
+        let dpi = DpiSettings {
+            current: 1,
+            levels: vec![DpiLevel {
+                x: 800,
+                y: 800,
+                color: RgbColor::default(),
+            }],
+        };
+        assert!(dpi.to_payload(0).is_err());
+        let empty = DpiSettings {
+            current: 0,
+            levels: Vec::new(),
+        };
+        assert!(empty.to_payload(0).is_err());
+    }
+}
@@ -33,13 +33,16 @@ impl Precision {
     /// Older firmware doesn't support feature list, so precision is
     /// inferred from version number thresholds.
     pub fn from_firmware_version(version: u16) -> Self {
-        use monsgeek_transport::protocol::precision;
-        if version >= precision::FINE_VERSION {
-            Self::Fine // 0.005mm
-        } else if version >= precision::MEDIUM_VERSION {
-            Self::Medium // 0.01mm
-        } else {
-            Self::Coarse // 0.1mm
+        let quirks = monsgeek_transport::Quirks::for_firmware(None, version);
+        Self::from_steps_per_mm(quirks.travel_steps_per_mm)
+    }
+
+    /// Create from the travel step count of a firmware's quirks
+    pub fn from_steps_per_mm(steps: u16) -> Self {
+        match steps {
+            200.. => Self::Fine,   // 0.005mm
+            100.. => Self::Medium, // 0.01mm
+            _ => Self::Coarse,     // 0.1mm
         }
     }
 
@@ -384,3 +387,39 @@ impl FeatureList {
         self.precision().map(|p| p.factor()).unwrap_or(10.0) // Default to coarse if invalid
     }
 }
+
+/// Hardware a model has, from the device database.
+///
+/// A flag is `None` when the database doesn't say. Only a known absence
+/// (`Some(false)`, or a rate above `max_polling_hz`) keeps
+/// [`KeyboardInterface`](crate::KeyboardInterface) from sending a command, so
+/// boards the database doesn't describe keep working as before.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeviceCapabilities {
+    /// Side light strip (GET/SET_SLEDPARAM)
+    pub has_side_led: Option<bool>,
+    /// Per-key lighting (userpic slots)
+    pub has_key_leds: Option<bool>,
+    /// Rotary knob or dial
+    pub has_knob: Option<bool>,
+    /// Screen features (SET_SCREEN_COLOR, screen sync mode)
+    pub has_screen: Option<bool>,
+    /// Hall-effect switches (key depth, triggers, calibration)
+    pub has_magnetism: Option<bool>,
+    /// Highest polling rate the model accepts, in Hz
+    pub max_polling_hz: Option<u16>,
+    /// 2.4 GHz or Bluetooth capable (sleep timers)
+    pub wireless: Option<bool>,
+}
+
+impl DeviceCapabilities {
+    /// Whether the database rules this feature out
+    pub fn lacks(flag: Option<bool>) -> bool {
+        flag == Some(false)
+    }
+
+    /// Whether `hz` is above the model's maximum
+    pub fn exceeds_polling(&self, hz: u16) -> bool {
+        self.max_polling_hz.is_some_and(|max| hz > max)
+    }
+}
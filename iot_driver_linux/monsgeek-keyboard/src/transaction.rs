@@ -0,0 +1,688 @@
+//! All-or-nothing application of several keyboard settings.
+//!
+//! [`SettingsTransaction`] stages changes, snapshots the current value of
+//! everything it is about to touch, then writes in a fixed order (profile
+//! first, since every other setting is stored per profile; triggers last,
+//! since they need the longest settle time). The profile switch happens
+//! before the other values are snapshotted, so they are read from the
+//! profile being written. Each step is read back; on the first write or
+//! verify failure every already-applied step is restored from the snapshot
+//! in reverse order, which leaves the profile switch-back for last.
+
+use std::time::Duration;
+
+use crate::error::KeyboardError;
+use crate::led::LedParams;
+use crate::magnetism::ModeByte;
+use crate::settings::{KeyboardOptions, PollingRate, SleepTimeSettings};
+use crate::{KeyboardInterface, PROFILE_SETTLE_MS};
+use monsgeek_transport::protocol::magnetism as mag_cmd;
+
+/// One stageable setting, in application order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SettingsStep {
+    Profile,
+    PollingRate,
+    Debounce,
+    SleepTime,
+    Options,
+    Led,
+    Triggers,
+}
+
+impl SettingsStep {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SettingsStep::Profile => "profile",
+            SettingsStep::PollingRate => "polling rate",
+            SettingsStep::Debounce => "debounce",
+            SettingsStep::SleepTime => "sleep time",
+            SettingsStep::Options => "keyboard options",
+            SettingsStep::Led => "LED",
+            SettingsStep::Triggers => "triggers",
+        }
+    }
+}
+
+impl std::fmt::Display for SettingsStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Bulk trigger changes applied uniformly to every key (raw travel units).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TriggerChanges {
+    pub actuation: Option<u16>,
+    pub release: Option<u16>,
+    pub rt_press: Option<u16>,
+    pub rt_lift: Option<u16>,
+    pub bottom_deadzone: Option<u16>,
+    pub top_deadzone: Option<u16>,
+    /// Set or clear the Rapid-Trigger flag on every key, keeping base modes.
+    pub rapid_trigger: Option<bool>,
+}
+
+impl TriggerChanges {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Pre-transaction values used for verification and rollback.
+#[derive(Default)]
+struct Snapshot {
+    profile: Option<u8>,
+    polling_rate: Option<PollingRate>,
+    debounce: Option<u8>,
+    sleep: Option<SleepTimeSettings>,
+    options: Option<KeyboardOptions>,
+    led: Option<LedParams>,
+    triggers: Option<crate::TriggerSettings>,
+}
+
+/// Builder that stages settings and applies them as one unit.
+///
+/// ```ignore
+/// kb.transaction()
+///     .debounce(2)
+///     .led(params)
+///     .actuation_all(120)
+///     .apply()?;
+/// ```
+#[must_use = "a transaction does nothing until apply() is called"]
+pub struct SettingsTransaction<'a> {
+    kb: &'a KeyboardInterface,
+    profile: Option<u8>,
+    polling_rate: Option<PollingRate>,
+    debounce: Option<u8>,
+    sleep: Option<SleepTimeSettings>,
+    options: Option<KeyboardOptions>,
+    led: Option<LedParams>,
+    triggers: TriggerChanges,
+    /// Full per-key tables, written before the bulk `triggers` changes.
+    trigger_table: Option<crate::TriggerSettings>,
+    verify: bool,
+}
+
+impl<'a> SettingsTransaction<'a> {
+    pub(crate) fn new(kb: &'a KeyboardInterface) -> Self {
+        Self {
+            kb,
+            profile: None,
+            polling_rate: None,
+            debounce: None,
+            sleep: None,
+            options: None,
+            led: None,
+            triggers: TriggerChanges::default(),
+            trigger_table: None,
+            verify: true,
+        }
+    }
+
+    /// Switch to `profile` before applying everything else.
+    pub fn profile(mut self, profile: u8) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub fn polling_rate(mut self, rate: PollingRate) -> Self {
+        self.polling_rate = Some(rate);
+        self
+    }
+
+    pub fn debounce(mut self, ms: u8) -> Self {
+        self.debounce = Some(ms);
+        self
+    }
+
+    pub fn sleep_time(mut self, settings: SleepTimeSettings) -> Self {
+        self.sleep = Some(settings);
+        self
+    }
+
+    pub fn options(mut self, options: KeyboardOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    pub fn led(mut self, params: LedParams) -> Self {
+        self.led = Some(params);
+        self
+    }
+
+    pub fn actuation_all(mut self, travel: u16) -> Self {
+        self.triggers.actuation = Some(travel);
+        self
+    }
+
+    pub fn release_all(mut self, travel: u16) -> Self {
+        self.triggers.release = Some(travel);
+        self
+    }
+
+    pub fn rt_press_all(mut self, sensitivity: u16) -> Self {
+        self.triggers.rt_press = Some(sensitivity);
+        self
+    }
+
+    pub fn rt_lift_all(mut self, sensitivity: u16) -> Self {
+        self.triggers.rt_lift = Some(sensitivity);
+        self
+    }
+
+    pub fn bottom_deadzone_all(mut self, travel: u16) -> Self {
+        self.triggers.bottom_deadzone = Some(travel);
+        self
+    }
+
+    pub fn top_deadzone_all(mut self, travel: u16) -> Self {
+        self.triggers.top_deadzone = Some(travel);
+        self
+    }
+
+    pub fn rapid_trigger_all(mut self, enable: bool) -> Self {
+        self.triggers.rapid_trigger = Some(enable);
+        self
+    }
+
+    /// Write these per-key trigger tables (as read by `get_all_triggers`).
+    /// Empty deadzone tables and a short `key_modes` are left alone.
+    pub fn trigger_table(mut self, table: crate::TriggerSettings) -> Self {
+        self.trigger_table = Some(table);
+        self
+    }
+
+    /// Skip the read-back after each step (rollback still covers write errors).
+    pub fn without_verify(mut self) -> Self {
+        self.verify = false;
+        self
+    }
+
+    /// Steps that have something staged, in application order.
+    pub fn staged_steps(&self) -> Vec<SettingsStep> {
+        let mut steps = Vec::new();
+        if self.profile.is_some() {
+            steps.push(SettingsStep::Profile);
+        }
+        if self.polling_rate.is_some() {
+            steps.push(SettingsStep::PollingRate);
+        }
+        if self.debounce.is_some() {
+            steps.push(SettingsStep::Debounce);
+        }
+        if self.sleep.is_some() {
+            steps.push(SettingsStep::SleepTime);
+        }
+        if self.options.is_some() {
+            steps.push(SettingsStep::Options);
+        }
+        if self.led.is_some() {
+            steps.push(SettingsStep::Led);
+        }
+        if !self.triggers.is_empty() || self.trigger_table.is_some() {
+            steps.push(SettingsStep::Triggers);
+        }
+        steps
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.staged_steps().is_empty()
+    }
+
+    /// Snapshot, apply, verify; roll back on the first failure.
+    ///
+    /// Returns the steps that were applied. On failure the error is
+    /// [`KeyboardError::Transaction`], which records whether the rollback
+    /// restored every applied step.
+    pub fn apply(self) -> Result<Vec<SettingsStep>, KeyboardError> {
+        let steps = self.staged_steps();
+        if steps.is_empty() {
+            return Ok(steps);
+        }
+
+        let mut snapshot = Snapshot::default();
+        let mut applied = Vec::new();
+        let mut rest = steps.as_slice();
+
+        // Every other setting is stored per profile, so switch first and
+        // snapshot them from the profile they are about to be written to.
+        if let Some((&SettingsStep::Profile, tail)) = steps.split_first() {
+            self.snapshot(SettingsStep::Profile, &mut snapshot)
+                .map_err(|e| unreadable(SettingsStep::Profile, e, true))?;
+            self.run_step(SettingsStep::Profile, &mut applied, &snapshot)?;
+            rest = tail;
+        }
+
+        // If a value can't be read we can't promise a rollback, so refuse
+        // before writing anything else.
+        for &step in rest {
+            if let Err(e) = self.snapshot(step, &mut snapshot) {
+                let rolled_back = self.rollback(&applied, &snapshot);
+                return Err(unreadable(step, e, rolled_back));
+            }
+        }
+
+        for &step in rest {
+            self.run_step(step, &mut applied, &snapshot)?;
+        }
+        Ok(applied)
+    }
+
+    /// Write and verify one step; on failure roll back everything applied so
+    /// far, including this step.
+    fn run_step(
+        &self,
+        step: SettingsStep,
+        applied: &mut Vec<SettingsStep>,
+        snapshot: &Snapshot,
+    ) -> Result<(), KeyboardError> {
+        let result = self.write(step).and_then(|()| {
+            if self.verify {
+                self.check(step)
+            } else {
+                Ok(())
+            }
+        });
+        // The step may have partially landed even if it errored.
+        applied.push(step);
+        result.map_err(|e| KeyboardError::Transaction {
+            step: step.as_str(),
+            reason: e.to_string(),
+            rolled_back: self.rollback(applied, snapshot),
+        })
+    }
+
+    fn snapshot(&self, step: SettingsStep, snap: &mut Snapshot) -> Result<(), KeyboardError> {
+        let kb = self.kb;
+        match step {
+            SettingsStep::Profile => snap.profile = Some(kb.get_profile()?),
+            SettingsStep::PollingRate => snap.polling_rate = Some(kb.get_polling_rate()?),
+            SettingsStep::Debounce => snap.debounce = Some(kb.get_debounce()?),
+            SettingsStep::SleepTime => snap.sleep = Some(kb.get_sleep_time()?),
+            SettingsStep::Options => snap.options = Some(kb.get_kb_options()?),
+            SettingsStep::Led => snap.led = Some(kb.get_led_params()?),
+            SettingsStep::Triggers => snap.triggers = Some(kb.get_all_triggers()?),
+        }
+        Ok(())
+    }
+
+    fn write(&self, step: SettingsStep) -> Result<(), KeyboardError> {
+        let kb = self.kb;
+        match step {
+            SettingsStep::Profile => {
+                kb.set_profile(self.profile.unwrap_or_default())?;
+                std::thread::sleep(Duration::from_millis(PROFILE_SETTLE_MS));
+            }
+            SettingsStep::PollingRate => {
+                if let Some(rate) = self.polling_rate {
+                    kb.set_polling_rate(rate)?;
+                }
+            }
+            SettingsStep::Debounce => kb.set_debounce(self.debounce.unwrap_or_default())?,
+            SettingsStep::SleepTime => {
+                if let Some(sleep) = &self.sleep {
+                    kb.set_sleep_time(sleep)?;
+                }
+            }
+            SettingsStep::Options => {
+                if let Some(options) = &self.options {
+                    kb.set_kb_options(options)?;
+                }
+            }
+            SettingsStep::Led => {
+                if let Some(led) = &self.led {
+                    kb.set_led_params(led)?;
+                }
+            }
+            SettingsStep::Triggers => {
+                if let Some(table) = &self.trigger_table {
+                    write_trigger_table(kb, table)?;
+                }
+                let t = &self.triggers;
+                if let Some(v) = t.actuation {
+                    kb.set_actuation_all_u16(v)?;
+                }
+                if let Some(v) = t.release {
+                    kb.set_release_all_u16(v)?;
+                }
+                if let Some(v) = t.rt_press {
+                    kb.set_rt_press_all_u16(v)?;
+                }
+                if let Some(v) = t.rt_lift {
+                    kb.set_rt_lift_all_u16(v)?;
+                }
+                if let Some(v) = t.bottom_deadzone {
+                    kb.set_bottom_deadzone_all_u16(v)?;
+                }
+                if let Some(v) = t.top_deadzone {
+                    kb.set_top_deadzone_all_u16(v)?;
+                }
+                if let Some(enable) = t.rapid_trigger {
+                    kb.set_rapid_trigger_all(enable)?;
+                }
+                std::thread::sleep(Duration::from_millis(crate::MAGNETISM_SETTLE_MS));
+            }
+        }
+        Ok(())
+    }
+
+    fn check(&self, step: SettingsStep) -> Result<(), KeyboardError> {
+        let kb = self.kb;
+        let mismatch = |what: &str| -> Result<(), KeyboardError> {
+            Err(KeyboardError::ProtocolMismatch {
+                cmd: None,
+                reason: format!("{what} read back differently after write"),
+            })
+        };
+        match step {
+            SettingsStep::Profile => {
+                if Some(kb.get_profile()?) != self.profile {
+                    return mismatch("profile");
+                }
+            }
+            SettingsStep::PollingRate => {
+                if Some(kb.get_polling_rate()?) != self.polling_rate {
+                    return mismatch("polling rate");
+                }
+            }
+            SettingsStep::Debounce => {
+                if Some(kb.get_debounce()?) != self.debounce {
+                    return mismatch("debounce");
+                }
+            }
+            SettingsStep::SleepTime => {
+                if Some(kb.get_sleep_time()?) != self.sleep {
+                    return mismatch("sleep time");
+                }
+            }
+            SettingsStep::Options => {
+                let want = self.options.as_ref().map(|o| o.to_bytes());
+                if Some(kb.get_kb_options()?.to_bytes()) != want {
+                    return mismatch("keyboard options");
+                }
+            }
+            SettingsStep::Led => {
+                let got = kb.get_led_params()?;
+                if let Some(want) = &self.led {
+                    if got.mode != want.mode
+                        || got.brightness != want.brightness
+                        || got.color != want.color
+                    {
+                        return mismatch("LED parameters");
+                    }
+                }
+            }
+            SettingsStep::Triggers => {
+                let got = kb.get_all_triggers()?;
+                let t = &self.triggers;
+                let all = |values: &[u16], want: Option<u16>| {
+                    want.is_none_or(|w| values.iter().all(|&v| v == w))
+                };
+                let rt_ok = t.rapid_trigger.is_none_or(|enable| {
+                    got.key_modes
+                        .iter()
+                        .all(|&m| (m & ModeByte::RT_FLAG != 0) == enable)
+                });
+                let table_ok = self.trigger_table.as_ref().is_none_or(|want| {
+                    let same = |got: &[u16], want: &[u16]| want.is_empty() || got == want;
+                    same(&got.press_travel, &want.press_travel)
+                        && same(&got.lift_travel, &want.lift_travel)
+                        && same(&got.rt_press, &want.rt_press)
+                        && same(&got.rt_lift, &want.rt_lift)
+                        && same(&got.bottom_deadzone, &want.bottom_deadzone)
+                        && same(&got.top_deadzone, &want.top_deadzone)
+                        && (want.key_modes.len() < kb.key_count() as usize
+                            || got.key_modes == want.key_modes)
+                });
+                if !(table_ok
+                    && all(&got.press_travel, t.actuation)
+                    && all(&got.lift_travel, t.release)
+                    && all(&got.rt_press, t.rt_press)
+                    && all(&got.rt_lift, t.rt_lift)
+                    && all(&got.bottom_deadzone, t.bottom_deadzone)
+                    && all(&got.top_deadzone, t.top_deadzone)
+                    && rt_ok)
+                {
+                    return mismatch("trigger table");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Restore `applied` steps from `snap` in reverse order, so per-profile
+    /// values go back into the profile they came from before the profile
+    /// itself is switched back. Returns true if every restore write
+    /// succeeded.
+    fn rollback(&self, applied: &[SettingsStep], snap: &Snapshot) -> bool {
+        let kb = self.kb;
+        let mut ok = true;
+        for &step in applied.iter().rev() {
+            let result = match step {
+                SettingsStep::Profile => snap.profile.map_or(Ok(()), |p| kb.set_profile(p)),
+                SettingsStep::PollingRate => {
+                    snap.polling_rate.map_or(Ok(()), |r| kb.set_polling_rate(r))
+                }
+                SettingsStep::Debounce => snap.debounce.map_or(Ok(()), |d| kb.set_debounce(d)),
+                SettingsStep::SleepTime => {
+                    snap.sleep.as_ref().map_or(Ok(()), |s| kb.set_sleep_time(s))
+                }
+                SettingsStep::Options => snap
+                    .options
+                    .as_ref()
+                    .map_or(Ok(()), |o| kb.set_kb_options(o)),
+                SettingsStep::Led => snap.led.as_ref().map_or(Ok(()), |l| kb.set_led_params(l)),
+                SettingsStep::Triggers => snap
+                    .triggers
+                    .as_ref()
+                    .map_or(Ok(()), |t| write_trigger_table(kb, t)),
+            };
+            if let Err(e) = result {
+                tracing::warn!("rollback of {step} failed: {e}");
+                ok = false;
+            }
+        }
+        ok
+    }
+}
+
+/// Transaction error for a value that couldn't be snapshotted.
+fn unreadable(step: SettingsStep, e: KeyboardError, rolled_back: bool) -> KeyboardError {
+    KeyboardError::Transaction {
+        step: step.as_str(),
+        reason: format!("could not read current value: {e}"),
+        rolled_back,
+    }
+}
+
+/// Write a full per-key trigger table (the bulk setters only take one value
+/// for every key, so tables and rollback go through the paged writers).
+fn write_trigger_table(
+    kb: &KeyboardInterface,
+    t: &crate::TriggerSettings,
+) -> Result<(), KeyboardError> {
+    kb.set_magnetism_u16(mag_cmd::PRESS_TRAVEL, &t.press_travel)?;
+    kb.set_magnetism_u16(mag_cmd::LIFT_TRAVEL, &t.lift_travel)?;
+    kb.set_magnetism_u16(mag_cmd::RT_PRESS, &t.rt_press)?;
+    kb.set_magnetism_u16(mag_cmd::RT_LIFT, &t.rt_lift)?;
+    if !t.bottom_deadzone.is_empty() {
+        kb.set_magnetism_u16(mag_cmd::BOTTOM_DEADZONE, &t.bottom_deadzone)?;
+    }
+    if !t.top_deadzone.is_empty() {
+        kb.set_magnetism_u16(mag_cmd::TOP_DEADZONE, &t.top_deadzone)?;
+    }
+    if t.key_modes.len() >= kb.key_count() as usize {
+        kb.set_magnetism_u8(mag_cmd::KEY_MODE, &t.key_modes)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    use monsgeek_transport::protocol::{ProtocolFamily, RY5088_COMMANDS};
+    use monsgeek_transport::{
+        ChecksumType, FlowControlTransport, Transport, TransportDeviceInfo, TransportError,
+        TransportType, VendorEvent,
+    };
+
+    /// Device with per-profile debounce and options, which refuses the
+    /// first options write.
+    struct FakeDevice {
+        info: TransportDeviceInfo,
+        state: Mutex<FakeState>,
+    }
+
+    struct FakeState {
+        profile: usize,
+        debounce: [u8; 4],
+        options: [[u8; 8]; 4],
+        fail_options_write: bool,
+        responses: VecDeque<Vec<u8>>,
+    }
+
+    impl FakeDevice {
+        fn new(debounce: [u8; 4]) -> Self {
+            Self {
+                info: TransportDeviceInfo {
+                    vid: 0x3151,
+                    pid: 0x5030,
+                    is_dongle: false,
+                    transport_type: TransportType::HidWired,
+                    device_path: "fake".into(),
+                    serial: None,
+                    product_name: None,
+                },
+                state: Mutex::new(FakeState {
+                    profile: 0,
+                    debounce,
+                    options: [[0; 8]; 4],
+                    fail_options_write: true,
+                    responses: VecDeque::new(),
+                }),
+            }
+        }
+    }
+
+    impl Transport for FakeDevice {
+        fn send_report(
+            &self,
+            cmd: u8,
+            data: &[u8],
+            _checksum: ChecksumType,
+        ) -> Result<(), TransportError> {
+            let c = &RY5088_COMMANDS;
+            let mut s = self.state.lock().unwrap();
+            let p = s.profile;
+            let mut resp = vec![cmd];
+            if cmd == c.set_profile {
+                s.profile = data[0] as usize;
+                return Ok(());
+            } else if cmd == c.set_debounce {
+                s.debounce[p] = data[0];
+                return Ok(());
+            } else if Some(cmd) == c.set_kboption {
+                if std::mem::take(&mut s.fail_options_write) {
+                    return Err(TransportError::Timeout { cmd: Some(cmd) });
+                }
+                s.options[p].copy_from_slice(&data[..8]);
+                return Ok(());
+            } else if cmd == c.get_profile {
+                resp.push(p as u8);
+            } else if cmd == c.get_debounce {
+                resp.push(s.debounce[p]);
+            } else if Some(cmd) == c.get_kboption {
+                resp.extend_from_slice(&s.options[p]);
+            }
+            resp.resize(64, 0);
+            s.responses.push_back(resp);
+            Ok(())
+        }
+
+        fn read_report(&self) -> Result<Vec<u8>, TransportError> {
+            let mut s = self.state.lock().unwrap();
+            s.responses
+                .pop_front()
+                .ok_or(TransportError::Timeout { cmd: None })
+        }
+
+        fn read_event(&self, _timeout_ms: u32) -> Result<Option<VendorEvent>, TransportError> {
+            Ok(None)
+        }
+
+        fn device_info(&self) -> &TransportDeviceInfo {
+            &self.info
+        }
+
+        fn is_connected(&self) -> bool {
+            true
+        }
+
+        fn close(&self) -> Result<(), TransportError> {
+            Ok(())
+        }
+
+        fn get_battery_status(&self) -> Result<(u8, bool, bool), TransportError> {
+            Ok((100, false, false))
+        }
+    }
+
+    #[test]
+    fn failed_apply_restores_the_target_profile() {
+        let device = Arc::new(FakeDevice::new([1, 3, 5, 7]));
+        let flow = Arc::new(FlowControlTransport::new(device.clone()));
+        let kb = KeyboardInterface::new(flow, 98, true, ProtocolFamily::Ry5088);
+
+        let err = kb
+            .transaction()
+            .profile(1)
+            .debounce(9)
+            .options(KeyboardOptions {
+                wasd_swap: true,
+                ..Default::default()
+            })
+            .apply()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            KeyboardError::Transaction {
+                step: "keyboard options",
+                rolled_back: true,
+                ..
+            }
+        ));
+
+        let s = device.state.lock().unwrap();
+        assert_eq!(s.profile, 0);
+        assert_eq!(s.debounce, [1, 3, 5, 7]);
+        assert_eq!(s.options, [[0; 8]; 4]);
+    }
+
+    #[test]
+    fn steps_are_ordered_profile_first_triggers_last() {
+        let mut steps = [
+            SettingsStep::Triggers,
+            SettingsStep::Led,
+            SettingsStep::Profile,
+            SettingsStep::Debounce,
+        ];
+        steps.sort();
+        assert_eq!(steps.first(), Some(&SettingsStep::Profile));
+        assert_eq!(steps.last(), Some(&SettingsStep::Triggers));
+    }
+
+    #[test]
+    fn empty_trigger_changes() {
+        assert!(TriggerChanges::default().is_empty());
+        let t = TriggerChanges {
+            rapid_trigger: Some(false),
+            ..Default::default()
+        };
+        assert!(!t.is_empty());
+    }
+}
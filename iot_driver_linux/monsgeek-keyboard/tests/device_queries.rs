@@ -6,7 +6,9 @@
 use std::sync::Arc;
 use std::time::Duration;
 
-use monsgeek_keyboard::{KeyboardInterface, PollingRate, Precision};
+use monsgeek_keyboard::{
+    KeyboardError, KeyboardInterface, PollingRate, Precision, SleepTimeSettings,
+};
 use monsgeek_transport::{ChecksumType, FlowControlTransport, HidDiscovery, Transport};
 
 /// Open the preferred keyboard and create a KeyboardInterface.
@@ -25,13 +27,12 @@ fn open_keyboard() -> (Arc<dyn Transport>, KeyboardInterface) {
         _ => (98, true),
     };
 
+    // No device database here, so YiChip boards (MOD007B HE on 0x4035) are
+    // told apart by PID alone
+    let protocol = monsgeek_transport::protocol::ProtocolFamily::detect(None, info.pid);
+
     let flow = Arc::new(FlowControlTransport::new(Arc::clone(&transport)));
-    let kb = KeyboardInterface::new(
-        flow,
-        key_count,
-        has_magnetism,
-        monsgeek_transport::protocol::ProtocolFamily::default(),
-    );
+    let kb = KeyboardInterface::new(flow, key_count, has_magnetism, protocol);
     (transport, kb)
 }
 
@@ -467,3 +468,109 @@ fn macro_set_and_readback() {
         Err(e) => eprintln!("  Read error: {e}"),
     }
 }
+
+/// Settings transaction: re-apply the current debounce and LED params as one
+/// unit, then change debounce and verify it landed and can be restored.
+#[test]
+#[ignore] // requires hardware
+fn settings_transaction_roundtrip() {
+    let (_raw, kb) = open_keyboard();
+
+    let debounce = kb.get_debounce().expect("get_debounce failed");
+    let led = kb.get_led_params().expect("get_led_params failed");
+
+    let applied = kb
+        .transaction()
+        .debounce(debounce)
+        .led(led.clone())
+        .apply()
+        .expect("no-op transaction failed");
+    eprintln!("applied: {applied:?}");
+    assert_eq!(applied.len(), 2);
+
+    let other = if debounce == 5 { 4 } else { 5 };
+    kb.transaction()
+        .debounce(other)
+        .apply()
+        .expect("debounce transaction failed");
+    assert_eq!(kb.get_debounce().unwrap(), other);
+
+    kb.transaction()
+        .debounce(debounce)
+        .apply()
+        .expect("restore transaction failed");
+    assert_eq!(kb.get_debounce().unwrap(), debounce);
+}
+
+/// Profile targeting: read another profile's trigger table and confirm the
+/// active profile is unchanged afterwards.
+#[test]
+#[ignore] // requires hardware
+fn with_profile_restores_active() {
+    let (_raw, kb) = open_keyboard();
+
+    let active = kb.get_profile().expect("get_profile failed");
+    let other = (active + 1) % 4;
+
+    let seen = kb
+        .with_profile(other, |kb| kb.get_profile())
+        .expect("with_profile failed");
+    assert_eq!(seen, other);
+
+    let triggers = kb
+        .with_profile(other, |kb| kb.get_all_triggers())
+        .expect("get_all_triggers in other profile failed");
+    eprintln!(
+        "profile {other}: key 0 actuation = {}",
+        triggers.press_travel[0]
+    );
+
+    assert_eq!(kb.get_profile().unwrap(), active);
+}
+
+/// Keep-awake: on wireless, sleep is disabled while the guard lives and the
+/// original timeouts come back afterwards. On USB the guard is inert.
+#[test]
+#[ignore] // requires hardware
+fn keep_awake_restores_sleep_time() {
+    let (_raw, kb) = open_keyboard();
+
+    let before = kb.get_sleep_time().ok();
+    {
+        let guard = kb.keep_awake().expect("keep_awake failed");
+        eprintln!("wireless={} active={}", kb.is_wireless(), guard.is_active());
+        if guard.is_active() {
+            let during = kb.get_sleep_time().expect("get_sleep_time failed");
+            assert_eq!(during, SleepTimeSettings::uniform(0, 0));
+        }
+    }
+    assert_eq!(kb.get_sleep_time().ok(), before);
+}
+
+/// Battery and sleep over the connection in use: every board reports a
+/// battery, while sleep time is missing on YiChip boards (MOD007B HE) and
+/// must come back as unsupported rather than a garbled reply.
+#[test]
+#[ignore] // requires hardware
+fn battery_and_sleep_paths() {
+    let (raw, kb) = open_keyboard();
+    let info = raw.device_info();
+    eprintln!(
+        "{:04x}:{:04x} wireless={}",
+        info.vid,
+        info.pid,
+        kb.is_wireless()
+    );
+
+    let battery = kb.get_battery().expect("get_battery failed");
+    eprintln!("battery: {battery:?}");
+    assert!(battery.level <= 100);
+
+    match kb.get_sleep_time() {
+        Ok(sleep) => eprintln!("sleep: {sleep:?}"),
+        Err(KeyboardError::NotSupported { .. }) => {
+            eprintln!("sleep: not supported on this board")
+        }
+        Err(e) => panic!("get_sleep_time failed: {e}"),
+    }
+}
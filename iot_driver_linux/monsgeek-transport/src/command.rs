@@ -1654,6 +1654,11 @@ pub enum ParsedResponse {
     RfInfo(RfInfoResponse),
     /// GET_DONGLE_ID (0xFD) response
     DongleId(DongleIdResponse),
+    /// FW_TRANSFER (0xBA) ack from the bootloader or RF-programming dongle
+    FwTransferAck {
+        subcmd: u8,
+        data: Vec<u8>,
+    },
     /// GET_PATCH_INFO (0xE7) response - patch name, version, capabilities
     PatchInfo {
         data: Vec<u8>,
@@ -1784,11 +1789,52 @@ pub enum ParsedCommand {
     SetCtrlByte {
         value: u8,
     },
-    EnterPairing,
+    /// ENTER_PAIRING (0xF8) - puts the dongle into PAN1082 programming mode
+    EnterPairing {
+        /// Payload carried the required 55AA55AA magic
+        magic_ok: bool,
+    },
     PairingCmd {
         action: u8,
         channel: u8,
     },
+    // Firmware update (RY bootloader, RF upgrade)
+    /// ISP_PREPARE (0xC5) - sent with param 0x3A before ENTER_BOOTLOADER
+    IspPrepare {
+        param: u8,
+    },
+    /// ENTER_BOOTLOADER (0x7F) - erases config and reboots into the bootloader
+    EnterBootloader {
+        /// Payload carried the required 55AA55AA magic
+        magic_ok: bool,
+    },
+    /// FW_TRANSFER START (0xBA 0xC0)
+    /// Format: [0xBA, 0xC0, chunks(2B LE), size(3B LE)]
+    FwTransferStart {
+        chunks: u16,
+        size: u32,
+    },
+    /// FW_TRANSFER COMPLETE (0xBA 0xC2)
+    /// Format: [0xBA, 0xC2, chunks(2B LE), checksum(4B LE), size(4B LE)]
+    FwTransferComplete {
+        chunks: u16,
+        checksum: u32,
+        size: u32,
+    },
+    /// FW_TRANSFER (0xBA) with a sub-command we don't decode yet
+    FwTransfer {
+        subcmd: u8,
+        data: Vec<u8>,
+    },
+    /// Raw 64-byte firmware chunk following FW_TRANSFER START.
+    ///
+    /// Chunks carry no command byte, so `try_parse_command` can't recognise
+    /// them; the Printer emits this while it is counting down a transfer.
+    FwChunk {
+        /// Zero-based chunk index
+        index: u16,
+        total: u16,
+    },
     // Animation engine (0xEA)
     AnimDefine {
         def_id: u8,
@@ -1946,6 +1992,10 @@ pub fn try_parse_response(data: &[u8]) -> ParsedResponse {
         cmd::GET_PATCH_INFO => ParsedResponse::PatchInfo {
             data: data[1..].to_vec(),
         },
+        cmd::FW_TRANSFER => ParsedResponse::FwTransferAck {
+            subcmd: data.get(1).copied().unwrap_or(0),
+            data: data.get(2..).unwrap_or(&[]).to_vec(),
+        },
         cmd::ANIM_CMD => {
             let sub = data.get(1).copied().unwrap_or(0);
             match sub {
@@ -2164,12 +2214,23 @@ pub fn try_parse_command(data: &[u8]) -> ParsedCommand {
         cmd::SET_CTRL_BYTE => ParsedCommand::SetCtrlByte {
             value: data.get(1).copied().unwrap_or(0),
         },
-        cmd::ENTER_PAIRING => ParsedCommand::EnterPairing,
+        cmd::ENTER_PAIRING => ParsedCommand::EnterPairing {
+            magic_ok: data.get(1..5) == Some(&protocol::fw_transfer::MAGIC[..]),
+        },
         cmd::PAIRING_CMD => ParsedCommand::PairingCmd {
             action: data.get(1).copied().unwrap_or(0),
             channel: data.get(2).copied().unwrap_or(0),
         },
 
+        // Firmware update
+        cmd::ISP_PREPARE => ParsedCommand::IspPrepare {
+            param: data.get(1).copied().unwrap_or(0),
+        },
+        cmd::ENTER_BOOTLOADER => ParsedCommand::EnterBootloader {
+            magic_ok: data.get(1..5) == Some(&protocol::fw_transfer::MAGIC[..]),
+        },
+        cmd::FW_TRANSFER => parse_fw_transfer_command(data),
+
         cmd::ANIM_CMD => {
             let sub = data.get(1).copied().unwrap_or(0);
             match sub {
@@ -2208,6 +2269,27 @@ pub fn try_parse_command(data: &[u8]) -> ParsedCommand {
     }
 }
 
+/// Parse FW_TRANSFER (0xBA) command by sub-command
+fn parse_fw_transfer_command(data: &[u8]) -> ParsedCommand {
+    let byte = |i: usize| data.get(i).copied().unwrap_or(0);
+    let chunks = u16::from_le_bytes([byte(2), byte(3)]);
+    match byte(1) {
+        protocol::fw_transfer::START if data.len() >= 7 => ParsedCommand::FwTransferStart {
+            chunks,
+            size: u32::from_le_bytes([byte(4), byte(5), byte(6), 0]),
+        },
+        protocol::fw_transfer::COMPLETE if data.len() >= 12 => ParsedCommand::FwTransferComplete {
+            chunks,
+            checksum: u32::from_le_bytes([byte(4), byte(5), byte(6), byte(7)]),
+            size: u32::from_le_bytes([byte(8), byte(9), byte(10), byte(11)]),
+        },
+        subcmd => ParsedCommand::FwTransfer {
+            subcmd,
+            data: data.get(2..).unwrap_or(&[]).to_vec(),
+        },
+    }
+}
+
 /// Parse LED params from command data
 /// Format: [cmd, mode, speed_inv, brightness, option, r, g, b]
 fn parse_led_params_command(data: &[u8]) -> Option<LedParamsResponse> {
@@ -2675,6 +2757,64 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_try_parse_command_pairing_and_bootloader() {
+        assert!(matches!(
+            try_parse_command(&[0xf8, 0x55, 0xaa, 0x55, 0xaa, 0x00, 0x00, 0x82]),
+            ParsedCommand::EnterPairing { magic_ok: true }
+        ));
+        assert!(matches!(
+            try_parse_command(&[0xf8, 0x00]),
+            ParsedCommand::EnterPairing { magic_ok: false }
+        ));
+        assert!(matches!(
+            try_parse_command(&[0x7f, 0x55, 0xaa, 0x55, 0xaa, 0x00, 0x00]),
+            ParsedCommand::EnterBootloader { magic_ok: true }
+        ));
+        assert!(matches!(
+            try_parse_command(&[0xc5, 0x3a]),
+            ParsedCommand::IspPrepare { param: 0x3a }
+        ));
+    }
+
+    #[test]
+    fn test_try_parse_command_fw_transfer() {
+        // 0x0c80 chunks, 0x032000 bytes
+        match try_parse_command(&[0xba, 0xc0, 0x80, 0x0c, 0x00, 0x20, 0x03]) {
+            ParsedCommand::FwTransferStart { chunks, size } => {
+                assert_eq!(chunks, 0x0c80);
+                assert_eq!(size, 0x032000);
+            }
+            other => panic!("Expected FwTransferStart, got {:?}", other),
+        }
+
+        let complete = [
+            0xba, 0xc2, 0x80, 0x0c, 0x78, 0x56, 0x34, 0x12, 0x00, 0x20, 0x03, 0x00,
+        ];
+        match try_parse_command(&complete) {
+            ParsedCommand::FwTransferComplete {
+                chunks,
+                checksum,
+                size,
+            } => {
+                assert_eq!(chunks, 0x0c80);
+                assert_eq!(checksum, 0x12345678);
+                assert_eq!(size, 0x032000);
+            }
+            other => panic!("Expected FwTransferComplete, got {:?}", other),
+        }
+
+        assert!(matches!(
+            try_parse_command(&[0xba, 0xff]),
+            ParsedCommand::FwTransfer { subcmd: 0xff, .. }
+        ));
+        // Truncated START falls back to the generic variant
+        assert!(matches!(
+            try_parse_command(&[0xba, 0xc0, 0x01]),
+            ParsedCommand::FwTransfer { subcmd: 0xc0, .. }
+        ));
+    }
+
     // =========================================================================
     // Typed Packet Struct Tests
     // =========================================================================
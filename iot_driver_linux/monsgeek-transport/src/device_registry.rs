@@ -1,8 +1,8 @@
 //! Device registry - transport type detection by PID
 //!
 //! This module provides centralized dongle PID detection for determining
-//! transport type. Device identity comes from firmware query (`get_device_id`),
-//! not from USB PID.
+//! transport type, and tells the vendor's mice apart from keyboards. Device
+//! identity comes from firmware query (`get_device_id`), not from USB PID.
 
 /// MonsGeek/Akko vendor ID
 pub const VENDOR_ID: u16 = 0x3151;
@@ -63,6 +63,100 @@ pub fn looks_like_dongle(product: Option<&str>) -> bool {
         .any(|needle| p.contains(needle))
 }
 
+/// PIDs the vendor database lists only for mice (wired or their own receiver)
+///
+/// RY6608 mice enumerate under 0x503A, which is also a keyboard dongle PID;
+/// only their device ID tells them apart.
+pub const MOUSE_PIDS: &[u16] = &[
+    0x4026, // PAN1080 mice (G63, IO1.1 Pro, ...)
+    0x402A, // PAN1080 mice (X3 8K, F9, ...)
+    0x5032, // PAN1076 mice
+    0x5043, // RY6601 mice
+];
+
+/// Check if PID belongs to a mouse
+#[inline]
+pub fn is_mouse_pid(pid: u16) -> bool {
+    MOUSE_PIDS.contains(&pid)
+}
+
+/// Heuristic: does this device's USB product string name a mouse?
+///
+/// Catches names like the BLE name "ROYUAN_Mouse" under PIDs not in [`MOUSE_PIDS`].
+pub fn looks_like_mouse(product: Option<&str>) -> bool {
+    product.is_some_and(|p| p.to_ascii_lowercase().contains("mouse"))
+}
+
+/// Kind of device behind a vendor interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeviceKind {
+    #[default]
+    Keyboard,
+    Mouse,
+}
+
+impl DeviceKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Keyboard => "keyboard",
+            Self::Mouse => "mouse",
+        }
+    }
+}
+
+/// Kind of device from its PID and USB product string.
+///
+/// A mouse on 0x503A looks like a keyboard here unless its product string
+/// says otherwise; callers with the device database refine this from the
+/// probed device ID.
+pub fn device_kind(pid: u16, product: Option<&str>) -> DeviceKind {
+    if is_mouse_pid(pid) || looks_like_mouse(product) {
+        DeviceKind::Mouse
+    } else {
+        DeviceKind::Keyboard
+    }
+}
+
+/// What a device in bootloader mode belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootModeKind {
+    Keyboard,
+    Dongle,
+    /// RF module, updated through the dongle
+    Rf,
+}
+
+impl BootModeKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Keyboard => "keyboard",
+            Self::Dongle => "dongle",
+            Self::Rf => "RF module",
+        }
+    }
+}
+
+/// Known bootloader (ISP mode) VID/PIDs
+///
+/// Devices re-enumerate under these IDs after ENTER_BOOTLOADER and stay there
+/// until a firmware transfer completes with a matching checksum.
+pub const BOOT_MODE_PIDS: &[(u16, u16, BootModeKind)] = &[
+    (0x3151, 0x502A, BootModeKind::Keyboard), // M1 V5 TMR bootloader
+    (0x3151, 0x5039, BootModeKind::Dongle),   // Dongle bootloader
+    (0x3141, 0x504A, BootModeKind::Keyboard), // Generic RY USB boot mode 1
+    (0x3141, 0x404A, BootModeKind::Keyboard), // Generic RY USB boot mode 2
+    (0x046A, 0x012E, BootModeKind::Rf),       // RF boot mode 1
+    (0x046A, 0x0130, BootModeKind::Rf),       // RF boot mode 2
+];
+
+/// Which device a VID/PID pair is the bootloader of, if any
+pub fn boot_mode_kind(vid: u16, pid: u16) -> Option<BootModeKind> {
+    BOOT_MODE_PIDS
+        .iter()
+        .find(|&&(v, p, _)| v == vid && p == pid)
+        .map(|&(_, _, kind)| kind)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -74,6 +168,15 @@ mod tests {
         assert!(is_dongle_pid(0x503D));
     }
 
+    #[test]
+    fn test_boot_mode_pids() {
+        assert_eq!(boot_mode_kind(0x3151, 0x502A), Some(BootModeKind::Keyboard));
+        assert_eq!(boot_mode_kind(0x3151, 0x5039), Some(BootModeKind::Dongle));
+        // Normal-mode keyboard and dongle
+        assert_eq!(boot_mode_kind(0x3151, 0x5030), None);
+        assert_eq!(boot_mode_kind(0x3151, 0x5038), None);
+    }
+
     #[test]
     fn test_wired_pids_not_dongle() {
         assert!(!is_dongle_pid(0x5030)); // M1 V5 wired
@@ -102,4 +205,20 @@ mod tests {
         assert!(!looks_like_dongle(Some("Monsgeek Multi-modes Keyboard")));
         assert!(!looks_like_dongle(None));
     }
+
+    #[test]
+    fn test_device_kind() {
+        assert_eq!(device_kind(0x4026, None), DeviceKind::Mouse);
+        assert_eq!(device_kind(0x503A, Some("ROYUAN_Mouse")), DeviceKind::Mouse);
+        // The shared receiver alone says nothing about what is paired to it
+        assert_eq!(
+            device_kind(0x503A, Some("MonsGeek 2.4G Wireless Keyboard")),
+            DeviceKind::Keyboard
+        );
+        assert_eq!(
+            device_kind(0x5030, Some("Monsgeek Keyboard")),
+            DeviceKind::Keyboard
+        );
+        assert!(!MOUSE_PIDS.iter().any(|&pid| is_dongle_pid(pid)));
+    }
 }
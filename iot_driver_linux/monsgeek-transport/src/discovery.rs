@@ -11,7 +11,7 @@ fn is_bluetooth_bus(device_info: &hidapi::DeviceInfo) -> bool {
     matches!(device_info.bus_type(), hidapi::BusType::Bluetooth)
 }
 
-use crate::device_registry;
+use crate::device_registry::{self, DeviceKind};
 use crate::error::TransportError;
 use crate::flow_control::FlowControlTransport;
 use crate::hid_bluetooth::HidBluetoothTransport;
@@ -19,7 +19,10 @@ use crate::hid_dongle::HidDongleTransport;
 use crate::hid_wired::HidWiredTransport;
 use crate::printer::{Printer, PrinterConfig};
 use crate::protocol::device;
-use crate::types::{DiscoveredDevice, DiscoveryEvent, TransportDeviceInfo, TransportType};
+use crate::types::{
+    BootModeDevice, DeviceLabel, DiscoveredDevice, DiscoveryEvent, TransportDeviceInfo,
+    TransportType,
+};
 use crate::Transport;
 
 /// Device discovery abstraction
@@ -384,6 +387,9 @@ impl DeviceDiscovery for HidDiscovery {
             device.info.transport_type, device.info.vid, device.info.pid
         );
 
+        #[cfg(feature = "hid-trace")]
+        let transport = crate::hid_trace::TracingTransport::wrap(transport);
+
         // Wrap with printer if monitoring is enabled
         let transport = match &self.printer_config {
             Some(config) => Printer::wrap(transport, config.clone()),
@@ -401,6 +407,35 @@ impl DeviceDiscovery for HidDiscovery {
     }
 }
 
+impl HidDiscovery {
+    /// List devices in bootloader mode.
+    ///
+    /// These don't show up in [`list_devices`](DeviceDiscovery::list_devices):
+    /// they enumerate under the bootloader PIDs and expose only the boot
+    /// interface (usage page 0xFF01).
+    pub fn list_boot_mode_devices(&self) -> Result<Vec<BootModeDevice>, TransportError> {
+        let api = HidApi::new().map_err(|e| TransportError::HidError(e.to_string()))?;
+        let devices: Vec<_> = api
+            .device_list()
+            .filter(|d| d.usage_page() == device::BOOT_USAGE_PAGE)
+            .filter_map(|d| {
+                let kind = device_registry::boot_mode_kind(d.vendor_id(), d.product_id())?;
+                Some(BootModeDevice {
+                    vid: d.vendor_id(),
+                    pid: d.product_id(),
+                    kind,
+                    path: d.path().to_string_lossy().to_string(),
+                    product_name: d.product_string().map(|s| s.to_string()),
+                })
+            })
+            .collect();
+        if !devices.is_empty() {
+            info!("Found {} device(s) in bootloader mode", devices.len());
+        }
+        Ok(devices)
+    }
+}
+
 /// Result of probing a device
 #[derive(Debug, Clone)]
 pub struct ProbedDevice {
@@ -412,6 +447,8 @@ pub struct ProbedDevice {
     pub device_id: Option<u32>,
     /// Firmware version if probe succeeded
     pub version: Option<u16>,
+    /// Keyboard or mouse, from the PID and product string
+    pub kind: DeviceKind,
 }
 
 impl HidDiscovery {
@@ -424,7 +461,7 @@ impl HidDiscovery {
     ///
     /// Returns a list of all discovered devices with their probe results,
     /// sorted by preference (Bluetooth > Dongle > Wired) with responsive
-    /// devices first.
+    /// devices first and keyboards ahead of mice.
     pub fn probe_devices(&self) -> Result<Vec<ProbedDevice>, TransportError> {
         use crate::protocol::cmd;
         use crate::ChecksumType;
@@ -476,30 +513,37 @@ impl HidDiscovery {
                 }
             };
 
+            let kind =
+                device_registry::device_kind(device.info.pid, device.info.product_name.as_deref());
             probed.push(ProbedDevice {
                 device,
                 responsive: probe_result.0,
                 device_id: probe_result.1,
                 version: probe_result.2,
+                kind,
             });
         }
 
-        // Sort: responsive first, then by transport preference (BT > Dongle > Wired)
+        // Sort: responsive first, keyboards before mice, then by transport
+        // preference (BT > Dongle > Wired)
         probed.sort_by(|a, b| {
             // Responsive devices first
             match (a.responsive, b.responsive) {
                 (true, false) => std::cmp::Ordering::Less,
                 (false, true) => std::cmp::Ordering::Greater,
                 _ => {
-                    // Same responsiveness, sort by transport type preference
+                    // Same responsiveness, sort by kind and transport type preference
                     let priority = |t: &TransportType| match t {
                         TransportType::Bluetooth => 0,
                         TransportType::HidDongle => 1,
                         TransportType::HidWired => 2,
                         _ => 3,
                     };
-                    priority(&a.device.info.transport_type)
-                        .cmp(&priority(&b.device.info.transport_type))
+                    let is_mouse = |p: &ProbedDevice| p.kind == DeviceKind::Mouse;
+                    is_mouse(a).cmp(&is_mouse(b)).then_with(|| {
+                        priority(&a.device.info.transport_type)
+                            .cmp(&priority(&b.device.info.transport_type))
+                    })
                 }
             }
         });
@@ -517,7 +561,7 @@ impl HidDiscovery {
     ///
     /// Probes all devices and returns a transport for the best one:
     /// - If only one device responds, use that one
-    /// - If multiple respond, prefer Bluetooth > Dongle > Wired
+    /// - If multiple respond, prefer keyboards over mice, then Bluetooth > Dongle > Wired
     /// - If none respond but devices exist, try to open the preferred transport type anyway
     pub fn open_preferred(&self) -> Result<Arc<dyn Transport>, TransportError> {
         let probed = self.probe_devices()?;
@@ -588,7 +632,7 @@ impl HidDiscovery {
     pub fn list_labeled_devices<F>(
         &self,
         model_name_fn: F,
-    ) -> Result<Vec<(ProbedDevice, crate::types::DeviceLabel)>, TransportError>
+    ) -> Result<Vec<(ProbedDevice, DeviceLabel)>, TransportError>
     where
         F: Fn(Option<u32>, u16, u16) -> Option<String>,
     {
@@ -607,12 +651,16 @@ impl HidDiscovery {
                 .or_else(|| p.device.info.product_name.clone())
                 .unwrap_or_else(|| "Unknown".to_string());
 
-            let label = crate::types::DeviceLabel {
+            let label = DeviceLabel {
                 index,
                 model_name,
                 transport_name,
+                kind: p.kind,
                 device_id: p.device_id,
                 version: p.version,
+                vid: p.device.info.vid,
+                pid: p.device.info.pid,
+                serial: p.device.info.serial.clone().filter(|s| !s.is_empty()),
                 hid_path: p.device.info.device_path.clone(),
             };
 
@@ -624,10 +672,197 @@ impl HidDiscovery {
 }
 
 /// Format a device list for display (e.g., to stderr when multiple devices found).
-pub fn format_device_list(labels: &[crate::types::DeviceLabel]) -> String {
+pub fn format_device_list(labels: &[DeviceLabel]) -> String {
     let mut out = String::new();
     for label in labels {
         out.push_str(&format!("{label}\n"));
     }
     out
 }
+
+/// Why a `--device` selector didn't pick exactly one device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceSelectError {
+    IndexOutOfRange { index: usize, count: usize },
+    Ambiguous { selector: String, matches: usize },
+    NoMatch(String),
+}
+
+impl std::fmt::Display for DeviceSelectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IndexOutOfRange { index, count: 0 } => {
+                write!(f, "Device index {index} out of range (no devices found)")
+            }
+            Self::IndexOutOfRange { index, count } => {
+                write!(f, "Device index {index} out of range (0-{})", count - 1)
+            }
+            Self::Ambiguous { selector, matches } => write!(
+                f,
+                "Ambiguous --device '{selector}': {matches} matches. Use index or serial."
+            ),
+            Self::NoMatch(selector) => write!(f, "No device matches '{selector}'"),
+        }
+    }
+}
+
+impl std::error::Error for DeviceSelectError {}
+
+/// Parse a USB id selector: `3151:5030` (VID:PID), `0x5030` or `pid:5030` (PID only).
+fn parse_usb_id(sel: &str) -> Option<(Option<u16>, u16)> {
+    let hex = |s: &str| u16::from_str_radix(s.trim_start_matches("0x"), 16).ok();
+    if let Some(pid) = sel.strip_prefix("pid:") {
+        return Some((None, hex(pid)?));
+    }
+    if let Some((vid, pid)) = sel.split_once(':') {
+        return Some((Some(hex(vid)?), hex(pid)?));
+    }
+    sel.starts_with("0x")
+        .then(|| hex(sel))
+        .flatten()
+        .map(|pid| (None, pid))
+}
+
+/// Pick the device a `--device` selector names, by position in `labels`.
+///
+/// Forms, tried in order until one matches:
+/// - list index: `0`
+/// - transport: `usb`, `dongle`, `bt`
+/// - kind: `keyboard`, `mouse`
+/// - USB id: `3151:5030`, `0x5030`, `pid:5030` (hex)
+/// - serial number, case-insensitive (`serial:` skips the other forms)
+/// - HID path substring (`path:` skips the other forms)
+pub fn select_device(labels: &[DeviceLabel], selector: &str) -> Result<usize, DeviceSelectError> {
+    let pick = |matches: Vec<usize>| -> Option<Result<usize, DeviceSelectError>> {
+        match matches.len() {
+            0 => None,
+            1 => Some(Ok(matches[0])),
+            n => Some(Err(DeviceSelectError::Ambiguous {
+                selector: selector.to_string(),
+                matches: n,
+            })),
+        }
+    };
+    let positions = |pred: &dyn Fn(&DeviceLabel) -> bool| -> Vec<usize> {
+        labels
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| pred(l))
+            .map(|(i, _)| i)
+            .collect()
+    };
+    let by_serial = |serial: &str| {
+        positions(&|l| {
+            l.serial
+                .as_deref()
+                .is_some_and(|s| s.eq_ignore_ascii_case(serial))
+        })
+    };
+    let by_path = |path: &str| positions(&|l| l.hid_path.contains(path));
+    let no_match = || Err(DeviceSelectError::NoMatch(selector.to_string()));
+
+    if let Some(serial) = selector.strip_prefix("serial:") {
+        return pick(by_serial(serial)).unwrap_or_else(no_match);
+    }
+    if let Some(path) = selector.strip_prefix("path:") {
+        return pick(by_path(path)).unwrap_or_else(no_match);
+    }
+
+    if let Ok(index) = selector.parse::<usize>() {
+        return labels.iter().position(|l| l.index == index).ok_or(
+            DeviceSelectError::IndexOutOfRange {
+                index,
+                count: labels.len(),
+            },
+        );
+    }
+    if let Some(result) = pick(positions(&|l| l.transport_name == selector)) {
+        return result;
+    }
+    if let Some(result) = pick(positions(&|l| l.kind.name() == selector)) {
+        return result;
+    }
+    if let Some((vid, pid)) = parse_usb_id(selector) {
+        let matches = positions(&|l| l.pid == pid && vid.is_none_or(|v| l.vid == v));
+        return pick(matches).unwrap_or_else(no_match);
+    }
+    pick(by_serial(selector))
+        .or_else(|| pick(by_path(selector)))
+        .unwrap_or_else(no_match)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(index: usize, transport_name: &'static str, pid: u16, serial: &str) -> DeviceLabel {
+        DeviceLabel {
+            index,
+            model_name: "M1 V5 HE".into(),
+            transport_name,
+            kind: DeviceKind::Keyboard,
+            device_id: None,
+            version: None,
+            vid: 0x3151,
+            pid,
+            serial: (!serial.is_empty()).then(|| serial.to_string()),
+            hid_path: format!("/dev/hidraw{index}"),
+        }
+    }
+
+    fn two_boards() -> Vec<DeviceLabel> {
+        vec![
+            label(0, "usb", 0x5030, "AAA111"),
+            label(1, "usb", 0x5038, "BBB222"),
+        ]
+    }
+
+    #[test]
+    fn selects_by_index_serial_and_pid() {
+        let labels = two_boards();
+        assert_eq!(select_device(&labels, "1"), Ok(1));
+        assert_eq!(select_device(&labels, "bbb222"), Ok(1));
+        assert_eq!(select_device(&labels, "serial:AAA111"), Ok(0));
+        assert_eq!(select_device(&labels, "0x5038"), Ok(1));
+        assert_eq!(select_device(&labels, "pid:5030"), Ok(0));
+        assert_eq!(select_device(&labels, "3151:5038"), Ok(1));
+        assert_eq!(select_device(&labels, "path:hidraw1"), Ok(1));
+    }
+
+    #[test]
+    fn selects_by_kind() {
+        let mut labels = two_boards();
+        labels[1].kind = DeviceKind::Mouse;
+        assert_eq!(select_device(&labels, "mouse"), Ok(1));
+        assert_eq!(select_device(&labels, "keyboard"), Ok(0));
+        assert!(labels[1].to_string().ends_with("(mouse) sn:BBB222"));
+    }
+
+    #[test]
+    fn reports_ambiguous_and_missing() {
+        let labels = two_boards();
+        assert_eq!(
+            select_device(&labels, "usb"),
+            Err(DeviceSelectError::Ambiguous {
+                selector: "usb".into(),
+                matches: 2
+            })
+        );
+        assert_eq!(
+            select_device(&labels, "5"),
+            Err(DeviceSelectError::IndexOutOfRange { index: 5, count: 2 })
+        );
+        assert_eq!(
+            select_device(&labels, "0x1234"),
+            Err(DeviceSelectError::NoMatch("0x1234".into()))
+        );
+        assert_eq!(
+            select_device(&labels, "dongle"),
+            Err(DeviceSelectError::NoMatch("dongle".into()))
+        );
+        assert_eq!(
+            select_device(&[], "0").unwrap_err().to_string(),
+            "Device index 0 out of range (no devices found)"
+        );
+    }
+}
@@ -12,12 +12,25 @@ pub enum TransportError {
     #[error("Device disconnected")]
     Disconnected,
 
-    #[error("Communication timeout")]
-    Timeout,
+    /// No matching response arrived within the retry budget
+    #[error("Communication timeout{}", cmd_suffix(.cmd))]
+    Timeout { cmd: Option<u8> },
+
+    /// Device or dongle could not accept the command right now
+    #[error("Device busy{}", cmd_suffix(.cmd))]
+    Busy { cmd: Option<u8> },
+
+    /// Command is not implemented by this device or firmware
+    #[error("Command 0x{cmd:02X} not supported by device")]
+    NotSupported { cmd: u8 },
 
     #[error("Invalid response: expected cmd 0x{expected:02X}, got 0x{actual:02X}")]
     InvalidResponse { expected: u8, actual: u8 },
 
+    /// Response echoed the right command but could not be decoded
+    #[error("Protocol mismatch for cmd 0x{cmd:02X}: {reason}")]
+    ProtocolMismatch { cmd: u8, reason: String },
+
     #[error("Checksum mismatch")]
     ChecksumError,
 
@@ -29,9 +42,6 @@ pub enum TransportError {
     HidPermissionDenied(String),
 
     // Dongle-specific
-    #[error("Dongle buffer overflow")]
-    DongleBufferOverflow,
-
     #[error("Keyboard not responding via dongle")]
     KeyboardOffline,
 
@@ -40,6 +50,50 @@ pub enum TransportError {
     Internal(String),
 }
 
+fn cmd_suffix(cmd: &Option<u8>) -> String {
+    cmd.map(|c| format!(" (cmd 0x{c:02X})")).unwrap_or_default()
+}
+
+impl TransportError {
+    /// Whether repeating the same request may succeed.
+    ///
+    /// Timeouts, busy/offline dongles and stale or corrupted responses are
+    /// transient. Missing devices, unsupported commands and undecodable
+    /// responses will fail the same way again.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            TransportError::Timeout { .. }
+                | TransportError::Busy { .. }
+                | TransportError::InvalidResponse { .. }
+                | TransportError::ChecksumError
+                | TransportError::KeyboardOffline
+        )
+    }
+
+    /// Command byte of the request that failed, if known.
+    pub fn command(&self) -> Option<u8> {
+        match self {
+            TransportError::Timeout { cmd } | TransportError::Busy { cmd } => *cmd,
+            TransportError::NotSupported { cmd } | TransportError::ProtocolMismatch { cmd, .. } => {
+                Some(*cmd)
+            }
+            TransportError::InvalidResponse { expected, .. } => Some(*expected),
+            _ => None,
+        }
+    }
+
+    /// Attach `cmd` to a timeout or busy error that was raised without one
+    /// (e.g. by a bare `read_report`).
+    pub fn with_command(self, cmd: u8) -> Self {
+        match self {
+            TransportError::Timeout { cmd: None } => TransportError::Timeout { cmd: Some(cmd) },
+            TransportError::Busy { cmd: None } => TransportError::Busy { cmd: Some(cmd) },
+            other => other,
+        }
+    }
+}
+
 impl From<hidapi::HidError> for TransportError {
     fn from(e: hidapi::HidError) -> Self {
         let msg = e.to_string();
@@ -50,3 +104,37 @@ impl From<hidapi::HidError> for TransportError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retryable_classification() {
+        assert!(TransportError::Timeout { cmd: Some(0x87) }.is_retryable());
+        assert!(TransportError::Busy { cmd: None }.is_retryable());
+        assert!(TransportError::InvalidResponse {
+            expected: 0x87,
+            actual: 0x8F
+        }
+        .is_retryable());
+        assert!(!TransportError::Disconnected.is_retryable());
+        assert!(!TransportError::NotSupported { cmd: 0xE5 }.is_retryable());
+        assert!(!TransportError::ProtocolMismatch {
+            cmd: 0x8F,
+            reason: "short".into()
+        }
+        .is_retryable());
+    }
+
+    #[test]
+    fn command_is_attached() {
+        let e = TransportError::Timeout { cmd: None }.with_command(0x87);
+        assert_eq!(e.command(), Some(0x87));
+        assert_eq!(e.to_string(), "Communication timeout (cmd 0x87)");
+        // An existing command is not overwritten
+        let e = TransportError::Timeout { cmd: Some(0x01) }.with_command(0x87);
+        assert_eq!(e.command(), Some(0x01));
+        assert_eq!(TransportError::Disconnected.command(), None);
+    }
+}
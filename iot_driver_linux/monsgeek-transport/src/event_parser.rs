@@ -340,6 +340,8 @@ pub fn run_event_reader_loop<F>(
                     &buf[..len.min(16)]
                 );
                 let event = parser(&buf[..len]);
+                #[cfg(feature = "hid-trace")]
+                crate::hid_trace::event(config.name, &buf[..len], &event);
                 let timestamped = TimestampedEvent::new(timestamp, event);
                 // Send to all subscribers (ignores if no receivers)
                 let _ = tx.send(timestamped);
@@ -14,7 +14,7 @@
 
 use std::collections::VecDeque;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 
 use parking_lot::Mutex;
@@ -23,6 +23,7 @@ use tracing::{debug, warn};
 
 use crate::error::TransportError;
 use crate::protocol::{cmd, dongle_timing, timing};
+use crate::quirks::Quirks;
 use crate::types::{
     ChecksumType, TimestampedEvent, TransportDeviceInfo, TransportType, VendorEvent,
 };
@@ -45,6 +46,8 @@ const MAX_CACHE_SIZE: usize = 16;
 pub struct FlowControlTransport {
     inner: Arc<dyn Transport>,
     flow: FlowState,
+    /// Firmware quirks, read on first use (see [`Self::quirks`]).
+    quirks: OnceLock<Quirks>,
 }
 
 enum FlowState {
@@ -205,7 +208,11 @@ impl FlowControlTransport {
             },
         };
 
-        Self { inner, flow }
+        Self {
+            inner,
+            flow,
+            quirks: OnceLock::new(),
+        }
     }
 
     /// Access the wrapped raw transport.
@@ -213,6 +220,35 @@ impl FlowControlTransport {
         &self.inner
     }
 
+    /// Firmware quirks of the connected board (see [`crate::quirks`]).
+    ///
+    /// Read from GET_USB_VERSION on first use and kept for the connection.
+    /// If the version can't be read, current firmware is assumed and kept
+    /// too, so a board that doesn't answer isn't asked again on every command.
+    pub fn quirks(&self) -> Quirks {
+        *self.quirks.get_or_init(|| {
+            match self.query_command(cmd::GET_USB_VERSION, &[], ChecksumType::Bit7) {
+                // [0] = cmd echo, [1..5] = device_id, [7..9] = version
+                Ok(r) if r.len() >= 9 => Quirks::for_firmware(
+                    Some(u32::from_le_bytes([r[1], r[2], r[3], r[4]])),
+                    u16::from_le_bytes([r[7], r[8]]),
+                ),
+                Ok(_) | Err(_) => {
+                    debug!("Firmware version unreadable, assuming current firmware");
+                    Quirks::default()
+                }
+            }
+        })
+    }
+
+    /// Refuse a command this firmware doesn't implement.
+    fn check_quirks(&self, cmd_byte: u8, data: &[u8]) -> Result<(), TransportError> {
+        if Quirks::gates(cmd_byte) && !self.quirks().allows(cmd_byte, data) {
+            return Err(TransportError::NotSupported { cmd: cmd_byte });
+        }
+        Ok(())
+    }
+
     // ========================================================================
     // Query methods (flow-controlled)
     // ========================================================================
@@ -224,6 +260,7 @@ impl FlowControlTransport {
         data: &[u8],
         checksum: ChecksumType,
     ) -> Result<Vec<u8>, TransportError> {
+        self.check_quirks(cmd_byte, data)?;
         match &self.flow {
             FlowState::Simple {
                 read_gap_ms,
@@ -246,6 +283,7 @@ impl FlowControlTransport {
         data: &[u8],
         checksum: ChecksumType,
     ) -> Result<Vec<u8>, TransportError> {
+        self.check_quirks(cmd_byte, data)?;
         match &self.flow {
             FlowState::Simple {
                 read_gap_ms,
@@ -268,6 +306,7 @@ impl FlowControlTransport {
         data: &[u8],
         checksum: ChecksumType,
     ) -> Result<(), TransportError> {
+        self.check_quirks(cmd_byte, data)?;
         match &self.flow {
             FlowState::Simple {
                 write_spacing_ms,
@@ -296,6 +335,7 @@ impl FlowControlTransport {
         checksum: ChecksumType,
         delay_ms: u64,
     ) -> Result<(), TransportError> {
+        self.check_quirks(cmd_byte, data)?;
         match &self.flow {
             FlowState::Simple { query_lock, .. } => {
                 let _guard = query_lock.lock().unwrap();
@@ -325,6 +365,7 @@ impl FlowControlTransport {
         delay_ms: u64,
         raw_mode: bool,
     ) -> Result<Vec<u8>, TransportError> {
+        let mut foreign = 0;
         for attempt in 0..timing::QUERY_RETRIES {
             if self.inner.send_report(cmd_byte, data, checksum).is_err() {
                 debug!("Send attempt {} failed for 0x{:02X}", attempt, cmd_byte);
@@ -343,11 +384,14 @@ impl FlowControlTransport {
                     if !resp.is_empty() && resp[0] == cmd_byte {
                         return Ok(resp);
                     }
+                    let got = resp.first().copied().unwrap_or(0);
                     debug!(
                         "Response mismatch: expected 0x{:02X}, got 0x{:02X}",
-                        cmd_byte,
-                        resp.first().copied().unwrap_or(0)
+                        cmd_byte, got
                     );
+                    if got != 0 {
+                        foreign += 1;
+                    }
                 }
                 Err(e) => {
                     debug!("Read attempt {} failed: {}", attempt, e);
@@ -355,7 +399,16 @@ impl FlowControlTransport {
             }
         }
 
-        Err(TransportError::Timeout)
+        // Every reply answered another command: something else (the vendor
+        // app, a second driver) is talking to the device at the same time.
+        if foreign == timing::QUERY_RETRIES {
+            return Err(TransportError::Busy {
+                cmd: Some(cmd_byte),
+            });
+        }
+        Err(TransportError::Timeout {
+            cmd: Some(cmd_byte),
+        })
     }
 
     // ---- Dongle dispatch ----
@@ -382,9 +435,11 @@ impl FlowControlTransport {
             })
             .map_err(|_| TransportError::Disconnected)?;
 
+        // The worker's reads and flushes fail without knowing the command
         response_rx
             .recv()
             .map_err(|_| TransportError::Disconnected)?
+            .map_err(|e| e.with_command(cmd_byte))
     }
 }
 
@@ -483,7 +538,10 @@ impl FlowControlTransport {
                 expected,
                 actual: got,
             },
-            _ => TransportError::Internal(e.to_string()),
+            _ => TransportError::ProtocolMismatch {
+                cmd: C::CMD,
+                reason: e.to_string(),
+            },
         })
     }
 
@@ -494,7 +552,10 @@ impl FlowControlTransport {
         R: HidResponse,
     {
         let resp = self.query_raw(C::CMD, &cmd.to_data(), C::CHECKSUM)?;
-        R::parse(&resp).map_err(|e| TransportError::Internal(e.to_string()))
+        R::parse(&resp).map_err(|e| TransportError::ProtocolMismatch {
+            cmd: C::CMD,
+            reason: e.to_string(),
+        })
     }
 }
 
@@ -728,7 +789,9 @@ fn execute_query(
         );
     }
 
-    Err(TransportError::Timeout)
+    Err(TransportError::Timeout {
+        cmd: Some(cmd_byte),
+    })
 }
 
 impl Drop for FlowControlTransport {
@@ -744,3 +807,119 @@ impl Drop for FlowControlTransport {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wired_info() -> TransportDeviceInfo {
+        TransportDeviceInfo {
+            vid: 0x3151,
+            pid: 0x5030,
+            is_dongle: false,
+            transport_type: TransportType::HidWired,
+            device_path: "fake".into(),
+            serial: None,
+            product_name: None,
+        }
+    }
+
+    /// Wired device that echoes every command. With `version`, GET_USB_VERSION
+    /// reports it; without, every reply belongs to another client's command.
+    struct FakeDevice {
+        info: TransportDeviceInfo,
+        version: Option<u16>,
+        last_cmd: Mutex<u8>,
+        sends: AtomicUsize,
+    }
+
+    impl FakeDevice {
+        fn new(version: Option<u16>) -> Self {
+            Self {
+                info: wired_info(),
+                version,
+                last_cmd: Mutex::new(0),
+                sends: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl Transport for FakeDevice {
+        fn send_report(&self, cmd: u8, _: &[u8], _: ChecksumType) -> Result<(), TransportError> {
+            *self.last_cmd.lock() = cmd;
+            self.sends.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn read_report(&self) -> Result<Vec<u8>, TransportError> {
+            let mut resp = vec![0u8; 64];
+            let Some(version) = self.version else {
+                resp[0] = cmd::GET_PROFILE;
+                return Ok(resp);
+            };
+            resp[0] = *self.last_cmd.lock();
+            resp[7..9].copy_from_slice(&version.to_le_bytes());
+            Ok(resp)
+        }
+
+        fn read_event(&self, _timeout_ms: u32) -> Result<Option<VendorEvent>, TransportError> {
+            Ok(None)
+        }
+
+        fn device_info(&self) -> &TransportDeviceInfo {
+            &self.info
+        }
+
+        fn is_connected(&self) -> bool {
+            true
+        }
+
+        fn close(&self) -> Result<(), TransportError> {
+            Ok(())
+        }
+
+        fn get_battery_status(&self) -> Result<(u8, bool, bool), TransportError> {
+            Ok((100, false, false))
+        }
+    }
+
+    #[test]
+    fn foreign_replies_mean_busy() {
+        let flow = FlowControlTransport::new(Arc::new(FakeDevice::new(None)));
+        let err = flow
+            .query_command(cmd::GET_USB_VERSION, &[], ChecksumType::Bit7)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            TransportError::Busy { cmd: Some(c) } if c == cmd::GET_USB_VERSION
+        ));
+        assert!(err.is_retryable());
+    }
+
+    #[test]
+    fn old_firmware_refuses_trigger_commands_unsent() {
+        let device = Arc::new(FakeDevice::new(Some(0x0107)));
+        let flow = FlowControlTransport::new(device.clone());
+        let err = flow
+            .query_raw(cmd::GET_MULTI_MAGNETISM, &[0, 1, 0], ChecksumType::Bit7)
+            .unwrap_err();
+        assert!(
+            matches!(err, TransportError::NotSupported { cmd: c } if c == cmd::GET_MULTI_MAGNETISM)
+        );
+        // Only the version query went out
+        assert_eq!(device.sends.load(Ordering::SeqCst), 1);
+        assert!(flow
+            .send_command(cmd::SET_PROFILE, &[0], ChecksumType::Bit7)
+            .is_ok());
+    }
+
+    #[test]
+    fn unreadable_version_is_not_asked_again() {
+        let device = Arc::new(FakeDevice::new(None));
+        let flow = FlowControlTransport::new(device.clone());
+        assert_eq!(flow.quirks(), Quirks::default());
+        let sends = device.sends.load(Ordering::SeqCst);
+        assert_eq!(flow.quirks(), Quirks::default());
+        assert_eq!(device.sends.load(Ordering::SeqCst), sends);
+    }
+}
@@ -142,7 +142,7 @@ impl Transport for HidBluetoothTransport {
                 Err(e) => return Err(TransportError::from(e)),
             }
         }
-        Err(TransportError::Timeout)
+        Err(TransportError::Timeout { cmd: None })
     }
 
     // send_flush: uses default no-op
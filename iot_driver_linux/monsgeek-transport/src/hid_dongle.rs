@@ -135,9 +135,10 @@ impl Transport for HidDongleTransport {
         //   buf[6] = rf_ready (0=waiting, 1=ready)
         let level = buf[2];
         if level > 100 {
-            return Err(TransportError::Internal(format!(
-                "Invalid battery level: {level}"
-            )));
+            return Err(TransportError::ProtocolMismatch {
+                cmd: cmd::GET_DONGLE_STATUS,
+                reason: format!("invalid battery level {level}"),
+            });
         }
 
         Ok(Some(DongleStatus {
@@ -0,0 +1,203 @@
+//! Structured `tracing` events for every HID frame (feature `hid-trace`).
+//!
+//! [`TracingTransport`] wraps a raw transport the same way [`crate::Printer`]
+//! does for `--monitor`, but emits one `tracing` event per frame instead of
+//! printing. Every transport opened through [`crate::HidDiscovery`] is
+//! wrapped, so the gRPC server, the TUI and every CLI command are covered.
+//! Input reports from the event reader threads are traced where they are
+//! read, via [`event`].
+//!
+//! All events use the `monsgeek_hid` target at TRACE level:
+//!
+//! ```text
+//! RUST_LOG=monsgeek_hid=trace iot_driver serve
+//! ```
+//!
+//! Fields: `dir` (`out`, `in`, `event`), `transport`, `cmd`, `cmd_name`,
+//! `decoded` (the `ParsedCommand` / `ParsedResponse` / `VendorEvent` debug
+//! form), `latency_us` (from the last command to its reply), `hex` and
+//! `error`. Decoding only runs when the target is enabled.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+use tokio::sync::broadcast;
+
+use crate::protocol::cmd;
+use crate::types::{DongleInfo, DongleStatus, RfInfo};
+use crate::{
+    try_parse_command, try_parse_response, ChecksumType, TimestampedEvent, Transport,
+    TransportDeviceInfo, TransportError, VendorEvent,
+};
+
+/// Compact hex for trace fields.
+fn hex(data: &[u8]) -> String {
+    let end = data.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    data[..end]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn enabled() -> bool {
+    tracing::enabled!(target: "monsgeek_hid", tracing::Level::TRACE)
+}
+
+/// Trace one input report read by an event reader thread.
+pub fn event(source: &str, raw: &[u8], event: &VendorEvent) {
+    if !enabled() {
+        return;
+    }
+    tracing::trace!(
+        target: "monsgeek_hid",
+        dir = "event",
+        transport = source,
+        decoded = ?event,
+        hex = %hex(raw),
+    );
+}
+
+/// Transport middleware that traces every frame.
+pub struct TracingTransport {
+    inner: Arc<dyn Transport>,
+    /// Type name for the `transport` field
+    kind: String,
+    /// Command byte and send time of the last command, for reply latency
+    last_sent: Mutex<Option<(u8, Instant)>>,
+}
+
+impl TracingTransport {
+    pub fn wrap(inner: Arc<dyn Transport>) -> Arc<dyn Transport> {
+        let kind = format!("{:?}", inner.device_info().transport_type);
+        Arc::new(Self {
+            inner,
+            kind,
+            last_sent: Mutex::new(None),
+        })
+    }
+
+    fn sent(&self, cmd_byte: u8, data: &[u8], result: &Result<(), TransportError>) {
+        *self.last_sent.lock() = Some((cmd_byte, Instant::now()));
+        if !enabled() {
+            return;
+        }
+        let mut packet = vec![cmd_byte];
+        packet.extend_from_slice(data);
+        let error = result.as_ref().err().map(|e| e.to_string());
+        tracing::trace!(
+            target: "monsgeek_hid",
+            dir = "out",
+            transport = %self.kind,
+            cmd = cmd_byte,
+            cmd_name = cmd::name(cmd_byte),
+            decoded = ?try_parse_command(&packet),
+            hex = %hex(&packet),
+            error = error.as_deref(),
+        );
+    }
+}
+
+impl Transport for TracingTransport {
+    fn send_report(
+        &self,
+        cmd_byte: u8,
+        data: &[u8],
+        checksum: ChecksumType,
+    ) -> Result<(), TransportError> {
+        let result = self.inner.send_report(cmd_byte, data, checksum);
+        self.sent(cmd_byte, data, &result);
+        result
+    }
+
+    fn read_report(&self) -> Result<Vec<u8>, TransportError> {
+        let result = self.inner.read_report();
+        if !enabled() {
+            return result;
+        }
+        let last = *self.last_sent.lock();
+        let latency_us = last.map(|(_, at)| at.elapsed().as_micros() as u64);
+        match &result {
+            Ok(data) => {
+                let echo = data.first().copied().unwrap_or(0);
+                tracing::trace!(
+                    target: "monsgeek_hid",
+                    dir = "in",
+                    transport = %self.kind,
+                    cmd = echo,
+                    cmd_name = cmd::name(echo),
+                    decoded = ?try_parse_response(data),
+                    latency_us,
+                    hex = %hex(data),
+                );
+            }
+            Err(e) => tracing::trace!(
+                target: "monsgeek_hid",
+                dir = "in",
+                transport = %self.kind,
+                cmd = last.map(|(c, _)| c),
+                latency_us,
+                error = %e,
+            ),
+        }
+        result
+    }
+
+    fn send_flush(&self) -> Result<(), TransportError> {
+        let result = self.inner.send_flush();
+        if enabled() {
+            let error = result.as_ref().err().map(|e| e.to_string());
+            tracing::trace!(
+                target: "monsgeek_hid",
+                dir = "out",
+                transport = %self.kind,
+                cmd = cmd::GET_CACHED_RESPONSE,
+                cmd_name = cmd::name(cmd::GET_CACHED_RESPONSE),
+                error = error.as_deref(),
+            );
+        }
+        result
+    }
+
+    fn read_event(&self, timeout_ms: u32) -> Result<Option<VendorEvent>, TransportError> {
+        // Already traced by the event reader thread that produced it
+        self.inner.read_event(timeout_ms)
+    }
+
+    fn device_info(&self) -> &TransportDeviceInfo {
+        self.inner.device_info()
+    }
+
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn close(&self) -> Result<(), TransportError> {
+        self.inner.close()
+    }
+
+    fn get_battery_status(&self) -> Result<(u8, bool, bool), TransportError> {
+        self.inner.get_battery_status()
+    }
+
+    fn query_dongle_status(&self) -> Result<Option<DongleStatus>, TransportError> {
+        self.inner.query_dongle_status()
+    }
+
+    fn query_dongle_info(&self) -> Result<Option<DongleInfo>, TransportError> {
+        self.inner.query_dongle_info()
+    }
+
+    fn query_rf_info(&self) -> Result<Option<RfInfo>, TransportError> {
+        self.inner.query_rf_info()
+    }
+
+    fn subscribe_events(&self) -> Option<broadcast::Receiver<TimestampedEvent>> {
+        self.inner.subscribe_events()
+    }
+
+    fn get_dongle_patch_info(&self) -> Result<Option<Vec<u8>>, TransportError> {
+        self.inner.get_dongle_patch_info()
+    }
+}
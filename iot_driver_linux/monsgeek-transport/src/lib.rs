@@ -6,14 +6,20 @@
 //! - HID Wired (direct USB connection)
 //! - HID Dongle (2.4GHz wireless via USB dongle)
 //! - HID Bluetooth (BLE via kernel's hid-over-gatt driver)
+//!
+//! The vendor's mice speak the same feature-report protocol and share the
+//! dongle; discovery labels them with [`DeviceKind::Mouse`].
 
 pub mod command;
 pub mod device_registry;
 pub mod error;
 pub mod event_parser;
 pub mod flow_control;
+#[cfg(feature = "hid-trace")]
+pub mod hid_trace;
 pub mod printer;
 pub mod protocol;
+pub mod quirks;
 pub mod types;
 
 mod discovery;
@@ -81,19 +87,24 @@ pub use command::{
     SPEED_MAX,
 };
 pub use device_registry::{
-    is_bluetooth_pid, is_dongle_pid, BLUETOOTH_PIDS, DONGLE_PIDS, VENDOR_ID,
+    boot_mode_kind, device_kind, is_bluetooth_pid, is_dongle_pid, is_mouse_pid, BootModeKind,
+    DeviceKind, BLUETOOTH_PIDS, BOOT_MODE_PIDS, DONGLE_PIDS, MOUSE_PIDS, VENDOR_ID,
 };
 pub use error::TransportError;
 pub use printer::{
     DecodedPacket, OutputFormat, PacketFilter, Printer, PrinterConfig, PrinterTransport,
 };
 pub use protocol::{KeyRef, Layer};
+pub use quirks::Quirks;
 pub use types::{
-    ChecksumType, DeviceLabel, DiscoveredDevice, DiscoveryEvent, DongleInfo, DongleStatus, RfInfo,
-    TimestampedEvent, TransportDeviceInfo, TransportType, VendorEvent,
+    BootModeDevice, ChecksumType, DeviceLabel, DiscoveredDevice, DiscoveryEvent, DongleInfo,
+    DongleStatus, RfInfo, TimestampedEvent, TransportDeviceInfo, TransportType, VendorEvent,
 };
 
-pub use discovery::{format_device_list, DeviceDiscovery, HidDiscovery, ProbedDevice};
+pub use discovery::{
+    format_device_list, select_device, DeviceDiscovery, DeviceSelectError, HidDiscovery,
+    ProbedDevice,
+};
 pub use flow_control::FlowControlTransport;
 pub use hid_bluetooth::HidBluetoothTransport;
 pub use hid_dongle::HidDongleTransport;
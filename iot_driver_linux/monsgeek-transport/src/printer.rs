@@ -224,6 +224,8 @@ struct CommandContext {
     last_magnetism_subcmd: Option<u8>,
     /// Last GetMultiMagnetism page (for parsing 0x00 responses)
     last_magnetism_page: Option<u8>,
+    /// Firmware transfer in progress: (next chunk index, total chunks)
+    fw_transfer: Option<(u16, u16)>,
 }
 
 /// Unified printer for monitoring transport operations
@@ -284,13 +286,27 @@ impl Printer {
         }
     }
 
-    /// Update command context for stateful response parsing
-    fn update_context(&self, cmd: u8, data: &[u8]) {
+    /// Update command context for stateful response/command parsing
+    fn update_context(&self, cmd: u8, data: &[u8], parsed: &ParsedCommand) {
         if cmd == cmd::GET_MULTI_MAGNETISM && data.len() >= 3 {
             let mut ctx = self.context.lock();
             ctx.last_magnetism_subcmd = Some(data[0]); // subcmd
             ctx.last_magnetism_page = Some(data[2]); // page
         }
+        if let ParsedCommand::FwTransferStart { chunks, .. } = parsed {
+            self.context.lock().fw_transfer = (*chunks > 0).then_some((0, *chunks));
+        }
+    }
+
+    /// Claim the next firmware chunk slot if a transfer is in progress.
+    ///
+    /// After FW_TRANSFER START the next `chunks` reports are raw firmware
+    /// bytes, so they must not be decoded as commands.
+    fn take_fw_chunk(&self) -> Option<(u16, u16)> {
+        let mut ctx = self.context.lock();
+        let (index, total) = ctx.fw_transfer?;
+        ctx.fw_transfer = (index + 1 < total).then_some((index + 1, total));
+        Some((index, total))
     }
 
     /// Check if a command should be shown based on filter
@@ -323,19 +339,23 @@ impl Printer {
     /// * `timestamp` - Optional timestamp (for pcap mode)
     /// * `endpoint` - Optional USB endpoint (for pcap mode)
     pub fn on_command(&self, cmd: u8, data: &[u8], timestamp: Option<f64>, endpoint: Option<u8>) {
+        // Build full packet for parsing (cmd + data)
+        let mut packet = vec![cmd];
+        packet.extend_from_slice(data);
+
+        // Firmware chunks have no command byte; file them under FW_TRANSFER
+        let (cmd, parsed) = match self.take_fw_chunk() {
+            Some((index, total)) => (cmd::FW_TRANSFER, ParsedCommand::FwChunk { index, total }),
+            None => (cmd, try_parse_command(&packet)),
+        };
+
         // Update context for stateful response parsing
-        self.update_context(cmd, data);
+        self.update_context(cmd, data, &parsed);
 
         if !self.should_show_command(cmd) {
             return;
         }
 
-        // Build full packet for parsing (cmd + data)
-        let mut packet = vec![cmd];
-        packet.extend_from_slice(data);
-
-        let parsed = try_parse_command(&packet);
-
         match self.config.format {
             OutputFormat::Text => {
                 let ts_prefix = format_timestamp(timestamp);
@@ -380,13 +400,16 @@ impl Printer {
                 }
             }
             OutputFormat::Json => {
-                let cmd_name = cmd::name(cmd);
+                let (cmd_name, payload) = match parsed {
+                    ParsedCommand::FwChunk { .. } => ("FW_CHUNK", &packet[..]),
+                    _ => (cmd::name(cmd), data),
+                };
                 let decoded = DecodedPacket::Command {
                     timestamp: timestamp.unwrap_or(0.0),
                     cmd,
                     cmd_name: cmd_name.to_string(),
                     direction: "CMD".to_string(),
-                    data: format!("{:02x?}", data),
+                    data: format!("{:02x?}", payload),
                 };
                 self.emit(&serde_json::to_string(&decoded).unwrap());
             }
@@ -981,4 +1004,24 @@ mod tests {
             PacketFilter::Cmd(0x8f)
         );
     }
+
+    #[test]
+    fn test_fw_transfer_chunks_tracked() {
+        let printer =
+            Printer::standalone(PrinterConfig::default().with_filter(PacketFilter::Events));
+        // START announcing 2 chunks
+        printer.on_command(0xba, &[0xc0, 0x02, 0x00, 0x80, 0x00, 0x00], None, None);
+        assert_eq!(printer.context.lock().fw_transfer, Some((0, 2)));
+
+        // Chunk payloads are consumed regardless of their first byte
+        printer.on_command(0xe5, &[0x00; 63], None, None);
+        assert_eq!(printer.context.lock().fw_transfer, Some((1, 2)));
+        assert_eq!(printer.context.lock().last_magnetism_subcmd, None);
+        printer.on_command(0x00, &[0xff; 63], None, None);
+        assert_eq!(printer.context.lock().fw_transfer, None);
+
+        // Back to normal decoding
+        printer.on_command(0x8f, &[], None, None);
+        assert_eq!(printer.take_fw_chunk(), None);
+    }
 }
@@ -88,6 +88,15 @@ pub mod cmd {
     /// Note: same byte as GET_CALIBRATION (0xFE) on keyboard.
     pub const SET_RESPONSE_SIZE: u8 = 0xFE;
 
+    // Firmware update (RY bootloader, PAN1082 RF upgrade via dongle)
+    /// ISP prepare: sent with param 0x3A right before ENTER_BOOTLOADER
+    pub const ISP_PREPARE: u8 = 0xC5;
+    /// Enter bootloader: requires 55AA55AA magic, erases config and reboots
+    pub const ENTER_BOOTLOADER: u8 = 0x7F;
+    /// Firmware transfer control, see [`super::fw_transfer`] for sub-commands.
+    /// Sent to the bootloader, or to the dongle while in RF programming mode.
+    pub const FW_TRANSFER: u8 = 0xBA;
+
     // Response status
     pub const STATUS_SUCCESS: u8 = 0xAA;
 
@@ -147,12 +156,38 @@ pub mod cmd {
             GET_RF_INFO => "GET_RF_INFO",
             GET_CACHED_RESPONSE => "GET_CACHED_RESPONSE",
             GET_DONGLE_ID => "GET_DONGLE_ID",
+            ISP_PREPARE => "ISP_PREPARE",
+            ENTER_BOOTLOADER => "ENTER_BOOTLOADER",
+            FW_TRANSFER => "FW_TRANSFER",
             STATUS_SUCCESS => "STATUS_SUCCESS",
             _ => "UNKNOWN",
         }
     }
 }
 
+/// FW_TRANSFER (0xBA) sub-commands
+pub mod fw_transfer {
+    /// Start: [0xBA, 0xC0, chunks(2B LE), size(3B LE)]
+    pub const START: u8 = 0xC0;
+    /// Complete: [0xBA, 0xC2, chunks(2B LE), checksum(4B LE), size(4B LE)]
+    pub const COMPLETE: u8 = 0xC2;
+
+    /// Magic payload for ENTER_BOOTLOADER and ENTER_PAIRING
+    pub const MAGIC: [u8; 4] = [0x55, 0xAA, 0x55, 0xAA];
+
+    /// Size of one firmware data chunk following START
+    pub const CHUNK_SIZE: usize = 64;
+
+    /// Get human-readable name for FW_TRANSFER sub-command
+    pub fn name(subcmd: u8) -> &'static str {
+        match subcmd {
+            START => "START",
+            COMPLETE => "COMPLETE",
+            _ => "UNKNOWN",
+        }
+    }
+}
+
 /// Magnetism (Hall Effect trigger) sub-commands for GET/SET_MULTI_MAGNETISM
 pub mod magnetism {
     /// Press travel (actuation point)
@@ -206,11 +241,40 @@ pub mod magnetism {
     }
 }
 
-/// Key matrix position to name mapping (M1 V5 / SG9000 layout)
+/// Mouse-only commands (PAN1080/RY6601 mouse firmware)
+///
+/// Mice share the keyboard framing and GET_USB_VERSION; their own settings
+/// live behind the vendor's `FEA_CMD_MOUSE_*` commands.
+pub mod mouse {
+    /// Set DPI levels for a profile (vendor name: SET_OPTIONPARAM1)
+    pub const SET_DPI: u8 = 0x54;
+    /// Get DPI levels for a profile (vendor name: GET_OPTIONPARAM1).
+    /// Response: [cmd, profile, current, count, 0.., x LE at 8, y LE at 24, RGB at 40]
+    pub const GET_DPI: u8 = 0xD4;
+
+    /// Most DPI levels a profile holds (x values fill bytes 8..24)
+    pub const MAX_DPI_LEVELS: usize = 8;
+}
+
+/// Key matrix position to name mapping (M1 V5 / SG9000 layout by default)
 ///
 /// Column-major order, 6 rows per column.  Verified against firmware
 /// GET_KEYMATRIX data (factory-default keycodes at each position).
+/// Boards that place keys differently have their own table, chosen with
+/// [`matrix::select_layout`] once the keyboard's device ID is known.
 pub mod matrix {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{PoisonError, RwLock};
+
+    /// Firmware device ID of the MonsGeek M3 V5 HE (GET_USB_VERSION)
+    pub const M3_V5_HE: u32 = 2874;
+
+    /// Firmware device IDs of the FUN60 family (Pro, Max, Ultra, ISO) that
+    /// share the `Common61_gk06` matrix.
+    pub const FUN60: &[u32] = &[
+        2299, 2304, 2305, 2306, 2307, 2352, 2381, 2387, 2464, 2600, 2785, 3299, 3722, 3853,
+    ];
+
     /// Key names indexed by matrix position (column-major order).
     ///
     /// Row 0 = F-key row, rows 1-4 = main alpha/symbol rows,
@@ -235,9 +299,111 @@ pub mod matrix {
         "?", "Home", "PgUp", "PgDn", "End", "Right",
     ];
 
+    /// M3 V5 HE (TKL): same alpha block as the M1 V5, but RWin/Fn/RCtl
+    /// sit one column further right and the nav cluster fills columns 13-15.
+    const M3_V5_HE_KEY_NAMES: &[&str] = &[
+        // Col 0 (0-5)
+        "Esc", "`", "Tab", "Caps", "LShf", "LCtl", // Col 1 (6-11)
+        "F1", "1", "Q", "A", "?", "Win", // Col 2 (12-17)
+        "F2", "2", "W", "S", "Z", "LAlt", // Col 3 (18-23)
+        "F3", "3", "E", "D", "X", "?", // Col 4 (24-29)
+        "F4", "4", "R", "F", "C", "?", // Col 5 (30-35)
+        "F5", "5", "T", "G", "V", "?", // Col 6 (36-41)
+        "F6", "6", "Y", "H", "B", "Spc", // Col 7 (42-47)
+        "F7", "7", "U", "J", "N", "?", // Col 8 (48-53)
+        "F8", "8", "I", "K", "M", "?", // Col 9 (54-59)
+        "F9", "9", "O", "L", ",", "RAlt", // Col 10 (60-65)
+        "F10", "0", "P", ";", ".", "RWin", // Col 11 (66-71)
+        "F11", "-", "[", "'", "/", "Fn", // Col 12 (72-77)
+        "F12", "=", "]", "?", "RShf", "RCtl", // Col 13 (78-83)
+        "?", "Bksp", "\\", "Ent", "?", "Left", // Col 14 (84-89)
+        "PrtSc", "Ins", "Del", "Pause", "Up", "Down", // Col 15 (90-95)
+        "ScrLk", "Home", "End", "PgUp", "PgDn", "Right",
+    ];
+
+    /// FUN60 (60%): no F-row (F1-F12 live on the Fn layer over the number row),
+    /// Esc in the backtick slot, and Alt/Fn/Menu/Ctrl on the right of the
+    /// bottom row. The matrix ends at column 13.
+    const FUN60_KEY_NAMES: &[&str] = &[
+        // Col 0 (0-5)
+        "?", "Esc", "Tab", "Caps", "LShf", "LCtl", // Col 1 (6-11)
+        "?", "1", "Q", "A", "?", "?", // Col 2 (12-17)
+        "?", "2", "W", "S", "Z", "Win", // Col 3 (18-23)
+        "?", "3", "E", "D", "X", "LAlt", // Col 4 (24-29)
+        "?", "4", "R", "F", "C", "?", // Col 5 (30-35)
+        "?", "5", "T", "G", "V", "?", // Col 6 (36-41)
+        "?", "6", "Y", "H", "B", "Spc", // Col 7 (42-47)
+        "?", "7", "U", "J", "N", "?", // Col 8 (48-53)
+        "?", "8", "I", "K", "M", "?", // Col 9 (54-59)
+        "?", "9", "O", "L", ",", "?", // Col 10 (60-65)
+        "?", "0", "P", ";", ".", "RAlt", // Col 11 (66-71)
+        "?", "-", "[", "'", "/", "Fn", // Col 12 (72-77)
+        "?", "=", "]", "?", "RShf", "Menu", // Col 13 (78-83)
+        "?", "Bksp", "\\", "Ent", "?", "RCtl",
+    ];
+
+    /// Device ID whose table [`key_name`] and [`key_index_from_name`] use.
+    static ACTIVE_LAYOUT: AtomicU32 = AtomicU32::new(0);
+
+    /// Tables added with [`install_layout`], by device ID.
+    static INSTALLED: RwLock<Vec<(u32, &'static [&'static str])>> = RwLock::new(Vec::new());
+
+    fn installed(device_id: u32) -> Option<&'static [&'static str]> {
+        INSTALLED
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .find(|(id, _)| *id == device_id)
+            .map(|&(_, names)| names)
+    }
+
+    /// Key names for the board with this firmware device ID. Boards without
+    /// a table of their own use the M1 V5 layout.
+    pub fn key_names_for(device_id: u32) -> &'static [&'static str] {
+        if let Some(names) = installed(device_id) {
+            return names;
+        }
+        match device_id {
+            M3_V5_HE => M3_V5_HE_KEY_NAMES,
+            id if FUN60.contains(&id) => FUN60_KEY_NAMES,
+            _ => KEY_NAMES,
+        }
+    }
+
+    /// Use `names` as the table of the board with this device ID, for boards
+    /// the built-in tables don't describe (numpads and other small boards,
+    /// layouts from a device database). `"?"` marks empty positions.
+    ///
+    /// Tables live for the rest of the process; installing a different table
+    /// for the same ID replaces it, so do it once per connected board.
+    pub fn install_layout(device_id: u32, names: Vec<&'static str>) {
+        let mut tables = INSTALLED.write().unwrap_or_else(PoisonError::into_inner);
+        let table = match tables.iter_mut().find(|(id, _)| *id == device_id) {
+            Some((_, table)) if **table == *names => return,
+            Some((_, table)) => table,
+            None => {
+                tables.push((device_id, &[]));
+                &mut tables.last_mut().expect("just pushed").1
+            }
+        };
+        *table = Box::leak(names.into_boxed_slice());
+    }
+
+    /// Use the layout of the board with this device ID for all name lookups.
+    ///
+    /// Called when a keyboard is opened; the process talks to one board at a
+    /// time, so a single active layout is enough.
+    pub fn select_layout(device_id: u32) {
+        ACTIVE_LAYOUT.store(device_id, Ordering::Relaxed);
+    }
+
+    fn key_names() -> &'static [&'static str] {
+        key_names_for(ACTIVE_LAYOUT.load(Ordering::Relaxed))
+    }
+
     /// Get key name from matrix position
     pub fn key_name(index: u8) -> &'static str {
-        KEY_NAMES.get(index as usize).copied().unwrap_or("?")
+        key_names().get(index as usize).copied().unwrap_or("?")
     }
 
     /// Look up matrix index from key name (case-insensitive)
@@ -245,7 +411,7 @@ pub mod matrix {
     /// Returns None if no matching key name is found.
     pub fn key_index_from_name(name: &str) -> Option<u8> {
         let name_lower = name.to_ascii_lowercase();
-        KEY_NAMES
+        key_names()
             .iter()
             .position(|&n| n.to_ascii_lowercase() == name_lower && n != "?")
             .map(|i| i as u8)
@@ -656,6 +822,9 @@ pub mod device {
     /// HID usage for input interface (USB)
     pub const USAGE_INPUT: u16 = 0x01;
 
+    /// HID usage page of the bootloader's vendor interface
+    pub const BOOT_USAGE_PAGE: u16 = 0xFF01;
+
     /// Feature interface number
     pub const INTERFACE_FEATURE: i32 = 2;
     /// Input interface number
@@ -0,0 +1,206 @@
+//! Firmware quirks
+//!
+//! What differs between firmware builds, as one table keyed by device ID and
+//! firmware version range instead of version checks spread over the callers.
+//! [`Quirks::for_firmware`] starts from the behaviour of current firmware and
+//! applies every matching [`QUIRKS`] entry in order, so later entries win.
+//!
+//! Versions are the GET_USB_VERSION value (0x0400 = v4.00). The entries come
+//! from the vendor app's own version checks.
+//!
+//! [`FlowControlTransport`](crate::FlowControlTransport) refuses the commands
+//! a firmware lacks (see [`Quirks::allows`]) before they reach the device.
+
+use std::ops::RangeInclusive;
+
+use crate::protocol::{cmd, magnetism, precision};
+
+/// One difference from current firmware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quirk {
+    /// Trigger (magnetism) commands aren't implemented
+    NoMagnetism,
+    /// The top deadzone table isn't implemented
+    NoTopDeadzone,
+    /// Travel values count in steps of 1/n mm
+    TravelStepsPerMm(u16),
+    /// Payload bytes per SET_MULTI_MAGNETISM page
+    MagnetismPageSize(usize),
+    /// Pause after each SET_MULTI_MAGNETISM page, in ms
+    MagnetismPageDelayMs(u64),
+}
+
+/// One row of [`QUIRKS`].
+#[derive(Debug, Clone)]
+pub struct QuirkEntry {
+    /// Firmware device IDs this applies to; empty for every device
+    pub device_ids: &'static [u32],
+    /// Firmware versions this applies to
+    pub versions: RangeInclusive<u16>,
+    pub quirks: &'static [Quirk],
+    /// Where the entry comes from
+    pub note: &'static str,
+}
+
+impl QuirkEntry {
+    fn matches(&self, device_id: Option<u32>, version: u16) -> bool {
+        let device =
+            self.device_ids.is_empty() || device_id.is_some_and(|id| self.device_ids.contains(&id));
+        device && self.versions.contains(&version)
+    }
+}
+
+/// Known firmware differences, oldest first.
+pub const QUIRKS: &[QuirkEntry] = &[
+    QuirkEntry {
+        device_ids: &[],
+        versions: 0..=0x01FF,
+        quirks: &[Quirk::NoMagnetism],
+        note: "vendor app enables trigger settings from v2.00 (checkIsSupportMagnetism)",
+    },
+    QuirkEntry {
+        device_ids: &[],
+        versions: 0..=precision::MEDIUM_VERSION - 1,
+        quirks: &[Quirk::TravelStepsPerMm(10)],
+        note: "0.1 mm travel steps before v3.00",
+    },
+    QuirkEntry {
+        device_ids: &[],
+        versions: precision::MEDIUM_VERSION..=precision::FINE_VERSION - 1,
+        quirks: &[Quirk::TravelStepsPerMm(100)],
+        note: "0.01 mm travel steps before v5.00",
+    },
+    QuirkEntry {
+        device_ids: &[],
+        versions: 0..=0x03FF,
+        quirks: &[Quirk::NoTopDeadzone],
+        note: "vendor app reads the top deadzone from v4.00",
+    },
+];
+
+/// Behaviour of one firmware build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// Trigger (magnetism) commands are implemented
+    pub magnetism: bool,
+    /// The top deadzone table is implemented
+    pub top_deadzone: bool,
+    /// Travel values count in steps of 1/n mm (10, 100 or 200)
+    pub travel_steps_per_mm: u16,
+    /// Payload bytes per SET_MULTI_MAGNETISM page
+    pub magnetism_page_size: usize,
+    /// Pause after each SET_MULTI_MAGNETISM page, in ms
+    pub magnetism_page_delay_ms: u64,
+}
+
+impl Default for Quirks {
+    /// Current firmware; also used when the version can't be read.
+    fn default() -> Self {
+        Self {
+            magnetism: true,
+            top_deadzone: true,
+            travel_steps_per_mm: 200,
+            magnetism_page_size: 56,
+            magnetism_page_delay_ms: 30,
+        }
+    }
+}
+
+impl Quirks {
+    /// Behaviour of firmware `version` on the board with this device ID.
+    pub fn for_firmware(device_id: Option<u32>, version: u16) -> Self {
+        let mut quirks = Self::default();
+        for entry in QUIRKS.iter().filter(|e| e.matches(device_id, version)) {
+            for &quirk in entry.quirks {
+                quirks.apply(quirk);
+            }
+        }
+        quirks
+    }
+
+    /// Whether `cmd_byte` depends on the firmware at all; only these are
+    /// checked with [`Self::allows`].
+    pub fn gates(cmd_byte: u8) -> bool {
+        matches!(
+            cmd_byte,
+            cmd::GET_MULTI_MAGNETISM
+                | cmd::SET_MULTI_MAGNETISM
+                | cmd::GET_KEY_MAGNETISM_MODE
+                | cmd::SET_KEY_MAGNETISM_MODE
+        )
+    }
+
+    /// Whether this firmware implements `cmd_byte` with this payload.
+    /// Multi-magnetism payloads start with the sub-command.
+    pub fn allows(&self, cmd_byte: u8, data: &[u8]) -> bool {
+        match cmd_byte {
+            cmd::GET_MULTI_MAGNETISM | cmd::SET_MULTI_MAGNETISM => {
+                self.magnetism
+                    && (self.top_deadzone || data.first() != Some(&magnetism::TOP_DEADZONE))
+            }
+            cmd::GET_KEY_MAGNETISM_MODE | cmd::SET_KEY_MAGNETISM_MODE => self.magnetism,
+            _ => true,
+        }
+    }
+
+    fn apply(&mut self, quirk: Quirk) {
+        match quirk {
+            Quirk::NoMagnetism => self.magnetism = false,
+            Quirk::NoTopDeadzone => self.top_deadzone = false,
+            Quirk::TravelStepsPerMm(steps) => self.travel_steps_per_mm = steps,
+            Quirk::MagnetismPageSize(size) => self.magnetism_page_size = size,
+            Quirk::MagnetismPageDelayMs(ms) => self.magnetism_page_delay_ms = ms,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_ranges_pick_the_vendor_behaviour() {
+        let old = Quirks::for_firmware(None, 0x0107);
+        assert!(!old.magnetism);
+        assert!(!old.top_deadzone);
+        assert_eq!(old.travel_steps_per_mm, 10);
+
+        let v3 = Quirks::for_firmware(Some(2949), 0x0300);
+        assert!(v3.magnetism);
+        assert!(!v3.top_deadzone);
+        assert_eq!(v3.travel_steps_per_mm, 100);
+
+        assert_eq!(
+            Quirks::for_firmware(Some(2949), 0x0407).travel_steps_per_mm,
+            100
+        );
+        assert_eq!(Quirks::for_firmware(Some(2949), 0x0500), Quirks::default());
+    }
+
+    #[test]
+    fn old_firmware_refuses_trigger_commands() {
+        let v1 = Quirks::for_firmware(None, 0x0107);
+        assert!(!v1.allows(cmd::GET_MULTI_MAGNETISM, &[magnetism::PRESS_TRAVEL]));
+        assert!(!v1.allows(cmd::SET_KEY_MAGNETISM_MODE, &[]));
+        assert!(v1.allows(cmd::GET_LEDPARAM, &[]));
+
+        let v3 = Quirks::for_firmware(None, 0x0300);
+        assert!(v3.allows(cmd::SET_MULTI_MAGNETISM, &[magnetism::PRESS_TRAVEL]));
+        assert!(!v3.allows(cmd::SET_MULTI_MAGNETISM, &[magnetism::TOP_DEADZONE]));
+        assert!(Quirks::default().allows(cmd::GET_MULTI_MAGNETISM, &[magnetism::TOP_DEADZONE]));
+        assert!(!Quirks::gates(cmd::GET_USB_VERSION));
+    }
+
+    #[test]
+    fn device_entries_only_match_their_devices() {
+        let entry = QuirkEntry {
+            device_ids: &[2949],
+            versions: 0..=u16::MAX,
+            quirks: &[Quirk::MagnetismPageSize(32)],
+            note: "test",
+        };
+        assert!(entry.matches(Some(2949), 0x0500));
+        assert!(!entry.matches(Some(2874), 0x0500));
+        assert!(!entry.matches(None, 0x0500));
+    }
+}
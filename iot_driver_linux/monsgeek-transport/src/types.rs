@@ -247,6 +247,30 @@ pub struct RfInfo {
     pub firmware_version_major: u8,
 }
 
+/// Device sitting in bootloader mode, found by
+/// [`HidDiscovery::list_boot_mode_devices`](crate::HidDiscovery::list_boot_mode_devices).
+///
+/// It doesn't speak the vendor protocol, so it can't be opened as a
+/// [`Transport`](crate::Transport); only the firmware transfer talks to it.
+#[derive(Debug, Clone)]
+pub struct BootModeDevice {
+    pub vid: u16,
+    pub pid: u16,
+    pub kind: crate::device_registry::BootModeKind,
+    /// HID path of the bootloader interface
+    pub path: String,
+    pub product_name: Option<String>,
+}
+
+impl BootModeDevice {
+    /// Open the bootloader interface for feature report I/O.
+    pub fn open(&self, api: &hidapi::HidApi) -> Result<hidapi::HidDevice, crate::TransportError> {
+        let path = std::ffi::CString::new(self.path.as_str())
+            .map_err(|e| crate::TransportError::DeviceNotFound(e.to_string()))?;
+        api.open_path(&path).map_err(crate::TransportError::from)
+    }
+}
+
 /// Discovered device that can be opened
 #[derive(Debug, Clone)]
 pub struct DiscoveredDevice {
@@ -263,10 +287,18 @@ pub struct DeviceLabel {
     pub model_name: String,
     /// Transport type short name: "usb", "dongle", "bt"
     pub transport_name: &'static str,
+    /// Keyboard or mouse
+    pub kind: crate::device_registry::DeviceKind,
     /// Firmware device ID if probed
     pub device_id: Option<u32>,
     /// Firmware version if probed
     pub version: Option<u16>,
+    /// USB Vendor ID
+    pub vid: u16,
+    /// USB Product ID
+    pub pid: u16,
+    /// USB serial number, if the device reports one
+    pub serial: Option<String>,
     /// HID device path
     pub hid_path: String,
 }
@@ -275,12 +307,18 @@ impl std::fmt::Display for DeviceLabel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "#{:<2} {:<20} {:<8}",
-            self.index, self.model_name, self.transport_name
+            "#{:<2} {:<20} {:<8} {:04x}:{:04x}",
+            self.index, self.model_name, self.transport_name, self.vid, self.pid
         )?;
+        if self.kind == crate::device_registry::DeviceKind::Mouse {
+            write!(f, " (mouse)")?;
+        }
         if let (Some(id), Some(ver)) = (self.device_id, self.version) {
             write!(f, " [{id} v{}.{:02}]", ver / 100, ver % 100)?;
         }
+        if let Some(serial) = &self.serial {
+            write!(f, " sn:{serial}")?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,339 @@
+//! Ambient idle effect: external state as subtle key zone colors.
+//!
+//! Configured under `[ambient]` in `settings.toml` and run by the daemon.
+//! Once the keyboard has been idle for a while, each `[[ambient.zones]]`
+//! entry whose external value holds (see [`crate::effect::state`]) lights
+//! its keys dimly in its color, slowly breathing: an alarm coming up, rain
+//! expected, a build failing — whatever scripts post with `iot_driver state
+//! set`. Nothing here knows about calendars or weather. The next key press
+//! hands the LEDs back to the keyboard. Needs the LED streaming patch.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::effect::state::{ExternalState, StateCondition};
+use crate::evdev::EventReader;
+use crate::idle_dim::IdleTracker;
+use crate::keyboard_config::parse_color;
+use crate::led_stream::send_overlay_diff;
+use crate::notify::keymap::{parse_key_target, MATRIX_LEN};
+use monsgeek_keyboard::{KeyboardInterface, RgbColor};
+
+/// Frame interval while showing.
+const TICK: Duration = Duration::from_millis(100);
+/// How often the daemon re-reads `settings.toml` and the state file.
+const CONFIG_RELOAD: Duration = Duration::from_secs(5);
+/// How long the daemon waits before retrying a keyboard without streaming.
+const RETRY: Duration = Duration::from_secs(60);
+/// Resend every lit key this often, in case the keyboard slept and lost the
+/// overlay.
+const FULL_REFRESH: Duration = Duration::from_secs(10);
+
+/// One state-to-zone mapping, `[[ambient.zones]]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AmbientZone {
+    #[serde(flatten)]
+    pub when: StateCondition,
+    /// Key target, as for `iot_driver notify` (`frow`, `numbers`, `F9..F12`)
+    pub keys: String,
+    /// `#RRGGBB`, shown at `[ambient] brightness`
+    pub color: String,
+}
+
+/// `[ambient]` settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AmbientConfig {
+    pub enabled: bool,
+    /// Minutes without a key press before showing
+    pub idle_minutes: f32,
+    /// Peak brightness of the zone colors, 0-100
+    pub brightness: u8,
+    /// Length of one slow breath (0 = steady)
+    pub breathe_seconds: f32,
+    /// Drawn in order; later zones win on shared keys
+    pub zones: Vec<AmbientZone>,
+}
+
+impl Default for AmbientConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_minutes: 2.0,
+            brightness: 30,
+            breathe_seconds: 6.0,
+            zones: Vec::new(),
+        }
+    }
+}
+
+impl AmbientConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs_f32(self.idle_minutes.max(0.1) * 60.0)
+    }
+
+    /// Resolve key targets and colors, failing on the first bad zone.
+    pub fn resolve(&self) -> Result<Vec<Zone>, String> {
+        self.zones
+            .iter()
+            .map(|z| {
+                let name = &z.when.state;
+                let indices = parse_key_target(&z.keys)
+                    .map_err(|e| format!("ambient zone '{name}': {e}"))?
+                    .indices;
+                let color = parse_color(&z.color)
+                    .ok_or_else(|| format!("ambient zone '{name}': invalid color '{}'", z.color))?;
+                Ok(Zone {
+                    when: z.when.clone(),
+                    indices,
+                    color,
+                })
+            })
+            .collect()
+    }
+
+    /// Gain for the whole frame `t` into showing: brightness times a slow
+    /// breath between half and full.
+    pub fn gain(&self, t: Duration) -> f32 {
+        let peak = self.brightness.min(100) as f32 / 100.0;
+        if self.breathe_seconds <= 0.0 {
+            return peak;
+        }
+        let phase = t.as_secs_f32() / self.breathe_seconds * std::f32::consts::TAU;
+        peak * (0.75 + 0.25 * phase.cos())
+    }
+}
+
+/// An [`AmbientZone`] resolved to LED indices and a color.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub when: StateCondition,
+    pub indices: Vec<usize>,
+    pub color: RgbColor,
+}
+
+/// Render the zones whose condition holds at `now` (Unix seconds) with
+/// `gain`; `None` if none does. Other keys stay dark (firmware lighting).
+pub fn render(
+    zones: &[Zone],
+    state: &ExternalState,
+    now: u64,
+    gain: f32,
+) -> Option<[(u8, u8, u8); MATRIX_LEN]> {
+    let mut leds = [(0, 0, 0); MATRIX_LEN];
+    let mut any = false;
+    for zone in zones.iter().filter(|z| z.when.holds(state, now)) {
+        any = true;
+        let s = |v: u8| (v as f32 * gain).round() as u8;
+        for &i in &zone.indices {
+            if let Some(led) = leds.get_mut(i) {
+                *led = (s(zone.color.r), s(zone.color.g), s(zone.color.b));
+            }
+        }
+    }
+    any.then_some(leds)
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// What the keyboard shows.
+#[derive(Default)]
+struct Overlay {
+    shown: Option<[(u8, u8, u8); MATRIX_LEN]>,
+    last_full: Option<Instant>,
+}
+
+impl Overlay {
+    fn show(
+        &mut self,
+        kb: &KeyboardInterface,
+        leds: [(u8, u8, u8); MATRIX_LEN],
+    ) -> Result<(), String> {
+        let full = self.last_full.is_none_or(|t| t.elapsed() >= FULL_REFRESH);
+        let prev = match self.shown {
+            Some(prev) if !full => prev,
+            _ => {
+                self.last_full = Some(Instant::now());
+                [(0, 0, 0); MATRIX_LEN]
+            }
+        };
+        send_overlay_diff(kb, &prev, &leds).map_err(|e| format!("send: {e}"))?;
+        self.shown = Some(leds);
+        Ok(())
+    }
+
+    fn release(&mut self, kb: &KeyboardInterface) {
+        if self.shown.take().is_some() {
+            kb.stream_led_release().ok();
+        }
+        self.last_full = None;
+    }
+}
+
+/// Daemon loop: show the `[ambient]` zones while the keyboard is idle,
+/// re-reading the settings and the state file every [`CONFIG_RELOAD`]. The
+/// keyboard is opened on demand and reopened after errors.
+pub fn run_daemon_ambient(open: impl Fn() -> Result<KeyboardInterface, String>) {
+    let mut keyboard: Option<(KeyboardInterface, EventReader)> = None;
+    let mut tracker = IdleTracker::new(Instant::now());
+    let mut overlay = Overlay::default();
+    let mut config = AmbientConfig::default();
+    let mut zones = Vec::new();
+    let mut state = ExternalState::default();
+    let mut next_reload = Instant::now();
+    let mut showing_since: Option<Instant> = None;
+    loop {
+        if Instant::now() >= next_reload {
+            let loaded = crate::settings::Settings::load().ambient;
+            if loaded != config {
+                zones = loaded.resolve().unwrap_or_else(|e| {
+                    tracing::warn!("ambient: {e}");
+                    Vec::new()
+                });
+                config = loaded;
+            }
+            state = ExternalState::load();
+            next_reload = Instant::now() + CONFIG_RELOAD;
+        }
+        if !config.enabled {
+            if let Some((kb, _)) = keyboard.take() {
+                overlay.release(&kb);
+            }
+            std::thread::sleep(CONFIG_RELOAD);
+            continue;
+        }
+
+        if keyboard.is_none() {
+            match open() {
+                Ok(kb) if crate::sysmon::supports_streaming(&kb) => {
+                    let reader = EventReader::open(kb.vid(), kb.pid());
+                    if reader.is_empty() {
+                        tracing::warn!(
+                            "ambient: no readable input nodes (not in the 'input' group?)"
+                        );
+                        std::thread::sleep(RETRY);
+                        continue;
+                    }
+                    tracker.input(Instant::now());
+                    keyboard = Some((kb, reader));
+                }
+                Ok(_) => {
+                    tracing::warn!("ambient: firmware has no LED streaming patch");
+                    std::thread::sleep(RETRY);
+                    continue;
+                }
+                Err(e) => {
+                    tracing::debug!("ambient: no keyboard: {e}");
+                    std::thread::sleep(CONFIG_RELOAD);
+                    continue;
+                }
+            }
+        }
+        let Some((kb, reader)) = &keyboard else {
+            continue;
+        };
+
+        if reader
+            .poll(TICK)
+            .iter()
+            .any(|ev| ev.is_key_edge() && ev.value == 1)
+        {
+            tracker.input(Instant::now());
+        }
+        let now = Instant::now();
+        let idle = tracker.idle_since(now, config.idle_timeout()).is_some()
+            && !crate::lock_lighting::is_locked();
+        let frame = if idle {
+            let since = *showing_since.get_or_insert(now);
+            render(&zones, &state, unix_now(), config.gain(now - since))
+        } else {
+            None
+        };
+        match frame {
+            Some(leds) => {
+                if let Err(e) = overlay.show(kb, leds) {
+                    tracing::warn!("ambient: {e}");
+                    overlay = Overlay::default();
+                    keyboard = None;
+                }
+            }
+            None => {
+                showing_since = None;
+                overlay.release(kb);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AmbientConfig {
+        toml::from_str(
+            r##"
+            enabled = true
+            [[zones]]
+            state = "calendar.soon"
+            keys = "frow"
+            color = "#FF8000"
+            [[zones]]
+            state = "weather.rain"
+            above = 0.5
+            keys = "F12"
+            color = "#0040FF"
+            "##,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn shows_only_zones_whose_state_holds() {
+        let zones = config().resolve().unwrap();
+        let mut state = ExternalState::default();
+        assert!(render(&zones, &state, 0, 1.0).is_none());
+
+        state.set("weather.rain", 0.3, None);
+        assert!(render(&zones, &state, 0, 1.0).is_none());
+        state.set("calendar.soon", 1.0, None);
+        let leds = render(&zones, &state, 0, 1.0).unwrap();
+        let f12 = *zones[1].indices.last().unwrap();
+        assert_eq!(leds[zones[0].indices[0]], (255, 128, 0));
+        // F12 is in the F-row; the rain zone would win but isn't active
+        assert_eq!(leds[f12], (255, 128, 0));
+
+        state.set("weather.rain", 0.9, None);
+        let leds = render(&zones, &state, 0, 1.0).unwrap();
+        assert_eq!(leds[f12], (0, 64, 255));
+        assert_eq!(leds.iter().filter(|&&c| c != (0, 0, 0)).count(), 12);
+    }
+
+    #[test]
+    fn breathes_gently_around_the_brightness() {
+        let config = config();
+        assert!((config.gain(Duration::ZERO) - 0.3).abs() < 1e-4);
+        assert!((config.gain(Duration::from_secs(3)) - 0.15).abs() < 1e-4);
+        let steady = AmbientConfig {
+            breathe_seconds: 0.0,
+            ..config
+        };
+        assert_eq!(steady.gain(Duration::from_secs(3)), 0.3);
+    }
+
+    #[test]
+    fn bad_zones_are_reported() {
+        let mut config = config();
+        config.zones[0].color = "orange".to_string();
+        assert!(config.resolve().unwrap_err().contains("calendar.soon"));
+    }
+}
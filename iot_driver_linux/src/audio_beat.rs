@@ -0,0 +1,354 @@
+//! Beat detection and beat-driven lighting (`iot_driver audio --mode beat`).
+//!
+//! The FFT thread feeds bass energy into a [`BeatDetector`]: a beat is an
+//! energy spike well above the last second's average, judged against how
+//! much the energy varies (so busy mixes need a bigger jump than sparse
+//! ones). Each beat triggers an attack/decay [`Envelope`] that drives the
+//! whole board: pulse, strobe or a hue step, streamed over the patch LED
+//! protocol.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::audio_reactive::{AudioCapture, AudioConfig};
+use crate::keyboard_config::parse_color;
+use crate::led_stream::{apply_power_budget, send_full_frame};
+use crate::notify::keymap::MATRIX_LEN;
+use monsgeek_keyboard::{KeyboardInterface, RgbColor};
+
+/// Energy history the threshold is judged against (~1s at 50 Hz).
+const HISTORY: usize = 50;
+/// Beats closer than this are one beat (caps detection at 300 BPM).
+const MIN_BEAT_INTERVAL: Duration = Duration::from_millis(200);
+/// Standard deviations above the mean that trigger at sensitivity 1.0.
+const BASE_THRESHOLD: f32 = 1.5;
+/// Energies this small are silence, never beats.
+const ENERGY_FLOOR: f32 = 1e-6;
+/// Hue step per beat in hue mode (degrees).
+const HUE_STEP: f32 = 45.0;
+
+/// Bass energy of the analyzer's low bands (kick range, ~20-200 Hz).
+pub fn bass_energy(bands: &[f32]) -> f32 {
+    bands.iter().take(4).map(|b| b * b).sum()
+}
+
+/// Onset detector over a stream of energy values.
+pub struct BeatDetector {
+    history: VecDeque<f32>,
+    /// Standard deviations above the mean needed for a beat
+    threshold: f32,
+    last_beat: Option<Instant>,
+}
+
+impl BeatDetector {
+    /// `sensitivity` scales how easily beats trigger (1.0 = default,
+    /// higher = more beats).
+    pub fn new(sensitivity: f32) -> Self {
+        Self {
+            history: VecDeque::with_capacity(HISTORY),
+            threshold: BASE_THRESHOLD / sensitivity.max(0.1),
+            last_beat: None,
+        }
+    }
+
+    /// Feed one frame's energy; returns whether it is a beat.
+    pub fn update(&mut self, energy: f32, now: Instant) -> bool {
+        let beat = self.history.len() >= HISTORY / 2 && {
+            let n = self.history.len() as f32;
+            let mean = self.history.iter().sum::<f32>() / n;
+            let var = self.history.iter().map(|e| (e - mean).powi(2)).sum::<f32>() / n;
+            energy > ENERGY_FLOOR
+                && energy > mean * 1.2
+                && energy > mean + self.threshold * var.sqrt()
+                && self
+                    .last_beat
+                    .is_none_or(|t| now.duration_since(t) >= MIN_BEAT_INTERVAL)
+        };
+        if beat {
+            self.last_beat = Some(now);
+        }
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(energy);
+        beat
+    }
+}
+
+/// Attack/decay envelope: rises linearly to 1.0 over `attack` when
+/// triggered, then falls exponentially with time constant `decay`.
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    attack: Duration,
+    decay: Duration,
+    level: f32,
+    rising: bool,
+}
+
+impl Envelope {
+    pub fn new(attack: Duration, decay: Duration) -> Self {
+        Self {
+            attack,
+            decay,
+            level: 0.0,
+            rising: false,
+        }
+    }
+
+    pub fn trigger(&mut self) {
+        self.rising = true;
+    }
+
+    /// Advance by `dt` and return the level (0.0-1.0).
+    pub fn step(&mut self, dt: Duration) -> f32 {
+        if self.rising {
+            if self.attack.is_zero() {
+                self.level = 1.0;
+            } else {
+                self.level += dt.as_secs_f32() / self.attack.as_secs_f32();
+            }
+            if self.level >= 1.0 {
+                self.level = 1.0;
+                self.rising = false;
+            }
+        } else if self.decay.is_zero() {
+            self.level = 0.0;
+        } else {
+            self.level *= (-dt.as_secs_f32() / self.decay.as_secs_f32()).exp();
+        }
+        self.level
+    }
+}
+
+/// What the board does on a beat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BeatEffect {
+    /// Fade from the idle color to the beat color and back
+    #[default]
+    Pulse,
+    /// Flash the beat color, dark in between
+    Strobe,
+    /// Step the hue on every beat, brightness following the envelope
+    Hue,
+}
+
+/// Beat effect settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeatStyle {
+    pub effect: BeatEffect,
+    /// Color between beats (pulse)
+    pub idle: RgbColor,
+    /// Color on the beat (pulse, strobe)
+    pub beat: RgbColor,
+    pub attack: Duration,
+    pub decay: Duration,
+}
+
+impl Default for BeatStyle {
+    fn default() -> Self {
+        Self {
+            effect: BeatEffect::Pulse,
+            idle: RgbColor::new(0, 0, 40),
+            beat: RgbColor::new(255, 0, 128),
+            attack: Duration::from_millis(10),
+            decay: Duration::from_millis(150),
+        }
+    }
+}
+
+/// Parse `--colors` for beat mode: `IDLE,BEAT` as `#RRGGBB`.
+pub fn parse_colors(s: &str) -> Result<(RgbColor, RgbColor), String> {
+    let color = |c: &str| parse_color(c).ok_or_else(|| format!("invalid color '{c}'"));
+    match s.split(',').map(str::trim).collect::<Vec<_>>().as_slice() {
+        [idle, beat] => Ok((color(idle)?, color(beat)?)),
+        _ => Err(format!("expected IDLE,BEAT colors, got '{s}'")),
+    }
+}
+
+/// Turns beats into board colors.
+pub struct BeatRenderer {
+    style: BeatStyle,
+    envelope: Envelope,
+    hue: f32,
+}
+
+impl BeatRenderer {
+    pub fn new(style: BeatStyle) -> Self {
+        Self {
+            envelope: Envelope::new(style.attack, style.decay),
+            style,
+            hue: 0.0,
+        }
+    }
+
+    /// Color for a frame `dt` after the previous one; `beat` if a beat was
+    /// detected since.
+    pub fn color(&mut self, beat: bool, dt: Duration) -> (u8, u8, u8) {
+        if beat {
+            self.envelope.trigger();
+            self.hue = (self.hue + HUE_STEP) % 360.0;
+        }
+        let level = self.envelope.step(dt);
+        let (idle, on) = (self.style.idle, self.style.beat);
+        match self.style.effect {
+            BeatEffect::Pulse => {
+                let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * level).round() as u8;
+                (mix(idle.r, on.r), mix(idle.g, on.g), mix(idle.b, on.b))
+            }
+            BeatEffect::Strobe if level > 0.5 => (on.r, on.g, on.b),
+            BeatEffect::Strobe => (0, 0, 0),
+            BeatEffect::Hue => hsv(self.hue, 0.25 + 0.75 * level),
+        }
+    }
+}
+
+/// Fully saturated color of `hue` degrees at brightness `value`.
+fn hsv(hue: f32, value: f32) -> (u8, u8, u8) {
+    let h = hue.rem_euclid(360.0) / 60.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    let (r, g, b) = match h as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    let v = |c: f32| (c * value * 255.0).round() as u8;
+    (v(r), v(g), v(b))
+}
+
+/// Run beat lighting (blocking) until `running` clears.
+///
+/// The keyboard must support patch LED streaming; the stream is released on
+/// exit so the firmware effect comes back.
+pub fn run_audio_beat(
+    keyboard: &KeyboardInterface,
+    config: AudioConfig,
+    style: BeatStyle,
+    power_budget: u32,
+    running: Arc<AtomicBool>,
+) -> Result<(), String> {
+    let capture = AudioCapture::start(config)?;
+    println!("Audio input: {}", capture.source_label);
+    println!(
+        "Beat effect: {:?}, attack {}ms, decay {}ms",
+        style.effect,
+        style.attack.as_millis(),
+        style.decay.as_millis()
+    );
+    let mut renderer = BeatRenderer::new(style);
+
+    let mut seen = capture.state.beats.load(Ordering::Relaxed);
+    let mut last_frame = Instant::now();
+    let mut result = Ok(());
+    while running.load(Ordering::SeqCst) && capture.state.is_running() {
+        let frame_start = Instant::now();
+        let beats = capture.state.beats.load(Ordering::Relaxed);
+        let color = renderer.color(beats != seen, frame_start - last_frame);
+        seen = beats;
+        last_frame = frame_start;
+
+        let mut leds = [color; MATRIX_LEN];
+        apply_power_budget(&mut leds, power_budget);
+        if let Err(e) = send_full_frame(keyboard, &leds) {
+            result = Err(format!("Failed to send LED frame: {e}"));
+            break;
+        }
+
+        let hz = capture
+            .state
+            .target_hz
+            .load(Ordering::Relaxed)
+            .clamp(5, 120);
+        let frame = Duration::from_millis(1000 / hz as u64);
+        let elapsed = frame_start.elapsed();
+        if elapsed < frame {
+            thread::sleep(frame - elapsed);
+        }
+    }
+
+    capture.stop();
+    keyboard.stream_led_release().ok();
+    println!("Beat lighting stopped");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `frames` at 20ms steps, returning the frames that were beats.
+    fn beats(detector: &mut BeatDetector, frames: &[f32]) -> Vec<usize> {
+        let start = Instant::now();
+        frames
+            .iter()
+            .enumerate()
+            .filter(|&(i, &e)| detector.update(e, start + Duration::from_millis(20 * i as u64)))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    #[test]
+    fn detects_kicks_over_a_noisy_floor() {
+        // Kick every 25 frames (500ms) over a slightly varying floor
+        let frames: Vec<f32> = (0..150)
+            .map(|i| {
+                if i % 25 == 0 {
+                    1.0
+                } else {
+                    0.1 + (i % 3) as f32 * 0.01
+                }
+            })
+            .collect();
+        let found = beats(&mut BeatDetector::new(1.0), &frames);
+        assert_eq!(found, vec![25, 50, 75, 100, 125]);
+    }
+
+    #[test]
+    fn ignores_silence_steady_level_and_close_repeats() {
+        assert!(beats(&mut BeatDetector::new(1.0), &[0.0; 100]).is_empty());
+        assert!(beats(&mut BeatDetector::new(1.0), &[0.5; 100]).is_empty());
+        // Two spikes 40ms apart count once
+        let mut frames = vec![0.1; 60];
+        frames[40] = 1.0;
+        frames[42] = 1.0;
+        assert_eq!(beats(&mut BeatDetector::new(2.0), &frames), vec![40]);
+    }
+
+    #[test]
+    fn envelope_attacks_then_decays() {
+        let mut env = Envelope::new(Duration::from_millis(20), Duration::from_millis(100));
+        env.trigger();
+        assert!((env.step(Duration::from_millis(10)) - 0.5).abs() < 1e-4);
+        assert_eq!(env.step(Duration::from_millis(10)), 1.0);
+        let after = env.step(Duration::from_millis(100));
+        assert!((after - (-1.0f32).exp()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn effects_follow_the_envelope() {
+        let instant = |effect| BeatStyle {
+            effect,
+            attack: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut pulse = BeatRenderer::new(instant(BeatEffect::Pulse));
+        assert_eq!(pulse.color(false, Duration::ZERO), (0, 0, 40));
+        assert_eq!(pulse.color(true, Duration::ZERO), (255, 0, 128));
+
+        let mut strobe = BeatRenderer::new(instant(BeatEffect::Strobe));
+        assert_eq!(strobe.color(true, Duration::ZERO), (255, 0, 128));
+        assert_eq!(strobe.color(false, Duration::from_secs(1)), (0, 0, 0));
+
+        let mut hue = BeatRenderer::new(instant(BeatEffect::Hue));
+        let first = hue.color(true, Duration::ZERO);
+        assert_ne!(hue.color(true, Duration::ZERO), first);
+
+        assert_eq!(parse_colors("#000000, ffffff").unwrap().1.g, 255);
+        assert!(parse_colors("#000000").is_err());
+    }
+}
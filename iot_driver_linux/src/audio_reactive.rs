@@ -9,8 +9,10 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
+use crate::audio_beat;
+use crate::audio_spectrum;
+use crate::pipewire_audio;
 use crate::protocol::{audio_viz, cmd};
-use crate::pulse;
 use monsgeek_keyboard::KeyboardInterface;
 use monsgeek_transport::{ChecksumType, Transport};
 
@@ -44,12 +46,23 @@ const LEVEL_CURVE: f32 = 0.6;
 /// attack) and fall by this factor each FFT frame, giving a punchy beat pulse.
 const DECAY: f32 = 0.85;
 
+/// Retry interval while the capture target is unavailable.
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
+/// How often to check whether the target moved (default sink changed, app
+/// switched output), since nothing errors when that happens.
+const RETARGET_CHECK: Duration = Duration::from_secs(2);
+
 /// Audio reactive state shared between threads
 pub struct AudioState {
     /// Current frequency band magnitudes (0.0 - 1.0)
     pub bands: Mutex<[f32; NUM_BANDS]>,
     /// Peak values for decay animation
     pub peaks: Mutex<[f32; NUM_BANDS]>,
+    /// Beats detected so far; watchers compare against the last value seen
+    pub beats: AtomicU32,
+    /// Full-range log bands for the spectrum analyzer (0.0 - 1.0); empty
+    /// unless [`AudioConfig::spectrum_bands`] is set
+    pub spectrum: Mutex<Vec<f32>>,
     /// Running flag
     pub running: AtomicBool,
     /// Sample rate from audio device
@@ -68,6 +81,8 @@ impl Default for AudioState {
         Self {
             bands: Mutex::new([0.0; NUM_BANDS]),
             peaks: Mutex::new([0.0; NUM_BANDS]),
+            beats: AtomicU32::new(0),
+            spectrum: Mutex::new(Vec::new()),
             running: AtomicBool::new(false),
             sample_rate: AtomicU32::new(44100),
             fft_hz: AtomicU32::new(0),
@@ -93,6 +108,11 @@ impl AudioState {
         *self.bands.lock().unwrap() = new_bands;
     }
 
+    /// Get a copy of the spectrum analyzer bands
+    pub fn get_spectrum(&self) -> Vec<f32> {
+        self.spectrum.lock().unwrap().clone()
+    }
+
     /// Check if running
     pub fn is_running(&self) -> bool {
         self.running.load(Ordering::SeqCst)
@@ -114,38 +134,74 @@ pub struct AudioCapture {
 }
 
 impl AudioCapture {
-    /// Start capturing from the resolved PulseAudio source.
+    /// Start capturing from the resolved PipeWire source or application.
     ///
-    /// Spawns a capture thread (blocking PulseAudio reads → ring buffer) and an
+    /// Spawns a capture thread (blocking PipeWire reads → ring buffer) and an
     /// FFT thread (ring buffer → smoothed bands). Both stop on [`Self::stop`] or
-    /// when the capture is dropped.
+    /// when the capture is dropped. The capture thread reopens the target when
+    /// it disappears or moves (device unplugged, default sink changed, app
+    /// restarted), rendering silence in between.
     pub fn start(config: AudioConfig) -> Result<Self, String> {
         let state = Arc::new(AudioState::default());
 
-        let source = pulse::resolve_source(config.device.as_deref())?;
-        let simple = pulse::open_record(&source.name)?;
+        let target =
+            pipewire_audio::CaptureTarget::new(config.device.as_deref(), config.app.as_deref());
         state
             .sample_rate
-            .store(pulse::SAMPLE_RATE, Ordering::SeqCst);
+            .store(pipewire_audio::SAMPLE_RATE, Ordering::SeqCst);
         state
             .target_hz
             .store(clamp_hz(config.update_hz), Ordering::SeqCst);
-        let source_label = source.label();
 
         let sample_buffer: Arc<Mutex<Vec<f32>>> =
             Arc::new(Mutex::new(Vec::with_capacity(FFT_SIZE * 2)));
         state.running.store(true, Ordering::SeqCst);
 
-        // Capture thread: blocking PulseAudio reads → ring buffer.
+        // Capture thread: blocking PipeWire reads → ring buffer. The stream is
+        // opened here and the outcome reported back.
         let capture_state = Arc::clone(&state);
         let capture_buffer = Arc::clone(&sample_buffer);
+        let (opened_tx, opened_rx) = std::sync::mpsc::channel();
         let capture_thread = thread::spawn(move || {
+            let capture = match pipewire_audio::Capture::open(&target) {
+                Ok(c) => {
+                    let _ = opened_tx.send(Ok(c.label().to_string()));
+                    c
+                }
+                Err(e) => {
+                    let _ = opened_tx.send(Err(e));
+                    return;
+                }
+            };
             // ~6 ms of audio per read: low latency while still polling `running`.
             const READ_SAMPLES: usize = 256;
             let mut byte_buf = vec![0u8; READ_SAMPLES * 4];
+            let mut capture = Some(capture);
+            let mut last_check = Instant::now();
             while capture_state.running.load(Ordering::SeqCst) {
-                if simple.read(&mut byte_buf).is_err() {
-                    break;
+                let Some(active) = capture.as_mut() else {
+                    match pipewire_audio::Capture::open(&target) {
+                        Ok(c) => {
+                            tracing::info!("Audio capture reconnected: {}", c.label());
+                            capture = Some(c);
+                            last_check = Instant::now();
+                        }
+                        Err(_) => thread::sleep(RECONNECT_INTERVAL),
+                    }
+                    continue;
+                };
+                let retarget = last_check.elapsed() >= RETARGET_CHECK && {
+                    last_check = Instant::now();
+                    active.is_stale()
+                };
+                if retarget || active.read(&mut byte_buf).is_err() {
+                    tracing::info!("Audio capture lost {}, reconnecting", active.label());
+                    capture = None;
+                    // Stale samples would freeze the bars; decay to silence instead
+                    if let Ok(mut buffer) = capture_buffer.lock() {
+                        buffer.clear();
+                    }
+                    continue;
                 }
                 if let Ok(mut buffer) = capture_buffer.lock() {
                     buffer.extend(
@@ -161,13 +217,29 @@ impl AudioCapture {
             }
         });
 
+        let opened = opened_rx
+            .recv()
+            .unwrap_or_else(|_| Err("Audio capture thread exited".into()));
+        let source_label = match opened {
+            Ok(label) => label,
+            Err(e) => {
+                state.stop();
+                let _ = capture_thread.join();
+                return Err(e);
+            }
+        };
+
         // FFT thread: ring buffer → smoothed bands.
         let sensitivity = config.sensitivity;
+        let spectrum_bands = config.spectrum_bands;
         let fft_state = Arc::clone(&state);
         let fft_buffer = Arc::clone(&sample_buffer);
         let fft_thread = thread::spawn(move || {
             let mut display_bands = [0.0f32; NUM_BANDS];
             let mut peak_ref = 0.0f32; // loudness AGC reference (peak follower)
+            let mut spectrum = vec![0.0f32; spectrum_bands];
+            let mut spectrum_ref = 0.0f32;
+            let mut beat_detector = audio_beat::BeatDetector::new(sensitivity);
             let mut loop_count = 0u32;
             let mut rate_count = 0u32;
             let mut rate_start = Instant::now();
@@ -204,7 +276,11 @@ impl AudioCapture {
                     eprintln!("[Audio] buf={buf_len}, peak={max_sample:.3}");
                 }
 
-                let raw_bands = analyze_spectrum(&samples, pulse::SAMPLE_RATE);
+                let raw_bands = analyze_spectrum(&samples, pipewire_audio::SAMPLE_RATE);
+
+                if beat_detector.update(audio_beat::bass_energy(&raw_bands), Instant::now()) {
+                    fft_state.beats.fetch_add(1, Ordering::Relaxed);
+                }
 
                 // Loudness AGC: reference follows the frame peak instantly (so no
                 // band ever exceeds it → no clipping) and decays slowly, so quiet
@@ -222,6 +298,24 @@ impl AudioCapture {
                 }
                 fft_state.set_bands(display_bands);
 
+                if spectrum_bands > 0 {
+                    let raw = audio_spectrum::log_bands(
+                        &samples,
+                        pipewire_audio::SAMPLE_RATE,
+                        FFT_SIZE,
+                        spectrum_bands,
+                    );
+                    let frame_max = raw.iter().copied().fold(0.0f32, f32::max);
+                    spectrum_ref = frame_max.max(spectrum_ref * AGC_RELEASE);
+                    let reference = spectrum_ref.max(AGC_EPS);
+                    for (display, &raw) in spectrum.iter_mut().zip(raw.iter()) {
+                        let norm = (raw / reference).clamp(0.0, 1.0);
+                        let v = (norm * sensitivity).clamp(0.0, 1.0).powf(LEVEL_CURVE);
+                        *display = if v > *display { v } else { *display * DECAY };
+                    }
+                    *fft_state.spectrum.lock().unwrap() = spectrum.clone();
+                }
+
                 let interval = Duration::from_millis(
                     1000 / clamp_hz(fft_state.target_hz.load(Ordering::Relaxed)) as u64,
                 );
@@ -277,6 +371,11 @@ pub struct AudioConfig {
     pub update_hz: u32,
     /// Capture device name (exact or case-insensitive substring); None = auto-detect monitor source
     pub device: Option<String>,
+    /// Capture only this application's output (name or substring); overrides `device`
+    pub app: Option<String>,
+    /// Also compute this many full-range log bands for the spectrum
+    /// analyzer ([`AudioState::spectrum`]); 0 = off
+    pub spectrum_bands: usize,
 }
 
 impl Default for AudioConfig {
@@ -287,6 +386,8 @@ impl Default for AudioConfig {
             sensitivity: 1.0,
             update_hz: 50,
             device: None,
+            app: None,
+            spectrum_bands: 0,
         }
     }
 }
@@ -352,12 +453,12 @@ fn analyze_spectrum(samples: &[f32], sample_rate: u32) -> [f32; NUM_BANDS] {
 }
 
 /// List available capture sources as human labels (description + `[monitor]` tag
-/// + raw name). Returns an empty list if PulseAudio enumeration fails.
+/// + raw name). Returns an empty list if PipeWire enumeration fails.
 pub fn list_audio_devices() -> Vec<String> {
-    pulse::list_sources()
+    pipewire_audio::list_sources()
         .unwrap_or_default()
         .iter()
-        .map(pulse::SourceEntry::label)
+        .map(pipewire_audio::SourceEntry::label)
         .collect()
 }
 
@@ -479,25 +580,37 @@ pub fn run_viz_loop(
     }
 }
 
+/// List applications currently playing audio (candidates for `--app`).
+/// Returns an empty list if PipeWire enumeration fails.
+pub fn list_audio_apps() -> Vec<String> {
+    pipewire_audio::list_app_streams()
+        .unwrap_or_default()
+        .iter()
+        .map(pipewire_audio::AppStream::label)
+        .collect()
+}
+
 /// Resolve the default capture source and confirm it opens.
 pub fn test_audio_capture() -> Result<(), String> {
-    let source = pulse::resolve_source(None)?;
-    println!("Capture source: {}", source.label());
-    println!("Format: {} Hz, mono f32", pulse::SAMPLE_RATE);
-    let _simple = pulse::open_record(&source.name)?;
+    let capture = pipewire_audio::Capture::open(&pipewire_audio::CaptureTarget::DefaultMonitor)?;
+    println!("Capture source: {}", capture.label());
+    println!("Format: {} Hz, mono f32", pipewire_audio::SAMPLE_RATE);
     println!("Stream opened OK.");
     Ok(())
 }
 
-/// Capture from a source and print a per-second peak level meter for 5 seconds.
-pub fn test_audio_levels(requested_device: Option<&str>) -> Result<(), String> {
+/// Capture from a source or application and print a per-second peak level
+/// meter for 5 seconds.
+pub fn test_audio_levels(
+    requested_device: Option<&str>,
+    requested_app: Option<&str>,
+) -> Result<(), String> {
     use std::io::Write;
 
-    let source = pulse::resolve_source(requested_device)?;
-    println!("Using source: {}", source.label());
-    println!("Format: {} Hz, mono f32", pulse::SAMPLE_RATE);
-
-    let simple = pulse::open_record(&source.name)?;
+    let target = pipewire_audio::CaptureTarget::new(requested_device, requested_app);
+    let mut capture = pipewire_audio::Capture::open(&target)?;
+    println!("Using source: {}", capture.label());
+    println!("Format: {} Hz, mono f32", pipewire_audio::SAMPLE_RATE);
 
     println!("\nListening for 5 seconds...");
     const READ_SAMPLES: usize = 882; // ~20 ms at 44.1 kHz
@@ -507,9 +620,7 @@ pub fn test_audio_levels(requested_device: Option<&str>) -> Result<(), String> {
         let mut reads = 0u32;
         let second_start = Instant::now();
         while second_start.elapsed() < Duration::from_secs(1) {
-            simple
-                .read(&mut byte_buf)
-                .map_err(|e| format!("PulseAudio read failed: {e}"))?;
+            capture.read(&mut byte_buf)?;
             reads += 1;
             for c in byte_buf.chunks_exact(4) {
                 let s = f32::from_le_bytes([c[0], c[1], c[2], c[3]]).abs();
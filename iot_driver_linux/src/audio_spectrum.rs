@@ -0,0 +1,302 @@
+//! Host-rendered spectrum analyzer (`iot_driver audio --mode spectrum`).
+//!
+//! Unlike the on-device music visualizer, which only takes 16 levels, this
+//! renders the frame itself and streams it over the patch LED protocol: FFT
+//! bands spread across the 16 columns as bars growing up from the bottom row,
+//! each with a peak dot that falls back slowly. Bands are log-spaced across
+//! the audible range, so treble gets columns too.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use spectrum_analyzer::scaling::divide_by_N_sqrt;
+use spectrum_analyzer::windows::hann_window;
+use spectrum_analyzer::{samples_fft_to_spectrum, FrequencyLimit};
+
+use crate::audio_reactive::{AudioCapture, AudioConfig};
+use crate::keyboard_config::parse_color;
+use crate::led_stream::{apply_power_budget, send_full_frame};
+use crate::notify::keymap::{COLS, MATRIX_LEN, ROWS};
+use monsgeek_keyboard::{KeyboardInterface, RgbColor};
+
+/// Frequency span of the analyzer bands.
+const SPECTRUM_LO_HZ: f32 = 40.0;
+const SPECTRUM_HI_HZ: f32 = 16_000.0;
+
+/// At most one band per column.
+pub const MAX_BANDS: usize = COLS;
+
+/// Bar colors and peak behavior.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpectrumStyle {
+    /// Number of bands (1-16), spread evenly over the columns
+    pub bands: usize,
+    /// Bar color at the bottom row
+    pub low: RgbColor,
+    /// Bar color at the top row
+    pub high: RgbColor,
+    /// Peak dot color; `None` hides the peaks
+    pub peak: Option<RgbColor>,
+    /// How fast peaks fall, in rows per second
+    pub falloff: f32,
+}
+
+impl Default for SpectrumStyle {
+    fn default() -> Self {
+        Self {
+            bands: MAX_BANDS,
+            low: RgbColor::new(0, 255, 0),
+            high: RgbColor::new(255, 0, 0),
+            peak: Some(RgbColor::new(255, 255, 255)),
+            falloff: 6.0,
+        }
+    }
+}
+
+/// Parse `--colors`: `LOW,HIGH[,PEAK]` as `#RRGGBB`; `PEAK` may be `none`.
+pub fn parse_colors(s: &str) -> Result<(RgbColor, RgbColor, Option<RgbColor>), String> {
+    let color = |c: &str| parse_color(c).ok_or_else(|| format!("invalid color '{c}'"));
+    match s.split(',').map(str::trim).collect::<Vec<_>>().as_slice() {
+        [low, high] => Ok((color(low)?, color(high)?, SpectrumStyle::default().peak)),
+        [low, high, peak] if peak.eq_ignore_ascii_case("none") => {
+            Ok((color(low)?, color(high)?, None))
+        }
+        [low, high, peak] => Ok((color(low)?, color(high)?, Some(color(peak)?))),
+        _ => Err(format!("expected LOW,HIGH[,PEAK] colors, got '{s}'")),
+    }
+}
+
+/// Analyze samples into `count` log-spaced band magnitudes (linear).
+///
+/// Each band takes its loudest bin, tilted +3dB/octave so typical music
+/// (which falls off toward the treble) reads level across the columns.
+/// Returns zeros on silence or when there are too few samples.
+pub fn log_bands(samples: &[f32], sample_rate: u32, fft_size: usize, count: usize) -> Vec<f32> {
+    let mut bands = vec![0.0f32; count];
+    if count == 0 || samples.len() < fft_size {
+        return bands;
+    }
+    let samples = &samples[samples.len() - fft_size..];
+    if samples.iter().map(|s| s.abs()).fold(0.0f32, f32::max) < 0.001 {
+        return bands;
+    }
+    let hi = SPECTRUM_HI_HZ.min(sample_rate as f32 / 2.0);
+    let windowed = hann_window(samples);
+    let Ok(spectrum) = samples_fft_to_spectrum(
+        &windowed,
+        sample_rate,
+        FrequencyLimit::Range(SPECTRUM_LO_HZ, hi),
+        Some(&divide_by_N_sqrt),
+    ) else {
+        return bands;
+    };
+
+    let span = (hi / SPECTRUM_LO_HZ).ln();
+    for (freq, magnitude) in spectrum.data().iter() {
+        let f = freq.val();
+        let idx = ((f / SPECTRUM_LO_HZ).ln() / span * count as f32) as usize;
+        if let Some(band) = bands.get_mut(idx.min(count - 1)) {
+            let tilted = magnitude.val() * (f / SPECTRUM_LO_HZ).sqrt();
+            *band = band.max(tilted);
+        }
+    }
+    // Narrow low bands can fall between bins; borrow the neighbor below
+    for i in 1..count {
+        if bands[i] == 0.0 {
+            bands[i] = bands[i - 1];
+        }
+    }
+    bands
+}
+
+/// Renders band levels into LED frames, keeping the falling peaks.
+pub struct SpectrumRenderer {
+    style: SpectrumStyle,
+    /// Peak height per band, in rows
+    peaks: Vec<f32>,
+}
+
+impl SpectrumRenderer {
+    pub fn new(mut style: SpectrumStyle) -> Self {
+        style.bands = style.bands.clamp(1, MAX_BANDS);
+        Self {
+            peaks: vec![0.0; style.bands],
+            style,
+        }
+    }
+
+    pub fn style(&self) -> &SpectrumStyle {
+        &self.style
+    }
+
+    /// One frame (row-major, row 0 at the top) from band levels in 0.0-1.0.
+    /// `dt` is the time since the previous frame, for the peak falloff.
+    pub fn render(&mut self, levels: &[f32], dt: Duration) -> [(u8, u8, u8); MATRIX_LEN] {
+        let mut leds = [(0u8, 0u8, 0u8); MATRIX_LEN];
+        let bands = self.style.bands;
+        let fall = self.style.falloff * dt.as_secs_f32();
+
+        for band in 0..bands {
+            let height = levels.get(band).copied().unwrap_or(0.0).clamp(0.0, 1.0) * ROWS as f32;
+            let peak = &mut self.peaks[band];
+            *peak = height.max(*peak - fall);
+            // Topmost row the peak dot sits in, if it is above the bar
+            let peak_row = (*peak > 0.0 && peak.ceil() > height.ceil())
+                .then(|| peak.ceil() as usize - 1)
+                .filter(|_| self.style.peak.is_some());
+
+            for col in (band * COLS / bands)..((band + 1) * COLS / bands) {
+                for level in 0..ROWS {
+                    let led = &mut leds[(ROWS - 1 - level) * COLS + col];
+                    if Some(level) == peak_row {
+                        let c = self.style.peak.unwrap();
+                        *led = (c.r, c.g, c.b);
+                        continue;
+                    }
+                    let fill = (height - level as f32).clamp(0.0, 1.0);
+                    if fill > 0.0 {
+                        let t = level as f32 / (ROWS - 1) as f32;
+                        *led = scale(blend(self.style.low, self.style.high, t), fill);
+                    }
+                }
+            }
+        }
+        leds
+    }
+}
+
+fn blend(a: RgbColor, b: RgbColor, t: f32) -> RgbColor {
+    let mix = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * t).round() as u8;
+    RgbColor::new(mix(a.r, b.r), mix(a.g, b.g), mix(a.b, b.b))
+}
+
+fn scale(c: RgbColor, f: f32) -> (u8, u8, u8) {
+    let s = |v: u8| (v as f32 * f).round() as u8;
+    (s(c.r), s(c.g), s(c.b))
+}
+
+/// Run the spectrum analyzer (blocking) until `running` clears.
+///
+/// The keyboard must support patch LED streaming; the stream is released on
+/// exit so the firmware effect comes back.
+pub fn run_audio_spectrum(
+    keyboard: &KeyboardInterface,
+    config: AudioConfig,
+    style: SpectrumStyle,
+    power_budget: u32,
+    running: Arc<AtomicBool>,
+) -> Result<(), String> {
+    let mut renderer = SpectrumRenderer::new(style);
+    let capture = AudioCapture::start(AudioConfig {
+        spectrum_bands: renderer.style().bands,
+        ..config
+    })?;
+    println!("Audio input: {}", capture.source_label);
+    println!(
+        "Spectrum: {} bands, {:.0}-{:.0} Hz",
+        renderer.style().bands,
+        SPECTRUM_LO_HZ,
+        SPECTRUM_HI_HZ
+    );
+
+    let mut last_frame = Instant::now();
+    let mut result = Ok(());
+    while running.load(Ordering::SeqCst) && capture.state.is_running() {
+        let frame_start = Instant::now();
+        let mut leds = renderer.render(&capture.state.get_spectrum(), frame_start - last_frame);
+        last_frame = frame_start;
+        apply_power_budget(&mut leds, power_budget);
+        if let Err(e) = send_full_frame(keyboard, &leds) {
+            result = Err(format!("Failed to send LED frame: {e}"));
+            break;
+        }
+
+        let hz = capture
+            .state
+            .target_hz
+            .load(Ordering::Relaxed)
+            .clamp(5, 120);
+        let frame = Duration::from_millis(1000 / hz as u64);
+        let elapsed = frame_start.elapsed();
+        if elapsed < frame {
+            thread::sleep(frame - elapsed);
+        }
+    }
+
+    capture.stop();
+    keyboard.stream_led_release().ok();
+    println!("Spectrum analyzer stopped");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(freq: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (2.0 * std::f32::consts::PI * freq * i as f32 / 44100.0).sin() * 0.5)
+            .collect()
+    }
+
+    fn loudest(bands: &[f32]) -> usize {
+        bands
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn log_bands_place_tones_low_to_high() {
+        let low = loudest(&log_bands(&tone(100.0, 4096), 44100, 2048, 8));
+        let mid = loudest(&log_bands(&tone(1000.0, 4096), 44100, 2048, 8));
+        let high = loudest(&log_bands(&tone(8000.0, 4096), 44100, 2048, 8));
+        assert!(low < mid && mid < high, "{low} {mid} {high}");
+        assert!(log_bands(&vec![0.0; 4096], 44100, 2048, 8)
+            .iter()
+            .all(|&b| b == 0.0));
+    }
+
+    #[test]
+    fn bars_fill_columns_from_the_bottom() {
+        let mut r = SpectrumRenderer::new(SpectrumStyle {
+            bands: 2,
+            peak: None,
+            ..Default::default()
+        });
+        // Left half full, right half at half height
+        let leds = r.render(&[1.0, 0.5], Duration::ZERO);
+        let at = |row: usize, col: usize| leds[row * COLS + col];
+        assert_eq!(at(ROWS - 1, 0), (0, 255, 0));
+        assert_eq!(at(0, 7), (255, 0, 0));
+        assert_ne!(at(3, 8), (0, 0, 0));
+        assert_eq!(at(2, 15), (0, 0, 0));
+    }
+
+    #[test]
+    fn peaks_hold_above_the_bar_and_fall() {
+        let mut r = SpectrumRenderer::new(SpectrumStyle {
+            bands: 1,
+            falloff: 2.0,
+            ..Default::default()
+        });
+        r.render(&[1.0], Duration::ZERO);
+        // Bar drops to zero; after 1s the peak fell 2 rows to row 2 from top
+        let leds = r.render(&[0.0], Duration::from_secs(1));
+        assert_eq!(leds[2 * COLS], (255, 255, 255));
+        assert_eq!(leds.iter().filter(|&&c| c != (0, 0, 0)).count(), COLS);
+    }
+
+    #[test]
+    fn parses_color_lists() {
+        let (low, high, peak) = parse_colors("#0000FF, ff00ff").unwrap();
+        assert_eq!((low.b, high.r), (255, 255));
+        assert!(peak.is_some());
+        assert_eq!(parse_colors("000000,ffffff,none").unwrap().2, None);
+        assert!(parse_colors("red").is_err());
+    }
+}
@@ -0,0 +1,258 @@
+//! Battery level history (`~/.config/monsgeek/battery_history.csv`).
+//!
+//! Wireless boards only report the current level, so battery life can only be
+//! judged from readings kept over time. Each line is
+//! `unix_secs,level,charging,device`; the device name goes last so a comma in
+//! it needs no quoting. Readings are appended when the level or charge state
+//! changes (or every [`MIN_INTERVAL`] otherwise) and anything older than
+//! [`MAX_AGE`] is dropped when the file is loaded.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::effect::config_dir;
+
+/// Repeat an unchanged reading at most this often, so gaps stay visible.
+pub const MIN_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Readings older than this are pruned.
+pub const MAX_AGE: Duration = Duration::from_secs(14 * 24 * 3600);
+
+/// Path to the history file in the shared config directory.
+pub fn history_path() -> PathBuf {
+    config_dir().join("battery_history.csv")
+}
+
+/// One battery reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatterySample {
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+    pub level: u8,
+    pub charging: bool,
+}
+
+/// A charge state change: `started` is true when charging began.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChargeEvent {
+    pub at: u64,
+    pub started: bool,
+}
+
+/// Readings for one device, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct BatteryHistory {
+    device: String,
+    samples: Vec<BatterySample>,
+    path: Option<PathBuf>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn parse_line(line: &str) -> Option<(BatterySample, &str)> {
+    let mut parts = line.splitn(4, ',');
+    let at = parts.next()?.trim().parse().ok()?;
+    let level = parts.next()?.trim().parse().ok()?;
+    let charging = parts.next()?.trim() == "1";
+    let device = parts.next()?;
+    Some((
+        BatterySample {
+            at,
+            level,
+            charging,
+        },
+        device,
+    ))
+}
+
+impl BatteryHistory {
+    /// In-memory history that is never written to disk.
+    pub fn new(device: &str) -> Self {
+        Self {
+            device: device.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Load `device`'s readings from the history file. A missing or partly
+    /// unreadable file yields what could be parsed.
+    pub fn load(device: &str) -> Self {
+        let path = history_path();
+        let cutoff = now_secs().saturating_sub(MAX_AGE.as_secs());
+        let samples = std::fs::read_to_string(&path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(parse_line)
+                    .filter(|(s, d)| *d == device && s.at >= cutoff)
+                    .map(|(s, _)| s)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            device: device.to_string(),
+            samples,
+            path: Some(path),
+        }
+    }
+
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    pub fn samples(&self) -> &[BatterySample] {
+        &self.samples
+    }
+
+    /// Record a reading taken now; see [`Self::record_at`].
+    pub fn record(&mut self, level: u8, charging: bool) -> bool {
+        self.record_at(now_secs(), level, charging)
+    }
+
+    /// Record a reading, skipping it if nothing changed within
+    /// [`MIN_INTERVAL`]. Returns whether it was kept. Levels above 100
+    /// (unknown/wired) are ignored.
+    pub fn record_at(&mut self, at: u64, level: u8, charging: bool) -> bool {
+        if level > 100 {
+            return false;
+        }
+        if let Some(last) = self.samples.last() {
+            let unchanged = last.level == level && last.charging == charging;
+            if at < last.at || (unchanged && at - last.at < MIN_INTERVAL.as_secs()) {
+                return false;
+            }
+        }
+        let sample = BatterySample {
+            at,
+            level,
+            charging,
+        };
+        self.samples.push(sample);
+        if let Some(path) = &self.path {
+            if let Err(e) = self.append(path, sample) {
+                tracing::warn!("battery history: write {}: {e}", path.display());
+            }
+        }
+        true
+    }
+
+    fn append(&self, path: &PathBuf, s: BatterySample) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(
+            file,
+            "{},{},{},{}",
+            s.at,
+            s.level,
+            u8::from(s.charging),
+            self.device
+        )
+    }
+
+    /// Points where charging started or stopped.
+    pub fn charge_events(&self) -> Vec<ChargeEvent> {
+        self.samples
+            .windows(2)
+            .filter(|w| w[0].charging != w[1].charging)
+            .map(|w| ChargeEvent {
+                at: w[1].at,
+                started: w[1].charging,
+            })
+            .collect()
+    }
+
+    /// Average discharge rate (percent per hour) over the current discharge
+    /// run, i.e. since charging last stopped. `None` while charging or until
+    /// the level has dropped at least one percent.
+    pub fn discharge_rate(&self) -> Option<f64> {
+        let last = self.samples.last()?;
+        if last.charging {
+            return None;
+        }
+        let start = self
+            .samples
+            .iter()
+            .rposition(|s| s.charging)
+            .map_or(0, |i| i + 1);
+        // Start from the highest reading: the level may still rise briefly
+        // after unplugging while the gauge settles.
+        let run = &self.samples[start..];
+        let first = run
+            .iter()
+            .max_by_key(|s| (s.level, std::cmp::Reverse(s.at)))?;
+        let dropped = first.level.checked_sub(last.level).filter(|&d| d > 0)?;
+        let hours = (last.at - first.at) as f64 / 3600.0;
+        (hours > 0.0).then(|| dropped as f64 / hours)
+    }
+
+    /// Estimated time until empty at the current discharge rate.
+    pub fn time_remaining(&self) -> Option<Duration> {
+        let rate = self.discharge_rate()?;
+        let level = self.samples.last()?.level as f64;
+        Some(Duration::from_secs_f64(level / rate * 3600.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const H: u64 = 3600;
+
+    #[test]
+    fn skips_unchanged_readings_within_interval() {
+        let mut h = BatteryHistory::new("kb");
+        assert!(h.record_at(0, 80, false));
+        assert!(!h.record_at(60, 80, false));
+        assert!(h.record_at(120, 79, false));
+        assert!(h.record_at(120 + MIN_INTERVAL.as_secs(), 79, false));
+        assert!(!h.record_at(10, 50, false));
+        assert!(!h.record_at(99 * H, 255, false));
+        assert_eq!(h.samples().len(), 3);
+    }
+
+    #[test]
+    fn estimates_from_current_discharge_run() {
+        let mut h = BatteryHistory::new("kb");
+        h.record_at(0, 20, false);
+        h.record_at(H, 40, true);
+        h.record_at(2 * H, 90, false);
+        h.record_at(12 * H, 80, false);
+        assert_eq!(h.discharge_rate(), Some(1.0));
+        assert_eq!(h.time_remaining(), Some(Duration::from_secs(80 * H)));
+        assert_eq!(
+            h.charge_events(),
+            vec![
+                ChargeEvent {
+                    at: H,
+                    started: true
+                },
+                ChargeEvent {
+                    at: 2 * H,
+                    started: false
+                },
+            ]
+        );
+        h.record_at(13 * H, 80, true);
+        assert_eq!(h.time_remaining(), None);
+    }
+
+    #[test]
+    fn parses_device_with_comma() {
+        let (s, device) = parse_line("100,55,1,Akko, 5075").unwrap();
+        assert_eq!((s.at, s.level, s.charging), (100, 55, true));
+        assert_eq!(device, "Akko, 5075");
+        assert!(parse_line("garbage").is_none());
+    }
+}
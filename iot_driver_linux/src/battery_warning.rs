@@ -0,0 +1,266 @@
+//! Low-battery warning on the LEDs.
+//!
+//! Configured under `[battery_warning]` in `settings.toml` and run by the
+//! daemon. When a wireless keyboard's battery drops to the threshold, the
+//! warning keys pulse in the warning color for a few seconds, then the
+//! previous lighting comes back; it repeats while the battery stays low.
+//! With the LED streaming patch only the chosen keys pulse (as an overlay);
+//! on stock firmware the whole board breathes in the warning color instead.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::keyboard_config::parse_color;
+use crate::notify::keymap::parse_key_target;
+use monsgeek_keyboard::led::LedParams;
+use monsgeek_keyboard::{KeyboardInterface, LedMode, RgbColor};
+
+/// How often the daemon checks the battery.
+const POLL: Duration = Duration::from_secs(60);
+/// Overlay frame interval while pulsing.
+const FRAME: Duration = Duration::from_millis(33);
+/// The battery has to climb this far above the threshold before the
+/// warning re-arms, so a level flickering around it doesn't re-warn.
+const HYSTERESIS: u8 = 3;
+
+/// `[battery_warning]` settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatteryWarning {
+    pub enabled: bool,
+    /// Warn at or below this level (percent)
+    pub threshold: u8,
+    /// Keys to pulse, as for `iot_driver notify` (`Esc`, `frow`, `F1..F4`)
+    pub keys: Vec<String>,
+    /// Warning color (`#RRGGBB`)
+    pub color: String,
+    /// How long the warning shows
+    pub duration_seconds: f32,
+    /// Length of one pulse
+    pub pulse_ms: u64,
+    /// Warn again after this many minutes while still low (0 = once)
+    pub repeat_minutes: f32,
+}
+
+impl Default for BatteryWarning {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 15,
+            keys: vec!["Esc".to_string(), "frow".to_string()],
+            color: "#FF0000".to_string(),
+            duration_seconds: 5.0,
+            pulse_ms: 1000,
+            repeat_minutes: 30.0,
+        }
+    }
+}
+
+impl BatteryWarning {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Matrix indices of the warning keys.
+    pub fn key_indices(&self) -> Result<Vec<usize>, String> {
+        let mut indices = Vec::new();
+        for key in &self.keys {
+            indices.extend(parse_key_target(key)?.indices);
+        }
+        indices.sort_unstable();
+        indices.dedup();
+        Ok(indices)
+    }
+
+    pub fn rgb(&self) -> Result<RgbColor, String> {
+        parse_color(&self.color).ok_or_else(|| format!("invalid color {:?}", self.color))
+    }
+
+    /// Pulse brightness (0.0-1.0) `elapsed` into the warning: starts and
+    /// ends dark, peaks mid-pulse.
+    pub fn pulse_level(&self, elapsed: Duration) -> f32 {
+        let period = self.pulse_ms.max(100) as f32 / 1000.0;
+        let phase = elapsed.as_secs_f32() / period * std::f32::consts::TAU;
+        0.5 - 0.5 * phase.cos()
+    }
+}
+
+/// Decides when to warn.
+#[derive(Debug, Default)]
+pub struct LowBatteryWatch {
+    /// When we last warned during this discharge
+    warned_at: Option<Instant>,
+}
+
+impl LowBatteryWatch {
+    /// Note a battery reading; returns whether to show the warning now.
+    pub fn update(
+        &mut self,
+        level: u8,
+        charging: bool,
+        config: &BatteryWarning,
+        now: Instant,
+    ) -> bool {
+        if charging || level > config.threshold.saturating_add(HYSTERESIS) {
+            self.warned_at = None;
+            return false;
+        }
+        if level > config.threshold {
+            return false;
+        }
+        let due = match self.warned_at {
+            None => true,
+            Some(_) if config.repeat_minutes <= 0.0 => false,
+            Some(at) => now - at >= Duration::from_secs_f32(config.repeat_minutes * 60.0),
+        };
+        if due {
+            self.warned_at = Some(now);
+        }
+        due
+    }
+}
+
+/// Pulse the warning keys as an LED streaming overlay, then hand the LEDs
+/// back to the firmware effect.
+fn show_overlay(kb: &KeyboardInterface, config: &BatteryWarning) -> Result<(), String> {
+    let indices = config.key_indices()?;
+    let color = config.rgb()?;
+    let duration = Duration::from_secs_f32(config.duration_seconds.max(0.5));
+    let start = Instant::now();
+    let result = loop {
+        let elapsed = start.elapsed();
+        if elapsed >= duration {
+            break Ok(());
+        }
+        let level = config.pulse_level(elapsed);
+        let scale = |v: u8| (v as f32 * level).round() as u8;
+        let entries: Vec<_> = indices
+            .iter()
+            .map(|&i| (i as u8, scale(color.r), scale(color.g), scale(color.b)))
+            .collect();
+        if let Err(e) = kb.stream_led_sparse(&entries) {
+            break Err(e.to_string());
+        }
+        std::thread::sleep(FRAME);
+    };
+    kb.stream_led_release().ok();
+    result
+}
+
+/// Breathe the whole board in the warning color, then restore the lighting
+/// unless it was changed meanwhile.
+fn show_firmware(kb: &KeyboardInterface, config: &BatteryWarning) -> Result<(), String> {
+    let saved = kb.get_led_params().map_err(|e| e.to_string())?;
+    let warning = LedParams {
+        mode: LedMode::Breathing,
+        brightness: 100,
+        color: config.rgb()?,
+        ..saved.clone()
+    };
+    kb.set_led_params(&warning).map_err(|e| e.to_string())?;
+    std::thread::sleep(Duration::from_secs_f32(config.duration_seconds.max(0.5)));
+    let current = kb.get_led_params().map_err(|e| e.to_string())?;
+    if current == warning {
+        kb.set_led_params(&saved).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Show the warning once.
+pub fn show(kb: &KeyboardInterface, config: &BatteryWarning) -> Result<(), String> {
+    if crate::sysmon::supports_streaming(kb) {
+        show_overlay(kb, config)
+    } else {
+        show_firmware(kb, config)
+    }
+}
+
+/// Daemon loop: check the battery every [`POLL`] and warn per
+/// `[battery_warning]`, re-reading the settings each time. Wired keyboards
+/// are skipped. The keyboard is opened on demand and reopened after errors.
+pub fn run_daemon_battery_warning(open: impl Fn() -> Result<KeyboardInterface, String>) {
+    let mut keyboard: Option<KeyboardInterface> = None;
+    let mut watch = LowBatteryWatch::default();
+    loop {
+        let config = crate::settings::Settings::load().battery_warning;
+        if !config.enabled {
+            keyboard = None;
+            std::thread::sleep(POLL);
+            continue;
+        }
+        if keyboard.is_none() {
+            keyboard = open()
+                .map_err(|e| tracing::debug!("battery warning: no keyboard: {e}"))
+                .ok();
+        }
+        if let Some(kb) = keyboard.as_ref().filter(|kb| kb.is_wireless()) {
+            match kb.get_battery() {
+                Ok(battery) if battery.online && !crate::lock_lighting::is_locked() => {
+                    if watch.update(battery.level, battery.charging, &config, Instant::now()) {
+                        tracing::info!("battery warning: {}%", battery.level);
+                        if let Err(e) = show(kb, &config) {
+                            tracing::warn!("battery warning: {e}");
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::debug!("battery warning: {e}");
+                    keyboard = None;
+                }
+            }
+        }
+        std::thread::sleep(POLL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warns_once_per_repeat_interval_and_rearms_above_the_threshold() {
+        let config = BatteryWarning {
+            repeat_minutes: 10.0,
+            ..Default::default()
+        };
+        let t0 = Instant::now();
+        let mins = |m: u64| t0 + Duration::from_secs(m * 60);
+        let mut watch = LowBatteryWatch::default();
+        assert!(!watch.update(40, false, &config, t0));
+        assert!(watch.update(15, false, &config, mins(1)));
+        assert!(!watch.update(14, false, &config, mins(5)));
+        assert!(watch.update(13, false, &config, mins(11)));
+        // Hovering just above the threshold doesn't re-arm
+        assert!(!watch.update(16, false, &config, mins(12)));
+        assert!(!watch.update(15, false, &config, mins(13)));
+        // Charging re-arms
+        assert!(!watch.update(15, true, &config, mins(14)));
+        assert!(watch.update(15, false, &config, mins(15)));
+
+        let once = BatteryWarning {
+            repeat_minutes: 0.0,
+            ..Default::default()
+        };
+        let mut watch = LowBatteryWatch::default();
+        assert!(watch.update(10, false, &once, t0));
+        assert!(!watch.update(5, false, &once, mins(600)));
+    }
+
+    #[test]
+    fn pulses_from_dark_to_full_and_back() {
+        let config = BatteryWarning::default();
+        assert_eq!(config.pulse_level(Duration::ZERO), 0.0);
+        assert!((config.pulse_level(Duration::from_millis(500)) - 1.0).abs() < 1e-4);
+        assert!(config.pulse_level(Duration::from_millis(1000)) < 1e-4);
+    }
+
+    #[test]
+    fn default_keys_are_esc_and_the_function_row() {
+        let config = BatteryWarning::default();
+        let keys = config.key_indices().unwrap();
+        assert_eq!(keys.len(), 13);
+        assert_eq!(config.rgb().unwrap(), RgbColor::new(255, 0, 0));
+    }
+}
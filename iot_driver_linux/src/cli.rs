@@ -1,6 +1,6 @@
 // CLI definitions using clap
 
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -32,10 +32,37 @@ pub struct Cli {
     #[arg(long, global = true)]
     pub filter: Option<String>,
 
-    /// Select device by index, transport (usb/dongle/bt), or HID path
+    /// Select device by index, transport (usb/dongle/bt), USB id
+    /// (VID:PID, 0xPID, pid:PID), serial number, or HID path
     #[arg(short = 'D', long, global = true, value_name = "DEVICE")]
     pub device: Option<String>,
 
+    /// Target profile for trigger, keymap and LED commands (default: active
+    /// profile for triggers and LEDs, profile 0 for keymap). The active
+    /// profile is restored afterwards.
+    #[arg(
+        short = 'P',
+        long = "profile",
+        global = true,
+        value_name = "PROFILE",
+        value_parser = clap::value_parser!(u8).range(0..4)
+    )]
+    pub target_profile: Option<u8>,
+
+    /// Print query results as JSON instead of formatted text
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Don't contact the firmware server; answer firmware checks and
+    /// downloads from the local cache only
+    #[arg(long, global = true)]
+    pub offline: bool,
+
+    /// Log every HID frame as a structured tracing event to stderr
+    #[cfg(feature = "hid-trace")]
+    #[arg(long, global = true)]
+    pub trace: bool,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -47,13 +74,19 @@ pub enum Commands {
     #[command(visible_aliases = ["version", "ver", "v"])]
     Info,
 
-    /// Get current profile (0-3)
+    /// Get current profile (0-3), or back up / restore all profiles
     #[command(visible_aliases = ["prof", "p"])]
-    Profile,
+    Profile {
+        #[command(subcommand)]
+        action: Option<ProfileCommands>,
+    },
 
-    /// Get LED settings (mode, brightness, speed, color)
+    /// Get LED settings (mode, brightness, speed, color), or preview them
     #[command(visible_aliases = ["light", "l"])]
-    Led,
+    Led {
+        #[command(subcommand)]
+        action: Option<LedCommands>,
+    },
 
     /// Get debounce time (ms)
     #[command(visible_aliases = ["deb", "d"])]
@@ -178,8 +211,12 @@ pub enum Commands {
         uniform: Option<String>,
     },
 
-    /// Factory reset keyboard
-    Reset,
+    /// Factory reset keyboard (backs up all profiles first)
+    Reset {
+        /// Don't back up the profiles before resetting
+        #[arg(long)]
+        no_backup: bool,
+    },
 
     /// Run calibration (min + max)
     #[command(visible_alias = "cal")]
@@ -327,9 +364,9 @@ pub enum Commands {
     /// Remap a key (supports layer prefix: Fn+Caps, L1+A)
     #[command(visible_alias = "set-key")]
     Remap {
-        /// Source key: name, index, or with layer prefix (Fn+Caps, L1+A, 42)
+        /// Source key: name (capslock, Esc), index, or with layer prefix (Fn+Caps, L1+A, 42)
         from: String,
-        /// Target HID keycode or key name
+        /// Target key name (esc, ctrl+c) or HID keycode (0x29)
         to: String,
         /// Layer (0=base, 1=layer1, 2=fn) — overridden by prefix in FROM
         #[arg(short, long, default_value = "0")]
@@ -352,7 +389,15 @@ pub enum Commands {
         key1: String,
         /// Second key
         key2: String,
-        /// Layer (0-3)
+        /// Profile to swap in (0-3); `--profile` takes precedence
+        #[arg(short, long, default_value = "0")]
+        layer: u8,
+    },
+
+    /// Draw the keyboard with each key's matrix index, name and mapping
+    #[command(visible_alias = "layout")]
+    Keys {
+        /// Layer whose mapping to show: 0=base, 1=layer1, 2=fn
         #[arg(short, long, default_value = "0")]
         layer: u8,
     },
@@ -384,12 +429,44 @@ pub enum Commands {
         layer: u8,
     },
 
+    /// Export or import the keymap as VIA/QMK-style JSON
+    Keymap {
+        #[command(subcommand)]
+        action: KeymapCommands,
+    },
+
+    /// Record per-key press counts and show them as a report or on the LEDs
+    Heatmap {
+        #[command(subcommand)]
+        action: HeatmapCommands,
+    },
+
+    /// List or run lighting plugins from ~/.config/monsgeek/plugins/
+    Plugin {
+        #[command(subcommand)]
+        action: PluginCommands,
+    },
+
+    /// Set external state values (calendar, weather, build status, ...) that
+    /// daemon effects such as [ambient] react to
+    State {
+        #[command(subcommand)]
+        action: StateCommands,
+    },
+
     // === Macro Commands ===
-    /// Get macro for a key
-    #[command(visible_alias = "get-macro")]
+    /// Get macro for a key, or record/export/import one (macro record <slot>)
+    #[command(
+        visible_alias = "get-macro",
+        args_conflicts_with_subcommands = true,
+        subcommand_negates_reqs = true
+    )]
     Macro {
         /// Key position or name
-        key: String,
+        #[arg(required = true)]
+        key: Option<String>,
+        #[command(subcommand)]
+        action: Option<MacroCommands>,
     },
 
     /// Set a text macro for a key
@@ -468,6 +545,98 @@ pub enum Commands {
         power_budget: u32,
     },
 
+    /// Drive LEDs from SignalRGB/Artemis/OpenRGB via WLED realtime UDP
+    LedBridge {
+        /// UDP address to listen on
+        #[arg(long, default_value = "127.0.0.1:21324")]
+        listen: std::net::SocketAddr,
+        /// Maximum frames per second sent to the keyboard
+        #[arg(long, default_value = "30")]
+        fps: f32,
+        /// LED power budget in milliamps (0 = unlimited)
+        #[arg(long, default_value = "400")]
+        power_budget: u32,
+    },
+
+    /// Light key zones from game telemetry (health, ammo, RPM) sent over UDP
+    /// or written to a file, mapped by [telemetry] in settings.toml
+    Telemetry {
+        /// UDP address to listen on
+        #[arg(long, default_value = "127.0.0.1:21325")]
+        listen: std::net::SocketAddr,
+        /// Also read values from this file whenever it changes
+        /// (e.g. /dev/shm/monsgeek-telemetry)
+        #[arg(long)]
+        file: Option<std::path::PathBuf>,
+        /// Maximum frames per second sent to the keyboard
+        #[arg(long, default_value = "30")]
+        fps: f32,
+        /// LED power budget in milliamps (0 = unlimited)
+        #[arg(long, default_value = "400")]
+        power_budget: u32,
+    },
+
+    /// Light the keyboard by live typing speed (words per minute; needs
+    /// patched firmware LED streaming)
+    Wpm {
+        /// gradient (whole board in one color) or bar (fills the function row)
+        #[arg(value_enum, long, default_value = "gradient")]
+        display: WpmDisplayArg,
+        /// Speed that reaches the fast color / fills the bar
+        #[arg(long, default_value = "120")]
+        max_wpm: f32,
+        /// Averaging window in seconds (shorter reacts faster, jumps more)
+        #[arg(long, default_value = "5")]
+        window: f32,
+        /// Colors as #RRGGBB: SLOW,FAST (default #00FF00,#FF0000)
+        #[arg(long)]
+        colors: Option<String>,
+        /// LED power budget in milliamps (0 = unlimited)
+        #[arg(long, default_value = "400")]
+        power_budget: u32,
+    },
+
+    /// Show CPU load, RAM usage or temperature as a gauge on the keys
+    /// (needs patched firmware LED streaming). Unset options come from
+    /// [sysmon] in settings.toml
+    Sysmon {
+        /// What to show
+        #[arg(value_enum, long, short)]
+        metric: Option<SysmonMetricArg>,
+        /// columns (fill left to right) or rows (fill bottom to top)
+        #[arg(value_enum, long)]
+        layout: Option<GaugeLayoutArg>,
+        /// Update interval in milliseconds (default 250)
+        #[arg(long)]
+        interval: Option<u64>,
+        /// LED power budget in milliamps (0 = unlimited, default 400)
+        #[arg(long)]
+        power_budget: Option<u32>,
+    },
+
+    /// Pomodoro timer on the LEDs: work intervals fade (or fill rows) toward
+    /// break time, phase changes flash (needs patched firmware LED streaming)
+    Timer {
+        /// Work interval in minutes
+        #[arg(long, default_value = "25")]
+        work: f32,
+        /// Break in minutes
+        #[arg(long = "break", default_value = "5")]
+        rest: f32,
+        /// Work intervals before the timer finishes (0 = until Ctrl+C)
+        #[arg(long, default_value = "4")]
+        cycles: u32,
+        /// fade (whole board changes color) or rows (rows fill up)
+        #[arg(value_enum, long, default_value = "fade")]
+        style: TimerStyleArg,
+        /// Colors as #RRGGBB: START,END,BREAK (default #00FF00,#FF0000,#0050FF)
+        #[arg(long)]
+        colors: Option<String>,
+        /// LED power budget in milliamps (0 = unlimited)
+        #[arg(long, default_value = "400")]
+        power_budget: u32,
+    },
+
     /// Set LED mode by name or number
     Mode {
         /// Mode name (breathing, wave, rainbow, etc.) or number (0-24)
@@ -498,6 +667,30 @@ pub enum Commands {
         /// Capture device (exact name or case-insensitive substring); default auto-detects the system monitor source. See `audio-test` for candidates.
         #[arg(short, long)]
         device: Option<String>,
+        /// Capture only this application's output (name or case-insensitive substring, e.g. "spotify"); see `audio-test` for what is playing
+        #[arg(short, long, conflicts_with = "device")]
+        app: Option<String>,
+        /// Spectrum mode: number of frequency bands spread over the 16 columns
+        #[arg(long, default_value = "16", value_parser = clap::value_parser!(u8).range(1..=16))]
+        bands: u8,
+        /// Colors as #RRGGBB. Spectrum mode: LOW,HIGH[,PEAK] (PEAK may be "none", default #00FF00,#FF0000,#FFFFFF); beat mode: IDLE,BEAT (default #000028,#FF0080)
+        #[arg(long)]
+        colors: Option<String>,
+        /// Spectrum mode: peak falloff speed in rows per second
+        #[arg(long, default_value = "6.0")]
+        falloff: f32,
+        /// Beat mode: what happens on a beat
+        #[arg(value_enum, long, default_value = "pulse")]
+        effect: BeatEffectArg,
+        /// Beat mode: rise time to full brightness in milliseconds
+        #[arg(long, default_value = "10")]
+        attack: u64,
+        /// Beat mode: fade time constant in milliseconds
+        #[arg(long, default_value = "150")]
+        decay: u64,
+        /// Spectrum/beat mode: LED power budget in milliamps (0 = unlimited)
+        #[arg(long, default_value = "400")]
+        power_budget: u32,
     },
 
     /// Test audio capture (list devices)
@@ -508,6 +701,9 @@ pub enum Commands {
         /// Capture device (exact name or case-insensitive substring); default auto-detects the system monitor source.
         #[arg(short, long)]
         device: Option<String>,
+        /// Capture only this application's output (name or case-insensitive substring)
+        #[arg(short, long, conflicts_with = "device")]
+        app: Option<String>,
     },
 
     // === Screen Color Commands ===
@@ -518,6 +714,32 @@ pub enum Commands {
         /// Capture framerate (1-60, default 2)
         #[arg(short, long, default_value = "2")]
         fps: u32,
+        /// Capture backend: auto picks wlr-screencopy when the compositor
+        /// offers it (Sway, Hyprland, niri, ...), else the desktop portal
+        #[arg(short, long, value_enum, default_value_t)]
+        backend: ScreenBackendArg,
+        /// Per-key ambient zones instead of one color (needs patched firmware
+        /// LED streaming); uses the mapping saved for this keyboard
+        #[arg(long)]
+        zones: bool,
+        /// Zones: grid (screen miniature) or edges (ambilight)
+        #[arg(long, value_enum, requires = "zones")]
+        zone_mode: Option<ZoneModeArg>,
+        /// Zones: LED matrix columns the layout uses, from the left (1-16)
+        #[arg(long, requires = "zones", value_parser = clap::value_parser!(u8).range(1..=16))]
+        zone_columns: Option<u8>,
+        /// Zones: LED matrix rows the layout uses, from the top (1-6)
+        #[arg(long, requires = "zones", value_parser = clap::value_parser!(u8).range(1..=6))]
+        zone_rows: Option<u8>,
+        /// Zones: edge band thickness as a fraction of the screen (edges mode)
+        #[arg(long, requires = "zones")]
+        edge_depth: Option<f32>,
+        /// Zones: save the zone options given as this keyboard's mapping
+        #[arg(long, requires = "zones")]
+        save_zones: bool,
+        /// Zones: LED power budget in milliamps (0 = unlimited)
+        #[arg(long, default_value = "400")]
+        power_budget: u32,
     },
 
     // === Dongle Commands ===
@@ -525,6 +747,12 @@ pub enum Commands {
     #[command(subcommand)]
     Dongle(DongleCommands),
 
+    /// Work with USB captures (decode one with the global --file flag)
+    Pcap {
+        #[command(subcommand)]
+        action: PcapCommands,
+    },
+
     // === Debug Commands ===
     /// Test new transport abstraction layer
     #[command(visible_alias = "tt")]
@@ -544,14 +772,88 @@ pub enum Commands {
         verbose: bool,
     },
 
+    /// Measure the achieved USB report rate from evdev timestamps and compare with the configured rate
+    #[command(visible_alias = "mr")]
+    MeasureRate {
+        /// Measurement window in seconds
+        #[arg(short, long, default_value = "10")]
+        duration: u64,
+    },
+
+    /// Measure latency between vendor depth events and evdev key presses, per transport
+    #[command(visible_alias = "lat")]
+    Latency {
+        /// Number of keystrokes to collect per device
+        #[arg(short = 'n', long, default_value = "50")]
+        presses: usize,
+        /// Measure every connected device/transport in turn and compare
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Diagnose Hall sensors: sample resting depth and flag noisy, drifting or stuck switches
+    #[command(visible_alias = "health")]
+    SwitchHealth {
+        /// Sampling window in seconds (keep hands off the keyboard)
+        #[arg(short, long, default_value = "10")]
+        duration: u64,
+        /// Max resting depth standard deviation in mm
+        #[arg(long, default_value = "0.05")]
+        noise: f32,
+        /// Max baseline drift over the window in mm
+        #[arg(long, default_value = "0.10")]
+        drift: f32,
+        /// Depth in mm above which a resting key counts as stuck
+        #[arg(long, default_value = "0.10")]
+        stuck: f32,
+        /// Also list keys that reported but passed every check
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
+    /// Interactive key test: highlight presses, report dead and chattering keys
+    #[command(visible_alias = "key-test")]
+    TestKeys {
+        /// Use analog depth instead of evdev (covers Fn and non-key bindings)
+        #[arg(long)]
+        depth: bool,
+        /// Depth in mm that counts as a press with --depth
+        #[arg(long, default_value = "0.5")]
+        threshold: f32,
+    },
+
+    /// Protocol conformance suite: query every supported command, write and
+    /// read back settings, restore the originals
+    Selftest {
+        /// Only run the queries
+        #[arg(long)]
+        read_only: bool,
+        /// Run the write checks without asking
+        #[arg(short, long, conflicts_with = "read_only")]
+        yes: bool,
+        /// Also save the report as JSON in this directory, named after the
+        /// device and firmware version
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+    },
+
+    /// Device definitions for keyboards the database doesn't know
+    #[command(subcommand)]
+    Device(DeviceCommands),
+
+    // === Config Commands ===
+    /// Declarative keyboard config files (apply, dump)
+    #[command(subcommand, visible_alias = "cfg")]
+    Config(ConfigCommands),
+
     // === Firmware Commands ===
     /// Firmware update tools
     #[command(subcommand, visible_alias = "fw")]
     Firmware(FirmwareCommands),
 
     // === Utility Commands ===
-    /// List all HID devices
-    #[command(visible_alias = "ls")]
+    /// List connected devices with their index, USB id and serial number
+    #[command(visible_aliases = ["ls", "devices"])]
     List,
 
     /// Generate a GitHub-ready diagnostic report (Markdown)
@@ -560,6 +862,9 @@ pub enum Commands {
         /// Also write the report to this file (still printed to stdout)
         #[arg(short, long, value_name = "FILE")]
         output: Option<std::path::PathBuf>,
+
+        #[command(subcommand)]
+        action: Option<ProbeCommands>,
     },
 
     /// Send raw command byte (hex)
@@ -569,13 +874,31 @@ pub enum Commands {
         cmd: String,
     },
 
-    /// Run gRPC server on port 3814
+    /// Run gRPC server (default 127.0.0.1:3814)
     #[command(visible_alias = "server")]
-    Serve,
+    Serve {
+        #[command(flatten)]
+        server: ServerArgs,
+    },
+
+    /// Run as a long-lived service: gRPC server with systemd readiness,
+    /// socket activation, watchdog and graceful shutdown on SIGTERM
+    Daemon {
+        #[command(flatten)]
+        server: ServerArgs,
+    },
 
     /// Run interactive terminal UI
     Tui,
 
+    /// Run the system tray applet (battery, profile and LED menu via the daemon)
+    #[cfg(feature = "dbus")]
+    Tray,
+
+    /// Print the focused application's id and title as focus changes, to
+    /// write [app_profiles] rules
+    WatchFocus,
+
     /// Run joystick mapper (maps magnetic keys to virtual joystick axes)
     #[command(visible_alias = "joy")]
     Joystick {
@@ -585,6 +908,18 @@ pub enum Commands {
         /// Run without TUI (headless mode)
         #[arg(long)]
         headless: bool,
+        /// Switch the running mapper to this profile instead ('auto' follows
+        /// the focused window again, 'default' is the top-level axes)
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+        /// Start from a built-in config instead of the config file
+        /// (flight-sim, racing); saving from the TUI writes it to the config file
+        #[arg(long, value_name = "NAME")]
+        preset: Option<String>,
+        /// Keep keys bound to the joystick from typing while the mapper runs
+        /// (grabs the keyboard; same as `suppress_keys = true` in the config)
+        #[arg(long)]
+        suppress_keys: bool,
     },
 
     // === Effect Commands ===
@@ -600,6 +935,10 @@ pub enum Commands {
         /// Print daemon activity to stderr
         #[arg(long, short)]
         verbose: bool,
+        /// Flash keys for desktop notifications, per `[desktop_notifications]`
+        /// in settings.toml (on by default with `enabled = true` there)
+        #[arg(long)]
+        desktop_notifications: bool,
     },
 
     /// Post a notification to the daemon (requires running notify-daemon)
@@ -743,14 +1082,163 @@ pub enum AudioMode {
     Bars,
     /// MusicPatterns (mode 20). Same on-device renderer as bars; --style 0-2
     Patterns,
+    /// Host-rendered spectrum analyzer with peak falloff (needs patched firmware
+    /// LED streaming); see --bands, --colors, --falloff
+    Spectrum,
+    /// Host-rendered beat lighting (needs patched firmware LED streaming); see
+    /// --effect, --attack, --decay, --colors. --sensitivity scales how easily beats trigger
+    Beat,
+}
+
+/// Beat mode effect
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Default)]
+pub enum BeatEffectArg {
+    /// Fade from the idle color to the beat color and back
+    #[default]
+    Pulse,
+    /// Flash the beat color, dark in between
+    Strobe,
+    /// Step the hue on every beat
+    Hue,
+}
+
+impl From<BeatEffectArg> for iot_driver::audio_beat::BeatEffect {
+    fn from(e: BeatEffectArg) -> Self {
+        match e {
+            BeatEffectArg::Pulse => Self::Pulse,
+            BeatEffectArg::Strobe => Self::Strobe,
+            BeatEffectArg::Hue => Self::Hue,
+        }
+    }
+}
+
+/// WPM meter display
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Default)]
+pub enum WpmDisplayArg {
+    /// Whole board in one color from the slow-fast gradient
+    #[default]
+    Gradient,
+    /// Bar filling the function row left to right
+    Bar,
+}
+
+impl From<WpmDisplayArg> for iot_driver::wpm::WpmDisplay {
+    fn from(d: WpmDisplayArg) -> Self {
+        match d {
+            WpmDisplayArg::Gradient => Self::Gradient,
+            WpmDisplayArg::Bar => Self::Bar,
+        }
+    }
+}
+
+/// System monitor gauge metric
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum SysmonMetricArg {
+    /// CPU load across all cores
+    Cpu,
+    /// Memory in use
+    Ram,
+    /// Hottest CPU temperature sensor
+    Temp,
+}
+
+impl From<SysmonMetricArg> for iot_driver::sysmon::Metric {
+    fn from(m: SysmonMetricArg) -> Self {
+        match m {
+            SysmonMetricArg::Cpu => Self::Cpu,
+            SysmonMetricArg::Ram => Self::Ram,
+            SysmonMetricArg::Temp => Self::Temp,
+        }
+    }
+}
+
+/// System monitor gauge layout
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum GaugeLayoutArg {
+    /// Columns light up left to right
+    Columns,
+    /// Rows light up bottom to top
+    Rows,
+}
+
+impl From<GaugeLayoutArg> for iot_driver::sysmon::GaugeLayout {
+    fn from(l: GaugeLayoutArg) -> Self {
+        match l {
+            GaugeLayoutArg::Columns => Self::Columns,
+            GaugeLayoutArg::Rows => Self::Rows,
+        }
+    }
+}
+
+/// Pomodoro timer style
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Default)]
+pub enum TimerStyleArg {
+    /// Whole board fades from the start color to the end color
+    #[default]
+    Fade,
+    /// Rows fill bottom to top; breaks drain them
+    Rows,
+}
+
+impl From<TimerStyleArg> for iot_driver::pomodoro::TimerStyle {
+    fn from(s: TimerStyleArg) -> Self {
+        match s {
+            TimerStyleArg::Fade => Self::Fade,
+            TimerStyleArg::Rows => Self::Rows,
+        }
+    }
 }
 
 impl AudioMode {
-    /// LED mode byte for this visualizer (MusicBars=22 / MusicPatterns=20).
-    pub fn led_mode(&self) -> u8 {
+    /// LED mode byte for the on-device visualizers (MusicBars=22 /
+    /// MusicPatterns=20); `None` for the host-rendered modes.
+    pub fn led_mode(&self) -> Option<u8> {
         match self {
-            AudioMode::Bars => iot_driver::protocol::cmd::LedMode::MusicBars.as_u8(),
-            AudioMode::Patterns => iot_driver::protocol::cmd::LedMode::MusicPatterns.as_u8(),
+            AudioMode::Bars => Some(iot_driver::protocol::cmd::LedMode::MusicBars.as_u8()),
+            AudioMode::Patterns => Some(iot_driver::protocol::cmd::LedMode::MusicPatterns.as_u8()),
+            AudioMode::Spectrum | AudioMode::Beat => None,
+        }
+    }
+}
+
+/// Screen capture backend
+#[cfg(feature = "screen-capture")]
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Default)]
+pub enum ScreenBackendArg {
+    /// wlr-screencopy if available, else the portal
+    #[default]
+    Auto,
+    /// xdg-desktop-portal ScreenCast via PipeWire (GNOME, KDE, portal-equipped wlroots)
+    Portal,
+    /// wlr-screencopy protocol (wlroots compositors)
+    Wlr,
+}
+
+#[cfg(feature = "screen-capture")]
+impl From<ScreenBackendArg> for iot_driver::screen_capture::CaptureBackend {
+    fn from(b: ScreenBackendArg) -> Self {
+        match b {
+            ScreenBackendArg::Auto => Self::Auto,
+            ScreenBackendArg::Portal => Self::Portal,
+            ScreenBackendArg::Wlr => Self::Wlr,
+        }
+    }
+}
+
+/// Screen zone mapping mode
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ZoneModeArg {
+    /// One cell of the screen per key
+    Grid,
+    /// Each key takes the nearest screen edge (ambilight)
+    Edges,
+}
+
+impl From<ZoneModeArg> for iot_driver::screen_zones::ZoneMode {
+    fn from(m: ZoneModeArg) -> Self {
+        match m {
+            ZoneModeArg::Grid => Self::Grid,
+            ZoneModeArg::Edges => Self::Edges,
         }
     }
 }
@@ -764,6 +1252,328 @@ pub enum PcapOutputFormat {
     Json,
 }
 
+/// Config commands
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Converge the keyboard to a TOML config, printing what changed
+    Apply {
+        /// Config file (TOML)
+        file: std::path::PathBuf,
+        /// Show the diff without writing anything
+        #[arg(long, short = 'n')]
+        dry_run: bool,
+    },
+
+    /// Write the keyboard's current settings as a TOML config for `apply`
+    Dump {
+        /// Write to this file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<std::path::PathBuf>,
+    },
+}
+
+/// LED commands
+#[derive(Subcommand)]
+pub enum LedCommands {
+    /// Show the current per-key colors as truecolor blocks in the layout
+    Preview {
+        /// Show this userpic slot (0-4) instead of the active lighting
+        #[arg(short, long)]
+        slot: Option<u8>,
+        /// Keep redrawing as the lighting changes (Ctrl+C to stop)
+        #[arg(short, long)]
+        watch: bool,
+    },
+}
+
+/// Profile commands
+#[derive(Subcommand)]
+pub enum ProfileCommands {
+    /// Save all four onboard profiles to one TOML file
+    Backup {
+        /// Output file (TOML)
+        file: std::path::PathBuf,
+    },
+
+    /// Write a backup file back to the keyboard, printing what changed
+    Restore {
+        /// Backup file (TOML)
+        file: std::path::PathBuf,
+        /// Show what would be written without writing anything
+        #[arg(long, short = 'n')]
+        dry_run: bool,
+    },
+}
+
+/// Keymap file commands
+#[derive(Subcommand)]
+pub enum KeymapCommands {
+    /// Save all three layers to a VIA-style JSON file
+    Export {
+        /// Output file (JSON)
+        file: PathBuf,
+        /// Name stored in the file
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// Write a VIA-style JSON keymap to the keyboard, printing what changed
+    Import {
+        /// Keymap file (JSON)
+        file: PathBuf,
+        /// Show what would be written without writing anything
+        #[arg(long, short = 'n')]
+        dry_run: bool,
+    },
+
+    /// Import a QMK keymap (Configurator JSON or keymap.c), listing what
+    /// can't be carried over
+    ImportQmk {
+        /// QMK keymap.json or keymap.c
+        file: PathBuf,
+        /// Show what would be written without writing anything
+        #[arg(long, short = 'n')]
+        dry_run: bool,
+    },
+}
+
+/// Lighting plugin commands
+#[derive(Subcommand)]
+pub enum PluginCommands {
+    /// Show the plugins found, their capabilities and load errors
+    List,
+
+    /// Stream an effect plugin to the LEDs (requires patched firmware) and
+    /// feed key events to every event processor until Ctrl+C
+    Run {
+        /// Effect plugin name (omit to run only the event processors)
+        name: Option<String>,
+        /// Frames per second (default 30, or [plugins] fps)
+        #[arg(long)]
+        fps: Option<u32>,
+        /// LED power budget in milliamps (0 = unlimited, default 400)
+        #[arg(long)]
+        power_budget: Option<u32>,
+    },
+}
+
+/// Protocol exploration commands
+#[derive(Subcommand)]
+pub enum ProbeCommands {
+    /// Send undocumented GET command bytes one by one and record which answer
+    /// (SET bytes and known state-changing commands are never sent)
+    Scan {
+        /// Command bytes to try (e.g. 0x80-0xff, 0xd0)
+        #[arg(short, long, default_value = "0x80-0xff")]
+        range: String,
+        /// Delay before each command, in ms
+        #[arg(short, long, default_value = "200")]
+        interval: u64,
+        /// Also probe bytes the driver already decodes
+        #[arg(long)]
+        include_known: bool,
+        /// Start without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        /// Write the results as JSON to this file
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+/// USB capture commands
+#[derive(Subcommand)]
+pub enum PcapCommands {
+    /// Align the vendor command streams of two captures and show where they
+    /// differ (e.g. the Windows driver vs ours doing the same thing)
+    Diff {
+        /// First capture (pcap or pcapng), shown as A
+        a: PathBuf,
+        /// Second capture, shown as B
+        b: PathBuf,
+        /// Also compare responses
+        #[arg(short, long)]
+        responses: bool,
+        /// Leave out these command bytes (e.g. 0x8f,0xe8)
+        #[arg(long, value_delimiter = ',', value_parser = parse_cmd_byte)]
+        ignore: Vec<u8>,
+        /// Show identical packets too
+        #[arg(long)]
+        same: bool,
+    },
+
+    /// Rebuild the keymap, macros, triggers and lighting the official driver
+    /// wrote in a capture, as a file for `config apply` / `profile restore`
+    Extract {
+        /// Capture of the official driver (pcap or pcapng)
+        file: PathBuf,
+        /// Directory to write the config and userpic images to (default: print)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Number of keys on the captured keyboard
+        #[arg(long, default_value = "98")]
+        keys: usize,
+    },
+
+    /// Command histogram, response and pacing times, timeouts and retries
+    /// (--json for the full report)
+    Stats {
+        /// Capture (pcap or pcapng)
+        file: PathBuf,
+        /// Also list the command bursts over time
+        #[arg(short, long)]
+        timeline: bool,
+        /// Idle time that separates two bursts, in ms
+        #[arg(long, default_value = "100")]
+        gap: u64,
+    },
+
+    /// Write every distinct packet and its current decode as a parser
+    /// regression test (for monsgeek-transport/tests/)
+    Fixtures {
+        /// Capture (pcap or pcapng)
+        file: PathBuf,
+        /// Test file to write (default: print)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Keep at most this many packets per command byte (0 = all)
+        #[arg(long, default_value = "20")]
+        max_per_cmd: usize,
+    },
+
+    /// Generate a Wireshark Lua dissector from the protocol definitions
+    Dissector {
+        /// Lua file to write (default: print)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Re-send a capture's commands to the device and check that our framing
+    /// reproduces them byte-for-byte and the replies match
+    Replay {
+        /// Capture of the official driver (pcap or pcapng)
+        file: PathBuf,
+        /// Replay SET commands without asking
+        #[arg(short, long)]
+        yes: bool,
+        /// Only replay GET commands
+        #[arg(long, conflicts_with = "yes")]
+        gets_only: bool,
+        /// Pause before each command, in ms
+        #[arg(long, default_value = "20")]
+        delay: u64,
+    },
+}
+
+fn parse_cmd_byte(s: &str) -> Result<u8, String> {
+    let hex = s.trim().trim_start_matches("0x").trim_start_matches("0X");
+    u8::from_str_radix(hex, 16).map_err(|_| format!("invalid command byte '{s}' (e.g. 0x8f)"))
+}
+
+/// External state commands
+#[derive(Subcommand)]
+pub enum StateCommands {
+    /// Set a value (a number, or true/false)
+    Set {
+        /// Value name (e.g. calendar.soon, weather.rain)
+        name: String,
+        value: String,
+        /// Drop the value after this long: seconds, or 30s, 15m, 2h, 1d
+        #[arg(long)]
+        ttl: Option<String>,
+    },
+
+    /// Remove a value
+    Clear {
+        /// Value name
+        name: String,
+    },
+
+    /// Show the current values
+    #[command(visible_alias = "ls")]
+    List,
+}
+
+/// Typing heatmap commands
+#[derive(Subcommand)]
+pub enum HeatmapCommands {
+    /// Count key presses until Ctrl+C, adding them to the saved heatmap
+    Record {
+        /// Count presses from analog depth reports instead of evdev
+        /// (sees keys evdev can't attribute, e.g. Fn)
+        #[arg(long)]
+        depth: bool,
+        /// Depth in mm that counts as a press with --depth
+        #[arg(long, default_value = "0.5")]
+        threshold: f32,
+    },
+
+    /// Show the heatmap as a colored layout and list the most pressed keys
+    /// (--json for the full per-key counts)
+    Show {
+        /// Number of keys to list
+        #[arg(long, short = 'n', default_value = "10")]
+        top: usize,
+    },
+
+    /// Light each key by how often it was pressed (requires patched firmware)
+    Led {
+        /// LED power budget in milliamps (0 = unlimited)
+        #[arg(long, default_value = "400")]
+        power_budget: u32,
+    },
+
+    /// Forget the recorded heatmap for the connected keyboard
+    Reset,
+}
+
+/// Macro commands
+#[derive(Subcommand)]
+pub enum MacroCommands {
+    /// Record keystrokes from the keyboard into a macro slot
+    Record {
+        /// Macro slot number
+        slot: u8,
+        /// Key that stops recording (not included in the macro)
+        #[arg(long, default_value = "Esc")]
+        stop: String,
+        /// Replace recorded timing with a fixed delay between events (ms)
+        #[arg(short, long)]
+        delay: Option<u16>,
+        /// Cap recorded pauses at this many ms
+        #[arg(long, default_value = "1000")]
+        max_delay: u16,
+        /// How many times to repeat the macro
+        #[arg(short, long, default_value = "1")]
+        repeat: u16,
+        /// Write without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Save a macro slot to a TOML file
+    Export {
+        /// Macro slot number
+        slot: u8,
+        /// Output file (TOML)
+        file: PathBuf,
+        /// Name stored in the file
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// Load a macro file into a slot
+    Import {
+        /// Macro file (TOML)
+        file: PathBuf,
+        /// Macro slot number
+        slot: u8,
+        /// Show the decoded macro without writing it
+        #[arg(long, short = 'n')]
+        dry_run: bool,
+    },
+}
+
 /// Firmware commands
 #[derive(Subcommand)]
 pub enum FirmwareCommands {
@@ -783,6 +1593,10 @@ pub enum FirmwareCommands {
         /// Show detailed command sequence
         #[arg(short, long)]
         verbose: bool,
+
+        /// Simulate a dongle update instead of a keyboard update
+        #[arg(long)]
+        dongle: bool,
     },
 
     /// Check for firmware updates from MonsGeek server
@@ -793,6 +1607,12 @@ pub enum FirmwareCommands {
         device_id: Option<u32>,
     },
 
+    /// List which device ID and firmware API ID each USB VID/PID maps to
+    Ids {
+        /// Only show products whose name, device ID or VID:PID (hex) matches
+        filter: Option<String>,
+    },
+
     /// Download firmware from MonsGeek server
     #[command(visible_alias = "dl")]
     Download {
@@ -819,8 +1639,129 @@ pub enum FirmwareCommands {
         #[arg(long)]
         dongle: bool,
 
-        /// Skip confirmation prompt
+        /// Skip the confirmation prompts (still needs --i-understand-the-risk)
         #[arg(short, long)]
         yes: bool,
+
+        /// Required: acknowledge that a failed flash can brick the device
+        #[arg(long = "i-understand-the-risk")]
+        i_understand_the_risk: bool,
+
+        /// Don't back up the keyboard's profiles before flashing
+        #[arg(long)]
+        no_backup: bool,
+    },
+
+    /// Re-flash the firmware that was installed before the last flash
+    Rollback {
+        /// HID device path (required when multiple devices found)
+        #[arg(long)]
+        device: Option<String>,
+
+        /// Roll back the dongle instead of the keyboard
+        #[arg(long)]
+        dongle: bool,
+
+        /// Only list the recorded flashes
+        #[arg(long)]
+        list: bool,
+
+        /// Skip the confirmation prompts (still needs --i-understand-the-risk)
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Required: acknowledge that a failed flash can brick the device
+        #[arg(long = "i-understand-the-risk")]
+        i_understand_the_risk: bool,
+
+        /// Don't back up the keyboard's profiles before flashing
+        #[arg(long)]
+        no_backup: bool,
     },
+
+    /// Recover a device stuck in bootloader mode by flashing a known-good image
+    #[command(visible_alias = "rec")]
+    Recover {
+        /// Firmware to flash; without it, only lists devices in bootloader mode
+        file: Option<PathBuf>,
+
+        /// Bootloader HID path (required when several are in bootloader mode)
+        #[arg(long)]
+        device: Option<String>,
+
+        /// Skip the confirmation prompts (still needs --i-understand-the-risk)
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Required to flash: acknowledge that a failed flash can brick the device
+        #[arg(long = "i-understand-the-risk")]
+        i_understand_the_risk: bool,
+    },
+}
+
+/// Device definition commands
+#[derive(Subcommand)]
+pub enum DeviceCommands {
+    /// Probe the connected keyboard and write a definition for it, lighting
+    /// each key so you can confirm its position
+    Add {
+        /// Write here instead of ~/.config/monsgeek/devices/
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Skip the per-key LED confirmation and trust the key matrix
+        #[arg(long)]
+        no_leds: bool,
+    },
+}
+
+/// Options shared by `serve` and `daemon`.
+#[derive(Args, Debug, Clone)]
+pub struct ServerArgs {
+    /// Address to listen on (the daemon ignores it when socket-activated).
+    /// Binding beyond localhost exposes the keyboard; set a token and TLS.
+    #[arg(long, default_value = "127.0.0.1:3814")]
+    pub listen: std::net::SocketAddr,
+
+    /// PEM certificate chain; serves gRPC/REST over TLS (needs --tls-key)
+    #[cfg(feature = "tls")]
+    #[arg(long, value_name = "FILE", requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[cfg(feature = "tls")]
+    #[arg(long, value_name = "FILE", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Also serve the REST/JSON API under /api/v1
+    #[cfg(feature = "rest")]
+    #[arg(long)]
+    pub rest: bool,
+
+    /// Stream keyboard events as JSON over WebSocket at ws://ADDR/events
+    #[cfg(feature = "rest")]
+    #[arg(long, value_name = "ADDR")]
+    pub events: Option<std::net::SocketAddr>,
+
+    /// Relay WebRTC offers/answers under /webrtc so remote clients can
+    /// open a data channel to the local peer
+    #[cfg(feature = "rest")]
+    #[arg(long)]
+    pub webrtc: bool,
+
+    /// Don't advertise the server via mDNS (_monsgeek._tcp) when it
+    /// listens beyond localhost
+    #[cfg(feature = "dbus")]
+    #[arg(long)]
+    pub no_mdns: bool,
+}
+
+impl ServerArgs {
+    /// Certificate and key files, if TLS is enabled.
+    pub fn tls(&self) -> Option<(&std::path::Path, &std::path::Path)> {
+        #[cfg(feature = "tls")]
+        return self.tls_cert.as_deref().zip(self.tls_key.as_deref());
+        #[cfg(not(feature = "tls"))]
+        None
+    }
 }
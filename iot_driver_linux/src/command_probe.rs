@@ -0,0 +1,214 @@
+//! Safety policy and reply classification for `probe scan`.
+//!
+//! The scan walks undocumented command bytes to see which ones the firmware
+//! answers and with what shape. Only the GET half of the command space (bit 7
+//! set) is ever sent: everything below 0x80 is a SET. A few bytes in the GET
+//! half still write or switch modes (LED streaming, firmware transfer, dongle
+//! RF programming) and are blocked outright. Sending lives in the command
+//! handler; this module only decides what may be sent and what came back.
+
+use std::ops::RangeInclusive;
+
+use monsgeek_transport::protocol::cmd;
+use serde::Serialize;
+
+/// Bytes with bit 7 set that change device state anyway. Never probed.
+pub const BLOCKLIST: &[(u8, &str)] = &[
+    (cmd::ISP_PREPARE, "prepares a firmware update"),
+    (cmd::FW_TRANSFER, "firmware transfer"),
+    (cmd::LED_STREAM, "writes the LED frame buffer"),
+    (cmd::ANIM_CMD, "defines and clears on-device animations"),
+    (cmd::SET_CTRL_BYTE, "writes the dongle control byte"),
+    (
+        cmd::ENTER_PAIRING,
+        "puts the dongle into RF programming mode",
+    ),
+    (
+        cmd::GET_CACHED_RESPONSE,
+        "consumes the dongle's cached reply",
+    ),
+    (
+        cmd::SET_RESPONSE_SIZE,
+        "dongle: overrides the next SPI packet length",
+    ),
+];
+
+/// Why a byte in the requested range isn't sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Skip {
+    /// Below 0x80: writes settings
+    SetCommand,
+    /// On the blocklist, with the reason
+    Blocked(&'static str),
+    /// Already decoded; only probed with `--include-known`
+    Known,
+}
+
+/// Whether `byte` may be probed.
+pub fn check(byte: u8, include_known: bool) -> Result<(), Skip> {
+    if byte & 0x80 == 0 {
+        return Err(Skip::SetCommand);
+    }
+    if let Some((_, why)) = BLOCKLIST.iter().find(|(b, _)| *b == byte) {
+        return Err(Skip::Blocked(why));
+    }
+    if !include_known && cmd::name(byte) != "UNKNOWN" {
+        return Err(Skip::Known);
+    }
+    Ok(())
+}
+
+/// Split `range` into bytes to send and bytes skipped (with the reason).
+pub fn plan(range: RangeInclusive<u8>, include_known: bool) -> (Vec<u8>, Vec<(u8, Skip)>) {
+    let mut send = Vec::new();
+    let mut skipped = Vec::new();
+    for byte in range {
+        match check(byte, include_known) {
+            Ok(()) => send.push(byte),
+            Err(skip) => skipped.push((byte, skip)),
+        }
+    }
+    (send, skipped)
+}
+
+/// Parse `0x80-0xff`, `a0-af` or a single byte.
+pub fn parse_range(s: &str) -> Result<RangeInclusive<u8>, String> {
+    let byte = |t: &str| {
+        let t = t.trim();
+        let hex = t.trim_start_matches("0x").trim_start_matches("0X");
+        u8::from_str_radix(hex, 16).map_err(|_| format!("invalid byte '{t}' (e.g. 0x80-0xff)"))
+    };
+    let (lo, hi) = match s.split_once('-') {
+        Some((lo, hi)) => (byte(lo)?, byte(hi)?),
+        None => {
+            let b = byte(s)?;
+            (b, b)
+        }
+    };
+    if lo > hi {
+        return Err(format!("range start 0x{lo:02x} is after end 0x{hi:02x}"));
+    }
+    Ok(lo..=hi)
+}
+
+/// What a probed byte sent back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReplyShape {
+    /// Timeout or transport error
+    NoReply { error: String },
+    /// All-zero buffer
+    Empty,
+    /// Byte-for-byte the previous reply: the firmware left its buffer alone
+    Stale,
+    /// First byte echoes the command; `len` counts bytes up to the last non-zero one
+    Echo { len: usize },
+    /// Answered without the echo
+    Other { first: u8, len: usize },
+}
+
+impl ReplyShape {
+    /// Whether the firmware produced a fresh reply for this byte.
+    pub fn answered(&self) -> bool {
+        matches!(self, ReplyShape::Echo { .. } | ReplyShape::Other { .. })
+    }
+}
+
+/// Reply with trailing zero padding removed.
+pub fn trimmed(reply: &[u8]) -> &[u8] {
+    let end = reply.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    &reply[..end]
+}
+
+/// Classify `reply` to `byte`, given the reply to the byte probed before it.
+pub fn classify(byte: u8, reply: &[u8], previous: Option<&[u8]>) -> ReplyShape {
+    let data = trimmed(reply);
+    if data.is_empty() {
+        return ReplyShape::Empty;
+    }
+    if previous.is_some_and(|p| trimmed(p) == data) {
+        return ReplyShape::Stale;
+    }
+    if data[0] == byte {
+        ReplyShape::Echo { len: data.len() }
+    } else {
+        ReplyShape::Other {
+            first: data[0],
+            len: data.len(),
+        }
+    }
+}
+
+/// One probed byte, as recorded by `probe scan --output`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeResult {
+    pub cmd: u8,
+    #[serde(flatten)]
+    pub shape: ReplyShape,
+    /// Reply bytes, trailing zeros trimmed
+    pub data: Vec<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_unknown_get_bytes_are_sent() {
+        assert_eq!(check(0x10, true), Err(Skip::SetCommand));
+        assert_eq!(check(cmd::ENTER_BOOTLOADER, true), Err(Skip::SetCommand));
+        assert!(matches!(
+            check(cmd::LED_STREAM, true),
+            Err(Skip::Blocked(_))
+        ));
+        assert!(matches!(
+            check(cmd::ENTER_PAIRING, true),
+            Err(Skip::Blocked(_))
+        ));
+        assert_eq!(check(cmd::GET_REV, false), Err(Skip::Known));
+        assert_eq!(check(cmd::GET_REV, true), Ok(()));
+        assert_eq!(check(0x81, false), Ok(()));
+
+        let (send, skipped) = plan(0x7e..=0x82, false);
+        assert_eq!(send, vec![0x81, 0x82]);
+        assert_eq!(skipped.len(), 3);
+    }
+
+    #[test]
+    fn blocklist_is_all_get_space() {
+        for (byte, _) in BLOCKLIST {
+            assert_ne!(byte & 0x80, 0, "0x{byte:02x} is already excluded");
+        }
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("0x80-0xff").unwrap(), 0x80..=0xff);
+        assert_eq!(parse_range("a0 - af").unwrap(), 0xa0..=0xaf);
+        assert_eq!(parse_range("0xd0").unwrap(), 0xd0..=0xd0);
+        assert!(parse_range("0xff-0x80").is_err());
+        assert!(parse_range("0x100").is_err());
+    }
+
+    #[test]
+    fn classifies_replies() {
+        assert_eq!(classify(0xd0, &[0; 64], None), ReplyShape::Empty);
+        assert_eq!(
+            classify(0xd0, &[0xd0, 1, 2, 0, 0], None),
+            ReplyShape::Echo { len: 3 }
+        );
+        assert_eq!(
+            classify(0xd1, &[0xd0, 1, 2, 0], Some(&[0xd0, 1, 2, 0, 0, 0])),
+            ReplyShape::Stale
+        );
+        assert_eq!(
+            classify(0xd1, &[0xaa, 0x55], Some(&[0xd0])),
+            ReplyShape::Other {
+                first: 0xaa,
+                len: 2
+            }
+        );
+        assert!(!ReplyShape::Stale.answered());
+        assert!(ReplyShape::Echo { len: 1 }.answered());
+    }
+}
@@ -0,0 +1,171 @@
+//! Declarative config command handlers (config apply, config dump, profile
+//! backup, profile restore) and the automatic backups taken before firmware
+//! flashes and factory resets.
+
+use super::{print_json, with_keyboard, CmdCtx, CommandResult};
+use iot_driver::keyboard_config::{
+    read_backup, restore_calibration, DeviceState, KeyboardConfig, ProfileBackup,
+};
+use monsgeek_keyboard::KeyboardInterface;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Converge the device to the config in `path`, printing what changed.
+///
+/// The target profile is `--profile`, else the file's `profile`, else the
+/// active one; it is switched to for the duration and restored afterwards.
+pub fn apply(ctx: &CmdCtx, path: &Path, dry_run: bool) -> CommandResult {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let config =
+        KeyboardConfig::from_toml(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+
+    with_keyboard(ctx, |kb| {
+        let profile = match ctx.profile.or(config.profile) {
+            Some(p) => p,
+            None => kb.get_profile()?,
+        };
+        kb.with_profile(profile, |kb| {
+            apply_in_profile(ctx, kb, &config, profile, dry_run)
+        })
+    })
+}
+
+fn apply_in_profile(
+    ctx: &CmdCtx,
+    kb: &KeyboardInterface,
+    config: &KeyboardConfig,
+    profile: u8,
+    dry_run: bool,
+) -> CommandResult {
+    let state = DeviceState::read(kb, config)?;
+    let plan = config.plan(&state)?;
+
+    if ctx.json {
+        print_json(&serde_json::json!({
+            "profile": profile,
+            "changes": plan.changes,
+            "skipped": plan.skipped,
+            "applied": !dry_run && !plan.is_empty(),
+        }))?;
+    } else {
+        for section in &plan.skipped {
+            println!("Skipping {section}: not supported by this device");
+        }
+        if plan.is_empty() {
+            println!("Profile {profile}: already matches config");
+            return Ok(());
+        }
+        println!("Profile {profile}: {} change(s)", plan.changes.len());
+        for change in &plan.changes {
+            println!("  {change}");
+        }
+    }
+    if dry_run || plan.is_empty() {
+        return Ok(());
+    }
+
+    plan.apply(kb, profile)?;
+
+    if !ctx.json {
+        println!("Config applied");
+    }
+    Ok(())
+}
+
+/// Write the current device state as a config file `apply` accepts.
+///
+/// Reads `--profile` (default: the active one) and prints to stdout unless
+/// `output` is given.
+pub fn dump(ctx: &CmdCtx, output: Option<&Path>) -> CommandResult {
+    with_keyboard(ctx, |kb| {
+        let profile = match ctx.profile {
+            Some(p) => p,
+            None => kb.get_profile()?,
+        };
+        let state = kb.with_profile(profile, DeviceState::read_all)?;
+        let text = state.to_config_toml(profile);
+        match output {
+            Some(path) => {
+                std::fs::write(path, &text)
+                    .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+                eprintln!("Wrote profile {profile} config to {}", path.display());
+            }
+            None => print!("{text}"),
+        }
+        Ok(())
+    })
+}
+
+/// Print which profile is being read, for the slow backup reads.
+pub(super) fn reading(profile: u8) {
+    eprintln!("Reading profile {profile}...");
+}
+
+/// Save every onboard profile to one backup file.
+pub fn backup(ctx: &CmdCtx, path: &Path) -> CommandResult {
+    with_keyboard(ctx, |kb| {
+        let backup = read_backup(kb, reading).map_err(|e| e.to_string())?;
+        std::fs::write(path, backup.to_toml())
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        eprintln!(
+            "Wrote {} profiles to {}",
+            backup.profiles.len(),
+            path.display()
+        );
+        Ok(())
+    })
+}
+
+/// After a destructive operation, offer to restore `path` (without asking
+/// when `yes`). Declining leaves the file for a later `profile restore`.
+pub fn offer_restore(ctx: &CmdCtx, path: &Path, yes: bool) -> CommandResult {
+    if !yes {
+        print!("Restore your settings from {}? (y/N) ", path.display());
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if input.trim().to_lowercase() != "y" {
+            println!(
+                "Not restored. Later: iot_driver profile restore {}",
+                path.display()
+            );
+            return Ok(());
+        }
+    }
+    restore(ctx, path, false)
+}
+
+/// Converge every profile in a backup file, as `apply` does for one.
+pub fn restore(ctx: &CmdCtx, path: &Path, dry_run: bool) -> CommandResult {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let backup = ProfileBackup::from_toml(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+
+    if !ctx.json {
+        if let Some(device) = &backup.device {
+            println!("Backup of {device}");
+        }
+    }
+    with_keyboard(ctx, |kb| {
+        for config in &backup.profiles {
+            let profile = config.profile.unwrap_or_default();
+            kb.with_profile(profile, |kb| {
+                apply_in_profile(ctx, kb, config, profile, dry_run)
+            })?;
+        }
+        let differ = restore_calibration(kb, &backup.calibration, dry_run)?;
+        if ctx.json {
+            print_json(&serde_json::json!({
+                "calibration": differ,
+                "applied": !dry_run && differ > 0,
+            }))?;
+        } else if differ > 0 {
+            println!("Calibration: {differ} key(s) differ");
+            if !dry_run {
+                println!("Calibration restored");
+            }
+        }
+        Ok(())
+    })
+}
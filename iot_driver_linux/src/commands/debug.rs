@@ -1,15 +1,24 @@
 //! Debug command handlers.
 
+use super::keymap::layout_names;
 use super::{
-    open_preferred_transport, setup_interrupt_handler, with_keyboard, CmdCtx, CommandResult,
+    open_keyboard, open_preferred_transport, resolve_model_name, setup_interrupt_handler,
+    with_keyboard, CmdCtx, CommandResult,
 };
-use iot_driver::protocol::cmd;
-use monsgeek_keyboard::KeyboardInterface;
+use iot_driver::evdev::{self, monotonic_now, EventReader};
+use iot_driver::input_timing::{estimate_report_rate, pair_nearest, LatencyStats};
+use iot_driver::key_action::KeyAction;
+use iot_driver::key_test::KeyTester;
+use iot_driver::keymap::{self, render_color_layout, Layer};
+use iot_driver::protocol::{cmd, polling_rate};
+use iot_driver::switch_health::{HealthThresholds, SwitchHealthMonitor};
+use monsgeek_keyboard::{KeyboardInterface, RgbColor};
 use monsgeek_transport::protocol::cmd as transport_cmd;
-use monsgeek_transport::{list_devices_sync, ChecksumType, Transport};
+use monsgeek_transport::{list_devices_sync, ChecksumType, HidDiscovery, Transport, TransportType};
 use std::collections::HashMap;
-use std::sync::atomic::Ordering;
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 /// Test the new transport abstraction layer
 pub fn test_transport(ctx: &CmdCtx) -> CommandResult {
@@ -258,3 +267,849 @@ pub fn depth(
     );
     Ok(())
 }
+
+/// Measure the report rate actually delivered over the bus
+///
+/// Timestamps every device-originated key report on the keyboard's evdev nodes
+/// and compares the polling interval the report gaps line up with against the
+/// configured polling rate. Some hubs and ports silently cap 4k/8k modes.
+pub fn measure_rate(keyboard: &KeyboardInterface, duration_secs: u64) -> CommandResult {
+    println!("Device: {}", keyboard.device_name());
+
+    let configured = keyboard.get_polling_rate().ok().map(|r| r.to_hz());
+    match configured {
+        Some(hz) => println!("Configured polling rate: {hz} ({})", polling_rate::name(hz)),
+        None => println!("Configured polling rate: unknown"),
+    }
+
+    let reader = EventReader::open(keyboard.vid(), keyboard.pid());
+    if reader.is_empty() {
+        eprintln!(
+            "No readable input nodes for {:04X}:{:04X}. Are you in the 'input' group?",
+            keyboard.vid(),
+            keyboard.pid()
+        );
+        return Ok(());
+    }
+
+    println!(
+        "\nFor the next {duration_secs}s, hold a key and rapidly wiggle it (or roll several keys)."
+    );
+    println!("Every press/release produces a report; more reports give a steadier estimate.\n");
+
+    let running = setup_interrupt_handler();
+    let window = Duration::from_secs(duration_secs);
+    let start = Instant::now();
+    let mut timestamps = Vec::new();
+    let mut frame_has_key = false;
+
+    while running.load(Ordering::SeqCst) && start.elapsed() < window {
+        for event in reader.poll(Duration::from_millis(50)) {
+            if event.is_key_edge() {
+                frame_has_key = true;
+            } else if event.is_syn_report() {
+                if frame_has_key {
+                    timestamps.push(event.time);
+                }
+                frame_has_key = false;
+            }
+        }
+        print!("\r\x1b[K  {} reports captured", timestamps.len());
+        use std::io::Write;
+        std::io::stdout().flush().ok();
+    }
+    println!();
+
+    let Some(est) = estimate_report_rate(&timestamps) else {
+        println!("Not enough reports captured — press some keys during the measurement.");
+        return Ok(());
+    };
+
+    let us = |d: Duration| d.as_secs_f64() * 1e6;
+    println!("\nReports:        {}", est.reports);
+    println!(
+        "Report gap:     min {:.0}us  p5 {:.0}us  median {:.0}us  p95 {:.0}us",
+        us(est.gaps.min),
+        us(est.gaps.p5),
+        us(est.gaps.median),
+        us(est.gaps.p95)
+    );
+    match est.achieved_hz {
+        Some(hz) => println!("Achieved rate:  {hz} Hz"),
+        None => {
+            println!("Achieved rate:  unknown (report gaps don't line up with a polling interval)")
+        }
+    }
+
+    if est.reports < 100 {
+        println!("Note: fewer than 100 reports — the estimate may read low.");
+    }
+    if let (Some(hz), Some(_)) = (configured, est.achieved_hz) {
+        if est.is_below(hz) {
+            println!(
+                "WARNING: achieved rate is below the configured {hz} Hz. \
+                 Try a different port or connect directly instead of through a hub."
+            );
+        } else {
+            println!("OK: achieved rate matches the configured {hz} Hz.");
+        }
+    }
+    Ok(())
+}
+
+/// Max distance between a vendor actuation crossing and an evdev press for
+/// the two to be considered the same keystroke.
+const LATENCY_MAX_SKEW: Duration = Duration::from_millis(50);
+
+fn transport_label(keyboard: &KeyboardInterface) -> &'static str {
+    match keyboard.transport().device_info().transport_type {
+        TransportType::HidWired => "usb",
+        TransportType::HidDongle => "dongle",
+        TransportType::Bluetooth => "bt",
+        TransportType::WebRtc => "webrtc",
+    }
+}
+
+/// Measure vendor-depth → evdev latency on the selected device, or on every
+/// connected transport in turn when `all` is set.
+///
+/// For each keystroke, the time the vendor depth stream first crosses the key's
+/// actuation point is paired with the kernel's EV_KEY press event. The vendor
+/// side is stamped when our read returns, so the numbers are relative (compare
+/// transports against each other) rather than absolute switch-to-OS latency.
+pub fn latency(ctx: &CmdCtx, presses: usize, all: bool) -> CommandResult {
+    let selectors: Vec<Option<String>> = if all {
+        let discovery = HidDiscovery::new();
+        discovery
+            .list_labeled_devices(resolve_model_name)?
+            .iter()
+            .map(|(_, l)| Some(l.index.to_string()))
+            .collect()
+    } else {
+        vec![ctx.device.clone()]
+    };
+
+    let running = setup_interrupt_handler();
+    let mut results: Vec<(String, &'static str, LatencyStats)> = Vec::new();
+
+    for selector in selectors {
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+        let dev_ctx = CmdCtx::new(ctx.printer_config.clone(), selector);
+        let keyboard = match open_keyboard(&dev_ctx) {
+            Ok(kb) => kb,
+            Err(e) => {
+                eprintln!("Skipping device: {e}");
+                continue;
+            }
+        };
+        let transport = transport_label(&keyboard);
+        println!("\n=== {} via {transport} ===", keyboard.device_name());
+
+        match measure_latency(&keyboard, presses, &running)? {
+            Some(stats) => {
+                print_latency(&stats);
+                results.push((keyboard.device_name(), transport, stats));
+            }
+            None => println!("No keystrokes could be paired on this transport."),
+        }
+    }
+
+    if results.len() > 1 {
+        println!(
+            "\n{:<10} {:>6} {:>8} {:>8} {:>8} {:>8}",
+            "Transport", "N", "p50", "p95", "mean", "stddev"
+        );
+        for (_, transport, s) in &results {
+            println!(
+                "{:<10} {:>6} {:>6.2}ms {:>6.2}ms {:>6.2}ms {:>6.2}ms",
+                transport, s.count, s.p50_ms, s.p95_ms, s.mean_ms, s.stddev_ms
+            );
+        }
+    }
+    Ok(())
+}
+
+fn print_latency(s: &LatencyStats) {
+    println!("Paired keystrokes: {}", s.count);
+    println!(
+        "evdev - vendor:    min {:.2}ms  p50 {:.2}ms  p95 {:.2}ms  max {:.2}ms",
+        s.min_ms, s.p50_ms, s.p95_ms, s.max_ms
+    );
+    println!(
+        "                   mean {:.2}ms  stddev {:.2}ms",
+        s.mean_ms, s.stddev_ms
+    );
+}
+
+/// Collect `presses` keystrokes on one keyboard and pair vendor/evdev timings.
+fn measure_latency(
+    keyboard: &KeyboardInterface,
+    presses: usize,
+    running: &AtomicBool,
+) -> Result<Option<LatencyStats>, Box<dyn std::error::Error>> {
+    let precision = keyboard.get_precision().unwrap_or_default();
+    let press_travel = keyboard
+        .get_all_triggers()
+        .map(|t| t.press_travel)
+        .unwrap_or_default();
+    let fallback = precision.mm_to_raw(1.2);
+    let actuation = |key: u8| {
+        press_travel
+            .get(key as usize)
+            .copied()
+            .filter(|&t| t > 0)
+            .unwrap_or(fallback)
+    };
+
+    let reader = EventReader::open(keyboard.vid(), keyboard.pid());
+    if reader.is_empty() {
+        eprintln!(
+            "No readable input nodes for {:04X}:{:04X}. Are you in the 'input' group?",
+            keyboard.vid(),
+            keyboard.pid()
+        );
+        return Ok(None);
+    }
+
+    if let Err(e) = keyboard.start_magnetism_report() {
+        eprintln!("Failed to enable magnetism reporting: {e}");
+        return Ok(None);
+    }
+
+    println!(
+        "Press and fully release any key {presses} times, one key at a time (Ctrl+C to stop)..."
+    );
+
+    let crossings = Mutex::new(Vec::new());
+    let stop = AtomicBool::new(false);
+    let mut evdev_presses = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(10 + presses as u64 * 2);
+
+    std::thread::scope(|scope| {
+        let crossings = &crossings;
+        let stop = &stop;
+        scope.spawn(move || {
+            let mut last_raw: HashMap<u8, u16> = HashMap::new();
+            while !stop.load(Ordering::SeqCst) {
+                if let Ok(Some(event)) = keyboard.read_key_depth(5, precision.factor()) {
+                    let now = monotonic_now();
+                    let prev = last_raw
+                        .insert(event.key_index, event.depth_raw)
+                        .unwrap_or(0);
+                    let threshold = actuation(event.key_index);
+                    if prev < threshold && event.depth_raw >= threshold {
+                        crossings.lock().unwrap().push(now);
+                    }
+                }
+            }
+        });
+
+        while running.load(Ordering::SeqCst)
+            && evdev_presses.len() < presses
+            && Instant::now() < deadline
+        {
+            for event in reader.poll(Duration::from_millis(50)) {
+                if event.kind == iot_driver::evdev::EV_KEY && event.value == 1 {
+                    evdev_presses.push(event.time);
+                }
+            }
+            print!("\r\x1b[K  {}/{presses} presses", evdev_presses.len());
+            use std::io::Write;
+            std::io::stdout().flush().ok();
+        }
+        stop.store(true, Ordering::SeqCst);
+    });
+    println!();
+
+    let _ = keyboard.stop_magnetism_report();
+
+    let crossings = crossings.into_inner().unwrap();
+    if crossings.is_empty() {
+        eprintln!("No vendor depth events received (depth reporting may be unsupported here).");
+        return Ok(None);
+    }
+    let deltas = pair_nearest(&crossings, &evdev_presses, LATENCY_MAX_SKEW);
+    Ok(LatencyStats::from_ms(&deltas))
+}
+
+/// Sample resting key depth and report switches with noisy, drifting or stuck sensors
+pub fn switch_health(
+    keyboard: &KeyboardInterface,
+    duration_secs: u64,
+    thresholds: &HealthThresholds,
+    verbose: bool,
+) -> CommandResult {
+    println!("Device: {}", keyboard.device_name());
+    let precision = keyboard.get_precision().unwrap_or_default();
+
+    if let Err(e) = keyboard.start_magnetism_report() {
+        eprintln!("Failed to enable magnetism reporting: {e}");
+        return Ok(());
+    }
+
+    println!(
+        "Sampling resting depth for {duration_secs}s — do not touch the keyboard (Ctrl+C to stop early)..."
+    );
+
+    let running = setup_interrupt_handler();
+    let window = Duration::from_secs(duration_secs);
+    let mut monitor = SwitchHealthMonitor::new();
+    let start = Instant::now();
+
+    while running.load(Ordering::SeqCst) && start.elapsed() < window {
+        match keyboard.read_key_depth(10, precision.factor()) {
+            Ok(Some(event)) => monitor.record(event.key_index, event.depth_mm, start.elapsed()),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("Read error: {e}");
+                break;
+            }
+        }
+    }
+
+    let _ = keyboard.stop_magnetism_report();
+    let elapsed = start.elapsed().min(window);
+
+    let results = monitor.evaluate(elapsed, thresholds);
+    let flagged: Vec<_> = results.iter().filter(|k| !k.is_healthy()).collect();
+
+    println!(
+        "\n{} key(s) reported during {:.1}s, {} flagged",
+        results.len(),
+        elapsed.as_secs_f32(),
+        flagged.len()
+    );
+
+    let shown: Vec<_> = if verbose {
+        results.iter().collect()
+    } else {
+        flagged
+    };
+    if shown.is_empty() {
+        println!("All switches look healthy.");
+        return Ok(());
+    }
+
+    println!(
+        "\n{:>4}  {:<10} {:>7} {:>7} {:>7} {:>7} {:>7} {:>6}  Issues",
+        "Key", "Name", "Samples", "Mean", "StdDev", "Max", "Drift", "Stuck"
+    );
+    for k in shown {
+        let issues = if k.is_healthy() {
+            "ok".to_string()
+        } else {
+            k.issues
+                .iter()
+                .map(|i| i.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        println!(
+            "{:>4}  {:<10} {:>7} {:>6.2}mm {:>5.3}mm {:>5.2}mm {:>+6.2}mm {:>5.0}%  {}",
+            k.key_index,
+            keyboard.matrix_key_name(k.key_index as usize),
+            k.samples,
+            k.mean_mm,
+            k.stddev_mm,
+            k.max_mm,
+            k.drift_mm,
+            k.stuck_fraction * 100.0,
+            issues
+        );
+    }
+    Ok(())
+}
+
+/// Map the HID codes evdev reports back to matrix indices through the current
+/// base layer. Also returns the keys that send no plain key code, which
+/// evdev can't attribute.
+pub(super) fn keys_by_hid_code(
+    keyboard: &KeyboardInterface,
+    names: &[String],
+) -> (HashMap<u8, u8>, Vec<u8>) {
+    let is_key = |i: usize| !names[i].is_empty() && names[i] != "?";
+    let base: HashMap<u8, KeyAction> = match keymap::load_sync(keyboard) {
+        Ok(km) => km.layer(Layer::Base).map(|e| (e.index, e.action)).collect(),
+        Err(e) => {
+            eprintln!("Failed to read key matrix, assuming factory layout: {e}");
+            HashMap::new()
+        }
+    };
+    let mut by_code = HashMap::new();
+    let mut unmapped = Vec::new();
+    for i in (0..names.len()).filter(|&i| is_key(i)) {
+        let index = i as u8;
+        let code = match base.get(&index) {
+            Some(KeyAction::Key(code)) | Some(KeyAction::Combo { key: code, .. }) => *code,
+            Some(_) => 0,
+            None => keymap::default_keycode(index),
+        };
+        if code == 0 {
+            unmapped.push(index);
+        } else {
+            by_code.entry(code).or_insert(index);
+        }
+    }
+    (by_code, unmapped)
+}
+
+/// Interactive key test: highlight keys as they are pressed and report the
+/// ones that never registered or chattered. Reads evdev by default (what the
+/// OS sees, after remaps), or analog depth with `depth` (every switch,
+/// including Fn and keys bound to non-key actions).
+pub fn test_keys(keyboard: &KeyboardInterface, depth: bool, threshold_mm: f32) -> CommandResult {
+    use crossterm::{event, terminal, ExecutableCommand};
+    use std::io::Write;
+
+    let names = layout_names(keyboard);
+    let is_key = |i: usize| !names[i].is_empty() && names[i] != "?";
+
+    // evdev reports HID codes; map them back through the current base layer.
+    let mut by_code: HashMap<u8, u8> = HashMap::new();
+    let mut untestable: Vec<u8> = Vec::new();
+    let mut reader = None;
+    let precision = keyboard.get_precision().unwrap_or_default();
+    if depth {
+        if let Err(e) = keyboard.start_magnetism_report() {
+            eprintln!("Failed to enable magnetism reporting: {e}");
+            return Ok(());
+        }
+        untestable.extend(
+            (0..names.len())
+                .filter(|&i| is_key(i) && keyboard.is_non_analog(i))
+                .map(|i| i as u8),
+        );
+    } else {
+        let r = EventReader::open(keyboard.vid(), keyboard.pid());
+        if r.is_empty() {
+            eprintln!(
+                "No readable input nodes for {:04X}:{:04X}. Are you in the 'input' group?",
+                keyboard.vid(),
+                keyboard.pid()
+            );
+            return Ok(());
+        }
+        reader = Some(r);
+        (by_code, untestable) = keys_by_hid_code(keyboard, &names);
+    }
+    let expected: Vec<u8> = (0..names.len())
+        .filter(|&i| is_key(i) && !untestable.contains(&(i as u8)))
+        .map(|i| i as u8)
+        .collect();
+
+    let mut stdout = std::io::stdout();
+    terminal::enable_raw_mode()?;
+    stdout.execute(terminal::EnterAlternateScreen)?;
+
+    let mut tester = KeyTester::new();
+    let mut dirty = true;
+    let start = Instant::now();
+    let result: CommandResult = (|| loop {
+        // Typed keys also reach the terminal; drain them, stop on Ctrl+C.
+        while event::poll(Duration::ZERO)? {
+            if let event::Event::Key(key) = event::read()? {
+                if key.code == event::KeyCode::Char('c')
+                    && key.modifiers.contains(event::KeyModifiers::CONTROL)
+                {
+                    return Ok(());
+                }
+            }
+        }
+
+        if let Some(reader) = &reader {
+            for ev in reader.poll(Duration::from_millis(10)) {
+                if !ev.is_key_edge() {
+                    continue;
+                }
+                let Some(&index) = evdev::keycode_to_hid(ev.code).and_then(|c| by_code.get(&c))
+                else {
+                    continue;
+                };
+                if ev.value == 1 {
+                    tester.press(index, ev.time);
+                } else {
+                    tester.release(index, ev.time);
+                }
+                dirty = true;
+            }
+        } else {
+            while let Some(ev) = keyboard.read_key_depth(10, precision.factor())? {
+                let was_down = tester.is_down(ev.key_index);
+                tester.depth(ev.key_index, ev.depth_mm, threshold_mm, start.elapsed());
+                dirty |= was_down != tester.is_down(ev.key_index);
+            }
+        }
+
+        if dirty {
+            dirty = false;
+            let layout = render_color_layout(&names, |i| {
+                if tester.is_down(i) {
+                    return Some(RgbColor::new(0, 255, 0));
+                }
+                match tester.get(i) {
+                    Some(k) if k.chatter > 0 => Some(RgbColor::new(255, 60, 0)),
+                    Some(k) if k.presses > 0 => Some(RgbColor::new(0, 110, 0)),
+                    _ if untestable.contains(&i) => Some(RgbColor::new(90, 90, 90)),
+                    _ => None,
+                }
+            });
+            let registered = expected.len() - tester.missing(expected.iter().copied()).len();
+            let frame = format!(
+                "Key test ({}): press every key. Green = registered, red = chattered, gray = not testable. Ctrl+C to finish.\n\n{layout}\n{registered}/{} keys registered, {} chattered",
+                if depth { "depth" } else { "evdev" },
+                expected.len(),
+                tester.chattering().count()
+            );
+            print!("\x1b[H\x1b[2J{}", frame.replace('\n', "\r\n"));
+            stdout.flush()?;
+        }
+    })();
+
+    stdout.execute(terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    if depth {
+        let _ = keyboard.stop_magnetism_report();
+    }
+    result?;
+
+    let name = |i: u8| names[i as usize].as_str();
+    let missing = tester.missing(expected.iter().copied());
+    println!(
+        "{}/{} keys registered",
+        expected.len() - missing.len(),
+        expected.len()
+    );
+    if !missing.is_empty() {
+        println!("\nNever registered ({}):", missing.len());
+        for i in &missing {
+            println!("  {i:<3} {}", name(*i));
+        }
+    }
+    let chattered: Vec<_> = tester.chattering().collect();
+    if !chattered.is_empty() {
+        println!("\nChattered ({}):", chattered.len());
+        for (i, k) in chattered {
+            println!(
+                "  {i:<3} {:<8} {} of {} presses",
+                name(i),
+                k.chatter,
+                k.presses
+            );
+        }
+    }
+    if !untestable.is_empty() {
+        let list: Vec<&str> = untestable.iter().map(|&i| name(i)).collect();
+        let hint = if depth { "" } else { " (try --depth)" };
+        println!("\nNot testable{hint}: {}", list.join(", "));
+    }
+    Ok(())
+}
+
+/// Compare the vendor command streams of two captures
+pub fn pcap_diff(
+    a: &std::path::Path,
+    b: &std::path::Path,
+    responses: bool,
+    ignore: Vec<u8>,
+    show_same: bool,
+) -> CommandResult {
+    use iot_driver::pcap_analyzer::diff::{self, DiffOptions};
+
+    let options = DiffOptions { responses, ignore };
+    let read = |path: &std::path::Path| {
+        diff::read_messages(path, &options).map_err(|e| format!("{}: {e}", path.display()))
+    };
+    let (ma, mb) = (read(a)?, read(b)?);
+    println!("A: {} ({} packets)", a.display(), ma.len());
+    println!("B: {} ({} packets)", b.display(), mb.len());
+    println!();
+    let ops = diff::align(&ma, &mb)?;
+    print!("{}", diff::render(&ma, &mb, &ops, show_same));
+    let s = diff::summarize(&ops);
+    println!(
+        "\n{} identical, {} changed, {} only in A, {} only in B",
+        s.same, s.changed, s.only_a, s.only_b
+    );
+    Ok(())
+}
+
+/// Rebuild the configuration the official driver wrote in a capture
+pub fn pcap_extract(
+    file: &std::path::Path,
+    output: Option<&std::path::Path>,
+    key_count: usize,
+) -> CommandResult {
+    use iot_driver::pcap_analyzer::extract;
+
+    let ex =
+        extract::extract_file(file, key_count).map_err(|e| format!("{}: {e}", file.display()))?;
+    if ex.is_empty() {
+        println!("No configuration writes found in {}.", file.display());
+        for note in &ex.notes {
+            println!("  {note}");
+        }
+        return Ok(());
+    }
+    let name = file.file_name().map_or_else(
+        || file.display().to_string(),
+        |n| n.to_string_lossy().into_owned(),
+    );
+    let config = (!ex.profiles.is_empty())
+        .then(|| ex.to_toml(&name))
+        .transpose()?;
+
+    let Some(dir) = output else {
+        if let Some(config) = &config {
+            print!("{config}");
+        }
+        for slot in ex.userpics.keys() {
+            println!("# userpic slot {slot} uploaded (use -o to save it as an image)");
+        }
+        return Ok(());
+    };
+
+    std::fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+    if let Some(config) = config {
+        let (file_name, import) = if ex.profiles.len() == 1 {
+            ("config.toml", "iot_driver config apply")
+        } else {
+            ("profiles.toml", "iot_driver profile restore")
+        };
+        let path = dir.join(file_name);
+        std::fs::write(&path, config).map_err(|e| format!("write {}: {e}", path.display()))?;
+        println!(
+            "Wrote {} (load with `{import} {}`)",
+            path.display(),
+            path.display()
+        );
+    }
+    for (slot, data) in &ex.userpics {
+        let path = dir.join(format!("userpic_{slot}.png"));
+        super::userpic::userpic_to_image(data)
+            .save(&path)
+            .map_err(|e| format!("write {}: {e}", path.display()))?;
+        println!(
+            "Wrote {} (load with `iot_driver userpic {} --slot {slot} --nearest`)",
+            path.display(),
+            path.display()
+        );
+    }
+    for note in &ex.notes {
+        println!("Not carried over: {note}");
+    }
+    Ok(())
+}
+
+/// Command histogram, timing and timeline of a capture
+pub fn pcap_stats(
+    file: &std::path::Path,
+    timeline: bool,
+    gap_ms: u64,
+    json: bool,
+) -> CommandResult {
+    use iot_driver::pcap_analyzer::diff::{self, DiffOptions};
+    use iot_driver::pcap_analyzer::stats;
+
+    let options = DiffOptions {
+        responses: true,
+        ignore: Vec::new(),
+    };
+    let messages =
+        diff::read_messages(file, &options).map_err(|e| format!("{}: {e}", file.display()))?;
+    let report = stats::report(&messages, gap_ms as f64 / 1000.0);
+    if json {
+        let out = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        println!("{out}");
+    } else {
+        print!("{}", stats::render(&report, timeline));
+    }
+    Ok(())
+}
+
+/// Turn the distinct packets of a capture into parser regression fixtures
+pub fn pcap_fixtures(
+    file: &std::path::Path,
+    output: Option<&std::path::Path>,
+    max_per_cmd: usize,
+) -> CommandResult {
+    use iot_driver::pcap_analyzer::diff::{self, DiffOptions, MessageKind};
+    use iot_driver::pcap_analyzer::fixtures;
+
+    let options = DiffOptions {
+        responses: true,
+        ignore: Vec::new(),
+    };
+    let messages =
+        diff::read_messages(file, &options).map_err(|e| format!("{}: {e}", file.display()))?;
+    let rows = fixtures::collect(&messages, max_per_cmd);
+    let name = file.file_name().map_or_else(
+        || file.display().to_string(),
+        |n| n.to_string_lossy().into_owned(),
+    );
+    let test = fixtures::render(&rows, &name);
+    let Some(path) = output else {
+        print!("{test}");
+        return Ok(());
+    };
+    std::fs::write(path, test).map_err(|e| format!("write {}: {e}", path.display()))?;
+    let commands = rows
+        .iter()
+        .filter(|f| f.kind == MessageKind::Command)
+        .count();
+    println!(
+        "Wrote {} ({commands} commands, {} responses)",
+        path.display(),
+        rows.len() - commands
+    );
+    Ok(())
+}
+
+/// Generate the Wireshark Lua dissector
+pub fn pcap_dissector(output: Option<&std::path::Path>) -> CommandResult {
+    let lua = iot_driver::pcap_analyzer::dissector::render();
+    let Some(path) = output else {
+        print!("{lua}");
+        return Ok(());
+    };
+    std::fs::write(path, lua).map_err(|e| format!("write {}: {e}", path.display()))?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+/// Re-send the command stream of a capture and compare framing and replies
+pub fn pcap_replay(
+    ctx: &CmdCtx,
+    file: &std::path::Path,
+    yes: bool,
+    gets_only: bool,
+    delay_ms: u64,
+) -> CommandResult {
+    use iot_driver::command_probe;
+    use iot_driver::pcap_analyzer::diff::{self, DiffOptions};
+    use iot_driver::pcap_analyzer::replay::{self, Framing};
+
+    let hex = |data: &[u8]| {
+        data.iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let options = DiffOptions {
+        responses: true,
+        ignore: Vec::new(),
+    };
+    let messages =
+        diff::read_messages(file, &options).map_err(|e| format!("{}: {e}", file.display()))?;
+    let plan = replay::plan(&messages);
+    let writes = plan
+        .steps
+        .iter()
+        .filter(|s| replay::is_write(s.cmd()))
+        .count();
+    println!(
+        "{}: {} command(s) to replay, {} of them SET",
+        file.display(),
+        plan.steps.len(),
+        writes
+    );
+    for (byte, why) in &plan.blocked {
+        println!("  0x{byte:02X} left out: {why}");
+    }
+    if let Some(byte) = plan.stopped_at {
+        println!(
+            "  Capture continues into {} (0x{byte:02X}); the rest is not replayed",
+            transport_cmd::name(byte)
+        );
+    }
+    if plan.steps.is_empty() {
+        println!("Nothing to replay.");
+        return Ok(());
+    }
+
+    let mut send_writes = writes > 0 && !gets_only;
+    if send_writes && !yes {
+        println!();
+        println!(
+            "The SET commands overwrite the device's current settings with the captured ones."
+        );
+        print!("Type 'yes' to replay them too, anything else replays GETs only: ");
+        use std::io::Write;
+        std::io::stdout().flush()?;
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        send_writes = input.trim() == "yes";
+    }
+
+    let transport = open_preferred_transport(ctx)?;
+    let running = setup_interrupt_handler();
+    let (mut sent, mut framed, mut replies, mut matched) = (0, 0, 0, 0);
+    println!();
+    for step in &plan.steps {
+        if !running.load(Ordering::SeqCst) {
+            println!("Interrupted.");
+            break;
+        }
+        let byte = step.cmd();
+        if replay::is_write(byte) && !send_writes {
+            continue;
+        }
+        std::thread::sleep(Duration::from_millis(delay_ms));
+
+        let framing = match step.framing {
+            Framing::Checksum(c) => {
+                framed += 1;
+                format!("{c:?}")
+            }
+            Framing::Verbatim => "verbatim".to_string(),
+        };
+        let name = transport_cmd::name(byte);
+        let checksum = step.framing.checksum();
+        sent += 1;
+        let Some(expected) = &step.expected else {
+            match transport.send_command(byte, &step.payload, checksum) {
+                Ok(()) => println!("{:>9.3}  0x{byte:02X} {name:<20} {framing}", step.ts),
+                Err(e) => println!(
+                    "{:>9.3}  0x{byte:02X} {name:<20} {framing}, send failed: {e}",
+                    step.ts
+                ),
+            }
+            continue;
+        };
+        let result = match transport.query_raw(byte, &step.payload, checksum) {
+            Ok(reply) => {
+                replies += 1;
+                let got = command_probe::trimmed(&reply);
+                let offsets = replay::differing_offsets(expected, got);
+                if offsets.is_empty() {
+                    matched += 1;
+                    "reply matches".to_string()
+                } else {
+                    let list: Vec<String> = offsets.iter().map(|o| o.to_string()).collect();
+                    format!(
+                        "reply differs at {}\n             captured: {}\n             device:   {}",
+                        list.join(","),
+                        hex(expected),
+                        hex(got)
+                    )
+                }
+            }
+            Err(e) => format!("no reply ({e})"),
+        };
+        println!(
+            "{:>9.3}  0x{byte:02X} {name:<20} {framing}, {result}",
+            step.ts
+        );
+    }
+
+    println!(
+        "\n{sent} sent, {framed} rebuilt byte-for-byte by our framing, {matched} of {replies} replies identical"
+    );
+    if writes > 0 && !send_writes {
+        println!("{writes} SET command(s) skipped (replay with --yes to include them)");
+    }
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! `device add` — guided definition for a keyboard the database doesn't know.
+//!
+//! Reads the device's identity, precision and factory key matrix, lights
+//! each key position through a userpic slot so the user can confirm or
+//! correct it, and writes a definition to `~/.config/monsgeek/devices/`.
+//! The matrix and answer handling live in `iot_driver::device_wizard`.
+
+use super::{open_keyboard, CmdCtx, CommandResult};
+use iot_driver::device_loader::user_devices_dir;
+use iot_driver::device_wizard::{self, Answer, DraftDevice, Slot};
+use iot_driver::protocol::hid;
+use monsgeek_keyboard::led::BRIGHTNESS_MAX;
+use monsgeek_keyboard::{KeyboardError, KeyboardInterface};
+use monsgeek_transport::Transport;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Number of pages to read for a full key matrix (126 positions × 4 bytes).
+const KEYMATRIX_PAGES: usize = 8;
+
+/// Userpic slot borrowed to light one key at a time; restored afterwards.
+const WIZARD_SLOT: u8 = 3;
+
+/// Bytes of RGB data in a userpic (16 columns × 6 rows); position `i` is at `i * 3`.
+const USERPIC_BYTES: usize = 288;
+
+fn ask(prompt: &str) -> io::Result<String> {
+    print!("{prompt}");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
+/// Ask with a default shown in brackets; empty input keeps it.
+fn ask_default(prompt: &str, default: &str) -> io::Result<String> {
+    let answer = ask(&format!("{prompt} [{default}]: "))?;
+    Ok(if answer.is_empty() {
+        default.to_string()
+    } else {
+        answer
+    })
+}
+
+fn ask_yes(prompt: &str, default: bool) -> io::Result<bool> {
+    let hint = if default { "Y/n" } else { "y/N" };
+    let answer = ask(&format!("{prompt} ({hint}) "))?.to_lowercase();
+    Ok(match answer.as_str() {
+        "" => default,
+        a => a.starts_with('y'),
+    })
+}
+
+/// Light `position` alone in the wizard slot.
+fn light(kb: &KeyboardInterface, position: usize) -> Result<(), KeyboardError> {
+    let mut frame = vec![0u8; USERPIC_BYTES];
+    if let Some(px) = frame.get_mut(position * 3..position * 3 + 3) {
+        px.copy_from_slice(&[255, 255, 255]);
+    }
+    kb.upload_userpic(WIZARD_SLOT, &frame)
+}
+
+/// Light each position and let the user confirm or correct it.
+fn confirm_leds(kb: &KeyboardInterface, slots: &[Slot], led_matrix: &mut [u8]) -> CommandResult {
+    let led = kb.get_led_params()?;
+    let userpic = kb.download_userpic(WIZARD_SLOT)?;
+    kb.set_led_with_option(13, BRIGHTNESS_MAX, 0, 0, 0, 0, false, WIZARD_SLOT)?;
+
+    println!("Each key lights up in turn. Press Enter if it is the key shown,");
+    println!("type the name of the key that lit instead (e.g. Esc, LShift, F5),");
+    println!("'n' if nothing lit, or 'q' to keep the key matrix for the rest.");
+    println!();
+
+    let result = (|| -> CommandResult {
+        for (position, slot) in slots.iter().enumerate() {
+            if *slot == Slot::Empty || position * 3 >= USERPIC_BYTES {
+                continue;
+            }
+            light(kb, position)?;
+            let expected = match slot {
+                Slot::Key(code) => hid::key_name(*code),
+                _ => "a special key (Fn, media...)",
+            };
+            let answer = loop {
+                let input = ask(&format!("  {position:>3}: {expected}? "))?;
+                match device_wizard::parse_answer(&input) {
+                    Ok(answer) => break answer,
+                    Err(e) => println!("       {e}"),
+                }
+            };
+            if answer == Answer::Stop {
+                break;
+            }
+            device_wizard::apply_answer(led_matrix, position, answer);
+        }
+        Ok(())
+    })();
+
+    // Put the borrowed slot and the lighting back whatever happened
+    let restored = kb
+        .upload_userpic(WIZARD_SLOT, &userpic)
+        .and_then(|()| kb.set_led_params(&led));
+    if let Err(e) = restored {
+        eprintln!("Could not restore the lighting: {e} (userpic slot {WIZARD_SLOT} may be blank)");
+    }
+    result
+}
+
+/// Probe the connected keyboard and write a device definition for it.
+pub fn add(ctx: &CmdCtx, output: Option<&Path>, no_leds: bool) -> CommandResult {
+    let kb = open_keyboard(ctx)?;
+    let info = kb.transport().device_info().clone();
+    let device_id = kb.get_device_id()? as i32;
+    let version = kb.get_version()?;
+
+    println!("Device Definition Wizard");
+    println!("========================");
+    println!("USB ID:     {:04x}:{:04x}", info.vid, info.pid);
+    println!("Device ID:  {device_id}");
+    println!("Firmware:   {}", version.format_dotted());
+
+    let registry = iot_driver::profile_registry();
+    if let Some(known) = registry.get_device_info_by_id_and_usb(device_id, info.vid, info.pid) {
+        println!();
+        println!(
+            "This keyboard is already known as {} ({}).",
+            known.display_name,
+            known.company_or_unknown()
+        );
+        if !ask_yes("Write a new definition that overrides it?", false)? {
+            return Ok(());
+        }
+    }
+
+    // Precision comes from the feature list; only Hall-effect boards answer it
+    let features = kb.get_feature_list().ok().filter(|f| f.precision != 0xFF);
+    let precision = kb.get_precision()?;
+    println!(
+        "Precision:  {} ({})",
+        precision.as_str(),
+        if features.is_some() {
+            "feature list"
+        } else {
+            "from firmware version"
+        }
+    );
+
+    let data = kb.get_keymatrix(0, KEYMATRIX_PAGES)?;
+    let slots = device_wizard::scan_keymatrix(&data);
+    let key_count = device_wizard::key_count(&slots);
+    println!(
+        "Key matrix: {key_count} keys in {} positions (profile 0; remapped keys show their remap)",
+        slots.len()
+    );
+    if key_count == 0 {
+        return Err("the key matrix is empty; is this a MonsGeek/Akko keyboard?".into());
+    }
+    println!();
+
+    let mut led_matrix: Vec<u8> = slots.iter().map(|s| s.hid_code()).collect();
+    if !no_leds {
+        confirm_leds(&kb, &slots, &mut led_matrix)?;
+        println!();
+    }
+
+    let product = info.product_name.clone().unwrap_or_default();
+    let display_name = ask_default("Product name", product.trim())?;
+    let company = ask_default("Brand", "MonsGeek")?;
+    let magnetism = ask_yes("Hall-effect (magnetic) switches?", features.is_some())?;
+
+    let draft = DraftDevice {
+        device_id,
+        vid: info.vid,
+        pid: info.pid,
+        name: display_name.clone(),
+        display_name,
+        company: Some(company).filter(|c| !c.is_empty()),
+        key_count: key_count.min(u8::MAX as usize) as u8,
+        magnetism,
+        led_matrix,
+    };
+
+    let path = match output {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let dir = user_devices_dir();
+            std::fs::create_dir_all(&dir)
+                .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+            dir.join(draft.file_name())
+        }
+    };
+    std::fs::write(&path, draft.to_json())
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+
+    println!();
+    println!("Wrote {}", path.display());
+    if output.is_none() {
+        println!("It is loaded on the next start; a running server picks it up within seconds.");
+    }
+    println!("Please consider sharing it in an issue so the board can be added for everyone.");
+    Ok(())
+}
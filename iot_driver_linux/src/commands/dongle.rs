@@ -17,6 +17,13 @@ fn open_dongle_transport(
     Ok(transport)
 }
 
+/// Dongle firmware version from GET_DONGLE_INFO, if a dongle answers.
+pub(super) fn firmware_version(ctx: &CmdCtx) -> Option<String> {
+    let transport = open_dongle_transport(ctx).ok()?;
+    let info = transport.query_dongle_info().ok()??;
+    Some(format!("v{}", info.firmware_version))
+}
+
 /// `iot_driver dongle info` — combined F0 + F7 + FB + FD view
 pub fn info(ctx: &CmdCtx) -> CommandResult {
     let transport = open_dongle_transport(ctx)?;
@@ -3,6 +3,7 @@
 use std::collections::BTreeMap;
 
 use super::CommandResult;
+use iot_driver::effect::state::{self, ExternalState};
 use iot_driver::effect::{self, EffectLibrary};
 use iot_driver::notify::keymap;
 
@@ -166,6 +167,7 @@ pub fn play(
     }
 
     let kb = super::led_stream::open_with_patch_check(ctx)?;
+    let _awake = super::keep_awake(&kb);
     let running = super::setup_interrupt_handler();
 
     println!(
@@ -181,3 +183,45 @@ pub fn play(
     println!("Done.");
     Ok(())
 }
+
+/// Set an external state value.
+pub fn state_set(name: &str, value: &str, ttl: Option<&str>) -> CommandResult {
+    let value = state::parse_value(value)?;
+    let ttl = ttl.map(state::parse_ttl).transpose()?;
+    let mut store = ExternalState::load();
+    store.set(name, value, ttl);
+    store.save()?;
+    Ok(())
+}
+
+/// Remove an external state value.
+pub fn state_clear(name: &str) -> CommandResult {
+    let mut store = ExternalState::load();
+    if !store.clear(name) {
+        println!("'{name}' was not set");
+    }
+    store.save()?;
+    Ok(())
+}
+
+/// List the current external state values.
+pub fn state_list() -> CommandResult {
+    let mut store = ExternalState::load();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    store.prune(now);
+    if store.values.is_empty() {
+        println!("No state set ({})", state::state_path().display());
+        return Ok(());
+    }
+    for (name, v) in &store.values {
+        let expires = match v.expires {
+            Some(at) => format!("expires in {}s", at.saturating_sub(now)),
+            None => String::new(),
+        };
+        println!("{name:<24} {:<10} {expires}", v.value);
+    }
+    Ok(())
+}
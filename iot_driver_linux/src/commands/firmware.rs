@@ -2,7 +2,9 @@
 
 use super::{CmdCtx, CommandResult};
 use iot_driver::firmware::FirmwareFile;
-use std::path::PathBuf;
+use iot_driver::firmware_api::Verification;
+use iot_driver::flash::{FlashEvent, FlashPhase};
+use std::path::{Path, PathBuf};
 
 /// Validate a firmware file
 pub fn validate(file: &PathBuf) -> CommandResult {
@@ -14,9 +16,26 @@ pub fn validate(file: &PathBuf) -> CommandResult {
             println!("=========================");
             println!("Filename:   {}", fw.filename);
             println!("Type:       {}", fw.firmware_type);
+            println!("Container:  {}", fw.container.container);
+            if let Some(addr) = fw.container.load_address {
+                println!("Load addr:  0x{addr:08X}");
+            }
+            if let Some((vid, pid)) = fw.container.usb_id {
+                println!("USB ID:     {vid:04x}:{pid:04x}");
+            }
+            if let Some(bcd) = fw.container.bcd_device {
+                println!("bcdDevice:  0x{bcd:04X}");
+            }
+            if let Some(name) = &fw.container.target_name {
+                println!("DFU target: {name}");
+            }
             println!("Size:       {} bytes ({} KB)", fw.size, fw.size / 1024);
             println!("Checksum:   0x{:08X}", fw.checksum);
             println!("Chunks:     {} (64 bytes each)", fw.chunk_count);
+            match fw.target() {
+                Some(t) => println!("Target:     {}", t.name()),
+                None => println!("Target:     unknown (no known chip ID)"),
+            }
 
             match fw.validate() {
                 Ok(()) => println!("\nStatus:     VALID"),
@@ -41,31 +60,49 @@ pub fn validate(file: &PathBuf) -> CommandResult {
 }
 
 /// Dry-run firmware update (no actual flashing)
-pub fn dry_run(ctx: &CmdCtx, file: &PathBuf, verbose: bool) -> CommandResult {
-    use iot_driver::firmware::dry_run_usb;
+pub fn dry_run(ctx: &CmdCtx, file: &PathBuf, verbose: bool, dongle: bool) -> CommandResult {
+    use iot_driver::firmware::{dry_run, DryRunResult};
+    use iot_driver::protocol::firmware_update::FlashTarget;
 
     println!("=== DRY RUN - NO CHANGES WILL BE MADE ===\n");
 
+    let target = if dongle {
+        FlashTarget::Dongle
+    } else {
+        FlashTarget::Keyboard
+    };
+
     // Try to get current device info
-    let (current_version, device_id) = match super::open_keyboard(ctx) {
-        Ok(keyboard) => {
-            let version = keyboard.get_version().unwrap_or_default();
-            let device_id = keyboard.get_device_id().unwrap_or(0);
-            (Some(version.format_dotted()), Some(device_id))
+    let (current_version, device_id) = if dongle {
+        let version = super::dongle::firmware_version(ctx);
+        if version.is_none() {
+            println!("Note: No dongle connected, simulating without device info\n");
         }
-        Err(_) => {
-            println!("Note: No device connected, simulating without device info\n");
-            (None, None)
+        (version, None)
+    } else {
+        match super::open_keyboard(ctx) {
+            Ok(keyboard) => {
+                let version = keyboard.get_version().unwrap_or_default();
+                let device_id = keyboard.get_device_id().unwrap_or(0);
+                (Some(version.format_dotted()), Some(device_id))
+            }
+            Err(_) => {
+                println!("Note: No device connected, simulating without device info\n");
+                (None, None)
+            }
         }
     };
 
-    match FirmwareFile::load(file) {
+    match FirmwareFile::load_for(file, target) {
         Ok(fw) => {
             if let Err(e) = fw.validate() {
                 eprintln!("Warning: Firmware validation failed: {e}");
             }
 
-            let result = dry_run_usb(&fw, current_version, device_id);
+            let result = DryRunResult {
+                manifest: manifest_status(device_id, file),
+                ..dry_run(&fw, target, current_version, device_id)
+            };
             result.print(verbose);
         }
         Err(e) => {
@@ -75,11 +112,58 @@ pub fn dry_run(ctx: &CmdCtx, file: &PathBuf, verbose: bool) -> CommandResult {
     Ok(())
 }
 
+/// How a local file compares with the vendor manifest of the last cached
+/// firmware check for `device_id`.
+#[cfg(feature = "firmware-api")]
+fn manifest_status(device_id: Option<u32>, file: &Path) -> Option<Verification> {
+    let id = device_id.filter(|&id| id != 0)?;
+    let data = std::fs::read(file).ok()?;
+    iot_driver::firmware_api::verify_against_cache(id, &data)
+}
+
+#[cfg(not(feature = "firmware-api"))]
+fn manifest_status(_device_id: Option<u32>, _file: &Path) -> Option<Verification> {
+    None
+}
+
+/// "device ID 2949 (MonsGeek M1 V5 HE)", or just the ID when the mapping
+/// doesn't name a single product.
+#[cfg(feature = "firmware-api")]
+fn describe_device_id(id: u32) -> String {
+    match iot_driver::firmware_api::device_ids::name(id) {
+        Some(name) => format!("device ID {id} ({name})"),
+        None => format!("device ID {id}"),
+    }
+}
+
+/// Explain how to pick `--device-id` when auto-detection failed, listing the
+/// products that share the connected keyboard's VID/PID.
+#[cfg(feature = "firmware-api")]
+fn unknown_device_id(usb: Option<(u16, u16)>) {
+    use iot_driver::firmware_api::device_ids;
+
+    eprintln!("Could not determine device ID. Use --device-id to specify.");
+    if let Some((vid, pid)) = usb {
+        let map = device_ids::map();
+        let candidates = map.candidates(vid, pid);
+        if !candidates.is_empty() {
+            eprintln!("Products using {vid:04x}:{pid:04x}:");
+            for e in candidates {
+                eprintln!("  {:>5}  {}", e.device_id, e.name);
+            }
+            return;
+        }
+    }
+    eprintln!("Known device IDs:");
+    eprintln!("  M1 V5 HE: {}", device_ids::M1_V5_HE);
+    eprintln!("Run `iot_driver firmware ids` for the full list.");
+}
+
 /// Check for firmware updates from server
 #[cfg(feature = "firmware-api")]
 pub fn check(ctx: &CmdCtx, device_id: Option<u32>) -> CommandResult {
     use iot_driver::firmware_api::{
-        check_firmware_blocking as check_firmware, device_ids, ApiError,
+        cache_dir, check_firmware_blocking as check_firmware, device_ids, format_age, ApiError,
     };
 
     // Try to get device ID from connected device or argument
@@ -99,20 +183,28 @@ pub fn check(ctx: &CmdCtx, device_id: Option<u32>) -> CommandResult {
     let api_device_id = match api_device_id {
         Some(id) => id,
         None => {
-            eprintln!("Could not determine device ID. Use --device-id to specify.");
-            eprintln!("Known device IDs:");
-            eprintln!("  M1 V5 HE: {}", device_ids::M1_V5_HE);
+            unknown_device_id(keyboard.as_ref().map(|kb| (kb.vid(), kb.pid())));
             return Ok(());
         }
     };
 
-    println!("Checking for firmware updates for device ID {api_device_id}...");
+    println!(
+        "Checking for firmware updates for {}...",
+        describe_device_id(api_device_id)
+    );
 
     match check_firmware(api_device_id) {
         Ok(response) => {
             println!("\nServer Firmware Versions");
             println!("========================");
             println!("{}", response.versions.display());
+            if let Some(age) = response.cached_age {
+                println!(
+                    "(cached {} ago in {})",
+                    format_age(age),
+                    cache_dir().display()
+                );
+            }
 
             if let Some(path) = &response.versions.download_path {
                 println!("\nDownload path: {path}");
@@ -165,36 +257,37 @@ pub fn download(ctx: &CmdCtx, device_id: Option<u32>, output: &PathBuf) -> Comma
     };
 
     // Try to get device ID from connected device or argument
+    let mut usb = None;
     let api_device_id = device_id.or_else(|| {
-        if let Ok(kb) = super::open_keyboard(ctx) {
-            kb.get_device_id()
-                .ok()
-                .filter(|&id| id != 0)
-                .or_else(|| device_ids::from_vid_pid(kb.vid(), kb.pid()))
-        } else {
-            None
-        }
+        let kb = super::open_keyboard(ctx).ok()?;
+        usb = Some((kb.vid(), kb.pid()));
+        kb.get_device_id()
+            .ok()
+            .filter(|&id| id != 0)
+            .or_else(|| device_ids::from_vid_pid(kb.vid(), kb.pid()))
     });
 
     let api_device_id = match api_device_id {
         Some(id) => id,
         None => {
-            eprintln!("Could not determine device ID. Use --device-id to specify.");
-            eprintln!("Known device IDs:");
-            eprintln!("  M1 V5 HE: {}", device_ids::M1_V5_HE);
+            unknown_device_id(usb);
             return Ok(());
         }
     };
 
-    println!("Getting firmware info for device ID {api_device_id}...");
+    println!(
+        "Getting firmware info for {}...",
+        describe_device_id(api_device_id)
+    );
 
     match check_firmware(api_device_id) {
         Ok(response) => {
             if let Some(path) = response.versions.download_path {
                 println!("Downloading from: {path}");
-                match download_firmware(&path, output) {
-                    Ok(size) => {
+                match download_firmware(&path, &response.manifest, output) {
+                    Ok((size, verification)) => {
                         println!("Downloaded {} bytes to {}", size, output.display());
+                        println!("Vendor manifest: {verification}");
                     }
                     Err(e) => {
                         eprintln!("Download failed: {e}");
@@ -217,7 +310,49 @@ pub fn download(_ctx: &CmdCtx, _device_id: Option<u32>, _output: &PathBuf) -> Co
     Ok(())
 }
 
-/// CLI progress reporter for flash operations.
+/// List the VID/PID <-> device ID <-> API ID mapping
+pub fn ids(filter: Option<&str>) -> CommandResult {
+    use iot_driver::firmware_api::device_ids;
+
+    let map = device_ids::map();
+    if map.entries().is_empty() {
+        eprintln!("Device database not loaded; only the builtin IDs are known:");
+        eprintln!("  M1 V5 HE: {}", device_ids::M1_V5_HE);
+        return Ok(());
+    }
+
+    let filter = filter.map(str::to_lowercase);
+    let matches = |e: &device_ids::DeviceIdEntry| match &filter {
+        None => true,
+        Some(f) => {
+            e.name.to_lowercase().contains(f.as_str())
+                || e.device_id.to_string() == *f
+                || format!("{:04x}:{:04x}", e.vid, e.pid) == *f
+        }
+    };
+
+    println!(
+        "{:<9}  {:>9}  {:>6}  Product",
+        "USB ID", "Device ID", "API ID"
+    );
+    let mut shown = 0;
+    for e in map.entries().iter().filter(|e| matches(e)) {
+        println!(
+            "{:04x}:{:04x}  {:>9}  {:>6}  {}",
+            e.vid,
+            e.pid,
+            e.device_id,
+            e.api_id(),
+            e.name
+        );
+        shown += 1;
+    }
+    println!("\n{shown} of {} products", map.entries().len());
+    Ok(())
+}
+
+/// CLI rendering of the flash event stream: a line per phase, a bar
+/// during the transfer.
 struct CliFlashProgress {
     last_pct: usize,
 }
@@ -228,15 +363,23 @@ impl CliFlashProgress {
             last_pct: usize::MAX,
         }
     }
-}
 
-impl iot_driver::flash::FlashProgress for CliFlashProgress {
-    fn on_phase(&mut self, phase: &iot_driver::flash::FlashPhase) {
-        println!("[flash] {phase}");
+    fn show(&mut self, event: &FlashEvent) {
+        match event.phase {
+            FlashPhase::TransferringData if event.chunk > 0 => self.bar(event),
+            // The returned error is printed by the caller
+            FlashPhase::Failed => {}
+            FlashPhase::Retrying { .. } => {
+                println!("[flash] {} ({} so far)", event.phase, event.retries)
+            }
+            FlashPhase::Complete => println!("[flash] Flash complete!"),
+            _ => println!("[flash] {}", event.phase),
+        }
     }
 
-    fn on_chunk(&mut self, sent: usize, total: usize) {
-        let pct = sent * 100 / total;
+    fn bar(&mut self, event: &FlashEvent) {
+        let (sent, total) = (event.chunk, event.total_chunks);
+        let pct = event.percent as usize;
         // Print every 5% or at completion
         if pct != self.last_pct && (pct.is_multiple_of(5) || sent == total) {
             self.last_pct = pct;
@@ -251,19 +394,37 @@ impl iot_driver::flash::FlashProgress for CliFlashProgress {
             std::io::stdout().flush().ok();
         }
     }
+}
 
-    fn on_error(&mut self, error: &iot_driver::flash::FlashError) {
-        eprintln!("[flash] ERROR: {error}");
-    }
-
-    fn on_complete(&mut self) {
-        println!("[flash] Flash complete! Device should reboot to normal mode.");
+/// Ask for `expected` on stdin; false (and "Aborted.") on anything else.
+fn confirm(prompt: &str, expected: &str) -> std::io::Result<bool> {
+    use std::io::Write;
+    println!();
+    print!("{prompt}");
+    std::io::stdout().flush()?;
+
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    if input.trim() != expected {
+        println!("Aborted.");
+        return Ok(false);
     }
+    Ok(true)
 }
 
 /// Flash firmware to a connected device (keyboard or dongle).
-pub fn flash(file: &PathBuf, device: Option<&str>, dongle: bool, yes: bool) -> CommandResult {
-    use iot_driver::flash::{flash_firmware, FlashOptions};
+pub fn flash(
+    ctx: &CmdCtx,
+    file: &PathBuf,
+    device: Option<&str>,
+    dongle: bool,
+    yes: bool,
+    understand_risk: bool,
+    no_backup: bool,
+) -> CommandResult {
+    use iot_driver::firmware_history;
+    use iot_driver::flash::{flash_firmware, EventProgress, FlashOptions};
+    use iot_driver::keyboard_config::auto_backup;
     use iot_driver::protocol::firmware_update::FlashTarget;
 
     let target = if dongle {
@@ -272,8 +433,33 @@ pub fn flash(file: &PathBuf, device: Option<&str>, dongle: bool, yes: bool) -> C
         FlashTarget::Keyboard
     };
 
+    // `--device` picks the device to flash; the backup and the post-flash
+    // check open the same one, by serial since its path changes on reboot.
+    let ctx = &match device.and_then(iot_driver::flash::serial_at) {
+        Some(serial) => CmdCtx {
+            device: Some(format!("serial:{serial}")),
+            ..ctx.clone()
+        },
+        None => ctx.clone(),
+    };
+
+    if !understand_risk {
+        eprintln!(
+            "Flashing replaces the {} firmware and is not supported by the vendor on Linux.",
+            target.name()
+        );
+        eprintln!("An interrupted or wrong image can leave the device unusable until it is");
+        eprintln!(
+            "recovered from its bootloader or the chip's ROM DFU (see docs/PROTOCOL.md 8.6)."
+        );
+        eprintln!(
+            "Try `iot_driver firmware dry-run` first, then re-run with --i-understand-the-risk."
+        );
+        return Err("refusing to flash without --i-understand-the-risk".into());
+    }
+
     // 1. Load + validate firmware, auto-strip bootloader if full flash dump
-    let fw = match FirmwareFile::load(file) {
+    let fw = match FirmwareFile::load_for(file, target) {
         Ok(fw) => fw,
         Err(e) => {
             eprintln!("Failed to load firmware file: {e}");
@@ -286,6 +472,21 @@ pub fn flash(file: &PathBuf, device: Option<&str>, dongle: bool, yes: bool) -> C
         return Ok(());
     }
 
+    if let Some(image) = fw.target().filter(|&t| t != target) {
+        let hint = if image == FlashTarget::Dongle {
+            "add --dongle"
+        } else {
+            "drop --dongle"
+        };
+        return Err(format!(
+            "{} is a {} image, not {} firmware ({hint})",
+            fw.filename,
+            image.name(),
+            target.name()
+        )
+        .into());
+    }
+
     let fw = if let Some(stripped) = iot_driver::firmware::strip_bootloader_if_needed(&fw, target) {
         eprintln!(
             "Detected full flash dump (includes 20KB bootloader), using app region at offset 0x{:X}",
@@ -310,37 +511,273 @@ pub fn flash(file: &PathBuf, device: Option<&str>, dongle: bool, yes: bool) -> C
     println!("The {device_name} will be unusable if the process is interrupted.");
     println!("Make sure you have a DFU recovery method available.");
 
-    // 3. Confirmation
+    // 3. Confirmations: one for the risk, one naming the target so a
+    // keyboard image can't be sent to the dongle by habit (and vice versa)
     if !yes {
+        if !confirm("Type 'yes' to continue: ", "yes")? {
+            return Ok(());
+        }
         println!();
-        print!("Type 'yes' to continue: ");
-        use std::io::Write;
-        std::io::stdout().flush()?;
-
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)?;
-        if input.trim() != "yes" {
-            println!("Aborted.");
+        println!("Entering the bootloader erases every profile, keymap, macro and lighting");
+        println!("setting on the {device_name}. Keep it plugged in until the flash finishes.");
+        if target == FlashTarget::Keyboard && !no_backup {
+            println!("Your profiles are backed up first and can be restored afterwards.");
+        }
+        if !confirm(
+            &format!("Type '{device_name}' to start flashing: "),
+            device_name,
+        )? {
             return Ok(());
         }
     }
 
     println!();
 
-    // 4. Flash
-    let mut progress = CliFlashProgress::new();
+    // What's installed now, for `firmware rollback`
+    let (device_id, previous_version) = installed_firmware(ctx, target);
+
+    // 4. Back up every profile; the bootloader wipes them. The dongle holds
+    // no user settings.
+    let backup = if target == FlashTarget::Keyboard && !no_backup {
+        let kb = super::open_keyboard(ctx)
+            .map_err(|e| format!("cannot back up settings before flashing: {e}"))?;
+        let path = auto_backup(&kb, "pre-flash", super::config::reading).map_err(|e| {
+            format!("cannot back up settings before flashing: {e} (--no-backup flashes anyway)")
+        })?;
+        drop(kb);
+        println!("Settings backed up to {}", path.display());
+        println!();
+        Some(path)
+    } else {
+        None
+    };
+
+    // 5. Flash
+    let mut bar = CliFlashProgress::new();
+    let mut progress = EventProgress::new(|event: &FlashEvent| bar.show(event));
     let options = FlashOptions {
         device_path: device.map(String::from),
         target,
         ..Default::default()
     };
 
-    match flash_firmware(&fw, &mut progress, &options) {
-        Ok(()) => {}
-        Err(e) => {
-            eprintln!("\nFlash failed: {e}");
+    if let Err(e) = flash_firmware(&fw, &mut progress, &options) {
+        return Err(format!("flash failed: {e}").into());
+    }
+
+    // 6. Post-flash check: the device answers and reports its version
+    let version = if target == FlashTarget::Dongle {
+        std::thread::sleep(std::time::Duration::from_millis(1000));
+        let version = super::dongle::firmware_version(ctx);
+        match &version {
+            Some(v) => println!("Dongle is running firmware {v}"),
+            None => eprintln!("Dongle is back but did not report its version"),
+        }
+        version
+    } else {
+        std::thread::sleep(std::time::Duration::from_millis(1000));
+        match super::open_keyboard(ctx) {
+            Ok(kb) => match kb.get_version() {
+                Ok(v) => {
+                    println!("Keyboard is running firmware {}", v.format_dotted());
+                    Some(v.format_dotted())
+                }
+                Err(e) => {
+                    eprintln!("Keyboard is back but did not report its version: {e}");
+                    None
+                }
+            },
+            Err(e) => {
+                eprintln!("Keyboard is back but could not be opened: {e}");
+                None
+            }
         }
+    };
+
+    // Keep the image so this flash can be backed out of later
+    let source = file.display().to_string();
+    match firmware_history::record(
+        &fw,
+        &source,
+        target.name(),
+        device_id,
+        previous_version,
+        version,
+    ) {
+        Ok(entry) => println!("Image kept for rollback: {}", entry.image_path().display()),
+        Err(e) => eprintln!("Could not store the image for rollback: {e}"),
     }
 
+    // 7. Offer to put the backed-up settings back
+    if let Some(path) = backup {
+        println!();
+        if let Err(e) = super::config::offer_restore(ctx, &path, yes) {
+            return Err(format!("restore failed: {e} (backup kept at {})", path.display()).into());
+        }
+    }
     Ok(())
 }
+
+/// Device ID and firmware version the target is running, as far as they can
+/// be read.
+fn installed_firmware(
+    ctx: &CmdCtx,
+    target: iot_driver::protocol::firmware_update::FlashTarget,
+) -> (Option<u32>, Option<String>) {
+    use iot_driver::protocol::firmware_update::FlashTarget;
+
+    if target == FlashTarget::Dongle {
+        return (None, super::dongle::firmware_version(ctx));
+    }
+    match super::open_keyboard(ctx) {
+        Ok(kb) => (
+            kb.get_device_id().ok().filter(|&id| id != 0),
+            kb.get_version().ok().map(|v| v.format_dotted()),
+        ),
+        Err(_) => (None, None),
+    }
+}
+
+/// Re-flash the image that was installed before the current firmware.
+pub fn rollback(
+    ctx: &CmdCtx,
+    device: Option<&str>,
+    dongle: bool,
+    list: bool,
+    yes: bool,
+    understand_risk: bool,
+    no_backup: bool,
+) -> CommandResult {
+    use iot_driver::firmware_api::format_age;
+    use iot_driver::firmware_history::{self, rollback_candidate};
+    use iot_driver::protocol::firmware_update::FlashTarget;
+    use std::time::Duration;
+
+    let history = firmware_history::load();
+    if list {
+        if history.is_empty() {
+            println!("No firmware flashed with iot_driver yet.");
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        for r in &history {
+            let age = Duration::from_secs(now.saturating_sub(r.flashed_at));
+            println!(
+                "{:>4} ago  {:<8} id {:<5} {} -> {}  {} ({})",
+                format_age(age),
+                r.target,
+                r.device_id
+                    .map_or_else(|| "-".to_string(), |id| id.to_string()),
+                r.previous_version.as_deref().unwrap_or("?"),
+                r.version.as_deref().unwrap_or("?"),
+                r.file,
+                r.image,
+            );
+        }
+        return Ok(());
+    }
+
+    let target = if dongle {
+        FlashTarget::Dongle
+    } else {
+        FlashTarget::Keyboard
+    };
+    let (device_id, current) = installed_firmware(ctx, target);
+    if target == FlashTarget::Keyboard && device_id.is_none() {
+        return Err("cannot read the keyboard's device ID; is it connected?".into());
+    }
+
+    let record = rollback_candidate(&history, target.name(), device_id, current.as_deref())?;
+    let image = record.image_path();
+    if !image.exists() {
+        return Err(format!("stored image {} is missing", image.display()).into());
+    }
+
+    println!(
+        "Rolling back the {} from {} to {}",
+        target.name(),
+        current.as_deref().unwrap_or("unknown firmware"),
+        record.version.as_deref().unwrap_or("the previous image")
+    );
+    println!("Image originally flashed from {}", record.file);
+    println!();
+    flash(ctx, &image, device, dongle, yes, understand_risk, no_backup)
+}
+
+/// Find a device stuck in bootloader mode and flash a known-good image to it.
+///
+/// The bootloader only leaves ISP mode after a transfer whose checksum
+/// matches (PROTOCOL.md 8.1), so replugging doesn't help and recovery means
+/// flashing. Without `file` this just reports what is in bootloader mode.
+pub fn recover(
+    ctx: &CmdCtx,
+    file: Option<&PathBuf>,
+    device: Option<&str>,
+    yes: bool,
+    understand_risk: bool,
+) -> CommandResult {
+    use iot_driver::protocol::firmware_update::FlashTarget;
+    use monsgeek_transport::HidDiscovery;
+
+    let found = HidDiscovery::new().list_boot_mode_devices()?;
+    if found.is_empty() {
+        println!("No device in bootloader mode.");
+        return Ok(());
+    }
+
+    println!("Devices in bootloader mode:");
+    for d in &found {
+        println!(
+            "  {} {:04x}:{:04x}  {}  {}",
+            d.kind.name(),
+            d.vid,
+            d.pid,
+            d.product_name.as_deref().unwrap_or("-"),
+            d.path
+        );
+    }
+    println!();
+
+    let candidates: Vec<_> = found
+        .iter()
+        .filter(|d| device.is_none_or(|p| d.path == p))
+        .collect();
+    let boot = match candidates.as_slice() {
+        [] => return Err(format!("no bootloader device at {}", device.unwrap_or("")).into()),
+        [one] => *one,
+        _ => return Err("several devices in bootloader mode; pick one with --device".into()),
+    };
+    let Some(target) = FlashTarget::from_boot_mode(boot.kind) else {
+        return Err(format!(
+            "the {} is updated through the dongle; it can't be flashed directly",
+            boot.kind.name()
+        )
+        .into());
+    };
+
+    let Some(file) = file else {
+        println!(
+            "The {} stays in bootloader mode until it receives a complete firmware image;",
+            target.name()
+        );
+        println!("replugging doesn't leave it. Flash a known-good image to recover:");
+        println!(
+            "  iot_driver firmware recover <file> --i-understand-the-risk --device {}",
+            boot.path
+        );
+        return Ok(());
+    };
+
+    // Settings are already gone once the bootloader is entered, so there is
+    // nothing to back up
+    flash(
+        ctx,
+        file,
+        Some(&boot.path),
+        target == FlashTarget::Dongle,
+        yes,
+        understand_risk,
+        true,
+    )
+}
@@ -0,0 +1,223 @@
+//! Typing heatmap commands (heatmap record, show, led, reset).
+
+use super::debug::keys_by_hid_code;
+use super::keymap::layout_names;
+use super::led_stream::{apply_power_budget, open_with_patch_check, send_full_frame, MATRIX_LEN};
+use super::{print_json, setup_interrupt_handler, with_keyboard, CmdCtx, CommandResult};
+use iot_driver::evdev::{self, EventReader};
+use iot_driver::heatmap::{heatmap_path, Heatmap, HeatmapStore};
+use iot_driver::key_test::KeyTester;
+use iot_driver::keymap::render_color_layout;
+use monsgeek_keyboard::KeyboardInterface;
+use serde_json::json;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+/// How often a running recorder flushes its counts to disk.
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Count key presses until Ctrl+C, adding them to the saved heatmap.
+pub fn record(ctx: &CmdCtx, depth: bool, threshold_mm: f32) -> CommandResult {
+    with_keyboard(ctx, |kb| record_keyboard(kb, depth, threshold_mm))
+}
+
+fn record_keyboard(keyboard: &KeyboardInterface, depth: bool, threshold_mm: f32) -> CommandResult {
+    let device = keyboard.device_name();
+    let names = layout_names(keyboard);
+    let precision = keyboard.get_precision().unwrap_or_default();
+
+    let mut reader = None;
+    let mut by_code = Default::default();
+    if depth {
+        keyboard
+            .start_magnetism_report()
+            .map_err(|e| format!("Failed to enable magnetism reporting: {e}"))?;
+    } else {
+        let r = EventReader::open(keyboard.vid(), keyboard.pid());
+        if r.is_empty() {
+            return Err(format!(
+                "No readable input nodes for {:04X}:{:04X}. Are you in the 'input' group?",
+                keyboard.vid(),
+                keyboard.pid()
+            )
+            .into());
+        }
+        reader = Some(r);
+        let unmapped;
+        (by_code, unmapped) = keys_by_hid_code(keyboard, &names);
+        if !unmapped.is_empty() {
+            println!(
+                "{} key(s) send no plain key code and won't be counted (try --depth)",
+                unmapped.len()
+            );
+        }
+    }
+
+    println!(
+        "Recording key presses on {device} ({}), saving to {} (Ctrl+C to stop)",
+        if depth { "depth" } else { "evdev" },
+        heatmap_path().display()
+    );
+
+    let running = setup_interrupt_handler();
+    let mut session = Heatmap::new();
+    let mut presses = 0u64;
+    let mut tester = KeyTester::new();
+    let start = Instant::now();
+    let mut last_save = Instant::now();
+    let result: CommandResult = (|| {
+        while running.load(Ordering::SeqCst) {
+            let before = presses;
+            if let Some(reader) = &reader {
+                for ev in reader.poll(Duration::from_millis(10)) {
+                    if !ev.is_key_edge() || ev.value != 1 {
+                        continue;
+                    }
+                    if let Some(&index) =
+                        evdev::keycode_to_hid(ev.code).and_then(|c| by_code.get(&c))
+                    {
+                        session.record(index);
+                        presses += 1;
+                    }
+                }
+            } else {
+                while let Some(ev) = keyboard.read_key_depth(10, precision.factor())? {
+                    let was_down = tester.is_down(ev.key_index);
+                    tester.depth(ev.key_index, ev.depth_mm, threshold_mm, start.elapsed());
+                    if !was_down && tester.is_down(ev.key_index) {
+                        session.record(ev.key_index);
+                        presses += 1;
+                    }
+                }
+            }
+
+            if presses != before {
+                print!("\r{presses} presses this session  ");
+                std::io::Write::flush(&mut std::io::stdout()).ok();
+            }
+            if last_save.elapsed() >= SAVE_INTERVAL && session.total() > 0 {
+                HeatmapStore::add_and_save(&device, &session)?;
+                session = Heatmap::new();
+                last_save = Instant::now();
+            }
+        }
+        Ok(())
+    })();
+
+    if depth {
+        let _ = keyboard.stop_magnetism_report();
+    }
+    if session.total() > 0 {
+        HeatmapStore::add_and_save(&device, &session)?;
+    }
+    result?;
+    println!("\nRecorded {presses} presses");
+    Ok(())
+}
+
+/// Print the heatmap as a colored layout plus the most pressed keys.
+pub fn show(ctx: &CmdCtx, top: usize) -> CommandResult {
+    with_keyboard(ctx, |kb| {
+        let device = kb.device_name();
+        let names = layout_names(kb);
+        let heatmap = HeatmapStore::load()
+            .devices
+            .remove(&device)
+            .unwrap_or_default();
+        let total = heatmap.total();
+        let name = |i: u8| {
+            names
+                .get(i as usize)
+                .filter(|n| !n.is_empty())
+                .cloned()
+                .unwrap_or_else(|| format!("#{i}"))
+        };
+        let share = |count: u64| {
+            if total == 0 {
+                0.0
+            } else {
+                count as f64 * 100.0 / total as f64
+            }
+        };
+
+        if ctx.json {
+            let keys: Vec<_> = heatmap
+                .ranked()
+                .into_iter()
+                .map(|(index, count)| {
+                    json!({
+                        "index": index,
+                        "name": name(index),
+                        "count": count,
+                        "share": share(count),
+                    })
+                })
+                .collect();
+            return print_json(&json!({
+                "device": device,
+                "since": heatmap.since,
+                "total": total,
+                "keys": keys,
+            }));
+        }
+
+        if total == 0 {
+            println!(
+                "No key presses recorded for {device}. Run 'iot_driver heatmap record' first."
+            );
+            return Ok(());
+        }
+        println!("{device}: {total} presses\n");
+        println!("{}", render_color_layout(&names, |i| heatmap.color(i)));
+        println!("Most pressed:");
+        for (index, count) in heatmap.ranked().into_iter().take(top) {
+            println!("  {:<10} {count:>8}  {:5.1}%", name(index), share(count));
+        }
+        Ok(())
+    })
+}
+
+/// Paint the heatmap onto the keys via patch LED streaming until Ctrl+C.
+pub fn led(ctx: &CmdCtx, power_budget: u32) -> CommandResult {
+    let kb = open_with_patch_check(ctx)?;
+    let _awake = super::keep_awake(&kb);
+    let device = kb.device_name();
+    let running = setup_interrupt_handler();
+    println!("Showing typing heatmap for {device} (Ctrl+C to stop)");
+
+    // Reload each second so a recorder running alongside shows up live
+    let mut leds = [(0, 0, 0); MATRIX_LEN];
+    let mut next_load = Instant::now();
+    while running.load(Ordering::SeqCst) {
+        if Instant::now() >= next_load {
+            let heatmap = HeatmapStore::load()
+                .devices
+                .remove(&device)
+                .unwrap_or_default();
+            leds = heatmap.led_frame();
+            apply_power_budget(&mut leds, power_budget);
+            next_load = Instant::now() + Duration::from_secs(1);
+        }
+        send_full_frame(&kb, &leds)?;
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    println!("\nReleasing LED stream...");
+    kb.stream_led_release().ok();
+    Ok(())
+}
+
+/// Forget the recorded heatmap for the connected keyboard.
+pub fn reset(ctx: &CmdCtx) -> CommandResult {
+    with_keyboard(ctx, |kb| {
+        let device = kb.device_name();
+        let mut store = HeatmapStore::load();
+        if store.devices.remove(&device).is_none() {
+            println!("No heatmap recorded for {device}");
+            return Ok(());
+        }
+        store.save()?;
+        println!("Cleared heatmap for {device}");
+        Ok(())
+    })
+}
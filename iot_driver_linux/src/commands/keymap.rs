@@ -1,18 +1,36 @@
 //! Key remapping command handlers.
 
-use super::CommandResult;
-use iot_driver::key_action::KeyAction;
+use super::{with_keyboard, CmdCtx, CommandResult};
+use iot_driver::key_action::{KeyAction, ParseKeyActionError};
 use iot_driver::keymap::{self, KeyRef, Layer};
 use iot_driver::protocol::hid;
+use iot_driver::qmk_keymap::QmkKeymap;
+use iot_driver::via_keymap::{vendor_product_id, ViaKeymap};
 use monsgeek_keyboard::KeyboardInterface;
 use monsgeek_transport::protocol::matrix;
+use std::path::Path;
+
+/// Device layout names indexed by matrix position, for key-name lookups.
+pub(super) fn layout_names(keyboard: &KeyboardInterface) -> Vec<String> {
+    (0..keyboard.matrix_size())
+        .map(|i| keyboard.matrix_key_name(i).to_string())
+        .collect()
+}
 
 /// Remap a key.
 ///
-/// `from` can include a layer prefix: `"Fn+Caps"`, `"L1+A"`, `"42"`.
+/// `from` is a key name (`"capslock"`, `"Esc"`) or matrix index and can
+/// include a layer prefix: `"Fn+Caps"`, `"L1+A"`, `"42"`.
 /// When a layer prefix is present, it takes precedence over the `--layer` flag.
-pub fn remap(keyboard: &KeyboardInterface, from: &str, to: &str, layer: u8) -> CommandResult {
-    let key_ref: KeyRef = match from.parse() {
+/// The mapping is written to `profile` without changing the active profile.
+pub fn remap(
+    keyboard: &KeyboardInterface,
+    from: &str,
+    to: &str,
+    layer: u8,
+    profile: u8,
+) -> CommandResult {
+    let key_ref = match keymap::resolve_key_ref(from, &layout_names(keyboard)) {
         Ok(kr) => kr,
         Err(msg) => {
             eprintln!("{msg}");
@@ -30,6 +48,11 @@ pub fn remap(keyboard: &KeyboardInterface, from: &str, to: &str, layer: u8) -> C
 
     let action: KeyAction = match to.parse() {
         Ok(a) => a,
+        Err(ParseKeyActionError::UnknownKey(name)) => {
+            let msg = keymap::unknown_key_message(&name, keymap::hid_key_names());
+            eprintln!("Invalid target key: {msg}");
+            return Ok(());
+        }
         Err(e) => {
             eprintln!("Invalid target key: {e}");
             return Ok(());
@@ -43,7 +66,7 @@ pub fn remap(keyboard: &KeyboardInterface, from: &str, to: &str, layer: u8) -> C
         key_ref.index,
         effective_layer.name()
     );
-    match keymap::set_key_sync(keyboard, key_ref.index, effective_layer, &action) {
+    match keymap::set_key_sync(keyboard, profile, key_ref.index, effective_layer, &action) {
         Ok(()) => println!("{display_ref} remapped to {action}"),
         Err(e) => eprintln!("Failed to remap key: {e}"),
     }
@@ -53,8 +76,8 @@ pub fn remap(keyboard: &KeyboardInterface, from: &str, to: &str, layer: u8) -> C
 /// Reset a key to default.
 ///
 /// `key` can include a layer prefix: `"Fn+Caps"`, `"L1+A"`.
-pub fn reset_key(keyboard: &KeyboardInterface, key: &str, layer: u8) -> CommandResult {
-    let key_ref: KeyRef = match key.parse() {
+pub fn reset_key(keyboard: &KeyboardInterface, key: &str, layer: u8, profile: u8) -> CommandResult {
+    let key_ref = match keymap::resolve_key_ref(key, &layout_names(keyboard)) {
         Ok(kr) => kr,
         Err(msg) => {
             eprintln!("{msg}");
@@ -75,23 +98,24 @@ pub fn reset_key(keyboard: &KeyboardInterface, key: &str, layer: u8) -> CommandR
         key_ref.index,
         effective_layer.name()
     );
-    match keymap::reset_key_sync(keyboard, key_ref.index, effective_layer) {
+    match keymap::reset_key_sync(keyboard, profile, key_ref.index, effective_layer) {
         Ok(()) => println!("{display_ref} reset to default"),
         Err(e) => eprintln!("Failed to reset key: {e}"),
     }
     Ok(())
 }
 
-/// Swap two keys
-pub fn swap(keyboard: &KeyboardInterface, key1: &str, key2: &str, layer: u8) -> CommandResult {
-    let kr_a: KeyRef = match key1.parse() {
+/// Swap two keys in `profile` (the packets carry it, so no switch is needed)
+pub fn swap(keyboard: &KeyboardInterface, key1: &str, key2: &str, profile: u8) -> CommandResult {
+    let layout = layout_names(keyboard);
+    let kr_a = match keymap::resolve_key_ref(key1, &layout) {
         Ok(kr) => kr,
         Err(msg) => {
             eprintln!("{msg}");
             return Ok(());
         }
     };
-    let kr_b: KeyRef = match key2.parse() {
+    let kr_b = match keymap::resolve_key_ref(key2, &layout) {
         Ok(kr) => kr,
         Err(msg) => {
             eprintln!("{msg}");
@@ -99,7 +123,7 @@ pub fn swap(keyboard: &KeyboardInterface, key1: &str, key2: &str, layer: u8) ->
         }
     };
 
-    match keyboard.get_keymatrix(layer, 8) {
+    match keyboard.get_keymatrix(profile, 8) {
         Ok(data) => {
             let key_a = kr_a.index;
             let key_b = kr_b.index;
@@ -120,7 +144,7 @@ pub fn swap(keyboard: &KeyboardInterface, key1: &str, key2: &str, layer: u8) ->
             let action_b = hid::key_name(code_b);
             println!("Swapping {name_a} ({action_a}) <-> {name_b} ({action_b})...");
 
-            match keyboard.swap_keys(layer, key_a, code_a, key_b, code_b) {
+            match keyboard.swap_keys(profile, key_a, code_a, key_b, code_b) {
                 Ok(()) => println!("Keys swapped successfully"),
                 Err(e) => eprintln!("Failed to swap keys: {e}"),
             }
@@ -130,6 +154,45 @@ pub fn swap(keyboard: &KeyboardInterface, key1: &str, key2: &str, layer: u8) ->
     Ok(())
 }
 
+/// CLI handler: draw the physical layout with matrix indices and the current
+/// mapping of `layer` (0=base, 1=layer1, 2=fn).
+pub fn keys(keyboard: &KeyboardInterface, layer: u8) -> CommandResult {
+    let layer = Layer::from_wire(layer);
+    let names = layout_names(keyboard);
+    let keymap = match keymap::load_sync(keyboard) {
+        Ok(km) => Some(km),
+        Err(e) => {
+            eprintln!("Failed to read key matrix, showing layout only: {e}");
+            None
+        }
+    };
+    let remaps: Vec<_> = keymap
+        .iter()
+        .flat_map(|km| km.layer_remaps(layer))
+        .collect();
+
+    println!("{} ({} layer):\n", keyboard.device_name(), layer.name());
+    print!(
+        "{}",
+        keymap::render_layout(&names, |i| {
+            remaps
+                .iter()
+                .find(|e| e.index == i)
+                .map(|e| e.action.to_string())
+        })
+    );
+
+    if !remaps.is_empty() {
+        println!("\nRemapped ({}):", remaps.len());
+        for e in &remaps {
+            let name = names.get(e.index as usize).map_or("", String::as_str);
+            println!("  {:<3} {:<8} -> {}", e.index, name, e.action);
+        }
+    }
+    println!("\nUse the index or name with remap, reset-key and the trigger commands.");
+    Ok(())
+}
+
 /// CLI handler: list key remappings.
 pub fn remap_list(
     keyboard: &KeyboardInterface,
@@ -253,3 +316,141 @@ pub fn keymatrix(keyboard: &KeyboardInterface, layer: u8) -> CommandResult {
     }
     Ok(())
 }
+
+/// Save the keymap of `--profile` (default 0) as a VIA-style JSON file
+pub fn export_via(ctx: &CmdCtx, path: &Path, name: Option<String>) -> CommandResult {
+    with_keyboard(ctx, |kb| {
+        let profile = ctx.keymap_profile();
+        let map = kb.with_profile(profile, keymap::load_sync)?;
+        let mut file = ViaKeymap::from_keymap(&map, kb.key_count() as usize);
+        file.name = name;
+        file.vendor_product_id = Some(vendor_product_id(kb.vid(), kb.pid()));
+        std::fs::write(path, file.to_json())
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        println!(
+            "Exported profile {profile} keymap ({} layers) to {}",
+            file.layers.len(),
+            path.display()
+        );
+        Ok(())
+    })
+}
+
+/// Write a VIA-style JSON keymap to `--profile` (default 0), printing what
+/// changed. Keys that already match are not written.
+pub fn import_via(ctx: &CmdCtx, path: &Path, dry_run: bool) -> CommandResult {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let file = ViaKeymap::from_json(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+    let actions = file.actions()?;
+
+    with_keyboard(ctx, |kb| {
+        if let Some(name) = &file.name {
+            println!("Keymap: {name}");
+        }
+        if let Some(id) = file.vendor_product_id {
+            if id != vendor_product_id(kb.vid(), kb.pid()) {
+                println!(
+                    "Warning: made for {:04X}:{:04X}, key positions may differ",
+                    id >> 16,
+                    id & 0xFFFF
+                );
+            }
+        }
+        write_key_changes(ctx, kb, actions, dry_run)
+    })
+}
+
+/// Import a QMK Configurator JSON export or `keymap.c` into `--profile`
+/// (default 0): the base layer and the layer behind the first `MO()`,
+/// matched to keys in reading order. Unsupported keycodes are listed.
+pub fn import_qmk(ctx: &CmdCtx, path: &Path, dry_run: bool) -> CommandResult {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let qmk = QmkKeymap::parse(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+
+    with_keyboard(ctx, |kb| {
+        if let Some(keyboard) = &qmk.keyboard {
+            println!("QMK keymap for {keyboard}");
+        }
+        let plan = qmk.plan(&keymap::reading_order(&layout_names(kb)));
+        match plan.fn_layer {
+            Some(l) => println!("QMK layer 0 -> base layer, layer {l} -> Fn layer"),
+            None => println!("QMK layer 0 -> base layer (no MO() key, Fn layer untouched)"),
+        }
+        for l in &plan.dropped_layers {
+            println!("Skipping QMK layer {l}: no matching layer on this keyboard");
+        }
+        if plan.extra_positions > 0 {
+            println!(
+                "Skipping {} trailing position(s): more keys than this keyboard has",
+                plan.extra_positions
+            );
+        }
+        if !plan.skipped.is_empty() {
+            println!("Unsupported keycodes (left unchanged):");
+            for s in &plan.skipped {
+                println!(
+                    "  layer {} position {}: {} ({})",
+                    s.layer, s.position, s.keycode, s.reason
+                );
+            }
+        }
+        let base = plan.keys.iter().filter(|k| k.1 == Layer::Base).count();
+        if plan.legend_mismatches * 2 > base {
+            println!(
+                "Warning: {} of {base} base keys differ from this keyboard's legends; \
+                 the layouts may not line up, check with --dry-run",
+                plan.legend_mismatches
+            );
+        }
+        write_key_changes(ctx, kb, plan.keys, dry_run)
+    })
+}
+
+/// Write the keys in `actions` that differ from `--profile` (default 0),
+/// listing each change.
+fn write_key_changes(
+    ctx: &CmdCtx,
+    kb: &KeyboardInterface,
+    actions: Vec<(u8, Layer, KeyAction)>,
+    dry_run: bool,
+) -> CommandResult {
+    let key_count = kb.key_count() as usize;
+    let profile = ctx.keymap_profile();
+    let current = kb.with_profile(profile, keymap::load_sync)?;
+    let old = |index: u8, layer: Layer| {
+        current
+            .get(index, layer)
+            .map(|e| e.action)
+            .unwrap_or(KeyAction::Disabled)
+    };
+
+    let changes: Vec<_> = actions
+        .into_iter()
+        .filter(|&(index, layer, action)| {
+            (index as usize) < key_count && old(index, layer) != action
+        })
+        .collect();
+    if changes.is_empty() {
+        println!("Profile {profile}: keymap already matches");
+        return Ok(());
+    }
+    println!("Profile {profile}: {} change(s)", changes.len());
+    for &(index, layer, action) in &changes {
+        println!(
+            "  {}: {} -> {action}",
+            KeyRef::new(index, layer),
+            old(index, layer)
+        );
+    }
+    if dry_run {
+        println!("\nDry run: nothing written");
+        return Ok(());
+    }
+    for (index, layer, action) in &changes {
+        keymap::set_key_sync(kb, profile, *index, *layer, action)?;
+    }
+    println!("Keymap imported");
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! Terminal preview of the keyboard's per-key colors (`led preview`).
+
+use super::keymap::layout_names;
+use super::{setup_interrupt_handler, with_keyboard, CmdCtx, CommandResult};
+use iot_driver::keymap::{render_color_layout, LAYOUT_ROWS};
+use monsgeek_keyboard::led::{BRIGHTNESS_MAX, DAZZLE_ON};
+use monsgeek_keyboard::{KeyboardError, KeyboardInterface, LedMode, RgbColor};
+use std::io::{self, Write};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+/// Refresh interval for `--watch`.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Render the current lighting as truecolor key cells, once or until Ctrl+C.
+///
+/// Static colors come from the LED mode: the userpic slot in User Picture
+/// mode (or `slot` when given), a rainbow for dazzle, otherwise the mode
+/// color. Animated modes show their base color.
+pub fn preview(ctx: &CmdCtx, slot: Option<u8>, watch: bool) -> CommandResult {
+    with_keyboard(ctx, |kb| {
+        let names = layout_names(kb);
+        let mut userpic = None;
+        if !watch {
+            print!("{}", frame(kb, &names, slot, &mut userpic)?);
+            return Ok(());
+        }
+
+        let running = setup_interrupt_handler();
+        while running.load(Ordering::SeqCst) {
+            let text = frame(kb, &names, slot, &mut userpic)?;
+            print!("\x1b[H\x1b[2J{text}\nWatching, Ctrl+C to stop\n");
+            io::stdout().flush()?;
+            std::thread::sleep(WATCH_INTERVAL);
+        }
+        Ok(())
+    })
+}
+
+/// Header plus colored layout for the current LED state. `userpic` caches the
+/// last downloaded slot, so watching only re-reads it when the slot changes.
+fn frame(
+    kb: &KeyboardInterface,
+    names: &[String],
+    slot: Option<u8>,
+    userpic: &mut Option<(u8, Vec<u8>)>,
+) -> Result<String, KeyboardError> {
+    let led = kb.get_led_params()?;
+    let c = led.color;
+    let mut out = format!(
+        "{} — {} (brightness {}/{BRIGHTNESS_MAX}, color #{:02X}{:02X}{:02X})\n",
+        kb.device_name(),
+        led.mode.name(),
+        led.brightness,
+        c.r,
+        c.g,
+        c.b
+    );
+
+    let slot = slot.or((led.mode == LedMode::UserPicture).then_some(led.direction >> 4));
+    let colors: Vec<RgbColor> = if let Some(slot) = slot {
+        if userpic.as_ref().is_none_or(|(s, _)| *s != slot) {
+            *userpic = Some((slot, kb.download_userpic(slot)?));
+        }
+        let data = userpic.as_ref().map_or(&[][..], |(_, d)| d.as_slice());
+        out.push_str(&format!("Userpic slot {slot}\n"));
+        (0..names.len())
+            .map(|i| match data.get(i * 3..i * 3 + 3) {
+                Some(&[r, g, b]) => RgbColor::new(r, g, b),
+                _ => RgbColor::BLACK,
+            })
+            .collect()
+    } else if led.mode == LedMode::Off || led.brightness == 0 {
+        vec![RgbColor::BLACK; names.len()]
+    } else if led.direction & 0x0F == DAZZLE_ON {
+        out.push_str("Dazzle: colors cycle, showing one frame of the rainbow\n");
+        let cols = names.len().div_ceil(LAYOUT_ROWS).max(1);
+        (0..names.len())
+            .map(|i| RgbColor::from_hsv((i / LAYOUT_ROWS * 360 / cols) as f32, 1.0, 1.0))
+            .collect()
+    } else {
+        if led.mode != LedMode::Constant {
+            out.push_str("Animated mode, showing its base color\n");
+        }
+        vec![led.color; names.len()]
+    };
+
+    out.push('\n');
+    out.push_str(&render_color_layout(names, |i| {
+        colors.get(i as usize).copied()
+    }));
+    Ok(out)
+}
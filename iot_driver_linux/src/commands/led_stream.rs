@@ -118,6 +118,7 @@ pub fn open_with_patch_check(
 /// "disappears" momentarily, which is the expected spatial behaviour.
 pub fn stream_test(ctx: &CmdCtx, fps: f32, power_budget: u32) -> CommandResult {
     let kb = open_with_patch_check(ctx)?;
+    let _awake = super::keep_awake(&kb);
 
     let frame_duration = std::time::Duration::from_secs_f32(1.0 / fps);
     let running = setup_interrupt_handler();
@@ -174,6 +175,7 @@ pub fn stream_gif(
     power_budget: u32,
 ) -> CommandResult {
     let kb = open_with_patch_check(ctx)?;
+    let _awake = super::keep_awake(&kb);
 
     // Decode GIF
     println!("Loading GIF: {file}");
@@ -297,3 +299,181 @@ pub fn stream_gif(
     println!("Done.");
     Ok(())
 }
+
+/// Drive the LEDs from WLED realtime UDP packets, so SignalRGB, Artemis,
+/// OpenRGB and friends can treat the keyboard as a 96-LED WLED device.
+///
+/// Frames are sent at most `max_fps` times per second; the LEDs go back to
+/// the firmware effect when the sender's timeout passes without packets.
+pub fn bridge(
+    ctx: &CmdCtx,
+    listen: std::net::SocketAddr,
+    max_fps: f32,
+    power_budget: u32,
+) -> CommandResult {
+    use iot_driver::led_bridge::apply_packet;
+    use std::time::{Duration, Instant};
+
+    let kb = open_with_patch_check(ctx)?;
+    let _awake = super::keep_awake(&kb);
+
+    let frame_interval = Duration::from_secs_f32(1.0 / max_fps);
+    let socket = std::net::UdpSocket::bind(listen)
+        .map_err(|e| format!("Failed to bind udp://{listen}: {e}"))?;
+    socket.set_read_timeout(Some(frame_interval))?;
+    let running = setup_interrupt_handler();
+    println!(
+        "LED bridge on udp://{listen}: WLED realtime (WARLS/DRGB/DRGBW/DNRGB), \
+         {MATRIX_LEN} LEDs ({COLS}×{ROWS} row-major), max {max_fps:.0} FPS (Ctrl+C to stop)"
+    );
+
+    let mut leds = [(0u8, 0u8, 0u8); MATRIX_LEN];
+    let mut buf = [0u8; 1500];
+    let mut dirty = false;
+    let mut streaming = false;
+    let mut warned = false;
+    let mut release_at: Option<Instant> = None;
+    let mut last_sent: Option<Instant> = None;
+    let mut frames = 0u64;
+
+    while running.load(Ordering::SeqCst) {
+        match socket.recv_from(&mut buf) {
+            Ok((len, peer)) => match apply_packet(&buf[..len], &mut leds) {
+                Ok(applied) => {
+                    dirty = true;
+                    release_at = applied.timeout.map(|t| Instant::now() + t);
+                }
+                Err(e) if !warned => {
+                    eprintln!("\nIgnoring packets from {peer}: {e}");
+                    warned = true;
+                }
+                Err(_) => {}
+            },
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        if dirty && last_sent.is_none_or(|t| t.elapsed() >= frame_interval) {
+            let mut frame = leds;
+            apply_power_budget(&mut frame, power_budget);
+            send_full_frame(&kb, &frame)?;
+            dirty = false;
+            streaming = true;
+            last_sent = Some(Instant::now());
+            frames += 1;
+            print!("\rFrames: {frames}  ");
+            std::io::Write::flush(&mut std::io::stdout()).ok();
+        }
+
+        if streaming && release_at.is_some_and(|t| Instant::now() >= t) {
+            kb.stream_led_release().ok();
+            streaming = false;
+            leds = [(0, 0, 0); MATRIX_LEN];
+            println!("\nSender went quiet, LEDs released");
+        }
+    }
+
+    println!("\nReleasing LED stream...");
+    kb.stream_led_release().ok();
+    println!("Done.");
+    Ok(())
+}
+
+/// Light key zones from game telemetry (`name=value` or JSON over UDP, or a
+/// file rewritten by the game, e.g. on `/dev/shm`), mapped by
+/// `[telemetry]` in settings.toml.
+///
+/// The LEDs go back to the firmware effect once no value has been updated
+/// within the configured timeout.
+pub fn telemetry(
+    ctx: &CmdCtx,
+    listen: std::net::SocketAddr,
+    file: Option<std::path::PathBuf>,
+    fps: f32,
+    power_budget: u32,
+) -> CommandResult {
+    use iot_driver::telemetry::{render, TelemetryState};
+    use std::time::{Duration, Instant};
+
+    let config = iot_driver::settings::Settings::load().telemetry;
+    let zones = config.zones()?;
+    let kb = open_with_patch_check(ctx)?;
+    let _awake = super::keep_awake(&kb);
+
+    let frame_interval = Duration::from_secs_f32(1.0 / fps.max(1.0));
+    let socket = std::net::UdpSocket::bind(listen)
+        .map_err(|e| format!("Failed to bind udp://{listen}: {e}"))?;
+    socket.set_read_timeout(Some(frame_interval))?;
+    let running = setup_interrupt_handler();
+    let names: Vec<_> = zones.iter().map(|z| z.value.as_str()).collect();
+    println!(
+        "Telemetry on udp://{listen}{}: {} (Ctrl+C to stop)",
+        file.as_ref()
+            .map(|f| format!(" and {}", f.display()))
+            .unwrap_or_default(),
+        names.join(", ")
+    );
+
+    let start = Instant::now();
+    let mut state = TelemetryState::default();
+    let mut buf = [0u8; 1500];
+    let mut file_modified = None;
+    let mut streaming = false;
+    let mut last_sent: Option<Instant> = None;
+
+    while running.load(Ordering::SeqCst) {
+        match socket.recv_from(&mut buf) {
+            Ok((len, _)) => {
+                state.apply(&String::from_utf8_lossy(&buf[..len]), Instant::now());
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => return Err(e.into()),
+        }
+        if let Some(path) = &file {
+            let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+            if modified.is_some() && modified != file_modified {
+                file_modified = modified;
+                if let Ok(text) = std::fs::read_to_string(path) {
+                    state.apply(&text, Instant::now());
+                }
+            }
+        }
+        if last_sent.is_some_and(|t| t.elapsed() < frame_interval) {
+            continue;
+        }
+
+        let values = state.fresh(Instant::now(), config.timeout());
+        if values.is_empty() {
+            if streaming {
+                kb.stream_led_release().ok();
+                streaming = false;
+                println!("\nTelemetry went quiet, LEDs released");
+            }
+            continue;
+        }
+        let mut frame = render(&zones, &values, start.elapsed());
+        apply_power_budget(&mut frame, power_budget);
+        send_full_frame(&kb, &frame)?;
+        streaming = true;
+        last_sent = Some(Instant::now());
+
+        let mut shown: Vec<_> = values.iter().collect();
+        shown.sort_by(|a, b| a.0.cmp(b.0));
+        let line: Vec<_> = shown.iter().map(|(k, v)| format!("{k}={v}")).collect();
+        print!("\r{}    ", line.join(" "));
+        std::io::Write::flush(&mut std::io::stdout()).ok();
+    }
+
+    println!("\nReleasing LED stream...");
+    kb.stream_led_release().ok();
+    println!("Done.");
+    Ok(())
+}
@@ -1,10 +1,16 @@
 //! Macro command handlers.
 
-use super::CommandResult;
-use iot_driver::macro_seq::MacroSeq;
+use super::{setup_interrupt_handler, with_keyboard, CmdCtx, CommandResult};
+use iot_driver::evdev::{self, EventReader};
+use iot_driver::macro_file::MacroFile;
+use iot_driver::macro_seq::{events_from_recording, MacroSeq};
 use iot_driver::protocol::hid;
-use monsgeek_keyboard::{parse_macro_events, KeyboardInterface};
+use monsgeek_keyboard::{parse_macro_events, KeyboardInterface, MacroEvent};
 use monsgeek_transport::protocol::matrix;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 /// Get macro for a key
 pub fn get_macro(keyboard: &KeyboardInterface, key: &str) -> CommandResult {
@@ -18,20 +24,7 @@ pub fn get_macro(keyboard: &KeyboardInterface, key: &str) -> CommandResult {
                 println!("Macro {macro_index} is empty");
             } else {
                 println!("Repeat count: {repeat_count}");
-                println!("Events ({}):", events.len());
-                for (i, evt) in events.iter().enumerate() {
-                    let arrow = if evt.is_down { "↓" } else { "↑" };
-                    let key_name = hid::key_name(evt.keycode);
-                    let delay_str = if evt.delay_ms > 0 {
-                        format!(" +{}ms", evt.delay_ms)
-                    } else {
-                        String::new()
-                    };
-                    println!(
-                        "  {i:3}: {arrow} {key_name} (0x{:02x}){delay_str}",
-                        evt.keycode
-                    );
-                }
+                print_macro_events(&events);
 
                 // Reconstruct as sequence syntax
                 let event_tuples: Vec<(u8, bool, u16)> = events
@@ -61,6 +54,223 @@ pub fn get_macro(keyboard: &KeyboardInterface, key: &str) -> CommandResult {
     Ok(())
 }
 
+/// Print a numbered event list: arrow, key name, keycode and delay.
+fn print_macro_events(events: &[MacroEvent]) {
+    println!("Events ({}):", events.len());
+    for (i, evt) in events.iter().enumerate() {
+        let arrow = if evt.is_down { "↓" } else { "↑" };
+        let key_name = hid::key_name(evt.keycode);
+        let delay_str = if evt.delay_ms > 0 {
+            format!(" +{}ms", evt.delay_ms)
+        } else {
+            String::new()
+        };
+        println!(
+            "  {i:3}: {arrow} {key_name} (0x{:02x}){delay_str}",
+            evt.keycode
+        );
+    }
+}
+
+/// Record keystrokes from the keyboard's input nodes into a macro slot.
+///
+/// Key edges are captured from evdev until `stop` is pressed, previewed as an
+/// event list and sequence, then written with the `set_macro` encoder once
+/// confirmed. `delay` replaces the recorded timing with a fixed delay;
+/// otherwise pauses are kept, capped at `max_delay` ms.
+pub fn record_macro(
+    keyboard: &KeyboardInterface,
+    slot: u8,
+    stop: &str,
+    delay: Option<u16>,
+    max_delay: u16,
+    repeat: u16,
+    yes: bool,
+) -> CommandResult {
+    let Some(stop_code) = hid::key_code_from_name(stop) else {
+        eprintln!("Unknown stop key: \"{stop}\"");
+        return Ok(());
+    };
+
+    let reader = EventReader::open(keyboard.vid(), keyboard.pid());
+    if reader.is_empty() {
+        eprintln!(
+            "No readable input nodes for {:04X}:{:04X}. Are you in the 'input' group?",
+            keyboard.vid(),
+            keyboard.pid()
+        );
+        return Ok(());
+    }
+
+    let running = setup_interrupt_handler();
+    println!(
+        "Recording macro {slot}: type on the keyboard, press {} to finish (Ctrl+C to abort)...",
+        hid::key_name(stop_code)
+    );
+
+    let mut edges = Vec::new();
+    let mut unmapped = 0usize;
+    'capture: while running.load(Ordering::SeqCst) {
+        for event in reader.poll(Duration::from_millis(50)) {
+            if !event.is_key_edge() {
+                continue;
+            }
+            let is_down = event.value == 1;
+            match evdev::keycode_to_hid(event.code) {
+                Some(code) if code == stop_code => {
+                    if is_down {
+                        break 'capture;
+                    }
+                }
+                Some(code) => edges.push((code, is_down, event.time)),
+                // Mouse buttons live above 0xFF; only count real keys
+                None if is_down && event.code < 0x100 => unmapped += 1,
+                None => {}
+            }
+        }
+    }
+    discard_typed_input();
+    println!();
+
+    if !running.load(Ordering::SeqCst) {
+        println!("Recording aborted");
+        return Ok(());
+    }
+
+    let mut events = events_from_recording(&edges, max_delay);
+    if let Some(d) = delay {
+        events.iter_mut().for_each(|e| e.2 = d);
+    }
+    if events.is_empty() {
+        println!("No keys recorded");
+        return Ok(());
+    }
+    if unmapped > 0 {
+        println!("Skipped {unmapped} key press(es) with no HID keyboard usage (media keys etc.)");
+    }
+
+    let preview: Vec<MacroEvent> = events
+        .iter()
+        .map(|&(keycode, is_down, delay_ms)| MacroEvent {
+            keycode,
+            is_down,
+            delay_ms,
+        })
+        .collect();
+    print_macro_events(&preview);
+    let seq = MacroSeq::from_events(&events, delay.unwrap_or(10), repeat);
+    println!("\nSequence: {seq}");
+    let text = text_preview_from_events(&preview);
+    if !text.is_empty() {
+        println!("Text preview: \"{text}\"");
+    }
+
+    if !yes {
+        print!("\nWrite to macro slot {slot}? (y/N) ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if input.trim().to_lowercase() != "y" {
+            println!("Cancelled");
+            return Ok(());
+        }
+    }
+
+    match keyboard.set_macro(slot, &events, repeat) {
+        Ok(()) => {
+            println!("Macro {slot} set successfully!");
+            println!("Assign this macro to a key with: assign-macro <key> {slot}");
+        }
+        Err(e) => eprintln!("Failed to set macro: {e}"),
+    }
+    Ok(())
+}
+
+/// Drop whatever the recorded keystrokes typed into the terminal, so it can't
+/// answer the confirmation prompt.
+fn discard_typed_input() {
+    std::thread::sleep(Duration::from_millis(50));
+    unsafe {
+        libc::tcflush(libc::STDIN_FILENO, libc::TCIFLUSH);
+    }
+}
+
+/// Save a macro slot to a TOML macro file
+pub fn export_macro(
+    keyboard: &KeyboardInterface,
+    slot: u8,
+    path: &Path,
+    name: Option<String>,
+) -> CommandResult {
+    let data = match keyboard.get_macro(slot) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Failed to read macro: {e}");
+            return Ok(());
+        }
+    };
+    let (repeat, events) = parse_macro_events(&data);
+    if events.is_empty() {
+        eprintln!("Macro {slot} is empty, nothing to export");
+        return Ok(());
+    }
+
+    let tuples: Vec<(u8, bool, u16)> = events
+        .iter()
+        .map(|e| (e.keycode, e.is_down, e.delay_ms))
+        .collect();
+    let mut file = MacroFile::from_events(&tuples, repeat);
+    file.name = name;
+    std::fs::write(path, file.to_toml())
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    println!(
+        "Exported macro {slot} ({} events) to {}",
+        events.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Load a TOML macro file into a macro slot
+pub fn import_macro(ctx: &CmdCtx, path: &Path, slot: u8, dry_run: bool) -> CommandResult {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let file = MacroFile::from_toml(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+    let events = file.to_events()?;
+
+    if let Some(name) = &file.name {
+        println!("Macro: {name}");
+    }
+    println!("Repeat count: {}", file.repeat);
+    let preview: Vec<MacroEvent> = events
+        .iter()
+        .map(|&(keycode, is_down, delay_ms)| MacroEvent {
+            keycode,
+            is_down,
+            delay_ms,
+        })
+        .collect();
+    print_macro_events(&preview);
+    let seq = MacroSeq::from_events(&events, file.delay_ms, file.repeat);
+    println!("\nSequence: {seq}");
+
+    if dry_run {
+        println!("\nDry run: macro {slot} not written");
+        return Ok(());
+    }
+
+    with_keyboard(ctx, |kb| {
+        match kb.set_macro(slot, &events, file.repeat) {
+            Ok(()) => {
+                println!("Macro {slot} set successfully!");
+                println!("Assign this macro to a key with: assign-macro <key> {slot}");
+            }
+            Err(e) => eprintln!("Failed to set macro: {e}"),
+        }
+        Ok(())
+    })
+}
+
 /// Set a text macro or key sequence for a macro slot
 pub fn set_macro(
     keyboard: &KeyboardInterface,
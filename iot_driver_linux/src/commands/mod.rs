@@ -4,28 +4,39 @@
 //! - `query`: Read-only commands (info, profile, led, debounce, etc.)
 //! - `set`: Setting commands (set-profile, set-debounce, etc.)
 //! - `triggers`: Trigger-related commands (calibrate, triggers, set-actuation, etc.)
-//! - `keymap`: Key remapping commands (remap, reset-key, swap, keymatrix)
+//! - `keymap`: Key remapping commands (keys, remap, reset-key, swap, keymatrix)
 //! - `macros`: Macro commands (macro, set-macro, clear-macro)
 //! - `animations`: Animation commands (mode, modes)
 //! - `userpic`: Userpic upload/download (mode 13 flash slots)
-//! - `reactive`: Reactive mode commands (audio, audio-test, audio-levels, screen)
-//! - `debug`: Debug commands (depth, test-transport)
+//! - `led_preview`: Terminal preview of per-key colors (led preview)
+//! - `reactive`: Reactive mode commands (audio, audio-test, audio-levels, screen, wpm, sysmon, timer)
+//! - `debug`: Debug commands (depth, measure-rate, latency, switch-health, test-keys, test-transport)
+//! - `selftest`: Protocol conformance suite against a connected keyboard
+//! - `heatmap`: Typing heatmap (heatmap record, show, led, reset)
+//! - `plugin`: Lighting plugins (plugin list, plugin run)
+//! - `config`: Declarative config files (config apply, config dump, profile backup/restore)
 //! - `firmware`: Firmware subcommands
 //! - `utility`: Utility commands (list, raw, serve, tui, joystick)
 
 pub mod animations;
+pub mod config;
 pub mod debug;
+pub mod device;
 pub mod dongle;
 pub mod effect;
 pub mod firmware;
+pub mod heatmap;
 pub mod keymap;
+pub mod led_preview;
 pub mod led_stream;
 pub mod macros;
 #[cfg(feature = "notify")]
 pub mod notify;
+pub mod plugin;
 pub mod probe;
 pub mod query;
 pub mod reactive;
+pub mod selftest;
 pub mod set;
 pub mod triggers;
 pub mod userpic;
@@ -34,8 +45,8 @@ pub mod utility;
 use iot_driver::protocol::{self, cmd};
 use monsgeek_keyboard::settings::FirmwareVersion;
 use monsgeek_transport::{
-    format_device_list, DeviceDiscovery, FlowControlTransport, HidDiscovery, PacketFilter,
-    PrinterConfig, Transport,
+    format_device_list, select_device, DeviceDiscovery, DeviceKind, DeviceLabel, DeviceSelectError,
+    FlowControlTransport, HidDiscovery, PacketFilter, PrinterConfig, ProbedDevice, Transport,
 };
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -44,11 +55,14 @@ use std::sync::Arc;
 pub type CommandResult = Result<(), Box<dyn std::error::Error>>;
 
 /// Command context threaded through all command handlers.
-/// Carries printer config (--monitor) and device selector (--device).
+/// Carries printer config (--monitor), device selector (--device), target
+/// profile (--profile) and output format (--json).
 #[derive(Clone, Default)]
 pub struct CmdCtx {
     pub printer_config: Option<PrinterConfig>,
     pub device: Option<String>,
+    pub profile: Option<u8>,
+    pub json: bool,
 }
 
 impl CmdCtx {
@@ -56,104 +70,132 @@ impl CmdCtx {
         Self {
             printer_config,
             device,
+            profile: None,
+            json: false,
         }
     }
 
+    pub fn with_profile(mut self, profile: Option<u8>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    pub fn with_json(mut self, json: bool) -> Self {
+        self.json = json;
+        self
+    }
+
+    /// Profile for keymap writes, which carry it in the packet.
+    pub fn keymap_profile(&self) -> u8 {
+        self.profile.unwrap_or(0)
+    }
+
     pub fn device_selector(&self) -> Option<&str> {
         self.device.as_deref()
     }
 }
 
+/// Print a value as a single line of JSON (for `--json` output).
+pub(crate) fn print_json(value: &impl serde::Serialize) -> CommandResult {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+/// Under `--json`, also print a failed command's error as `{"error": ...}`,
+/// so scripts get an object to parse either way.
+pub(crate) fn json_errors(ctx: &CmdCtx, result: CommandResult) -> CommandResult {
+    if let Err(e) = &result {
+        if ctx.json {
+            print_json(&serde_json::json!({ "error": e.to_string() }))?;
+        }
+    }
+    result
+}
+
 /// Model name resolver for device labeling.
 /// Uses the device database to look up display names.
-fn resolve_model_name(device_id: Option<u32>, vid: u16, pid: u16) -> Option<String> {
+pub(crate) fn resolve_model_name(device_id: Option<u32>, vid: u16, pid: u16) -> Option<String> {
     iot_driver::devices::get_device_info_with_id(device_id.map(|id| id as i32), vid, pid)
         .map(|info| info.display_name)
 }
 
+/// Mark labels whose probed device ID the database lists as a mouse.
+///
+/// Discovery only knows mice by PID and product string; RY6608 mice share the
+/// keyboard dongle PID and are recognized here.
+fn mark_mice(labeled: &mut [(ProbedDevice, DeviceLabel)]) {
+    let registry = iot_driver::profile_registry();
+    for (probed, label) in labeled.iter_mut() {
+        let is_mouse = label.device_id.is_some_and(|id| {
+            registry
+                .get_device_info_by_id_and_usb(id as i32, label.vid, label.pid)
+                .is_some_and(|d| d.is_mouse())
+        });
+        if is_mouse {
+            probed.kind = DeviceKind::Mouse;
+            label.kind = DeviceKind::Mouse;
+        }
+    }
+}
+
 /// Resolve which device to use based on the --device selector.
 ///
 /// When selector is None:
 /// - 0 devices: error
-/// - 1 device: use it
+/// - 1 device, or 1 keyboard next to mice: use it
 /// - Multiple: print numbered list to stderr, return error
 ///
-/// When selector is Some:
-/// - Try parse as index (usize)
-/// - Try match transport name ("usb", "dongle", "bt")
-/// - Otherwise treat as HID path prefix
+/// When selector is Some it is matched by [`select_device`]: list index,
+/// transport name, kind, USB id, serial number, then HID path.
 pub(crate) fn resolve_device(
     discovery: &HidDiscovery,
     selector: Option<&str>,
 ) -> Result<monsgeek_transport::DiscoveredDevice, Box<dyn std::error::Error>> {
-    let labeled = discovery.list_labeled_devices(resolve_model_name)?;
+    let mut labeled = discovery.list_labeled_devices(resolve_model_name)?;
+    mark_mice(&mut labeled);
 
     if labeled.is_empty() {
-        return Err(monsgeek_transport::TransportError::DeviceNotFound(
-            "No supported device found".into(),
-        )
-        .into());
+        let boot = discovery.list_boot_mode_devices().unwrap_or_default();
+        let msg = match boot.first() {
+            Some(b) => format!(
+                "No supported device found, but a {} is in bootloader mode ({:04x}:{:04x}); \
+                 see `iot_driver firmware recover`",
+                b.kind.name(),
+                b.vid,
+                b.pid
+            ),
+            None => "No supported device found".into(),
+        };
+        return Err(monsgeek_transport::TransportError::DeviceNotFound(msg).into());
     }
 
+    let labels: Vec<_> = labeled.iter().map(|(_, l)| l.clone()).collect();
     if let Some(sel) = selector {
-        // Try parse as index
-        if let Ok(idx) = sel.parse::<usize>() {
-            let len = labeled.len();
-            return labeled
-                .into_iter()
-                .find(|(_, l)| l.index == idx)
-                .map(|(p, _)| p.device)
-                .ok_or_else(|| format!("Device index {idx} out of range (0-{})", len - 1).into());
-        }
-
-        // Try match transport name
-        let transport_matches: Vec<_> = labeled
-            .iter()
-            .filter(|(_, l)| l.transport_name == sel)
-            .collect();
-        if transport_matches.len() == 1 {
-            return Ok(transport_matches[0].0.device.clone());
-        }
-        if transport_matches.len() > 1 {
-            let labels: Vec<_> = labeled.iter().map(|(_, l)| l.clone()).collect();
-            eprintln!("Multiple devices match transport '{sel}':");
-            eprint!("{}", format_device_list(&labels));
-            return Err(format!(
-                "Ambiguous --device '{sel}': {} matches. Use index or HID path.",
-                transport_matches.len()
-            )
-            .into());
-        }
-
-        // Try HID path prefix match
-        let path_matches: Vec<_> = labeled
-            .iter()
-            .filter(|(_, l)| l.hid_path.contains(sel))
-            .collect();
-        if path_matches.len() == 1 {
-            return Ok(path_matches[0].0.device.clone());
-        }
-        if path_matches.len() > 1 {
-            let labels: Vec<_> = labeled.iter().map(|(_, l)| l.clone()).collect();
-            eprintln!("Multiple devices match path '{sel}':");
-            eprint!("{}", format_device_list(&labels));
-            return Err(format!(
-                "Ambiguous --device '{sel}': {} matches.",
-                path_matches.len()
-            )
-            .into());
-        }
-
-        return Err(format!("No device matches '{sel}'").into());
+        return match select_device(&labels, sel) {
+            Ok(pos) => Ok(labeled.into_iter().nth(pos).unwrap().0.device),
+            Err(e) => {
+                if matches!(e, DeviceSelectError::Ambiguous { .. }) {
+                    eprintln!("Multiple devices match '{sel}':");
+                    eprint!("{}", format_device_list(&labels));
+                }
+                Err(e.into())
+            }
+        };
     }
 
     // No selector: auto-select
     if labeled.len() == 1 {
         return Ok(labeled.into_iter().next().unwrap().0.device);
     }
+    // A keyboard and mice on the same desk: commands are for the keyboard
+    let mut keyboards = labeled
+        .iter()
+        .filter(|(p, _)| p.kind == DeviceKind::Keyboard);
+    if let (Some((p, _)), None) = (keyboards.next(), keyboards.next()) {
+        return Ok(p.device.clone());
+    }
 
     // Multiple devices: print list and error
-    let labels: Vec<_> = labeled.iter().map(|(_, l)| l.clone()).collect();
     eprintln!("Multiple devices found. Use --device (-D) to select:");
     eprint!("{}", format_device_list(&labels));
     Err("Multiple devices found, use --device to select".into())
@@ -201,19 +243,29 @@ pub fn open_keyboard(
         }
     }
 
+    // A builtin profile knows how many positions the firmware keeps magnetism
+    // data for, which decides how many pages trigger reads span.
+    let profile = registry.find_for_device(device_id, vid, pid);
+    if let Some(p) = &profile {
+        key_count = key_count.max(p.key_count());
+    }
+    iot_driver::keymap::select_board_layout(device_id, matrix_db);
+    iot_driver::keymap::select_led_layout(device_id, vid, pid);
+
     let mut kb =
         monsgeek_keyboard::KeyboardInterface::new(flow, key_count, has_magnetism, protocol);
 
-    // Cap settable polling rates at what this model supports.
+    // Cap settable polling rates at what this model supports, and keep
+    // commands for hardware it lacks from being sent.
     if let Some(def) = device_id.and_then(|id| registry.get_device_info_by_id_and_usb(id, vid, pid))
     {
         kb.set_polling_rates(def.polling_rates().to_vec());
     }
+    kb.set_capabilities(iot_driver::devices::capabilities_with_id(
+        device_id, vid, pid,
+    ));
 
     // Resolve key names: prefer builtin profile, fall back to matrix database.
-    let profile = device_id
-        .and_then(|id| registry.find_by_id(id as u32))
-        .or_else(|| registry.find_by_vid_pid(vid, pid));
     if let Some(p) = profile {
         let names: Vec<String> = (0..p.matrix_size())
             .map(|i| p.matrix_key_name(i as u8).to_string())
@@ -225,6 +277,16 @@ pub fn open_keyboard(
             .map(|i| matrix.key_name(i).unwrap_or("").to_string())
             .collect();
         kb.set_matrix_key_names(names);
+    } else if let Some(def) = device_id
+        .and_then(|id| registry.get_device_info_by_id_and_usb(id, vid, pid))
+        .filter(|d| d.led_matrix.is_some())
+    {
+        // User definitions (`device add`) carry their own LED matrix
+        let size = def.led_matrix.as_ref().map_or(0, Vec::len);
+        let names: Vec<String> = (0..size)
+            .map(|i| def.key_name(i).unwrap_or("").to_string())
+            .collect();
+        kb.set_matrix_key_names(names);
     }
 
     // Set non-analog positions from matrix database (encoder/GPIO keys).
@@ -244,6 +306,7 @@ where
 {
     match open_keyboard(ctx) {
         Ok(keyboard) => f(&keyboard),
+        Err(e) if ctx.json => Err(format!("No device found: {e}").into()),
         Err(e) => {
             eprintln!("No device found: {e}");
             Ok(())
@@ -251,6 +314,40 @@ where
     }
 }
 
+/// Like [`with_keyboard`], but runs the closure with the `--profile` target
+/// temporarily active (switch, apply, restore). Without `--profile` the
+/// closure runs against the active profile.
+pub fn with_keyboard_in_profile<F>(ctx: &CmdCtx, f: F) -> CommandResult
+where
+    F: FnOnce(&monsgeek_keyboard::KeyboardInterface) -> CommandResult,
+{
+    with_keyboard(ctx, |kb| match ctx.profile {
+        Some(profile) => kb.with_profile(profile, f),
+        None => f(kb),
+    })
+}
+
+/// Hold off wireless sleep for the duration of a streaming command.
+///
+/// Not fatal on failure: the stream still runs, it just stops if the keyboard
+/// idles into sleep.
+pub(crate) fn keep_awake(
+    kb: &monsgeek_keyboard::KeyboardInterface,
+) -> Option<monsgeek_keyboard::KeepAwake<'_>> {
+    match kb.keep_awake() {
+        Ok(guard) => {
+            if guard.is_active() {
+                println!("Wireless sleep disabled while streaming (restored on exit)");
+            }
+            Some(guard)
+        }
+        Err(e) => {
+            eprintln!("Warning: could not disable sleep: {e}");
+            None
+        }
+    }
+}
+
 /// Open a device via the transport layer with device selection support.
 /// Prefers wired USB > Bluetooth > dongle when no --device is specified and only one device exists.
 pub fn open_preferred_transport(
@@ -0,0 +1,119 @@
+//! Lighting plugin commands (plugin list, plugin run).
+
+use super::led_stream::open_with_patch_check;
+use super::{open_keyboard, print_json, setup_interrupt_handler, CmdCtx, CommandResult};
+use iot_driver::evdev::EventReader;
+use iot_driver::plugin::{load_all, plugins_dir, run};
+use iot_driver::settings::Settings;
+use serde_json::json;
+
+/// List the plugins in the plugins directory and whether they load.
+pub fn list(ctx: &CmdCtx) -> CommandResult {
+    let dir = plugins_dir();
+    let settings = Settings::load().plugins;
+    let (plugins, errors) = load_all(&dir, &settings);
+
+    if ctx.json {
+        let loaded: Vec<_> = plugins
+            .iter()
+            .map(|p| {
+                json!({
+                    "name": p.name(),
+                    "path": p.path(),
+                    "effect": p.is_effect(),
+                    "events": p.handles_events(),
+                })
+            })
+            .collect();
+        return print_json(&json!({
+            "dir": dir,
+            "plugins": loaded,
+            "errors": errors,
+            "effect": settings.effect,
+        }));
+    }
+
+    if plugins.is_empty() && errors.is_empty() {
+        println!("No plugins in {}", dir.display());
+        return Ok(());
+    }
+    println!("Plugins in {}:", dir.display());
+    for p in &plugins {
+        let active = settings.effect.as_deref() == Some(p.name()) && p.is_effect();
+        println!(
+            "  {:<20} {:<16} {}{}",
+            p.name(),
+            p.describe_caps(),
+            p.path()
+                .file_name()
+                .map(|f| f.to_string_lossy())
+                .unwrap_or_default(),
+            if active { "  (daemon effect)" } else { "" }
+        );
+    }
+    for e in &errors {
+        println!("  failed: {e}");
+    }
+    Ok(())
+}
+
+/// Run one effect plugin on the LEDs, with every event processor fed key
+/// events, until Ctrl+C.
+pub fn run_effect(
+    ctx: &CmdCtx,
+    name: Option<&str>,
+    fps: Option<u32>,
+    power_budget: Option<u32>,
+) -> CommandResult {
+    let saved = Settings::load().plugins;
+    let settings = iot_driver::plugin::PluginSettings {
+        fps: fps.or(saved.fps),
+        power_budget: power_budget.or(saved.power_budget),
+        ..saved
+    };
+    let (mut plugins, errors) = load_all(&plugins_dir(), &settings);
+    for e in &errors {
+        eprintln!("Skipping {e}");
+    }
+
+    let effect = match name {
+        Some(name) => {
+            let i = plugins
+                .iter()
+                .position(|p| p.name() == name)
+                .ok_or_else(|| {
+                    format!("No plugin named '{name}' (see 'iot_driver plugin list')")
+                })?;
+            if !plugins[i].is_effect() {
+                return Err(format!("Plugin '{name}' is not an effect").into());
+            }
+            Some(i)
+        }
+        None => None,
+    };
+    if effect.is_none() && !plugins.iter().any(|p| p.handles_events()) {
+        return Err("No event processor plugins loaded; name an effect to run".into());
+    }
+
+    let kb = match effect {
+        Some(_) => open_with_patch_check(ctx)?,
+        None => open_keyboard(ctx)?,
+    };
+    let _awake = super::keep_awake(&kb);
+    let reader = EventReader::open(kb.vid(), kb.pid());
+    if reader.is_empty() {
+        eprintln!(
+            "No readable input nodes for {:04X}:{:04X}; plugins get no key events \
+             (are you in the 'input' group?)",
+            kb.vid(),
+            kb.pid()
+        );
+    }
+
+    let names: Vec<_> = plugins.iter().map(|p| p.name()).collect();
+    println!("Running plugins: {} (Ctrl+C to stop)", names.join(", "));
+    let running = setup_interrupt_handler();
+    run(&kb, &reader, &mut plugins, effect, &settings, running)?;
+    println!("\nPlugins stopped");
+    Ok(())
+}
@@ -1,4 +1,5 @@
-//! `probe` — self-service diagnostic report.
+//! `probe` — self-service diagnostic report, and `probe scan` for mapping
+//! undocumented GET commands.
 //!
 //! Collects device identity, USB/HID descriptors, device-database match status,
 //! and raw protocol responses into a GitHub-ready Markdown report so users with
@@ -6,14 +7,18 @@
 //! to add support or debug. Every query is wrapped so a failure appends a
 //! `timeout/error` line and the report still completes.
 
-use super::{open_preferred_transport, resolve_device, CmdCtx, CommandResult};
+use super::{
+    open_preferred_transport, resolve_device, setup_interrupt_handler, CmdCtx, CommandResult,
+};
+use iot_driver::command_probe::{self, ProbeResult, ReplyShape, Skip};
 use iot_driver::protocol::patch_info;
 use monsgeek_transport::protocol::cmd;
 use monsgeek_transport::protocol::ProtocolFamily;
 use monsgeek_transport::{ChecksumType, FlowControlTransport, HidDiscovery, Transport};
 use std::fmt::Write as _;
 use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Format bytes as space-separated lowercase hex.
 fn hex(bytes: &[u8]) -> String {
@@ -328,3 +333,137 @@ pub fn run(ctx: &CmdCtx, output: Option<&Path>) -> CommandResult {
 
     Ok(())
 }
+
+/// Send unknown GET command bytes one at a time and record how each answers.
+///
+/// SET bytes and the blocklist in `command_probe` are never sent. After any
+/// byte that gets no reply the device is checked with GET_USB_VERSION, and the
+/// scan stops if that fails too.
+pub fn scan(
+    ctx: &CmdCtx,
+    range: &str,
+    interval_ms: u64,
+    include_known: bool,
+    yes: bool,
+    output: Option<&Path>,
+) -> CommandResult {
+    let range = command_probe::parse_range(range)?;
+    let (send, skipped) = command_probe::plan(range.clone(), include_known);
+
+    println!(
+        "Range 0x{:02X}-0x{:02X}: {} to probe, {} skipped",
+        range.start(),
+        range.end(),
+        send.len(),
+        skipped.len()
+    );
+    for (byte, skip) in &skipped {
+        if let Skip::Blocked(why) = skip {
+            println!("  0x{byte:02X} blocked: {why}");
+        }
+    }
+    let set = skipped
+        .iter()
+        .filter(|(_, s)| *s == Skip::SetCommand)
+        .count();
+    if set > 0 {
+        println!("  {set} SET command byte(s) below 0x80 are never probed");
+    }
+    if send.is_empty() {
+        println!("Nothing to probe.");
+        return Ok(());
+    }
+
+    println!();
+    println!("Undocumented commands may do more than read. Probing is at your own risk;");
+    println!("have a way to recover the device (see `firmware flash`) before continuing.");
+    if !yes {
+        println!();
+        print!("Type 'yes' to continue: ");
+        use std::io::Write;
+        std::io::stdout().flush()?;
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if input.trim() != "yes" {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    let transport = open_preferred_transport(ctx)?;
+    let alive = || {
+        transport
+            .query_command(cmd::GET_USB_VERSION, &[], ChecksumType::Bit7)
+            .is_ok()
+    };
+    if !alive() {
+        return Err("device does not answer GET_USB_VERSION; not probing".into());
+    }
+
+    let running = setup_interrupt_handler();
+    let mut results: Vec<ProbeResult> = Vec::new();
+    let mut previous: Option<Vec<u8>> = None;
+    println!();
+    for &byte in &send {
+        if !running.load(Ordering::SeqCst) {
+            println!("Interrupted.");
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(interval_ms));
+
+        let (shape, data) = match transport.query_raw(byte, &[], ChecksumType::Bit7) {
+            Ok(reply) => {
+                let shape = command_probe::classify(byte, &reply, previous.as_deref());
+                let data = command_probe::trimmed(&reply).to_vec();
+                previous = Some(reply);
+                (shape, data)
+            }
+            Err(e) => (
+                ReplyShape::NoReply {
+                    error: e.to_string(),
+                },
+                Vec::new(),
+            ),
+        };
+        let line = match &shape {
+            ReplyShape::NoReply { error } => format!("no reply ({error})"),
+            ReplyShape::Empty => "empty".to_string(),
+            ReplyShape::Stale => "stale (previous reply)".to_string(),
+            ReplyShape::Echo { len } => format!("echo, {len} bytes: {}", hex(&data)),
+            ReplyShape::Other { first, len } => {
+                format!("no echo (0x{first:02X}), {len} bytes: {}", hex(&data))
+            }
+        };
+        println!("0x{byte:02X}  {line}");
+
+        let lost = matches!(shape, ReplyShape::NoReply { .. });
+        results.push(ProbeResult {
+            cmd: byte,
+            shape,
+            data,
+        });
+        if lost && !alive() {
+            println!("Device stopped answering after 0x{byte:02X}; stopping.");
+            break;
+        }
+    }
+
+    let answered: Vec<_> = results.iter().filter(|r| r.shape.answered()).collect();
+    println!(
+        "\n{} of {} probed byte(s) answered{}",
+        answered.len(),
+        results.len(),
+        if answered.is_empty() { "" } else { ":" }
+    );
+    for r in &answered {
+        println!("  0x{:02X}  {}", r.cmd, hex(&r.data));
+    }
+
+    if let Some(path) = output {
+        let json = serde_json::to_string_pretty(&results).map_err(|e| e.to_string())?;
+        std::fs::write(path, json)?;
+        println!("Results written to {}", path.display());
+    }
+    Ok(())
+}
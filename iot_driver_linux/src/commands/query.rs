@@ -1,36 +1,75 @@
 //! Query (read-only) command handlers.
 
-use super::{format_command_response, open_preferred_transport, CmdCtx, CommandResult};
+use super::{format_command_response, open_preferred_transport, print_json, CmdCtx, CommandResult};
 use hidapi::HidApi;
 use iot_driver::hal;
 use iot_driver::protocol::{self, cmd};
 use monsgeek_keyboard::SleepTimeSettings;
 use monsgeek_transport::protocol::cmd as transport_cmd;
 use monsgeek_transport::{ChecksumType, Transport};
+use serde_json::json;
+use std::ops::Range;
 use std::time::Duration;
 
+/// Decode a patch-info payload (`[magic_hi, magic_lo, ver, caps_lo, caps_hi, name..]`)
+/// starting at `off`, with a name of at most `name_max` bytes.
+/// Returns `(version, capabilities, name)`.
+fn parse_patch_payload(buf: &[u8], off: usize, name_max: usize) -> Option<(u8, u16, String)> {
+    if buf.len() < off + 6
+        || buf[off] != protocol::patch_info::MAGIC_HI
+        || buf[off + 1] != protocol::patch_info::MAGIC_LO
+    {
+        return None;
+    }
+    let ver = buf[off + 2];
+    let caps = u16::from_le_bytes([buf[off + 3], buf[off + 4]]);
+    let name_off = off + 5;
+    let name_end = buf.len().min(name_off + name_max);
+    let name_bytes = &buf[name_off..name_end];
+    let name_len = name_bytes
+        .iter()
+        .position(|&b| b == 0)
+        .unwrap_or(name_bytes.len());
+    Some((
+        ver,
+        caps,
+        String::from_utf8_lossy(&name_bytes[..name_len]).into_owned(),
+    ))
+}
+
+fn patch_text(label: &str, patch: &(u8, u16, String)) -> String {
+    let (ver, caps, name) = patch;
+    let cap_names = protocol::patch_info::capability_names(*caps);
+    if cap_names.is_empty() {
+        format!("{label} {name} v{ver} (no features enabled).")
+    } else {
+        format!("{label} {name} v{ver} [{}]", cap_names.join(", "))
+    }
+}
+
+fn patch_json(patch: &Option<(u8, u16, String)>) -> serde_json::Value {
+    match patch {
+        Some((ver, caps, name)) => json!({
+            "name": name,
+            "version": ver,
+            "capabilities": caps,
+            "features": protocol::patch_info::capability_names(*caps),
+        }),
+        None => serde_json::Value::Null,
+    }
+}
+
 /// Get device info (firmware version, device ID, patch, boot mode, API ID)
 pub fn info(ctx: &CmdCtx) -> CommandResult {
     let transport = open_preferred_transport(ctx)?;
     let dev = transport.device_info();
 
-    // Device identity
-    if let Some(ref name) = dev.product_name {
-        println!("Device:    {name}");
-    }
-    println!(
-        "  VID/PID:  {:04X}:{:04X}  type={:?}",
-        dev.vid, dev.pid, dev.transport_type
-    );
-
     let resp = transport.query_command(transport_cmd::GET_USB_VERSION, &[], ChecksumType::Bit7)?;
     let device_id = u32::from_le_bytes([resp[1], resp[2], resp[3], resp[4]]);
     let version = u16::from_le_bytes([resp[7], resp[8]]);
 
     // Version is stored as major.minor in high/low byte (e.g. 0x0407 = v4.07)
     let (major, minor) = (version >> 8, version & 0xFF);
-    println!("Firmware:  v{major}.{minor:02} (raw 0x{version:04X}, dec {version})");
-    println!("Device ID: {device_id} (0x{device_id:08X})");
 
     // Protocol family
     let device_info =
@@ -39,11 +78,9 @@ pub fn info(ctx: &CmdCtx) -> CommandResult {
         device_info.as_ref().map(|d| d.name.as_str()),
         dev.pid,
     );
-    println!("Protocol:  {protocol}");
 
     // Boot mode (bootloader / firmware update mode)
     let is_boot = iot_driver::protocol::firmware_update::is_boot_mode(dev.vid, dev.pid);
-    println!("Boot mode: {}", if is_boot { "Yes" } else { "No" });
 
     // API ID (for firmware server; same as device ID or VID/PID fallback)
     let api_id = if device_id != 0 {
@@ -51,108 +88,86 @@ pub fn info(ctx: &CmdCtx) -> CommandResult {
     } else {
         iot_driver::firmware_api::device_ids::from_vid_pid(dev.vid, dev.pid)
     };
+
+    // Patched firmware (battery HID, LED stream, etc.)
+    // Probe 0xE7: wired HID returns [cmd_echo, magic_hi, magic_lo, ...]; some paths may return [magic_hi, magic_lo, ...]
+    let patch_resp = transport
+        .query_raw(protocol::patch_info::CMD, &[], ChecksumType::Bit7)
+        .ok();
+    let patch = patch_resp.as_ref().and_then(|resp| {
+        parse_patch_payload(resp, 0, 9).or_else(|| parse_patch_payload(resp, 1, 9))
+    });
+
+    // Dongle patch info (Feature Report ID 8) — only available on dongle transport
+    let dongle = transport.inner().get_dongle_patch_info();
+    let dongle_patch = match &dongle {
+        Ok(Some(buf)) if buf.len() >= 8 => parse_patch_payload(buf, 1, 8),
+        _ => None,
+    };
+
+    if ctx.json {
+        return print_json(&json!({
+            "device": dev.product_name,
+            "vid": dev.vid,
+            "pid": dev.pid,
+            "transport": format!("{:?}", dev.transport_type),
+            "firmware": format!("{major}.{minor:02}"),
+            "firmware_raw": version,
+            "device_id": device_id,
+            "protocol": protocol.to_string(),
+            "boot_mode": is_boot,
+            "api_id": api_id,
+            "patch": patch_json(&patch),
+            "dongle_patch": match dongle {
+                Ok(None) => serde_json::Value::Null,
+                _ => patch_json(&dongle_patch),
+            },
+        }));
+    }
+
+    // Device identity
+    if let Some(ref name) = dev.product_name {
+        println!("Device:    {name}");
+    }
+    println!(
+        "  VID/PID:  {:04X}:{:04X}  type={:?}",
+        dev.vid, dev.pid, dev.transport_type
+    );
+    println!("Firmware:  v{major}.{minor:02} (raw 0x{version:04X}, dec {version})");
+    println!("Device ID: {device_id} (0x{device_id:08X})");
+    println!("Protocol:  {protocol}");
+    println!("Boot mode: {}", if is_boot { "Yes" } else { "No" });
     if let Some(id) = api_id {
         println!("API ID:    {id}");
     }
 
-    // Patched firmware (battery HID, LED stream, etc.)
-    // Probe 0xE7: wired HID returns [cmd_echo, magic_hi, magic_lo, ...]; some paths may return [magic_hi, magic_lo, ...]
-    match transport.query_raw(protocol::patch_info::CMD, &[], ChecksumType::Bit7) {
-        Ok(resp) => {
-            let offsets = if resp.len() >= 6
-                && resp[0] == protocol::patch_info::MAGIC_HI
-                && resp[1] == protocol::patch_info::MAGIC_LO
-            {
-                Some((2, 3, 5)) // payload only
-            } else if resp.len() >= 7
-                && resp[1] == protocol::patch_info::MAGIC_HI
-                && resp[2] == protocol::patch_info::MAGIC_LO
-            {
-                Some((3, 4, 6)) // echo + payload (wired strips report ID only)
-            } else {
-                None
-            };
-            match offsets {
-                Some((ver_off, caps_off, name_off)) => {
-                    let patch_ver = resp[ver_off];
-                    let caps = u16::from_le_bytes([resp[caps_off], resp[caps_off + 1]]);
-                    let name_end = resp.len().min(name_off + 9);
-                    let name_bytes = &resp[name_off..name_end];
-                    let name_len = name_bytes
-                        .iter()
-                        .position(|&b| b == 0)
-                        .unwrap_or(name_bytes.len());
-                    let name = String::from_utf8_lossy(&name_bytes[..name_len]);
-                    let cap_names = protocol::patch_info::capability_names(caps);
-                    if cap_names.is_empty() {
-                        println!("Patch:     {} v{} (no features enabled).", name, patch_ver);
-                    } else {
-                        println!(
-                            "Patch:     {} v{} [{}]",
-                            name,
-                            patch_ver,
-                            cap_names.join(", ")
-                        );
-                    }
-                }
-                None => {
-                    println!("Patch:     Stock firmware (no patch support).");
-                    // Show raw 0xE7 response to investigate: patched FW returns 0xCA 0xFE magic;
-                    // stock may echo the command and return other data.
-                    let hex_len = resp.len().min(16);
-                    println!(
-                        "           0xE7 response ({} bytes): {}",
-                        resp.len(),
-                        resp[..hex_len]
-                            .iter()
-                            .map(|b| format!("{:02x}", b))
-                            .collect::<Vec<_>>()
-                            .join(" ")
-                    );
-                }
-            }
+    match (&patch, &patch_resp) {
+        (Some(p), _) => println!("{}", patch_text("Patch:    ", p)),
+        (None, Some(resp)) => {
+            println!("Patch:     Stock firmware (no patch support).");
+            // Show raw 0xE7 response to investigate: patched FW returns 0xCA 0xFE magic;
+            // stock may echo the command and return other data.
+            let hex_len = resp.len().min(16);
+            println!(
+                "           0xE7 response ({} bytes): {}",
+                resp.len(),
+                resp[..hex_len]
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            );
         }
-        Err(_) => {
+        (None, None) => {
             println!("Patch:     Stock firmware (no patch support).");
             println!("           0xE7 response: none (timeout or error).");
         }
     }
 
-    // Dongle patch info (Feature Report ID 8) — only available on dongle transport
-    match transport.inner().get_dongle_patch_info() {
-        Ok(Some(buf)) => {
-            if buf.len() >= 8
-                && buf[1] == protocol::patch_info::MAGIC_HI
-                && buf[2] == protocol::patch_info::MAGIC_LO
-            {
-                let patch_ver = buf[3];
-                let caps = u16::from_le_bytes([buf[4], buf[5]]);
-                let name_end = buf.len().min(14);
-                let name_bytes = &buf[6..name_end];
-                let name_len = name_bytes
-                    .iter()
-                    .position(|&b| b == 0)
-                    .unwrap_or(name_bytes.len());
-                let name = String::from_utf8_lossy(&name_bytes[..name_len]);
-                let cap_names = protocol::patch_info::capability_names(caps);
-                if cap_names.is_empty() {
-                    println!("Dongle:    {} v{} (no features enabled).", name, patch_ver);
-                } else {
-                    println!(
-                        "Dongle:    {} v{} [{}]",
-                        name,
-                        patch_ver,
-                        cap_names.join(", ")
-                    );
-                }
-            } else {
-                println!("Dongle:    Stock firmware (no patch support).");
-            }
-        }
-        Ok(None) => {} // Not a dongle transport, skip silently
-        Err(_) => {
-            println!("Dongle:    Stock firmware (no patch support).");
-        }
+    match (&dongle, &dongle_patch) {
+        (Ok(None), _) => {} // Not a dongle transport, skip silently
+        (_, Some(p)) => println!("{}", patch_text("Dongle:   ", p)),
+        _ => println!("Dongle:    Stock firmware (no patch support)."),
     }
 
     Ok(())
@@ -162,6 +177,9 @@ pub fn info(ctx: &CmdCtx) -> CommandResult {
 pub fn profile(ctx: &CmdCtx) -> CommandResult {
     let transport = open_preferred_transport(ctx)?;
     let resp = transport.query_command(transport_cmd::GET_PROFILE, &[], ChecksumType::Bit7)?;
+    if ctx.json {
+        return print_json(&json!({ "profile": resp[1] }));
+    }
     println!("Profile: {}", resp[1]);
     Ok(())
 }
@@ -170,12 +188,11 @@ pub fn profile(ctx: &CmdCtx) -> CommandResult {
 pub fn led(ctx: &CmdCtx) -> CommandResult {
     let transport = open_preferred_transport(ctx)?;
     let resp = transport.query_command(transport_cmd::GET_LEDPARAM, &[], ChecksumType::Bit7)?;
-    let mode = resp[1];
-    let speed = resp[2];
-    let brightness = resp[3];
-    let r = resp[5];
-    let g = resp[6];
-    let b = resp[7];
+    if ctx.json {
+        return print_json(&led_json(&resp));
+    }
+    let (mode, brightness, speed) = (resp[1], resp[2], led_speed(&resp));
+    let (r, g, b) = (resp[5], resp[6], resp[7]);
     println!("LED:");
     println!("  Mode:       {} ({})", mode, cmd::led_mode_name(mode));
     println!("  Speed:      {speed}/4");
@@ -184,33 +201,70 @@ pub fn led(ctx: &CmdCtx) -> CommandResult {
     Ok(())
 }
 
+/// LED speed from a GET_LEDPARAM response (the firmware stores it inverted)
+fn led_speed(resp: &[u8]) -> u8 {
+    protocol::LED_SPEED_MAX - resp[3].min(protocol::LED_SPEED_MAX)
+}
+
+/// GET_LEDPARAM response as printed by `led --json` and `all --json`
+fn led_json(resp: &[u8]) -> serde_json::Value {
+    json!({
+        "mode": resp[1],
+        "mode_name": cmd::led_mode_name(resp[1]),
+        "brightness": resp[2],
+        "speed": led_speed(resp),
+        "color": format!("#{:02X}{:02X}{:02X}", resp[5], resp[6], resp[7]),
+    })
+}
+
 /// Get debounce time
 pub fn debounce(ctx: &CmdCtx) -> CommandResult {
     let transport = open_preferred_transport(ctx)?;
     let resp = transport.query_command(transport_cmd::GET_DEBOUNCE, &[], ChecksumType::Bit7)?;
+    if ctx.json {
+        return print_json(&json!({ "debounce_ms": resp[1] }));
+    }
     println!("Debounce: {} ms", resp[1]);
     Ok(())
 }
 
 /// Get polling rate
-pub fn rate(keyboard: &monsgeek_keyboard::KeyboardInterface) -> CommandResult {
+pub fn rate(keyboard: &monsgeek_keyboard::KeyboardInterface, json: bool) -> CommandResult {
     use iot_driver::protocol::polling_rate;
 
     match keyboard.get_polling_rate() {
         Ok(rate) => {
             let hz = rate.to_hz();
+            if json {
+                return print_json(&json!({ "polling_rate_hz": hz }));
+            }
             println!("Polling rate: {hz} ({})", polling_rate::name(hz));
         }
+        Err(e) if json => return Err(e.into()),
         Err(e) => eprintln!("Failed to get polling rate: {e}"),
     }
     Ok(())
 }
 
+/// Response bytes reported raw by `options` and `features` (and `all`);
+/// byte 0 echoes the command and is left out.
+const OPTIONS_BYTES: Range<usize> = 1..16;
+const FEATURES_BYTES: Range<usize> = 1..24;
+
+/// The `bytes` of a response, cut short if the response is shorter
+fn raw_bytes(resp: &[u8], bytes: Range<usize>) -> &[u8] {
+    &resp[bytes.start.min(resp.len())..bytes.end.min(resp.len())]
+}
+
 /// Get keyboard options
 pub fn options(ctx: &CmdCtx) -> CommandResult {
     let transport = open_preferred_transport(ctx)?;
     let resp = transport.query_command(transport_cmd::GET_KBOPTION, &[], ChecksumType::Bit7)?;
-    println!("Options (raw): {:02X?}", &resp[..16.min(resp.len())]);
+    let raw = raw_bytes(&resp, OPTIONS_BYTES);
+    if ctx.json {
+        return print_json(&json!({ "options_raw": raw }));
+    }
+    println!("Options (raw): {raw:02X?}");
     Ok(())
 }
 
@@ -218,13 +272,23 @@ pub fn options(ctx: &CmdCtx) -> CommandResult {
 pub fn features(ctx: &CmdCtx) -> CommandResult {
     let transport = open_preferred_transport(ctx)?;
     let resp = transport.query_command(transport_cmd::GET_FEATURE_LIST, &[], ChecksumType::Bit7)?;
-    println!("Features (raw): {:02X?}", &resp[..24.min(resp.len())]);
+    let raw = raw_bytes(&resp, FEATURES_BYTES);
+    if ctx.json {
+        return print_json(&json!({ "features_raw": raw }));
+    }
+    println!("Features (raw): {raw:02X?}");
     Ok(())
 }
 
 /// Get sleep time settings
-pub fn sleep(keyboard: &monsgeek_keyboard::KeyboardInterface) -> CommandResult {
+pub fn sleep(keyboard: &monsgeek_keyboard::KeyboardInterface, json: bool) -> CommandResult {
     match keyboard.get_sleep_time() {
+        Ok(settings) if json => {
+            return print_json(&json!({
+                "bluetooth": { "idle_s": settings.idle_bt, "deep_s": settings.deep_bt },
+                "2.4ghz": { "idle_s": settings.idle_24g, "deep_s": settings.deep_24g },
+            }));
+        }
         Ok(settings) => {
             println!("Sleep Time Settings:");
             println!("  Bluetooth:");
@@ -250,6 +314,7 @@ pub fn sleep(keyboard: &monsgeek_keyboard::KeyboardInterface) -> CommandResult {
                 SleepTimeSettings::format_duration(settings.deep_24g)
             );
         }
+        Err(e) if json => return Err(e.into()),
         Err(e) => eprintln!("Failed to get sleep settings: {e}"),
     }
     Ok(())
@@ -257,6 +322,10 @@ pub fn sleep(keyboard: &monsgeek_keyboard::KeyboardInterface) -> CommandResult {
 
 /// Show all device information
 pub fn all(ctx: &CmdCtx) -> CommandResult {
+    if ctx.json {
+        return all_json(ctx);
+    }
+
     println!("MonsGeek M1 V5 HE - Device Information");
     println!("======================================\n");
 
@@ -289,6 +358,36 @@ pub fn all(ctx: &CmdCtx) -> CommandResult {
     Ok(())
 }
 
+/// `all --json`: every setting `all` prints, decoded into one object.
+/// Settings that fail to read are `null`.
+fn all_json(ctx: &CmdCtx) -> CommandResult {
+    let transport = open_preferred_transport(ctx)?;
+    let info = transport.device_info();
+    let query = |cmd_byte| {
+        transport
+            .query_command(cmd_byte, &[], ChecksumType::Bit7)
+            .ok()
+    };
+
+    let version = query(transport_cmd::GET_USB_VERSION).map(|r| {
+        json!({
+            "device_id": u32::from_le_bytes([r[1], r[2], r[3], r[4]]),
+            "firmware_raw": u16::from_le_bytes([r[7], r[8]]),
+        })
+    });
+    print_json(&json!({
+        "vid": info.vid,
+        "pid": info.pid,
+        "transport": format!("{:?}", info.transport_type),
+        "version": version,
+        "profile": query(transport_cmd::GET_PROFILE).map(|r| r[1]),
+        "debounce_ms": query(transport_cmd::GET_DEBOUNCE).map(|r| r[1]),
+        "led": query(transport_cmd::GET_LEDPARAM).map(|r| led_json(&r)),
+        "options_raw": query(transport_cmd::GET_KBOPTION).map(|r| raw_bytes(&r, OPTIONS_BYTES).to_vec()),
+        "features_raw": query(transport_cmd::GET_FEATURE_LIST).map(|r| raw_bytes(&r, FEATURES_BYTES).to_vec()),
+    }))
+}
+
 /// Get battery status from 2.4GHz dongle
 ///
 /// Checks kernel power_supply first (when eBPF filter loaded), falls back to vendor protocol.
@@ -298,6 +397,7 @@ pub fn battery(
     show_hex: bool,
     watch: Option<Option<u64>>,
     force_vendor: bool,
+    json: bool,
 ) -> CommandResult {
     use iot_driver::power_supply::{find_dongle_battery_power_supply, read_kernel_battery};
 
@@ -308,7 +408,15 @@ pub fn battery(
         // Check for kernel power_supply (eBPF filter loaded) unless --vendor flag
         if !force_vendor {
             if let Some(path) = find_dongle_battery_power_supply() {
-                if quiet {
+                if json {
+                    let info = read_kernel_battery(&path);
+                    print_json(&json!({
+                        "source": "kernel",
+                        "level": info.as_ref().map(|i| i.level),
+                        "connected": info.as_ref().map(|i| i.online),
+                        "charging": info.as_ref().map(|i| i.charging),
+                    }))?;
+                } else if quiet {
                     if let Some(info) = read_kernel_battery(&path) {
                         println!("{}", info.level);
                     } else {
@@ -340,7 +448,14 @@ pub fn battery(
 
         match result {
             Some((battery_level, online, idle, raw_bytes)) => {
-                if quiet {
+                if json {
+                    print_json(&json!({
+                        "source": "vendor",
+                        "level": battery_level,
+                        "connected": online,
+                        "idle": idle,
+                    }))?;
+                } else if quiet {
                     println!("{battery_level}");
                 } else if show_hex {
                     print_hex_dump(&raw_bytes);
@@ -363,7 +478,9 @@ pub fn battery(
                 }
             }
             None => {
-                if quiet {
+                if json {
+                    print_json(&json!({ "source": null, "level": null }))?;
+                } else if quiet {
                     eprintln!("No battery data");
                     std::process::exit(1);
                 } else {
@@ -1,4 +1,4 @@
-//! Reactive mode command handlers (audio, screen).
+//! Reactive mode command handlers (audio, screen, wpm, sysmon, timer).
 
 use super::{setup_interrupt_handler, CmdCtx, CommandResult};
 
@@ -10,8 +10,10 @@ pub fn audio(
     sensitivity: f32,
     rate: u32,
     device: Option<String>,
+    app: Option<String>,
 ) -> CommandResult {
     let keyboard = super::open_keyboard(ctx).map_err(|e| format!("Failed to open device: {e}"))?;
+    let _awake = super::keep_awake(&keyboard);
 
     println!(
         "Starting audio reactive mode on {}...",
@@ -27,6 +29,8 @@ pub fn audio(
         sensitivity,
         update_hz: rate,
         device,
+        app,
+        spectrum_bands: 0,
     };
 
     if let Err(e) = iot_driver::audio_reactive::run_audio_reactive(&keyboard, config, running) {
@@ -35,6 +39,195 @@ pub fn audio(
     Ok(())
 }
 
+/// Run the host-rendered spectrum analyzer (patch LED streaming)
+pub fn audio_spectrum(
+    ctx: &CmdCtx,
+    style: iot_driver::audio_spectrum::SpectrumStyle,
+    sensitivity: f32,
+    rate: u32,
+    device: Option<String>,
+    app: Option<String>,
+    power_budget: u32,
+) -> CommandResult {
+    let keyboard = super::led_stream::open_with_patch_check(ctx)?;
+    let _awake = super::keep_awake(&keyboard);
+
+    println!(
+        "Starting spectrum analyzer on {}...",
+        keyboard.device_name()
+    );
+    println!("Press Ctrl+C to stop");
+
+    let running = setup_interrupt_handler();
+
+    let config = iot_driver::audio_reactive::AudioConfig {
+        sensitivity,
+        update_hz: rate,
+        device,
+        app,
+        ..Default::default()
+    };
+
+    if let Err(e) = iot_driver::audio_spectrum::run_audio_spectrum(
+        &keyboard,
+        config,
+        style,
+        power_budget,
+        running,
+    ) {
+        eprintln!("Spectrum analyzer error: {e}");
+    }
+    Ok(())
+}
+
+/// Run host-rendered beat lighting (patch LED streaming)
+pub fn audio_beat(
+    ctx: &CmdCtx,
+    style: iot_driver::audio_beat::BeatStyle,
+    sensitivity: f32,
+    rate: u32,
+    device: Option<String>,
+    app: Option<String>,
+    power_budget: u32,
+) -> CommandResult {
+    let keyboard = super::led_stream::open_with_patch_check(ctx)?;
+    let _awake = super::keep_awake(&keyboard);
+
+    println!("Starting beat lighting on {}...", keyboard.device_name());
+    println!("Press Ctrl+C to stop");
+
+    let running = setup_interrupt_handler();
+
+    let config = iot_driver::audio_reactive::AudioConfig {
+        sensitivity,
+        update_hz: rate,
+        device,
+        app,
+        ..Default::default()
+    };
+
+    if let Err(e) =
+        iot_driver::audio_beat::run_audio_beat(&keyboard, config, style, power_budget, running)
+    {
+        eprintln!("Beat lighting error: {e}");
+    }
+    Ok(())
+}
+
+/// Run the typing speed meter (patch LED streaming, evdev key presses)
+pub fn wpm(
+    ctx: &CmdCtx,
+    style: &iot_driver::wpm::WpmStyle,
+    window: std::time::Duration,
+    power_budget: u32,
+) -> CommandResult {
+    let keyboard = super::led_stream::open_with_patch_check(ctx)?;
+    let _awake = super::keep_awake(&keyboard);
+
+    let reader = iot_driver::evdev::EventReader::open(keyboard.vid(), keyboard.pid());
+    if reader.is_empty() {
+        return Err(format!(
+            "No readable input nodes for {:04X}:{:04X}. Are you in the 'input' group?",
+            keyboard.vid(),
+            keyboard.pid()
+        )
+        .into());
+    }
+
+    println!("Starting WPM meter on {}...", keyboard.device_name());
+    println!("Press Ctrl+C to stop");
+
+    let running = setup_interrupt_handler();
+    if let Err(e) =
+        iot_driver::wpm::run_wpm(&keyboard, &reader, style, window, power_budget, running)
+    {
+        eprintln!("WPM meter error: {e}");
+    }
+    Ok(())
+}
+
+/// Run the system monitor gauge until Ctrl+C (sparse overlay diffs)
+pub fn sysmon(ctx: &CmdCtx, config: iot_driver::sysmon::SysmonConfig) -> CommandResult {
+    use std::sync::atomic::Ordering;
+
+    let keyboard = super::led_stream::open_with_patch_check(ctx)?;
+    let _awake = super::keep_awake(&keyboard);
+
+    println!(
+        "Showing {:?} gauge on {} every {}ms...",
+        config.metric,
+        keyboard.device_name(),
+        config.interval().as_millis()
+    );
+    println!("Press Ctrl+C to stop");
+
+    let running = setup_interrupt_handler();
+    let mut gauge = iot_driver::sysmon::Gauge::new(config);
+    let mut result = Ok(());
+    while running.load(Ordering::SeqCst) {
+        match gauge.tick(&keyboard) {
+            Ok(Some(level)) => {
+                print!("\r{:5.1}%  ", level * 100.0);
+                std::io::Write::flush(&mut std::io::stdout()).ok();
+            }
+            Ok(None) => {}
+            Err(e) => {
+                result = Err(e.into());
+                break;
+            }
+        }
+        std::thread::sleep(config.interval());
+    }
+
+    keyboard.stream_led_release().ok();
+    println!("\nGauge stopped");
+    result
+}
+
+/// Run the pomodoro timer until it finishes or Ctrl+C (patch LED streaming)
+pub fn timer(
+    ctx: &CmdCtx,
+    config: &iot_driver::pomodoro::TimerConfig,
+    power_budget: u32,
+) -> CommandResult {
+    let keyboard = super::led_stream::open_with_patch_check(ctx)?;
+    let _awake = super::keep_awake(&keyboard);
+
+    let cycles = match config.cycles {
+        0 => "until stopped".to_string(),
+        n => format!("{n} cycles"),
+    };
+    println!(
+        "Timer on {}: {}m work, {}m break, {cycles}",
+        keyboard.device_name(),
+        config.work.as_secs() / 60,
+        config.rest.as_secs() / 60
+    );
+    println!("Press Ctrl+C to stop");
+
+    let running = setup_interrupt_handler();
+    let result = iot_driver::pomodoro::run_timer(
+        &keyboard,
+        config,
+        std::time::Instant::now(),
+        power_budget,
+        running,
+        |status| {
+            let left = status.remaining().as_secs();
+            print!(
+                "\r#{} {:<5} {:02}:{:02}  ",
+                status.cycle,
+                status.phase.name(),
+                left / 60,
+                left % 60
+            );
+            std::io::Write::flush(&mut std::io::stdout()).ok();
+        },
+    );
+    println!("\nTimer stopped");
+    result.map_err(Into::into)
+}
+
 /// Test audio capture (list devices)
 pub fn audio_test() -> CommandResult {
     println!("Testing audio capture...\n");
@@ -45,6 +238,16 @@ pub fn audio_test() -> CommandResult {
     }
     println!();
 
+    println!("Applications playing audio (for --app):");
+    let apps = iot_driver::audio_reactive::list_audio_apps();
+    if apps.is_empty() {
+        println!("  (none)");
+    }
+    for name in apps {
+        println!("  - {name}");
+    }
+    println!();
+
     if let Err(e) = iot_driver::audio_reactive::test_audio_capture() {
         eprintln!("Audio test failed: {e}");
     }
@@ -52,8 +255,9 @@ pub fn audio_test() -> CommandResult {
 }
 
 /// Show real-time audio levels
-pub fn audio_levels(device: Option<String>) -> CommandResult {
-    if let Err(e) = iot_driver::audio_reactive::test_audio_levels(device.as_deref()) {
+pub fn audio_levels(device: Option<String>, app: Option<String>) -> CommandResult {
+    if let Err(e) = iot_driver::audio_reactive::test_audio_levels(device.as_deref(), app.as_deref())
+    {
         eprintln!("Audio levels test failed: {e}");
     }
     Ok(())
@@ -61,10 +265,15 @@ pub fn audio_levels(device: Option<String>) -> CommandResult {
 
 /// Run screen color reactive LED mode
 #[cfg(feature = "screen-capture")]
-pub async fn screen(ctx: &CmdCtx, fps: u32) -> CommandResult {
+pub async fn screen(
+    ctx: &CmdCtx,
+    fps: u32,
+    backend: iot_driver::screen_capture::CaptureBackend,
+) -> CommandResult {
     let fps = fps.clamp(1, 60);
 
     let keyboard = super::open_keyboard(ctx).map_err(|e| format!("Failed to open device: {e}"))?;
+    let _awake = super::keep_awake(&keyboard);
 
     println!(
         "Starting screen color mode on {}...",
@@ -74,9 +283,54 @@ pub async fn screen(ctx: &CmdCtx, fps: u32) -> CommandResult {
 
     let running = setup_interrupt_handler();
 
-    if let Err(e) = iot_driver::screen_capture::run_screen_color_mode(&keyboard, running, fps).await
+    if let Err(e) =
+        iot_driver::screen_capture::run_screen_color_mode(&keyboard, running, fps, backend).await
     {
         eprintln!("Screen color mode error: {e}");
     }
     Ok(())
 }
+
+/// Run per-key screen zone mode (patch LED streaming)
+#[cfg(feature = "screen-capture")]
+pub async fn screen_zones(
+    ctx: &CmdCtx,
+    fps: u32,
+    backend: iot_driver::screen_capture::CaptureBackend,
+    overrides: iot_driver::screen_zones::ZoneOverrides,
+    save: bool,
+    power_budget: u32,
+) -> CommandResult {
+    let fps = fps.clamp(1, 60);
+
+    let keyboard = super::led_stream::open_with_patch_check(ctx)?;
+    let _awake = super::keep_awake(&keyboard);
+
+    let device = keyboard.device_name();
+    let mapping = overrides.apply(iot_driver::settings::Settings::load().zone_mapping(&device));
+    if save {
+        iot_driver::settings::Settings::update(|s| {
+            s.screen_zones.insert(device.to_string(), mapping);
+        });
+        println!("Saved zone mapping for {device}");
+    }
+
+    println!("Starting screen zone mode on {device}...");
+    println!("Press Ctrl+C to stop");
+
+    let running = setup_interrupt_handler();
+
+    if let Err(e) = iot_driver::screen_capture::run_screen_zones_mode(
+        &keyboard,
+        running,
+        fps,
+        backend,
+        mapping,
+        power_budget,
+    )
+    .await
+    {
+        eprintln!("Screen zone mode error: {e}");
+    }
+    Ok(())
+}
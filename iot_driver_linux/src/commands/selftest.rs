@@ -0,0 +1,162 @@
+//! `selftest` — protocol conformance suite against a connected keyboard.
+//!
+//! Queries every read command the device supports, then round-trips the
+//! writable settings (write a neighbouring value, read back, restore). The
+//! report and round-trip logic live in `iot_driver::selftest`.
+
+use super::{with_keyboard, CmdCtx, CommandResult};
+use iot_driver::selftest::{self, Report, Stage};
+use monsgeek_keyboard::{KeyboardError, KeyboardInterface};
+use monsgeek_transport::Transport;
+use std::fmt::Debug;
+use std::io::Write;
+use std::path::Path;
+
+/// Record a query, as a skip if the device doesn't have the command.
+fn query<T: Debug>(report: &mut Report, name: &str, result: Result<T, KeyboardError>) {
+    match result {
+        Err(KeyboardError::NotSupported { feature, .. }) => {
+            report.skip(Stage::Query, name, &feature)
+        }
+        other => report.query(name, other.map(|v| format!("{v:?}"))),
+    }
+}
+
+/// Round-trip a setting, as a skip if the device doesn't have it.
+fn roundtrip<T: Debug>(
+    report: &mut Report,
+    name: &str,
+    read: impl Fn() -> Result<T, KeyboardError>,
+    write: impl Fn(&T) -> Result<(), KeyboardError>,
+    alt: impl Fn(&T) -> T,
+    same: impl Fn(&T, &T) -> bool,
+) {
+    if let Err(KeyboardError::NotSupported { feature, .. }) = read() {
+        report.skip(Stage::Roundtrip, name, &feature);
+        return;
+    }
+    report.roundtrip(name, read, write, alt, same);
+}
+
+fn queries(kb: &KeyboardInterface, report: &mut Report) {
+    let device_id = kb.get_device_id();
+    report.device_id = device_id.as_ref().ok().copied();
+    query(report, "device id", device_id);
+    let version = kb.get_version();
+    report.firmware = version.as_ref().map_or(0, |v| v.raw);
+    report.query("version", version.map(|v| v.format()));
+    query(report, "patch info", kb.get_patch_info());
+    query(report, "profile", kb.get_profile());
+    query(report, "polling rate", kb.get_polling_rate());
+    query(report, "debounce", kb.get_debounce());
+    query(report, "LED", kb.get_led_params());
+    query(report, "side LED", kb.get_side_led_params());
+    query(report, "sleep time", kb.get_sleep_time());
+    query(report, "keyboard options", kb.get_kb_options());
+    query(report, "feature list", kb.get_feature_list());
+    query(report, "precision", kb.get_precision());
+    if kb.is_wireless() {
+        query(report, "battery", kb.get_battery());
+    } else {
+        report.skip(Stage::Query, "battery", "wired connection");
+    }
+    if kb.has_magnetism() {
+        let triggers = kb.get_all_triggers();
+        report.query(
+            "triggers",
+            triggers.map(|t| format!("{} keys", t.key_count)),
+        );
+        query(
+            report,
+            "mod-tap times",
+            kb.get_modtap_times().map(|t| t.len()),
+        );
+    } else {
+        report.skip(Stage::Query, "triggers", "no Hall-effect switches");
+    }
+}
+
+fn roundtrips(kb: &KeyboardInterface, report: &mut Report) {
+    roundtrip(
+        report,
+        "debounce",
+        || kb.get_debounce(),
+        |&ms| kb.set_debounce(ms),
+        |&ms| selftest::alt_debounce(ms),
+        |a, b| a == b,
+    );
+    roundtrip(
+        report,
+        "LED",
+        || kb.get_led_params(),
+        |p| kb.set_led_params(p),
+        selftest::alt_led,
+        selftest::same_led,
+    );
+    roundtrip(
+        report,
+        "sleep time",
+        || kb.get_sleep_time(),
+        |s| kb.set_sleep_time(s),
+        selftest::alt_sleep,
+        |a, b| a == b,
+    );
+    roundtrip(
+        report,
+        "keyboard options",
+        || kb.get_kb_options(),
+        |o| kb.set_kb_options(o),
+        selftest::alt_options,
+        |a, b| a.to_bytes() == b.to_bytes(),
+    );
+}
+
+/// Run the conformance suite and print (or save) the report
+pub fn run(ctx: &CmdCtx, read_only: bool, yes: bool, output: Option<&Path>) -> CommandResult {
+    with_keyboard(ctx, |kb| {
+        let mut writes = !read_only;
+        if writes && !yes {
+            println!("The write checks change debounce, LED brightness, sleep time and");
+            println!("anti-mistouch for a moment, then put back the current values.");
+            print!("Type 'yes' to include them, anything else runs queries only: ");
+            std::io::stdout().flush()?;
+
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            writes = input.trim() == "yes";
+            println!();
+        }
+
+        let info = kb.transport().device_info();
+        let mut report = Report::new(
+            &kb.device_name(),
+            kb.vid(),
+            kb.pid(),
+            &format!("{:?}", info.transport_type),
+        );
+        queries(kb, &mut report);
+        if writes {
+            roundtrips(kb, &mut report);
+        } else {
+            report.skip(Stage::Roundtrip, "all", "queries only");
+        }
+
+        if ctx.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print!("{}", report.render());
+        }
+        if let Some(dir) = output {
+            std::fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+            let path = dir.join(report.file_name());
+            let json = serde_json::to_string_pretty(&report)?;
+            std::fs::write(&path, json).map_err(|e| format!("write {}: {e}", path.display()))?;
+            println!("Wrote {}", path.display());
+        }
+        if report.not_restored.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("not restored: {}", report.not_restored.join(", ")).into())
+        }
+    })
+}
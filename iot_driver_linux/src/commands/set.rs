@@ -1,6 +1,7 @@
 //! Set (write) command handlers.
 
-use super::CommandResult;
+use super::{CmdCtx, CommandResult};
+use iot_driver::keyboard_config::auto_backup;
 use iot_driver::protocol::{cmd, polling_rate};
 use monsgeek_keyboard::{KeyboardInterface, PollingRate, SleepTimeSettings};
 use std::io::{self, Write};
@@ -234,19 +235,41 @@ pub fn set_sleep(
     Ok(())
 }
 
-/// Factory reset keyboard
-pub fn reset(keyboard: &KeyboardInterface) -> CommandResult {
+/// Factory reset keyboard, backing up every profile first and offering to
+/// restore them afterwards
+pub fn reset(ctx: &CmdCtx, keyboard: &KeyboardInterface, no_backup: bool) -> CommandResult {
     print!("This will factory reset the keyboard. Are you sure? (y/N) ");
     io::stdout().flush().unwrap();
     let mut input = String::new();
     io::stdin().read_line(&mut input).unwrap();
-    if input.trim().to_lowercase() == "y" {
-        match keyboard.reset() {
-            Ok(_) => println!("Keyboard reset to factory defaults"),
-            Err(e) => eprintln!("Failed to reset keyboard: {e}"),
-        }
-    } else {
+    if input.trim().to_lowercase() != "y" {
         println!("Reset cancelled");
+        return Ok(());
+    }
+    let backup = if no_backup {
+        None
+    } else {
+        match auto_backup(keyboard, "pre-reset", super::config::reading) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                eprintln!("Failed to back up settings: {e}");
+                eprintln!("Reset cancelled (--no-backup resets anyway)");
+                return Ok(());
+            }
+        }
+    };
+    if let Err(e) = keyboard.reset() {
+        eprintln!("Failed to reset keyboard: {e}");
+        return Ok(());
+    }
+    println!("Keyboard reset to factory defaults");
+    if let Some(path) = backup {
+        // Give the firmware time to rewrite its defaults before reading back
+        std::thread::sleep(std::time::Duration::from_millis(1000));
+        println!("Previous settings saved to {}", path.display());
+        if let Err(e) = super::config::offer_restore(ctx, &path, false) {
+            return Err(format!("restore failed: {e} (backup kept at {})", path.display()).into());
+        }
     }
     Ok(())
 }
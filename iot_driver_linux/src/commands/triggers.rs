@@ -4,8 +4,8 @@ use super::CommandResult;
 use iot_driver::key_action::KeyAction;
 use iot_driver::protocol::hid;
 use monsgeek_keyboard::{
-    DksAction, DksBinding, DksCombo, DksConfig, DksPhase, KeyMode, KeyTriggerSettings,
-    KeyboardInterface, ModeByte,
+    DeviceCapabilities, DksAction, DksBinding, DksCombo, DksConfig, DksPhase, KeyMode,
+    KeyTriggerSettings, KeyboardError, KeyboardInterface, ModeByte,
 };
 use std::collections::{BTreeSet, HashSet};
 use std::io::Write;
@@ -71,7 +71,11 @@ pub fn calibrate(keyboard: &KeyboardInterface) -> CommandResult {
         println!("  (No device profile found — key names unavailable)");
     }
     println!();
-    println!("  To stop: Ctrl+C, any key, mouse click, or encoder knob.");
+    if DeviceCapabilities::lacks(keyboard.capabilities().has_knob) {
+        println!("  To stop: Ctrl+C, any key or mouse click.");
+    } else {
+        println!("  To stop: Ctrl+C, any key, mouse click, or encoder knob.");
+    }
     println!("  Auto-stops after 10s of no progress.");
     println!();
 
@@ -275,44 +279,10 @@ fn setup_input_monitor(vid: u16, pid: u16) -> InputMonitor {
     }
 }
 
-/// Scan sysfs for /dev/input/eventN devices matching VID:PID, open non-blocking.
+/// Open /dev/input/eventN devices matching VID:PID, non-blocking.
 #[cfg(unix)]
 fn find_evdev_devices(vid: u16, pid: u16) -> Vec<std::os::unix::io::RawFd> {
-    use std::os::unix::io::RawFd;
-
-    let vid_hex = format!("{:04x}", vid);
-    let pid_hex = format!("{:04x}", pid);
-    let mut fds: Vec<RawFd> = Vec::new();
-
-    let Ok(entries) = std::fs::read_dir("/sys/class/input") else {
-        return fds;
-    };
-
-    for entry in entries.flatten() {
-        let name = entry.file_name();
-        let name_str = name.to_string_lossy();
-        if !name_str.starts_with("event") {
-            continue;
-        }
-
-        let id_dir = entry.path().join("device/id");
-        let vendor = std::fs::read_to_string(id_dir.join("vendor")).unwrap_or_default();
-        let product = std::fs::read_to_string(id_dir.join("product")).unwrap_or_default();
-
-        if vendor.trim() == vid_hex && product.trim() == pid_hex {
-            let dev_path = format!("/dev/input/{name_str}");
-            let c_path = match std::ffi::CString::new(dev_path.as_str()) {
-                Ok(p) => p,
-                Err(_) => continue,
-            };
-            let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_RDONLY | libc::O_NONBLOCK) };
-            if fd >= 0 {
-                fds.push(fd);
-            }
-        }
-    }
-
-    fds
+    iot_driver::evdev::open_nonblocking(vid, pid)
 }
 
 #[cfg(unix)]
@@ -418,8 +388,33 @@ fn check_input(_monitor: &InputMonitor) -> bool {
 #[cfg(not(unix))]
 fn restore_input(_monitor: &InputMonitor) {}
 
+/// Trigger settings of every key in mm, as printed by `triggers --json` and
+/// served by the REST API.
+pub fn triggers_json(keyboard: &KeyboardInterface) -> Result<serde_json::Value, KeyboardError> {
+    let version = keyboard.get_version().unwrap_or_default();
+    let precision = keyboard.get_precision().unwrap_or_default();
+    let factor = precision.factor() as f32;
+    let t = keyboard.get_all_triggers()?;
+    let mm = |v: &[u16]| v.iter().map(|&x| x as f32 / factor).collect::<Vec<_>>();
+    Ok(serde_json::json!({
+        "firmware": version.format(),
+        "precision": precision.as_str(),
+        "key_count": t.key_count,
+        "actuation_mm": mm(&t.press_travel),
+        "release_mm": mm(&t.lift_travel),
+        "rt_press_mm": mm(&t.rt_press),
+        "rt_release_mm": mm(&t.rt_lift),
+        "bottom_deadzone_mm": mm(&t.bottom_deadzone),
+        "top_deadzone_mm": mm(&t.top_deadzone),
+        "modes": t.key_modes,
+    }))
+}
+
 /// Show current trigger settings
-pub fn triggers(keyboard: &KeyboardInterface) -> CommandResult {
+pub fn triggers(keyboard: &KeyboardInterface, json: bool) -> CommandResult {
+    if json {
+        return super::print_json(&triggers_json(keyboard)?);
+    }
     let version = keyboard.get_version().unwrap_or_default();
     let precision = keyboard.get_precision().unwrap_or_default();
     let factor = precision.factor() as f32;
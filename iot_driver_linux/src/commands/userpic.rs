@@ -34,7 +34,7 @@ fn image_to_userpic(img: &image::DynamicImage, nearest: bool) -> Vec<u8> {
 }
 
 /// Convert userpic column-major data to a 16x6 RGB image.
-fn userpic_to_image(data: &[u8]) -> RgbImage {
+pub(crate) fn userpic_to_image(data: &[u8]) -> RgbImage {
     let mut img = RgbImage::new(COLS as u32, ROWS as u32);
     for col in 0..COLS {
         for row in 0..ROWS {
@@ -3,7 +3,7 @@
 use super::{format_command_response, open_preferred_transport, CmdCtx, CommandResult};
 use monsgeek_transport::{format_device_list, ChecksumType, HidDiscovery, Transport};
 
-/// List supported devices with probe results (replaces raw HID dump)
+/// List supported devices with probe results and the identities `--device` accepts
 pub fn list() -> CommandResult {
     let discovery = HidDiscovery::new();
     let resolve_name = |device_id: Option<u32>, vid: u16, pid: u16| -> Option<String> {
@@ -51,14 +51,96 @@ pub fn raw(cmd_str: &str, ctx: &CmdCtx) -> CommandResult {
     Ok(())
 }
 
+/// Print focus changes and whether an [app_profiles] rule matches
+pub fn watch_focus() -> CommandResult {
+    let config = iot_driver::settings::Settings::load().app_profiles;
+    println!("Watching the focused window (Ctrl+C to stop)...");
+    iot_driver::focus::watch(|window| {
+        if window.app_id.is_empty() && window.title.is_empty() {
+            println!("(no window)");
+            return;
+        }
+        let matched = match config.rule_for(&window) {
+            Some(_) => "  [matches a rule]",
+            None => "",
+        };
+        println!("{:<28} {}{matched}", window.app_id, window.title);
+    })?;
+    Ok(())
+}
+
 /// Run the TUI
 pub async fn tui(device_selector: Option<String>) -> CommandResult {
     iot_driver::tui::run(device_selector).await?;
     Ok(())
 }
 
-/// Launch the joystick mapper
-pub fn joystick(config: Option<std::path::PathBuf>, headless: bool) -> CommandResult {
+/// Run the system tray applet
+#[cfg(feature = "dbus")]
+pub async fn tray() -> CommandResult {
+    iot_driver::tray::run().await
+}
+
+/// Launch the joystick mapper, forwarding focus changes to it so it can
+/// switch profiles per application; with `profile`, switch the running one.
+pub fn joystick(
+    config: Option<std::path::PathBuf>,
+    headless: bool,
+    profile: Option<String>,
+    preset: Option<String>,
+    suppress_keys: bool,
+) -> CommandResult {
+    use monsgeek_joystick::config::JoystickConfig;
+    use monsgeek_joystick::control::{self, ControlCommand};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    if let Some(name) = profile {
+        let command = if name == "auto" {
+            ControlCommand::Auto
+        } else {
+            ControlCommand::Profile(name)
+        };
+        control::send(&command)?;
+        return Ok(());
+    }
+
+    std::thread::spawn(|| {
+        let result = iot_driver::focus::watch(|window| {
+            // Not listening yet (or any more): the next change is sent again
+            let _ = control::send(&ControlCommand::Focus {
+                app_id: window.app_id,
+                title: window.title,
+            });
+        });
+        if let Err(e) = result {
+            tracing::debug!("joystick: no per-application profiles: {e}");
+        }
+    });
+
+    // The keys the mapper binds, as it will load them
+    let config_path = config.clone().unwrap_or_else(JoystickConfig::default_path);
+    let load_config = {
+        let preset = preset.clone();
+        move || match &preset {
+            Some(name) => JoystickConfig::preset(name),
+            None => JoystickConfig::load(&config_path).ok(),
+        }
+    };
+    let suppressing = suppress_keys || load_config().is_some_and(|c| c.suppress_keys);
+    let stop = Arc::new(AtomicBool::new(false));
+    let suppressor = suppressing.then(|| {
+        let stop = Arc::clone(&stop);
+        std::thread::spawn(move || {
+            let bound_keys = || {
+                load_config()
+                    .map(|c| c.bound_key_names())
+                    .unwrap_or_default()
+            };
+            iot_driver::key_suppress::run(bound_keys, &stop);
+        })
+    });
+
     let mut cmd = std::process::Command::new("monsgeek-joystick");
     if let Some(config_path) = config {
         cmd.arg("--config").arg(config_path);
@@ -66,7 +148,14 @@ pub fn joystick(config: Option<std::path::PathBuf>, headless: bool) -> CommandRe
     if headless {
         cmd.arg("--headless");
     }
+    if let Some(name) = preset {
+        cmd.arg("--preset").arg(name);
+    }
     let status = cmd.status();
+    if let Some(suppressor) = suppressor {
+        stop.store(true, Ordering::Relaxed);
+        let _ = suppressor.join();
+    }
     match status {
         Ok(s) if s.success() => {}
         Ok(s) => {
@@ -0,0 +1,483 @@
+//! D-Bus interface for desktop integration.
+//!
+//! Bus name: `org.monsgeek.Keyboard1`
+//! Object path: `/org/monsgeek/Keyboard1`
+//!
+//! Served on the session bus by `iot_driver daemon`, so shell extensions and
+//! scripts can read and change the common settings with `busctl`/`gdbus`
+//! instead of gRPC stubs. State the keyboard reports on its own (Fn-key
+//! profile/LED changes, battery updates) is announced through
+//! `PropertiesChanged`, and every vendor event is also sent as an `Event`
+//! signal. `StartTimer`/`StopTimer` run the pomodoro timer lighting.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use monsgeek_keyboard::{KeyboardError, KeyboardInterface, LedMode};
+use monsgeek_transport::VendorEvent;
+use tokio::sync::broadcast::error::RecvError;
+use zbus::object_server::{InterfaceRef, SignalEmitter};
+use zbus::{fdo, interface};
+
+use crate::cmd;
+use crate::pomodoro::{Phase, TimerConfig};
+
+pub const BUS_NAME: &str = "org.monsgeek.Keyboard1";
+pub const OBJECT_PATH: &str = "/org/monsgeek/Keyboard1";
+
+/// How long to wait before retrying when no keyboard is connected.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Highest LED brightness level (same 0-4 scale as `set-led`).
+const MAX_BRIGHTNESS: u8 = 4;
+
+/// LED power budget for the pomodoro timer (mA).
+const TIMER_POWER_BUDGET: u32 = 400;
+
+/// A named actuation / Rapid Trigger setup applied to every key.
+#[derive(Debug, Clone, Copy)]
+pub struct TriggerPreset {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Actuation (and release) point in mm.
+    pub actuation_mm: f32,
+    /// Rapid Trigger sensitivity in mm, `None` to turn Rapid Trigger off.
+    pub rapid_trigger_mm: Option<f32>,
+}
+
+pub const TRIGGER_PRESETS: &[TriggerPreset] = &[
+    TriggerPreset {
+        name: "typing",
+        description: "2.0mm actuation, Rapid Trigger off",
+        actuation_mm: 2.0,
+        rapid_trigger_mm: None,
+    },
+    TriggerPreset {
+        name: "balanced",
+        description: "1.5mm actuation, Rapid Trigger off",
+        actuation_mm: 1.5,
+        rapid_trigger_mm: None,
+    },
+    TriggerPreset {
+        name: "gaming",
+        description: "1.0mm actuation, 0.3mm Rapid Trigger",
+        actuation_mm: 1.0,
+        rapid_trigger_mm: Some(0.3),
+    },
+    TriggerPreset {
+        name: "competitive",
+        description: "0.5mm actuation, 0.1mm Rapid Trigger",
+        actuation_mm: 0.5,
+        rapid_trigger_mm: Some(0.1),
+    },
+];
+
+/// Look up a preset by name (case-insensitive).
+pub fn trigger_preset(name: &str) -> Option<&'static TriggerPreset> {
+    TRIGGER_PRESETS
+        .iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+impl TriggerPreset {
+    /// Write this preset to every key.
+    pub fn apply(&self, kb: &KeyboardInterface) -> Result<(), KeyboardError> {
+        let factor = kb.get_precision().unwrap_or_default().factor() as f32;
+        let travel = (self.actuation_mm * factor) as u16;
+        kb.set_actuation_all_u16(travel)?;
+        kb.set_release_all_u16(travel)?;
+        match self.rapid_trigger_mm {
+            Some(mm) => {
+                let sensitivity = (mm * factor) as u16;
+                kb.set_rapid_trigger_all(true)?;
+                kb.set_rt_press_all_u16(sensitivity)?;
+                kb.set_rt_lift_all_u16(sensitivity)
+            }
+            None => kb.set_rapid_trigger_all(false),
+        }
+    }
+}
+
+/// Name and value of the `Event` signal for a vendor event. Key depth, mouse
+/// and raw reports are not forwarded.
+pub fn event_signal(event: &VendorEvent) -> Option<(&'static str, u8)> {
+    Some(match *event {
+        VendorEvent::Wake => ("wake", 0),
+        VendorEvent::Sleep => ("sleep", 0),
+        VendorEvent::DeepSleep => ("deep-sleep", 0),
+        VendorEvent::ProfileChange { profile } => ("profile", profile),
+        VendorEvent::LedEffectMode { effect_id } => ("led-mode", effect_id),
+        VendorEvent::LedEffectSpeed { speed } => ("led-speed", speed),
+        VendorEvent::BrightnessLevel { level } => ("brightness", level),
+        VendorEvent::LedColor { color } => ("led-color", color),
+        VendorEvent::WinLockToggle { locked } => ("win-lock", locked as u8),
+        VendorEvent::WasdSwapToggle { swapped } => ("wasd-swap", swapped as u8),
+        VendorEvent::BacklightToggle => ("backlight-toggle", 0),
+        VendorEvent::FnLayerToggle { layer } => ("fn-layer", layer),
+        VendorEvent::DialModeToggle => ("dial-mode", 0),
+        VendorEvent::BatteryStatus { level, .. } => ("battery", level),
+        _ => return None,
+    })
+}
+
+/// Opens the keyboard the service talks to.
+pub type KeyboardOpener = Box<dyn Fn() -> Result<KeyboardInterface, String> + Send + Sync>;
+
+/// The keyboard handle, opened on first use and dropped after a failed
+/// command so an unplugged board is reopened on the next call.
+pub struct KeyboardSlot {
+    open: KeyboardOpener,
+    kb: Mutex<Option<Arc<KeyboardInterface>>>,
+}
+
+impl KeyboardSlot {
+    pub fn new(open: KeyboardOpener) -> Self {
+        Self {
+            open,
+            kb: Mutex::new(None),
+        }
+    }
+
+    /// The open keyboard, opening it if needed.
+    pub fn get(&self) -> Option<Arc<KeyboardInterface>> {
+        let mut kb = self.kb.lock().unwrap();
+        if kb.is_none() {
+            match (self.open)() {
+                Ok(opened) => *kb = Some(Arc::new(opened)),
+                Err(e) => tracing::debug!("D-Bus: no keyboard: {e}"),
+            }
+        }
+        kb.clone()
+    }
+
+    fn reset(&self) {
+        self.kb.lock().unwrap().take();
+    }
+
+    /// Run a command on the keyboard, mapping failures to D-Bus errors.
+    fn with<T>(
+        &self,
+        f: impl FnOnce(&KeyboardInterface) -> Result<T, KeyboardError>,
+    ) -> fdo::Result<T> {
+        let kb = self
+            .get()
+            .ok_or_else(|| fdo::Error::Failed("no keyboard connected".into()))?;
+        f(&kb).map_err(|e| match e {
+            KeyboardError::InvalidParameter(msg) => fdo::Error::InvalidArgs(msg),
+            e => {
+                self.reset();
+                fdo::Error::Failed(e.to_string())
+            }
+        })
+    }
+}
+
+/// A pomodoro timer streaming on its own thread.
+struct RunningTimer {
+    config: TimerConfig,
+    started: Instant,
+    running: Arc<AtomicBool>,
+}
+
+/// D-Bus interface implementation.
+pub struct KeyboardService {
+    slot: Arc<KeyboardSlot>,
+    /// Last charge state from a battery event; the query has no charge flag.
+    charging: AtomicBool,
+    timer: Mutex<Option<RunningTimer>>,
+}
+
+impl KeyboardService {
+    pub fn new(slot: Arc<KeyboardSlot>) -> Self {
+        Self {
+            slot,
+            charging: AtomicBool::new(false),
+            timer: Mutex::new(None),
+        }
+    }
+
+    fn stop_timer_thread(&self) {
+        if let Some(timer) = self.timer.lock().unwrap().take() {
+            timer.running.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+#[interface(name = "org.monsgeek.Keyboard1")]
+impl KeyboardService {
+    /// Whether a keyboard is connected.
+    #[zbus(property)]
+    fn connected(&self) -> bool {
+        self.slot.get().is_some()
+    }
+
+    /// Device name, empty when disconnected.
+    #[zbus(property)]
+    fn name(&self) -> String {
+        self.slot
+            .get()
+            .map(|kb| kb.device_name())
+            .unwrap_or_default()
+    }
+
+    /// Battery level in percent (100 on wired connections).
+    #[zbus(property)]
+    fn battery_level(&self) -> fdo::Result<u8> {
+        self.slot.with(|kb| kb.get_battery()).map(|b| b.level)
+    }
+
+    #[zbus(property)]
+    fn charging(&self) -> bool {
+        self.charging.load(Ordering::Relaxed)
+    }
+
+    /// Active profile (0-3).
+    #[zbus(property)]
+    fn profile(&self) -> fdo::Result<u8> {
+        self.slot.with(|kb| kb.get_profile())
+    }
+
+    #[zbus(property)]
+    fn set_profile(&self, profile: u8) -> zbus::Result<()> {
+        Ok(self.slot.with(|kb| kb.set_profile(profile))?)
+    }
+
+    /// LED effect mode number; see `ListLedModes`.
+    #[zbus(property)]
+    fn led_mode(&self) -> fdo::Result<u8> {
+        self.slot
+            .with(|kb| kb.get_led_params())
+            .map(|p| p.mode as u8)
+    }
+
+    #[zbus(property)]
+    fn set_led_mode(&self, mode: u8) -> zbus::Result<()> {
+        let mode = LedMode::from_u8(mode)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("unknown LED mode {mode}")))?;
+        Ok(self.slot.with(|kb| kb.set_led_mode(mode))?)
+    }
+
+    /// LED brightness level (0-4).
+    #[zbus(property)]
+    fn led_brightness(&self) -> fdo::Result<u8> {
+        self.slot
+            .with(|kb| kb.get_led_params())
+            .map(|p| p.brightness)
+    }
+
+    #[zbus(property)]
+    fn set_led_brightness(&self, level: u8) -> zbus::Result<()> {
+        if level > MAX_BRIGHTNESS {
+            return Err(
+                fdo::Error::InvalidArgs(format!("brightness must be 0-{MAX_BRIGHTNESS}")).into(),
+            );
+        }
+        Ok(self.slot.with(|kb| {
+            let mut params = kb.get_led_params()?;
+            params.brightness = level;
+            kb.set_led_params(&params)
+        })?)
+    }
+
+    /// LED effect modes as (number, name) pairs.
+    fn list_led_modes(&self) -> Vec<(u8, String)> {
+        (0..=cmd::LED_MODE_MAX)
+            .filter(|&m| LedMode::from_u8(m).is_some())
+            .map(|m| (m, cmd::led_mode_name(m).to_string()))
+            .collect()
+    }
+
+    /// Trigger presets as (name, description) pairs.
+    fn list_trigger_presets(&self) -> Vec<(String, String)> {
+        TRIGGER_PRESETS
+            .iter()
+            .map(|p| (p.name.to_string(), p.description.to_string()))
+            .collect()
+    }
+
+    /// Apply a trigger preset to every key.
+    fn apply_trigger_preset(&self, name: &str) -> fdo::Result<()> {
+        let preset = trigger_preset(name)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("unknown trigger preset '{name}'")))?;
+        self.slot.with(|kb| preset.apply(kb))
+    }
+
+    /// Start a pomodoro timer on the LEDs (needs the LED streaming patch),
+    /// replacing a running one. `cycles` 0 runs until `StopTimer`.
+    fn start_timer(&self, work_minutes: u32, break_minutes: u32, cycles: u32) -> fdo::Result<()> {
+        if work_minutes == 0 {
+            return Err(fdo::Error::InvalidArgs(
+                "work_minutes must be at least 1".into(),
+            ));
+        }
+        let kb = self
+            .slot
+            .get()
+            .ok_or_else(|| fdo::Error::Failed("no keyboard connected".into()))?;
+        self.stop_timer_thread();
+
+        let config = TimerConfig {
+            work: Duration::from_secs(work_minutes as u64 * 60),
+            rest: Duration::from_secs(break_minutes as u64 * 60),
+            cycles,
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let running = Arc::new(AtomicBool::new(true));
+        let (thread_config, thread_running) = (config.clone(), Arc::clone(&running));
+        std::thread::spawn(move || {
+            let result = crate::pomodoro::run_timer(
+                &kb,
+                &thread_config,
+                started,
+                TIMER_POWER_BUDGET,
+                thread_running,
+                |_| {},
+            );
+            if let Err(e) = result {
+                tracing::warn!("D-Bus: timer: {e}");
+            }
+        });
+        *self.timer.lock().unwrap() = Some(RunningTimer {
+            config,
+            started,
+            running,
+        });
+        Ok(())
+    }
+
+    /// Stop the pomodoro timer and give the LEDs back to the firmware effect.
+    fn stop_timer(&self) {
+        self.stop_timer_thread();
+    }
+
+    /// Timer state as (phase, seconds left in it, work interval number).
+    /// Phase is "idle", "work", "break" or "done".
+    fn timer_status(&self) -> (String, u32, u32) {
+        let timer = self.timer.lock().unwrap();
+        match timer.as_ref().filter(|t| t.running.load(Ordering::SeqCst)) {
+            Some(t) => {
+                let status = t.config.status_at(t.started.elapsed());
+                let phase = match status.phase {
+                    Phase::Done if !status.flashing() => "idle",
+                    phase => phase.name(),
+                };
+                (
+                    phase.to_string(),
+                    status.remaining().as_secs() as u32,
+                    status.cycle,
+                )
+            }
+            None => ("idle".to_string(), 0, 0),
+        }
+    }
+
+    /// A vendor event from the keyboard, see [`event_signal`] for the names.
+    #[zbus(signal)]
+    async fn event(emitter: &SignalEmitter<'_>, kind: &str, value: u8) -> zbus::Result<()>;
+}
+
+/// Announce the state an event changed and forward it as an `Event` signal.
+async fn forward_event(iface: &InterfaceRef<KeyboardService>, event: &VendorEvent) {
+    let Some((kind, value)) = event_signal(event) else {
+        return;
+    };
+    let emitter = iface.signal_emitter();
+    let service = iface.get().await;
+    let result = match event {
+        VendorEvent::ProfileChange { .. } => service.profile_changed(emitter).await,
+        VendorEvent::LedEffectMode { .. } => service.led_mode_changed(emitter).await,
+        VendorEvent::BrightnessLevel { .. } => service.led_brightness_changed(emitter).await,
+        VendorEvent::BatteryStatus { charging, .. } => {
+            service.charging.store(*charging, Ordering::Relaxed);
+            service.charging_changed(emitter).await.ok();
+            service.battery_level_changed(emitter).await
+        }
+        _ => Ok(()),
+    };
+    if let Err(e) = result.and(KeyboardService::event(emitter, kind, value).await) {
+        tracing::warn!("D-Bus: signal {kind}: {e}");
+    }
+}
+
+/// Forward keyboard events as signals, reconnecting when the board goes away.
+async fn forward_events(slot: Arc<KeyboardSlot>, iface: InterfaceRef<KeyboardService>) {
+    loop {
+        if let Some(mut rx) = slot.get().and_then(|kb| kb.subscribe_events()) {
+            iface
+                .get()
+                .await
+                .connected_changed(iface.signal_emitter())
+                .await
+                .ok();
+            loop {
+                match rx.recv().await {
+                    Ok(ev) => forward_event(&iface, &ev.event).await,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+            tracing::info!("D-Bus: keyboard event stream closed");
+            slot.reset();
+            iface
+                .get()
+                .await
+                .connected_changed(iface.signal_emitter())
+                .await
+                .ok();
+        }
+        tokio::time::sleep(RECONNECT_INTERVAL).await;
+    }
+}
+
+/// Serve `org.monsgeek.Keyboard1` on the session bus. The returned connection
+/// keeps the service alive; events are forwarded until it is dropped.
+pub async fn serve(open: KeyboardOpener) -> zbus::Result<zbus::Connection> {
+    let slot = Arc::new(KeyboardSlot::new(open));
+    let conn = zbus::connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, KeyboardService::new(Arc::clone(&slot)))?
+        .build()
+        .await?;
+    let iface = conn
+        .object_server()
+        .interface::<_, KeyboardService>(OBJECT_PATH)
+        .await?;
+    tokio::spawn(forward_events(slot, iface));
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_by_name() {
+        assert_eq!(
+            trigger_preset("Gaming").unwrap().rapid_trigger_mm,
+            Some(0.3)
+        );
+        assert!(trigger_preset("typing").unwrap().rapid_trigger_mm.is_none());
+        assert!(trigger_preset("nope").is_none());
+    }
+
+    #[test]
+    fn forwards_settings_events_only() {
+        assert_eq!(
+            event_signal(&VendorEvent::ProfileChange { profile: 2 }),
+            Some(("profile", 2))
+        );
+        assert_eq!(
+            event_signal(&VendorEvent::WinLockToggle { locked: true }),
+            Some(("win-lock", 1))
+        );
+        assert_eq!(
+            event_signal(&VendorEvent::KeyDepth {
+                key_index: 1,
+                depth_raw: 100
+            }),
+            None
+        );
+    }
+}
@@ -2,9 +2,10 @@
 // Supports loading from embedded JSON or external file
 
 use crate::profile::types::FnSysLayer;
+use monsgeek_keyboard::DeviceCapabilities;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tracing::{info, warn};
 
 /// Travel range configuration from JSON
@@ -69,6 +70,12 @@ pub struct JsonDeviceDefinition {
     pub has_light_layout: Option<bool>,
     #[serde(default)]
     pub has_side_light: Option<bool>,
+    /// True if the board has a built-in screen
+    #[serde(default)]
+    pub has_screen: Option<bool>,
+    /// True if the board has a 2.4 GHz or Bluetooth radio
+    #[serde(default)]
+    pub wireless: Option<bool>,
     #[serde(default)]
     pub hot_swap: Option<bool>,
     #[serde(default)]
@@ -80,12 +87,43 @@ pub struct JsonDeviceDefinition {
     /// Used for LED effects and depth report key identification
     #[serde(default)]
     pub led_matrix: Option<Vec<u8>>,
+    /// Per LED grid row, the first matrix column that sits one LED column
+    /// further right. Lets LED effects place keys on boards whose LEDs don't
+    /// follow the key matrix one to one.
+    #[serde(default)]
+    pub led_gap_columns: Option<Vec<u8>>,
     /// Chip family (e.g., "RY5088", "YC3123")
     #[serde(default)]
     pub chip_family: Option<String>,
 }
 
 impl JsonDeviceDefinition {
+    /// Hardware flags for `KeyboardInterface::set_capabilities`.
+    ///
+    /// Vendor entries only ever set `hasSideLight` and `hasLightLayout` to
+    /// true, so a full keyboard entry (one with a key layout) that leaves one
+    /// out lacks that light. Screen and wireless are only known when a
+    /// definition says so; the knob comes from the device profile (see
+    /// [`devices::capabilities_with_id`](crate::devices::capabilities_with_id)).
+    pub fn capabilities(&self) -> DeviceCapabilities {
+        let magnetism_known = self.magnetism.is_some() || self.no_magnetic_switch.is_some();
+        let full_entry_default = self.key_layout_name.is_some().then_some(false);
+        DeviceCapabilities {
+            has_side_led: self.has_side_light.or(full_entry_default),
+            has_key_leds: self.has_light_layout.or(full_entry_default),
+            has_knob: None,
+            has_screen: self.has_screen,
+            has_magnetism: magnetism_known.then(|| self.has_magnetism()),
+            max_polling_hz: self.report_rate,
+            wireless: self.wireless,
+        }
+    }
+
+    /// Whether this entry is one of the vendor's mice rather than a keyboard
+    pub fn is_mouse(&self) -> bool {
+        self.device_type == "mouse"
+    }
+
     /// Check if this device has magnetism (Hall effect switches)
     /// Returns true if magnetism is explicitly true, or if no_magnetic_switch is explicitly false
     pub fn has_magnetism(&self) -> bool {
@@ -163,7 +201,7 @@ impl JsonDeviceDefinition {
         if over_bluetooth {
             return PollingRateSupport::Unsupported;
         }
-        if self.device_type == "mouse" {
+        if self.is_mouse() {
             return PollingRateSupport::Always;
         }
         // A keyboard with no known maximum, or one capped at 1 kHz, has no control.
@@ -388,6 +426,24 @@ const DEFAULT_DEVICE_DB_PATHS: &[&str] = &[
     "../data/devices.json", // When running from iot_driver_linux/
 ];
 
+/// User device definitions (`~/.config/monsgeek/devices/*.json`), merged over
+/// the built-in database so unsupported boards can be added without a release.
+pub fn user_devices_dir() -> PathBuf {
+    crate::effect::config_dir().join("devices")
+}
+
+/// `*.json` files in `dir`, sorted by name so later files override earlier ones.
+pub fn user_device_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<_> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    files
+}
+
 /// Default paths to search for device_matrices.json
 const DEFAULT_MATRIX_DB_PATHS: &[&str] = &[
     "/usr/local/share/akko/device_matrices.json",
@@ -459,6 +515,66 @@ impl DeviceDatabase {
         Ok(db)
     }
 
+    /// The built-in database with the user's definitions from
+    /// [`user_devices_dir`] merged over it. Either part may be missing, but
+    /// not both.
+    pub fn load_with_user_dir() -> Result<Self, String> {
+        let (mut db, builtin_err) = match Self::load_default() {
+            Ok(db) => (db, None),
+            Err(e) => (Self::new(), Some(e)),
+        };
+        let dir = user_devices_dir();
+        let added = db.load_user_dir(&dir);
+        if added > 0 {
+            info!(
+                "Loaded {} user device definitions from {}",
+                added,
+                dir.display()
+            );
+        }
+        match builtin_err {
+            Some(e) if db.is_empty() => Err(e),
+            _ => Ok(db),
+        }
+    }
+
+    /// Merge every `*.json` file in `dir` over the loaded devices; returns how
+    /// many definitions were read. Files use the same format as `devices.json`
+    /// (a bare array is fine). A definition with the same ID and VID/PID as an
+    /// existing one replaces it. Unreadable files are logged and skipped.
+    pub fn load_user_dir(&mut self, dir: &Path) -> usize {
+        let mut added = 0;
+        for path in user_device_files(dir) {
+            let devices = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|json| Self::load_from_json(&json))
+                .map(|db| db.devices);
+            match devices {
+                Ok(devices) => {
+                    added += devices.len();
+                    self.merge_devices(devices);
+                }
+                Err(e) => warn!("Skipping device definitions in {}: {}", path.display(), e),
+            }
+        }
+        added
+    }
+
+    /// Add `devices`, replacing existing entries with the same ID and VID/PID.
+    fn merge_devices(&mut self, devices: Vec<JsonDeviceDefinition>) {
+        let key = |d: &JsonDeviceDefinition| (d.id, d.vid, d.pid);
+        let mut merged = std::mem::take(&mut self.devices);
+        merged.retain(|old| !devices.iter().any(|new| key(new) == key(old)));
+        merged.extend(devices);
+
+        self.devices_by_id.clear();
+        self.devices_by_vid_pid.clear();
+        self.devices_by_company.clear();
+        for device in merged {
+            self.add_device(device);
+        }
+    }
+
     /// Load device matrices from a JSON file
     pub fn load_matrices_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, String> {
         let content = std::fs::read_to_string(path.as_ref())
@@ -548,10 +664,22 @@ impl DeviceDatabase {
     pub fn find_by_id(&self, id: i32) -> Option<&JsonDeviceDefinition> {
         match self.devices_by_id.get(&id)?.as_slice() {
             [only] => self.devices.get(*only),
-            _ => None,
+            claimants => self.single_product(claimants),
         }
     }
 
+    /// The product behind `slots` when every entry is the same firmware on a
+    /// different USB PID (the Akko MOD007S V3-HE is listed under both 0x5030
+    /// and 0x5029), so the ID isn't really shared.
+    fn single_product(&self, slots: &[usize]) -> Option<&JsonDeviceDefinition> {
+        let first = self.devices.get(*slots.first()?)?;
+        slots
+            .iter()
+            .filter_map(|&s| self.devices.get(s))
+            .all(|d| d.name == first.name)
+            .then_some(first)
+    }
+
     /// Find device by the ID it reports, disambiguated by the USB IDs we reached it through.
     ///
     /// The device ID comes from the keyboard itself, so it leads; `vid`/`pid` identify the
@@ -564,7 +692,8 @@ impl DeviceDatabase {
             claimants => claimants
                 .iter()
                 .filter_map(|&s| self.devices.get(s))
-                .find(|d| d.vid == vid && d.pid == pid),
+                .find(|d| d.vid == vid && d.pid == pid)
+                .or_else(|| self.single_product(claimants)),
         }
     }
 
@@ -656,7 +785,17 @@ impl DeviceDatabase {
     pub fn get_matrix(&self, vid: u16, pid: u16, device_id: i32) -> Option<&JsonDeviceMatrix> {
         match self.matrices.get(&device_id)?.as_slice() {
             [only] => Some(only),
-            claimants => claimants.iter().find(|m| m.vid == vid && m.pid == pid),
+            claimants => claimants
+                .iter()
+                .find(|m| m.vid == vid && m.pid == pid)
+                .or_else(|| {
+                    // One product under several PIDs, as in `find_by_id_and_usb`
+                    let first = claimants.first()?;
+                    claimants
+                        .iter()
+                        .all(|m| m.name == first.name)
+                        .then_some(first)
+                }),
         }
     }
 
@@ -861,6 +1000,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn capabilities_leave_unknown_hardware_unknown() {
+        // Bare entry: nothing known, so nothing is gated
+        let bare = keyboard_with("MonsGeek", 1, None).capabilities();
+        assert_eq!(bare, DeviceCapabilities::default());
+
+        let json = r#"[{"id": 2, "vid": 12625, "pid": 20528, "name": "kb", "displayName": "KB",
+                        "type": "keyboard", "keyLayoutName": "Common75_Magnetism",
+                        "magnetism": true, "reportRate": 8000, "hasLightLayout": true}]"#;
+        let db = DeviceDatabase::load_from_json(json).unwrap();
+        let caps = db.find_by_id(2).unwrap().capabilities();
+        // A full entry without hasSideLight has no side light
+        assert_eq!(caps.has_side_led, Some(false));
+        assert_eq!(caps.has_magnetism, Some(true));
+        assert_eq!(caps.has_key_leds, Some(true));
+        assert_eq!(caps.has_knob, None);
+        assert_eq!(caps.has_screen, None);
+        assert_eq!(caps.max_polling_hz, Some(8000));
+        assert!(DeviceCapabilities::lacks(caps.has_side_led));
+        assert!(!DeviceCapabilities::lacks(caps.wireless));
+    }
+
     #[test]
     fn device_lookup_keeps_every_claimant_of_a_shared_id() {
         // Same shape as the real collision: one ID, two unrelated keyboards.
@@ -928,6 +1089,36 @@ mod tests {
         assert_eq!(db.device_key_index(12625, 16400, 790, "b"), Some(1));
     }
 
+    #[test]
+    fn one_product_under_two_pids_resolves_over_a_dongle() {
+        // The MOD007S V3-HE is listed once per USB PID with the same firmware name
+        let mut db = DeviceDatabase::load_from_json(
+            r#"[
+                {"id": 2683, "vid": 12625, "pid": 20528, "name": "ry5088_mod007sv3he_dm_8k",
+                 "displayName": "MOD007S V3-HE", "type": "keyboard", "company": "akko"},
+                {"id": 2683, "vid": 12625, "pid": 20521, "name": "ry5088_mod007sv3he_dm_8k",
+                 "displayName": "MOD007S V3-HE", "type": "keyboard", "company": "akko"}
+            ]"#,
+        )
+        .unwrap();
+        db.load_matrices_from_json(
+            r#"{"version": 3, "devices": {
+                "12625:20528:2683": {"name": "ry5088_mod007sv3he_dm_8k", "displayName": "MOD007S V3-HE",
+                    "vid": 12625, "pid": 20528, "keyCount": 1, "matchMethod": "exactName",
+                    "matrix": [41], "keyNames": ["Esc"]},
+                "12625:20521:2683": {"name": "ry5088_mod007sv3he_dm_8k", "displayName": "MOD007S V3-HE",
+                    "vid": 12625, "pid": 20521, "keyCount": 1, "matchMethod": "exactName",
+                    "matrix": [41], "keyNames": ["Esc"]}
+            }}"#,
+        )
+        .unwrap();
+
+        assert_eq!(db.find_by_id(2683).unwrap().display_name, "MOD007S V3-HE");
+        let dongle = db.find_by_id_and_usb(2683, 0x3151, 0x5038).unwrap();
+        assert_eq!(dongle.name, "ry5088_mod007sv3he_dm_8k");
+        assert_eq!(db.device_key_name(0x3151, 0x5038, 2683, 0), Some("Esc"));
+    }
+
     #[test]
     fn test_load_legacy_json() {
         let db = DeviceDatabase::load_from_json(TEST_JSON_LEGACY).unwrap();
@@ -935,6 +1126,29 @@ mod tests {
         assert_eq!(db.version(), 0); // Legacy format has no version
     }
 
+    #[test]
+    fn user_dir_overrides_and_extends_the_database() {
+        let dir = std::env::temp_dir().join(format!("monsgeek-devices-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("a.json"),
+            r#"[{"id": 100, "vid": 1234, "pid": 5678, "name": "akko_k1", "displayName": "K1 (fixed)", "company": "akko"},
+                {"id": 4242, "vid": 12625, "pid": 20600, "name": "mine", "displayName": "My Board"}]"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("b.json"), "not json").unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let mut db = DeviceDatabase::load_from_json(TEST_JSON_LEGACY).unwrap();
+        assert_eq!(db.load_user_dir(&dir), 2);
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(db.len(), 4);
+        assert_eq!(db.find_by_id(100).unwrap().display_name, "K1 (fixed)");
+        assert_eq!(db.find_by_vid_pid(12625, 20600)[0].id, 4242);
+        assert_eq!(db.find_by_company("akko").len(), 1);
+    }
+
     #[test]
     fn test_load_versioned_json() {
         let db = DeviceDatabase::load_from_json(TEST_JSON_VERSIONED).unwrap();
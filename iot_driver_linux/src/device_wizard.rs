@@ -0,0 +1,225 @@
+//! Device definition for an unsupported keyboard (`iot_driver device add`).
+//!
+//! The factory key matrix (GET_KEYMATRIX) gives every matrix position's HID
+//! code, which is both the key count and a first guess at the LED matrix.
+//! The wizard then lights each position in turn and the user confirms or
+//! corrects the key that lit up. This module holds the matrix scan, the
+//! answers and the resulting [`JsonDeviceDefinition`]; talking to the device
+//! and the user lives in the `device` command.
+
+use crate::device_loader::JsonDeviceDefinition;
+use crate::key_action::KeyAction;
+use crate::protocol::hid;
+
+/// What the key matrix holds at one position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    /// No key here
+    Empty,
+    /// A key sending this HID code
+    Key(u8),
+    /// A key without a HID code of its own (Fn, macro, media...)
+    Special,
+}
+
+impl Slot {
+    /// HID code for the LED matrix (0 for no key or a special key).
+    pub fn hid_code(self) -> u8 {
+        match self {
+            Slot::Key(code) => code,
+            Slot::Empty | Slot::Special => 0,
+        }
+    }
+}
+
+fn slot(k: &[u8]) -> Slot {
+    if k == [0, 0, 0, 0] {
+        return Slot::Empty;
+    }
+    match KeyAction::from_config_bytes([k[0], k[1], k[2], k[3]]) {
+        KeyAction::Key(code) => Slot::Key(code),
+        _ => Slot::Special,
+    }
+}
+
+/// Decode GET_KEYMATRIX pages (4 bytes per position) into slots.
+///
+/// Trailing empty positions are dropped, so the length is the matrix size.
+pub fn scan_keymatrix(data: &[u8]) -> Vec<Slot> {
+    let mut slots: Vec<Slot> = data.chunks_exact(4).map(slot).collect();
+    let len = slots
+        .iter()
+        .rposition(|&s| s != Slot::Empty)
+        .map_or(0, |i| i + 1);
+    slots.truncate(len);
+    slots
+}
+
+/// Number of keys in a scan.
+pub fn key_count(slots: &[Slot]) -> usize {
+    slots.iter().filter(|&&s| s != Slot::Empty).count()
+}
+
+/// The user's answer for one lit position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    /// The lit key is the one expected
+    Confirmed,
+    /// The lit key is this one instead
+    Key(u8),
+    /// Nothing lit up: no LED at this position
+    NoLed,
+    /// Stop asking; keep the key matrix for the remaining positions
+    Stop,
+}
+
+/// Parse an answer: empty or `y` confirms, `n` means nothing lit, `q` stops,
+/// anything else is taken as a key name.
+pub fn parse_answer(input: &str) -> Result<Answer, String> {
+    match input.trim() {
+        "" | "y" | "Y" => Ok(Answer::Confirmed),
+        "n" | "N" | "-" => Ok(Answer::NoLed),
+        "q" | "Q" => Ok(Answer::Stop),
+        name => hid::key_code_from_name(name)
+            .map(Answer::Key)
+            .ok_or_else(|| format!("unknown key name '{name}'")),
+    }
+}
+
+/// Apply an answer for `position` to the LED matrix.
+pub fn apply_answer(led_matrix: &mut [u8], position: usize, answer: Answer) {
+    match answer {
+        Answer::Key(code) => led_matrix[position] = code,
+        Answer::NoLed => led_matrix[position] = 0,
+        Answer::Confirmed | Answer::Stop => {}
+    }
+}
+
+/// What the wizard found, before it's written out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DraftDevice {
+    pub device_id: i32,
+    pub vid: u16,
+    pub pid: u16,
+    pub name: String,
+    pub display_name: String,
+    pub company: Option<String>,
+    pub key_count: u8,
+    pub magnetism: bool,
+    /// Position -> HID code (0 for none)
+    pub led_matrix: Vec<u8>,
+}
+
+impl DraftDevice {
+    pub fn to_definition(&self) -> JsonDeviceDefinition {
+        JsonDeviceDefinition {
+            id: self.device_id,
+            vid: self.vid,
+            pid: self.pid,
+            vid_hex: format!("0x{:04x}", self.vid),
+            pid_hex: format!("0x{:04x}", self.pid),
+            name: self.name.clone(),
+            display_name: self.display_name.clone(),
+            company: self.company.clone(),
+            device_type: "keyboard".to_string(),
+            sources: vec!["iot_driver device add".to_string()],
+            key_count: Some(self.key_count),
+            key_layout_name: None,
+            layer: None,
+            fn_sys_layer: None,
+            magnetism: Some(self.magnetism),
+            no_magnetic_switch: None,
+            has_light_layout: Some(self.led_matrix.iter().any(|&c| c != 0)),
+            has_side_light: None,
+            has_screen: None,
+            wireless: None,
+            hot_swap: None,
+            travel_setting: None,
+            report_rate: None,
+            led_matrix: Some(self.led_matrix.clone()),
+            led_gap_columns: None,
+            chip_family: None,
+        }
+    }
+
+    /// `<name>-<device id>.json`, for the user device directory.
+    pub fn file_name(&self) -> String {
+        let slug: String = self
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        format!("{}-{}.json", slug.trim_matches('-'), self.device_id)
+    }
+
+    /// The definition as a one-entry `devices.json` array.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&[self.to_definition()]).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device_loader::DeviceDatabase;
+
+    #[test]
+    fn scan_reads_keys_specials_and_matrix_size() {
+        let data = [
+            [0, 0, 0x29, 0], // Esc
+            [0, 0, 0, 0],    // empty
+            KeyAction::Fn.to_config_bytes(),
+            [0, 0, 0x04, 0], // A
+            [0, 0, 0, 0],
+            [0, 0, 0, 0],
+        ]
+        .concat();
+        let slots = scan_keymatrix(&data);
+        assert_eq!(
+            slots,
+            vec![Slot::Key(0x29), Slot::Empty, Slot::Special, Slot::Key(0x04)]
+        );
+        assert_eq!(key_count(&slots), 3);
+        let leds: Vec<u8> = slots.iter().map(|s| s.hid_code()).collect();
+        assert_eq!(leds, vec![0x29, 0, 0, 0x04]);
+    }
+
+    #[test]
+    fn answers_correct_the_led_matrix() {
+        let mut leds = vec![0x29, 0x04, 0x05];
+        apply_answer(&mut leds, 0, parse_answer("").unwrap());
+        apply_answer(&mut leds, 1, parse_answer("n").unwrap());
+        apply_answer(&mut leds, 2, parse_answer("Esc").unwrap());
+        assert_eq!(leds, vec![0x29, 0, 0x29]);
+        assert_eq!(parse_answer("q"), Ok(Answer::Stop));
+        assert!(parse_answer("NotAKey").is_err());
+    }
+
+    #[test]
+    fn draft_round_trips_through_the_database() {
+        let draft = DraftDevice {
+            device_id: 4242,
+            vid: 0x3151,
+            pid: 0x5099,
+            name: "My Board 75".to_string(),
+            display_name: "My Board 75".to_string(),
+            company: Some("akko".to_string()),
+            key_count: 2,
+            magnetism: true,
+            led_matrix: vec![0x29, 0x04],
+        };
+        assert_eq!(draft.file_name(), "my-board-75-4242.json");
+
+        let db = DeviceDatabase::load_from_json(&draft.to_json()).unwrap();
+        let def = db.find_by_id(4242).unwrap();
+        assert_eq!(def.key_name(1), Some("A"));
+        assert!(def.has_magnetism());
+        assert_eq!(def.key_count, Some(2));
+    }
+}
@@ -4,6 +4,7 @@
 
 use crate::hal;
 use crate::profile::registry::profile_registry;
+use monsgeek_keyboard::DeviceCapabilities;
 
 /// Check if a VID/PID combination is supported (VID-based: any 0x3151 device)
 pub fn is_supported(vid: u16, _pid: u16) -> bool {
@@ -56,6 +57,21 @@ pub fn key_count_with_id(device_id: Option<i32>, vid: u16, pid: u16) -> u8 {
         .unwrap_or(0)
 }
 
+/// Hardware flags for `KeyboardInterface::set_capabilities`, with firmware
+/// device ID for accurate lookup. The database entry supplies them, except
+/// the knob, which a matching device profile knows about.
+pub fn capabilities_with_id(device_id: Option<i32>, vid: u16, pid: u16) -> DeviceCapabilities {
+    let registry = profile_registry();
+    let mut capabilities = device_id
+        .and_then(|id| registry.get_device_info_by_id_and_usb(id, vid, pid))
+        .map(|d| d.capabilities())
+        .unwrap_or_default();
+    if let Some(profile) = registry.find_for_device(device_id, vid, pid) {
+        capabilities.has_knob = Some(profile.has_knob());
+    }
+    capabilities
+}
+
 /// Get device display name
 pub fn get_display_name(vid: u16, pid: u16) -> Option<String> {
     get_display_name_with_id(None, vid, pid)
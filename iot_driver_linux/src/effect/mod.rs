@@ -28,6 +28,7 @@
 //! ```
 
 pub mod preview;
+pub mod state;
 
 use keyframe::functions as ease;
 use serde::{Deserialize, Serialize};
@@ -15,8 +15,7 @@ use crossterm::{
 
 use super::{resolve, EffectDef, ResolvedEffect};
 use crate::led_stream::{apply_power_budget, send_full_frame, DEFAULT_POWER_BUDGET_MA};
-use crate::notify::keymap::{pos_to_matrix_index, COLS, MATRIX_LEN, ROWS};
-use crate::profile::M1_V5_HE_KEY_NAMES;
+use crate::notify::keymap::{led_layout, COLS, MATRIX_LEN, ROWS};
 
 /// Width of each cell in characters.
 const CELL_W: usize = 5;
@@ -160,19 +159,16 @@ fn run_loop(
     Ok(())
 }
 
-/// Build 3-char labels for each matrix position from the key name table.
+/// Build 3-char labels for each matrix position from the selected LED layout.
 pub fn build_labels() -> Vec<String> {
     let mut labels = vec![String::new(); MATRIX_LEN];
 
-    // M1_V5_HE_KEY_NAMES is column-major: index = col * 6 + row
-    for (col_major_idx, &name) in M1_V5_HE_KEY_NAMES.iter().enumerate() {
-        if col_major_idx >= MATRIX_LEN {
-            break;
-        }
+    // Key names are column-major: index = col * 6 + row
+    let layout = led_layout();
+    for (col_major_idx, name) in layout.names.iter().enumerate() {
         let col = col_major_idx / ROWS;
         let row = col_major_idx % ROWS;
-        let matrix_idx = pos_to_matrix_index(row as u8, col as u8);
-        if matrix_idx < MATRIX_LEN {
+        if let Some(matrix_idx) = layout.grid_index(row as u8, col as u8) {
             // Truncate to 3 chars for display
             let label: String = name.chars().take(3).collect();
             labels[matrix_idx] = label;
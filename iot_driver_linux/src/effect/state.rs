@@ -0,0 +1,227 @@
+//! External state for effects.
+//!
+//! Named values set from outside (`iot_driver state set calendar.soon 1
+//! --ttl 15m` from a calendar hook, a weather script on a timer, ...) that
+//! effects read without knowing where they came from. Values live in a JSON
+//! file in the runtime directory, so any process can set them and they are
+//! gone after a reboot; an optional TTL lets stale values expire on their
+//! own when the script that set them stops running.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Where the values are kept: `$XDG_RUNTIME_DIR/monsgeek/state.json`, or
+/// the config directory without a runtime directory.
+pub fn state_path() -> PathBuf {
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime) => PathBuf::from(runtime).join("monsgeek"),
+        None => super::config_dir(),
+    };
+    dir.join("state.json")
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Parse a value: a number, or `true`/`on`/`yes` (1) and
+/// `false`/`off`/`no` (0).
+pub fn parse_value(s: &str) -> Result<f32, String> {
+    match s.trim().to_lowercase().as_str() {
+        "true" | "on" | "yes" => Ok(1.0),
+        "false" | "off" | "no" => Ok(0.0),
+        v => v
+            .parse::<f32>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| format!("invalid value '{s}' (expected a number or true/false)")),
+    }
+}
+
+/// Parse a TTL: seconds, or a number with `s`, `m`, `h` or `d`.
+pub fn parse_ttl(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let secs = match unit {
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        "d" => 86400.0,
+        _ => return Err(format!("invalid TTL unit in '{s}' (use s, m, h or d)")),
+    };
+    num.parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite() && *n > 0.0)
+        .map(|n| Duration::from_secs_f64(n * secs))
+        .ok_or_else(|| format!("invalid TTL '{s}'"))
+}
+
+/// One value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateValue {
+    pub value: f32,
+    /// Seconds since the Unix epoch after which the value is ignored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+}
+
+impl StateValue {
+    pub fn live_at(&self, now: u64) -> bool {
+        self.expires.is_none_or(|at| now < at)
+    }
+}
+
+/// All external values, by lowercase name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExternalState {
+    pub values: BTreeMap<String, StateValue>,
+}
+
+impl ExternalState {
+    /// Read the state file; missing or unreadable means no values.
+    pub fn load() -> Self {
+        let path = state_path();
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("state: parse {}: {e}; ignoring", path.display());
+            Self::default()
+        })
+    }
+
+    /// Write the state file, dropping expired values. Written to a temporary
+    /// file first so readers never see half a file.
+    pub fn save(&mut self) -> Result<(), String> {
+        self.prune(unix_now());
+        let path = state_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("create state dir: {e}"))?;
+        }
+        let content = serde_json::to_string_pretty(self).map_err(|e| format!("serialize: {e}"))?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, content).map_err(|e| format!("write {}: {e}", tmp.display()))?;
+        std::fs::rename(&tmp, &path).map_err(|e| format!("write {}: {e}", path.display()))
+    }
+
+    /// Set `name`, expiring after `ttl` if given.
+    pub fn set(&mut self, name: &str, value: f32, ttl: Option<Duration>) {
+        let expires = ttl.map(|ttl| unix_now() + ttl.as_secs().max(1));
+        self.values
+            .insert(name.trim().to_lowercase(), StateValue { value, expires });
+    }
+
+    /// Remove `name`; returns whether it was set.
+    pub fn clear(&mut self, name: &str) -> bool {
+        self.values.remove(&name.trim().to_lowercase()).is_some()
+    }
+
+    /// Drop values expired at `now` (Unix seconds).
+    pub fn prune(&mut self, now: u64) {
+        self.values.retain(|_, v| v.live_at(now));
+    }
+
+    /// The value of `name` at `now` (Unix seconds), unless unset or expired.
+    pub fn get_at(&self, name: &str, now: u64) -> Option<f32> {
+        self.values
+            .get(&name.trim().to_lowercase())
+            .filter(|v| v.live_at(now))
+            .map(|v| v.value)
+    }
+
+    /// The current value of `name`.
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.get_at(name, unix_now())
+    }
+}
+
+/// A test on one external value, as used in effect settings: true while the
+/// value is set and inside the (optional) bounds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StateCondition {
+    /// Value name (case-insensitive)
+    pub state: String,
+    /// Only while the value is above this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub above: Option<f32>,
+    /// Only while the value is below this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub below: Option<f32>,
+}
+
+impl StateCondition {
+    /// Whether the condition holds for `value`; without bounds any non-zero
+    /// value counts.
+    pub fn matches(&self, value: Option<f32>) -> bool {
+        let Some(v) = value else { return false };
+        if self.above.is_none() && self.below.is_none() {
+            return v != 0.0;
+        }
+        self.above.is_none_or(|a| v > a) && self.below.is_none_or(|b| v < b)
+    }
+
+    pub fn holds(&self, state: &ExternalState, now: u64) -> bool {
+        self.matches(state.get_at(&self.state, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values_and_ttls() {
+        assert_eq!(parse_value("on").unwrap(), 1.0);
+        assert_eq!(parse_value(" 12.5 ").unwrap(), 12.5);
+        assert!(parse_value("NaN").is_err());
+        assert_eq!(parse_ttl("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_ttl("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_ttl("1.5h").unwrap(), Duration::from_secs(5400));
+        assert!(parse_ttl("5w").is_err());
+        assert!(parse_ttl("0").is_err());
+    }
+
+    #[test]
+    fn values_expire_and_names_ignore_case() {
+        let mut state = ExternalState::default();
+        state.values.insert(
+            "rain".into(),
+            StateValue {
+                value: 0.8,
+                expires: Some(100),
+            },
+        );
+        state.set("Calendar.Soon", 1.0, None);
+        assert_eq!(state.get_at("RAIN", 99), Some(0.8));
+        assert_eq!(state.get_at("rain", 100), None);
+        assert_eq!(state.get("calendar.soon"), Some(1.0));
+        state.prune(100);
+        assert!(!state.values.contains_key("rain"));
+        assert!(state.clear("calendar.SOON"));
+        assert!(state.values.is_empty());
+    }
+
+    #[test]
+    fn conditions_default_to_non_zero() {
+        let cond = |above, below| StateCondition {
+            state: "rain".into(),
+            above,
+            below,
+        };
+        assert!(cond(None, None).matches(Some(1.0)));
+        assert!(!cond(None, None).matches(Some(0.0)));
+        assert!(!cond(None, None).matches(None));
+        assert!(cond(Some(0.5), None).matches(Some(0.6)));
+        assert!(!cond(Some(0.5), Some(0.7)).matches(Some(0.8)));
+        assert!(cond(None, Some(10.0)).matches(Some(0.0)));
+    }
+}
@@ -0,0 +1,383 @@
+//! Minimal evdev reader for the keyboard's kernel input nodes.
+//!
+//! Finds `/dev/input/eventN` nodes belonging to a VID:PID via sysfs and reads
+//! raw `input_event` records with kernel timestamps. Timestamps are switched
+//! to `CLOCK_MONOTONIC` so they can be compared against [`monotonic_now`].
+//! Used by `measure-rate`, `latency`, `macro record` and the software
+//! remapping layer.
+//!
+//! Reading evdev nodes normally requires membership in the `input` group.
+
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_MSC: u16 = 0x04;
+pub const SYN_REPORT: u16 = 0x00;
+
+/// EV_KEY value for kernel-generated autorepeat (not a device report).
+pub const KEY_REPEAT: i32 = 2;
+
+/// `_IOW('E', 0xa0, int)` — select the clock used for event timestamps.
+const EVIOCSCLOCKID: libc::c_ulong = 0x4004_45a0;
+/// `_IOW('E', 0x90, int)` — grab the device for exclusive use.
+const EVIOCGRAB: libc::c_ulong = 0x4004_4590;
+/// `_IOC(_IOC_READ, 'E', 0x20 + EV_KEY, 32)` — bitmask of reported keys
+/// (the first 256 codes).
+const EVIOCGBIT_KEY: libc::c_ulong = 0x8020_4521;
+/// `KEY_A`, which only the keyboard interface reports.
+const KEY_A: u16 = 30;
+
+/// One decoded input event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    /// Kernel timestamp (CLOCK_MONOTONIC when the clock switch succeeded).
+    pub time: Duration,
+    pub kind: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl InputEvent {
+    pub fn is_syn_report(&self) -> bool {
+        self.kind == EV_SYN && self.code == SYN_REPORT
+    }
+
+    /// Key press or release coming from the device (autorepeat excluded).
+    pub fn is_key_edge(&self) -> bool {
+        self.kind == EV_KEY && self.value != KEY_REPEAT
+    }
+}
+
+/// Map a Linux `KEY_*` code to its HID keyboard usage.
+///
+/// Inverse of the kernel's hid-input table for the keyboard page; media and
+/// other consumer keys return `None`.
+pub fn keycode_to_hid(code: u16) -> Option<u8> {
+    const LETTERS: [u8; 26] = [
+        0x14, 0x1A, 0x08, 0x15, 0x17, 0x1C, 0x18, 0x0C, 0x12, 0x13, // Q..P (16-25)
+        0x04, 0x16, 0x07, 0x09, 0x0A, 0x0B, 0x0D, 0x0E, 0x0F, // A..L (30-38)
+        0x1D, 0x1B, 0x06, 0x19, 0x05, 0x11, 0x10, // Z..M (44-50)
+    ];
+    Some(match code {
+        1 => 0x29,                                     // Esc
+        2..=11 => 0x1E + (code - 2) as u8,             // 1..0
+        12 => 0x2D,                                    // -
+        13 => 0x2E,                                    // =
+        14 => 0x2A,                                    // Backspace
+        15 => 0x2B,                                    // Tab
+        16..=25 => LETTERS[(code - 16) as usize],      // Q..P
+        26 => 0x2F,                                    // [
+        27 => 0x30,                                    // ]
+        28 => 0x28,                                    // Enter
+        29 => 0xE0,                                    // LCtrl
+        30..=38 => LETTERS[(code - 30 + 10) as usize], // A..L
+        39 => 0x33,                                    // ;
+        40 => 0x34,                                    // '
+        41 => 0x35,                                    // `
+        42 => 0xE1,                                    // LShift
+        43 => 0x31,                                    // \
+        44..=50 => LETTERS[(code - 44 + 19) as usize], // Z..M
+        51 => 0x36,                                    // ,
+        52 => 0x37,                                    // .
+        53 => 0x38,                                    // /
+        54 => 0xE5,                                    // RShift
+        55 => 0x55,                                    // KP *
+        56 => 0xE2,                                    // LAlt
+        57 => 0x2C,                                    // Space
+        58 => 0x39,                                    // CapsLock
+        59..=68 => 0x3A + (code - 59) as u8,           // F1..F10
+        69 => 0x53,                                    // NumLock
+        70 => 0x47,                                    // ScrollLock
+        71 => 0x5F,                                    // KP 7
+        72 => 0x60,                                    // KP 8
+        73 => 0x61,                                    // KP 9
+        74 => 0x56,                                    // KP -
+        75 => 0x5C,                                    // KP 4
+        76 => 0x5D,                                    // KP 5
+        77 => 0x5E,                                    // KP 6
+        78 => 0x57,                                    // KP +
+        79 => 0x59,                                    // KP 1
+        80 => 0x5A,                                    // KP 2
+        81 => 0x5B,                                    // KP 3
+        82 => 0x62,                                    // KP 0
+        83 => 0x63,                                    // KP .
+        86 => 0x64,                                    // 102nd (IntlBackslash)
+        87 => 0x44,                                    // F11
+        88 => 0x45,                                    // F12
+        89 => 0x87,                                    // RO
+        96 => 0x58,                                    // KP Enter
+        97 => 0xE4,                                    // RCtrl
+        98 => 0x54,                                    // KP /
+        99 => 0x46,                                    // SysRq / PrintScreen
+        100 => 0xE6,                                   // RAlt
+        102 => 0x4A,                                   // Home
+        103 => 0x52,                                   // Up
+        104 => 0x4B,                                   // PageUp
+        105 => 0x50,                                   // Left
+        106 => 0x4F,                                   // Right
+        107 => 0x4D,                                   // End
+        108 => 0x51,                                   // Down
+        109 => 0x4E,                                   // PageDown
+        110 => 0x49,                                   // Insert
+        111 => 0x4C,                                   // Delete
+        117 => 0x67,                                   // KP =
+        119 => 0x48,                                   // Pause
+        124 => 0x89,                                   // Yen
+        125 => 0xE3,                                   // LMeta
+        126 => 0xE7,                                   // RMeta
+        127 => 0x65,                                   // Compose / Menu
+        183..=194 => 0x68 + (code - 183) as u8,        // F13..F24
+        _ => return None,
+    })
+}
+
+/// Map a HID keyboard usage to its Linux `KEY_*` code (inverse of
+/// [`keycode_to_hid`]).
+pub fn hid_to_keycode(hid: u8) -> Option<u16> {
+    (1..=255).find(|&code| keycode_to_hid(code) == Some(hid))
+}
+
+/// Current `CLOCK_MONOTONIC` time, comparable with [`InputEvent::time`].
+pub fn monotonic_now() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+    }
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// List `/dev/input/eventN` nodes whose sysfs parent matches VID:PID.
+pub fn find_event_nodes(vid: u16, pid: u16) -> Vec<PathBuf> {
+    let vid_hex = format!("{:04x}", vid);
+    let pid_hex = format!("{:04x}", pid);
+    let mut nodes = Vec::new();
+
+    let Ok(entries) = std::fs::read_dir("/sys/class/input") else {
+        return nodes;
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name_str = name.to_string_lossy();
+        if !name_str.starts_with("event") {
+            continue;
+        }
+
+        let id_dir = entry.path().join("device/id");
+        let vendor = std::fs::read_to_string(id_dir.join("vendor")).unwrap_or_default();
+        let product = std::fs::read_to_string(id_dir.join("product")).unwrap_or_default();
+
+        if vendor.trim() == vid_hex && product.trim() == pid_hex {
+            nodes.push(PathBuf::from(format!("/dev/input/{name_str}")));
+        }
+    }
+
+    nodes.sort();
+    nodes
+}
+
+/// Device name of an event node (`/sys/class/input/eventN/device/name`).
+fn node_name(path: &std::path::Path) -> String {
+    let Some(node) = path.file_name() else {
+        return String::new();
+    };
+    let sys = PathBuf::from("/sys/class/input")
+        .join(node)
+        .join("device/name");
+    std::fs::read_to_string(sys)
+        .map(|n| n.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Event nodes matching VID:PID, skipping devices named `exclude` (our own
+/// uinput device, which shares the keyboard's IDs).
+pub fn find_physical_nodes(vid: u16, pid: u16, exclude: &str) -> Vec<PathBuf> {
+    find_event_nodes(vid, pid)
+        .into_iter()
+        .filter(|path| node_name(path) != exclude)
+        .collect()
+}
+
+/// Whether an open event node reports `KEY_A`.
+fn reports_letter_keys(fd: RawFd) -> bool {
+    let mut bits = [0u8; 32];
+    let n = unsafe { libc::ioctl(fd, EVIOCGBIT_KEY, bits.as_mut_ptr()) };
+    n > 0 && bits[(KEY_A / 8) as usize] & (1 << (KEY_A % 8)) != 0
+}
+
+/// Open every event node matching VID:PID read-only and non-blocking.
+///
+/// Nodes that fail to open (permissions) are skipped. The caller owns the
+/// returned descriptors; prefer [`EventReader`] which closes them on drop.
+pub fn open_nonblocking(vid: u16, pid: u16) -> Vec<RawFd> {
+    find_event_nodes(vid, pid)
+        .into_iter()
+        .filter_map(|path| {
+            let c_path = std::ffi::CString::new(path.to_string_lossy().as_bytes()).ok()?;
+            let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_RDONLY | libc::O_NONBLOCK) };
+            (fd >= 0).then_some(fd)
+        })
+        .collect()
+}
+
+/// Owned set of evdev descriptors for one keyboard.
+pub struct EventReader {
+    fds: Vec<RawFd>,
+}
+
+impl EventReader {
+    /// Open all event nodes for VID:PID with monotonic timestamps.
+    pub fn open(vid: u16, pid: u16) -> Self {
+        let fds = open_nonblocking(vid, pid);
+        for &fd in &fds {
+            let clock: libc::c_int = libc::CLOCK_MONOTONIC;
+            unsafe {
+                libc::ioctl(fd, EVIOCSCLOCKID, &clock);
+            }
+        }
+        Self { fds }
+    }
+
+    /// Open only the nodes for VID:PID that report letter keys (the
+    /// keyboard interface rather than the mouse or media-key ones),
+    /// skipping devices named `exclude`.
+    pub fn open_keyboard(vid: u16, pid: u16, exclude: &str) -> Self {
+        let mut reader = Self { fds: Vec::new() };
+        for path in find_physical_nodes(vid, pid, exclude) {
+            let Ok(c_path) = std::ffi::CString::new(path.to_string_lossy().as_bytes()) else {
+                continue;
+            };
+            let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_RDONLY | libc::O_NONBLOCK) };
+            if fd < 0 {
+                continue;
+            }
+            if !reports_letter_keys(fd) {
+                unsafe {
+                    libc::close(fd);
+                }
+                continue;
+            }
+            let clock: libc::c_int = libc::CLOCK_MONOTONIC;
+            unsafe {
+                libc::ioctl(fd, EVIOCSCLOCKID, &clock);
+            }
+            reader.fds.push(fd);
+        }
+        reader
+    }
+
+    /// Grab the nodes exclusively: their events stop reaching the desktop
+    /// and other readers until the reader is dropped.
+    pub fn grab(&self) -> Result<(), String> {
+        for &fd in &self.fds {
+            let grab: libc::c_int = 1;
+            if unsafe { libc::ioctl(fd, EVIOCGRAB, grab) } < 0 {
+                return Err(format!(
+                    "EVIOCGRAB failed: {}",
+                    std::io::Error::last_os_error()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Grab the nodes nobody else has grabbed (the remap daemon, say) and
+    /// close the others. Fails if none could be grabbed.
+    pub fn grab_available(&mut self) -> Result<(), String> {
+        let mut error = None;
+        self.fds.retain(|&fd| {
+            let grab: libc::c_int = 1;
+            if unsafe { libc::ioctl(fd, EVIOCGRAB, grab) } < 0 {
+                error = Some(std::io::Error::last_os_error());
+                unsafe {
+                    libc::close(fd);
+                }
+                return false;
+            }
+            true
+        });
+        match error {
+            Some(e) if self.fds.is_empty() => Err(format!("EVIOCGRAB failed: {e}")),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fds.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fds.len()
+    }
+
+    /// Wait up to `timeout` for input, then drain every ready descriptor.
+    ///
+    /// Events from all nodes are returned sorted by timestamp.
+    pub fn poll(&self, timeout: Duration) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        if self.fds.is_empty() {
+            return events;
+        }
+
+        let mut pollfds: Vec<libc::pollfd> = self
+            .fds
+            .iter()
+            .map(|&fd| libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+        let ready = unsafe {
+            libc::poll(
+                pollfds.as_mut_ptr(),
+                pollfds.len() as libc::nfds_t,
+                timeout.as_millis().min(i32::MAX as u128) as libc::c_int,
+            )
+        };
+        if ready <= 0 {
+            return events;
+        }
+
+        for p in pollfds.iter().filter(|p| p.revents & libc::POLLIN != 0) {
+            loop {
+                let mut raw: libc::input_event = unsafe { std::mem::zeroed() };
+                let n = unsafe {
+                    libc::read(
+                        p.fd,
+                        &mut raw as *mut libc::input_event as *mut libc::c_void,
+                        std::mem::size_of::<libc::input_event>(),
+                    )
+                };
+                if n != std::mem::size_of::<libc::input_event>() as isize {
+                    break;
+                }
+                events.push(InputEvent {
+                    time: Duration::new(raw.time.tv_sec as u64, raw.time.tv_usec as u32 * 1000),
+                    kind: raw.type_,
+                    code: raw.code,
+                    value: raw.value,
+                });
+            }
+        }
+
+        events.sort_by_key(|e| e.time);
+        events
+    }
+}
+
+impl Drop for EventReader {
+    fn drop(&mut self) {
+        for &fd in &self.fds {
+            unsafe {
+                libc::close(fd);
+            }
+        }
+    }
+}
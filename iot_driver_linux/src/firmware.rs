@@ -1,11 +1,13 @@
 // Firmware file handling and dry-run simulation
-// This module is for ANALYSIS ONLY - no actual firmware flashing
+// Flashing lives in crate::flash; HEX/DFU parsing in crate::firmware_container
 
 use std::fs;
 use std::io::{self, Read};
 use std::path::Path;
 use zip::ZipArchive;
 
+use crate::firmware_api::Verification;
+use crate::firmware_container::{self, Container, ContainerInfo, APP_BASE};
 use crate::protocol::firmware_update::{self, FlashTarget};
 
 /// Error types for firmware operations
@@ -59,6 +61,8 @@ pub struct FirmwareFile {
     pub filename: String,
     /// Firmware type (detected from file structure)
     pub firmware_type: FirmwareType,
+    /// Container the image came in, with its metadata
+    pub container: ContainerInfo,
 }
 
 /// Type of firmware file
@@ -98,6 +102,7 @@ impl FirmwareFile {
             chunk_count,
             filename,
             firmware_type,
+            container: ContainerInfo::default(),
         }
     }
 
@@ -110,27 +115,107 @@ impl FirmwareFile {
             .unwrap_or("firmware.bin")
             .to_string();
 
-        let data = fs::read(path)?;
+        Self::from_bytes(fs::read(path)?, filename)
+    }
 
+    /// Build a firmware file from the contents of a `.bin`, `.hex`, `.dfu`
+    /// or `.zip` named `filename`.
+    pub fn from_bytes(data: Vec<u8>, filename: String) -> Result<Self, FirmwareError> {
         if data.is_empty() {
             return Err(FirmwareError::FileTooSmall(0));
         }
 
         // Check if it's a ZIP file
         if data.len() >= 4 && &data[0..4] == b"PK\x03\x04" {
-            return Self::load_zip(path, filename);
+            return Self::load_zip(data, filename);
+        }
+
+        Self::decode(data, filename, None)
+    }
+
+    /// Unpack Intel HEX / DFU / DfuSe containers, then build the file.
+    /// `zip` marks an image taken from a ZIP package.
+    fn decode(data: Vec<u8>, filename: String, zip: Option<&str>) -> Result<Self, FirmwareError> {
+        let inner_name = zip.unwrap_or(&filename);
+        let (data, mut container) = match firmware_container::decode(&data, inner_name) {
+            Some(decoded) => decoded.map_err(FirmwareError::InvalidFormat)?,
+            None => (data, ContainerInfo::default()),
+        };
+        if data.is_empty() {
+            return Err(FirmwareError::FileTooSmall(0));
+        }
+        let firmware_type = match zip {
+            Some(_) => FirmwareType::Combined,
+            None => Self::detect_type(&data, &filename),
+        };
+        if zip.is_some() && container.container == Container::Bin {
+            container.container = Container::Zip;
         }
+        Ok(Self {
+            container,
+            ..Self::from_data(data, filename, firmware_type)
+        })
+    }
+
+    /// Load the image for `target` from disk.
+    ///
+    /// Vendor packages can carry keyboard and dongle images side by side; from
+    /// a ZIP this picks the `.bin` whose chip ID matches `target`, falling back
+    /// to [`load`](Self::load) when none does. Plain `.bin` files load as-is.
+    pub fn load_for<P: AsRef<Path>>(path: P, target: FlashTarget) -> Result<Self, FirmwareError> {
+        let path = path.as_ref();
+        let filename = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("firmware.bin")
+            .to_string();
+        Self::from_bytes_for(fs::read(path)?, filename, target)
+    }
 
-        // Detect firmware type based on size/content
-        let firmware_type = Self::detect_type(&data, &filename);
+    /// [`from_bytes`](Self::from_bytes), picking the image for `target`
+    /// from a ZIP like [`load_for`](Self::load_for).
+    pub fn from_bytes_for(
+        data: Vec<u8>,
+        filename: String,
+        target: FlashTarget,
+    ) -> Result<Self, FirmwareError> {
+        if !data.starts_with(b"PK\x03\x04") {
+            return Self::from_bytes(data, filename);
+        }
+        let mut archive = ZipArchive::new(io::Cursor::new(data))?;
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let lower = entry.name().to_lowercase();
+            if ![".bin", ".hex", ".dfu"]
+                .iter()
+                .any(|ext| lower.ends_with(ext))
+            {
+                continue;
+            }
+            let mut bin = Vec::new();
+            entry.read_to_end(&mut bin)?;
+            let inner = entry.name().to_string();
+            let name = format!("{filename}:{inner}");
+            let Ok(fw) = Self::decode(bin, name, Some(&inner)) else {
+                continue;
+            };
+            if fw.target() == Some(target) {
+                return Ok(fw);
+            }
+        }
+        Self::from_bytes(archive.into_inner().into_inner(), filename)
+    }
 
-        Ok(Self::from_data(data, filename, firmware_type))
+    /// Device this image is built for, from its chip ID (at the start, or
+    /// after the bootloader in a full flash dump), else from the USB IDs in
+    /// a DFU suffix.
+    pub fn target(&self) -> Option<FlashTarget> {
+        image_target(&self.data).or_else(|| self.container.target())
     }
 
     /// Load firmware from a ZIP archive
-    fn load_zip<P: AsRef<Path>>(path: P, filename: String) -> Result<Self, FirmwareError> {
-        let file = fs::File::open(path)?;
-        let mut archive = ZipArchive::new(file)?;
+    fn load_zip(data: Vec<u8>, filename: String) -> Result<Self, FirmwareError> {
+        let mut archive = ZipArchive::new(io::Cursor::new(data))?;
 
         // Look for main firmware file
         let firmware_names = ["firmwareFile.bin", "firmware.bin", "usb_firmware.bin"];
@@ -139,18 +224,21 @@ impl FirmwareFile {
             if let Ok(mut entry) = archive.by_name(name) {
                 let mut data = Vec::new();
                 entry.read_to_end(&mut data)?;
-                return Ok(Self::from_data(data, filename, FirmwareType::Combined));
+                return Self::decode(data, filename, Some(name));
             }
         }
 
-        // If no known firmware file, try first .bin file
+        // If no known firmware file, try the first image file
         for i in 0..archive.len() {
             let mut entry = archive.by_index(i)?;
             let name = entry.name().to_lowercase();
-            if name.ends_with(".bin") {
+            if [".bin", ".hex", ".dfu"]
+                .iter()
+                .any(|ext| name.ends_with(ext))
+            {
                 let mut data = Vec::new();
                 entry.read_to_end(&mut data)?;
-                return Ok(Self::from_data(data, filename, FirmwareType::Combined));
+                return Self::decode(data, filename, Some(&name));
             }
         }
 
@@ -206,6 +294,10 @@ impl FirmwareFile {
             ));
         }
 
+        self.container
+            .check_address()
+            .map_err(FirmwareError::InvalidFormat)?;
+
         Ok(())
     }
 
@@ -231,6 +323,17 @@ impl FirmwareFile {
     }
 }
 
+/// Match an image's chip ID against the known targets.
+fn image_target(data: &[u8]) -> Option<FlashTarget> {
+    let offset = firmware_update::USB_FIRMWARE_OFFSET;
+    [FlashTarget::Keyboard, FlashTarget::Dongle]
+        .into_iter()
+        .find(|t| {
+            let id = t.chip_id();
+            data.starts_with(id) || data.get(offset..).is_some_and(|app| app.starts_with(id))
+        })
+}
+
 /// If the firmware looks like a full flash dump (bootloader + app), strip the
 /// bootloader prefix and return a new `FirmwareFile` with only the app region.
 ///
@@ -275,11 +378,14 @@ pub fn strip_bootloader_if_needed(
     }
 
     let data = firmware.data[offset..].to_vec();
-    Some(FirmwareFile::from_data(
-        data,
-        firmware.filename.clone(),
-        firmware.firmware_type,
-    ))
+    let mut container = firmware.container.clone();
+    if container.load_address.is_some() {
+        container.load_address = Some(APP_BASE);
+    }
+    Some(FirmwareFile {
+        container,
+        ..FirmwareFile::from_data(data, firmware.filename.clone(), firmware.firmware_type)
+    })
 }
 
 /// Represents a command in the dry-run simulation
@@ -363,6 +469,8 @@ impl DryRunCommand {
 pub struct DryRunResult {
     /// Firmware file being analyzed
     pub firmware: FirmwareFile,
+    /// Device being updated
+    pub target: FlashTarget,
     /// Current device firmware version (if available)
     pub current_version: Option<String>,
     /// Device ID (if available)
@@ -371,6 +479,9 @@ pub struct DryRunResult {
     pub commands: Vec<DryRunCommand>,
     /// Estimated transfer time in seconds
     pub estimated_time_secs: f32,
+    /// File checked against the vendor manifest from the last firmware
+    /// check for this device (`None` if there was none)
+    pub manifest: Option<Verification>,
 }
 
 impl DryRunResult {
@@ -390,12 +501,30 @@ impl DryRunResult {
         println!("  Chunks: {} (64 bytes each)", self.firmware.chunk_count);
         println!();
 
+        match self.firmware.target() {
+            Some(t) if t == self.target => println!("Target: {}", t.name()),
+            Some(t) => println!(
+                "Target: {} - WARNING: this is a {} image, flashing would be refused",
+                self.target.name(),
+                t.name()
+            ),
+            None => println!(
+                "Target: {} - WARNING: no known chip ID in image",
+                self.target.name()
+            ),
+        }
         if let Some(ref ver) = self.current_version {
-            println!("Current device firmware: {ver}");
+            println!("Current {} firmware: {ver}", self.target.name());
         }
         if let Some(id) = self.device_id {
             println!("Device ID: 0x{id:08X}");
         }
+        match &self.manifest {
+            Some(v) => println!("Vendor manifest: {v}"),
+            None => {
+                println!("Vendor manifest: not checked (no cached firmware check for this device)")
+            }
+        }
         println!();
 
         if verbose {
@@ -431,6 +560,19 @@ pub fn dry_run_usb(
     firmware: &FirmwareFile,
     current_version: Option<String>,
     device_id: Option<u32>,
+) -> DryRunResult {
+    dry_run(firmware, FlashTarget::Keyboard, current_version, device_id)
+}
+
+/// Generate a dry-run simulation for updating `target`.
+///
+/// Keyboard and dongle run the same RY bootloader protocol; they differ in
+/// the bootloader PID waited for and the chip ID the image must carry.
+pub fn dry_run(
+    firmware: &FirmwareFile,
+    target: FlashTarget,
+    current_version: Option<String>,
+    device_id: Option<u32>,
 ) -> DryRunResult {
     let mut commands = Vec::new();
 
@@ -441,9 +583,10 @@ pub fn dry_run_usb(
     });
 
     // 2. Wait for reconnection
+    let (vid, pid) = target.boot_vid_pids()[0];
     commands.push(DryRunCommand::WaitReconnect {
-        vid: firmware_update::BOOT_VID_PIDS[0].0,
-        pid: firmware_update::BOOT_VID_PIDS[0].1,
+        vid,
+        pid,
         timeout_ms: 5000,
     });
 
@@ -484,10 +627,12 @@ pub fn dry_run_usb(
 
     DryRunResult {
         firmware: firmware.clone(),
+        target,
         current_version,
         device_id,
         commands,
         estimated_time_secs,
+        manifest: None,
     }
 }
 
@@ -510,6 +655,35 @@ mod tests {
         assert_eq!(firmware_update::calculate_checksum(&data), 64);
     }
 
+    #[test]
+    fn detects_target_from_chip_id() {
+        let mut image = firmware_update::CHIP_ID_DONGLE.to_vec();
+        image.resize(4096, 0x11);
+        let fw = FirmwareFile::from_data(image.clone(), "d.bin".into(), FirmwareType::Usb);
+        assert_eq!(fw.target(), Some(FlashTarget::Dongle));
+
+        // Full dump: chip ID after the 20KB bootloader
+        let mut dump = vec![0u8; firmware_update::USB_FIRMWARE_OFFSET];
+        dump.extend_from_slice(firmware_update::CHIP_ID_KEYBOARD);
+        let fw = FirmwareFile::from_data(dump, "k.bin".into(), FirmwareType::Usb);
+        assert_eq!(fw.target(), Some(FlashTarget::Keyboard));
+
+        let fw = FirmwareFile::from_data(vec![0x11; 4096], "x.bin".into(), FirmwareType::Usb);
+        assert_eq!(fw.target(), None);
+    }
+
+    #[test]
+    fn dongle_dry_run_waits_for_dongle_bootloader() {
+        let mut image = firmware_update::CHIP_ID_DONGLE.to_vec();
+        image.resize(4096, 0x11);
+        let fw = FirmwareFile::from_data(image, "d.bin".into(), FirmwareType::Usb);
+        let result = dry_run(&fw, FlashTarget::Dongle, None, None);
+        assert!(result.commands.iter().any(|c| matches!(
+            c,
+            DryRunCommand::WaitReconnect { pid, .. } if *pid == firmware_update::BOOT_PID_DONGLE
+        )));
+    }
+
     #[test]
     fn test_build_start_header() {
         let header = firmware_update::build_start_header(2048, 131072);
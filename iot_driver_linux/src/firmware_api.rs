@@ -3,7 +3,11 @@
 
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
 
 /// API base URL
 pub const API_BASE: &str = "https://api2.rongyuan.tech:3816/api/v2";
@@ -18,6 +22,10 @@ pub enum ApiError {
     ParseError(String),
     IoError(std::io::Error),
     ServerError(i32, String),
+    /// Offline mode and nothing cached to answer from
+    Offline(String),
+    /// Downloaded file doesn't match the checksums the server published
+    Verification(String),
 }
 
 impl std::fmt::Display for ApiError {
@@ -27,6 +35,8 @@ impl std::fmt::Display for ApiError {
             Self::ParseError(msg) => write!(f, "Parse error: {msg}"),
             Self::IoError(e) => write!(f, "I/O error: {e}"),
             Self::ServerError(code, msg) => write!(f, "Server error {code}: {msg}"),
+            Self::Offline(msg) => write!(f, "Offline: {msg}"),
+            Self::Verification(msg) => write!(f, "Verification failed: {msg}"),
         }
     }
 }
@@ -173,6 +183,10 @@ pub struct FirmwareCheckResponse {
     pub versions: FirmwareVersions,
     /// Minimum app version required
     pub lowest_app_version: Option<String>,
+    /// Checksums published for the download
+    pub manifest: DownloadManifest,
+    /// Age of the cached answer, `None` when it came from the server just now
+    pub cached_age: Option<Duration>,
 }
 
 /// Simplified result of firmware version check for UI display
@@ -184,6 +198,8 @@ pub struct FirmwareCheckResult {
     pub has_update: bool,
     /// Download path if update available
     pub download_path: Option<String>,
+    /// Checksums to verify the download against
+    pub manifest: DownloadManifest,
     /// Message (e.g., "up to date" or "not in database")
     pub message: String,
 }
@@ -195,6 +211,7 @@ impl FirmwareCheckResult {
             server_version: None,
             has_update: false,
             download_path: None,
+            manifest: DownloadManifest::default(),
             message: "Up to date".to_string(),
         }
     }
@@ -205,6 +222,7 @@ impl FirmwareCheckResult {
             server_version: None,
             has_update: false,
             download_path: None,
+            manifest: DownloadManifest::default(),
             message: "Not in database (up to date)".to_string(),
         }
     }
@@ -223,18 +241,52 @@ impl FirmwareCheckResult {
             "Up to date".to_string()
         };
 
+        let message = match response.cached_age {
+            Some(age) => format!("{message} (cached {} ago)", format_age(age)),
+            None => message,
+        };
+
         Self {
             server_version,
             has_update,
             download_path: response.versions.download_path.clone(),
+            manifest: response.manifest.clone(),
             message,
         }
     }
 }
 
-/// Check firmware version from API (blocking)
+/// Parse a `get_fw_version` reply into the form the cache stores.
+#[cfg(feature = "firmware-api-async")]
+fn parse_check_reply(json: &serde_json::Value) -> Result<CachedCheck, ApiError> {
+    // Check error code
+    if let Some(err_code) = json.get("errCode").and_then(|v| v.as_i64()) {
+        if err_code != 0 {
+            return Err(ApiError::ServerError(
+                err_code as i32,
+                "API error".to_string(),
+            ));
+        }
+    }
+
+    // Parse data
+    let data = json
+        .get("data")
+        .ok_or_else(|| ApiError::ParseError("No data in response".to_string()))?;
+
+    let field = |name: &str| data.get(name).and_then(|v| v.as_str()).map(str::to_string);
+
+    Ok(CachedCheck {
+        fetched_at: unix_now(),
+        version_str: field("version_str").unwrap_or_default(),
+        path: field("path"),
+        lowest_app_version: field("lowest_app_version_str"),
+        manifest: DownloadManifest::from_reply(data),
+    })
+}
+
 #[cfg(feature = "firmware-api")]
-pub fn check_firmware_blocking(device_id: u32) -> Result<FirmwareCheckResponse, ApiError> {
+fn fetch_check_blocking(device_id: u32) -> Result<CachedCheck, ApiError> {
     use reqwest::blocking::Client;
 
     let client = Client::builder()
@@ -264,51 +316,21 @@ pub fn check_firmware_blocking(device_id: u32) -> Result<FirmwareCheckResponse,
         .json()
         .map_err(|e| ApiError::ParseError(e.to_string()))?;
 
-    // Check error code
-    if let Some(err_code) = json.get("errCode").and_then(|v| v.as_i64()) {
-        if err_code != 0 {
-            return Err(ApiError::ServerError(
-                err_code as i32,
-                "API error".to_string(),
-            ));
-        }
-    }
-
-    // Parse data
-    let data = json
-        .get("data")
-        .ok_or_else(|| ApiError::ParseError("No data in response".to_string()))?;
-
-    let version_str = data
-        .get("version_str")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-
-    let mut versions = FirmwareVersions::parse(version_str);
-
-    // Get download path
-    versions.download_path = data
-        .get("path")
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
-
-    let lowest_app_version = data
-        .get("lowest_app_version_str")
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
+    parse_check_reply(&json)
+}
 
-    Ok(FirmwareCheckResponse {
-        versions,
-        lowest_app_version,
-    })
+/// Check firmware version from API (blocking), going through the cache
+#[cfg(feature = "firmware-api")]
+pub fn check_firmware_blocking(device_id: u32) -> Result<FirmwareCheckResponse, ApiError> {
+    let stale = match cache_lookup(device_id)? {
+        Lookup::Use(cached) => return Ok(cached.cached_response(unix_now())),
+        Lookup::Fetch(stale) => stale,
+    };
+    finish_check(device_id, fetch_check_blocking(device_id), stale)
 }
 
-/// Download firmware file from server (blocking)
 #[cfg(feature = "firmware-api")]
-pub fn download_firmware_blocking<P: AsRef<Path>>(
-    download_path: &str,
-    output: P,
-) -> Result<usize, ApiError> {
+fn fetch_download_blocking(download_path: &str) -> Result<Vec<u8>, ApiError> {
     use reqwest::blocking::Client;
 
     let client = Client::builder()
@@ -334,15 +356,28 @@ pub fn download_firmware_blocking<P: AsRef<Path>>(
         .bytes()
         .map_err(|e| ApiError::RequestError(e.to_string()))?;
 
-    let size = bytes.len();
-    fs::write(output, &bytes)?;
+    Ok(bytes.to_vec())
+}
 
-    Ok(size)
+/// Download firmware file from server (blocking), or take it from the cache.
+///
+/// The file is checked against `manifest` before it is written; a mismatch
+/// is an error and nothing is written.
+#[cfg(feature = "firmware-api")]
+pub fn download_firmware_blocking<P: AsRef<Path>>(
+    download_path: &str,
+    manifest: &DownloadManifest,
+    output: P,
+) -> Result<(usize, Verification), ApiError> {
+    let bytes = match read_cached_download(download_path, manifest)? {
+        Some(bytes) => bytes,
+        None => fetch_download_blocking(download_path)?,
+    };
+    store_download(download_path, &bytes, manifest, output.as_ref())
 }
 
-/// Check firmware version from API (async)
 #[cfg(feature = "firmware-api-async")]
-pub async fn check_firmware(device_id: u32) -> Result<FirmwareCheckResponse, ApiError> {
+async fn fetch_check(device_id: u32) -> Result<CachedCheck, ApiError> {
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(10))
         .build()
@@ -372,50 +407,21 @@ pub async fn check_firmware(device_id: u32) -> Result<FirmwareCheckResponse, Api
         .await
         .map_err(|e| ApiError::ParseError(e.to_string()))?;
 
-    // Check error code
-    if let Some(err_code) = json.get("errCode").and_then(|v| v.as_i64()) {
-        if err_code != 0 {
-            return Err(ApiError::ServerError(
-                err_code as i32,
-                "API error".to_string(),
-            ));
-        }
-    }
-
-    // Parse data
-    let data = json
-        .get("data")
-        .ok_or_else(|| ApiError::ParseError("No data in response".to_string()))?;
-
-    let version_str = data
-        .get("version_str")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-
-    let mut versions = FirmwareVersions::parse(version_str);
-
-    versions.download_path = data
-        .get("path")
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
-
-    let lowest_app_version = data
-        .get("lowest_app_version_str")
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
+    parse_check_reply(&json)
+}
 
-    Ok(FirmwareCheckResponse {
-        versions,
-        lowest_app_version,
-    })
+/// Check firmware version from API (async), going through the cache
+#[cfg(feature = "firmware-api-async")]
+pub async fn check_firmware(device_id: u32) -> Result<FirmwareCheckResponse, ApiError> {
+    let stale = match cache_lookup(device_id)? {
+        Lookup::Use(cached) => return Ok(cached.cached_response(unix_now())),
+        Lookup::Fetch(stale) => stale,
+    };
+    finish_check(device_id, fetch_check(device_id).await, stale)
 }
 
-/// Download firmware file from server (async)
 #[cfg(feature = "firmware-api-async")]
-pub async fn download_firmware<P: AsRef<Path>>(
-    download_path: &str,
-    output: P,
-) -> Result<usize, ApiError> {
+async fn fetch_download(download_path: &str) -> Result<Vec<u8>, ApiError> {
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(60))
         .build()
@@ -441,24 +447,505 @@ pub async fn download_firmware<P: AsRef<Path>>(
         .await
         .map_err(|e| ApiError::RequestError(e.to_string()))?;
 
-    let size = bytes.len();
-    fs::write(output, &bytes)?;
+    Ok(bytes.to_vec())
+}
 
-    Ok(size)
+/// Download firmware file from server (async), or take it from the cache.
+///
+/// Verified against `manifest` like [`download_firmware_blocking`].
+#[cfg(feature = "firmware-api-async")]
+pub async fn download_firmware<P: AsRef<Path>>(
+    download_path: &str,
+    manifest: &DownloadManifest,
+    output: P,
+) -> Result<(usize, Verification), ApiError> {
+    let bytes = match read_cached_download(download_path, manifest)? {
+        Some(bytes) => bytes,
+        None => fetch_download(download_path).await?,
+    };
+    store_download(download_path, &bytes, manifest, output.as_ref())
+}
+
+// ── Cache ────────────────────────────────────────────────────────────
+//
+// Check results are kept per device ID and reused for CACHE_TTL; after that
+// the server is asked again, and the stale entry is still used if it can't
+// be reached. Downloads are kept by server path, which changes with every
+// release, so they never expire. In offline mode only the cache is read.
+
+/// How long a cached check result is used without asking the server
+pub const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Never contact the server; answer from the cache only (`--offline`)
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Whether `--offline` is in effect
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// `$XDG_CACHE_HOME/monsgeek/firmware` (or `~/.cache/monsgeek/firmware`)
+pub fn cache_dir() -> PathBuf {
+    let base = if let Some(cache) = std::env::var_os("XDG_CACHE_HOME") {
+        PathBuf::from(cache)
+    } else if let Some(home) = std::env::var_os("HOME") {
+        PathBuf::from(home).join(".cache")
+    } else {
+        PathBuf::from("/tmp")
+    };
+    base.join("monsgeek/firmware")
+}
+
+/// Cache age as `42m`, `5h` or `3d`
+pub fn format_age(age: Duration) -> String {
+    let mins = age.as_secs() / 60;
+    match mins {
+        0..=59 => format!("{mins}m"),
+        60..=2879 => format!("{}h", mins / 60),
+        _ => format!("{}d", mins / (24 * 60)),
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// A `get_fw_version` reply as stored in `check-<device id>.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CachedCheck {
+    /// Unix time the server answered
+    fetched_at: u64,
+    version_str: String,
+    path: Option<String>,
+    lowest_app_version: Option<String>,
+    #[serde(default)]
+    manifest: DownloadManifest,
+}
+
+impl CachedCheck {
+    fn age(&self, now: u64) -> Duration {
+        Duration::from_secs(now.saturating_sub(self.fetched_at))
+    }
+
+    fn response(&self) -> FirmwareCheckResponse {
+        let mut versions = FirmwareVersions::parse(&self.version_str);
+        versions.download_path = self.path.clone();
+        FirmwareCheckResponse {
+            versions,
+            lowest_app_version: self.lowest_app_version.clone(),
+            manifest: self.manifest.clone(),
+            cached_age: None,
+        }
+    }
+
+    fn cached_response(&self, now: u64) -> FirmwareCheckResponse {
+        FirmwareCheckResponse {
+            cached_age: Some(self.age(now)),
+            ..self.response()
+        }
+    }
+}
+
+/// What to do about a check, given what the cache holds.
+#[derive(Debug, PartialEq)]
+enum Lookup {
+    /// Answer from the cache
+    Use(CachedCheck),
+    /// Ask the server; the stale entry (if any) is the fallback
+    Fetch(Option<CachedCheck>),
+}
+
+fn decide(cached: Option<CachedCheck>, now: u64, offline: bool) -> Lookup {
+    match cached {
+        Some(c) if offline || c.age(now) < CACHE_TTL => Lookup::Use(c),
+        stale => Lookup::Fetch(stale),
+    }
+}
+
+fn check_cache_path(device_id: u32) -> PathBuf {
+    cache_dir().join(format!("check-{device_id}.json"))
+}
+
+fn read_cached_check(device_id: u32) -> Option<CachedCheck> {
+    let text = fs::read_to_string(check_cache_path(device_id)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+#[cfg(feature = "firmware-api-async")]
+fn cache_lookup(device_id: u32) -> Result<Lookup, ApiError> {
+    match decide(read_cached_check(device_id), unix_now(), is_offline()) {
+        Lookup::Fetch(_) if is_offline() => Err(ApiError::Offline(format!(
+            "no cached firmware check for device ID {device_id}"
+        ))),
+        lookup => Ok(lookup),
+    }
+}
+
+/// Store a fresh answer, or fall back to the stale entry when the server
+/// couldn't be reached. Server errors (such as 500 for unknown devices) are
+/// passed through; they're an answer, not an outage.
+#[cfg(feature = "firmware-api-async")]
+fn finish_check(
+    device_id: u32,
+    fetched: Result<CachedCheck, ApiError>,
+    stale: Option<CachedCheck>,
+) -> Result<FirmwareCheckResponse, ApiError> {
+    match (fetched, stale) {
+        (Ok(fresh), _) => {
+            if let Ok(json) = serde_json::to_string_pretty(&fresh) {
+                let _ = fs::create_dir_all(cache_dir())
+                    .and_then(|()| fs::write(check_cache_path(device_id), json));
+            }
+            Ok(fresh.response())
+        }
+        (Err(ApiError::RequestError(_)), Some(stale)) => Ok(stale.cached_response(unix_now())),
+        (Err(e), _) => Err(e),
+    }
+}
+
+/// Cache file for a server download path (`/fw/abc/M1V5.zip` → `fw_abc_M1V5.zip`)
+fn download_cache_path(download_path: &str) -> PathBuf {
+    let name: String = download_path
+        .trim_start_matches('/')
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    cache_dir().join("downloads").join(name)
+}
+
+/// A cached download; `None` when it isn't cached. A cached file that no
+/// longer matches the manifest is dropped so it gets fetched again.
+#[cfg(feature = "firmware-api-async")]
+fn read_cached_download(
+    download_path: &str,
+    manifest: &DownloadManifest,
+) -> Result<Option<Vec<u8>>, ApiError> {
+    let cached = download_cache_path(download_path);
+    if let Ok(bytes) = fs::read(&cached) {
+        if !matches!(manifest.verify(&bytes), Verification::Mismatch(_)) {
+            return Ok(Some(bytes));
+        }
+        let _ = fs::remove_file(&cached);
+    }
+    if is_offline() {
+        return Err(ApiError::Offline(format!(
+            "{download_path} has not been downloaded before"
+        )));
+    }
+    Ok(None)
+}
+
+/// Verify a download, then write it to `output` and the cache.
+#[cfg(feature = "firmware-api-async")]
+fn store_download(
+    download_path: &str,
+    bytes: &[u8],
+    manifest: &DownloadManifest,
+    output: &Path,
+) -> Result<(usize, Verification), ApiError> {
+    let verification = manifest.verify(bytes);
+    if let Verification::Mismatch(why) = verification {
+        return Err(ApiError::Verification(format!("{download_path}: {why}")));
+    }
+    fs::write(output, bytes)?;
+    let cached = download_cache_path(download_path);
+    if let Some(dir) = cached.parent() {
+        let _ = fs::create_dir_all(dir).and_then(|()| fs::write(&cached, bytes));
+    }
+    Ok((bytes.len(), verification))
+}
+
+/// Check a local file against the manifest of the last cached check for
+/// `device_id`; `None` when that device was never checked.
+#[cfg(feature = "firmware-api-async")]
+pub fn verify_against_cache(device_id: u32, file: &[u8]) -> Option<Verification> {
+    Some(read_cached_check(device_id)?.manifest.verify(file))
+}
+
+// ── Download verification ────────────────────────────────────────────
+//
+// The check reply can carry the size and digests of the download. The key
+// names aren't documented, so the usual spellings are accepted. The vendor
+// doesn't publish a signing key, so signatures can't be checked.
+
+/// Size and digests the server published for a download.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadManifest {
+    pub size: Option<u64>,
+    /// Lowercase hex
+    pub md5: Option<String>,
+    /// Lowercase hex
+    pub sha256: Option<String>,
+}
+
+/// How a file compares with its manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// Everything published matched; lists what was checked
+    Verified(Vec<&'static str>),
+    /// A published value didn't match
+    Mismatch(String),
+    /// Nothing was published to check against
+    Unavailable,
+}
+
+impl std::fmt::Display for Verification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Verified(what) => write!(f, "verified ({})", what.join(", ")),
+            Self::Mismatch(why) => write!(f, "MISMATCH: {why}"),
+            Self::Unavailable => write!(f, "not verified (server published no checksums)"),
+        }
+    }
+}
+
+impl DownloadManifest {
+    /// Pick the size and digests out of the check reply's `data` object.
+    pub fn from_reply(data: &serde_json::Value) -> Self {
+        let find = |keys: &[&str]| keys.iter().find_map(|k| data.get(*k));
+        let digest = |keys: &[&str]| {
+            find(keys)
+                .and_then(|v| v.as_str())
+                .map(|s| s.trim().to_ascii_lowercase())
+                .filter(|s| !s.is_empty())
+        };
+        let size = find(&["size", "file_size", "fileSize"]).and_then(|v| {
+            v.as_u64()
+                .or_else(|| v.as_str().and_then(|s| s.trim().parse().ok()))
+        });
+        Self {
+            size,
+            md5: digest(&["md5", "file_md5", "fileMd5", "md5_str"]),
+            sha256: digest(&["sha256", "file_sha256", "fileSha256", "sha256_str"]),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size.is_none() && self.md5.is_none() && self.sha256.is_none()
+    }
+
+    /// Compare `file` with every published value.
+    #[cfg(feature = "firmware-api-async")]
+    pub fn verify(&self, file: &[u8]) -> Verification {
+        let mut checked = Vec::new();
+        if let Some(size) = self.size {
+            if size != file.len() as u64 {
+                return Verification::Mismatch(format!(
+                    "size is {} bytes, expected {size}",
+                    file.len()
+                ));
+            }
+            checked.push("size");
+        }
+        if let Some(expected) = &self.md5 {
+            let got = hex(&md5(file));
+            if &got != expected {
+                return Verification::Mismatch(format!("MD5 is {got}, expected {expected}"));
+            }
+            checked.push("MD5");
+        }
+        if let Some(expected) = &self.sha256 {
+            let got = hex(ring::digest::digest(&ring::digest::SHA256, file).as_ref());
+            if &got != expected {
+                return Verification::Mismatch(format!("SHA-256 is {got}, expected {expected}"));
+            }
+            checked.push("SHA-256");
+        }
+        if checked.is_empty() {
+            Verification::Unavailable
+        } else {
+            Verification::Verified(checked)
+        }
+    }
+}
+
+#[cfg(feature = "firmware-api-async")]
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// MD5 (RFC 1321); only used to compare against published digests.
+#[cfg(feature = "firmware-api-async")]
+fn md5(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [[u32; 4]; 4] = [
+        [7, 12, 17, 22],
+        [5, 9, 14, 20],
+        [4, 11, 16, 23],
+        [6, 10, 15, 21],
+    ];
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&(data.len() as u64).wrapping_mul(8).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    for block in msg.chunks_exact(64) {
+        let m: Vec<u32> = block
+            .chunks_exact(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            // K[i] = floor(|sin(i + 1)| * 2^32)
+            let k = ((i as f64 + 1.0).sin().abs() * 4_294_967_296.0) as u32;
+            let f = f.wrapping_add(a).wrapping_add(k).wrapping_add(m[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(SHIFTS[i / 16][i % 4]));
+        }
+        for (s, v) in state.iter_mut().zip([a, b, c, d]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+    let mut out = [0; 16];
+    for (o, s) in out.chunks_exact_mut(4).zip(state) {
+        o.copy_from_slice(&s.to_le_bytes());
+    }
+    out
 }
 
 /// Known device IDs
+/// Mapping between USB VID/PID, the device ID a keyboard reports and the
+/// vendor API ID.
+///
+/// The vendor API identifies products by the same ID the keyboard reports via
+/// GET_USB_VERSION, so the API ID is the device ID. The table is built from the
+/// JSON device database, so regenerating `devices.json` keeps it current; a few
+/// builtin rows cover devices whose VID/PID is shared by other products.
 pub mod device_ids {
+    use crate::device_loader::DeviceDatabase;
+    use crate::profile::profile_registry;
+
     /// MonsGeek M1 V5 HE / M1 V5 TMR
     /// Note: The device reports this ID directly via GET_USB_VERSION command
     pub const M1_V5_HE: u32 = 2949;
 
+    /// Builtin (vid, pid, device id) rows; these win over the database, where
+    /// the M1 V5 HE's USB IDs are shared with hundreds of other products.
+    const BUILTIN: &[(u16, u16, u32)] = &[
+        (0x3151, 0x5030, M1_V5_HE),
+        (0x3151, 0x503A, M1_V5_HE), // Wireless mode
+    ];
+
+    /// One product in the mapping.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct DeviceIdEntry {
+        pub vid: u16,
+        pub pid: u16,
+        /// ID reported by the keyboard (GET_USB_VERSION)
+        pub device_id: u32,
+        pub name: String,
+    }
+
+    impl DeviceIdEntry {
+        /// ID to query the vendor firmware API with
+        pub fn api_id(&self) -> u32 {
+            self.device_id
+        }
+    }
+
+    /// VID/PID <-> device ID <-> API ID table.
+    #[derive(Debug, Clone, Default)]
+    pub struct DeviceIdMap {
+        entries: Vec<DeviceIdEntry>,
+    }
+
+    impl DeviceIdMap {
+        /// Build the table from a device database. Special devices with
+        /// non-positive IDs have nothing on the firmware server and are left out.
+        pub fn from_database(db: &DeviceDatabase) -> Self {
+            let mut entries: Vec<_> = db
+                .all_devices()
+                .filter(|d| d.id > 0)
+                .map(|d| DeviceIdEntry {
+                    vid: d.vid,
+                    pid: d.pid,
+                    device_id: d.id as u32,
+                    name: match &d.company {
+                        Some(company) => format!("{company} {}", d.display_name),
+                        None => d.display_name.clone(),
+                    },
+                })
+                .collect();
+            entries.sort_by_key(|e| (e.vid, e.pid, e.device_id));
+            Self { entries }
+        }
+
+        /// Device ID for a VID/PID: a builtin row, or the database when exactly
+        /// one device ID is listed under it.
+        pub fn from_vid_pid(&self, vid: u16, pid: u16) -> Option<u32> {
+            if let Some(&(_, _, id)) = BUILTIN.iter().find(|(v, p, _)| (*v, *p) == (vid, pid)) {
+                return Some(id);
+            }
+            let mut ids = self.candidates(vid, pid).into_iter().map(|e| e.device_id);
+            let first = ids.next()?;
+            ids.all(|id| id == first).then_some(first)
+        }
+
+        /// Every product listed under a VID/PID.
+        pub fn candidates(&self, vid: u16, pid: u16) -> Vec<&DeviceIdEntry> {
+            self.entries
+                .iter()
+                .filter(|e| e.vid == vid && e.pid == pid)
+                .collect()
+        }
+
+        /// Every product that reports this device ID.
+        pub fn by_device_id(&self, device_id: u32) -> Vec<&DeviceIdEntry> {
+            self.entries
+                .iter()
+                .filter(|e| e.device_id == device_id)
+                .collect()
+        }
+
+        pub fn entries(&self) -> &[DeviceIdEntry] {
+            &self.entries
+        }
+    }
+
+    /// The table for the global device database (empty if it isn't loaded).
+    /// Built on each call, so it follows database reloads.
+    pub fn map() -> DeviceIdMap {
+        profile_registry()
+            .device_database()
+            .map(DeviceIdMap::from_database)
+            .unwrap_or_default()
+    }
+
     /// Get device ID from VID/PID if known (fallback when device query fails)
     /// Prefer querying the device directly via KeyboardInterface::get_device_id()
     pub fn from_vid_pid(vid: u16, pid: u16) -> Option<u32> {
-        match (vid, pid) {
-            (0x3151, 0x5030) => Some(M1_V5_HE),
-            (0x3151, 0x503A) => Some(M1_V5_HE), // Wireless mode
+        map().from_vid_pid(vid, pid)
+    }
+
+    /// Product name for a device ID, when it names a single product.
+    pub fn name(device_id: u32) -> Option<String> {
+        if device_id == M1_V5_HE {
+            return Some("MonsGeek M1 V5 HE".to_string());
+        }
+        match map().by_device_id(device_id).as_slice() {
+            [only] => Some(only.name.clone()),
             _ => None,
         }
     }
@@ -512,4 +999,128 @@ mod tests {
 
         assert!(!no_update.has_updates(&current));
     }
+
+    fn cached(fetched_at: u64) -> CachedCheck {
+        CachedCheck {
+            fetched_at,
+            version_str: "usb_405".to_string(),
+            path: Some("/fw/m1v5.zip".to_string()),
+            lowest_app_version: None,
+            manifest: DownloadManifest::default(),
+        }
+    }
+
+    #[test]
+    fn cache_is_used_until_ttl_then_refetched() {
+        let now = 1_000_000;
+        let ttl = CACHE_TTL.as_secs();
+        assert_eq!(decide(None, now, false), Lookup::Fetch(None));
+        assert_eq!(
+            decide(Some(cached(now - 60)), now, false),
+            Lookup::Use(cached(now - 60))
+        );
+        assert_eq!(
+            decide(Some(cached(now - ttl)), now, false),
+            Lookup::Fetch(Some(cached(now - ttl)))
+        );
+        // Offline: any age will do
+        assert_eq!(
+            decide(Some(cached(now - 10 * ttl)), now, true),
+            Lookup::Use(cached(now - 10 * ttl))
+        );
+
+        let response = cached(now - 3600).cached_response(now);
+        assert_eq!(response.versions.usb, Some(0x405));
+        assert_eq!(
+            response.versions.download_path.as_deref(),
+            Some("/fw/m1v5.zip")
+        );
+        assert_eq!(response.cached_age, Some(Duration::from_secs(3600)));
+        assert_eq!(
+            FirmwareCheckResult::from_response(&response, 0x405).message,
+            "Up to date (cached 1h ago)"
+        );
+    }
+
+    #[cfg(feature = "firmware-api-async")]
+    #[test]
+    fn md5_matches_rfc_vectors() {
+        assert_eq!(hex(&md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(hex(&md5(b"abc")), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(hex(&md5(&[0x5a; 1000])), "00dde050b563dc1cd920ba043e0d5d70");
+    }
+
+    #[cfg(feature = "firmware-api-async")]
+    #[test]
+    fn manifest_verifies_downloads() {
+        let data = serde_json::json!({
+            "path": "/fw/m1v5.zip",
+            "fileSize": "3",
+            "file_md5": "900150983CD24FB0D6963F7D28E17F72",
+        });
+        let manifest = DownloadManifest::from_reply(&data);
+        assert_eq!(manifest.size, Some(3));
+        assert_eq!(
+            manifest.md5.as_deref(),
+            Some("900150983cd24fb0d6963f7d28e17f72")
+        );
+        assert_eq!(
+            manifest.verify(b"abc"),
+            Verification::Verified(vec!["size", "MD5"])
+        );
+        assert!(matches!(manifest.verify(b"abd"), Verification::Mismatch(_)));
+        assert!(matches!(
+            manifest.verify(b"abcd"),
+            Verification::Mismatch(_)
+        ));
+
+        let sha = DownloadManifest {
+            sha256: Some(
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+            ),
+            ..DownloadManifest::default()
+        };
+        assert_eq!(sha.verify(b"abc"), Verification::Verified(vec!["SHA-256"]));
+
+        let none = DownloadManifest::from_reply(&serde_json::json!({ "path": "/fw/x.zip" }));
+        assert!(none.is_empty());
+        assert_eq!(none.verify(b"abc"), Verification::Unavailable);
+    }
+
+    #[test]
+    fn download_cache_name_is_flat() {
+        let path = download_cache_path("/fw/2949/M1 V5 HE.zip");
+        assert_eq!(path.file_name().unwrap(), "fw_2949_M1_V5_HE.zip");
+        assert_eq!(path.parent().unwrap(), cache_dir().join("downloads"));
+    }
+
+    #[test]
+    fn device_id_map_only_answers_unambiguous_vid_pids() {
+        let db = crate::device_loader::DeviceDatabase::load_from_json(
+            r#"[
+                {"id": 790, "vid": 12625, "pid": 16405, "name": "a", "displayName": "5108B Plus", "company": "akko"},
+                {"id": 790, "vid": 12625, "pid": 16400, "name": "b", "displayName": "YZ-21", "company": "YUNZII"},
+                {"id": 3804, "vid": 12625, "pid": 16400, "name": "c", "displayName": "SK75 TMR"},
+                {"id": 2914, "vid": 12625, "pid": 20538, "name": "d", "displayName": "M5 PRO"},
+                {"id": -1, "vid": 12625, "pid": 4096, "name": "e", "displayName": "Special"}
+            ]"#,
+        )
+        .unwrap();
+        let map = device_ids::DeviceIdMap::from_database(&db);
+
+        assert_eq!(map.from_vid_pid(0x3151, 0x4015), Some(790));
+        assert_eq!(map.from_vid_pid(0x3151, 0x4010), None);
+        assert_eq!(map.candidates(0x3151, 0x4010).len(), 2);
+        assert_eq!(map.from_vid_pid(0x3151, 0x1000), None);
+        // Builtin rows win over a shared database entry
+        assert_eq!(map.from_vid_pid(0x3151, 0x503A), Some(device_ids::M1_V5_HE));
+
+        let names: Vec<_> = map
+            .by_device_id(790)
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["YUNZII YZ-21", "akko 5108B Plus"]);
+        assert_eq!(map.by_device_id(3804)[0].api_id(), 3804);
+    }
 }
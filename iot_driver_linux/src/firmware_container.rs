@@ -0,0 +1,390 @@
+// Firmware container formats besides raw .bin: Intel HEX, DFU (with the
+// standard 16-byte suffix) and ST DfuSe. Each decodes to the flat image the
+// bootloader is sent, plus whatever metadata the container carries (load
+// address, USB IDs). Used by `FirmwareFile::load`.
+
+use monsgeek_transport::{boot_mode_kind, BootModeKind};
+
+use crate::protocol::firmware_update::{self, FlashTarget};
+
+/// Flash base address of the AT32F405 (start of the bootloader).
+pub const FLASH_BASE: u32 = 0x0800_0000;
+
+/// Where application images start, after the 20KB bootloader.
+pub const APP_BASE: u32 = FLASH_BASE + firmware_update::USB_FIRMWARE_OFFSET as u32;
+
+/// Largest image `FirmwareFile::validate` accepts; also caps address gaps.
+const MAX_IMAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// File format a firmware image came in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Container {
+    #[default]
+    Bin,
+    Zip,
+    IntelHex,
+    /// Raw image with a DFU 1.1 suffix
+    Dfu,
+    /// ST DfuSe file (addressed elements)
+    DfuSe,
+}
+
+impl std::fmt::Display for Container {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bin => write!(f, "bin"),
+            Self::Zip => write!(f, "zip"),
+            Self::IntelHex => write!(f, "Intel HEX"),
+            Self::Dfu => write!(f, "DFU"),
+            Self::DfuSe => write!(f, "DfuSe"),
+        }
+    }
+}
+
+/// Metadata a container carried alongside the image.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContainerInfo {
+    pub container: Container,
+    /// Flash address of the first image byte (HEX and DfuSe only)
+    pub load_address: Option<u32>,
+    /// idVendor / idProduct from a DFU suffix (0xFFFF = any)
+    pub usb_id: Option<(u16, u16)>,
+    /// bcdDevice from a DFU suffix, usually the firmware version
+    pub bcd_device: Option<u16>,
+    /// DfuSe target name
+    pub target_name: Option<String>,
+}
+
+impl ContainerInfo {
+    fn new(container: Container) -> Self {
+        Self {
+            container,
+            ..Self::default()
+        }
+    }
+
+    /// Device the container's USB IDs point at, normal-mode or bootloader.
+    pub fn target(&self) -> Option<FlashTarget> {
+        let (vid, pid) = self.usb_id?;
+        if let Some(kind) = boot_mode_kind(vid, pid) {
+            return match kind {
+                BootModeKind::Keyboard => Some(FlashTarget::Keyboard),
+                BootModeKind::Dongle => Some(FlashTarget::Dongle),
+                BootModeKind::Rf => None,
+            };
+        }
+        if vid != firmware_update::VID {
+            return None;
+        }
+        [FlashTarget::Keyboard, FlashTarget::Dongle]
+            .into_iter()
+            .find(|t| t.normal_pid() == pid)
+    }
+
+    /// Problem with the load address, if the container gave one.
+    ///
+    /// Images must start at the bootloader (full dump) or at the application
+    /// region; anything else would be written to the wrong place.
+    pub fn check_address(&self) -> Result<(), String> {
+        match self.load_address {
+            None | Some(FLASH_BASE) | Some(APP_BASE) => Ok(()),
+            Some(addr) => Err(format!(
+                "image loads at 0x{addr:08X}, expected 0x{APP_BASE:08X} (application) \
+                 or 0x{FLASH_BASE:08X} (full dump)"
+            )),
+        }
+    }
+}
+
+/// Decode `data` if it is a container we know; `None` for raw images.
+pub fn decode(data: &[u8], filename: &str) -> Option<Result<(Vec<u8>, ContainerInfo), String>> {
+    if data.starts_with(b"DfuSe") {
+        return Some(parse_dfuse(data));
+    }
+    let name = filename.to_lowercase();
+    let hex_name = name.ends_with(".hex") || name.ends_with(".ihex");
+    if hex_name || looks_like_ihex(data) {
+        return Some(
+            std::str::from_utf8(data)
+                .map_err(|_| "Intel HEX file is not text".to_string())
+                .and_then(parse_ihex),
+        );
+    }
+    let suffix = DfuSuffix::parse(data)?;
+    Some(suffix.map(|s| {
+        let mut info = ContainerInfo::new(Container::Dfu);
+        info.usb_id = Some((s.vid, s.pid));
+        info.bcd_device = Some(s.bcd_device);
+        (data[..data.len() - s.len].to_vec(), info)
+    }))
+}
+
+fn looks_like_ihex(data: &[u8]) -> bool {
+    data.starts_with(b":")
+        && data
+            .iter()
+            .take(256)
+            .all(|b| b.is_ascii_hexdigit() || matches!(b, b':' | b'\r' | b'\n'))
+}
+
+/// Parse Intel HEX (record types 00, 01, 02, 04; 03/05 start addresses are
+/// ignored). Gaps between records are filled with 0xFF, as erased flash reads.
+pub fn parse_ihex(text: &str) -> Result<(Vec<u8>, ContainerInfo), String> {
+    let mut chunks: Vec<(u32, Vec<u8>)> = Vec::new();
+    let mut base: u32 = 0;
+    let mut ended = false;
+
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let at = |msg: &str| format!("line {}: {msg}", n + 1);
+        if ended {
+            return Err(at("data after end-of-file record"));
+        }
+        let hex = line
+            .strip_prefix(':')
+            .ok_or_else(|| at("record doesn't start with ':'"))?;
+        if hex.len() % 2 != 0 || hex.len() < 10 {
+            return Err(at("truncated record"));
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| at("invalid hex digit"))?;
+        let len = bytes[0] as usize;
+        if bytes.len() != len + 5 {
+            return Err(at("record length doesn't match its byte count"));
+        }
+        if bytes.iter().fold(0u8, |s, &b| s.wrapping_add(b)) != 0 {
+            return Err(at("checksum mismatch"));
+        }
+        let offset = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+        let payload = &bytes[4..4 + len];
+        match bytes[3] {
+            0x00 => chunks.push((base + offset, payload.to_vec())),
+            0x01 => ended = true,
+            0x02 if len == 2 => base = (u16::from_be_bytes([payload[0], payload[1]]) as u32) << 4,
+            0x04 if len == 2 => base = (u16::from_be_bytes([payload[0], payload[1]]) as u32) << 16,
+            0x03 | 0x05 => {}
+            t => return Err(at(&format!("unsupported record type {t:02X}"))),
+        }
+    }
+    if !ended {
+        return Err("missing end-of-file record".to_string());
+    }
+
+    let (start, image) = flatten(chunks)?;
+    let mut info = ContainerInfo::new(Container::IntelHex);
+    info.load_address = Some(start);
+    Ok((image, info))
+}
+
+/// Lay addressed chunks out as one image from the lowest address.
+fn flatten(mut chunks: Vec<(u32, Vec<u8>)>) -> Result<(u32, Vec<u8>), String> {
+    chunks.sort_by_key(|(addr, _)| *addr);
+    let Some(&(start, _)) = chunks.first() else {
+        return Err("no data records".to_string());
+    };
+    let mut image = Vec::new();
+    for (addr, data) in chunks {
+        let offset = (addr - start) as usize;
+        if offset < image.len() {
+            return Err(format!("overlapping data at 0x{addr:08X}"));
+        }
+        if offset > MAX_IMAGE_SIZE {
+            return Err(format!("data at 0x{addr:08X} is outside flash"));
+        }
+        image.resize(offset, 0xFF);
+        image.extend_from_slice(&data);
+    }
+    Ok((start, image))
+}
+
+/// The 16-byte DFU 1.1 file suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DfuSuffix {
+    pub bcd_device: u16,
+    pub pid: u16,
+    pub vid: u16,
+    /// Suffix length (bLength), stripped from the image
+    pub len: usize,
+}
+
+impl DfuSuffix {
+    /// Read the suffix: `None` without the "UFD" signature, `Err` when its
+    /// CRC doesn't match the file.
+    pub fn parse(data: &[u8]) -> Option<Result<Self, String>> {
+        let n = data.len();
+        if n < 16 || &data[n - 8..n - 5] != b"UFD" {
+            return None;
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+        let len = data[n - 5] as usize;
+        let stored = u32::from_le_bytes(data[n - 4..].try_into().unwrap());
+        if len < 16 || len > n {
+            return Some(Err(format!("DFU suffix length {len} is invalid")));
+        }
+        let crc = dfu_crc(&data[..n - 4]);
+        if crc != stored {
+            return Some(Err(format!(
+                "DFU suffix CRC 0x{stored:08X} doesn't match file (0x{crc:08X})"
+            )));
+        }
+        Some(Ok(Self {
+            bcd_device: u16_at(n - 16),
+            pid: u16_at(n - 14),
+            vid: u16_at(n - 12),
+            len,
+        }))
+    }
+}
+
+/// CRC-32 as the DFU spec and dfu-util compute it: reflected 0xEDB88320,
+/// initial 0xFFFFFFFF, no final inversion.
+pub fn dfu_crc(data: &[u8]) -> u32 {
+    data.iter().fold(0xFFFF_FFFF, |crc, &b| {
+        (0..8).fold(crc ^ b as u32, |c, _| {
+            if c & 1 != 0 {
+                (c >> 1) ^ 0xEDB8_8320
+            } else {
+                c >> 1
+            }
+        })
+    })
+}
+
+/// Parse an ST DfuSe file: the first target's elements, flattened.
+pub fn parse_dfuse(data: &[u8]) -> Result<(Vec<u8>, ContainerInfo), String> {
+    let mut info = ContainerInfo::new(Container::DfuSe);
+    let body = match DfuSuffix::parse(data) {
+        Some(suffix) => {
+            let s = suffix?;
+            info.usb_id = Some((s.vid, s.pid));
+            info.bcd_device = Some(s.bcd_device);
+            &data[..data.len() - s.len]
+        }
+        None => data,
+    };
+
+    let u32_at = |i: usize| -> Result<u32, String> {
+        body.get(i..i + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| "DfuSe file is truncated".to_string())
+    };
+    // Prefix: "DfuSe", bVersion, DFUImageSize(4), bTargets
+    let targets = *body.get(10).ok_or("DfuSe file is truncated")?;
+    if targets == 0 {
+        return Err("DfuSe file has no targets".to_string());
+    }
+    // Target prefix: "Target", bAlternateSetting, bTargetNamed(4),
+    // szTargetName(255), dwTargetSize(4), dwNbElements(4)
+    let t = 11;
+    if body.get(t..t + 6) != Some(b"Target".as_slice()) {
+        return Err("DfuSe target signature missing".to_string());
+    }
+    let elements = u32_at(t + 270)?;
+    if u32_at(t + 7)? != 0 {
+        let name = &body[t + 11..t + 266];
+        let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        info.target_name = Some(String::from_utf8_lossy(&name[..end]).into_owned());
+    }
+
+    let mut chunks = Vec::new();
+    let mut at = t + 274;
+    for _ in 0..elements {
+        let addr = u32_at(at)?;
+        let size = u32_at(at + 4)? as usize;
+        let data = body
+            .get(at + 8..at + 8 + size)
+            .ok_or("DfuSe element runs past the end of the file")?;
+        chunks.push((addr, data.to_vec()));
+        at += 8 + size;
+    }
+    let (start, image) = flatten(chunks)?;
+    info.load_address = Some(start);
+    Ok((image, info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One Intel HEX record with its checksum.
+    fn record(kind: u8, offset: u16, data: &[u8]) -> String {
+        let mut bytes = vec![data.len() as u8, (offset >> 8) as u8, offset as u8, kind];
+        bytes.extend_from_slice(data);
+        let sum = bytes.iter().fold(0u8, |s, &b| s.wrapping_add(b));
+        bytes.push(sum.wrapping_neg());
+        let hex: String = bytes.iter().map(|b| format!("{b:02X}")).collect();
+        format!(":{hex}\n")
+    }
+
+    #[test]
+    fn ihex_with_linear_address_and_gap() {
+        let text = [
+            record(0x04, 0, &[0x08, 0x00]),
+            record(0x00, 0x5000, &[1, 2, 3, 4]),
+            record(0x00, 0x5006, &[5, 6]),
+            record(0x01, 0, &[]),
+        ]
+        .concat();
+        let (image, info) = parse_ihex(&text).unwrap();
+        assert_eq!(image, vec![1, 2, 3, 4, 0xFF, 0xFF, 5, 6]);
+        assert_eq!(info.load_address, Some(APP_BASE));
+        assert!(info.check_address().is_ok());
+
+        let bad = text.replacen(":04500000", ":04500001", 1);
+        assert!(parse_ihex(&bad).unwrap_err().contains("checksum"));
+    }
+
+    #[test]
+    fn dfu_suffix_is_checked_and_stripped() {
+        let mut file = vec![0xAA; 100];
+        file.extend_from_slice(&[0x07, 0x04, 0x30, 0x50, 0x51, 0x31, 0x1A, 0x01]);
+        file.extend_from_slice(b"UFD");
+        file.push(16);
+        let crc = dfu_crc(&file);
+        file.extend_from_slice(&crc.to_le_bytes());
+
+        let (image, info) = decode(&file, "fw.dfu").unwrap().unwrap();
+        assert_eq!(image.len(), 100);
+        assert_eq!(info.usb_id, Some((0x3151, 0x5030)));
+        assert_eq!(info.bcd_device, Some(0x0407));
+        assert_eq!(info.target(), Some(FlashTarget::Keyboard));
+
+        file[0] = 0;
+        assert!(decode(&file, "fw.dfu").unwrap().is_err());
+        // No suffix: a raw image
+        assert!(decode(&[0xAA; 100], "fw.bin").is_none());
+    }
+
+    #[test]
+    fn dfuse_single_element() {
+        let payload = [9u8; 32];
+        let mut target = b"Target".to_vec();
+        target.push(0);
+        target.extend_from_slice(&1u32.to_le_bytes());
+        let mut name = b"Internal Flash".to_vec();
+        name.resize(255, 0);
+        target.extend_from_slice(&name);
+        target.extend_from_slice(&((8 + payload.len()) as u32).to_le_bytes());
+        target.extend_from_slice(&1u32.to_le_bytes());
+        target.extend_from_slice(&(FLASH_BASE + 0x6000).to_le_bytes());
+        target.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        target.extend_from_slice(&payload);
+
+        let mut file = b"DfuSe\x01".to_vec();
+        file.extend_from_slice(&((11 + target.len()) as u32).to_le_bytes());
+        file.push(1);
+        file.extend_from_slice(&target);
+
+        let (image, info) = parse_dfuse(&file).unwrap();
+        assert_eq!(image, payload);
+        assert_eq!(info.target_name.as_deref(), Some("Internal Flash"));
+        assert_eq!(info.load_address, Some(FLASH_BASE + 0x6000));
+        assert!(info.check_address().is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Firmware flashed by `iot_driver firmware flash`, kept for rollback.
+//!
+//! Every successful flash stores the image it sent (after bootloader
+//! stripping) under `installed/` in the firmware cache and appends a
+//! [`FlashRecord`] to `installed/history.json`: the version running before
+//! and after, the file it came from and the stored image. `firmware rollback`
+//! picks the image that was installed before the current one with
+//! [`rollback_candidate`]; that only works when the earlier version was
+//! itself flashed through us, since the vendor updater leaves no copy.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::firmware::FirmwareFile;
+use crate::firmware_api::cache_dir;
+
+/// Directory holding the stored images and the history file.
+pub fn history_dir() -> PathBuf {
+    cache_dir().join("installed")
+}
+
+fn history_path() -> PathBuf {
+    history_dir().join("history.json")
+}
+
+/// One successful flash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlashRecord {
+    /// Seconds since the Unix epoch
+    pub flashed_at: u64,
+    /// `keyboard` or `dongle`
+    pub target: String,
+    /// Device ID the keyboard reported (none for the dongle)
+    pub device_id: Option<u32>,
+    /// Version running before the flash, if it could be read
+    pub previous_version: Option<String>,
+    /// Version reported after the flash, if it could be read
+    pub version: Option<String>,
+    /// File the image was loaded from
+    pub file: String,
+    /// Stored copy of the flashed image, relative to [`history_dir`]
+    pub image: String,
+    pub size: usize,
+    pub checksum: u32,
+}
+
+impl FlashRecord {
+    pub fn image_path(&self) -> PathBuf {
+        history_dir().join(&self.image)
+    }
+
+    fn same_device(&self, target: &str, device_id: Option<u32>) -> bool {
+        self.target == target && self.device_id == device_id
+    }
+}
+
+/// All recorded flashes, oldest first. A missing or unreadable file is an
+/// empty history.
+pub fn load() -> Vec<FlashRecord> {
+    fs::read_to_string(history_path())
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// Store the image `fw` and append a record for it.
+pub fn record(
+    fw: &FirmwareFile,
+    file: &str,
+    target: &str,
+    device_id: Option<u32>,
+    previous_version: Option<String>,
+    version: Option<String>,
+) -> std::io::Result<FlashRecord> {
+    let dir = history_dir();
+    fs::create_dir_all(&dir)?;
+    let image = format!("{target}-{:08x}-{}.bin", fw.checksum, fw.size);
+    let path = dir.join(&image);
+    if !path.exists() {
+        fs::write(&path, &fw.data)?;
+    }
+
+    let entry = FlashRecord {
+        flashed_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        target: target.to_string(),
+        device_id,
+        previous_version,
+        version,
+        file: file.to_string(),
+        image,
+        size: fw.size,
+        checksum: fw.checksum,
+    };
+    let mut history = load();
+    history.push(entry.clone());
+    let json = serde_json::to_string_pretty(&history).map_err(std::io::Error::other)?;
+    fs::write(history_path(), json)?;
+    Ok(entry)
+}
+
+/// The image to go back to for a device now running `current`.
+///
+/// If the device no longer runs what we last flashed (the vendor updater was
+/// used since), that last image is the one to go back to. Otherwise it's the
+/// newest earlier flash of the version that was running before the last one,
+/// or, when that version wasn't read, the newest earlier flash of a
+/// different image.
+pub fn rollback_candidate<'a>(
+    history: &'a [FlashRecord],
+    target: &str,
+    device_id: Option<u32>,
+    current: Option<&str>,
+) -> Result<&'a FlashRecord, String> {
+    let mut flashes = history
+        .iter()
+        .rev()
+        .filter(|r| r.same_device(target, device_id));
+    let Some(last) = flashes.next() else {
+        return Err(format!(
+            "no firmware was flashed to this {target} with iot_driver, so no previous image is stored"
+        ));
+    };
+
+    if let (Some(current), Some(flashed)) = (current, last.version.as_deref()) {
+        if current != flashed {
+            return Ok(last);
+        }
+    }
+
+    flashes
+        .find(|r| match &last.previous_version {
+            Some(previous) => r.version.as_deref() == Some(previous.as_str()),
+            None => r.checksum != last.checksum,
+        })
+        .ok_or_else(|| match &last.previous_version {
+            Some(previous) => format!(
+                "firmware {previous} (installed before the last flash) wasn't flashed with iot_driver, so no copy is stored"
+            ),
+            None => "no earlier image is stored for this device".to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rec(prev: Option<&str>, version: &str, checksum: u32) -> FlashRecord {
+        FlashRecord {
+            flashed_at: 0,
+            target: "keyboard".to_string(),
+            device_id: Some(2949),
+            previous_version: prev.map(String::from),
+            version: Some(version.to_string()),
+            file: format!("fw-{version}.bin"),
+            image: format!("keyboard-{checksum:08x}-1.bin"),
+            size: 1,
+            checksum,
+        }
+    }
+
+    #[test]
+    fn rollback_goes_to_the_version_before_the_last_flash() {
+        let history = vec![
+            rec(Some("1.0.0"), "1.0.5", 5),
+            rec(Some("1.0.5"), "1.0.6", 6),
+            rec(Some("1.0.6"), "1.0.7", 7),
+        ];
+        let pick = rollback_candidate(&history, "keyboard", Some(2949), Some("1.0.7")).unwrap();
+        assert_eq!(pick.checksum, 6);
+
+        // Another device has nothing recorded
+        assert!(rollback_candidate(&history, "keyboard", Some(1), Some("1.0.7")).is_err());
+        assert!(rollback_candidate(&history, "dongle", None, None).is_err());
+
+        // The version before the first flash was never stored
+        assert!(rollback_candidate(&history[..1], "keyboard", Some(2949), Some("1.0.5")).is_err());
+    }
+
+    #[test]
+    fn rollback_after_an_outside_update_restores_our_last_image() {
+        let history = vec![rec(None, "1.0.5", 5), rec(None, "1.0.6", 6)];
+        let pick = rollback_candidate(&history, "keyboard", Some(2949), Some("1.0.9")).unwrap();
+        assert_eq!(pick.checksum, 6);
+
+        // Previous version unknown: the newest different image
+        let pick = rollback_candidate(&history, "keyboard", Some(2949), Some("1.0.6")).unwrap();
+        assert_eq!(pick.checksum, 5);
+    }
+}
@@ -1,7 +1,10 @@
 //! Firmware flash engine for the RY bootloader protocol.
 //!
 //! Handles entering bootloader mode, discovering the bootloader device,
-//! transferring firmware chunks, and reporting progress via a callback trait.
+//! transferring firmware chunks, checking that the device boots the new image
+//! afterwards, and reporting progress via a callback trait. [`EventProgress`]
+//! folds those callbacks into [`FlashEvent`]s, the one progress stream the
+//! CLI bar, the TUI and the gRPC `flashFirmware` stream all render.
 
 use std::ffi::CString;
 use std::fmt;
@@ -14,18 +17,64 @@ use monsgeek_transport::types::ChecksumType;
 use crate::firmware::FirmwareFile;
 use crate::protocol::firmware_update;
 
+/// Start-ack reads tried before the transfer is given up
+const ACK_ATTEMPTS: u32 = 3;
+
 /// Phases of the flash process (reported to progress callback).
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum FlashPhase {
+    #[default]
     Scanning,
     BootloaderDetected,
     EnteringBootloader,
-    WaitingForBootloader { elapsed_ms: u64, timeout_ms: u64 },
+    WaitingForBootloader {
+        elapsed_ms: u64,
+        timeout_ms: u64,
+    },
     BootloaderFound,
-    StartingTransfer { chunks: usize, size: usize },
+    StartingTransfer {
+        chunks: usize,
+        size: usize,
+    },
     TransferringData,
     CompletingTransfer,
     WaitingForReboot,
+    /// Waiting for the device to come back after the bootloader's checksum check
+    Verifying {
+        elapsed_ms: u64,
+        timeout_ms: u64,
+    },
+    /// Device re-enumerated in normal mode: the bootloader accepted the image
+    Verified,
+    /// Repeating a read the bootloader didn't answer
+    Retrying {
+        what: &'static str,
+        attempt: u32,
+    },
+    Complete,
+    Failed,
+}
+
+impl FlashPhase {
+    /// Stable snake_case name for machine consumers.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Scanning => "scanning",
+            Self::BootloaderDetected => "bootloader_detected",
+            Self::EnteringBootloader => "entering_bootloader",
+            Self::WaitingForBootloader { .. } => "waiting_for_bootloader",
+            Self::BootloaderFound => "bootloader_found",
+            Self::StartingTransfer { .. } => "starting_transfer",
+            Self::TransferringData => "transferring",
+            Self::CompletingTransfer => "completing_transfer",
+            Self::WaitingForReboot => "waiting_for_reboot",
+            Self::Verifying { .. } => "verifying",
+            Self::Verified => "verified",
+            Self::Retrying { .. } => "retrying",
+            Self::Complete => "complete",
+            Self::Failed => "failed",
+        }
+    }
 }
 
 impl fmt::Display for FlashPhase {
@@ -50,6 +99,21 @@ impl fmt::Display for FlashPhase {
             Self::TransferringData => write!(f, "Transferring firmware data"),
             Self::CompletingTransfer => write!(f, "Completing transfer"),
             Self::WaitingForReboot => write!(f, "Waiting for device reboot"),
+            Self::Verifying {
+                elapsed_ms,
+                timeout_ms,
+            } => write!(
+                f,
+                "Waiting for the device to boot the new firmware ({:.1}s / {:.1}s)",
+                *elapsed_ms as f64 / 1000.0,
+                *timeout_ms as f64 / 1000.0
+            ),
+            Self::Verified => write!(f, "Device is back in normal mode"),
+            Self::Retrying { what, attempt } => {
+                write!(f, "No {what}, retrying ({attempt}/{ACK_ATTEMPTS})")
+            }
+            Self::Complete => write!(f, "Flash complete"),
+            Self::Failed => write!(f, "Flash failed"),
         }
     }
 }
@@ -63,6 +127,8 @@ pub enum FlashError {
     BootloaderTimeout,
     TransferFailed(String),
     AckFailed(String),
+    /// The device did not come back running the new firmware
+    VerifyFailed(String),
     HidError(String),
 }
 
@@ -81,6 +147,7 @@ impl fmt::Display for FlashError {
             Self::BootloaderTimeout => write!(f, "Timeout waiting for bootloader device"),
             Self::TransferFailed(msg) => write!(f, "Transfer failed: {msg}"),
             Self::AckFailed(msg) => write!(f, "Ack failed: {msg}"),
+            Self::VerifyFailed(msg) => write!(f, "Verification failed: {msg}"),
             Self::HidError(msg) => write!(f, "HID error: {msg}"),
         }
     }
@@ -98,10 +165,117 @@ impl From<hidapi::HidError> for FlashError {
 pub trait FlashProgress: Send {
     fn on_phase(&mut self, phase: &FlashPhase);
     fn on_chunk(&mut self, sent: usize, total: usize);
+    /// A bootloader read went unanswered and is tried again
+    fn on_retry(&mut self, what: &'static str, attempt: u32);
     fn on_error(&mut self, error: &FlashError);
     fn on_complete(&mut self);
 }
 
+/// Progress of a flash as one self-contained snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct FlashEvent {
+    pub phase: FlashPhase,
+    /// 0-100, following the data transfer
+    pub percent: f32,
+    /// Chunks sent so far
+    pub chunk: usize,
+    pub total_chunks: usize,
+    /// Retried bootloader reads so far
+    pub retries: u32,
+    /// Why the flash failed (with [`FlashPhase::Failed`])
+    pub error: Option<String>,
+}
+
+impl FlashEvent {
+    /// Whether this is the last event of the flash.
+    pub fn is_final(&self) -> bool {
+        matches!(self.phase, FlashPhase::Complete | FlashPhase::Failed)
+    }
+}
+
+impl FlashProgress for FlashEvent {
+    fn on_phase(&mut self, phase: &FlashPhase) {
+        if let FlashPhase::StartingTransfer { chunks, .. } = phase {
+            self.total_chunks = *chunks;
+            self.chunk = 0;
+            self.percent = 0.0;
+        }
+        self.phase = phase.clone();
+    }
+
+    fn on_chunk(&mut self, sent: usize, total: usize) {
+        self.phase = FlashPhase::TransferringData;
+        self.chunk = sent;
+        self.total_chunks = total;
+        self.percent = if total == 0 {
+            100.0
+        } else {
+            sent as f32 * 100.0 / total as f32
+        };
+    }
+
+    fn on_retry(&mut self, what: &'static str, attempt: u32) {
+        self.retries += 1;
+        self.phase = FlashPhase::Retrying { what, attempt };
+    }
+
+    fn on_error(&mut self, error: &FlashError) {
+        self.phase = FlashPhase::Failed;
+        self.error = Some(error.to_string());
+    }
+
+    fn on_complete(&mut self) {
+        self.phase = FlashPhase::Complete;
+        self.percent = 100.0;
+    }
+}
+
+/// Keeps a [`FlashEvent`] up to date and hands every update to `sink`.
+pub struct EventProgress<F> {
+    event: FlashEvent,
+    sink: F,
+}
+
+impl<F: FnMut(&FlashEvent) + Send> EventProgress<F> {
+    pub fn new(sink: F) -> Self {
+        Self {
+            event: FlashEvent::default(),
+            sink,
+        }
+    }
+
+    fn emit(&mut self) {
+        (self.sink)(&self.event);
+    }
+}
+
+impl<F: FnMut(&FlashEvent) + Send> FlashProgress for EventProgress<F> {
+    fn on_phase(&mut self, phase: &FlashPhase) {
+        self.event.on_phase(phase);
+        self.emit();
+    }
+
+    fn on_chunk(&mut self, sent: usize, total: usize) {
+        self.event.on_chunk(sent, total);
+        self.emit();
+    }
+
+    fn on_retry(&mut self, what: &'static str, attempt: u32) {
+        self.event.on_retry(what, attempt);
+        self.emit();
+    }
+
+    fn on_error(&mut self, error: &FlashError) {
+        self.event.on_error(error);
+        self.emit();
+    }
+
+    fn on_complete(&mut self) {
+        self.event.on_complete();
+        self.emit();
+    }
+}
+
 /// Options for the flash operation.
 pub struct FlashOptions {
     /// Specific device path to use (None = autodetect).
@@ -112,6 +286,9 @@ pub struct FlashOptions {
     pub boot_entry_delay_ms: u64,
     /// Which device to target (keyboard or dongle).
     pub target: firmware_update::FlashTarget,
+    /// How long to wait for the device to come back after the transfer
+    /// (ms). 0 skips the check.
+    pub verify_timeout_ms: u64,
 }
 
 impl Default for FlashOptions {
@@ -121,6 +298,7 @@ impl Default for FlashOptions {
             bootloader_timeout_ms: 10_000,
             boot_entry_delay_ms: firmware_update::BOOT_ENTRY_DELAY_MS,
             target: firmware_update::FlashTarget::Keyboard,
+            verify_timeout_ms: 15_000,
         }
     }
 }
@@ -133,6 +311,67 @@ enum FlashTarget {
     AlreadyInBootloader(CString),
 }
 
+/// Whether `d` is the normal-mode interface of a `target` device.
+fn is_normal(d: &hidapi::DeviceInfo, target: firmware_update::FlashTarget) -> bool {
+    d.vendor_id() == firmware_update::VID
+        && d.product_id() == target.normal_pid()
+        && d.usage_page() == firmware_update::NORMAL_USAGE_PAGE
+}
+
+/// Serial number of the HID device at `path`, if it reports one.
+pub fn serial_at(path: &str) -> Option<String> {
+    let api = HidApi::new().ok()?;
+    let path = CString::new(path).ok()?;
+    let serial = api
+        .device_list()
+        .find(|d| d.path() == path.as_c_str())
+        .and_then(|d| d.serial_number())
+        .filter(|s| !s.is_empty())
+        .map(String::from);
+    serial
+}
+
+/// Which normal-mode device is the flashed one, once it comes back under a
+/// new path.
+struct Flashed {
+    /// Its serial number, if it was found in normal mode and reports one
+    serial: Option<String>,
+    /// Normal-mode devices of the same kind that were already there
+    others: Vec<CString>,
+}
+
+impl Flashed {
+    fn new(api: &HidApi, found: &FlashTarget, target: firmware_update::FlashTarget) -> Self {
+        let own = match found {
+            FlashTarget::Normal(path) => Some(path.as_c_str()),
+            FlashTarget::AlreadyInBootloader(_) => None,
+        };
+        let mut flashed = Self {
+            serial: None,
+            others: Vec::new(),
+        };
+        for d in api.device_list().filter(|d| is_normal(d, target)) {
+            if Some(d.path()) == own {
+                flashed.serial = d
+                    .serial_number()
+                    .filter(|s| !s.is_empty())
+                    .map(String::from);
+            } else {
+                flashed.others.push(d.path().to_owned());
+            }
+        }
+        flashed
+    }
+
+    fn is(&self, d: &hidapi::DeviceInfo) -> bool {
+        let serial_ok = match &self.serial {
+            Some(serial) => d.serial_number() == Some(serial.as_str()),
+            None => true,
+        };
+        serial_ok && !self.others.iter().any(|p| p.as_c_str() == d.path())
+    }
+}
+
 /// Scan HID bus for a single flash target.
 fn find_flash_target(
     api: &HidApi,
@@ -256,6 +495,83 @@ fn poll_for_bootloader(
     }
 }
 
+/// Whether the flashed device is back in normal mode, and whether a
+/// bootloader device of `target` is present.
+fn present(target: firmware_update::FlashTarget, flashed: &Flashed) -> (bool, bool) {
+    let Ok(api) = HidApi::new() else {
+        return (false, false);
+    };
+    let boot_pids = target.boot_vid_pids();
+    let mut normal = false;
+    let mut boot = false;
+    for d in api.device_list() {
+        if is_normal(d, target) && flashed.is(d) {
+            normal = true;
+        }
+        if boot_pids.contains(&(d.vendor_id(), d.product_id()))
+            && d.usage_page() == firmware_update::BOOT_USAGE_PAGE
+        {
+            boot = true;
+        }
+    }
+    (normal, boot)
+}
+
+/// Wait for the device to reboot out of the bootloader after FW_TRANSFER_COMPLETE.
+///
+/// The bootloader clears its mailbox and boots the image only if the checksum
+/// matched (PROTOCOL.md 8.3). Otherwise it reboots into itself again, so a
+/// bootloader device that reappears after dropping off means the image was
+/// rejected. Other devices of the same kind don't count as it coming back.
+fn verify_reboot(
+    timeout_ms: u64,
+    progress: &mut dyn FlashProgress,
+    target: firmware_update::FlashTarget,
+    flashed: &Flashed,
+) -> Result<(), FlashError> {
+    let start = std::time::Instant::now();
+    let poll_interval = std::time::Duration::from_millis(300);
+    let mut left_bootloader = false;
+
+    loop {
+        let elapsed_ms = start.elapsed().as_millis() as u64;
+        let (normal, boot) = present(target, flashed);
+        if normal {
+            progress.on_phase(&FlashPhase::Verified);
+            return Ok(());
+        }
+        if !boot {
+            left_bootloader = true;
+        } else if left_bootloader {
+            return Err(FlashError::VerifyFailed(format!(
+                "the {} rebooted into the bootloader again; it rejected the image \
+                 (checksum mismatch or transfer error). Nothing was booted, so \
+                 flashing again is safe.",
+                target.name()
+            )));
+        }
+        if elapsed_ms > timeout_ms {
+            let state = if boot {
+                "is still in bootloader mode"
+            } else {
+                "has not re-enumerated"
+            };
+            return Err(FlashError::VerifyFailed(format!(
+                "the {} {state} after {:.0}s. Replug it; if it comes back as a \
+                 bootloader device, flash again.",
+                target.name(),
+                timeout_ms as f64 / 1000.0
+            )));
+        }
+
+        progress.on_phase(&FlashPhase::Verifying {
+            elapsed_ms,
+            timeout_ms,
+        });
+        std::thread::sleep(poll_interval);
+    }
+}
+
 /// Send the ENTER_BOOTLOADER sequence to a normal-mode device.
 ///
 /// Sends ISP_PREPARE (0xC5, param 0x3A) first, matching the official app,
@@ -331,11 +647,23 @@ fn do_transfer(
     dev.send_feature_report(&boot_feature_buf(&start_payload))
         .map_err(|e| FlashError::TransferFailed(format!("FW_TRANSFER_START failed: {e}")))?;
 
-    // 2. Read ack
-    let mut ack_buf = [0u8; 65];
-    ack_buf[0] = 0x00; // request report ID 0
-    dev.get_feature_report(&mut ack_buf)
-        .map_err(|e| FlashError::AckFailed(format!("Start ack failed: {e}")))?;
+    // 2. Read ack. Reading is side-effect free, so a missed one is retried.
+    let mut attempt = 1;
+    loop {
+        let mut ack_buf = [0u8; 65];
+        ack_buf[0] = 0x00; // request report ID 0
+        match dev.get_feature_report(&mut ack_buf) {
+            Ok(_) => break,
+            Err(e) if attempt >= ACK_ATTEMPTS => {
+                return Err(FlashError::AckFailed(format!("Start ack failed: {e}")));
+            }
+            Err(_) => {
+                progress.on_retry("start ack", attempt);
+                attempt += 1;
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+        }
+    }
 
     // 3. Send firmware chunks
     progress.on_phase(&FlashPhase::TransferringData);
@@ -374,8 +702,6 @@ fn do_transfer(
     final_ack[0] = 0x00;
     // May fail if device reboots immediately — that's fine
     let _ = dev.get_feature_report(&mut final_ack);
-
-    progress.on_complete();
     Ok(())
 }
 
@@ -386,13 +712,28 @@ fn do_transfer(
 /// 2. Device discovery and autodetection
 /// 3. Entering bootloader mode (if needed)
 /// 4. Firmware transfer
-/// 5. Progress reporting
+/// 5. Waiting for the device to boot the new image (unless
+///    `verify_timeout_ms` is 0)
+/// 6. Progress reporting, ending in `on_complete` or `on_error`
 ///
 /// Runs synchronously (blocking) — call from `spawn_blocking` if needed.
 pub fn flash_firmware(
     firmware: &FirmwareFile,
     progress: &mut dyn FlashProgress,
     options: &FlashOptions,
+) -> Result<(), FlashError> {
+    let result = run_flash(firmware, progress, options);
+    match &result {
+        Ok(()) => progress.on_complete(),
+        Err(e) => progress.on_error(e),
+    }
+    result
+}
+
+fn run_flash(
+    firmware: &FirmwareFile,
+    progress: &mut dyn FlashProgress,
+    options: &FlashOptions,
 ) -> Result<(), FlashError> {
     // Validate firmware first
     firmware
@@ -418,6 +759,7 @@ pub fn flash_firmware(
     progress.on_phase(&FlashPhase::Scanning);
     let api = HidApi::new()?;
     let target = find_flash_target(&api, options.device_path.as_deref(), options.target)?;
+    let flashed = Flashed::new(&api, &target, options.target);
 
     // Drop the HidApi — we'll re-create as needed (required after device re-enumeration)
     drop(api);
@@ -447,5 +789,46 @@ pub fn flash_firmware(
     // Transfer firmware
     do_transfer(&boot_path, firmware, progress)?;
 
+    if options.verify_timeout_ms > 0 {
+        verify_reboot(
+            options.verify_timeout_ms,
+            progress,
+            options.target,
+            &flashed,
+        )?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_track_transfer_retries_and_outcome() {
+        let mut seen = Vec::new();
+        let mut progress = EventProgress::new(|e: &FlashEvent| seen.push(e.clone()));
+        progress.on_phase(&FlashPhase::StartingTransfer {
+            chunks: 4,
+            size: 256,
+        });
+        progress.on_retry("start ack", 1);
+        progress.on_phase(&FlashPhase::TransferringData);
+        progress.on_chunk(1, 4);
+        progress.on_chunk(4, 4);
+        progress.on_error(&FlashError::VerifyFailed("rejected".into()));
+        drop(progress);
+
+        assert_eq!(seen.len(), 6);
+        assert_eq!(seen[1].phase.id(), "retrying");
+        assert_eq!(seen[1].retries, 1);
+        assert_eq!((seen[3].chunk, seen[3].total_chunks), (1, 4));
+        assert_eq!(seen[3].percent, 25.0);
+        assert_eq!(seen[4].percent, 100.0);
+        let last = &seen[5];
+        assert!(last.is_final());
+        assert_eq!(last.phase.id(), "failed");
+        assert_eq!(last.retries, 1);
+        assert!(last.error.as_deref().unwrap().contains("rejected"));
+    }
+}
@@ -0,0 +1,504 @@
+//! Per-application profile and lighting switching.
+//!
+//! Configured under `[app_profiles]` in `settings.toml` and run by the
+//! daemon. The focused window comes from `zwlr_foreign_toplevel_manager_v1`
+//! on wlroots compositors (Sway, Hyprland, niri, river, …) or from
+//! `_NET_ACTIVE_WINDOW` on X11 (read with `xprop`). When an application with
+//! a rule gains focus its profile and lighting are applied; when focus moves
+//! to one without a rule, the profile and lighting from before come back.
+//! GNOME and KDE don't tell Wayland clients which window is focused (the
+//! desktop portal has no such interface), so there only X11 apps are seen.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::LightingPreset;
+use crate::wayland::{Request, Wire};
+use monsgeek_keyboard::led::LedParams;
+use monsgeek_keyboard::KeyboardInterface;
+
+const TOPLEVEL_MANAGER: &str = "zwlr_foreign_toplevel_manager_v1";
+/// `zwlr_foreign_toplevel_handle_v1.state` value for the focused window.
+const STATE_ACTIVATED: u32 = 2;
+/// How long focus has to stay put before switching; the old window loses
+/// focus before the new one gains it.
+const SETTLE: Duration = Duration::from_millis(200);
+/// How often the daemon checks whether switching got enabled.
+const CONFIG_RELOAD: Duration = Duration::from_secs(5);
+/// Wait before reconnecting after the window system connection ended.
+const RETRY: Duration = Duration::from_secs(30);
+
+/// The window with keyboard focus.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FocusedWindow {
+    /// Wayland `app_id`, or the class part of X11 `WM_CLASS`
+    pub app_id: String,
+    pub title: String,
+}
+
+/// One application rule: which windows, and what to switch to.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AppRule {
+    /// Case-insensitive substrings of the app id (Wayland `app_id`, X11
+    /// `WM_CLASS`)
+    pub apps: Vec<String>,
+    /// Also require the window title to contain this (case-insensitive)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(flatten)]
+    pub lighting: LightingPreset,
+}
+
+impl AppRule {
+    /// Whether the rule covers a window.
+    pub fn matches(&self, window: &FocusedWindow) -> bool {
+        let app_id = window.app_id.to_lowercase();
+        let title_matches = self
+            .title
+            .as_ref()
+            .is_none_or(|t| window.title.to_lowercase().contains(&t.to_lowercase()));
+        !app_id.is_empty()
+            && title_matches
+            && self
+                .apps
+                .iter()
+                .any(|pattern| app_id.contains(&pattern.to_lowercase()))
+    }
+}
+
+/// `[app_profiles]` settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppProfiles {
+    pub enabled: bool,
+    /// Checked in order; the first match wins
+    pub apps: Vec<AppRule>,
+}
+
+impl AppProfiles {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The first rule matching a window, if any.
+    pub fn rule_for(&self, window: &FocusedWindow) -> Option<&AppRule> {
+        self.apps.iter().find(|r| r.matches(window))
+    }
+}
+
+/// What to do on a focus change.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Switch {
+    /// Nothing changes
+    Keep,
+    /// Apply a rule's preset, saving the current state first if `save`
+    Apply { preset: LightingPreset, save: bool },
+    /// Put back the saved profile and lighting
+    Restore { profile: u8, params: LedParams },
+}
+
+/// Switches presets as focus moves between applications and restores the
+/// state from before the first switch.
+#[derive(Debug, Default)]
+pub struct FocusSwitcher {
+    /// Profile and lighting before the first switch
+    saved: Option<(u8, LedParams)>,
+    active: Option<LightingPreset>,
+}
+
+impl FocusSwitcher {
+    pub fn is_switched(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Decide what to do when a window matching `rule` (or none) gets focus.
+    pub fn plan(&mut self, rule: Option<&AppRule>) -> Switch {
+        let preset = rule.map(|r| r.lighting.clone());
+        if preset == self.active {
+            return Switch::Keep;
+        }
+        self.active = preset.clone();
+        match preset {
+            Some(preset) => Switch::Apply {
+                preset,
+                save: self.saved.is_none(),
+            },
+            None => match self.saved.take() {
+                Some((profile, params)) => Switch::Restore { profile, params },
+                None => Switch::Keep,
+            },
+        }
+    }
+
+    /// Apply a focus change to the keyboard.
+    pub fn focus(
+        &mut self,
+        keyboard: &KeyboardInterface,
+        rule: Option<&AppRule>,
+    ) -> Result<(), String> {
+        let result = match self.plan(rule) {
+            Switch::Keep => Ok(()),
+            Switch::Apply { preset, save } => {
+                if save {
+                    let profile = keyboard.get_profile().map_err(|e| e.to_string())?;
+                    let params = keyboard.get_led_params().map_err(|e| e.to_string())?;
+                    self.saved = Some((profile, params));
+                }
+                preset.apply(keyboard)
+            }
+            Switch::Restore { profile, params } => {
+                let current = keyboard.get_profile().map_err(|e| e.to_string())?;
+                if current != profile {
+                    keyboard.set_profile(profile).map_err(|e| e.to_string())?;
+                }
+                keyboard.set_led_params(&params).map_err(|e| e.to_string())
+            }
+        };
+        if result.is_err() {
+            // Try again on the next focus change
+            self.active = None;
+        }
+        result
+    }
+}
+
+/// Follow the focused window, calling `on_focus` for every change. Uses
+/// wlr-foreign-toplevel under Wayland when the compositor has it, else X11.
+/// Returns when the connection ends or neither is available.
+pub fn watch(mut on_focus: impl FnMut(FocusedWindow)) -> Result<(), String> {
+    let x11 = std::env::var_os("DISPLAY").is_some_and(|d| !d.is_empty());
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        match watch_wayland(&mut on_focus) {
+            Err(e) if x11 => tracing::debug!("focus: {e}, using X11"),
+            result => return result,
+        }
+    }
+    if !x11 {
+        return Err("no Wayland or X11 session".into());
+    }
+    watch_x11(&mut on_focus)
+}
+
+/// Per-window state from wlr-foreign-toplevel, applied on `done`.
+#[derive(Debug, Default)]
+struct Toplevel {
+    window: FocusedWindow,
+    activated: bool,
+    pending: FocusedWindow,
+    pending_activated: bool,
+}
+
+fn watch_wayland(on_focus: &mut impl FnMut(FocusedWindow)) -> Result<(), String> {
+    let mut wire = Wire::connect(None)?;
+    let registry = wire.registry()?;
+    let (global, version) = registry
+        .find(TOPLEVEL_MANAGER)
+        .ok_or("compositor does not support wlr-foreign-toplevel")?;
+    let manager = registry.bind(&mut wire, global, TOPLEVEL_MANAGER, version.min(3))?;
+
+    let mut toplevels: HashMap<u32, Toplevel> = HashMap::new();
+    let mut focused = None;
+    loop {
+        let event = wire.next_event()?;
+        let mut args = event.args();
+        if event.object == manager {
+            match event.opcode {
+                0 => {
+                    toplevels.insert(args.uint()?, Toplevel::default());
+                }
+                1 => return Err("compositor stopped sending windows".into()),
+                _ => {}
+            }
+            continue;
+        }
+        let id = event.object;
+        let Some(toplevel) = toplevels.get_mut(&id) else {
+            continue;
+        };
+        match event.opcode {
+            0 => toplevel.pending.title = args.string()?,
+            1 => toplevel.pending.app_id = args.string()?,
+            4 => toplevel.pending_activated = args.uint_array()?.contains(&STATE_ACTIVATED),
+            // done: apply the pending state
+            5 => {
+                let changed = toplevel.window != toplevel.pending;
+                toplevel.window = toplevel.pending.clone();
+                toplevel.activated = toplevel.pending_activated;
+                if toplevel.activated && (changed || focused != Some(id)) {
+                    focused = Some(id);
+                    on_focus(toplevel.window.clone());
+                } else if !toplevel.activated && focused == Some(id) {
+                    focused = None;
+                    on_focus(FocusedWindow::default());
+                }
+            }
+            // closed
+            6 => {
+                toplevels.remove(&id);
+                wire.send(Request::new(id, 7))?; // handle.destroy
+                if focused == Some(id) {
+                    focused = None;
+                    on_focus(FocusedWindow::default());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn watch_x11(on_focus: &mut impl FnMut(FocusedWindow)) -> Result<(), String> {
+    let mut child = Command::new("xprop")
+        .args(["-root", "-spy", "_NET_ACTIVE_WINDOW"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("failed to run xprop (install x11-utils / xorg-xprop): {e}"))?;
+    let stdout = child.stdout.take().ok_or("xprop has no stdout")?;
+    for line in BufReader::new(stdout).lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let window = parse_active_window(&line)
+            .and_then(x11_window)
+            .unwrap_or_default();
+        on_focus(window);
+    }
+    let _ = child.wait();
+    Err("xprop exited".into())
+}
+
+/// Window id from an `xprop -spy _NET_ACTIVE_WINDOW` line; `None` when no
+/// window has focus.
+fn parse_active_window(line: &str) -> Option<&str> {
+    let id = line.rsplit_once('#')?.1.trim();
+    let id = id.split(',').next()?.trim();
+    let value = u32::from_str_radix(id.strip_prefix("0x")?, 16).ok()?;
+    (value != 0).then_some(id)
+}
+
+fn x11_window(id: &str) -> Option<FocusedWindow> {
+    let output = Command::new("xprop")
+        .args(["-id", id, "WM_CLASS", "_NET_WM_NAME", "WM_NAME"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    Some(parse_x11_window(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// App id (the `WM_CLASS` class) and title from `xprop -id` output.
+fn parse_x11_window(output: &str) -> FocusedWindow {
+    let mut window = FocusedWindow::default();
+    let mut net_wm_name = None;
+    for line in output.lines() {
+        let Some((name, value)) = line.split_once(" = ") else {
+            continue;
+        };
+        let strings = xprop_strings(value);
+        match name.split('(').next() {
+            Some("WM_CLASS") => window.app_id = strings.last().cloned().unwrap_or_default(),
+            Some("_NET_WM_NAME") => net_wm_name = strings.into_iter().next(),
+            Some("WM_NAME") if window.title.is_empty() => {
+                window.title = strings.into_iter().next().unwrap_or_default()
+            }
+            _ => {}
+        }
+    }
+    if let Some(title) = net_wm_name {
+        window.title = title;
+    }
+    window
+}
+
+/// The quoted strings in an xprop value (`"a", "b"`), unescaped.
+fn xprop_strings(value: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut chars = value.chars();
+    while chars.by_ref().any(|c| c == '"') {
+        let mut s = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => s.extend(chars.next()),
+                '"' => break,
+                c => s.push(c),
+            }
+        }
+        strings.push(s);
+    }
+    strings
+}
+
+/// Daemon loop: switch presets per `[app_profiles]` as focus moves,
+/// re-reading the settings on every change. The keyboard is opened on
+/// demand and reopened after errors.
+pub fn run_daemon_focus(open: impl Fn() -> Result<KeyboardInterface, String>) {
+    let mut switcher = FocusSwitcher::default();
+    let mut keyboard = None;
+    let mut warned = false;
+    loop {
+        if !crate::settings::Settings::load().app_profiles.enabled {
+            std::thread::sleep(CONFIG_RELOAD);
+            continue;
+        }
+        let (tx, rx) = mpsc::channel();
+        let watcher = std::thread::spawn(move || {
+            watch(|window| {
+                let _ = tx.send(window);
+            })
+        });
+        while let Ok(mut window) = rx.recv() {
+            while let Ok(next) = rx.recv_timeout(SETTLE) {
+                window = next;
+            }
+            let config = crate::settings::Settings::load().app_profiles;
+            let rule = config.enabled.then(|| config.rule_for(&window)).flatten();
+            tracing::debug!(
+                "focus: {:?} ({:?}){}",
+                window.app_id,
+                window.title,
+                if rule.is_some() { ", matched" } else { "" }
+            );
+            if rule.is_none() && !switcher.is_switched() {
+                continue;
+            }
+            if keyboard.is_none() {
+                keyboard = open()
+                    .map_err(|e| tracing::debug!("focus: no keyboard: {e}"))
+                    .ok();
+            }
+            if let Some(kb) = &keyboard {
+                if let Err(e) = switcher.focus(kb, rule) {
+                    tracing::warn!("focus: {e}");
+                    keyboard = None;
+                }
+            }
+        }
+        match watcher.join() {
+            Ok(Err(e)) if !warned => {
+                tracing::warn!("focus: can't follow the focused window: {e}");
+                warned = true;
+            }
+            Ok(Err(e)) => tracing::debug!("focus: {e}"),
+            _ => {}
+        }
+        std::thread::sleep(RETRY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(app_id: &str, title: &str) -> FocusedWindow {
+        FocusedWindow {
+            app_id: app_id.to_string(),
+            title: title.to_string(),
+        }
+    }
+
+    fn rule(apps: &[&str], profile: u8) -> AppRule {
+        AppRule {
+            apps: apps.iter().map(|a| a.to_string()).collect(),
+            title: None,
+            lighting: LightingPreset {
+                profile: Some(profile),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn rules_match_app_id_and_title() {
+        let game = rule(&["steam_app_"], 2);
+        assert!(game.matches(&window("steam_app_1091500", "Cyberpunk 2077")));
+        assert!(!game.matches(&window("firefox", "steam_app_ in a tab")));
+
+        let titled = AppRule {
+            title: Some("YouTube".into()),
+            ..rule(&["Firefox"], 1)
+        };
+        assert!(titled.matches(&window("firefox", "Music - youtube — Mozilla Firefox")));
+        assert!(!titled.matches(&window("firefox", "Docs")));
+        assert!(!rule(&[""], 0).matches(&FocusedWindow::default()));
+    }
+
+    #[test]
+    fn saves_once_and_restores_when_focus_leaves() {
+        let (game, editor) = (rule(&["game"], 2), rule(&["code"], 1));
+        let mut switcher = FocusSwitcher::default();
+        assert_eq!(switcher.plan(None), Switch::Keep);
+
+        let Switch::Apply { save, .. } = switcher.plan(Some(&game)) else {
+            panic!("expected apply");
+        };
+        assert!(save);
+        switcher.saved = Some((0, LedParams::default()));
+        assert_eq!(switcher.plan(Some(&game)), Switch::Keep);
+        // Moving between two apps with rules keeps the original state
+        assert_eq!(
+            switcher.plan(Some(&editor)),
+            Switch::Apply {
+                preset: editor.lighting.clone(),
+                save: false
+            }
+        );
+        assert_eq!(
+            switcher.plan(None),
+            Switch::Restore {
+                profile: 0,
+                params: LedParams::default()
+            }
+        );
+        assert!(!switcher.is_switched());
+        assert_eq!(switcher.plan(None), Switch::Keep);
+    }
+
+    #[test]
+    fn parses_xprop_output() {
+        assert_eq!(
+            parse_active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007"),
+            Some("0x3a00007")
+        );
+        assert_eq!(
+            parse_active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x0"),
+            None
+        );
+        assert_eq!(parse_active_window("_NET_ACTIVE_WINDOW:  not found."), None);
+
+        let window = parse_x11_window(
+            "WM_CLASS(STRING) = \"Navigator\", \"firefox\"\n\
+             _NET_WM_NAME(UTF8_STRING) = \"Say \\\"hi\\\" — Mozilla Firefox\"\n\
+             WM_NAME(STRING) = \"Say hi\"\n",
+        );
+        assert_eq!(
+            window,
+            self::window("firefox", "Say \"hi\" — Mozilla Firefox")
+        );
+        assert_eq!(
+            parse_x11_window("WM_NAME(STRING) = \"xterm\"\n"),
+            self::window("", "xterm")
+        );
+    }
+
+    #[test]
+    fn loads_from_toml() {
+        let config: AppProfiles = toml::from_str(
+            r##"
+            enabled = true
+            [[apps]]
+            apps = ["steam_app_", "gamescope"]
+            profile = 2
+            [[apps]]
+            apps = ["code"]
+            mode = "constant"
+            color = "#0080FF"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(config.apps[0].lighting.profile, Some(2));
+        let rule = config.rule_for(&window("Code", "main.rs")).unwrap();
+        assert_eq!(rule.lighting.color.as_deref(), Some("#0080FF"));
+    }
+}
@@ -4,7 +4,7 @@
 // Now uses the transport abstraction layer for unified device access
 // across wired, 2.4GHz dongle, and Bluetooth connections.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::pin::Pin;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -21,8 +21,8 @@ use iot_driver::effect::{self, EffectLibrary};
 use iot_driver::hal::HidInterface;
 use monsgeek_keyboard::KeyboardInterface;
 use monsgeek_transport::{
-    ChecksumType, DeviceDiscovery, HidDiscovery, PrinterConfig, TimestampedEvent, Transport,
-    TransportType, VendorEvent,
+    ChecksumType, DeviceDiscovery, FlowControlTransport, HidDiscovery, PrinterConfig,
+    TimestampedEvent, Transport, TransportType, VendorEvent,
 };
 
 #[allow(non_camel_case_types)] // Proto types use camelCase to match original iot_driver.exe
@@ -38,6 +38,9 @@ pub use driver::*;
 const DEVICE_CHANNEL_SIZE: usize = 16;
 const VENDOR_CHANNEL_SIZE: usize = 256;
 
+/// Most operations accepted in one `batch` call
+const MAX_BATCH_OPS: usize = 512;
+
 /// Connected device with transport
 struct ConnectedTransport {
     transport: Arc<dyn Transport>,
@@ -46,6 +49,152 @@ struct ConnectedTransport {
     is_dongle: bool,
     vid: u16,
     pid: u16,
+    /// Stable ID clients may use instead of the path key
+    stable_id: String,
+}
+
+impl ConnectedTransport {
+    /// Proto description of this device as opened at `path`.
+    fn device(&self, path: &str, battery: u32, is_online: bool) -> Device {
+        Device {
+            dev_type: DeviceType::YzwKeyboard as i32,
+            is24: self.is_dongle,
+            path: path.to_string(),
+            id: self.device_id,
+            battery,
+            is_online,
+            vid: self.vid as u32,
+            pid: self.pid as u32,
+            stable_id: self.stable_id.clone(),
+        }
+    }
+}
+
+/// Publishes `watchDeviceState` events. Remembers the stable IDs of
+/// unplugged devices so a returning device is reported as reconnected.
+#[derive(Clone)]
+struct DeviceStateFeed {
+    tx: broadcast::Sender<DeviceStateEvent>,
+    unplugged: Arc<std::sync::Mutex<HashSet<String>>>,
+}
+
+impl DeviceStateFeed {
+    fn new() -> Self {
+        let (tx, _) = broadcast::channel(DEVICE_CHANNEL_SIZE);
+        Self {
+            tx,
+            unplugged: Arc::new(std::sync::Mutex::new(HashSet::new())),
+        }
+    }
+
+    fn added(&self, device: Device) {
+        let state = if self.unplugged.lock().unwrap().remove(&device.stable_id) {
+            DeviceState::DeviceReconnected
+        } else {
+            DeviceState::DeviceAdded
+        };
+        self.send(state, device);
+    }
+
+    fn removed(&self, device: Device) {
+        self.unplugged
+            .lock()
+            .unwrap()
+            .insert(device.stable_id.clone());
+        self.send(DeviceState::DeviceRemoved, device);
+    }
+
+    fn send(&self, state: DeviceState, device: Device) {
+        info!("Device state: {:?} {}", state, device.stable_id);
+        let _ = self.tx.send(DeviceStateEvent {
+            state: state as i32,
+            device: Some(device),
+        });
+    }
+}
+
+/// Keyboard opened for LED streaming (`None` until the first LED RPC)
+type LedKeyboard = Arc<AsyncMutex<Option<KeyboardInterface>>>;
+
+/// Effect render task and the keyboard it plays on
+type RunningEffect = (LedKeyboard, tokio::task::JoinHandle<()>);
+
+/// Stable device ID: `vid:pid:serial`, or `vid:pid:device_id` when the
+/// device reports no serial. Unlike the path key it survives replugs and
+/// hidraw renumbering; `#2`, `#3`, ... separate identical devices without
+/// serials (in the order they were opened).
+fn stable_device_id(
+    devices: &HashMap<String, ConnectedTransport>,
+    path: &str,
+    info: &monsgeek_transport::TransportDeviceInfo,
+    device_id: i32,
+) -> String {
+    let serial = info
+        .serial
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    let base = match serial {
+        Some(serial) => format!("{:04x}:{:04x}:{serial}", info.vid, info.pid),
+        None => format!("{:04x}:{:04x}:{device_id}", info.vid, info.pid),
+    };
+    let taken = |id: &str| devices.iter().any(|(p, c)| p != path && c.stable_id == id);
+    if !taken(&base) {
+        return base;
+    }
+    (2..)
+        .map(|n| format!("{base}#{n}"))
+        .find(|id| !taken(id))
+        .expect("unbounded suffixes")
+}
+
+fn batch_err(err: impl Into<String>) -> BatchResult {
+    BatchResult {
+        err: err.into(),
+        msg: Vec::new(),
+    }
+}
+
+/// Run one batch op on `transport` (the device at `path`).
+fn run_batch_op(
+    op: BatchOp,
+    path: &str,
+    transport: &Arc<dyn Transport>,
+    flows: &mut HashMap<String, FlowControlTransport>,
+) -> Result<BatchResult, String> {
+    let split = |m: &SendMsg| -> Result<(u8, Vec<u8>, ChecksumType), String> {
+        let (&cmd, payload) = m.msg.split_first().ok_or("Empty command data")?;
+        let checksum = CheckSumType::try_from(m.check_sum_type).unwrap_or(CheckSumType::Bit7);
+        Ok((cmd, payload.to_vec(), proto_to_transport_checksum(checksum)))
+    };
+    let msg = match op.op {
+        Some(batch_op::Op::Send(m)) => {
+            let (cmd, payload, checksum) = split(&m)?;
+            transport
+                .send_report(cmd, &payload, checksum)
+                .map_err(|e| format!("Send error: {e}"))?;
+            transport
+                .send_flush()
+                .map_err(|e| format!("Flush error: {e}"))?;
+            Vec::new()
+        }
+        Some(batch_op::Op::Read(_)) => transport
+            .read_report()
+            .map_err(|e| format!("Read error: {e}"))?,
+        Some(batch_op::Op::Query(m)) => {
+            let (cmd, payload, checksum) = split(&m)?;
+            flows
+                .entry(path.to_string())
+                .or_insert_with(|| FlowControlTransport::new(Arc::clone(transport)))
+                .query_command(cmd, &payload, checksum)
+                .map_err(|e| format!("Query error: {e}"))?
+        }
+        None => return Err("Empty batch op".to_string()),
+    };
+    Ok(BatchResult {
+        err: String::new(),
+        msg,
+    })
 }
 
 /// Convert proto CheckSumType to transport ChecksumType
@@ -157,16 +306,67 @@ impl Drop for VendorSubscriberGuard {
     }
 }
 
+/// Register (`true`) or drop (`false`) interest in key depth reports.
+/// Magnetism reporting is switched on for every open device with the
+/// first subscriber and off again after the last one.
+async fn set_key_depth_reporting(
+    devices: &AsyncMutex<HashMap<String, ConnectedTransport>>,
+    subscribers: &AtomicUsize,
+    enable: bool,
+) {
+    let before = if enable {
+        subscribers.fetch_add(1, Ordering::AcqRel)
+    } else {
+        subscribers.fetch_sub(1, Ordering::AcqRel)
+    };
+    let toggles = if enable { before == 0 } else { before == 1 };
+    if !toggles {
+        return;
+    }
+    use monsgeek_transport::protocol::cmd::SET_MAGNETISM_REPORT;
+    let devices = devices.lock().await;
+    for (path, connected) in devices.iter() {
+        if let Err(e) = connected.transport.send_report(
+            SET_MAGNETISM_REPORT,
+            &[enable as u8],
+            ChecksumType::Bit7,
+        ) {
+            debug!("Key depth reporting on {}: {}", path, e);
+        }
+    }
+    info!(
+        "Key depth reporting {}",
+        if enable { "enabled" } else { "disabled" }
+    );
+}
+
+/// Drops a `watchKeyDepth` client's interest in key depth reports when its
+/// stream ends.
+struct KeyDepthGuard {
+    devices: Arc<AsyncMutex<HashMap<String, ConnectedTransport>>>,
+    subscribers: Arc<AtomicUsize>,
+}
+
+impl Drop for KeyDepthGuard {
+    fn drop(&mut self) {
+        let devices = Arc::clone(&self.devices);
+        let subscribers = Arc::clone(&self.subscribers);
+        tokio::spawn(async move {
+            set_key_depth_reporting(&devices, &subscribers, false).await;
+        });
+    }
+}
+
 // Stream wrapper that holds a drop guard alongside the inner stream
 pin_project_lite::pin_project! {
-    struct GuardedStream<S> {
+    struct GuardedStream<S, G = VendorSubscriberGuard> {
         #[pin]
         inner: S,
-        _guard: VendorSubscriberGuard,
+        _guard: G,
     }
 }
 
-impl<S: Stream> Stream for GuardedStream<S> {
+impl<S: Stream, G> Stream for GuardedStream<S, G> {
     type Item = S::Item;
 
     fn poll_next(
@@ -177,6 +377,24 @@ impl<S: Stream> Stream for GuardedStream<S> {
     }
 }
 
+/// A decoded vendor event and the device that sent it.
+#[derive(Debug, Clone)]
+pub struct DeviceEvent {
+    /// Stable ID of the sending device
+    pub device: String,
+    /// Seconds since the device was opened, taken when the report was read
+    pub timestamp: f64,
+    pub event: VendorEvent,
+}
+
+/// Decoded vendor events from every open device, for consumers other than
+/// `watchVender` (e.g. the WebSocket event stream). Keeps event polling
+/// running while alive.
+pub struct EventSubscription {
+    pub rx: broadcast::Receiver<DeviceEvent>,
+    _guard: VendorSubscriberGuard,
+}
+
 /// Key for the in-memory DB: (dbPath, key)
 type DbKey = (String, Vec<u8>);
 
@@ -185,16 +403,20 @@ pub struct DriverService {
     discovery: Arc<HidDiscovery>,
     devices: Arc<AsyncMutex<HashMap<String, ConnectedTransport>>>,
     device_tx: broadcast::Sender<DeviceList>,
+    device_state: DeviceStateFeed,
     vendor_tx: broadcast::Sender<VenderMsg>,
+    event_tx: broadcast::Sender<DeviceEvent>,
     vendor_polling: Arc<AsyncMutex<bool>>,
     vendor_subscribers: Arc<AtomicUsize>,
+    /// Clients that want key depth reports (magnetism reporting is on while > 0)
+    depth_subscribers: Arc<AtomicUsize>,
     hotplug_running: Arc<std::sync::Mutex<bool>>,
     /// In-memory key-value store for webapp DB RPCs
     db: Arc<AsyncMutex<HashMap<DbKey, Vec<u8>>>>,
-    /// Lazily-opened keyboard for LED streaming RPCs
-    led_kb: Arc<AsyncMutex<Option<KeyboardInterface>>>,
-    /// Running effect render tasks (effect_id -> JoinHandle)
-    led_effects: Arc<AsyncMutex<HashMap<u64, tokio::task::JoinHandle<()>>>>,
+    /// Lazily-opened keyboards for LED streaming RPCs, by stable ID
+    led_kbs: Arc<AsyncMutex<HashMap<String, LedKeyboard>>>,
+    /// Running effect render tasks (effect_id -> keyboard, JoinHandle)
+    led_effects: Arc<AsyncMutex<HashMap<u64, RunningEffect>>>,
     /// Next effect ID counter
     led_next_id: Arc<AsyncMutex<u64>>,
 }
@@ -205,6 +427,7 @@ impl DriverService {
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let (device_tx, _) = broadcast::channel(DEVICE_CHANNEL_SIZE);
         let (vendor_tx, _) = broadcast::channel(VENDOR_CHANNEL_SIZE);
+        let (event_tx, _) = broadcast::channel(VENDOR_CHANNEL_SIZE);
 
         // Create discovery with printer config - wrapping happens automatically in open_device()
         let discovery = match printer_config {
@@ -216,12 +439,15 @@ impl DriverService {
             discovery: Arc::new(discovery),
             devices: Arc::new(AsyncMutex::new(HashMap::new())),
             device_tx,
+            device_state: DeviceStateFeed::new(),
             vendor_tx,
+            event_tx,
             vendor_polling: Arc::new(AsyncMutex::new(false)),
             vendor_subscribers: Arc::new(AtomicUsize::new(0)),
+            depth_subscribers: Arc::new(AtomicUsize::new(0)),
             hotplug_running: Arc::new(std::sync::Mutex::new(false)),
             db: Arc::new(AsyncMutex::new(HashMap::new())),
-            led_kb: Arc::new(AsyncMutex::new(None)),
+            led_kbs: Arc::new(AsyncMutex::new(HashMap::new())),
             led_effects: Arc::new(AsyncMutex::new(HashMap::new())),
             led_next_id: Arc::new(AsyncMutex::new(1)),
         })
@@ -231,6 +457,7 @@ impl DriverService {
     fn start_vendor_polling(&self) {
         let devices = Arc::clone(&self.devices);
         let vendor_tx = self.vendor_tx.clone();
+        let event_tx = self.event_tx.clone();
         let vendor_polling = Arc::clone(&self.vendor_polling);
         let vendor_subscribers = Arc::clone(&self.vendor_subscribers);
 
@@ -245,11 +472,11 @@ impl DriverService {
 
             info!("Started vendor event polling");
 
-            // Track persistent receivers per device path
+            // Track persistent receivers (and stable IDs) per device path
             // Using subscribe_events() gives us a receiver that persists across the loop,
             // so we don't miss events between iterations (unlike read_event() which
             // creates a new receiver each call)
-            let mut receivers: HashMap<String, broadcast::Receiver<TimestampedEvent>> =
+            let mut receivers: HashMap<String, (String, broadcast::Receiver<TimestampedEvent>)> =
                 HashMap::new();
 
             loop {
@@ -282,7 +509,7 @@ impl DriverService {
                         if !receivers.contains_key(path) {
                             if let Some(rx) = connected.transport.subscribe_events() {
                                 debug!("Subscribed to events for device {}", path);
-                                receivers.insert(path.clone(), rx);
+                                receivers.insert(path.clone(), (connected.stable_id.clone(), rx));
                             }
                         }
                     }
@@ -291,13 +518,21 @@ impl DriverService {
                 // Poll all receivers with short timeout
                 let mut got_event = false;
                 let mut closed = Vec::new();
-                for (path, rx) in receivers.iter_mut() {
+                for (path, (device, rx)) in receivers.iter_mut() {
                     match tokio::time::timeout(std::time::Duration::from_millis(1), rx.recv()).await
                     {
                         Ok(Ok(timestamped)) => {
                             debug!("Vendor event from {}: {:?}", path, timestamped.event);
                             let msg = vendor_event_to_bytes(&timestamped.event);
-                            let _ = vendor_tx.send(VenderMsg { msg });
+                            let _ = vendor_tx.send(VenderMsg {
+                                msg,
+                                device_id: device.clone(),
+                            });
+                            let _ = event_tx.send(DeviceEvent {
+                                device: device.clone(),
+                                timestamp: timestamped.timestamp,
+                                event: timestamped.event,
+                            });
                             got_event = true;
                         }
                         Ok(Err(broadcast::error::RecvError::Lagged(n))) => {
@@ -323,6 +558,24 @@ impl DriverService {
         });
     }
 
+    /// Subscribe to decoded vendor events from all open devices.
+    pub fn subscribe_events(&self) -> EventSubscription {
+        self.vendor_subscribers.fetch_add(1, Ordering::Release);
+        self.start_vendor_polling();
+        EventSubscription {
+            rx: self.event_tx.subscribe(),
+            _guard: VendorSubscriberGuard(Arc::clone(&self.vendor_subscribers)),
+        }
+    }
+
+    /// Register (`true`) or drop (`false`) interest in key depth reports.
+    /// Magnetism reporting is switched on for every open device with the
+    /// first subscriber and off again after the last one.
+    #[cfg_attr(not(feature = "rest"), allow(dead_code))]
+    pub async fn set_key_depth_reporting(&self, enable: bool) {
+        set_key_depth_reporting(&self.devices, &self.depth_subscribers, enable).await;
+    }
+
     /// Start hot-plug monitoring using udev (runs in a separate thread)
     pub fn start_hotplug_monitor(&self) {
         let mut running = self.hotplug_running.lock().unwrap();
@@ -335,6 +588,7 @@ impl DriverService {
         let discovery = Arc::clone(&self.discovery);
         let devices = Arc::clone(&self.devices);
         let device_tx = self.device_tx.clone();
+        let device_state = self.device_state.clone();
         let hotplug_running = Arc::clone(&self.hotplug_running);
 
         // Use a standard thread since udev types aren't Send
@@ -412,14 +666,20 @@ impl DriverService {
                             info!("Device added: {:?}", devnode);
                             // Re-scan and broadcast new devices
                             rt.block_on(Self::rescan_devices_static(
-                                &discovery, &devices, &device_tx,
+                                &discovery,
+                                &devices,
+                                &device_tx,
+                                &device_state,
                             ));
                         }
                         EventType::Remove => {
                             info!("Device removed: {:?}", devnode);
                             // Clean up disconnected devices and broadcast removal
                             rt.block_on(Self::cleanup_disconnected_static(
-                                &discovery, &devices, &device_tx,
+                                &discovery,
+                                &devices,
+                                &device_tx,
+                                &device_state,
                             ));
                         }
                         _ => {}
@@ -436,6 +696,7 @@ impl DriverService {
         discovery: &Arc<HidDiscovery>,
         devices: &Arc<AsyncMutex<HashMap<String, ConnectedTransport>>>,
         device_tx: &broadcast::Sender<DeviceList>,
+        device_state: &DeviceStateFeed,
     ) {
         let discovered = match discovery.list_devices() {
             Ok(d) => d,
@@ -471,36 +732,27 @@ impl DriverService {
                         (100, true)
                     };
 
-                    let dev_info = Device {
-                        dev_type: DeviceType::YzwKeyboard as i32,
-                        is24: dev.info.is_dongle,
-                        path: path.clone(),
-                        id: device_id,
-                        battery,
-                        is_online,
-                        vid: dev.info.vid as u32,
-                        pid: dev.info.pid as u32,
+                    let stable_id = stable_device_id(&devs, &path, &dev.info, device_id);
+                    // Transport is already wrapped by HidDiscovery if monitoring enabled
+                    let connected = ConnectedTransport {
+                        transport,
+                        device_id,
+                        is_dongle: dev.info.is_dongle,
+                        vid: dev.info.vid,
+                        pid: dev.info.pid,
+                        stable_id,
                     };
+                    let dev_info = connected.device(&path, battery, is_online);
+                    devs.insert(path, connected);
 
                     // Broadcast new device
+                    device_state.added(dev_info.clone());
                     let _ = device_tx.send(DeviceList {
                         dev_list: vec![DjDev {
                             oneof_dev: Some(dj_dev::OneofDev::Dev(dev_info)),
                         }],
                         r#type: DeviceListChangeType::Add as i32,
                     });
-
-                    // Transport is already wrapped by HidDiscovery if monitoring enabled
-                    devs.insert(
-                        path,
-                        ConnectedTransport {
-                            transport,
-                            device_id,
-                            is_dongle: dev.info.is_dongle,
-                            vid: dev.info.vid,
-                            pid: dev.info.pid,
-                        },
-                    );
                 }
                 Err(e) => {
                     warn!("Hot-plug: failed to open device: {}", e);
@@ -514,6 +766,7 @@ impl DriverService {
         discovery: &Arc<HidDiscovery>,
         devices: &Arc<AsyncMutex<HashMap<String, ConnectedTransport>>>,
         device_tx: &broadcast::Sender<DeviceList>,
+        device_state: &DeviceStateFeed,
     ) {
         let discovered = match discovery.list_devices() {
             Ok(d) => d,
@@ -539,17 +792,10 @@ impl DriverService {
             info!("Hot-plug: removing disconnected device {}", path);
             if let Some(removed) = devs.remove(&path) {
                 // Broadcast removal with cached device info so webapp can identify it
+                let device = removed.device(&path, 0, false);
+                device_state.removed(device.clone());
                 let removed_dev = DjDev {
-                    oneof_dev: Some(dj_dev::OneofDev::Dev(Device {
-                        dev_type: DeviceType::YzwKeyboard as i32,
-                        is24: removed.is_dongle,
-                        path: path.clone(),
-                        id: removed.device_id,
-                        battery: 0,
-                        is_online: false,
-                        vid: removed.vid as u32,
-                        pid: removed.pid as u32,
-                    })),
+                    oneof_dev: Some(dj_dev::OneofDev::Dev(device)),
                 };
                 let _ = device_tx.send(DeviceList {
                     dev_list: vec![removed_dev],
@@ -639,13 +885,13 @@ impl DriverService {
                 devices.contains_key(&path)
             };
 
-            let (device_id, battery, is_online) = if already_open {
+            let (device_id, battery, is_online, stable_id) = if already_open {
                 debug!("Device {} already open, using cached info", path);
                 let devices = self.devices.lock().await;
                 let connected = devices.get(&path).unwrap();
                 // Use cached device_id — never re-query a live transport
                 // (would interleave with TUI/webapp commands)
-                (connected.device_id, 100, true)
+                (connected.device_id, 100, true, connected.stable_id.clone())
             } else {
                 // Open new device
                 match self.discovery.open_device(&dev) {
@@ -668,25 +914,27 @@ impl DriverService {
                         };
 
                         // Transport is already wrapped by HidDiscovery if monitoring enabled
-                        {
-                            let mut devices = self.devices.lock().await;
-                            devices.insert(
-                                path.clone(),
-                                ConnectedTransport {
-                                    transport,
-                                    device_id: id,
-                                    is_dongle: dev.info.is_dongle,
-                                    vid: dev.info.vid,
-                                    pid: dev.info.pid,
-                                },
-                            );
-                        }
+                        let mut devices = self.devices.lock().await;
+                        let stable_id = stable_device_id(&devices, &path, &dev.info, id);
+                        let connected = ConnectedTransport {
+                            transport,
+                            device_id: id,
+                            is_dongle: dev.info.is_dongle,
+                            vid: dev.info.vid,
+                            pid: dev.info.pid,
+                            stable_id: stable_id.clone(),
+                        };
+                        self.device_state
+                            .added(connected.device(&path, batt, online));
+                        devices.insert(path.clone(), connected);
 
-                        (id, batt, online)
+                        (id, batt, online, stable_id)
                     }
                     Err(e) => {
                         warn!("Could not open device to query ID: {}", e);
-                        (0, 100, true)
+                        let devices = self.devices.lock().await;
+                        let stable_id = stable_device_id(&devices, &path, &dev.info, 0);
+                        (0, 100, true, stable_id)
                     }
                 }
             };
@@ -710,6 +958,7 @@ impl DriverService {
                     mouse_id: 0,
                     vid: dev.info.vid as u32,
                     pid: dev.info.pid as u32,
+                    stable_id,
                 };
                 found.push(DjDev {
                     oneof_dev: Some(dj_dev::OneofDev::DangleCommonDev(dongle)),
@@ -725,6 +974,7 @@ impl DriverService {
                     is_online,
                     vid: dev.info.vid as u32,
                     pid: dev.info.pid as u32,
+                    stable_id,
                 };
                 found.push(DjDev {
                     oneof_dev: Some(dj_dev::OneofDev::Dev(device)),
@@ -775,32 +1025,93 @@ impl DriverService {
             .discovery
             .open_device(dev)
             .map_err(|e| Status::internal(format!("Failed to open device: {}", e)))?;
+        let device_id = Self::query_device_id_static(&transport).await.unwrap_or(0);
 
         let mut devices = self.devices.lock().await;
-        devices.insert(
-            device_path.to_string(),
-            ConnectedTransport {
-                transport,
-                device_id: 0,
-                is_dongle: dev.info.is_dongle,
-                vid: dev.info.vid,
-                pid: dev.info.pid,
-            },
-        );
+        let stable_id = stable_device_id(&devices, device_path, &dev.info, device_id);
+        let connected = ConnectedTransport {
+            transport,
+            device_id,
+            is_dongle: dev.info.is_dongle,
+            vid: dev.info.vid,
+            pid: dev.info.pid,
+            stable_id,
+        };
+        self.device_state
+            .added(connected.device(device_path, 100, true));
+        devices.insert(device_path.to_string(), connected);
 
         info!("Opened device: {}", device_path);
         Ok(())
     }
 
-    /// Open keyboard for LED streaming, caching it for reuse.
-    /// Returns error if no patched device found.
-    async fn ensure_led_kb(&self) -> Result<(), Status> {
-        let mut guard = self.led_kb.lock().await;
+    /// Path key and stable ID of an open device, looked up by either.
+    async fn find_device(&self, id: &str) -> Option<(String, String)> {
+        let devices = self.devices.lock().await;
+        devices
+            .iter()
+            .find(|(path, c)| *path == id || c.stable_id == id)
+            .map(|(path, c)| (path.clone(), c.stable_id.clone()))
+    }
+
+    /// Map a client's device reference (path key or stable ID) to a path key.
+    /// Unknown stable IDs trigger a rescan; anything else is passed through
+    /// for `open_device` to parse.
+    async fn resolve_path(&self, id: &str) -> String {
+        if let Some((path, _)) = self.find_device(id).await {
+            return path;
+        }
+        if parse_device_path(id).is_none() {
+            self.scan_devices().await;
+            if let Some((path, _)) = self.find_device(id).await {
+                return path;
+            }
+        }
+        id.to_string()
+    }
+
+    /// The device an LED RPC targets: `requested` (stable ID or path key),
+    /// or the only connected device when empty. Returns (stable ID, path key).
+    async fn led_target(&self, requested: &str) -> Result<(String, String), Status> {
+        if requested.is_empty() {
+            if self.devices.lock().await.is_empty() {
+                self.scan_devices().await;
+            }
+            let devices = self.devices.lock().await;
+            let mut ids: Vec<_> = devices.values().map(|c| c.stable_id.as_str()).collect();
+            return match devices.iter().next() {
+                None => Err(Status::unavailable("No keyboard found")),
+                Some((path, c)) if devices.len() == 1 => Ok((c.stable_id.clone(), path.clone())),
+                Some(_) => {
+                    ids.sort_unstable();
+                    Err(Status::invalid_argument(format!(
+                        "Several keyboards connected, set device_id to one of: {}",
+                        ids.join(", ")
+                    )))
+                }
+            };
+        }
+        let path = self.resolve_path(requested).await;
+        self.find_device(&path)
+            .await
+            .map(|(path, id)| (id, path))
+            .ok_or_else(|| Status::not_found(format!("Unknown device {requested}")))
+    }
+
+    /// Open the keyboard for LED streaming on `requested` (see `led_target`),
+    /// caching it per device for reuse.
+    /// Returns error if the device is not patched for LED streaming.
+    async fn ensure_led_kb(&self, requested: &str) -> Result<LedKeyboard, Status> {
+        let (stable_id, path) = self.led_target(requested).await?;
+        let slot = Arc::clone(self.led_kbs.lock().await.entry(stable_id).or_default());
+        let mut guard = slot.lock().await;
         if guard.is_some() {
-            return Ok(());
+            drop(guard);
+            return Ok(slot);
         }
 
-        let kb = crate::commands::open_keyboard(&crate::commands::CmdCtx::default())
+        let selector = path.split_once('@').map(|(_, p)| format!("path:{p}"));
+        let kb = crate::commands::open_keyboard(&crate::commands::CmdCtx::new(None, selector))
             .map_err(|e| Status::unavailable(format!("No keyboard found: {e}")))?;
 
         let patch = kb
@@ -828,7 +1139,8 @@ impl DriverService {
         }
 
         *guard = Some(kb);
-        Ok(())
+        drop(guard);
+        Ok(slot)
     }
 
     /// Send command immediately to the device
@@ -844,6 +1156,7 @@ impl DriverService {
         checksum: ChecksumType,
     ) -> Result<(), Status> {
         // Ensure device is open
+        let device_path = &self.resolve_path(device_path).await;
         self.open_device(device_path).await?;
 
         let devices = self.devices.lock().await;
@@ -879,6 +1192,7 @@ impl DriverService {
     /// The command was already sent in send_command, so we just read.
     async fn read_response(&self, device_path: &str) -> Result<Vec<u8>, Status> {
         // Ensure device is open
+        let device_path = &self.resolve_path(device_path).await;
         self.open_device(device_path).await?;
 
         let devices = self.devices.lock().await;
@@ -898,15 +1212,92 @@ impl DriverService {
 
         Ok(response)
     }
+
+    /// Run a batch in order. Devices are resolved and opened up front, then
+    /// the ops run one after another on a blocking thread, each waiting for
+    /// the previous one to finish. Queries share one flow-control wrapper per
+    /// device; what the batch saves is the gRPC round trip per op.
+    async fn run_batch(&self, ops: Vec<BatchOp>, stop_on_error: bool) -> Vec<BatchResult> {
+        let mut paths = Vec::with_capacity(ops.len());
+        for op in &ops {
+            let path = match &op.op {
+                Some(batch_op::Op::Send(m) | batch_op::Op::Query(m)) => &m.device_path,
+                Some(batch_op::Op::Read(m)) => &m.device_path,
+                None => {
+                    paths.push(Err("Empty batch op".to_string()));
+                    continue;
+                }
+            };
+            let path = self.resolve_path(path).await;
+            paths.push(match self.open_device(&path).await {
+                Ok(()) => Ok(path),
+                Err(e) => Err(e.message().to_string()),
+            });
+        }
+
+        let transports: HashMap<String, Arc<dyn Transport>> = {
+            let devices = self.devices.lock().await;
+            paths
+                .iter()
+                .flatten()
+                .filter_map(|p| Some((p.clone(), Arc::clone(&devices.get(p)?.transport))))
+                .collect()
+        };
+
+        let run = move || {
+            let mut flows: HashMap<String, FlowControlTransport> = HashMap::new();
+            let mut failed = false;
+            ops.into_iter()
+                .zip(paths)
+                .map(|(op, path)| {
+                    if failed && stop_on_error {
+                        return batch_err("Skipped after an earlier error");
+                    }
+                    let result = path.and_then(|path| {
+                        let transport = transports
+                            .get(&path)
+                            .ok_or_else(|| "Device not connected".to_string())?;
+                        run_batch_op(op, &path, transport, &mut flows)
+                    });
+                    result.unwrap_or_else(|e| {
+                        failed = true;
+                        batch_err(e)
+                    })
+                })
+                .collect()
+        };
+        tokio::task::spawn_blocking(run)
+            .await
+            .unwrap_or_else(|e| vec![batch_err(format!("Batch failed: {e}"))])
+    }
+}
+
+/// Proto form of a flash engine progress event.
+fn flash_progress_event(event: &iot_driver::flash::FlashEvent) -> FlashProgressEvent {
+    FlashProgressEvent {
+        phase: event.phase.id().to_string(),
+        message: event.phase.to_string(),
+        percent: event.percent,
+        chunk: event.chunk as u32,
+        total_chunks: event.total_chunks as u32,
+        retries: event.retries,
+        err: event.error.clone().unwrap_or_default(),
+        done: event.is_final(),
+    }
 }
 
 #[tonic::async_trait]
 #[allow(non_camel_case_types)]
 impl DriverGrpc for DriverService {
     type watchDevListStream = Pin<Box<dyn Stream<Item = Result<DeviceList, Status>> + Send>>;
+    type watchDeviceStateStream =
+        Pin<Box<dyn Stream<Item = Result<DeviceStateEvent, Status>> + Send>>;
     type watchSystemInfoStream = Pin<Box<dyn Stream<Item = Result<SystemInfo, Status>> + Send>>;
     type upgradeOTAGATTStream = Pin<Box<dyn Stream<Item = Result<Progress, Status>> + Send>>;
     type watchVenderStream = Pin<Box<dyn Stream<Item = Result<VenderMsg, Status>> + Send>>;
+    type watchKeyDepthStream = Pin<Box<dyn Stream<Item = Result<KeyDepthEvent, Status>> + Send>>;
+    type flashFirmwareStream =
+        Pin<Box<dyn Stream<Item = Result<FlashProgressEvent, Status>> + Send>>;
 
     async fn watch_dev_list(
         &self,
@@ -940,6 +1331,23 @@ impl DriverGrpc for DriverService {
         Ok(Response::new(Box::pin(combined)))
     }
 
+    async fn list_devices(&self, _request: Request<Empty>) -> Result<Response<DeviceList>, Status> {
+        Ok(Response::new(DeviceList {
+            dev_list: self.scan_devices().await,
+            r#type: DeviceListChangeType::Init as i32,
+        }))
+    }
+
+    async fn watch_device_state(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::watchDeviceStateStream>, Status> {
+        info!("watch_device_state called");
+        let stream = BroadcastStream::new(self.device_state.tx.subscribe())
+            .filter_map(|result| async move { result.ok().map(Ok) });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
     async fn watch_system_info(
         &self,
         _request: Request<Empty>,
@@ -1028,6 +1436,22 @@ impl DriverGrpc for DriverService {
         }
     }
 
+    async fn batch(
+        &self,
+        request: Request<BatchRequest>,
+    ) -> Result<Response<BatchResponse>, Status> {
+        let req = request.into_inner();
+        debug!("batch: {} ops", req.ops.len());
+        if req.ops.len() > MAX_BATCH_OPS {
+            return Err(Status::invalid_argument(format!(
+                "Batch has {} ops, at most {MAX_BATCH_OPS} allowed",
+                req.ops.len()
+            )));
+        }
+        let results = self.run_batch(req.ops, req.stop_on_error).await;
+        Ok(Response::new(BatchResponse { results }))
+    }
+
     async fn get_item_from_db(&self, request: Request<GetItem>) -> Result<Response<Item>, Status> {
         let req = request.into_inner();
         let key_str = String::from_utf8_lossy(&req.key);
@@ -1128,6 +1552,29 @@ impl DriverGrpc for DriverService {
         }))
     }
 
+    async fn reload_device_database(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<DeviceDatabaseInfo>, Status> {
+        let registry = iot_driver::profile_registry();
+        let reply = match registry.reload_device_database() {
+            Ok((devices, version)) => DeviceDatabaseInfo {
+                devices: devices as u32,
+                version,
+                err: String::new(),
+            },
+            Err(e) => {
+                let (devices, version) = registry.device_database_stats().unwrap_or_default();
+                DeviceDatabaseInfo {
+                    devices: devices as u32,
+                    version,
+                    err: e,
+                }
+            }
+        };
+        Ok(Response::new(reply))
+    }
+
     async fn upgrade_otagatt(
         &self,
         _request: Request<OtaUpgrade>,
@@ -1135,6 +1582,61 @@ impl DriverGrpc for DriverService {
         Ok(Response::new(Box::pin(futures::stream::empty())))
     }
 
+    async fn flash_firmware(
+        &self,
+        request: Request<FlashFirmwareRequest>,
+    ) -> Result<Response<Self::flashFirmwareStream>, Status> {
+        use iot_driver::firmware::FirmwareFile;
+        use iot_driver::flash::{self, EventProgress, FlashEvent, FlashOptions};
+        use iot_driver::protocol::firmware_update::FlashTarget;
+
+        let req = request.into_inner();
+        let target = if req.dongle {
+            FlashTarget::Dongle
+        } else {
+            FlashTarget::Keyboard
+        };
+        let name = if req.file_name.is_empty() {
+            "firmware.bin".to_string()
+        } else {
+            req.file_name
+        };
+        let firmware = FirmwareFile::from_bytes_for(req.file_buf, name, target)
+            .map_err(|e| Status::invalid_argument(format!("Invalid firmware file: {e}")))?;
+        if let Some(image) = firmware.target().filter(|&t| t != target) {
+            return Err(Status::failed_precondition(format!(
+                "{} is a {} image, not a {} one",
+                firmware.filename,
+                image.name(),
+                target.name()
+            )));
+        }
+        let options = FlashOptions {
+            device_path: (!req.dev_path.is_empty()).then_some(req.dev_path),
+            target,
+            ..Default::default()
+        };
+        info!(
+            "flash_firmware: {} to the {}",
+            firmware.filename,
+            target.name()
+        );
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::task::spawn_blocking(move || {
+            let mut progress = EventProgress::new(|event: &FlashEvent| {
+                let _ = tx.send(Ok(flash_progress_event(event)));
+            });
+            if let Err(e) = flash::flash_firmware(&firmware, &mut progress, &options) {
+                warn!("flash_firmware failed: {e}");
+            }
+        });
+
+        Ok(Response::new(Box::pin(
+            tokio_stream::wrappers::UnboundedReceiverStream::new(rx),
+        )))
+    }
+
     async fn mute_microphone(
         &self,
         _request: Request<MuteMicrophone>,
@@ -1202,6 +1704,65 @@ impl DriverGrpc for DriverService {
         Ok(Response::new(Box::pin(guarded_stream)))
     }
 
+    async fn watch_key_depth(
+        &self,
+        request: Request<KeyDepthRequest>,
+    ) -> Result<Response<Self::watchKeyDepthStream>, Status> {
+        let req = request.into_inner();
+        info!("watch_key_depth called (device: {:?})", req.device_id);
+
+        // Open whatever is plugged in so reporting reaches it
+        self.scan_devices().await;
+        let device = if req.device_id.is_empty() {
+            None
+        } else {
+            let path = self.resolve_path(&req.device_id).await;
+            let (_, id) = self
+                .find_device(&path)
+                .await
+                .ok_or_else(|| Status::not_found(format!("Unknown device {}", req.device_id)))?;
+            Some(id)
+        };
+
+        let EventSubscription {
+            rx,
+            _guard: vendor_guard,
+        } = self.subscribe_events();
+        set_key_depth_reporting(&self.devices, &self.depth_subscribers, true).await;
+        let depth_guard = KeyDepthGuard {
+            devices: Arc::clone(&self.devices),
+            subscribers: Arc::clone(&self.depth_subscribers),
+        };
+
+        let stream = BroadcastStream::new(rx).filter_map(move |result| {
+            let event = match result {
+                Ok(DeviceEvent {
+                    device: id,
+                    timestamp,
+                    event:
+                        VendorEvent::KeyDepth {
+                            key_index,
+                            depth_raw,
+                        },
+                }) if device.as_ref().is_none_or(|d| *d == id) => Some(Ok(KeyDepthEvent {
+                    device_id: id,
+                    key: key_index as u32,
+                    depth_raw: depth_raw as u32,
+                    timestamp,
+                })),
+                _ => None,
+            };
+            async move { event }
+        });
+
+        let guarded_stream = GuardedStream {
+            inner: stream,
+            _guard: (vendor_guard, depth_guard),
+        };
+
+        Ok(Response::new(Box::pin(guarded_stream)))
+    }
+
     async fn get_weather(
         &self,
         _request: Request<WeatherReq>,
@@ -1248,7 +1809,7 @@ impl DriverGrpc for DriverService {
             }));
         }
 
-        self.ensure_led_kb().await?;
+        let led_kb = self.ensure_led_kb(&frame.device_id).await?;
 
         let mut leds = [(0u8, 0u8, 0u8); MATRIX_LEN];
         for (i, led) in leds.iter_mut().enumerate() {
@@ -1259,7 +1820,7 @@ impl DriverGrpc for DriverService {
             apply_power_budget(&mut leds, frame.power_budget);
         }
 
-        let guard = self.led_kb.lock().await;
+        let guard = led_kb.lock().await;
         let kb = guard.as_ref().unwrap();
 
         if let Err(e) = send_full_frame(kb, &leds) {
@@ -1311,7 +1872,7 @@ impl DriverGrpc for DriverService {
             }));
         }
 
-        self.ensure_led_kb().await?;
+        let led_kb = self.ensure_led_kb(&req.device_id).await?;
 
         let effect_id = {
             let mut id = self.led_next_id.lock().await;
@@ -1326,7 +1887,7 @@ impl DriverGrpc for DriverService {
             req.power_budget
         };
 
-        let led_kb = Arc::clone(&self.led_kb);
+        let effect_kb = Arc::clone(&led_kb);
         let led_effects = Arc::clone(&self.led_effects);
         let eid = effect_id;
 
@@ -1356,7 +1917,7 @@ impl DriverGrpc for DriverService {
                 apply_power_budget(&mut leds, power_budget);
 
                 // Send frame
-                let guard = led_kb.blocking_lock();
+                let guard = effect_kb.blocking_lock();
                 if let Some(ref kb) = *guard {
                     if send_full_frame(kb, &leds).is_err() {
                         break;
@@ -1371,7 +1932,7 @@ impl DriverGrpc for DriverService {
 
             // Release LEDs and remove from map
             {
-                let guard = led_kb.blocking_lock();
+                let guard = effect_kb.blocking_lock();
                 if let Some(ref kb) = *guard {
                     kb.stream_led_release().ok();
                 }
@@ -1384,7 +1945,7 @@ impl DriverGrpc for DriverService {
 
         {
             let mut effects = self.led_effects.lock().await;
-            effects.insert(effect_id, handle);
+            effects.insert(effect_id, (led_kb, handle));
         }
 
         info!("Started effect '{}' as id={}", req.effect, effect_id);
@@ -1402,27 +1963,28 @@ impl DriverGrpc for DriverService {
         let req = request.into_inner();
         let mut effects = self.led_effects.lock().await;
 
-        if req.effect_id == 0 {
+        let stopped: Vec<_> = if req.effect_id == 0 {
             // Stop all
-            let count = effects.len();
-            for (_, handle) in effects.drain() {
-                handle.abort();
-            }
-            info!("Stopped all {} running effects", count);
-        } else if let Some(handle) = effects.remove(&req.effect_id) {
-            handle.abort();
+            let stopped: Vec<_> = effects.drain().map(|(_, effect)| effect).collect();
+            info!("Stopped all {} running effects", stopped.len());
+            stopped
+        } else if let Some(effect) = effects.remove(&req.effect_id) {
             info!("Stopped effect id={}", req.effect_id);
+            vec![effect]
         } else {
             return Ok(Response::new(ResSend {
                 err: format!("no running effect with id={}", req.effect_id),
             }));
-        }
+        };
         drop(effects);
 
-        // Release LEDs
-        let guard = self.led_kb.lock().await;
-        if let Some(ref kb) = *guard {
-            kb.stream_led_release().ok();
+        // Release LEDs on the keyboards the effects were playing on
+        for (led_kb, handle) in stopped {
+            handle.abort();
+            let guard = led_kb.lock().await;
+            if let Some(ref kb) = *guard {
+                kb.stream_led_release().ok();
+            }
         }
 
         Ok(Response::new(ResSend { err: String::new() }))
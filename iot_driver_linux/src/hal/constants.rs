@@ -60,3 +60,9 @@ pub const KEY_COUNT_M1_V5: u8 = 98;
 
 /// Total matrix positions for M1 V5 HE (98 active keys + empty positions)
 pub const MATRIX_SIZE_M1_V5: usize = 126;
+
+/// Key positions the M3 V5 HE firmware keeps magnetism data for (87 keys + gaps)
+pub const KEY_COUNT_M3_V5: u8 = 96;
+
+/// Total matrix positions for M3 V5 HE (nothing is wired past the nav cluster)
+pub const MATRIX_SIZE_M3_V5: usize = 96;
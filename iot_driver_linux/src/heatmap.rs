@@ -0,0 +1,251 @@
+//! Typing heatmap (`~/.config/monsgeek/heatmap.json`).
+//!
+//! Per-key press counts, one map per device name, keyed by matrix index.
+//! `iot_driver heatmap record` counts presses from evdev (or analog depth),
+//! `heatmap show` reports them and `heatmap led` paints them onto the keys,
+//! cold keys dim blue and hot keys red. Counts are log-scaled for display so
+//! the space bar doesn't wash out everything else.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::effect::config_dir;
+use crate::notify::keymap::{pos_to_matrix_index, MATRIX_LEN, ROWS};
+use monsgeek_keyboard::RgbColor;
+
+/// Heat gradient stops, cold to hot.
+const GRADIENT: [(f32, RgbColor); 4] = [
+    (0.0, RgbColor { r: 0, g: 0, b: 60 }),
+    (
+        0.4,
+        RgbColor {
+            r: 0,
+            g: 90,
+            b: 255,
+        },
+    ),
+    (
+        0.75,
+        RgbColor {
+            r: 255,
+            g: 150,
+            b: 0,
+        },
+    ),
+    (1.0, RgbColor { r: 255, g: 0, b: 0 }),
+];
+
+/// Path to the heatmap file in the shared config directory.
+pub fn heatmap_path() -> PathBuf {
+    config_dir().join("heatmap.json")
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Press counts for one keyboard.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heatmap {
+    /// Seconds since the Unix epoch when counting started.
+    pub since: u64,
+    /// Presses per matrix index.
+    pub counts: BTreeMap<u8, u64>,
+}
+
+impl Heatmap {
+    /// An empty heatmap starting now.
+    pub fn new() -> Self {
+        Self {
+            since: unix_now(),
+            counts: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, index: u8) {
+        *self.counts.entry(index).or_default() += 1;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Add `other`'s counts; the earlier start time wins.
+    pub fn merge(&mut self, other: &Heatmap) {
+        for (&index, &count) in &other.counts {
+            *self.counts.entry(index).or_default() += count;
+        }
+        if self.since == 0 || (other.since != 0 && other.since < self.since) {
+            self.since = other.since;
+        }
+    }
+
+    /// Keys by press count, most pressed first.
+    pub fn ranked(&self) -> Vec<(u8, u64)> {
+        let mut keys: Vec<_> = self
+            .counts
+            .iter()
+            .filter(|(_, &c)| c > 0)
+            .map(|(&i, &c)| (i, c))
+            .collect();
+        keys.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        keys
+    }
+
+    /// Log-scaled heat of a key, 0.0-1.0 relative to the hottest key;
+    /// `None` if it was never pressed.
+    pub fn heat(&self, index: u8) -> Option<f32> {
+        let count = *self.counts.get(&index).filter(|&&c| c > 0)?;
+        let max = self.counts.values().copied().max().unwrap_or(count);
+        if max <= 1 {
+            return Some(1.0);
+        }
+        Some(((count as f32).ln_1p() / (max as f32).ln_1p()).clamp(0.0, 1.0))
+    }
+
+    /// Display color of a key; `None` if it was never pressed.
+    pub fn color(&self, index: u8) -> Option<RgbColor> {
+        self.heat(index).map(heat_color)
+    }
+
+    /// The heatmap as an LED frame (row-major, for patch LED streaming).
+    /// Keys never pressed stay dark.
+    pub fn led_frame(&self) -> [(u8, u8, u8); MATRIX_LEN] {
+        let mut leds = [(0, 0, 0); MATRIX_LEN];
+        for &index in self.counts.keys() {
+            let (row, col) = (index as usize % ROWS, index as usize / ROWS);
+            let led = pos_to_matrix_index(row as u8, col as u8);
+            if let (Some(c), Some(slot)) = (self.color(index), led.and_then(|i| leds.get_mut(i))) {
+                *slot = (c.r, c.g, c.b);
+            }
+        }
+        leds
+    }
+}
+
+/// Gradient color for a heat of 0.0 (cold) to 1.0 (hot).
+pub fn heat_color(t: f32) -> RgbColor {
+    let t = t.clamp(0.0, 1.0);
+    let i = GRADIENT
+        .windows(2)
+        .position(|w| t <= w[1].0)
+        .unwrap_or(GRADIENT.len() - 2);
+    let ((t0, a), (t1, b)) = (GRADIENT[i], GRADIENT[i + 1]);
+    let f = (t - t0) / (t1 - t0);
+    let mix = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * f).round() as u8;
+    RgbColor::new(mix(a.r, b.r), mix(a.g, b.g), mix(a.b, b.b))
+}
+
+/// All recorded heatmaps, by device name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeatmapStore {
+    pub devices: BTreeMap<String, Heatmap>,
+}
+
+impl HeatmapStore {
+    /// Load the store, falling back to empty if the file is missing or invalid.
+    pub fn load() -> Self {
+        let path = heatmap_path();
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("heatmap: parse {}: {e}; starting over", path.display());
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = heatmap_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("create config dir: {e}"))?;
+        }
+        let content = serde_json::to_string_pretty(self).map_err(|e| format!("serialize: {e}"))?;
+        std::fs::write(&path, content).map_err(|e| format!("write {}: {e}", path.display()))
+    }
+
+    /// Add a recording session's counts to `device` and save. Re-reads the
+    /// file first so concurrent recorders don't overwrite each other.
+    pub fn add_and_save(device: &str, session: &Heatmap) -> Result<(), String> {
+        let mut store = Self::load();
+        store
+            .devices
+            .entry(device.to_string())
+            .or_insert_with(|| Heatmap {
+                since: session.since,
+                counts: BTreeMap::new(),
+            })
+            .merge(session);
+        store.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(counts: &[(u8, u64)]) -> Heatmap {
+        Heatmap {
+            since: 100,
+            counts: counts.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn ranks_and_merges_counts() {
+        let mut a = map(&[(3, 5), (9, 1)]);
+        a.merge(&Heatmap {
+            since: 50,
+            counts: [(9, 10), (4, 5)].into_iter().collect(),
+        });
+        assert_eq!(a.since, 50);
+        assert_eq!(a.total(), 21);
+        assert_eq!(a.ranked(), vec![(9, 11), (3, 5), (4, 5)]);
+
+        let mut fresh = Heatmap::default();
+        fresh.merge(&a);
+        assert_eq!(fresh.since, 50);
+    }
+
+    #[test]
+    fn heat_is_log_scaled_to_the_hottest_key() {
+        let m = map(&[(0, 1000), (1, 31), (2, 0)]);
+        assert_eq!(m.heat(0), Some(1.0));
+        let mid = m.heat(1).unwrap();
+        assert!((0.45..0.55).contains(&mid), "{mid}");
+        assert_eq!(m.heat(2), None);
+        assert_eq!(m.heat(7), None);
+    }
+
+    #[test]
+    fn gradient_runs_blue_to_red() {
+        assert_eq!(heat_color(0.0), RgbColor::new(0, 0, 60));
+        assert_eq!(heat_color(1.0), RgbColor::new(255, 0, 0));
+        let warm = heat_color(0.8);
+        assert!(warm.r == 255 && warm.b == 0);
+    }
+
+    #[test]
+    fn led_frame_places_keys_row_major() {
+        // Matrix index 0 is Esc (row 0, col 0); index 7 is row 1, col 1
+        let frame = map(&[(0, 10), (7, 1)]).led_frame();
+        assert_eq!(frame[0], (255, 0, 0));
+        let (r, _, b) = frame[pos_to_matrix_index(1, 1).unwrap()];
+        assert!(b > r, "cold key should be blue");
+        assert_eq!(frame.iter().filter(|&&c| c != (0, 0, 0)).count(), 2);
+    }
+
+    #[test]
+    fn store_round_trips_through_json() {
+        let mut store = HeatmapStore::default();
+        store.devices.insert("M1 V5".into(), map(&[(12, 3)]));
+        let text = serde_json::to_string(&store).unwrap();
+        assert_eq!(serde_json::from_str::<HeatmapStore>(&text).unwrap(), store);
+    }
+}
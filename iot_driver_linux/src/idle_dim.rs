@@ -0,0 +1,373 @@
+//! Idle dimming: fade the backlight after a period without typing.
+//!
+//! Configured under `[idle_dim]` in `settings.toml` and run by the daemon.
+//! Key presses come from evdev; optionally logind's `IdleHint` (set by the
+//! desktop when the session goes idle) dims early too. The next key press
+//! puts the lighting back at once. This is independent of the firmware's
+//! sleep timers, which only apply on battery. It pauses while the session
+//! is locked (see [`crate::lock_lighting`]).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::evdev::EventReader;
+use monsgeek_keyboard::led::LedParams;
+use monsgeek_keyboard::KeyboardInterface;
+
+/// How often the daemon loop checks for input and steps the fade.
+const TICK: Duration = Duration::from_millis(250);
+/// How often the daemon re-reads `settings.toml`.
+const CONFIG_RELOAD: Duration = Duration::from_secs(5);
+
+/// `[idle_dim]` settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdleDim {
+    pub enabled: bool,
+    /// Minutes without a key press before dimming
+    pub timeout_minutes: f32,
+    /// Brightness when idle, 0-100 (0 = off)
+    pub brightness: u8,
+    /// Length of the fade down in seconds
+    pub fade_seconds: f32,
+    /// Also dim when logind reports the session idle
+    pub logind: bool,
+}
+
+impl Default for IdleDim {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_minutes: 5.0,
+            brightness: 0,
+            fade_seconds: 3.0,
+            logind: true,
+        }
+    }
+}
+
+impl IdleDim {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs_f32(self.timeout_minutes.max(0.1) * 60.0)
+    }
+
+    /// How far dimmed (0.0 = normal, 1.0 = fully) `dimming_for` after the
+    /// fade started.
+    pub fn fade_level(&self, dimming_for: Duration) -> f32 {
+        if self.fade_seconds <= 0.0 {
+            return 1.0;
+        }
+        (dimming_for.as_secs_f32() / self.fade_seconds).clamp(0.0, 1.0)
+    }
+
+    /// Brightness for `base` at fade `level`.
+    pub fn brightness_at(&self, base: u8, level: f32) -> u8 {
+        let target = self.brightness.min(base) as f32;
+        (base as f32 + (target - base as f32) * level).round() as u8
+    }
+}
+
+/// Tracks input and decides when the fade starts.
+#[derive(Debug)]
+pub struct IdleTracker {
+    last_input: Instant,
+    /// When logind went idle, if it did since the last key press
+    hinted_at: Option<Instant>,
+    prev_hint: bool,
+}
+
+impl IdleTracker {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            last_input: now,
+            hinted_at: None,
+            prev_hint: false,
+        }
+    }
+
+    pub fn input(&mut self, now: Instant) {
+        self.last_input = now;
+        self.hinted_at = None;
+    }
+
+    /// Note logind's current `IdleHint`; only a change to idle counts, so a
+    /// key press wins over a hint that is still set.
+    pub fn hint(&mut self, idle: bool, now: Instant) {
+        if idle && !self.prev_hint {
+            self.hinted_at = Some(now);
+        }
+        self.prev_hint = idle;
+    }
+
+    /// When dimming started, if idle at `now`.
+    pub fn idle_since(&self, now: Instant, timeout: Duration) -> Option<Instant> {
+        let timed_out = self.last_input + timeout;
+        match self.hinted_at {
+            Some(at) if at < timed_out => Some(at),
+            _ => (now >= timed_out).then_some(timed_out),
+        }
+    }
+}
+
+/// Dims the firmware lighting and restores it.
+///
+/// Remembers the user's LED settings when dimming starts and puts them back
+/// afterwards. If the lighting was changed while dimmed, the change is kept:
+/// nothing is restored and dimming waits for the next idle period.
+#[derive(Debug, Default)]
+pub struct IdleDimmer {
+    baseline: Option<LedParams>,
+    applied: Option<LedParams>,
+    /// The user took over during this idle period
+    overridden: bool,
+}
+
+impl IdleDimmer {
+    pub fn is_dimmed(&self) -> bool {
+        self.baseline.is_some()
+    }
+
+    /// What to write given the keyboard's `current` settings and the fade
+    /// `level` (`None` = not idle); `None` when nothing needs to change.
+    pub fn plan(
+        &mut self,
+        current: &LedParams,
+        level: Option<f32>,
+        dim: &IdleDim,
+    ) -> Option<LedParams> {
+        if self.applied.as_ref().is_some_and(|a| a != current) {
+            // Changed by the user while dimmed: keep it
+            self.baseline = None;
+            self.applied = None;
+            self.overridden = true;
+        }
+        let Some(level) = level else {
+            self.overridden = false;
+            self.applied = None;
+            return self.baseline.take().filter(|base| base != current);
+        };
+        if self.overridden {
+            return None;
+        }
+        let base = self.baseline.get_or_insert_with(|| current.clone());
+        let want = LedParams {
+            brightness: dim.brightness_at(base.brightness, level),
+            ..base.clone()
+        };
+        self.applied = Some(want.clone());
+        (want != *current).then_some(want)
+    }
+
+    /// Apply one step.
+    pub fn tick(
+        &mut self,
+        keyboard: &KeyboardInterface,
+        level: Option<f32>,
+        dim: &IdleDim,
+    ) -> Result<(), String> {
+        if level.is_none() && !self.is_dimmed() {
+            return Ok(());
+        }
+        let current = keyboard.get_led_params().map_err(|e| e.to_string())?;
+        if let Some(params) = self.plan(&current, level, dim) {
+            keyboard
+                .set_led_params(&params)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// Daemon loop: dim while idle per `[idle_dim]`, re-reading the settings so
+/// edits apply without a restart. `idle_hint` mirrors logind's `IdleHint`
+/// (see [`watch_idle_hint`]). The keyboard is opened on demand and reopened
+/// after errors.
+pub fn run_daemon_idle(
+    open: impl Fn() -> Result<KeyboardInterface, String>,
+    idle_hint: Arc<AtomicBool>,
+) {
+    let mut dimmer = IdleDimmer::default();
+    let mut tracker = IdleTracker::new(Instant::now());
+    let mut keyboard: Option<(KeyboardInterface, EventReader)> = None;
+    let mut settings = IdleDim::default();
+    let mut next_reload = Instant::now();
+    loop {
+        if Instant::now() >= next_reload {
+            settings = crate::settings::Settings::load().idle_dim;
+            next_reload = Instant::now() + CONFIG_RELOAD;
+        }
+        if !settings.enabled && !dimmer.is_dimmed() {
+            keyboard = None;
+            std::thread::sleep(CONFIG_RELOAD);
+            continue;
+        }
+
+        if keyboard.is_none() {
+            match open() {
+                Ok(kb) => {
+                    let reader = EventReader::open(kb.vid(), kb.pid());
+                    if reader.is_empty() {
+                        tracing::warn!(
+                            "idle dim: no readable input nodes (not in the 'input' group?), \
+                             only logind idle is seen"
+                        );
+                    }
+                    tracker.input(Instant::now());
+                    keyboard = Some((kb, reader));
+                }
+                Err(e) => {
+                    tracing::debug!("idle dim: no keyboard: {e}");
+                    std::thread::sleep(CONFIG_RELOAD);
+                    continue;
+                }
+            }
+        }
+        let Some((kb, reader)) = &keyboard else {
+            continue;
+        };
+        if crate::lock_lighting::is_locked() {
+            // Lock lighting owns the LEDs; start over after unlock
+            reader.poll(TICK);
+            tracker.input(Instant::now());
+            continue;
+        }
+
+        if reader.is_empty() {
+            std::thread::sleep(TICK);
+        } else if reader
+            .poll(TICK)
+            .iter()
+            .any(|ev| ev.is_key_edge() && ev.value == 1)
+        {
+            tracker.input(Instant::now());
+        }
+        let now = Instant::now();
+        tracker.hint(settings.logind && idle_hint.load(Ordering::Relaxed), now);
+        let level = tracker
+            .idle_since(now, settings.timeout())
+            .filter(|_| settings.enabled)
+            .map(|since| settings.fade_level(now - since));
+        if let Err(e) = dimmer.tick(kb, level, &settings) {
+            tracing::warn!("idle dim: {e}");
+            keyboard = None;
+        }
+    }
+}
+
+/// Keep `idle` in sync with logind's `IdleHint` for the current user (all
+/// sessions idle), polling every few seconds. Returns if logind is missing.
+#[cfg(feature = "dbus")]
+pub async fn watch_idle_hint(idle: Arc<AtomicBool>) -> zbus::Result<()> {
+    let conn = zbus::Connection::system().await?;
+    let user = zbus::Proxy::new(
+        &conn,
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1/user/self",
+        "org.freedesktop.login1.User",
+    )
+    .await?;
+    let mut tick = tokio::time::interval(Duration::from_secs(2));
+    loop {
+        tick.tick().await;
+        if !crate::settings::Settings::load().idle_dim.enabled {
+            idle.store(false, Ordering::Relaxed);
+            continue;
+        }
+        let hint: bool = user.get_property("IdleHint").await?;
+        idle.store(hint, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(brightness: u8) -> LedParams {
+        LedParams {
+            brightness,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fades_from_the_user_brightness_to_the_idle_level() {
+        let dim = IdleDim {
+            brightness: 20,
+            fade_seconds: 2.0,
+            ..Default::default()
+        };
+        assert_eq!(dim.fade_level(Duration::ZERO), 0.0);
+        assert_eq!(dim.fade_level(Duration::from_secs(1)), 0.5);
+        assert_eq!(dim.fade_level(Duration::from_secs(9)), 1.0);
+        assert_eq!(dim.brightness_at(80, 0.5), 50);
+        assert_eq!(dim.brightness_at(80, 1.0), 20);
+        // Never brightens a keyboard already below the idle level
+        assert_eq!(dim.brightness_at(10, 1.0), 10);
+    }
+
+    #[test]
+    fn idle_after_timeout_or_logind_until_the_next_key() {
+        let start = Instant::now();
+        let timeout = Duration::from_secs(60);
+        let mut tracker = IdleTracker::new(start);
+        assert_eq!(
+            tracker.idle_since(start + Duration::from_secs(30), timeout),
+            None
+        );
+        assert_eq!(
+            tracker.idle_since(start + Duration::from_secs(90), timeout),
+            Some(start + timeout)
+        );
+
+        tracker.input(start + Duration::from_secs(90));
+        let now = start + Duration::from_secs(100);
+        assert_eq!(tracker.idle_since(now, timeout), None);
+        tracker.hint(true, now);
+        assert_eq!(tracker.idle_since(now, timeout), Some(now));
+
+        // A key press wins over a hint that stays set
+        tracker.input(now);
+        tracker.hint(true, now);
+        assert_eq!(
+            tracker.idle_since(now + Duration::from_secs(1), timeout),
+            None
+        );
+    }
+
+    #[test]
+    fn dims_then_restores_the_baseline() {
+        let dim = IdleDim::default();
+        let mut dimmer = IdleDimmer::default();
+        assert_eq!(dimmer.plan(&params(80), None, &dim), None);
+
+        let half = dimmer.plan(&params(80), Some(0.5), &dim).unwrap();
+        assert_eq!(half.brightness, 40);
+        let off = dimmer.plan(&half, Some(1.0), &dim).unwrap();
+        assert_eq!(off.brightness, 0);
+        assert_eq!(dimmer.plan(&off, Some(1.0), &dim), None);
+
+        assert_eq!(dimmer.plan(&off, None, &dim), Some(params(80)));
+        assert!(!dimmer.is_dimmed());
+    }
+
+    #[test]
+    fn user_change_while_dimmed_is_kept() {
+        let dim = IdleDim::default();
+        let mut dimmer = IdleDimmer::default();
+        dimmer.plan(&params(80), Some(1.0), &dim).unwrap();
+        // The user turned the lighting back up with Fn keys
+        assert_eq!(dimmer.plan(&params(60), Some(1.0), &dim), None);
+        assert_eq!(dimmer.plan(&params(60), Some(1.0), &dim), None);
+        assert!(!dimmer.is_dimmed());
+        // The next idle period dims again
+        assert_eq!(dimmer.plan(&params(60), None, &dim), None);
+        assert_eq!(dimmer.plan(&params(60), Some(1.0), &dim), Some(params(0)));
+    }
+}
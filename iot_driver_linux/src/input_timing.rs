@@ -0,0 +1,281 @@
+//! Timing statistics for input report measurements.
+//!
+//! Pure helpers behind `measure-rate` and `latency`: summarize a set of
+//! durations into percentiles, estimate the achieved USB report rate from
+//! report timestamps, and pair vendor depth events with evdev key events.
+//!
+//! A keyboard only sends a report when its state changes, but only ever in a
+//! polling slot, so consecutive-report gaps are whole multiples of the
+//! interval achieved on the bus. How fast keys are pressed only changes the
+//! multiples, so the rate comes from the longest standard interval that the
+//! gaps line up with rather than from the gap lengths themselves.
+
+use std::time::Duration;
+
+/// Polling rates the firmware can be configured to (Hz).
+pub const STANDARD_RATES: [u16; 7] = [125, 250, 500, 1000, 2000, 4000, 8000];
+
+/// Share of gaps that must be whole multiples of an interval for it to count
+/// as the achieved one.
+const FIT_SHARE: f64 = 0.9;
+
+/// Gaps shorter than this are treated as the same report seen on two input
+/// nodes (e.g. boot keyboard + NKRO interface) and ignored.
+const MIN_REPORT_GAP: Duration = Duration::from_micros(20);
+
+/// Percentile summary of a set of durations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimingStats {
+    pub count: usize,
+    pub min: Duration,
+    pub p5: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+}
+
+impl TimingStats {
+    /// Summarize `samples`. Returns `None` if empty.
+    pub fn from_samples(samples: &[Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        let pick = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+        let total: Duration = sorted.iter().sum();
+        Some(Self {
+            count: sorted.len(),
+            min: sorted[0],
+            p5: pick(0.05),
+            median: pick(0.5),
+            p95: pick(0.95),
+            max: sorted[sorted.len() - 1],
+            mean: total / sorted.len() as u32,
+        })
+    }
+}
+
+/// Achieved report rate derived from report timestamps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateEstimate {
+    /// Number of reports the estimate is based on.
+    pub reports: usize,
+    /// Distribution of gaps between consecutive reports.
+    pub gaps: TimingStats,
+    /// Lowest standard rate whose interval the gaps are multiples of, or
+    /// `None` if they don't line up with any.
+    pub achieved_hz: Option<u16>,
+}
+
+impl RateEstimate {
+    /// True if the bus delivered a lower rate than `configured_hz`.
+    pub fn is_below(&self, configured_hz: u16) -> bool {
+        self.achieved_hz.is_some_and(|hz| hz < configured_hz)
+    }
+}
+
+/// Whether `gap` is a whole (non-zero) multiple of `interval`, give or take a
+/// quarter interval of timestamp jitter.
+fn is_multiple(gap: Duration, interval: Duration) -> bool {
+    let ratio = gap.as_secs_f64() / interval.as_secs_f64();
+    ratio.round() >= 1.0 && (ratio - ratio.round()).abs() <= 0.25
+}
+
+/// Estimate the achieved report rate from report timestamps (any order).
+///
+/// Needs at least two distinct reports; the fewer there are, the likelier
+/// they happen to line up with a longer interval and the estimate reads low.
+pub fn estimate_report_rate(timestamps: &[Duration]) -> Option<RateEstimate> {
+    let mut ts = timestamps.to_vec();
+    ts.sort();
+    let gaps: Vec<Duration> = ts
+        .windows(2)
+        .map(|w| w[1] - w[0])
+        .filter(|g| *g >= MIN_REPORT_GAP)
+        .collect();
+    let stats = TimingStats::from_samples(&gaps)?;
+    let achieved_hz = STANDARD_RATES.iter().copied().find(|&hz| {
+        let interval = Duration::from_secs(1) / hz as u32;
+        let fit = gaps.iter().filter(|g| is_multiple(**g, interval)).count();
+        fit as f64 >= gaps.len() as f64 * FIT_SHARE
+    });
+    Some(RateEstimate {
+        reports: gaps.len() + 1,
+        gaps: stats,
+        achieved_hz,
+    })
+}
+
+/// Summary of signed latency samples in milliseconds.
+///
+/// Unlike [`TimingStats`] this allows negative values: host-side timestamps of
+/// vendor events include our own read latency, so the kernel-stamped evdev
+/// event can appear to arrive first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub min_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    pub mean_ms: f64,
+    pub stddev_ms: f64,
+}
+
+impl LatencyStats {
+    /// Summarize `samples` (ms). Returns `None` if empty.
+    pub fn from_ms(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len();
+        let pick = |p: f64| sorted[((n - 1) as f64 * p).round() as usize];
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let var = sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n as f64;
+        Some(Self {
+            count: n,
+            min_ms: sorted[0],
+            p50_ms: pick(0.5),
+            p95_ms: pick(0.95),
+            max_ms: sorted[n - 1],
+            mean_ms: mean,
+            stddev_ms: var.sqrt(),
+        })
+    }
+}
+
+/// Pair each `observed` timestamp with the nearest unused `reference`
+/// timestamp within `max_skew`, returning `observed - reference` in ms.
+///
+/// Both inputs may be unsorted. Observations with no reference close enough
+/// (e.g. a key the vendor stream never reported) are dropped.
+pub fn pair_nearest(reference: &[Duration], observed: &[Duration], max_skew: Duration) -> Vec<f64> {
+    let mut refs: Vec<(Duration, bool)> = reference.iter().map(|&t| (t, false)).collect();
+    refs.sort_by_key(|(t, _)| *t);
+    let mut obs = observed.to_vec();
+    obs.sort();
+
+    let mut deltas = Vec::new();
+    for o in obs {
+        let best = refs
+            .iter()
+            .enumerate()
+            .filter(|(_, (r, used))| !used && o.abs_diff(*r) <= max_skew)
+            .min_by_key(|(_, (r, _))| o.abs_diff(*r))
+            .map(|(i, _)| i);
+        if let Some(i) = best {
+            refs[i].1 = true;
+            let r = refs[i].0;
+            let ms = if o >= r {
+                (o - r).as_secs_f64() * 1e3
+            } else {
+                -((r - o).as_secs_f64() * 1e3)
+            };
+            deltas.push(ms);
+        }
+    }
+    deltas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn us(v: u64) -> Duration {
+        Duration::from_micros(v)
+    }
+
+    #[test]
+    fn stats_percentiles() {
+        let samples: Vec<_> = (1..=100).map(us).collect();
+        let s = TimingStats::from_samples(&samples).unwrap();
+        assert_eq!(s.count, 100);
+        assert_eq!(s.min, us(1));
+        assert_eq!(s.max, us(100));
+        assert_eq!(s.median, us(51));
+        assert_eq!(s.p5, us(6));
+        assert!(TimingStats::from_samples(&[]).is_none());
+    }
+
+    #[test]
+    fn estimates_1khz_from_multiples_of_interval() {
+        // Reports land on 1 ms boundaries with occasional idle frames between.
+        let mut t = 0u64;
+        let mut ts = Vec::new();
+        for i in 0..200 {
+            ts.push(us(t));
+            t += if i % 4 == 0 { 3000 } else { 1000 };
+        }
+        let est = estimate_report_rate(&ts).unwrap();
+        assert_eq!(est.achieved_hz, Some(1000));
+        assert!(!est.is_below(1000));
+        assert!(est.is_below(8000));
+    }
+
+    #[test]
+    fn slow_typing_does_not_read_as_a_low_rate() {
+        // 8 kHz slots, but keystrokes tens of milliseconds apart, with jitter
+        let mut t = 0u64;
+        let mut ts = Vec::new();
+        for i in 0..200u64 {
+            ts.push(us(t + i % 3 * 10));
+            t += 125 * (200 + i * 37 % 300);
+        }
+        let est = estimate_report_rate(&ts).unwrap();
+        assert_eq!(est.achieved_hz, Some(8000));
+        assert!(!est.is_below(8000));
+    }
+
+    #[test]
+    fn unaligned_gaps_give_no_rate() {
+        let ts: Vec<_> = (0..50u64).map(|i| us(i * i * 397)).collect();
+        let est = estimate_report_rate(&ts).unwrap();
+        assert_eq!(est.achieved_hz, None);
+        assert!(!est.is_below(1000));
+    }
+
+    #[test]
+    fn duplicate_node_reports_are_ignored() {
+        let ts = vec![us(0), us(1), us(125), us(126), us(250), us(251)];
+        let est = estimate_report_rate(&ts).unwrap();
+        assert_eq!(est.achieved_hz, Some(8000));
+    }
+
+    #[test]
+    fn too_few_reports() {
+        assert!(estimate_report_rate(&[us(5)]).is_none());
+    }
+
+    #[test]
+    fn latency_stats_allow_negative() {
+        let s = LatencyStats::from_ms(&[-0.5, 1.0, 2.0, 3.0]).unwrap();
+        assert_eq!(s.count, 4);
+        assert_eq!(s.min_ms, -0.5);
+        assert_eq!(s.max_ms, 3.0);
+        assert!((s.mean_ms - 1.375).abs() < 1e-9);
+        assert!(LatencyStats::from_ms(&[]).is_none());
+    }
+
+    #[test]
+    fn pairs_nearest_reference_once() {
+        let ms = Duration::from_millis;
+        let refs = [ms(100), ms(200), ms(300)];
+        // 205 pairs with 200, 298 with 300; 500 has nothing within skew.
+        let obs = [ms(298), ms(205), ms(500)];
+        let d = pair_nearest(&refs, &obs, ms(20));
+        assert_eq!(d.len(), 2);
+        assert!((d[0] - 5.0).abs() < 1e-9);
+        assert!((d[1] + 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn reference_is_not_reused() {
+        let ms = Duration::from_millis;
+        let d = pair_nearest(&[ms(100)], &[ms(101), ms(102)], ms(20));
+        assert_eq!(d.len(), 1);
+    }
+}
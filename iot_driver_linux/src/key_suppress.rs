@@ -0,0 +1,176 @@
+//! Keeps keys bound to the joystick mapper from typing.
+//!
+//! With `iot_driver joystick --suppress-keys` (or `suppress_keys = true` in
+//! `joystick.toml`) the keyboard's evdev node is grabbed while the mapper
+//! runs and its key events are replayed through a virtual keyboard, minus
+//! the keys bound to enabled axes. The mapper reads key depth over the
+//! vendor HID interface, so it still sees them. Once the mapper exits the
+//! node is ungrabbed and every key types again. Needs the same access as
+//! the software remap layer ([`crate::remap`]); with that layer running,
+//! its virtual keyboard is filtered instead of the physical one.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::evdev;
+use crate::protocol::hid;
+use crate::remap::{self, KeyRewrite};
+
+/// Name of the virtual keyboard, skipped when grabbing.
+pub const VIRTUAL_NAME: &str = "MonsGeek joystick key filter";
+
+/// How often `stop` is checked while waiting.
+const TICK: Duration = Duration::from_millis(250);
+/// How long to wait before looking for the keyboard again.
+const RELOAD: Duration = Duration::from_secs(5);
+
+/// Decides which key events reach the desktop.
+#[derive(Debug, Default)]
+pub struct KeyFilter {
+    /// Evdev codes of the bound keys
+    bound: HashSet<u16>,
+    /// Bound keys whose press was held back, so their release is too
+    held_back: HashSet<u16>,
+    /// Keys whose press went through
+    passed: HashSet<u16>,
+}
+
+impl KeyFilter {
+    /// Filter holding back these keys (matrix key names, as in
+    /// `joystick.toml`); names without a Linux key code are skipped.
+    pub fn new(names: &[&str]) -> Self {
+        let mut filter = Self::default();
+        filter.set_keys(names);
+        filter
+    }
+
+    /// Replace the bound keys. Keys held right now are released the way
+    /// they were pressed.
+    pub fn set_keys(&mut self, names: &[&str]) {
+        self.bound = names
+            .iter()
+            .filter_map(|name| hid::key_code_from_name(name))
+            .filter_map(evdev::hid_to_keycode)
+            .collect();
+    }
+
+    /// Whether a key press (1) or release (0) of evdev `code` goes through.
+    pub fn pass(&mut self, code: u16, value: i32) -> bool {
+        if value == 0 {
+            self.passed.remove(&code);
+            return !self.held_back.remove(&code);
+        }
+        if self.bound.contains(&code) {
+            self.held_back.insert(code);
+            return false;
+        }
+        self.passed.insert(code);
+        true
+    }
+
+    /// Releases for every key still held through the filter.
+    pub fn release_all(&mut self) -> Vec<(u16, i32)> {
+        self.held_back.clear();
+        self.passed.drain().map(|code| (code, 0)).collect()
+    }
+}
+
+/// VID:PID of the first supported keyboard, as the mapper picks it.
+fn keyboard_ids() -> Option<(u16, u16)> {
+    let devices = monsgeek_transport::list_devices_sync().ok()?;
+    let info = &devices.first()?.info;
+    Some((info.vid, info.pid))
+}
+
+/// Sleep for `duration`, or until `stop` is set.
+fn wait(stop: &AtomicBool, duration: Duration) {
+    let until = Instant::now() + duration;
+    while !stop.load(Ordering::Relaxed) && Instant::now() < until {
+        std::thread::sleep(TICK);
+    }
+}
+
+/// A [`KeyFilter`] session, re-reading the bound keys on every reload.
+struct Suppress<F> {
+    filter: KeyFilter,
+    bound_keys: F,
+}
+
+impl<F: Fn() -> Vec<&'static str>> KeyRewrite for Suppress<F> {
+    fn key(&mut self, code: u16, value: i32) -> Vec<(u16, i32)> {
+        if self.filter.pass(code, value) {
+            vec![(code, value)]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn release_all(&mut self) -> Vec<(u16, i32)> {
+        self.filter.release_all()
+    }
+
+    fn reload(&mut self) -> bool {
+        self.filter.set_keys(&(self.bound_keys)());
+        true
+    }
+}
+
+/// Filter the keyboard until `stop` is set, then ungrab it. `bound_keys`
+/// names the keys to hold back; it is re-read every few seconds so edits
+/// saved from the mapper apply.
+pub fn run(bound_keys: impl Fn() -> Vec<&'static str>, stop: &AtomicBool) {
+    let mut last_error = String::new();
+    while !stop.load(Ordering::Relaxed) {
+        let Some((vid, pid)) = keyboard_ids() else {
+            wait(stop, RELOAD);
+            continue;
+        };
+        let mut session = Suppress {
+            filter: KeyFilter::new(&bound_keys()),
+            bound_keys: &bound_keys,
+        };
+        match remap::replay(vid, pid, VIRTUAL_NAME, "joystick", &mut session, stop) {
+            Ok(()) => last_error.clear(),
+            Err(e) => {
+                remap::warn_once(&mut last_error, "joystick: can't suppress bound keys", e);
+                wait(stop, RELOAD);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: u16 = 17;
+    const A: u16 = 30;
+    const T: u16 = 20;
+
+    #[test]
+    fn bound_keys_are_held_back() {
+        let mut filter = KeyFilter::new(&["W", "A", "NoSuchKey"]);
+        assert!(!filter.pass(W, 1));
+        assert!(!filter.pass(W, 0));
+        assert!(filter.pass(T, 1));
+        assert!(filter.pass(T, 0));
+        // Pressed before the grab: the release still goes through
+        assert!(filter.pass(A, 0));
+    }
+
+    #[test]
+    fn releases_follow_their_press_across_key_changes() {
+        let mut filter = KeyFilter::new(&["W"]);
+        assert!(!filter.pass(W, 1));
+        assert!(filter.pass(T, 1));
+
+        filter.set_keys(&["T"]);
+        assert!(!filter.pass(W, 0));
+        assert!(filter.pass(T, 0));
+        assert!(!filter.pass(T, 1));
+
+        assert!(filter.pass(W, 1));
+        assert_eq!(filter.release_all(), vec![(W, 0)]);
+    }
+}
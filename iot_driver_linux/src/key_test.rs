@@ -0,0 +1,139 @@
+//! Press bookkeeping for the interactive key test (`iot_driver test-keys`).
+//!
+//! Keys are tracked by matrix index from either evdev edges or analog depth.
+//! A key "registered" once it has been pressed; it "chattered" when a press
+//! arrives within [`CHATTER_WINDOW`] of its previous release, or twice without
+//! a release in between — the double-typing a worn or dirty switch produces.
+//! This module only keeps the counts; reading input lives in the command.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Release-to-press gap below which a new press counts as chatter. Faster
+/// than any deliberate double tap, slower than contact bounce after debounce.
+pub const CHATTER_WINDOW: Duration = Duration::from_millis(30);
+
+/// Depth hysteresis (mm) below the threshold before a depth press releases.
+const DEPTH_HYSTERESIS_MM: f32 = 0.1;
+
+/// Counts for one key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyRecord {
+    pub presses: u32,
+    pub chatter: u32,
+    pub down: bool,
+    last_release: Option<Duration>,
+}
+
+/// Per-key press and chatter counts for one test session.
+#[derive(Debug, Default)]
+pub struct KeyTester {
+    keys: BTreeMap<u8, KeyRecord>,
+}
+
+impl KeyTester {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a press edge at `at` (any monotonic clock).
+    pub fn press(&mut self, index: u8, at: Duration) {
+        let key = self.keys.entry(index).or_default();
+        let bounced = key
+            .last_release
+            .is_some_and(|r| at.saturating_sub(r) < CHATTER_WINDOW);
+        if key.down || bounced {
+            key.chatter += 1;
+        }
+        key.presses += 1;
+        key.down = true;
+    }
+
+    /// Record a release edge at `at`.
+    pub fn release(&mut self, index: u8, at: Duration) {
+        let key = self.keys.entry(index).or_default();
+        key.down = false;
+        key.last_release = Some(at);
+    }
+
+    /// Turn an analog depth reading into press/release edges: pressed at
+    /// `threshold_mm`, released a little below it.
+    pub fn depth(&mut self, index: u8, depth_mm: f32, threshold_mm: f32, at: Duration) {
+        let down = self.is_down(index);
+        if !down && depth_mm >= threshold_mm {
+            self.press(index, at);
+        } else if down && depth_mm < threshold_mm - DEPTH_HYSTERESIS_MM {
+            self.release(index, at);
+        }
+    }
+
+    pub fn is_down(&self, index: u8) -> bool {
+        self.keys.get(&index).is_some_and(|k| k.down)
+    }
+
+    pub fn get(&self, index: u8) -> Option<&KeyRecord> {
+        self.keys.get(&index)
+    }
+
+    /// Keys of `expected` that were never pressed.
+    pub fn missing(&self, expected: impl IntoIterator<Item = u8>) -> Vec<u8> {
+        expected
+            .into_iter()
+            .filter(|i| self.keys.get(i).is_none_or(|k| k.presses == 0))
+            .collect()
+    }
+
+    /// Keys that chattered at least once, by index.
+    pub fn chattering(&self) -> impl Iterator<Item = (u8, &KeyRecord)> {
+        self.keys
+            .iter()
+            .filter(|(_, k)| k.chatter > 0)
+            .map(|(&i, k)| (i, k))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn counts_presses_and_missing_keys() {
+        let mut t = KeyTester::new();
+        t.press(3, ms(0));
+        assert!(t.is_down(3));
+        t.release(3, ms(80));
+        t.press(3, ms(300));
+        t.release(3, ms(380));
+        assert_eq!(t.get(3).unwrap().presses, 2);
+        assert_eq!(t.missing([1, 3, 5]), vec![1, 5]);
+        assert_eq!(t.chattering().count(), 0);
+    }
+
+    #[test]
+    fn flags_fast_repress_and_double_press() {
+        let mut t = KeyTester::new();
+        t.press(1, ms(0));
+        t.release(1, ms(50));
+        t.press(1, ms(60));
+        t.release(1, ms(120));
+        t.press(2, ms(0));
+        t.press(2, ms(40));
+        let flagged: Vec<_> = t.chattering().map(|(i, k)| (i, k.chatter)).collect();
+        assert_eq!(flagged, vec![(1, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn depth_uses_hysteresis() {
+        let mut t = KeyTester::new();
+        t.depth(4, 0.6, 0.5, ms(0));
+        t.depth(4, 0.45, 0.5, ms(10));
+        assert!(t.is_down(4));
+        t.depth(4, 0.3, 0.5, ms(20));
+        assert!(!t.is_down(4));
+        assert_eq!(t.get(4).unwrap().presses, 1);
+    }
+}
@@ -0,0 +1,1809 @@
+//! Declarative keyboard configuration files (`iot_driver config apply/dump`).
+//!
+//! A [`KeyboardConfig`] describes the desired device state as TOML. Every
+//! section and field is optional: anything left out stays as it is on the
+//! device. [`KeyboardConfig::plan`] compares the file against a
+//! [`DeviceState`] snapshot and returns a [`Plan`] holding only what differs,
+//! so applying the same file twice writes nothing the second time.
+//! [`DeviceState::to_config_toml`] goes the other way, producing a commented
+//! file from the device's current state.
+//!
+//! ```toml
+//! profile = 1                 # target profile (default: active)
+//!
+//! [settings]
+//! polling_rate_hz = 1000
+//! debounce_ms = 2
+//!
+//! [led]
+//! mode = "wave"               # name or number, see `iot_driver modes`
+//! brightness = 4              # 0-4
+//! speed = 2                   # 0-4
+//! color = "#00ffcc"
+//! dazzle = false
+//!
+//! [sleep]
+//! idle = "2m"                 # both radios; idle_bt / idle_24g override
+//! deep = "28m"                # both radios; deep_bt / deep_24g override
+//!
+//! [options]
+//! os_mode = 0                 # 0=Windows, 1=Mac
+//! wasd_swap = false
+//!
+//! [triggers]                  # applied to every key, in mm
+//! actuation_mm = 1.2
+//! rapid_trigger = true
+//! rt_press_mm = 0.3
+//!
+//! [remap]
+//! Caps = "Esc"
+//! "Fn+F1" = "Macro(0)"
+//!
+//! [[macros]]
+//! slot = 0
+//! sequence = "Ctrl+C,50ms,Ctrl+V"   # or: text = "hello"
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::key_action::KeyAction;
+use crate::keymap::{KeyMap, KeyRef, Layer};
+use crate::macro_seq::MacroSeq;
+use crate::protocol::cmd;
+use monsgeek_keyboard::led::{BRIGHTNESS_MAX, DAZZLE_OFF, DAZZLE_ON, SPEED_MAX};
+use monsgeek_keyboard::{
+    parse_macro_events, text_macro_events, KeyboardError, KeyboardInterface, KeyboardOptions,
+    LedMode, LedParams, ModeByte, PollingRate, Precision, RgbColor, SettingsTransaction,
+    SleepTimeSettings, TriggerChanges, TriggerSettings,
+};
+
+/// Default inter-event delay for macros that don't set `delay_ms`.
+pub const DEFAULT_MACRO_DELAY_MS: u16 = 10;
+
+pub(crate) fn default_macro_delay() -> u16 {
+    DEFAULT_MACRO_DELAY_MS
+}
+
+pub(crate) fn default_macro_repeat() -> u16 {
+    1
+}
+
+/// A full declarative keyboard config, as read from TOML.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyboardConfig {
+    /// Profile to configure (0-3). Defaults to the active profile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<u8>,
+    /// Reset remapped keys that `remap` doesn't list to their factory
+    /// action. Backups set this so a restore also undoes later remaps.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reset_unlisted_remaps: bool,
+    #[serde(default)]
+    pub settings: SettingsSection,
+    #[serde(default)]
+    pub led: LedSection,
+    #[serde(default)]
+    pub sleep: SleepSection,
+    #[serde(default)]
+    pub options: OptionsSection,
+    #[serde(default)]
+    pub triggers: TriggersSection,
+    /// Exact per-key trigger values, as saved by `profile backup`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger_table: Option<TriggerTableSection>,
+    /// Key reference (`Caps`, `Fn+F1`, `L1+A`) to action (`Esc`, `Ctrl+C`, `Macro(0)`).
+    #[serde(default)]
+    pub remap: BTreeMap<String, String>,
+    #[serde(default)]
+    pub macros: Vec<MacroSection>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SettingsSection {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub polling_rate_hz: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debounce_ms: Option<u8>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LedSection {
+    /// Mode name or number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<u8>,
+    /// `#RRGGBB`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dazzle: Option<bool>,
+}
+
+/// Durations use the `set-sleep` syntax (`"2m"`, `"1h 30m"`, `"off"`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SleepSection {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deep: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_bt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_24g: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deep_bt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deep_24g: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OptionsSection {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_mode: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fn_layer: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anti_mistouch: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rt_stability: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wasd_swap: Option<bool>,
+}
+
+/// Bulk trigger values applied to every key, in millimetres.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TriggersSection {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actuation_mm: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_mm: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rt_press_mm: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rt_lift_mm: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bottom_deadzone_mm: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_deadzone_mm: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rapid_trigger: Option<bool>,
+}
+
+/// Per-key trigger tables in raw firmware units, indexed by matrix position.
+/// An empty table leaves that value as it is on the device.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TriggerTableSection {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub press_travel: Vec<u16>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lift_travel: Vec<u16>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rt_press: Vec<u16>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rt_lift: Vec<u16>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bottom_deadzone: Vec<u16>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_deadzone: Vec<u16>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_modes: Vec<u8>,
+}
+
+impl From<&TriggerSettings> for TriggerTableSection {
+    fn from(t: &TriggerSettings) -> Self {
+        Self {
+            press_travel: t.press_travel.clone(),
+            lift_travel: t.lift_travel.clone(),
+            rt_press: t.rt_press.clone(),
+            rt_lift: t.rt_lift.clone(),
+            bottom_deadzone: t.bottom_deadzone.clone(),
+            top_deadzone: t.top_deadzone.clone(),
+            key_modes: t.key_modes.clone(),
+        }
+    }
+}
+
+impl TriggerTableSection {
+    /// The bulk values these tables amount to; values that differ between
+    /// keys are left unset.
+    pub fn to_bulk(&self, precision: Precision) -> TriggersSection {
+        let mm = |values: &[u16]| {
+            let first = *values.first()?;
+            values
+                .iter()
+                .all(|&v| v == first)
+                .then(|| precision.raw_to_mm(first))
+        };
+        let rt_on = self
+            .key_modes
+            .iter()
+            .filter(|&&m| m & ModeByte::RT_FLAG != 0)
+            .count();
+        TriggersSection {
+            actuation_mm: mm(&self.press_travel),
+            release_mm: mm(&self.lift_travel),
+            rt_press_mm: mm(&self.rt_press),
+            rt_lift_mm: mm(&self.rt_lift),
+            bottom_deadzone_mm: mm(&self.bottom_deadzone),
+            top_deadzone_mm: mm(&self.top_deadzone),
+            rapid_trigger: match rt_on {
+                0 if !self.key_modes.is_empty() => Some(false),
+                n if n == self.key_modes.len() && n > 0 => Some(true),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// One macro slot. Exactly one of `sequence` (macro-sequence syntax, see
+/// [`crate::macro_seq`]) or `text` must be set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MacroSection {
+    pub slot: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default = "default_macro_delay")]
+    pub delay_ms: u16,
+    #[serde(default = "default_macro_repeat")]
+    pub repeat: u16,
+}
+
+/// Error loading or resolving a config file.
+#[derive(Debug)]
+pub enum ConfigError {
+    Toml(toml::de::Error),
+    /// A field parsed as TOML but holds a value the device can't take.
+    Invalid {
+        field: String,
+        reason: String,
+    },
+}
+
+impl ConfigError {
+    pub(crate) fn invalid(field: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self::Invalid {
+            field: field.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Toml(e) => write!(f, "invalid config: {e}"),
+            Self::Invalid { field, reason } => write!(f, "{field}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        Self::Toml(e)
+    }
+}
+
+/// Current device values for everything a config can set.
+///
+/// `None` means the value was not read, or the device doesn't support it;
+/// sections that need a missing value are skipped by [`KeyboardConfig::plan`].
+#[derive(Default)]
+pub struct DeviceState {
+    pub precision: Precision,
+    pub polling_rate: Option<PollingRate>,
+    pub debounce: Option<u8>,
+    pub led: Option<LedParams>,
+    pub sleep: Option<SleepTimeSettings>,
+    pub options: Option<KeyboardOptions>,
+    pub triggers: Option<TriggerSettings>,
+    pub keymap: Option<KeyMap>,
+    pub macros: BTreeMap<u8, MacroWrite>,
+}
+
+/// Map "not supported on this model" to `None`, keep real errors.
+fn supported<T>(result: Result<T, KeyboardError>) -> Result<Option<T>, KeyboardError> {
+    match result {
+        Ok(v) => Ok(Some(v)),
+        Err(KeyboardError::NotSupported { .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl DeviceState {
+    /// Read the values `config` touches from the active profile.
+    pub fn read(kb: &KeyboardInterface, config: &KeyboardConfig) -> Result<Self, KeyboardError> {
+        let mut state = Self::default();
+        let s = &config.settings;
+        if s.polling_rate_hz.is_some() {
+            state.polling_rate = supported(kb.get_polling_rate())?;
+        }
+        if s.debounce_ms.is_some() {
+            state.debounce = supported(kb.get_debounce())?;
+        }
+        if config.led != LedSection::default() {
+            state.led = supported(kb.get_led_params())?;
+        }
+        if config.sleep != SleepSection::default() {
+            state.sleep = supported(kb.get_sleep_time())?;
+        }
+        if config.options != OptionsSection::default() {
+            state.options = supported(kb.get_kb_options())?;
+        }
+        if config.triggers != TriggersSection::default() || config.trigger_table.is_some() {
+            state.precision = kb.get_precision().unwrap_or_default();
+            state.triggers = supported(kb.get_all_triggers())?;
+        }
+        if !config.remap.is_empty() || config.reset_unlisted_remaps {
+            state.keymap = supported(crate::keymap::load_sync(kb))?;
+        }
+        for m in &config.macros {
+            state.macros.insert(m.slot, read_macro(kb, m.slot)?);
+        }
+        Ok(state)
+    }
+
+    /// Read everything a config can describe, for `config dump`.
+    ///
+    /// Only macro slots some key is bound to are read: each slot is four
+    /// paged queries, so reading all of them would dominate the dump.
+    pub fn read_all(kb: &KeyboardInterface) -> Result<Self, KeyboardError> {
+        let mut state = Self {
+            precision: kb.get_precision().unwrap_or_default(),
+            polling_rate: supported(kb.get_polling_rate())?,
+            debounce: supported(kb.get_debounce())?,
+            led: supported(kb.get_led_params())?,
+            sleep: supported(kb.get_sleep_time())?,
+            options: supported(kb.get_kb_options())?,
+            triggers: supported(kb.get_all_triggers())?,
+            keymap: supported(crate::keymap::load_sync(kb))?,
+            macros: BTreeMap::new(),
+        };
+        let slots: BTreeSet<u8> = state
+            .keymap
+            .iter()
+            .flat_map(|keymap| keymap.remaps())
+            .filter_map(|e| match e.action {
+                KeyAction::Macro { index, .. } => Some(index),
+                _ => None,
+            })
+            .collect();
+        for slot in slots {
+            state.macros.insert(slot, read_macro(kb, slot)?);
+        }
+        Ok(state)
+    }
+}
+
+fn read_macro(kb: &KeyboardInterface, slot: u8) -> Result<MacroWrite, KeyboardError> {
+    let data = kb.get_macro(slot)?;
+    let (repeat, events) = parse_macro_events(&data);
+    Ok(MacroWrite {
+        slot,
+        events: events
+            .iter()
+            .map(|e| (e.keycode, e.is_down, e.delay_ms))
+            .collect(),
+        repeat,
+    })
+}
+
+/// One changed value, for the diff printed before applying.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub field: String,
+    pub from: String,
+    pub to: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.from, self.to)
+    }
+}
+
+/// A macro slot's contents, as read or to be written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacroWrite {
+    pub slot: u8,
+    pub events: Vec<(u8, bool, u16)>,
+    pub repeat: u16,
+}
+
+/// Everything that differs between a config and the device.
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub changes: Vec<Change>,
+    /// Sections the device can't take (unsupported on this model).
+    pub skipped: Vec<String>,
+    pub polling_rate: Option<PollingRate>,
+    pub debounce: Option<u8>,
+    pub sleep: Option<SleepTimeSettings>,
+    pub options: Option<KeyboardOptions>,
+    pub led: Option<LedParams>,
+    pub triggers: TriggerChanges,
+    pub trigger_table: Option<TriggerSettings>,
+    pub remaps: Vec<(KeyRef, KeyAction)>,
+    /// Keys to put back to their factory action.
+    pub resets: Vec<KeyRef>,
+    /// Previous action of each key in `remaps`, then `resets` (`None`: the
+    /// factory action), to undo them if the apply fails.
+    pub keys_before: Vec<Option<KeyAction>>,
+    pub macros: Vec<MacroWrite>,
+    /// Previous contents of each slot in `macros`, if read.
+    pub macros_before: Vec<Option<MacroWrite>>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn change(&mut self, field: &str, from: impl fmt::Display, to: impl fmt::Display) {
+        self.changes.push(Change {
+            field: field.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+        });
+    }
+
+    /// Stage the settings part of the plan (everything but remaps and macros).
+    pub fn stage<'a>(&self, mut tx: SettingsTransaction<'a>) -> SettingsTransaction<'a> {
+        if let Some(rate) = self.polling_rate {
+            tx = tx.polling_rate(rate);
+        }
+        if let Some(ms) = self.debounce {
+            tx = tx.debounce(ms);
+        }
+        if let Some(sleep) = self.sleep {
+            tx = tx.sleep_time(sleep);
+        }
+        if let Some(options) = &self.options {
+            tx = tx.options(options.clone());
+        }
+        if let Some(led) = &self.led {
+            tx = tx.led(led.clone());
+        }
+        let t = &self.triggers;
+        if let Some(v) = t.actuation {
+            tx = tx.actuation_all(v);
+        }
+        if let Some(v) = t.release {
+            tx = tx.release_all(v);
+        }
+        if let Some(v) = t.rt_press {
+            tx = tx.rt_press_all(v);
+        }
+        if let Some(v) = t.rt_lift {
+            tx = tx.rt_lift_all(v);
+        }
+        if let Some(v) = t.bottom_deadzone {
+            tx = tx.bottom_deadzone_all(v);
+        }
+        if let Some(v) = t.top_deadzone {
+            tx = tx.top_deadzone_all(v);
+        }
+        if let Some(v) = t.rapid_trigger {
+            tx = tx.rapid_trigger_all(v);
+        }
+        if let Some(table) = &self.trigger_table {
+            tx = tx.trigger_table(table.clone());
+        }
+        tx
+    }
+
+    /// Write the plan to `profile`, which must be the active profile.
+    ///
+    /// Macros and keys go first. If they or the settings transaction fail,
+    /// the ones written are put back to the values they were planned against,
+    /// and the error is a [`KeyboardError::Transaction`] saying whether
+    /// everything was restored.
+    pub fn apply(&self, kb: &KeyboardInterface, profile: u8) -> Result<(), KeyboardError> {
+        let mut written = (0, 0);
+        let result = self.write_keys(kb, profile, &mut written).and_then(|()| {
+            let tx = self.stage(kb.transaction());
+            if tx.is_empty() {
+                return Ok(());
+            }
+            tx.apply().map(drop)
+        });
+        let Err(e) = result else {
+            return Ok(());
+        };
+        let undone = self.undo_keys(kb, profile, written);
+        Err(match e {
+            KeyboardError::Transaction {
+                step,
+                reason,
+                rolled_back,
+            } => KeyboardError::Transaction {
+                step,
+                reason,
+                rolled_back: rolled_back && undone,
+            },
+            e => e,
+        })
+    }
+
+    /// Remaps then resets, with the action to write (`None`: factory action).
+    fn key_writes(&self) -> impl Iterator<Item = (&KeyRef, Option<&KeyAction>)> {
+        let remaps = self.remaps.iter().map(|(key, action)| (key, Some(action)));
+        remaps.chain(self.resets.iter().map(|key| (key, None)))
+    }
+
+    /// Write macros, then keys, counting each in `written` before it is sent
+    /// (a write that errors may still have landed).
+    fn write_keys(
+        &self,
+        kb: &KeyboardInterface,
+        profile: u8,
+        written: &mut (usize, usize),
+    ) -> Result<(), KeyboardError> {
+        let failed = |step: &'static str| {
+            move |e: KeyboardError| KeyboardError::Transaction {
+                step,
+                reason: e.to_string(),
+                rolled_back: true,
+            }
+        };
+        // Macros before remaps, so a key bound to a macro never fires stale contents.
+        for m in &self.macros {
+            written.0 += 1;
+            kb.set_macro(m.slot, &m.events, m.repeat)
+                .map_err(failed("macros"))?;
+        }
+        for (key, action) in self.key_writes() {
+            written.1 += 1;
+            write_key(kb, profile, key, action).map_err(failed("remap"))?;
+        }
+        Ok(())
+    }
+
+    /// Put the first `written` macros and keys back, keys first. Returns true
+    /// if every one was restored.
+    fn undo_keys(&self, kb: &KeyboardInterface, profile: u8, written: (usize, usize)) -> bool {
+        let mut ok = true;
+        let keys = self.key_writes().zip(&self.keys_before).take(written.1);
+        for ((key, _), before) in keys {
+            if let Err(e) = write_key(kb, profile, key, before.as_ref()) {
+                tracing::warn!("restoring remap.{key} failed: {e}");
+                ok = false;
+            }
+        }
+        let macros = self.macros.iter().zip(&self.macros_before).take(written.0);
+        for (m, before) in macros {
+            let Some(before) = before else {
+                tracing::warn!("can't restore macro {}: previous contents unknown", m.slot);
+                ok = false;
+                continue;
+            };
+            if let Err(e) = kb.set_macro(before.slot, &before.events, before.repeat) {
+                tracing::warn!("restoring macro {} failed: {e}", m.slot);
+                ok = false;
+            }
+        }
+        ok
+    }
+}
+
+/// Set a key's action, or put it back to the factory one.
+fn write_key(
+    kb: &KeyboardInterface,
+    profile: u8,
+    key: &KeyRef,
+    action: Option<&KeyAction>,
+) -> Result<(), KeyboardError> {
+    match action {
+        Some(action) => crate::keymap::set_key_sync(kb, profile, key.index, key.layer, action),
+        None => crate::keymap::reset_key_sync(kb, profile, key.index, key.layer),
+    }
+}
+
+/// Format an RGB color as `#RRGGBB`.
+pub fn format_color(c: RgbColor) -> String {
+    format!("#{:02X}{:02X}{:02X}", c.r, c.g, c.b)
+}
+
+/// Parse `#RRGGBB` (the `#` is optional).
+pub fn parse_color(s: &str) -> Option<RgbColor> {
+    let hex = s.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let v = u32::from_str_radix(hex, 16).ok()?;
+    Some(RgbColor::new((v >> 16) as u8, (v >> 8) as u8, v as u8))
+}
+
+fn on_off(b: bool) -> &'static str {
+    if b {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// Describe a per-key table as one value (`1.20mm`) or a range (`mixed 1.00-2.00mm`).
+fn describe_travel(values: &[u16], precision: Precision) -> String {
+    let min = values.iter().copied().min().unwrap_or(0);
+    let max = values.iter().copied().max().unwrap_or(0);
+    if min == max {
+        format!("{:.2}mm", precision.raw_to_mm(min))
+    } else {
+        format!(
+            "mixed {:.2}-{:.2}mm",
+            precision.raw_to_mm(min),
+            precision.raw_to_mm(max)
+        )
+    }
+}
+
+fn describe_macro(events: &[(u8, bool, u16)], repeat: u16) -> String {
+    if events.is_empty() {
+        "empty".to_string()
+    } else {
+        let seq = MacroSeq::from_events(events, DEFAULT_MACRO_DELAY_MS, repeat);
+        format!("{seq} (x{repeat})")
+    }
+}
+
+impl KeyboardConfig {
+    /// Parse and validate a config. Value errors (unknown LED mode, bad key
+    /// name, malformed macro) are reported here, before any device access.
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(s)?;
+        config.plan(&DeviceState::default())?;
+        Ok(config)
+    }
+
+    /// Compare the config with `state` and collect what needs writing.
+    pub fn plan(&self, state: &DeviceState) -> Result<Plan, ConfigError> {
+        let mut plan = Plan::default();
+        if let Some(p) = self.profile {
+            if p >= PROFILE_COUNT {
+                return Err(ConfigError::invalid("profile", "must be 0-3"));
+            }
+        }
+        self.plan_settings(state, &mut plan)?;
+        self.plan_sleep(state, &mut plan)?;
+        self.plan_options(state, &mut plan);
+        self.plan_led(state, &mut plan)?;
+        self.plan_triggers(state, &mut plan)?;
+        self.plan_trigger_table(state, &mut plan)?;
+        self.plan_macros(state, &mut plan)?;
+        self.plan_remaps(state, &mut plan)?;
+        Ok(plan)
+    }
+
+    fn plan_settings(&self, state: &DeviceState, plan: &mut Plan) -> Result<(), ConfigError> {
+        let s = &self.settings;
+        if let Some(hz) = s.polling_rate_hz {
+            let rate = PollingRate::from_hz(hz).ok_or_else(|| {
+                ConfigError::invalid("settings.polling_rate_hz", format!("unknown rate {hz} Hz"))
+            })?;
+            match state.polling_rate {
+                Some(cur) if cur != rate => {
+                    plan.change("settings.polling_rate_hz", cur.to_hz(), hz);
+                    plan.polling_rate = Some(rate);
+                }
+                Some(_) => {}
+                None => plan.skipped.push("settings.polling_rate_hz".into()),
+            }
+        }
+        if let Some(ms) = s.debounce_ms {
+            if ms > 50 {
+                return Err(ConfigError::invalid("settings.debounce_ms", "must be 0-50"));
+            }
+            match state.debounce {
+                Some(cur) if cur != ms => {
+                    plan.change("settings.debounce_ms", cur, ms);
+                    plan.debounce = Some(ms);
+                }
+                Some(_) => {}
+                None => plan.skipped.push("settings.debounce_ms".into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn plan_sleep(&self, state: &DeviceState, plan: &mut Plan) -> Result<(), ConfigError> {
+        let s = &self.sleep;
+        if *s == SleepSection::default() {
+            return Ok(());
+        }
+        let parse = |field: &str, v: &Option<String>| -> Result<Option<u16>, ConfigError> {
+            v.as_deref()
+                .map(|v| {
+                    SleepTimeSettings::parse_duration(v).ok_or_else(|| {
+                        ConfigError::invalid(
+                            format!("sleep.{field}"),
+                            format!("bad duration {v:?}"),
+                        )
+                    })
+                })
+                .transpose()
+        };
+        let idle = parse("idle", &s.idle)?;
+        let deep = parse("deep", &s.deep)?;
+        let idle_bt = parse("idle_bt", &s.idle_bt)?.or(idle);
+        let idle_24g = parse("idle_24g", &s.idle_24g)?.or(idle);
+        let deep_bt = parse("deep_bt", &s.deep_bt)?.or(deep);
+        let deep_24g = parse("deep_24g", &s.deep_24g)?.or(deep);
+
+        let Some(cur) = state.sleep else {
+            plan.skipped.push("sleep".into());
+            return Ok(());
+        };
+        let want = SleepTimeSettings {
+            idle_bt: idle_bt.unwrap_or(cur.idle_bt),
+            idle_24g: idle_24g.unwrap_or(cur.idle_24g),
+            deep_bt: deep_bt.unwrap_or(cur.deep_bt),
+            deep_24g: deep_24g.unwrap_or(cur.deep_24g),
+        };
+        if want == cur {
+            return Ok(());
+        }
+        let fmt = SleepTimeSettings::format_duration;
+        for (field, from, to) in [
+            ("sleep.idle_bt", cur.idle_bt, want.idle_bt),
+            ("sleep.idle_24g", cur.idle_24g, want.idle_24g),
+            ("sleep.deep_bt", cur.deep_bt, want.deep_bt),
+            ("sleep.deep_24g", cur.deep_24g, want.deep_24g),
+        ] {
+            if from != to {
+                plan.change(field, fmt(from), fmt(to));
+            }
+        }
+        plan.sleep = Some(want);
+        Ok(())
+    }
+
+    fn plan_options(&self, state: &DeviceState, plan: &mut Plan) {
+        let o = &self.options;
+        if *o == OptionsSection::default() {
+            return;
+        }
+        let Some(cur) = &state.options else {
+            plan.skipped.push("options".into());
+            return;
+        };
+        let want = KeyboardOptions {
+            os_mode: o.os_mode.unwrap_or(cur.os_mode),
+            fn_layer: o.fn_layer.unwrap_or(cur.fn_layer),
+            anti_mistouch: o.anti_mistouch.unwrap_or(cur.anti_mistouch),
+            rt_stability: o.rt_stability.unwrap_or(cur.rt_stability),
+            wasd_swap: o.wasd_swap.unwrap_or(cur.wasd_swap),
+        };
+        if want.to_bytes() == cur.to_bytes() {
+            return;
+        }
+        for (field, from, to) in [
+            ("options.os_mode", cur.os_mode, want.os_mode),
+            ("options.fn_layer", cur.fn_layer, want.fn_layer),
+            ("options.rt_stability", cur.rt_stability, want.rt_stability),
+        ] {
+            if from != to {
+                plan.change(field, from, to);
+            }
+        }
+        for (field, from, to) in [
+            (
+                "options.anti_mistouch",
+                cur.anti_mistouch,
+                want.anti_mistouch,
+            ),
+            ("options.wasd_swap", cur.wasd_swap, want.wasd_swap),
+        ] {
+            if from != to {
+                plan.change(field, on_off(from), on_off(to));
+            }
+        }
+        plan.options = Some(want);
+    }
+
+    fn plan_led(&self, state: &DeviceState, plan: &mut Plan) -> Result<(), ConfigError> {
+        let l = &self.led;
+        if *l == LedSection::default() {
+            return Ok(());
+        }
+        let mode = l
+            .mode
+            .as_deref()
+            .map(|m| {
+                cmd::LedMode::parse(m)
+                    .and_then(|m| LedMode::from_u8(m.as_u8()))
+                    .ok_or_else(|| ConfigError::invalid("led.mode", format!("unknown mode {m:?}")))
+            })
+            .transpose()?;
+        if l.brightness.is_some_and(|b| b > BRIGHTNESS_MAX) {
+            return Err(ConfigError::invalid(
+                "led.brightness",
+                format!("must be 0-{BRIGHTNESS_MAX}"),
+            ));
+        }
+        if l.speed.is_some_and(|s| s > SPEED_MAX) {
+            return Err(ConfigError::invalid(
+                "led.speed",
+                format!("must be 0-{SPEED_MAX}"),
+            ));
+        }
+        let color = l
+            .color
+            .as_deref()
+            .map(|c| {
+                parse_color(c).ok_or_else(|| {
+                    ConfigError::invalid("led.color", format!("expected #RRGGBB, got {c:?}"))
+                })
+            })
+            .transpose()?;
+
+        let Some(cur) = &state.led else {
+            plan.skipped.push("led".into());
+            return Ok(());
+        };
+        let mut want = cur.clone();
+        let before = plan.changes.len();
+        let cur_dazzle = cur.direction & 0x0F == DAZZLE_ON;
+        if let Some(mode) = mode {
+            if mode != cur.mode {
+                plan.change("led.mode", cur.mode.name(), mode.name());
+                want.mode = mode;
+            }
+        }
+        if let Some(b) = l.brightness.filter(|&b| b != cur.brightness) {
+            plan.change("led.brightness", cur.brightness, b);
+            want.brightness = b;
+        }
+        if let Some(s) = l.speed.filter(|&s| s != cur.speed) {
+            plan.change("led.speed", cur.speed, s);
+            want.speed = s;
+        }
+        if let Some(c) = color.filter(|&c| c != cur.color) {
+            plan.change("led.color", format_color(cur.color), format_color(c));
+            want.color = c;
+        }
+        if let Some(d) = l.dazzle.filter(|&d| d != cur_dazzle) {
+            plan.change("led.dazzle", on_off(cur_dazzle), on_off(d));
+            want.direction = (cur.direction & 0xF0) | if d { DAZZLE_ON } else { DAZZLE_OFF };
+        }
+        if plan.changes.len() > before {
+            plan.led = Some(want);
+        }
+        Ok(())
+    }
+
+    fn plan_triggers(&self, state: &DeviceState, plan: &mut Plan) -> Result<(), ConfigError> {
+        let t = &self.triggers;
+        if *t == TriggersSection::default() {
+            return Ok(());
+        }
+        for (field, mm) in [
+            ("actuation_mm", t.actuation_mm),
+            ("release_mm", t.release_mm),
+            ("rt_press_mm", t.rt_press_mm),
+            ("rt_lift_mm", t.rt_lift_mm),
+            ("bottom_deadzone_mm", t.bottom_deadzone_mm),
+            ("top_deadzone_mm", t.top_deadzone_mm),
+        ] {
+            if mm.is_some_and(|mm| !(0.0..=4.0).contains(&mm)) {
+                return Err(ConfigError::invalid(
+                    format!("triggers.{field}"),
+                    "must be 0.0-4.0 mm",
+                ));
+            }
+        }
+
+        let Some(cur) = &state.triggers else {
+            plan.skipped.push("triggers".into());
+            return Ok(());
+        };
+        let precision = state.precision;
+        let mut stage = |field: &str, mm: Option<f64>, values: &[u16]| -> Option<u16> {
+            let raw = precision.mm_to_raw(mm?);
+            if values.iter().all(|&v| v == raw) {
+                return None;
+            }
+            plan.change(
+                &format!("triggers.{field}"),
+                describe_travel(values, precision),
+                format!("{:.2}mm", precision.raw_to_mm(raw)),
+            );
+            Some(raw)
+        };
+        let changes = TriggerChanges {
+            actuation: stage("actuation_mm", t.actuation_mm, &cur.press_travel),
+            release: stage("release_mm", t.release_mm, &cur.lift_travel),
+            rt_press: stage("rt_press_mm", t.rt_press_mm, &cur.rt_press),
+            rt_lift: stage("rt_lift_mm", t.rt_lift_mm, &cur.rt_lift),
+            bottom_deadzone: stage(
+                "bottom_deadzone_mm",
+                t.bottom_deadzone_mm,
+                &cur.bottom_deadzone,
+            ),
+            top_deadzone: stage("top_deadzone_mm", t.top_deadzone_mm, &cur.top_deadzone),
+            rapid_trigger: None,
+        };
+        plan.triggers = changes;
+
+        if let Some(enable) = t.rapid_trigger {
+            let on = cur
+                .key_modes
+                .iter()
+                .filter(|&&m| m & ModeByte::RT_FLAG != 0)
+                .count();
+            let from = match on {
+                0 => "off",
+                n if n == cur.key_modes.len() => "on",
+                _ => "mixed",
+            };
+            if from != on_off(enable) {
+                plan.change("triggers.rapid_trigger", from, on_off(enable));
+                plan.triggers.rapid_trigger = Some(enable);
+            }
+        }
+        Ok(())
+    }
+
+    fn plan_trigger_table(&self, state: &DeviceState, plan: &mut Plan) -> Result<(), ConfigError> {
+        let Some(table) = &self.trigger_table else {
+            return Ok(());
+        };
+        if self.triggers != TriggersSection::default() {
+            return Err(ConfigError::invalid(
+                "trigger_table",
+                "set either [triggers] or [trigger_table], not both",
+            ));
+        }
+        let Some(cur) = &state.triggers else {
+            plan.skipped.push("trigger_table".into());
+            return Ok(());
+        };
+
+        let precision = state.precision;
+        let mut changed = false;
+        let mut pick = |field: &str, saved: &[u16], have: &[u16]| {
+            if saved.is_empty() || saved == have {
+                return Ok(have.to_vec());
+            }
+            if saved.len() != have.len() {
+                return Err(ConfigError::invalid(
+                    format!("trigger_table.{field}"),
+                    format!("has {} keys, the device has {}", saved.len(), have.len()),
+                ));
+            }
+            plan.change(
+                &format!("trigger_table.{field}"),
+                describe_travel(have, precision),
+                describe_travel(saved, precision),
+            );
+            changed = true;
+            Ok(saved.to_vec())
+        };
+        let mut want = TriggerSettings {
+            key_count: cur.key_count,
+            press_travel: pick("press_travel", &table.press_travel, &cur.press_travel)?,
+            lift_travel: pick("lift_travel", &table.lift_travel, &cur.lift_travel)?,
+            rt_press: pick("rt_press", &table.rt_press, &cur.rt_press)?,
+            rt_lift: pick("rt_lift", &table.rt_lift, &cur.rt_lift)?,
+            bottom_deadzone: pick(
+                "bottom_deadzone",
+                &table.bottom_deadzone,
+                &cur.bottom_deadzone,
+            )?,
+            top_deadzone: pick("top_deadzone", &table.top_deadzone, &cur.top_deadzone)?,
+            key_modes: cur.key_modes.clone(),
+        };
+        if !table.key_modes.is_empty() && table.key_modes != cur.key_modes {
+            if table.key_modes.len() != cur.key_modes.len() {
+                return Err(ConfigError::invalid(
+                    "trigger_table.key_modes",
+                    format!(
+                        "has {} keys, the device has {}",
+                        table.key_modes.len(),
+                        cur.key_modes.len()
+                    ),
+                ));
+            }
+            let differ = cur
+                .key_modes
+                .iter()
+                .zip(&table.key_modes)
+                .filter(|(a, b)| a != b)
+                .count();
+            plan.change(
+                "trigger_table.key_modes",
+                format!("{differ} key(s) differ"),
+                "as saved",
+            );
+            want.key_modes.clone_from(&table.key_modes);
+            changed = true;
+        }
+        if changed {
+            plan.trigger_table = Some(want);
+        }
+        Ok(())
+    }
+
+    fn plan_macros(&self, state: &DeviceState, plan: &mut Plan) -> Result<(), ConfigError> {
+        for m in &self.macros {
+            let field = format!("macros.{}", m.slot);
+            let events = match (&m.sequence, &m.text) {
+                (Some(seq), None) => {
+                    let mut seq: MacroSeq = seq
+                        .parse()
+                        .map_err(|e| ConfigError::invalid(&field, format!("{e}")))?;
+                    seq.default_delay = m.delay_ms;
+                    seq.to_events()
+                }
+                (None, Some(text)) => text_macro_events(text, m.delay_ms),
+                _ => {
+                    return Err(ConfigError::invalid(
+                        field,
+                        "set exactly one of `sequence` or `text`",
+                    ))
+                }
+            };
+            let want = MacroWrite {
+                slot: m.slot,
+                events,
+                repeat: m.repeat,
+            };
+            let current = state.macros.get(&m.slot);
+            if current == Some(&want) {
+                continue;
+            }
+            let from = current.map_or("unknown".to_string(), |cur| {
+                describe_macro(&cur.events, cur.repeat)
+            });
+            plan.change(&field, from, describe_macro(&want.events, want.repeat));
+            plan.macros.push(want);
+            plan.macros_before.push(current.cloned());
+        }
+        Ok(())
+    }
+
+    fn plan_remaps(&self, state: &DeviceState, plan: &mut Plan) -> Result<(), ConfigError> {
+        let mut listed = Vec::new();
+        for (key, target) in &self.remap {
+            let field = format!("remap.{key}");
+            let key_ref: KeyRef = key
+                .parse()
+                .map_err(|e: String| ConfigError::invalid(&field, e))?;
+            let action: KeyAction = target
+                .parse()
+                .map_err(|e| ConfigError::invalid(&field, format!("{e}")))?;
+            listed.push((key_ref, action));
+        }
+        if listed.is_empty() && !self.reset_unlisted_remaps {
+            return Ok(());
+        }
+        let Some(keymap) = &state.keymap else {
+            plan.skipped.push("remap".into());
+            return Ok(());
+        };
+
+        for (key_ref, action) in &listed {
+            let current = keymap.get(key_ref.index, key_ref.layer).map(|e| e.action);
+            if current == Some(*action) {
+                continue;
+            }
+            let from = match current {
+                Some(a) => a.to_string(),
+                None if key_ref.layer == Layer::Fn => "none".to_string(),
+                None => "unknown".to_string(),
+            };
+            plan.change(&format!("remap.{key_ref}"), from, action);
+            plan.remaps.push((key_ref.clone(), *action));
+            plan.keys_before.push(current);
+        }
+
+        if !self.reset_unlisted_remaps {
+            return Ok(());
+        }
+        for entry in keymap.remaps() {
+            let is_listed = listed
+                .iter()
+                .any(|(k, _)| (k.index, k.layer) == (entry.index, entry.layer));
+            if is_listed {
+                continue;
+            }
+            let key_ref = entry.key_ref();
+            plan.change(&format!("remap.{key_ref}"), entry.action, "default");
+            plan.resets.push(key_ref);
+            plan.keys_before.push(Some(entry.action));
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Dump: render a DeviceState as a config file
+// ---------------------------------------------------------------------------
+
+/// Quote a TOML string value.
+fn toml_str(s: &str) -> String {
+    toml::Value::String(s.to_string()).to_string()
+}
+
+/// A TOML float that reads back as a float (`1.0`, not `1`).
+fn toml_float(v: f64) -> String {
+    toml::Value::Float(v).to_string()
+}
+
+/// Bare key when TOML allows it, quoted otherwise (`Caps`, `"Fn+F1"`).
+fn toml_key(k: &str) -> String {
+    if !k.is_empty()
+        && k.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        k.to_string()
+    } else {
+        toml_str(k)
+    }
+}
+
+/// LED mode as the shortest name `led.mode` parses back, else its number.
+fn led_mode_key(mode: LedMode) -> String {
+    let n = mode as u8;
+    let name: String = cmd::led_mode_name(n)
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase();
+    match cmd::LedMode::parse(&name) {
+        Some(m) if m.as_u8() == n => name,
+        _ => n.to_string(),
+    }
+}
+
+/// The delay most events use, so the dumped sequence only spells out exceptions.
+pub(crate) fn dominant_delay(events: &[(u8, bool, u16)]) -> u16 {
+    let mut counts: BTreeMap<u16, usize> = BTreeMap::new();
+    for &(_, _, d) in events {
+        if d != 0 {
+            *counts.entry(d).or_default() += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(_, n)| n)
+        .map_or(DEFAULT_MACRO_DELAY_MS, |(d, _)| d)
+}
+
+impl DeviceState {
+    /// Render as a commented TOML config that `config apply` accepts and that
+    /// plans no changes against this same state.
+    ///
+    /// Trigger values that differ between keys can't be expressed as one
+    /// bulk value; they are written commented out with their range.
+    pub fn to_config_toml(&self, profile: u8) -> String {
+        self.to_config_toml_with_header(
+            profile,
+            "# Keyboard config written by `iot_driver config dump`.",
+        )
+    }
+
+    /// [`to_config_toml`](Self::to_config_toml) with a different first line
+    /// (`#` comment lines naming where the file came from).
+    pub fn to_config_toml_with_header(&self, profile: u8, header: &str) -> String {
+        let mut out = String::new();
+        let mut line = |s: String| {
+            out.push_str(&s);
+            out.push('\n');
+        };
+
+        line(header.into());
+        line("# Apply with `iot_driver config apply <file>`. Remove any line or".into());
+        line("# section to leave that setting as it is on the device.".into());
+        line(String::new());
+        line(format!("profile = {profile}"));
+
+        if self.polling_rate.is_some() || self.debounce.is_some() {
+            line(String::new());
+            line("[settings]".into());
+            if let Some(rate) = self.polling_rate {
+                line(format!("polling_rate_hz = {}", rate.to_hz()));
+            }
+            if let Some(ms) = self.debounce {
+                line(format!("debounce_ms = {ms}  # 0-50"));
+            }
+        }
+
+        if let Some(led) = &self.led {
+            let dazzle = led.direction & 0x0F == DAZZLE_ON;
+            line(String::new());
+            line("[led]".into());
+            line(format!(
+                "mode = {}  # name or number, see `iot_driver modes`",
+                toml_str(&led_mode_key(led.mode))
+            ));
+            line(format!(
+                "brightness = {}  # 0-{BRIGHTNESS_MAX}",
+                led.brightness
+            ));
+            line(format!(
+                "speed = {}  # 0-{SPEED_MAX}, slow to fast",
+                led.speed
+            ));
+            line(format!("color = {}", toml_str(&format_color(led.color))));
+            line(format!(
+                "dazzle = {dazzle}  # cycle colors instead of `color`"
+            ));
+        }
+
+        if let Some(sleep) = &self.sleep {
+            let dur = |secs| toml_str(&SleepTimeSettings::format_duration(secs));
+            line(String::new());
+            line("[sleep]  # wireless timeouts; \"off\" disables".into());
+            for (name, bt, g24) in [
+                ("idle", sleep.idle_bt, sleep.idle_24g),
+                ("deep", sleep.deep_bt, sleep.deep_24g),
+            ] {
+                if bt == g24 {
+                    line(format!("{name} = {}", dur(bt)));
+                } else {
+                    line(format!("{name}_bt = {}", dur(bt)));
+                    line(format!("{name}_24g = {}", dur(g24)));
+                }
+            }
+        }
+
+        if let Some(o) = &self.options {
+            line(String::new());
+            line("[options]".into());
+            line(format!("os_mode = {}  # 0=Windows, 1=Mac", o.os_mode));
+            line(format!("fn_layer = {}", o.fn_layer));
+            line(format!("anti_mistouch = {}", o.anti_mistouch));
+            line(format!("rt_stability = {}  # 0=off, 1-3", o.rt_stability));
+            line(format!("wasd_swap = {}", o.wasd_swap));
+        }
+
+        if let Some(t) = &self.triggers {
+            let precision = self.precision;
+            line(String::new());
+            line("[triggers]  # applied to every key, in mm".into());
+            for (field, values) in [
+                ("actuation_mm", &t.press_travel),
+                ("release_mm", &t.lift_travel),
+                ("rt_press_mm", &t.rt_press),
+                ("rt_lift_mm", &t.rt_lift),
+                ("bottom_deadzone_mm", &t.bottom_deadzone),
+                ("top_deadzone_mm", &t.top_deadzone),
+            ] {
+                let Some(&first) = values.first() else {
+                    continue;
+                };
+                let mm = toml_float(precision.raw_to_mm(first));
+                if values.iter().all(|&v| v == first) {
+                    line(format!("{field} = {mm}"));
+                } else {
+                    line(format!(
+                        "# {field} = {mm}  # keys differ: {}",
+                        describe_travel(values, precision)
+                    ));
+                }
+            }
+            let on = t
+                .key_modes
+                .iter()
+                .filter(|&&m| m & ModeByte::RT_FLAG != 0)
+                .count();
+            // No modes means unknown (a capture that never wrote them)
+            let n = t.key_modes.len();
+            if n > 0 && (on == 0 || on == n) {
+                line(format!("rapid_trigger = {}", on != 0));
+            } else if n > 0 {
+                line(format!(
+                    "# rapid_trigger = true  # keys differ: {on} of {n} enabled"
+                ));
+            }
+        }
+
+        if let Some(keymap) = &self.keymap {
+            line(String::new());
+            line("[remap]  # keys that differ from the factory layout".into());
+            for entry in keymap.remaps() {
+                let key = entry.key_ref().to_string();
+                line(format!(
+                    "{} = {}",
+                    toml_key(&key),
+                    toml_str(&entry.action.to_string())
+                ));
+            }
+        }
+
+        for m in self.macros.values() {
+            if m.events.is_empty() {
+                continue;
+            }
+            let delay = dominant_delay(&m.events);
+            let seq = MacroSeq::from_events(&m.events, delay, m.repeat);
+            line(String::new());
+            line("[[macros]]".into());
+            line(format!("slot = {}", m.slot));
+            line(format!("sequence = {}", toml_str(&seq.to_string())));
+            line(format!("delay_ms = {delay}"));
+            line(format!("repeat = {}", m.repeat));
+        }
+
+        out
+    }
+}
+
+/// Number of onboard profiles.
+pub const PROFILE_COUNT: u8 = 4;
+
+/// Every onboard profile in one file (`iot_driver profile backup/restore`).
+///
+/// Each entry is a [`KeyboardConfig`] with `profile` set, so a backup restores
+/// through the same plan as `config apply`. Unlike `config dump`, entries keep
+/// the exact per-key trigger tables and reset keys remapped since the backup.
+/// The switch calibration is device-wide and stored once.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileBackup {
+    /// Device the backup was taken from; informational only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    /// Per-key calibration values in matrix order; empty if not read.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calibration: Vec<u16>,
+    #[serde(default)]
+    pub profiles: Vec<KeyboardConfig>,
+}
+
+impl ProfileBackup {
+    /// Parse and validate a backup; every entry must name a distinct profile.
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        let backup: Self = toml::from_str(s)?;
+        let mut seen = BTreeSet::new();
+        for (i, config) in backup.profiles.iter().enumerate() {
+            let field = format!("profiles[{i}].profile");
+            let Some(p) = config.profile else {
+                return Err(ConfigError::invalid(field, "missing"));
+            };
+            if !seen.insert(p) {
+                return Err(ConfigError::invalid(
+                    field,
+                    format!("profile {p} listed twice"),
+                ));
+            }
+            config.plan(&DeviceState::default()).map_err(|e| match e {
+                ConfigError::Invalid { field, reason } => {
+                    ConfigError::invalid(format!("profiles[{i}].{field}"), reason)
+                }
+                e => e,
+            })?;
+        }
+        Ok(backup)
+    }
+
+    /// Add a profile from its device state, via the `config dump` rendering
+    /// with the per-key trigger tables in place of the bulk `[triggers]`.
+    pub fn push_state(&mut self, profile: u8, state: &DeviceState) -> Result<(), ConfigError> {
+        let mut config = KeyboardConfig::from_toml(&state.to_config_toml(profile))?;
+        config.reset_unlisted_remaps = state.keymap.is_some();
+        if let Some(triggers) = &state.triggers {
+            config.triggers = TriggersSection::default();
+            config.trigger_table = Some(triggers.into());
+        }
+        self.profiles.push(config);
+        Ok(())
+    }
+
+    /// Render as TOML with a short header naming the restore command.
+    pub fn to_toml(&self) -> String {
+        let mut out = String::from(
+            "# Profile backup written by `iot_driver profile backup`.\n\
+             # Restore with `iot_driver profile restore <file>` (--dry-run to preview).\n\n",
+        );
+        out.push_str(&toml::to_string(self).unwrap_or_default());
+        out
+    }
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Read every onboard profile, and the calibration, into one backup.
+/// `progress` is called with each profile before it is read.
+pub fn read_backup(
+    kb: &KeyboardInterface,
+    mut progress: impl FnMut(u8),
+) -> Result<ProfileBackup, BoxError> {
+    let mut backup = ProfileBackup {
+        device: Some(kb.device_name()),
+        ..Default::default()
+    };
+    for profile in 0..PROFILE_COUNT {
+        progress(profile);
+        let state = kb
+            .with_profile(profile, DeviceState::read_all)
+            .map_err(|e| format!("profile {profile}: {e}"))?;
+        backup
+            .push_state(profile, &state)
+            .map_err(|e| format!("profile {profile}: {e}"))?;
+    }
+    // Unread pages come back as zeros; an all-zero table is no calibration
+    let calibration = supported(kb.get_calibration())?.unwrap_or_default();
+    if calibration.iter().any(|&v| v != 0) {
+        backup.calibration = calibration;
+    }
+    Ok(backup)
+}
+
+/// Back up every profile to [`auto_backup_dir`] before a destructive
+/// operation; returns the file written.
+pub fn auto_backup(
+    kb: &KeyboardInterface,
+    reason: &str,
+    progress: impl FnMut(u8),
+) -> Result<std::path::PathBuf, BoxError> {
+    let backup = read_backup(kb, progress)?;
+    let dir = auto_backup_dir();
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = dir.join(auto_backup_name(reason, secs));
+    std::fs::write(&path, backup.to_toml())
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    Ok(path)
+}
+
+/// Write a backup's `calibration` if it differs from the device's; returns
+/// how many keys differ. Nothing is written with `dry_run`.
+pub fn restore_calibration(
+    kb: &KeyboardInterface,
+    calibration: &[u16],
+    dry_run: bool,
+) -> Result<usize, KeyboardError> {
+    if calibration.is_empty() {
+        return Ok(0);
+    }
+    let current = kb.get_calibration()?;
+    if current.len() != calibration.len() {
+        return Err(KeyboardError::InvalidParameter(format!(
+            "calibration has {} keys, the device has {}",
+            calibration.len(),
+            current.len()
+        )));
+    }
+    let differ = current
+        .iter()
+        .zip(calibration)
+        .filter(|(a, b)| a != b)
+        .count();
+    if differ > 0 && !dry_run {
+        kb.set_calibration(calibration)?;
+    }
+    Ok(differ)
+}
+
+/// Where automatic backups go: `<config dir>/backups`.
+///
+/// `firmware flash` and `reset` save every profile here before touching the
+/// device, named by [`auto_backup_name`].
+pub fn auto_backup_dir() -> std::path::PathBuf {
+    crate::effect::config_dir().join("backups")
+}
+
+/// `<reason>-<unix seconds>.toml`, e.g. `pre-flash-1760000000.toml`.
+pub fn auto_backup_name(reason: &str, unix_secs: u64) -> String {
+    format!("{reason}-{unix_secs}.toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_with_led() -> DeviceState {
+        DeviceState {
+            led: Some(LedParams {
+                mode: LedMode::Wave,
+                brightness: 4,
+                speed: 2,
+                color: RgbColor::new(255, 0, 0),
+                direction: DAZZLE_OFF,
+            }),
+            debounce: Some(2),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_full_example() {
+        let cfg = KeyboardConfig::from_toml(
+            r##"
+            profile = 1
+            [settings]
+            polling_rate_hz = 1000
+            debounce_ms = 4
+            [led]
+            mode = "breathing"
+            color = "#00ff80"
+            [sleep]
+            idle = "2m"
+            deep_bt = "off"
+            [triggers]
+            actuation_mm = 1.2
+            rapid_trigger = true
+            [remap]
+            Caps = "Esc"
+            "Fn+F1" = "Macro(0)"
+            [[macros]]
+            slot = 0
+            sequence = "Ctrl+C"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(cfg.profile, Some(1));
+        assert_eq!(cfg.settings.debounce_ms, Some(4));
+        assert_eq!(cfg.macros[0].delay_ms, DEFAULT_MACRO_DELAY_MS);
+        assert_eq!(cfg.macros[0].repeat, 1);
+        assert_eq!(cfg.remap.len(), 2);
+    }
+
+    #[test]
+    fn rejects_bad_values_before_device_access() {
+        for bad in [
+            "[led]\nmode = \"sparkles\"",
+            "[led]\ncolor = \"red\"",
+            "[settings]\ndebounce_ms = 99",
+            "[sleep]\nidle = \"2x\"",
+            "[remap]\nNoSuchKey = \"A\"",
+            "[[macros]]\nslot = 0",
+            "[unknown]\nx = 1",
+        ] {
+            assert!(KeyboardConfig::from_toml(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn plan_only_lists_differences() {
+        let cfg = KeyboardConfig::from_toml(
+            "[settings]\ndebounce_ms = 2\n[led]\nmode = \"wave\"\nbrightness = 2",
+        )
+        .unwrap();
+        let plan = cfg.plan(&state_with_led()).unwrap();
+        assert_eq!(plan.changes.len(), 1);
+        assert_eq!(plan.changes[0].to_string(), "led.brightness: 4 -> 2");
+        assert_eq!(plan.debounce, None);
+        let led = plan.led.unwrap();
+        assert_eq!(led.brightness, 2);
+        assert_eq!(led.mode, LedMode::Wave);
+    }
+
+    #[test]
+    fn unread_sections_are_skipped() {
+        let cfg = KeyboardConfig::from_toml("[sleep]\nidle = \"5m\"").unwrap();
+        let plan = cfg.plan(&state_with_led()).unwrap();
+        assert!(plan.is_empty());
+        assert_eq!(plan.skipped, vec!["sleep".to_string()]);
+    }
+
+    #[test]
+    fn sleep_overrides_apply_per_radio() {
+        let cfg = KeyboardConfig::from_toml("[sleep]\nidle = \"5m\"\nidle_bt = \"1m\"").unwrap();
+        let state = DeviceState {
+            sleep: Some(SleepTimeSettings::default()),
+            ..Default::default()
+        };
+        let plan = cfg.plan(&state).unwrap();
+        let sleep = plan.sleep.unwrap();
+        assert_eq!((sleep.idle_bt, sleep.idle_24g), (60, 300));
+        assert_eq!(sleep.deep_bt, SleepTimeSettings::default().deep_bt);
+    }
+
+    #[test]
+    fn triggers_compare_every_key() {
+        let cfg = KeyboardConfig::from_toml("[triggers]\nactuation_mm = 1.0").unwrap();
+        let mut triggers = TriggerSettings::new(3);
+        let precision = Precision::default();
+        let raw = precision.mm_to_raw(1.0);
+        triggers.press_travel = vec![raw; 3];
+        let mut state = DeviceState {
+            precision,
+            triggers: Some(triggers.clone()),
+            ..Default::default()
+        };
+        assert!(cfg.plan(&state).unwrap().is_empty());
+
+        triggers.press_travel[1] = raw + 1;
+        state.triggers = Some(triggers);
+        let plan = cfg.plan(&state).unwrap();
+        assert_eq!(plan.triggers.actuation, Some(raw));
+        assert!(plan.changes[0].from.starts_with("mixed"));
+    }
+
+    #[test]
+    fn unchanged_macro_is_not_rewritten() {
+        let cfg = KeyboardConfig::from_toml("[[macros]]\nslot = 3\ntext = \"hi\"").unwrap();
+        let mut state = DeviceState::default();
+        let mut slot = MacroWrite {
+            slot: 3,
+            events: text_macro_events("hi", DEFAULT_MACRO_DELAY_MS),
+            repeat: 1,
+        };
+        state.macros.insert(3, slot.clone());
+        assert!(cfg.plan(&state).unwrap().is_empty());
+
+        slot.events.clear();
+        state.macros.insert(3, slot);
+        let plan = cfg.plan(&state).unwrap();
+        assert_eq!(plan.macros.len(), 1);
+        assert_eq!(plan.changes[0].from, "empty");
+    }
+
+    fn full_state() -> DeviceState {
+        use crate::keymap::{default_keycode, RawKeyMapData};
+
+        let key_count = 10;
+        let mut base0 = Vec::new();
+        for i in 0..key_count as u8 {
+            base0.extend_from_slice(&[0, 0, default_keycode(i), 0]);
+        }
+        let caps: KeyRef = "Caps".parse().unwrap();
+        let esc: KeyAction = "Esc".parse().unwrap();
+        let at = caps.index as usize * 4;
+        base0[at..at + 4].copy_from_slice(&esc.to_config_bytes());
+        let mut fn_layer = vec![0; key_count * 4];
+        fn_layer[4..8].copy_from_slice(&KeyAction::Macro { index: 2, kind: 0 }.to_config_bytes());
+
+        let precision = Precision::default();
+        let mut triggers = TriggerSettings::new(key_count);
+        triggers.press_travel = vec![precision.mm_to_raw(1.2); key_count];
+        triggers.lift_travel[0] = precision.mm_to_raw(0.5);
+        triggers.key_modes = vec![ModeByte::RT_FLAG; key_count];
+
+        let macro_seq: MacroSeq = "Ctrl+C,50ms,Ctrl+V".parse().unwrap();
+        let mut state = state_with_led();
+        state.precision = precision;
+        state.polling_rate = PollingRate::from_hz(1000);
+        state.sleep = Some(SleepTimeSettings::new(60, 120, 0, 1680));
+        state.options = Some(KeyboardOptions {
+            os_mode: 1,
+            wasd_swap: true,
+            ..Default::default()
+        });
+        state.triggers = Some(triggers);
+        state.keymap = Some(KeyMap::from_raw(&RawKeyMapData {
+            base0,
+            base1: vec![0; key_count * 4],
+            fn_layer: Some(fn_layer),
+            key_count,
+        }));
+        state.macros.insert(
+            2,
+            MacroWrite {
+                slot: 2,
+                events: macro_seq.to_events(),
+                repeat: 1,
+            },
+        );
+        state
+    }
+
+    #[test]
+    fn dump_reapplies_as_no_op() {
+        let state = full_state();
+        let text = state.to_config_toml(2);
+        let cfg = KeyboardConfig::from_toml(&text).unwrap_or_else(|e| panic!("{e}\n{text}"));
+        assert_eq!(cfg.profile, Some(2));
+        assert_eq!(cfg.remap.get("Caps").map(String::as_str), Some("Escape"));
+        assert_eq!(cfg.macros.len(), 1);
+        let plan = cfg.plan(&state).unwrap();
+        assert!(plan.is_empty(), "{:?}\n{text}", plan.changes);
+    }
+
+    #[test]
+    fn dump_comments_out_mixed_triggers() {
+        let text = full_state().to_config_toml(0);
+        assert!(text.contains("\nactuation_mm = 1.2\n"));
+        assert!(text.contains("\n# release_mm = 0.5  # keys differ"));
+        assert!(text.contains("\nrapid_trigger = true\n"));
+        assert!(text.contains("\nidle_bt = \"1m\"\n"));
+    }
+
+    #[test]
+    fn auto_backups_sort_by_time() {
+        let a = auto_backup_name("pre-flash", 1_760_000_000);
+        let b = auto_backup_name("pre-flash", 1_760_000_060);
+        assert_eq!(a, "pre-flash-1760000000.toml");
+        assert!(a < b);
+        assert!(auto_backup_dir().ends_with("monsgeek/backups"));
+    }
+
+    #[test]
+    fn backup_roundtrips_all_profiles() {
+        let state = full_state();
+        let mut backup = ProfileBackup {
+            device: Some("Test Board".into()),
+            ..Default::default()
+        };
+        for p in 0..PROFILE_COUNT {
+            backup.push_state(p, &state).unwrap();
+        }
+        let text = backup.to_toml();
+        let back = ProfileBackup::from_toml(&text).unwrap_or_else(|e| panic!("{e}\n{text}"));
+        assert_eq!(back, backup);
+        for cfg in &back.profiles {
+            assert!(cfg.plan(&state).unwrap().is_empty());
+        }
+
+        let dup = "[[profiles]]\nprofile = 1\n[[profiles]]\nprofile = 1\n";
+        let err = ProfileBackup::from_toml(dup).unwrap_err().to_string();
+        assert!(err.contains("profiles[1].profile"), "{err}");
+    }
+
+    #[test]
+    fn backup_restores_per_key_triggers_and_remaps() {
+        let saved = full_state();
+        let mut backup = ProfileBackup::default();
+        backup.push_state(0, &saved).unwrap();
+        let text = backup.to_toml();
+        let back = ProfileBackup::from_toml(&text).unwrap_or_else(|e| panic!("{e}\n{text}"));
+        let cfg = &back.profiles[0];
+
+        // The device drifted: uniform release point, one more remap
+        let mut state = full_state();
+        let mut triggers = saved.triggers.clone().unwrap();
+        triggers.lift_travel = vec![triggers.lift_travel[1]; triggers.key_count];
+        state.triggers = Some(triggers);
+        let mut raw = crate::keymap::RawKeyMapData {
+            base0: Vec::new(),
+            base1: vec![0; 40],
+            fn_layer: None,
+            key_count: 10,
+        };
+        for i in 0..10 {
+            raw.base0
+                .extend_from_slice(&[0, 0, crate::keymap::default_keycode(i), 0]);
+        }
+        let tab: KeyRef = "Tab".parse().unwrap();
+        let a: KeyAction = "A".parse().unwrap();
+        let at = tab.index as usize * 4;
+        raw.base0[at..at + 4].copy_from_slice(&a.to_config_bytes());
+        state.keymap = Some(KeyMap::from_raw(&raw));
+
+        let plan = cfg.plan(&state).unwrap();
+        let table = plan.trigger_table.expect("release table restored");
+        assert_eq!(
+            table.lift_travel,
+            saved.triggers.as_ref().unwrap().lift_travel
+        );
+        assert_eq!(plan.triggers, TriggerChanges::default());
+        assert_eq!(plan.resets.len(), 1);
+        assert_eq!(plan.resets[0].index, tab.index);
+        assert!(plan.remaps.iter().any(|(k, _)| k.position == "Caps"));
+        // Undo values line up with the writes: remaps first, then resets
+        assert_eq!(plan.keys_before.len(), plan.remaps.len() + 1);
+        assert_eq!(plan.keys_before.last(), Some(&Some(a)));
+    }
+
+    #[test]
+    fn remaps_without_a_keymap_are_skipped() {
+        let cfg = KeyboardConfig::from_toml("[remap]\nCaps = \"Escape\"").unwrap();
+        let plan = cfg.plan(&state_with_led()).unwrap();
+        assert!(plan.is_empty());
+        assert_eq!(plan.skipped, vec!["remap".to_string()]);
+        // Still validated
+        assert!(KeyboardConfig::from_toml("[remap]\nNoSuchKey = \"A\"").is_err());
+    }
+}
@@ -11,7 +11,7 @@ use crate::protocol::hid;
 use monsgeek_transport::protocol::matrix;
 
 use monsgeek_keyboard::{
-    DksConfig, KeyMode, KeyboardError, KeyboardInterface, ModeByte, SNAPTAP_UNBOUND,
+    DksConfig, KeyMode, KeyboardError, KeyboardInterface, ModeByte, RgbColor, SNAPTAP_UNBOUND,
 };
 
 // Re-export from monsgeek-transport so existing `use crate::keymap::{Layer, KeyRef}` still works.
@@ -246,14 +246,15 @@ pub fn load_async(keyboard: &KeyboardInterface) -> Result<KeyMap, KeyboardError>
 // I/O: writing
 // ---------------------------------------------------------------------------
 
-/// Write a key config via KeyboardInterface (CLI).
+/// Write a key config to `profile` via KeyboardInterface (CLI).
 pub fn set_key_sync(
     kb: &KeyboardInterface,
+    profile: u8,
     index: u8,
     layer: Layer,
     action: &KeyAction,
 ) -> Result<(), KeyboardError> {
-    kb.set_key_config(0, index, layer.wire_layer(), action.to_config_bytes())
+    kb.set_key_config(profile, index, layer.wire_layer(), action.to_config_bytes())
 }
 
 /// Write a key config via KeyboardInterface (TUI async).
@@ -278,20 +279,26 @@ pub fn default_keycode(index: u8) -> u8 {
 /// reset by writing the position's factory-default keycode, not zeros. The overlay
 /// layers (Layer1 / Fn) treat a zero entry as a transparent fall-through to the
 /// base, so zeros are the correct "default" there.
-fn reset_key_impl(kb: &KeyboardInterface, index: u8, layer: Layer) -> Result<(), KeyboardError> {
+fn reset_key_impl(
+    kb: &KeyboardInterface,
+    profile: u8,
+    index: u8,
+    layer: Layer,
+) -> Result<(), KeyboardError> {
     match layer {
-        Layer::Base => kb.set_keymatrix(0, index, default_keycode(index), true, 0),
-        Layer::Layer1 | Layer::Fn => kb.reset_key(layer.wire_layer(), index),
+        Layer::Base => kb.set_keymatrix(profile, index, default_keycode(index), true, 0),
+        Layer::Layer1 | Layer::Fn => kb.reset_key_in_profile(profile, layer.wire_layer(), index),
     }
 }
 
-/// Reset a key to default via KeyboardInterface (CLI).
+/// Reset a key in `profile` to default via KeyboardInterface (CLI).
 pub fn reset_key_sync(
     kb: &KeyboardInterface,
+    profile: u8,
     index: u8,
     layer: Layer,
 ) -> Result<(), KeyboardError> {
-    reset_key_impl(kb, index, layer)
+    reset_key_impl(kb, profile, index, layer)
 }
 
 /// Reset a key to default via KeyboardInterface (TUI async).
@@ -300,7 +307,7 @@ pub fn reset_key_async(
     index: u8,
     layer: Layer,
 ) -> Result<(), KeyboardError> {
-    reset_key_impl(kb, index, layer)
+    reset_key_impl(kb, 0, index, layer)
 }
 
 // ---------------------------------------------------------------------------
@@ -428,6 +435,317 @@ pub fn load_key_rows(kb: &KeyboardInterface) -> Result<Vec<KeyRow>, KeyboardErro
     Ok(rows)
 }
 
+// ---------------------------------------------------------------------------
+// Board layout — which table `matrix::key_name` answers from
+// ---------------------------------------------------------------------------
+
+/// Point the matrix name table (`matrix::key_name`) at the connected board.
+///
+/// Boards the built-in tables don't describe (numpads and macropads, most
+/// boards other than the M1/M3 V5 and FUN60) get a table built from their
+/// database matrix, so default keycodes, resets and name lookups follow the
+/// board's own keys instead of the M1 V5's.
+pub fn select_board_layout(
+    device_id: Option<i32>,
+    db_matrix: Option<&crate::device_loader::JsonDeviceMatrix>,
+) {
+    let Some(id) = device_id else { return };
+    let id = id as u32;
+    if let Some(m) = db_matrix.filter(|m| !layout_describes(matrix::key_names_for(id), &m.matrix)) {
+        matrix::install_layout(id, layout_from_hid_codes(&m.matrix));
+    }
+    matrix::select_layout(id);
+}
+
+/// Point the LED grid lookups (`notify::keymap`) at the connected board.
+///
+/// Key names come from the same places as the matrix names: a builtin or
+/// JSON profile, the database matrix, then a user definition's `ledMatrix`.
+/// Gap columns come from the definition's `ledGapColumns`, then the profile;
+/// boards with neither are taken to have none. Unknown boards keep the
+/// M1 V5 HE layout.
+pub fn select_led_layout(device_id: Option<i32>, vid: u16, pid: u16) {
+    use crate::notify::keymap::{self as led_keymap, LedLayout};
+
+    let registry = crate::profile_registry();
+    let def = device_id.and_then(|id| registry.get_device_info_by_id_and_usb(id, vid, pid));
+    let def_gaps = def.and_then(|d| d.led_gap_columns.as_deref());
+    let layout = if let Some(p) = registry.find_for_device(device_id, vid, pid) {
+        let names = (0..p.matrix_size()).map(|i| p.matrix_key_name(i as u8).to_string());
+        LedLayout::new(names, def_gaps.or(p.led_gap_columns()))
+    } else if let Some(m) = device_id.and_then(|id| registry.get_device_matrix(vid, pid, id)) {
+        let names = (0..m.key_names.len()).map(|i| m.key_name(i).unwrap_or(""));
+        LedLayout::new(names, def_gaps)
+    } else if let Some(def) = def.filter(|d| d.led_matrix.is_some()) {
+        let size = def.led_matrix.as_ref().map_or(0, Vec::len);
+        LedLayout::new((0..size).map(|i| def.key_name(i).unwrap_or("")), def_gaps)
+    } else {
+        LedLayout::m1_v5_he()
+    };
+    led_keymap::select_led_layout(layout);
+}
+
+/// Whether `names` gives every key of a database matrix (HID code per
+/// position) its keycode. Media/consumer codes aren't compared: the built-in
+/// tables leave encoders unnamed.
+fn layout_describes(names: &[&str], codes: &[u8]) -> bool {
+    codes
+        .iter()
+        .enumerate()
+        .filter(|&(_, &code)| (0x04..=0xE7).contains(&code))
+        .all(|(i, &code)| names.get(i).and_then(|n| hid::key_code_from_name(n)) == Some(code))
+}
+
+/// Name table from HID codes per position; `"?"` where no key (or no name
+/// that maps back to the same code).
+fn layout_from_hid_codes(codes: &[u8]) -> Vec<&'static str> {
+    codes
+        .iter()
+        .map(|&code| {
+            let name = hid::key_name(code);
+            if code != 0 && hid::key_code_from_name(name) == Some(code) {
+                name
+            } else {
+                "?"
+            }
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Key name resolution — layout names + HID names, with suggestions
+// ---------------------------------------------------------------------------
+
+/// Resolve a key reference against a device's layout names.
+///
+/// Accepts the same forms as `KeyRef` ("Fn+Caps", "42"), "#1" for a matrix
+/// index that is also a key name, plus the device layout's own names
+/// ("CapsLock", "PageUp") and any HID name or alias that maps to the same
+/// keycode as a layout key ("capslock", "escape", "lshift"). `layout` is
+/// indexed by matrix position; pass an empty slice to fall back to the
+/// built-in M1 V5 names.
+pub fn resolve_key_ref(s: &str, layout: &[String]) -> Result<KeyRef, String> {
+    let (layer, key) = match s.split_once('+') {
+        Some((prefix, key)) if !key.is_empty() => (prefix.parse::<Layer>()?, key),
+        _ => (Layer::Base, s),
+    };
+    if let Some(number) = fn_row_number_key(key, layout) {
+        return Err(format!(
+            "this board has no {key} key; {key} is Fn+{number} (use \"Fn+{number}\")"
+        ));
+    }
+    match resolve_key_index(key, layout) {
+        Some(index) => Ok(KeyRef::new(index, layer)),
+        None => {
+            let candidates = layout.iter().map(String::as_str).chain(hid_key_names());
+            Err(unknown_key_message(key, candidates))
+        }
+    }
+}
+
+/// Matrix position for a key name: `#index`, layout name, bare index,
+/// HID-equivalent name, then the built-in matrix names.
+///
+/// A layout name beats a bare index, so "1" is the 1 key where the board has
+/// one; `#1` always means matrix index 1.
+fn resolve_key_index(key: &str, layout: &[String]) -> Option<u8> {
+    if let Some(index) = key.strip_prefix('#') {
+        return index.parse().ok();
+    }
+    let position = |pred: &dyn Fn(&str) -> bool| {
+        layout
+            .iter()
+            .position(|n| !n.is_empty() && n != "?" && pred(n))
+            .map(|i| i as u8)
+    };
+    position(&|n| n.eq_ignore_ascii_case(key))
+        .or_else(|| key.parse::<u8>().ok())
+        .or_else(|| {
+            let code = hid::key_code_from_name(key)?;
+            position(&|n| hid::key_code_from_name(n) == Some(code))
+        })
+        .or_else(|| matrix::key_index_from_name(key))
+        .or_else(|| {
+            let code = hid::key_code_from_name(key)?;
+            (0..=u8::MAX).find(|&i| matrix::key_name(i) != "?" && default_keycode(i) == code)
+        })
+}
+
+/// On a board without an F-row (60% layouts), the number-row key whose Fn
+/// layer carries `key` (F1 → "1" ... F10 → "0", F11 → "-", F12 → "=").
+///
+/// Only answers for a non-empty `layout` that has the number row but no key
+/// named `key`; the built-in matrix would otherwise place F1 at an empty slot.
+fn fn_row_number_key<'a>(key: &str, layout: &'a [String]) -> Option<&'a str> {
+    const NUMBER_ROW: [&str; 12] = ["1", "2", "3", "4", "5", "6", "7", "8", "9", "0", "-", "="];
+    let n: usize = key.strip_prefix(['F', 'f'])?.parse().ok()?;
+    let number = NUMBER_ROW.get(n.checked_sub(1)?)?;
+    if layout.iter().any(|name| name.eq_ignore_ascii_case(key)) {
+        return None;
+    }
+    layout
+        .iter()
+        .find(|name| name.as_str() == *number)
+        .map(String::as_str)
+}
+
+/// Canonical names from the HID usage table.
+pub fn hid_key_names<'a>() -> impl Iterator<Item = &'a str> {
+    (0x04..=0x67u8)
+        .chain(0xE0..=0xE7)
+        .map(|code| -> &'a str { hid::key_name(code) })
+        .filter(|&n| n != "?")
+}
+
+/// Up to three candidate names closest to `input` (case-insensitive edit
+/// distance), for "did you mean" hints.
+pub fn suggest_key_names<'a>(
+    input: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<&'a str> {
+    let input = input.to_ascii_lowercase();
+    let max_distance = (input.len() / 3).max(1);
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|c| !c.is_empty())
+        .map(|c| (edit_distance(&input, &c.to_ascii_lowercase()), c))
+        .filter(|&(d, c)| d <= max_distance || c.to_ascii_lowercase().starts_with(&input))
+        .collect();
+    scored.sort_by_key(|&(d, _)| d);
+    let mut out: Vec<&str> = Vec::new();
+    for (_, c) in scored {
+        if !out.iter().any(|o| o.eq_ignore_ascii_case(c)) {
+            out.push(c);
+        }
+        if out.len() == 3 {
+            break;
+        }
+    }
+    out
+}
+
+/// "unknown key" error text, with suggestions when any are close enough.
+pub fn unknown_key_message<'a>(key: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    let suggestions = suggest_key_names(key, candidates);
+    if suggestions.is_empty() {
+        format!("unknown key: \"{key}\". Use a matrix index or a name like Esc, CapsLock")
+    } else {
+        format!(
+            "unknown key: \"{key}\". Did you mean: {}?",
+            suggestions.join(", ")
+        )
+    }
+}
+
+/// Levenshtein distance over bytes (key names are ASCII).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.as_bytes();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.bytes().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let sub = prev[j] + usize::from(ca != cb);
+            cur[j + 1] = sub.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+// ---------------------------------------------------------------------------
+// Layout rendering — ASCII keyboard for `iot_driver keys` and `led preview`
+// ---------------------------------------------------------------------------
+
+/// Physical rows in the column-major matrix (`index = col * 6 + row`).
+pub const LAYOUT_ROWS: usize = 6;
+
+/// Inner width of one key cell.
+const CELL_WIDTH: usize = 6;
+
+/// Draw the matrix as a grid of key cells, each showing the matrix index, the
+/// layout name and, when `mapping` returns one, the current action (cut to
+/// the cell width). Positions without a key are left blank; trailing empty
+/// columns are dropped.
+pub fn render_layout(names: &[String], mapping: impl Fn(u8) -> Option<String>) -> String {
+    render_grid(names, |i| {
+        [
+            fit_cell(&i.to_string()),
+            fit_cell(&names[i]),
+            fit_cell(&mapping(i as u8).map_or(String::new(), |m| format!(">{m}"))),
+        ]
+    })
+}
+
+/// Draw the matrix as truecolor key cells: each key is filled with
+/// `color(index)` and labelled in black or white, whichever reads better.
+/// Keys without a color are drawn unfilled.
+pub fn render_color_layout(names: &[String], color: impl Fn(u8) -> Option<RgbColor>) -> String {
+    render_grid(names, |i| {
+        let name = fit_cell(&names[i]);
+        match color(i as u8) {
+            Some(c) => {
+                let luma = 299 * c.r as u32 + 587 * c.g as u32 + 114 * c.b as u32;
+                let fg = if luma > 128_000 { 30 } else { 97 };
+                let fill = |s: &str| format!("\x1b[48;2;{};{};{};{fg}m{s}\x1b[0m", c.r, c.g, c.b);
+                [fill(&fit_cell("")), fill(&name)]
+            }
+            None => [fit_cell(""), name],
+        }
+    })
+}
+
+/// Center `s` in a cell, cut to the cell width.
+fn fit_cell(s: &str) -> String {
+    let s: String = s.chars().take(CELL_WIDTH).collect();
+    format!("{s:^CELL_WIDTH$}")
+}
+
+/// Lay out key cells in physical rows and columns. `cell(index)` gives the
+/// cell's lines, each `CELL_WIDTH` columns wide on screen; empty positions
+/// are blank.
+fn render_grid<const N: usize>(names: &[String], cell: impl Fn(usize) -> [String; N]) -> String {
+    let is_key = |i: usize| names.get(i).is_some_and(|n| !n.is_empty() && n != "?");
+    let cols = (0..names.len())
+        .filter(|&i| is_key(i))
+        .map(|i| i / LAYOUT_ROWS + 1)
+        .max()
+        .unwrap_or(0);
+    let border = format!("+{}\n", format!("{}+", "-".repeat(CELL_WIDTH)).repeat(cols));
+    let blank = fit_cell("");
+
+    let mut out = String::new();
+    for row in 0..LAYOUT_ROWS {
+        out.push_str(&border);
+        let mut lines: [String; N] = std::array::from_fn(|_| String::from("|"));
+        for col in 0..cols {
+            let i = col * LAYOUT_ROWS + row;
+            let text = is_key(i).then(|| cell(i));
+            for (n, line) in lines.iter_mut().enumerate() {
+                line.push_str(text.as_ref().map_or(&blank, |t| &t[n]));
+                line.push('|');
+            }
+        }
+        for line in lines {
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    out.push_str(&border);
+    out
+}
+
+/// Matrix indices of the keys in `names` in reading order: row by row,
+/// left to right, the order QMK `LAYOUT()` macros list keys in.
+pub fn reading_order(names: &[String]) -> Vec<u8> {
+    let is_key = |i: usize| names.get(i).is_some_and(|n| !n.is_empty() && n != "?");
+    let cols = names.len().div_ceil(LAYOUT_ROWS);
+    (0..LAYOUT_ROWS)
+        .flat_map(|row| (0..cols).map(move |col| col * LAYOUT_ROWS + row))
+        .filter(|&i| is_key(i))
+        .map(|i| i as u8)
+        .collect()
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -652,4 +970,120 @@ mod tests {
     fn remap_detection_fn_key() {
         assert!(!is_user_remap(&[10, 1, 0, 0], 0xE4));
     }
+
+    // -- Key name resolution --
+
+    fn layout(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn resolve_hid_alias_against_layout() {
+        let names = layout(&["Esc", "`", "Tab", "CapsLock", "LShift", "LCtrl"]);
+        assert_eq!(resolve_key_ref("capslock", &names).unwrap().index, 3);
+        assert_eq!(resolve_key_ref("Caps", &names).unwrap().index, 3);
+        assert_eq!(resolve_key_ref("escape", &names).unwrap().index, 0);
+        let kr = resolve_key_ref("Fn+lshf", &names).unwrap();
+        assert_eq!((kr.index, kr.layer), (4, Layer::Fn));
+    }
+
+    #[test]
+    fn resolve_without_layout_uses_builtin_matrix() {
+        assert_eq!(resolve_key_ref("capslock", &[]).unwrap().index, 3);
+        assert_eq!(resolve_key_ref("Esc", &[]).unwrap().index, 0);
+        assert_eq!(resolve_key_ref("42", &[]).unwrap().index, 42);
+    }
+
+    #[test]
+    fn layout_names_beat_bare_indices() {
+        let names = layout(&["Esc", "1", "2", "Q"]);
+        assert_eq!(resolve_key_ref("2", &names).unwrap().index, 2);
+        assert_eq!(resolve_key_ref("1", &names).unwrap().index, 1);
+        let names = layout(&["Esc", "Tab", "1"]);
+        assert_eq!(resolve_key_ref("1", &names).unwrap().index, 2);
+        assert_eq!(resolve_key_ref("#1", &names).unwrap().index, 1);
+        assert_eq!(resolve_key_ref("3", &names).unwrap().index, 3);
+        assert!(resolve_key_ref("#x", &names).is_err());
+    }
+
+    #[test]
+    fn f_keys_on_a_60_percent_board_point_at_the_fn_layer() {
+        let names = layout(&["", "Esc", "Tab", "Caps", "LShift", "LCtrl", "", "1", "Q"]);
+        let err = resolve_key_ref("F1", &names).unwrap_err();
+        assert!(err.contains("Fn+1"), "{err}");
+        assert_eq!(resolve_key_ref("Fn+1", &names).unwrap().index, 7);
+
+        // Boards with an F-row, and the built-in matrix, resolve F1 normally
+        let names = layout(&["Esc", "`", "Tab", "Caps", "LShf", "LCtl", "F1", "1"]);
+        assert_eq!(resolve_key_ref("F1", &names).unwrap().index, 6);
+        assert_eq!(resolve_key_ref("F1", &[]).unwrap().index, 6);
+    }
+
+    // -- Board layout --
+
+    #[test]
+    fn numpad_matrix_gets_its_own_table() {
+        // YZ-21 numpad: Esc, KP-, KP6 down the first column
+        let numpad = [0x29, 0x56, 0x5E, 0, 0, 0, 0x2B, 0x5F, 0x59];
+        assert!(!layout_describes(matrix::key_names_for(0), &numpad));
+
+        let names = layout_from_hid_codes(&numpad);
+        assert_eq!(names[1], "KP-");
+        assert_eq!(names[3], "?");
+        assert!(layout_describes(&names, &numpad));
+
+        // The M1 V5 table describes an M1 V5 matrix; encoder codes are ignored
+        assert!(layout_describes(
+            matrix::key_names_for(0),
+            &[0x29, 0x35, 0x2B, 0x39, 0xE1, 0xE0, 0x3A, 0x1E]
+        ));
+        assert!(layout_describes(matrix::key_names_for(0), &[0x29, 0xE9]));
+    }
+
+    #[test]
+    fn unknown_key_suggests_close_names() {
+        let err = resolve_key_ref("capslok", &[]).unwrap_err();
+        assert!(err.contains("Did you mean: CapsLock"), "{err}");
+        assert!(suggest_key_names("zzzzzz", hid_key_names()).is_empty());
+    }
+
+    // -- Layout rendering --
+
+    #[test]
+    fn render_layout_places_keys_column_major() {
+        let mut names = vec![String::new(); 14];
+        names[0] = "Esc".into();
+        names[1] = "`".into();
+        names[6] = "F1".into();
+        names[7] = "1".into();
+        names[12] = "F2".into();
+        let out = render_layout(&names, |i| (i == 7).then(|| "Escape".into()));
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), LAYOUT_ROWS * 4 + 1);
+        assert_eq!(lines[0], "+------+------+------+");
+        assert_eq!(lines[1], "|  0   |  6   |  12  |");
+        assert_eq!(lines[2], "| Esc  |  F1  |  F2  |");
+        assert_eq!(lines[6], "|  `   |  1   |      |");
+        assert_eq!(lines[7], "|      |>Escap|      |");
+    }
+
+    #[test]
+    fn reading_order_walks_rows() {
+        let mut names = vec![String::new(); 14];
+        names[0] = "Esc".into();
+        names[1] = "`".into();
+        names[6] = "F1".into();
+        names[7] = "1".into();
+        names[12] = "F2".into();
+        assert_eq!(reading_order(&names), vec![0, 6, 12, 1, 7]);
+    }
+
+    #[test]
+    fn render_color_layout_fills_keys() {
+        let names = vec!["Esc".to_string(), "`".to_string()];
+        let out = render_color_layout(&names, |i| (i == 0).then(|| RgbColor::new(255, 255, 0)));
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[2], "|\x1b[48;2;255;255;0;30m Esc  \x1b[0m|");
+        assert_eq!(lines[5], "|  `   |");
+    }
 }
@@ -0,0 +1,154 @@
+//! WLED realtime UDP packets for the LED bridge.
+//!
+//! SignalRGB, Artemis, OpenRGB, Hyperion and LedFx can all drive a WLED
+//! device over UDP (port 21324). `led-bridge` pretends to be one: packets
+//! are decoded here into the 16×6 matrix frame used by the streaming path,
+//! LED index = matrix position (`row * 16 + col`).
+//!
+//! Packet layout: `[protocol, timeout, data...]`, where `timeout` is the
+//! number of seconds without packets before the keyboard goes back to its
+//! own effect (255 = never).
+
+use std::time::Duration;
+
+use crate::notify::keymap::MATRIX_LEN;
+
+/// Supported WLED realtime protocols.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// `[index, r, g, b]*`
+    Warls = 1,
+    /// `[r, g, b]*` from LED 0
+    Drgb = 2,
+    /// `[r, g, b, w]*` from LED 0 (white is mixed into r, g, b)
+    Drgbw = 3,
+    /// `[start_hi, start_lo, [r, g, b]*]`
+    Dnrgb = 4,
+}
+
+/// Why a packet was ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketError {
+    TooShort,
+    UnknownProtocol(u8),
+}
+
+impl std::fmt::Display for PacketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooShort => f.write_str("packet too short"),
+            Self::UnknownProtocol(p) => write!(f, "unsupported protocol {p}"),
+        }
+    }
+}
+
+impl std::error::Error for PacketError {}
+
+/// A decoded packet's effect on the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Applied {
+    pub protocol: Protocol,
+    /// Release the LEDs after this long without packets (`None` = never).
+    pub timeout: Option<Duration>,
+}
+
+/// Apply one packet to `leds`. LEDs outside the matrix are ignored, as are
+/// trailing partial entries.
+pub fn apply_packet(
+    packet: &[u8],
+    leds: &mut [(u8, u8, u8); MATRIX_LEN],
+) -> Result<Applied, PacketError> {
+    let [protocol, timeout, data @ ..] = packet else {
+        return Err(PacketError::TooShort);
+    };
+    let mut set = |index: usize, rgb: (u8, u8, u8)| {
+        if let Some(led) = leds.get_mut(index) {
+            *led = rgb;
+        }
+    };
+    let protocol = match protocol {
+        1 => {
+            for e in data.chunks_exact(4) {
+                set(e[0] as usize, (e[1], e[2], e[3]));
+            }
+            Protocol::Warls
+        }
+        2 => {
+            for (i, e) in data.chunks_exact(3).enumerate() {
+                set(i, (e[0], e[1], e[2]));
+            }
+            Protocol::Drgb
+        }
+        3 => {
+            for (i, e) in data.chunks_exact(4).enumerate() {
+                let w = e[3];
+                set(
+                    i,
+                    (
+                        e[0].saturating_add(w),
+                        e[1].saturating_add(w),
+                        e[2].saturating_add(w),
+                    ),
+                );
+            }
+            Protocol::Drgbw
+        }
+        4 => {
+            let [hi, lo, rgb @ ..] = data else {
+                return Err(PacketError::TooShort);
+            };
+            let start = u16::from_be_bytes([*hi, *lo]) as usize;
+            for (i, e) in rgb.chunks_exact(3).enumerate() {
+                set(start + i, (e[0], e[1], e[2]));
+            }
+            Protocol::Dnrgb
+        }
+        &other => return Err(PacketError::UnknownProtocol(other)),
+    };
+    let timeout = match *timeout {
+        255 => None,
+        secs => Some(Duration::from_secs(secs.max(1) as u64)),
+    };
+    Ok(Applied { protocol, timeout })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drgb_fills_from_zero_and_warls_by_index() {
+        let mut leds = [(0, 0, 0); MATRIX_LEN];
+        let applied = apply_packet(&[2, 5, 10, 20, 30, 40, 50, 60, 7], &mut leds).unwrap();
+        assert_eq!(applied.protocol, Protocol::Drgb);
+        assert_eq!(applied.timeout, Some(Duration::from_secs(5)));
+        assert_eq!(leds[0], (10, 20, 30));
+        assert_eq!(leds[1], (40, 50, 60));
+        assert_eq!(leds[2], (0, 0, 0));
+
+        let applied = apply_packet(&[1, 255, 95, 1, 2, 3, 200, 9, 9, 9], &mut leds).unwrap();
+        assert_eq!(applied.timeout, None);
+        assert_eq!(leds[95], (1, 2, 3));
+    }
+
+    #[test]
+    fn dnrgb_starts_at_offset_and_drgbw_mixes_white() {
+        let mut leds = [(0, 0, 0); MATRIX_LEN];
+        apply_packet(&[4, 1, 0, 94, 1, 1, 1, 2, 2, 2, 3, 3, 3], &mut leds).unwrap();
+        assert_eq!(leds[94], (1, 1, 1));
+        assert_eq!(leds[95], (2, 2, 2));
+
+        apply_packet(&[3, 1, 250, 0, 0, 10], &mut leds).unwrap();
+        assert_eq!(leds[0], (255, 10, 10));
+    }
+
+    #[test]
+    fn rejects_bad_packets() {
+        let mut leds = [(0, 0, 0); MATRIX_LEN];
+        assert_eq!(apply_packet(&[2], &mut leds), Err(PacketError::TooShort));
+        assert_eq!(
+            apply_packet(&[9, 1, 0], &mut leds),
+            Err(PacketError::UnknownProtocol(9))
+        );
+    }
+}
@@ -1,31 +1,76 @@
 // MonsGeek M1 V5 HE Linux Driver - Shared Library
 // Protocol definitions, device registry, and HID communication
 
+pub mod ambient;
 pub mod anim;
+pub mod audio_beat;
 pub mod audio_reactive;
+pub mod audio_spectrum;
+pub mod battery_history;
+pub mod battery_warning;
 pub mod bpf_loader;
+pub mod command_probe;
+#[cfg(feature = "dbus")]
+pub mod dbus;
 pub mod device_loader;
+pub mod device_wizard;
 pub mod devices;
 pub mod effect;
+pub mod evdev;
 pub mod firmware;
 pub mod firmware_api;
+pub mod firmware_container;
+pub mod firmware_history;
 pub mod flash;
+pub mod focus;
 pub mod hal;
+pub mod heatmap;
 pub mod hid;
+pub mod idle_dim;
+pub mod input_timing;
 pub mod key_action;
+pub mod key_suppress;
+pub mod key_test;
+pub mod keyboard_config;
 pub mod keymap;
+pub mod led_bridge;
 pub mod led_stream;
+pub mod lock_lighting;
+pub mod macro_file;
 pub mod macro_seq;
+#[cfg(feature = "dbus")]
+pub mod mdns;
+pub mod night_mode;
 pub mod pcap_analyzer;
+pub mod pipewire_audio;
+pub mod plugin;
+pub mod pomodoro;
 pub mod power_supply;
 pub mod profile;
 pub mod protocol;
-pub mod pulse;
+pub mod qmk_keymap;
+pub mod remap;
+pub mod schedule;
 pub mod screen_calib;
 #[cfg(feature = "screen-capture")]
 pub mod screen_capture;
+pub mod screen_zones;
+pub mod selftest;
 pub mod settings;
+#[cfg(feature = "rest")]
+pub mod signaling;
+pub mod switch_health;
+pub mod sysmon;
+pub mod systemd;
+pub mod telemetry;
+#[cfg(feature = "dbus")]
+pub mod tray;
 pub mod tui;
+pub mod via_keymap;
+#[cfg(feature = "rest")]
+pub mod wayland;
+pub mod wlr_screencopy;
+pub mod wpm;
 
 pub use bpf_loader::{AkkoBpfLoader, BpfStatus, KernelBatteryInfo};
 pub use device_loader::{DeviceDatabase, JsonDeviceDefinition};
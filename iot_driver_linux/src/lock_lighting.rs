@@ -0,0 +1,266 @@
+//! Lock-screen lighting: switch the LEDs off (or to a minimal mode) while
+//! the session is locked.
+//!
+//! Configured under `[lock_lighting]` in `settings.toml` and run by the
+//! daemon, which follows logind's `Lock`/`Unlock` signals and the
+//! session's `LockedHint` (set by most screen lockers). The lighting the
+//! user had is restored on unlock.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::keyboard_config::parse_color;
+use crate::protocol::cmd;
+use monsgeek_keyboard::led::LedParams;
+use monsgeek_keyboard::{KeyboardInterface, LedMode};
+
+/// Whether the daemon saw the session locked; other lighting features
+/// (idle dimming) hold off while it is.
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_locked() -> bool {
+    LOCKED.load(Ordering::Relaxed)
+}
+
+/// `[lock_lighting]` settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LockLighting {
+    pub enabled: bool,
+    /// LED mode while locked, as for `set-led` (`off`, `breathing`, ...)
+    pub mode: String,
+    /// Brightness while locked, 0-100 (ignored for `off`)
+    pub brightness: u8,
+    /// Color while locked (`#RRGGBB`, ignored for `off`)
+    pub color: String,
+}
+
+impl Default for LockLighting {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: "off".to_string(),
+            brightness: 10,
+            color: "#FFFFFF".to_string(),
+        }
+    }
+}
+
+impl LockLighting {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The LED settings to show while locked, based on the user's `current`.
+    pub fn locked_params(&self, current: &LedParams) -> Result<LedParams, String> {
+        let mode = cmd::LedMode::parse(&self.mode)
+            .and_then(|m| LedMode::from_u8(m.as_u8()))
+            .ok_or_else(|| format!("unknown lock mode {:?}", self.mode))?;
+        if mode == LedMode::Off {
+            return Ok(LedParams {
+                mode,
+                ..current.clone()
+            });
+        }
+        let color = parse_color(&self.color)
+            .ok_or_else(|| format!("invalid lock color {:?}", self.color))?;
+        Ok(LedParams {
+            mode,
+            brightness: self.brightness.min(100),
+            color,
+            ..current.clone()
+        })
+    }
+}
+
+/// Saves the lighting on lock and puts it back on unlock.
+#[derive(Debug, Default)]
+pub struct LockState {
+    /// The user's settings before locking
+    saved: Option<LedParams>,
+    /// What we wrote on lock
+    applied: Option<LedParams>,
+}
+
+impl LockState {
+    /// Settings to write when the session locks; `None` if already locked
+    /// or nothing changes.
+    pub fn lock(
+        &mut self,
+        current: &LedParams,
+        config: &LockLighting,
+    ) -> Result<Option<LedParams>, String> {
+        if self.saved.is_some() {
+            return Ok(None);
+        }
+        let want = config.locked_params(current)?;
+        self.saved = Some(current.clone());
+        self.applied = Some(want.clone());
+        Ok((want != *current).then_some(want))
+    }
+
+    /// Settings to restore on unlock. A lighting change made while locked
+    /// is kept.
+    pub fn unlock(&mut self, current: &LedParams) -> Option<LedParams> {
+        let saved = self.saved.take()?;
+        let applied = self.applied.take();
+        (applied.as_ref() == Some(current) && saved != *current).then_some(saved)
+    }
+
+    /// Apply a lock state change to the keyboard.
+    pub fn apply(
+        &mut self,
+        keyboard: &KeyboardInterface,
+        locked: bool,
+        config: &LockLighting,
+    ) -> Result<(), String> {
+        let current = keyboard.get_led_params().map_err(|e| e.to_string())?;
+        let change = if locked {
+            self.lock(&current, config)?
+        } else {
+            self.unlock(&current)
+        };
+        if let Some(params) = change {
+            keyboard
+                .set_led_params(&params)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// Daemon task: follow the user's display session lock state and switch
+/// the lighting per `[lock_lighting]`, re-reading the settings on every
+/// change. The keyboard is opened for each change.
+#[cfg(feature = "dbus")]
+pub async fn watch_session_lock(
+    open: impl Fn() -> Result<KeyboardInterface, String> + Send + Sync + 'static,
+) -> zbus::Result<()> {
+    use futures::StreamExt;
+    use std::sync::{Arc, Mutex};
+    use zbus::zvariant::OwnedObjectPath;
+
+    const LOGIN1: &str = "org.freedesktop.login1";
+
+    let conn = zbus::Connection::system().await?;
+    let user = zbus::Proxy::new(
+        &conn,
+        LOGIN1,
+        "/org/freedesktop/login1/user/self",
+        "org.freedesktop.login1.User",
+    )
+    .await?;
+    // The graphical session; a service outside any session has no "auto"
+    let (id, path): (String, OwnedObjectPath) = user.get_property("Display").await?;
+    let path = if id.is_empty() {
+        OwnedObjectPath::try_from("/org/freedesktop/login1/session/auto")?
+    } else {
+        path
+    };
+    let session = zbus::Proxy::new(&conn, LOGIN1, path, "org.freedesktop.login1.Session").await?;
+    let mut lock = session.receive_signal("Lock").await?;
+    let mut unlock = session.receive_signal("Unlock").await?;
+    let mut hint = session.receive_property_changed::<bool>("LockedHint").await;
+
+    let open = Arc::new(open);
+    let state = Arc::new(Mutex::new(LockState::default()));
+    let mut locked = session.get_property::<bool>("LockedHint").await?;
+    let apply = |locked: bool| {
+        LOCKED.store(locked, Ordering::Relaxed);
+        let (open, state) = (Arc::clone(&open), Arc::clone(&state));
+        tokio::task::spawn_blocking(move || {
+            let config = crate::settings::Settings::load().lock_lighting;
+            let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+            // Still restore after being disabled while locked
+            let restoring = !locked && state.saved.is_some();
+            if !config.enabled && !restoring {
+                return;
+            }
+            let result = open().and_then(|kb| state.apply(&kb, locked, &config));
+            if let Err(e) = result {
+                tracing::warn!("lock lighting: {e}");
+            }
+        });
+    };
+    if locked {
+        apply(true);
+    }
+
+    loop {
+        let now = tokio::select! {
+            Some(_) = lock.next() => true,
+            Some(_) = unlock.next() => false,
+            Some(change) = hint.next() => change.get().await?,
+            else => return Ok(()),
+        };
+        if now != locked {
+            locked = now;
+            tracing::debug!(
+                "lock lighting: session {}",
+                if locked { "locked" } else { "unlocked" }
+            );
+            apply(locked);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monsgeek_keyboard::RgbColor;
+
+    fn user() -> LedParams {
+        LedParams {
+            mode: LedMode::Wave,
+            brightness: 80,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn off_by_default_and_minimal_modes_use_the_lock_colors() {
+        let off = LockLighting::default().locked_params(&user()).unwrap();
+        assert_eq!(off.mode, LedMode::Off);
+        assert_eq!(off.brightness, 80);
+
+        let dim = LockLighting {
+            mode: "breathing".to_string(),
+            color: "#FF0000".to_string(),
+            ..Default::default()
+        };
+        let params = dim.locked_params(&user()).unwrap();
+        assert_eq!(params.mode, LedMode::Breathing);
+        assert_eq!(params.brightness, 10);
+        assert_eq!(params.color, RgbColor::new(255, 0, 0));
+
+        let bad = LockLighting {
+            mode: "disco".to_string(),
+            ..Default::default()
+        };
+        assert!(bad.locked_params(&user()).is_err());
+    }
+
+    #[test]
+    fn restores_on_unlock() {
+        let config = LockLighting::default();
+        let mut state = LockState::default();
+        let off = state.lock(&user(), &config).unwrap().unwrap();
+        // A second Lock (signal plus LockedHint) changes nothing
+        assert_eq!(state.lock(&off, &config).unwrap(), None);
+        assert_eq!(state.unlock(&off), Some(user()));
+        assert_eq!(state.unlock(&user()), None);
+    }
+
+    #[test]
+    fn change_made_while_locked_is_kept() {
+        let config = LockLighting::default();
+        let mut state = LockState::default();
+        state.lock(&user(), &config).unwrap();
+        let changed = LedParams {
+            mode: LedMode::Constant,
+            ..user()
+        };
+        assert_eq!(state.unlock(&changed), None);
+    }
+}
@@ -0,0 +1,236 @@
+//! Macro files (`iot_driver macro export/import`).
+//!
+//! One macro per TOML file, so macros can live in version control, be shared,
+//! and be loaded onto several keyboards. The body is exactly one of
+//! `sequence` (see [`crate::macro_seq`]), `text`, or an `[[events]]` list.
+//! `export` always writes events, which round-trip exactly.
+//!
+//! ```toml
+//! name = "copy-paste"   # optional, informational only
+//! repeat = 1            # times to play per trigger
+//! delay_ms = 10         # default for events that don't set their own
+//!
+//! [[events]]
+//! key = "LCtrl"         # HID key name (A, Escape, LShift, F13) or "0xE0"
+//! action = "down"       # down | up | tap (down + up)
+//! delay_ms = 0          # wait after this event
+//!
+//! [[events]]
+//! key = "C"
+//! action = "tap"
+//!
+//! [[events]]
+//! key = "LCtrl"
+//! action = "up"
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::keyboard_config::{
+    default_macro_delay, default_macro_repeat, dominant_delay, ConfigError,
+};
+use crate::keymap::{hid_key_names, suggest_key_names};
+use crate::macro_seq::MacroSeq;
+use crate::protocol::hid;
+use monsgeek_keyboard::text_macro_events;
+
+/// A single macro, as stored in a macro file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MacroFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default = "default_macro_repeat")]
+    pub repeat: u16,
+    #[serde(default = "default_macro_delay")]
+    pub delay_ms: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<MacroFileEvent>,
+}
+
+/// One entry of `[[events]]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MacroFileEvent {
+    pub key: String,
+    pub action: EventAction,
+    /// Wait after this event; the file's `delay_ms` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_ms: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventAction {
+    Down,
+    Up,
+    /// Press then release, each followed by the event's delay.
+    Tap,
+}
+
+impl MacroFile {
+    /// Parse and validate a macro file; key names and sequences are checked here.
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        let file: Self = toml::from_str(s)?;
+        file.to_events()?;
+        Ok(file)
+    }
+
+    /// Build a file from wire events, folding press/release pairs into taps.
+    pub fn from_events(events: &[(u8, bool, u16)], repeat: u16) -> Self {
+        let delay_ms = dominant_delay(events);
+        let delay = |d: u16| (d != delay_ms).then_some(d);
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < events.len() {
+            let (code, is_down, d) = events[i];
+            let tap = is_down && events.get(i + 1) == Some(&(code, false, d));
+            out.push(MacroFileEvent {
+                key: key_label(code),
+                action: match (tap, is_down) {
+                    (true, _) => EventAction::Tap,
+                    (false, true) => EventAction::Down,
+                    (false, false) => EventAction::Up,
+                },
+                delay_ms: delay(d),
+            });
+            i += if tap { 2 } else { 1 };
+        }
+        Self {
+            name: None,
+            repeat,
+            delay_ms,
+            sequence: None,
+            text: None,
+            events: out,
+        }
+    }
+
+    /// Expand to wire events `(keycode, is_down, delay_ms)`.
+    pub fn to_events(&self) -> Result<Vec<(u8, bool, u16)>, ConfigError> {
+        match (&self.sequence, &self.text, self.events.is_empty()) {
+            (Some(seq), None, true) => {
+                let mut seq: MacroSeq = seq
+                    .parse()
+                    .map_err(|e| ConfigError::invalid("sequence", format!("{e}")))?;
+                seq.default_delay = self.delay_ms;
+                Ok(seq.to_events())
+            }
+            (None, Some(text), true) => Ok(text_macro_events(text, self.delay_ms)),
+            (None, None, false) => {
+                let mut out = Vec::new();
+                for (i, e) in self.events.iter().enumerate() {
+                    let code = parse_key(&e.key).map_err(|reason| {
+                        ConfigError::invalid(format!("events[{i}].key"), reason)
+                    })?;
+                    let d = e.delay_ms.unwrap_or(self.delay_ms);
+                    match e.action {
+                        EventAction::Down => out.push((code, true, d)),
+                        EventAction::Up => out.push((code, false, d)),
+                        EventAction::Tap => out.extend([(code, true, d), (code, false, d)]),
+                    }
+                }
+                Ok(out)
+            }
+            _ => Err(ConfigError::invalid(
+                "macro",
+                "set exactly one of `sequence`, `text` or `[[events]]`",
+            )),
+        }
+    }
+
+    /// Render as TOML with a short header naming the import command.
+    pub fn to_toml(&self) -> String {
+        let mut out =
+            String::from("# Macro file: load with `iot_driver macro import <file> <slot>`\n");
+        if let Ok(events) = self.to_events() {
+            let seq = MacroSeq::from_events(&events, self.delay_ms, self.repeat);
+            out.push_str(&format!("# Sequence: {seq}\n"));
+        }
+        out.push('\n');
+        out.push_str(&toml::to_string(self).unwrap_or_default());
+        out
+    }
+}
+
+/// Key name that reads back to the same keycode, else hex.
+fn key_label(code: u8) -> String {
+    if (0x68..=0x73).contains(&code) {
+        return format!("F{}", code - 0x68 + 13);
+    }
+    let name = hid::key_name(code);
+    if hid::key_code_from_name(name) == Some(code) {
+        name.to_string()
+    } else {
+        format!("0x{code:02X}")
+    }
+}
+
+/// HID keycode from a name or hex literal.
+fn parse_key(key: &str) -> Result<u8, String> {
+    if let Some(hex) = key.strip_prefix("0x").or_else(|| key.strip_prefix("0X")) {
+        return u8::from_str_radix(hex, 16).map_err(|_| format!("invalid hex keycode \"{key}\""));
+    }
+    hid::key_code_from_name(key).ok_or_else(|| {
+        let suggestions = suggest_key_names(key, hid_key_names());
+        if suggestions.is_empty() {
+            format!("unknown key \"{key}\"")
+        } else {
+            format!(
+                "unknown key \"{key}\". Did you mean: {}?",
+                suggestions.join(", ")
+            )
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_roundtrip_through_toml() {
+        let events = vec![
+            (0xE0, true, 0),
+            (0x06, true, 10),
+            (0x06, false, 10),
+            (0xE0, false, 50),
+            (0x6A, true, 10),
+            (0x6A, false, 10),
+            (0x91, true, 10),
+        ];
+        let file = MacroFile::from_events(&events, 2);
+        assert_eq!(file.events[1].action, EventAction::Tap);
+        assert_eq!(file.events[3].key, "F15");
+        assert_eq!(file.events[4].key, "0x91");
+        let text = file.to_toml();
+        let back = MacroFile::from_toml(&text).unwrap();
+        assert_eq!(back.repeat, 2);
+        assert_eq!(back.to_events().unwrap(), events);
+    }
+
+    #[test]
+    fn sequence_and_text_bodies() {
+        let seq = MacroFile::from_toml("sequence = \"Ctrl+C\"\ndelay_ms = 20").unwrap();
+        assert_eq!(seq.to_events().unwrap().len(), 4);
+        let text = MacroFile::from_toml("text = \"hi\"").unwrap();
+        assert_eq!(text.to_events().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn rejects_bad_bodies() {
+        assert!(MacroFile::from_toml("repeat = 1").is_err());
+        assert!(MacroFile::from_toml("text = \"a\"\nsequence = \"A\"").is_err());
+        let err = MacroFile::from_toml("[[events]]\nkey = \"Escpe\"\naction = \"tap\"")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("events[0].key") && err.contains("Escape"),
+            "{err}"
+        );
+    }
+}
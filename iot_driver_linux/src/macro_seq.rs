@@ -415,6 +415,47 @@ impl MacroSeq {
     }
 }
 
+/// Convert recorded key edges `(keycode, is_down, timestamp)` into wire events.
+///
+/// Each event's delay is the gap until the next edge, capped at `max_delay`.
+/// Releases with no recorded press (keys already held when recording began)
+/// are dropped, and keys still held at the end get a trailing release so the
+/// macro never leaves a key stuck down.
+pub fn events_from_recording(
+    edges: &[(u8, bool, std::time::Duration)],
+    max_delay: u16,
+) -> Vec<(u8, bool, u16)> {
+    let mut held: Vec<u8> = Vec::new();
+    let mut kept: Vec<(u8, bool, std::time::Duration)> = Vec::new();
+    for &(code, is_down, time) in edges {
+        if is_down {
+            if held.contains(&code) {
+                continue;
+            }
+            held.push(code);
+        } else if let Some(pos) = held.iter().position(|&c| c == code) {
+            held.remove(pos);
+        } else {
+            continue;
+        }
+        kept.push((code, is_down, time));
+    }
+
+    let mut events: Vec<(u8, bool, u16)> = kept
+        .iter()
+        .enumerate()
+        .map(|(i, &(code, is_down, time))| {
+            let gap = kept
+                .get(i + 1)
+                .map(|next| next.2.saturating_sub(time).as_millis())
+                .unwrap_or(0);
+            (code, is_down, gap.min(max_delay as u128) as u16)
+        })
+        .collect();
+    events.extend(held.into_iter().rev().map(|code| (code, false, 0)));
+    events
+}
+
 /// Try to match a combo tap pattern starting at `events[0]`.
 /// Pattern: ↓Mod1(0)... ↓ModN(0) ↓Key(d) ↑Key(0) ↑ModN(0)... ↑Mod1(d2)
 /// Returns the MacroStep and number of events consumed.
@@ -1214,4 +1255,46 @@ mod tests {
         let reconstructed = MacroSeq::from_events(&events, seq.default_delay, seq.repeat);
         assert_eq!(reconstructed.to_string(), input);
     }
+
+    // --- Recording tests ---
+
+    #[test]
+    fn recording_delays_are_gaps_to_next_edge() {
+        let ms = std::time::Duration::from_millis;
+        let edges = [
+            (0x04, true, ms(1000)),
+            (0x04, false, ms(1040)),
+            (0x05, true, ms(5000)),
+            (0x05, false, ms(5020)),
+        ];
+        assert_eq!(
+            events_from_recording(&edges, 500),
+            vec![
+                (0x04, true, 40),
+                (0x04, false, 500),
+                (0x05, true, 20),
+                (0x05, false, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn recording_drops_orphan_release_and_releases_held_keys() {
+        let ms = std::time::Duration::from_millis;
+        let edges = [
+            (0x28, false, ms(0)), // Enter released from launching the command
+            (0xE1, true, ms(10)),
+            (0x04, true, ms(20)),
+            (0x04, false, ms(30)),
+        ];
+        assert_eq!(
+            events_from_recording(&edges, 1000),
+            vec![
+                (0xE1, true, 10),
+                (0x04, true, 10),
+                (0x04, false, 0),
+                (0xE1, false, 0)
+            ]
+        );
+    }
 }
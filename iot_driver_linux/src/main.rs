@@ -4,13 +4,19 @@
 
 use clap::Parser;
 use hidapi::HidApi;
+use tonic::transport::server::TcpIncoming;
 use tonic::transport::Server;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::validate_request::{ValidateRequest, ValidateRequestHeaderLayer};
 use tracing::info;
 
 // CLI definitions
 mod cli;
-use cli::{Cli, Commands, DongleCommands, EffectCommands, FirmwareCommands};
+use cli::{
+    AudioMode, Cli, Commands, ConfigCommands, DeviceCommands, DongleCommands, EffectCommands,
+    FirmwareCommands, HeatmapCommands, KeymapCommands, LedCommands, MacroCommands, PcapCommands,
+    PluginCommands, ProbeCommands, ProfileCommands, ServerArgs, StateCommands,
+};
 
 // Command handlers (split from main.rs)
 mod commands;
@@ -20,10 +26,29 @@ use commands::CmdCtx;
 mod grpc;
 use grpc::{dj_dev, DriverGrpcServer, DriverService};
 
+// REST/JSON façade served next to gRPC
+#[cfg(feature = "rest")]
+mod rest;
+#[cfg(feature = "rest")]
+mod webrtc;
+#[cfg(feature = "rest")]
+mod ws;
+
+// TLS listener for the server
+#[cfg(feature = "tls")]
+mod tls;
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
+    #[cfg(feature = "hid-trace")]
+    if cli.trace {
+        init_trace_logging();
+    }
+
+    iot_driver::firmware_api::set_offline(cli.offline);
+
     // Handle --file flag for pcap replay mode (no device needed)
     if let Some(ref pcap_file) = cli.pcap_file {
         return iot_driver::pcap_analyzer::run_pcap_analysis(
@@ -45,41 +70,58 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         cli.filter.as_deref(),
         cli.record.as_deref(),
     )?;
-    let ctx = CmdCtx::new(printer_config.clone(), cli.device);
+    let ctx = CmdCtx::new(printer_config.clone(), cli.device)
+        .with_profile(cli.target_profile)
+        .with_json(cli.json);
 
     match cli.command {
         None => {
             // Default: show device info
-            commands::query::info(&ctx)?;
+            commands::json_errors(&ctx, commands::query::info(&ctx))?;
         }
 
         // === Query Commands ===
         Some(Commands::Info) => {
-            commands::query::info(&ctx)?;
-        }
-        Some(Commands::Profile) => {
-            commands::query::profile(&ctx)?;
-        }
-        Some(Commands::Led) => {
-            commands::query::led(&ctx)?;
+            commands::json_errors(&ctx, commands::query::info(&ctx))?;
         }
+        Some(Commands::Profile { action }) => match action {
+            Some(ProfileCommands::Backup { file }) => {
+                commands::config::backup(&ctx, &file)?;
+            }
+            Some(ProfileCommands::Restore { file, dry_run }) => {
+                commands::config::restore(&ctx, &file, dry_run)?;
+            }
+            None => {
+                commands::json_errors(&ctx, commands::query::profile(&ctx))?;
+            }
+        },
+        Some(Commands::Led { action }) => match action {
+            Some(LedCommands::Preview { slot, watch }) => {
+                commands::led_preview::preview(&ctx, slot, watch)?;
+            }
+            None => {
+                commands::json_errors(&ctx, commands::query::led(&ctx))?;
+            }
+        },
         Some(Commands::Debounce) => {
-            commands::query::debounce(&ctx)?;
+            commands::json_errors(&ctx, commands::query::debounce(&ctx))?;
         }
         Some(Commands::Rate) => {
-            commands::with_keyboard(&ctx, commands::query::rate)?;
+            let result = commands::with_keyboard(&ctx, |kb| commands::query::rate(kb, ctx.json));
+            commands::json_errors(&ctx, result)?;
         }
         Some(Commands::Options) => {
-            commands::query::options(&ctx)?;
+            commands::json_errors(&ctx, commands::query::options(&ctx))?;
         }
         Some(Commands::Features) => {
-            commands::query::features(&ctx)?;
+            commands::json_errors(&ctx, commands::query::features(&ctx))?;
         }
         Some(Commands::Sleep) => {
-            commands::with_keyboard(&ctx, commands::query::sleep)?;
+            let result = commands::with_keyboard(&ctx, |kb| commands::query::sleep(kb, ctx.json));
+            commands::json_errors(&ctx, result)?;
         }
         Some(Commands::All) => {
-            commands::query::all(&ctx)?;
+            commands::json_errors(&ctx, commands::query::all(&ctx))?;
         }
         Some(Commands::Battery {
             quiet,
@@ -87,8 +129,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             watch,
             vendor,
         }) => {
-            let hidapi = HidApi::new()?;
-            commands::query::battery(&hidapi, quiet, hex, watch, vendor)?;
+            let result = HidApi::new().map_err(Into::into).and_then(|hidapi| {
+                commands::query::battery(&hidapi, quiet, hex, watch, vendor, ctx.json)
+            });
+            commands::json_errors(&ctx, result)?;
         }
 
         // === Set Commands ===
@@ -109,7 +153,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             g,
             b,
         }) => {
-            commands::with_keyboard(&ctx, |kb| {
+            commands::with_keyboard_in_profile(&ctx, |kb| {
                 commands::set::set_led(kb, &mode, brightness, speed, r, g, b)
             })?;
         }
@@ -128,11 +172,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 )
             })?;
         }
-        Some(Commands::Reset) => {
-            commands::with_keyboard(&ctx, commands::set::reset)?;
+        Some(Commands::Reset { no_backup }) => {
+            commands::with_keyboard(&ctx, |kb| commands::set::reset(&ctx, kb, no_backup))?;
         }
         Some(Commands::SetColorAll { r, g, b, layer }) => {
-            commands::with_keyboard(&ctx, |kb| commands::set::set_color_all(kb, r, g, b, layer))?;
+            commands::with_keyboard_in_profile(&ctx, |kb| {
+                commands::set::set_color_all(kb, r, g, b, layer)
+            })?;
         }
 
         // === Trigger Commands ===
@@ -140,22 +186,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             commands::with_keyboard(&ctx, commands::triggers::calibrate)?;
         }
         Some(Commands::Triggers) => {
-            commands::with_keyboard(&ctx, commands::triggers::triggers)?;
+            let result = commands::with_keyboard_in_profile(&ctx, |kb| {
+                commands::triggers::triggers(kb, ctx.json)
+            });
+            commands::json_errors(&ctx, result)?;
         }
         Some(Commands::SetActuation { mm }) => {
-            commands::with_keyboard(&ctx, |kb| commands::triggers::set_actuation(kb, mm))?;
+            commands::with_keyboard_in_profile(&ctx, |kb| {
+                commands::triggers::set_actuation(kb, mm)
+            })?;
         }
         Some(Commands::SetRt { value }) => {
-            commands::with_keyboard(&ctx, |kb| commands::triggers::set_rt(kb, &value))?;
+            commands::with_keyboard_in_profile(&ctx, |kb| commands::triggers::set_rt(kb, &value))?;
         }
         Some(Commands::SetRelease { mm }) => {
-            commands::with_keyboard(&ctx, |kb| commands::triggers::set_release(kb, mm))?;
+            commands::with_keyboard_in_profile(&ctx, |kb| commands::triggers::set_release(kb, mm))?;
         }
         Some(Commands::SetBottomDeadzone { mm }) => {
-            commands::with_keyboard(&ctx, |kb| commands::triggers::set_bottom_deadzone(kb, mm))?;
+            commands::with_keyboard_in_profile(&ctx, |kb| {
+                commands::triggers::set_bottom_deadzone(kb, mm)
+            })?;
         }
         Some(Commands::SetTopDeadzone { mm }) => {
-            commands::with_keyboard(&ctx, |kb| commands::triggers::set_top_deadzone(kb, mm))?;
+            commands::with_keyboard_in_profile(&ctx, |kb| {
+                commands::triggers::set_top_deadzone(kb, mm)
+            })?;
         }
         Some(Commands::SetKeyTrigger {
             key,
@@ -165,21 +220,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             rt,
         }) => {
             let mode = mode.map(Into::into);
-            commands::with_keyboard(&ctx, |kb| {
+            commands::with_keyboard_in_profile(&ctx, |kb| {
                 commands::triggers::set_key_trigger(kb, key, actuation, release, mode, rt)
             })?;
         }
         Some(Commands::SetModeAll { mode, rt }) => {
             let mode = mode.into();
-            commands::with_keyboard(&ctx, |kb| commands::triggers::set_mode_all(kb, mode, rt))?;
+            commands::with_keyboard_in_profile(&ctx, |kb| {
+                commands::triggers::set_mode_all(kb, mode, rt)
+            })?;
         }
         Some(Commands::SetSnaptap { key, with, clear }) => {
-            commands::with_keyboard(&ctx, |kb| {
+            commands::with_keyboard_in_profile(&ctx, |kb| {
                 commands::triggers::set_snaptap(kb, key, with, clear)
             })?;
         }
         Some(Commands::SetModtapTime { key, ms }) => {
-            commands::with_keyboard(&ctx, |kb| commands::triggers::set_modtap_time(kb, key, ms))?;
+            commands::with_keyboard_in_profile(&ctx, |kb| {
+                commands::triggers::set_modtap_time(kb, key, ms)
+            })?;
         }
         Some(Commands::Dks {
             key,
@@ -188,7 +247,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             slots,
             rt,
         }) => {
-            commands::with_keyboard(&ctx, |kb| {
+            commands::with_keyboard_in_profile(&ctx, |kb| {
                 commands::triggers::dks(kb, key, travel_mm, modes, slots, rt)
             })?;
         }
@@ -198,13 +257,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // === Keymap Commands ===
         Some(Commands::Remap { from, to, layer }) => {
-            commands::with_keyboard(&ctx, |kb| commands::keymap::remap(kb, &from, &to, layer))?;
+            commands::with_keyboard(&ctx, |kb| {
+                commands::keymap::remap(kb, &from, &to, layer, ctx.keymap_profile())
+            })?;
         }
         Some(Commands::ResetKey { key, layer }) => {
-            commands::with_keyboard(&ctx, |kb| commands::keymap::reset_key(kb, &key, layer))?;
+            commands::with_keyboard(&ctx, |kb| {
+                commands::keymap::reset_key(kb, &key, layer, ctx.keymap_profile())
+            })?;
         }
         Some(Commands::Swap { key1, key2, layer }) => {
-            commands::with_keyboard(&ctx, |kb| commands::keymap::swap(kb, &key1, &key2, layer))?;
+            let profile = ctx.profile.unwrap_or(layer);
+            commands::with_keyboard(&ctx, |kb| commands::keymap::swap(kb, &key1, &key2, profile))?;
+        }
+        Some(Commands::Keys { layer }) => {
+            commands::with_keyboard(&ctx, |kb| commands::keymap::keys(kb, layer))?;
         }
         Some(Commands::RemapList { layer, all }) => {
             commands::with_keyboard(&ctx, |kb| commands::keymap::remap_list(kb, layer, all))?;
@@ -215,11 +282,103 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Some(Commands::Keymatrix { layer }) => {
             commands::with_keyboard(&ctx, |kb| commands::keymap::keymatrix(kb, layer))?;
         }
+        Some(Commands::Keymap { action }) => match action {
+            KeymapCommands::Export { file, name } => {
+                commands::keymap::export_via(&ctx, &file, name)?;
+            }
+            KeymapCommands::Import { file, dry_run } => {
+                commands::keymap::import_via(&ctx, &file, dry_run)?;
+            }
+            KeymapCommands::ImportQmk { file, dry_run } => {
+                commands::keymap::import_qmk(&ctx, &file, dry_run)?;
+            }
+        },
+        Some(Commands::Plugin { action }) => match action {
+            PluginCommands::List => commands::plugin::list(&ctx)?,
+            PluginCommands::Run {
+                name,
+                fps,
+                power_budget,
+            } => commands::plugin::run_effect(&ctx, name.as_deref(), fps, power_budget)?,
+        },
+        Some(Commands::Pcap { action }) => match action {
+            PcapCommands::Diff {
+                a,
+                b,
+                responses,
+                ignore,
+                same,
+            } => commands::debug::pcap_diff(&a, &b, responses, ignore, same)?,
+            PcapCommands::Extract { file, output, keys } => {
+                commands::debug::pcap_extract(&file, output.as_deref(), keys)?
+            }
+            PcapCommands::Stats {
+                file,
+                timeline,
+                gap,
+            } => commands::debug::pcap_stats(&file, timeline, gap, cli.json)?,
+            PcapCommands::Fixtures {
+                file,
+                output,
+                max_per_cmd,
+            } => commands::debug::pcap_fixtures(&file, output.as_deref(), max_per_cmd)?,
+            PcapCommands::Dissector { output } => {
+                commands::debug::pcap_dissector(output.as_deref())?
+            }
+            PcapCommands::Replay {
+                file,
+                yes,
+                gets_only,
+                delay,
+            } => commands::debug::pcap_replay(&ctx, &file, yes, gets_only, delay)?,
+        },
+        Some(Commands::State { action }) => match action {
+            StateCommands::Set { name, value, ttl } => {
+                commands::effect::state_set(&name, &value, ttl.as_deref())?;
+            }
+            StateCommands::Clear { name } => commands::effect::state_clear(&name)?,
+            StateCommands::List => commands::effect::state_list()?,
+        },
+        Some(Commands::Heatmap { action }) => match action {
+            HeatmapCommands::Record { depth, threshold } => {
+                commands::heatmap::record(&ctx, depth, threshold)?;
+            }
+            HeatmapCommands::Show { top } => commands::heatmap::show(&ctx, top)?,
+            HeatmapCommands::Led { power_budget } => commands::heatmap::led(&ctx, power_budget)?,
+            HeatmapCommands::Reset => commands::heatmap::reset(&ctx)?,
+        },
 
         // === Macro Commands ===
-        Some(Commands::Macro { key }) => {
-            commands::with_keyboard(&ctx, |kb| commands::macros::get_macro(kb, &key))?;
-        }
+        Some(Commands::Macro { key, action }) => match action {
+            Some(MacroCommands::Record {
+                slot,
+                stop,
+                delay,
+                max_delay,
+                repeat,
+                yes,
+            }) => {
+                commands::with_keyboard(&ctx, |kb| {
+                    commands::macros::record_macro(kb, slot, &stop, delay, max_delay, repeat, yes)
+                })?;
+            }
+            Some(MacroCommands::Export { slot, file, name }) => {
+                commands::with_keyboard(&ctx, |kb| {
+                    commands::macros::export_macro(kb, slot, &file, name)
+                })?;
+            }
+            Some(MacroCommands::Import {
+                file,
+                slot,
+                dry_run,
+            }) => {
+                commands::macros::import_macro(&ctx, &file, slot, dry_run)?;
+            }
+            None => {
+                let key = key.unwrap_or_default();
+                commands::with_keyboard(&ctx, |kb| commands::macros::get_macro(kb, &key))?;
+            }
+        },
         Some(Commands::SetMacro {
             key,
             text,
@@ -264,8 +423,87 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }) => {
             commands::led_stream::stream_gif(&ctx, &file, fps, r#loop, power_budget)?;
         }
+        Some(Commands::LedBridge {
+            listen,
+            fps,
+            power_budget,
+        }) => {
+            commands::led_stream::bridge(&ctx, listen, fps, power_budget)?;
+        }
+        Some(Commands::Telemetry {
+            listen,
+            file,
+            fps,
+            power_budget,
+        }) => {
+            commands::led_stream::telemetry(&ctx, listen, file, fps, power_budget)?;
+        }
+        Some(Commands::Wpm {
+            display,
+            max_wpm,
+            window,
+            colors,
+            power_budget,
+        }) => {
+            let defaults = iot_driver::wpm::WpmStyle::default();
+            let (slow, fast) = match colors {
+                Some(c) => iot_driver::wpm::parse_colors(&c)?,
+                None => (defaults.slow, defaults.fast),
+            };
+            let style = iot_driver::wpm::WpmStyle {
+                display: display.into(),
+                slow,
+                fast,
+                max_wpm,
+            };
+            let window = std::time::Duration::from_secs_f32(window.max(1.0));
+            commands::reactive::wpm(&ctx, &style, window, power_budget)?;
+        }
+        Some(Commands::Sysmon {
+            metric,
+            layout,
+            interval,
+            power_budget,
+        }) => {
+            let saved = iot_driver::settings::Settings::load().sysmon;
+            let config = iot_driver::sysmon::SysmonConfig {
+                metric: metric.map_or(saved.metric, Into::into),
+                layout: layout.map_or(saved.layout, Into::into),
+                interval_ms: interval.unwrap_or(saved.interval_ms),
+                power_budget: power_budget.unwrap_or(saved.power_budget),
+                ..saved
+            };
+            commands::reactive::sysmon(&ctx, config)?;
+        }
+        Some(Commands::Timer {
+            work,
+            rest,
+            cycles,
+            style,
+            colors,
+            power_budget,
+        }) => {
+            let defaults = iot_driver::pomodoro::TimerConfig::default();
+            let (start, end, rest_color) = match colors {
+                Some(c) => iot_driver::pomodoro::parse_colors(&c)?,
+                None => (defaults.start, defaults.end, defaults.rest_color),
+            };
+            let minutes = |m: f32| std::time::Duration::from_secs_f32(m.max(0.0) * 60.0);
+            let config = iot_driver::pomodoro::TimerConfig {
+                work: minutes(work),
+                rest: minutes(rest),
+                cycles,
+                style: style.into(),
+                start,
+                end,
+                rest_color,
+            };
+            commands::reactive::timer(&ctx, &config, power_budget)?;
+        }
         Some(Commands::Mode { mode, layer }) => {
-            commands::with_keyboard(&ctx, |kb| commands::animations::mode(kb, &mode, layer))?;
+            commands::with_keyboard_in_profile(&ctx, |kb| {
+                commands::animations::mode(kb, &mode, layer)
+            })?;
         }
         Some(Commands::Modes) => {
             commands::animations::modes()?;
@@ -278,18 +516,102 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             sensitivity,
             rate,
             device,
-        }) => {
-            commands::reactive::audio(&ctx, mode.led_mode(), style, sensitivity, rate, device)?;
-        }
+            app,
+            bands,
+            colors,
+            falloff,
+            effect,
+            attack,
+            decay,
+            power_budget,
+        }) => match mode.led_mode() {
+            Some(led_mode) => {
+                commands::reactive::audio(&ctx, led_mode, style, sensitivity, rate, device, app)?;
+            }
+            None if mode == AudioMode::Beat => {
+                let defaults = iot_driver::audio_beat::BeatStyle::default();
+                let (idle, beat) = match colors {
+                    Some(c) => iot_driver::audio_beat::parse_colors(&c)?,
+                    None => (defaults.idle, defaults.beat),
+                };
+                let style = iot_driver::audio_beat::BeatStyle {
+                    effect: effect.into(),
+                    idle,
+                    beat,
+                    attack: std::time::Duration::from_millis(attack),
+                    decay: std::time::Duration::from_millis(decay),
+                };
+                commands::reactive::audio_beat(
+                    &ctx,
+                    style,
+                    sensitivity,
+                    rate,
+                    device,
+                    app,
+                    power_budget,
+                )?;
+            }
+            None => {
+                let defaults = iot_driver::audio_spectrum::SpectrumStyle::default();
+                let (low, high, peak) = match colors {
+                    Some(c) => iot_driver::audio_spectrum::parse_colors(&c)?,
+                    None => (defaults.low, defaults.high, defaults.peak),
+                };
+                let style = iot_driver::audio_spectrum::SpectrumStyle {
+                    bands: bands as usize,
+                    low,
+                    high,
+                    peak,
+                    falloff,
+                };
+                commands::reactive::audio_spectrum(
+                    &ctx,
+                    style,
+                    sensitivity,
+                    rate,
+                    device,
+                    app,
+                    power_budget,
+                )?;
+            }
+        },
         Some(Commands::AudioTest) => {
             commands::reactive::audio_test()?;
         }
-        Some(Commands::AudioLevels { device }) => {
-            commands::reactive::audio_levels(device)?;
+        Some(Commands::AudioLevels { device, app }) => {
+            commands::reactive::audio_levels(device, app)?;
         }
         #[cfg(feature = "screen-capture")]
-        Some(Commands::Screen { fps }) => {
-            commands::reactive::screen(&ctx, fps).await?;
+        Some(Commands::Screen {
+            fps,
+            backend,
+            zones,
+            zone_mode,
+            zone_columns,
+            zone_rows,
+            edge_depth,
+            save_zones,
+            power_budget,
+        }) => {
+            if zones {
+                let overrides = iot_driver::screen_zones::ZoneOverrides {
+                    mode: zone_mode.map(Into::into),
+                    columns: zone_columns.map(usize::from),
+                    rows: zone_rows.map(usize::from),
+                    edge_depth,
+                };
+                commands::reactive::screen_zones(
+                    &ctx,
+                    fps,
+                    backend.into(),
+                    overrides,
+                    save_zones,
+                    power_budget,
+                )
+                .await?;
+            } else {
+                commands::reactive::screen(&ctx, fps, backend.into()).await?;
+            }
         }
 
         // === Dongle Commands ===
@@ -306,21 +628,75 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Some(Commands::Depth { raw, zero, verbose }) => {
             commands::with_keyboard(&ctx, |kb| commands::debug::depth(kb, raw, zero, verbose))?;
         }
+        Some(Commands::MeasureRate { duration }) => {
+            commands::with_keyboard(&ctx, |kb| commands::debug::measure_rate(kb, duration))?;
+        }
+        Some(Commands::Latency { presses, all }) => {
+            commands::debug::latency(&ctx, presses, all)?;
+        }
+        Some(Commands::TestKeys { depth, threshold }) => {
+            commands::with_keyboard(&ctx, |kb| commands::debug::test_keys(kb, depth, threshold))?;
+        }
+        Some(Commands::SwitchHealth {
+            duration,
+            noise,
+            drift,
+            stuck,
+            verbose,
+        }) => {
+            let thresholds = iot_driver::switch_health::HealthThresholds {
+                noise_mm: noise,
+                drift_mm: drift,
+                stuck_mm: stuck,
+                ..Default::default()
+            };
+            commands::with_keyboard(&ctx, |kb| {
+                commands::debug::switch_health(kb, duration, &thresholds, verbose)
+            })?;
+        }
         Some(Commands::TestTransport) => {
             commands::debug::test_transport(&ctx)?;
         }
+        Some(Commands::Selftest {
+            read_only,
+            yes,
+            output,
+        }) => commands::selftest::run(&ctx, read_only, yes, output.as_deref())?,
+
+        Some(Commands::Device(device_cmd)) => match device_cmd {
+            DeviceCommands::Add { output, no_leds } => {
+                commands::device::add(&ctx, output.as_deref(), no_leds)?
+            }
+        },
+
+        // === Config Commands ===
+        Some(Commands::Config(cfg_cmd)) => match cfg_cmd {
+            ConfigCommands::Apply { file, dry_run } => {
+                commands::config::apply(&ctx, &file, dry_run)?;
+            }
+            ConfigCommands::Dump { output } => {
+                commands::config::dump(&ctx, output.as_deref())?;
+            }
+        },
 
         // === Firmware Commands ===
         Some(Commands::Firmware(fw_cmd)) => match fw_cmd {
             FirmwareCommands::Validate { file } => {
                 commands::firmware::validate(&file)?;
             }
-            FirmwareCommands::DryRun { file, verbose } => {
-                commands::firmware::dry_run(&ctx, &file, verbose)?;
+            FirmwareCommands::DryRun {
+                file,
+                verbose,
+                dongle,
+            } => {
+                commands::firmware::dry_run(&ctx, &file, verbose, dongle)?;
             }
             FirmwareCommands::Check { device_id } => {
                 commands::firmware::check(&ctx, device_id)?;
             }
+            FirmwareCommands::Ids { filter } => {
+                commands::firmware::ids(filter.as_deref())?;
+            }
             FirmwareCommands::Download { device_id, output } => {
                 commands::firmware::download(&ctx, device_id, &output)?;
             }
@@ -329,10 +705,53 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 device,
                 dongle,
                 yes,
+                i_understand_the_risk,
+                no_backup,
             } => {
                 // firmware flash has its own --device flag; prefer it over global --device
                 let device_path = device.as_deref().or(ctx.device_selector());
-                commands::firmware::flash(&file, device_path, dongle, yes)?;
+                commands::firmware::flash(
+                    &ctx,
+                    &file,
+                    device_path,
+                    dongle,
+                    yes,
+                    i_understand_the_risk,
+                    no_backup,
+                )?;
+            }
+            FirmwareCommands::Rollback {
+                device,
+                dongle,
+                list,
+                yes,
+                i_understand_the_risk,
+                no_backup,
+            } => {
+                let device_path = device.as_deref().or(ctx.device_selector());
+                commands::firmware::rollback(
+                    &ctx,
+                    device_path,
+                    dongle,
+                    list,
+                    yes,
+                    i_understand_the_risk,
+                    no_backup,
+                )?;
+            }
+            FirmwareCommands::Recover {
+                file,
+                device,
+                yes,
+                i_understand_the_risk,
+            } => {
+                commands::firmware::recover(
+                    &ctx,
+                    file.as_ref(),
+                    device.as_deref(),
+                    yes,
+                    i_understand_the_risk,
+                )?;
             }
         },
 
@@ -340,20 +759,50 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Some(Commands::List) => {
             commands::utility::list()?;
         }
-        Some(Commands::Probe { output }) => {
-            commands::probe::run(&ctx, output.as_deref())?;
-        }
+        Some(Commands::Probe { output, action }) => match action {
+            None => commands::probe::run(&ctx, output.as_deref())?,
+            Some(ProbeCommands::Scan {
+                range,
+                interval,
+                include_known,
+                yes,
+                output,
+            }) => commands::probe::scan(
+                &ctx,
+                &range,
+                interval,
+                include_known,
+                yes,
+                output.as_deref(),
+            )?,
+        },
         Some(Commands::Raw { cmd: cmd_str }) => {
             commands::utility::raw(&cmd_str, &ctx)?;
         }
-        Some(Commands::Serve) => {
-            run_server(printer_config).await?;
+        Some(Commands::Serve { server }) => {
+            run_server(printer_config, server).await?;
+        }
+        Some(Commands::Daemon { server }) => {
+            run_daemon(printer_config, server).await?;
         }
         Some(Commands::Tui) => {
             commands::utility::tui(ctx.device).await?;
         }
-        Some(Commands::Joystick { config, headless }) => {
-            commands::utility::joystick(config, headless)?;
+        #[cfg(feature = "dbus")]
+        Some(Commands::Tray) => {
+            commands::utility::tray().await?;
+        }
+        Some(Commands::WatchFocus) => {
+            commands::utility::watch_focus()?;
+        }
+        Some(Commands::Joystick {
+            config,
+            headless,
+            profile,
+            preset,
+            suppress_keys,
+        }) => {
+            commands::utility::joystick(config, headless, profile, preset, suppress_keys)?;
         }
 
         // === Effect Commands ===
@@ -379,8 +828,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // === Notification Commands ===
         #[cfg(feature = "notify")]
-        Some(Commands::NotifyDaemon { verbose }) => {
-            commands::notify::daemon(&ctx, verbose).await?;
+        Some(Commands::NotifyDaemon {
+            verbose,
+            desktop_notifications,
+        }) => {
+            commands::notify::daemon(&ctx, verbose, desktop_notifications).await?;
         }
         #[cfg(feature = "notify")]
         Some(Commands::Notify {
@@ -424,23 +876,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-async fn run_server(
-    printer_config: Option<monsgeek_transport::PrinterConfig>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt()
+fn init_server_logging() {
+    // try_init: --trace may have installed a subscriber already
+    let _ = tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::from_default_env()
                 .add_directive("iot_driver=debug".parse().unwrap()),
         )
-        .init();
+        .try_init();
+}
 
-    let addr = "127.0.0.1:3814".parse()?;
+#[cfg(feature = "hid-trace")]
+fn init_trace_logging() {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::from_default_env()
+                .add_directive("monsgeek_hid=trace".parse().unwrap()),
+        )
+        .init();
+}
 
-    info!("Starting IOT Driver Linux on {}", addr);
+/// Create the driver service, start hot-plug monitoring and open the devices
+/// present now. Later arrivals are picked up by the udev monitor.
+async fn start_driver_service(
+    printer_config: Option<monsgeek_transport::PrinterConfig>,
+) -> Result<(DriverService, usize), Box<dyn std::error::Error>> {
     if printer_config.is_some() {
         info!("Monitor mode enabled - printing all commands/responses");
     }
-    println!("addr :: {addr}");
 
     let service = DriverService::with_printer_config(printer_config)
         .map_err(|e| format!("Failed to initialize HID API: {e}"))?;
@@ -448,6 +912,12 @@ async fn run_server(
     // Start hot-plug monitoring for device connect/disconnect
     service.start_hotplug_monitor();
 
+    // Pick up device definitions the user adds or edits while we run
+    iot_driver::profile_registry().watch_device_dir(
+        iot_driver::device_loader::user_devices_dir(),
+        std::time::Duration::from_secs(2),
+    );
+
     // Scan for devices on startup
     let devices = service.scan_devices().await;
     info!("Found {} devices on startup", devices.len());
@@ -459,28 +929,347 @@ async fn run_server(
             );
         }
     }
+    Ok((service, devices.len()))
+}
+
+async fn run_server(
+    printer_config: Option<monsgeek_transport::PrinterConfig>,
+    args: ServerArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    init_server_logging();
+
+    let addr = args.listen;
+
+    info!("Starting IOT Driver Linux on {}", addr);
+    println!("addr :: {addr}");
+
+    let (service, _) = start_driver_service(printer_config).await?;
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    serve_grpc(service, listener, std::future::pending(), &args).await
+}
+
+/// `iot_driver daemon`: the gRPC server as a long-lived (systemd) service.
+///
+/// Uses a socket-activated listener when one is passed, reports `READY=1`
+/// once devices are open, pings the watchdog if configured, and on SIGTERM or
+/// SIGINT stops accepting connections and lets in-flight calls finish.
+async fn run_daemon(
+    printer_config: Option<monsgeek_transport::PrinterConfig>,
+    args: ServerArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use iot_driver::systemd;
+    use tokio::signal::unix::{signal, SignalKind};
+
+    init_server_logging();
+
+    let listener = match systemd::take_tcp_listener()? {
+        Some(listener) => {
+            info!("Using socket-activated listener {}", listener.local_addr()?);
+            tokio::net::TcpListener::from_std(listener)?
+        }
+        None => {
+            info!("Listening on {}", args.listen);
+            tokio::net::TcpListener::bind(args.listen).await?
+        }
+    };
+
+    let (service, device_count) = start_driver_service(printer_config).await?;
+
+    // Desktop integration; the daemon still serves gRPC without a session bus.
+    #[cfg(feature = "dbus")]
+    let _dbus = match iot_driver::dbus::serve(Box::new(|| {
+        commands::open_keyboard(&CmdCtx::new(None, None)).map_err(|e| e.to_string())
+    }))
+    .await
+    {
+        Ok(conn) => {
+            info!("D-Bus service {} ready", iot_driver::dbus::BUS_NAME);
+            Some(conn)
+        }
+        Err(e) => {
+            tracing::warn!("D-Bus service unavailable: {e}");
+            None
+        }
+    };
+
+    // Night mode for the firmware lighting (no-op unless enabled in settings).
+    std::thread::spawn(|| {
+        iot_driver::night_mode::run_static_schedule(|| {
+            commands::open_keyboard(&CmdCtx::new(None, None)).map_err(|e| e.to_string())
+        })
+    });
+
+    // Time-of-day lighting schedule (no-op unless [schedule] is enabled in settings).
+    std::thread::spawn(|| {
+        iot_driver::schedule::run_schedule(|| {
+            commands::open_keyboard(&CmdCtx::new(None, None)).map_err(|e| e.to_string())
+        })
+    });
+
+    // Per-application profiles (no-op unless [app_profiles] is enabled in settings).
+    std::thread::spawn(|| {
+        iot_driver::focus::run_daemon_focus(|| {
+            commands::open_keyboard(&CmdCtx::new(None, None)).map_err(|e| e.to_string())
+        })
+    });
+
+    // Software remapping layer (no-op unless [remap] is enabled in settings).
+    std::thread::spawn(|| {
+        iot_driver::remap::run_remap(|| {
+            commands::open_keyboard(&CmdCtx::new(None, None)).map_err(|e| e.to_string())
+        })
+    });
+
+    // Low-battery warning (no-op unless [battery_warning] is enabled in settings).
+    std::thread::spawn(|| {
+        iot_driver::battery_warning::run_daemon_battery_warning(|| {
+            commands::open_keyboard(&CmdCtx::new(None, None)).map_err(|e| e.to_string())
+        })
+    });
+
+    // Ambient idle effect (no-op unless [ambient] is enabled in settings).
+    std::thread::spawn(|| {
+        iot_driver::ambient::run_daemon_ambient(|| {
+            commands::open_keyboard(&CmdCtx::new(None, None)).map_err(|e| e.to_string())
+        })
+    });
+
+    // Lock-screen lighting (no-op unless [lock_lighting] is enabled in settings).
+    #[cfg(feature = "dbus")]
+    tokio::spawn(async {
+        let open = || commands::open_keyboard(&CmdCtx::new(None, None)).map_err(|e| e.to_string());
+        if let Err(e) = iot_driver::lock_lighting::watch_session_lock(open).await {
+            tracing::debug!("lock lighting: logind session unavailable: {e}");
+        }
+    });
+
+    // Idle dimming (no-op unless [idle_dim] is enabled in settings).
+    let idle_hint = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    #[cfg(feature = "dbus")]
+    {
+        let idle_hint = std::sync::Arc::clone(&idle_hint);
+        tokio::spawn(async move {
+            if let Err(e) = iot_driver::idle_dim::watch_idle_hint(idle_hint).await {
+                tracing::debug!("idle dim: logind IdleHint unavailable: {e}");
+            }
+        });
+    }
+    std::thread::spawn(move || {
+        iot_driver::idle_dim::run_daemon_idle(
+            || commands::open_keyboard(&CmdCtx::new(None, None)).map_err(|e| e.to_string()),
+            idle_hint,
+        )
+    });
+
+    // System monitor gauge (no-op unless [sysmon] is enabled in settings).
+    std::thread::spawn(|| {
+        iot_driver::sysmon::run_daemon_gauge(|| {
+            commands::open_keyboard(&CmdCtx::new(None, None)).map_err(|e| e.to_string())
+        })
+    });
+
+    // Lighting plugins (no-op unless ~/.config/monsgeek/plugins/ has any).
+    std::thread::spawn(|| {
+        iot_driver::plugin::run_daemon_plugins(|| {
+            commands::open_keyboard(&CmdCtx::new(None, None)).map_err(|e| e.to_string())
+        })
+    });
+
+    if let Some(interval) = systemd::watchdog_interval() {
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(interval);
+            loop {
+                tick.tick().await;
+                systemd::notify_or_log("WATCHDOG=1");
+            }
+        });
+    }
+
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let shutdown = async move {
+        tokio::select! {
+            _ = sigterm.recv() => info!("SIGTERM received, shutting down"),
+            _ = sigint.recv() => info!("SIGINT received, shutting down"),
+        }
+        systemd::notify_or_log("STOPPING=1");
+    };
+
+    systemd::notify_or_log(&format!(
+        "READY=1\nSTATUS=Serving gRPC, {device_count} device(s) at startup"
+    ));
+    serve_grpc(service, listener, shutdown, &args).await?;
+    info!("Daemon stopped");
+    Ok(())
+}
+
+/// Per-request `Authorization: Bearer <token>` check against the configured
+/// server token; lets everything through when no token is set. CORS sits
+/// outside it, so preflight requests never need the token.
+#[derive(Clone)]
+struct TokenAuth(std::sync::Arc<iot_driver::settings::ServerSettings>);
+
+impl<B> ValidateRequest<B> for TokenAuth {
+    type ResponseBody = tonic::body::BoxBody;
+
+    fn validate(
+        &mut self,
+        request: &mut http::Request<B>,
+    ) -> Result<(), http::Response<Self::ResponseBody>> {
+        let presented = request
+            .headers()
+            .get(http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if self.0.token_matches(presented) {
+            return Ok(());
+        }
+        let mut response = http::Response::new(tonic::body::empty_body());
+        *response.status_mut() = http::StatusCode::UNAUTHORIZED;
+        Err(response)
+    }
+}
+
+/// Serve the driver over gRPC (+ gRPC-Web for browsers, and the REST API and
+/// event WebSocket when enabled in `args`), over TLS if configured, until
+/// `shutdown`.
+/// Announce the server via mDNS when it is reachable from other machines.
+/// Failures (e.g. no Avahi) are logged; the server runs either way.
+#[cfg(feature = "dbus")]
+async fn advertise(
+    listener: &tokio::net::TcpListener,
+    args: &ServerArgs,
+    access: &iot_driver::settings::ServerSettings,
+) -> Option<iot_driver::mdns::Advertisement> {
+    use iot_driver::mdns;
+
+    let addr = listener.local_addr().ok()?;
+    if args.no_mdns || !mdns::is_lan(&addr) {
+        return None;
+    }
+    let info = mdns::ServiceInfo {
+        tls: args.tls().is_some(),
+        #[cfg(feature = "rest")]
+        rest: args.rest,
+        #[cfg(not(feature = "rest"))]
+        rest: false,
+        token: access.token().is_some(),
+    };
+    match mdns::advertise(addr.port(), &info).await {
+        Ok(ad) => {
+            info!("Advertised as '{}' ({})", ad.name, mdns::SERVICE_TYPE);
+            Some(ad)
+        }
+        Err(e) => {
+            tracing::warn!("mDNS advertisement failed (is avahi-daemon running?): {e}");
+            None
+        }
+    }
+}
 
-    // CORS layer for browser access
+async fn serve_grpc(
+    service: DriverService,
+    listener: tokio::net::TcpListener,
+    shutdown: impl std::future::Future<Output = ()>,
+    args: &ServerArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Access control from settings.toml: optional token, CORS origin allowlist
+    let access = iot_driver::settings::Settings::load().server;
+    let allow_origin = if access.any_origin() {
+        AllowOrigin::any()
+    } else {
+        let origins = access.allowed_origins.iter().filter_map(|o| {
+            o.trim()
+                .trim_end_matches('/')
+                .parse()
+                .inspect_err(|_| tracing::warn!("Ignoring invalid allowed origin {o:?}"))
+                .ok()
+        });
+        AllowOrigin::list(origins)
+    };
+    info!(
+        "Allowed browser origins: {}",
+        access.allowed_origins.join(", ")
+    );
     let cors = CorsLayer::new()
-        .allow_origin(Any)
+        .allow_origin(allow_origin)
         .allow_headers(Any)
         .allow_methods(Any)
         .expose_headers(Any);
+    if access.token().is_some() {
+        info!("Token authentication enabled");
+    }
+    let auth = ValidateRequestHeaderLayer::custom(TokenAuth(std::sync::Arc::new(access.clone())));
 
     // Wrap service with gRPC-Web support for browser clients
-    let grpc_service = tonic_web::enable(DriverGrpcServer::new(service));
+    let service = std::sync::Arc::new(service);
+    let grpc_service = tonic_web::enable(DriverGrpcServer::from_arc(service.clone()));
+    let routes = tonic::service::Routes::new(grpc_service);
+
+    #[cfg(feature = "rest")]
+    let events = match args.events {
+        Some(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            info!("WebSocket event stream on ws://{addr}/events");
+            Some(tokio::spawn(ws::serve(
+                service.clone(),
+                listener,
+                access.clone(),
+            )))
+        }
+        None => None,
+    };
+    #[cfg(feature = "rest")]
+    let routes = if args.rest || args.webrtc {
+        let mut router = routes.into_axum_router();
+        if args.rest {
+            info!("REST API enabled under /api/v1");
+            router = router.merge(rest::router(service));
+        }
+        if args.webrtc {
+            info!("WebRTC signaling enabled under /webrtc");
+            router = router.merge(webrtc::router());
+        }
+        router.into()
+    } else {
+        routes
+    };
 
     info!("Server ready with gRPC-Web support");
 
-    Server::builder()
+    #[cfg(feature = "dbus")]
+    let _advertisement = advertise(&listener, args, &access).await;
+
+    let server = Server::builder()
         .accept_http1(true)
         .tcp_nodelay(true)
         .initial_stream_window_size(4096)
         .initial_connection_window_size(4096)
         .layer(cors)
-        .add_service(grpc_service)
-        .serve(addr)
-        .await?;
+        .layer(auth)
+        .add_routes(routes);
 
-    Ok(())
+    let served = match args.tls() {
+        #[cfg(feature = "tls")]
+        Some((cert, key)) => {
+            let acceptor = tls::acceptor(cert, key)?;
+            info!("TLS enabled ({})", cert.display());
+            server
+                .serve_with_incoming_shutdown(tls::incoming(listener, acceptor), shutdown)
+                .await
+        }
+        _ => {
+            let incoming = TcpIncoming::from_listener(listener, true, None)
+                .map_err(|e| e as Box<dyn std::error::Error>)?;
+            server
+                .serve_with_incoming_shutdown(incoming, shutdown)
+                .await
+        }
+    };
+
+    #[cfg(feature = "rest")]
+    if let Some(events) = events {
+        events.abort();
+    }
+    Ok(served?)
 }
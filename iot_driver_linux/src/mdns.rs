@@ -0,0 +1,121 @@
+//! mDNS/DNS-SD advertisement of the driver server.
+//!
+//! When the server listens beyond localhost it announces itself as
+//! `_monsgeek._tcp` so companion apps can find it without typing an IP.
+//! Publishing goes through Avahi on the system bus, which already answers
+//! mDNS on most Linux desktops; the entry disappears when the returned
+//! [`Advertisement`] is dropped (Avahi frees a client's entry groups when
+//! its bus connection closes).
+
+use std::net::SocketAddr;
+
+use zbus::zvariant::OwnedObjectPath;
+
+/// DNS-SD service type.
+pub const SERVICE_TYPE: &str = "_monsgeek._tcp";
+
+const AVAHI_BUS_NAME: &str = "org.freedesktop.Avahi";
+const AVAHI_SERVER: &str = "org.freedesktop.Avahi.Server";
+const AVAHI_ENTRY_GROUP: &str = "org.freedesktop.Avahi.EntryGroup";
+/// `AVAHI_IF_UNSPEC` / `AVAHI_PROTO_UNSPEC`: every interface, IPv4 and IPv6.
+const UNSPEC: i32 = -1;
+
+/// What the server offers, published as TXT records.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceInfo {
+    pub tls: bool,
+    /// REST API under `/api/v1`
+    pub rest: bool,
+    /// Clients must send the server token
+    pub token: bool,
+}
+
+impl ServiceInfo {
+    /// TXT records: `txtvers`, `version`, `tls`, `rest` and `auth`.
+    pub fn txt_records(&self) -> Vec<Vec<u8>> {
+        let flag = |on: bool| if on { "1" } else { "0" };
+        [
+            "txtvers=1".to_string(),
+            format!("version={}", env!("CARGO_PKG_VERSION")),
+            format!("tls={}", flag(self.tls)),
+            format!("rest={}", flag(self.rest)),
+            format!("auth={}", if self.token { "token" } else { "none" }),
+        ]
+        .into_iter()
+        .map(String::into_bytes)
+        .collect()
+    }
+}
+
+/// Whether a server bound to `addr` is reachable from other machines,
+/// i.e. worth advertising.
+pub fn is_lan(addr: &SocketAddr) -> bool {
+    !addr.ip().is_loopback()
+}
+
+/// A published service; withdrawn on drop.
+pub struct Advertisement {
+    _conn: zbus::Connection,
+    pub name: String,
+}
+
+/// Publish the server on `port` as "MonsGeek driver on <host>".
+pub async fn advertise(port: u16, info: &ServiceInfo) -> zbus::Result<Advertisement> {
+    let conn = zbus::Connection::system().await?;
+    let server = zbus::Proxy::new(&conn, AVAHI_BUS_NAME, "/", AVAHI_SERVER).await?;
+    let host: String = server.call("GetHostName", &()).await?;
+    let name = format!("MonsGeek driver on {host}");
+
+    let group_path: OwnedObjectPath = server.call("EntryGroupNew", &()).await?;
+    let group = zbus::Proxy::new(&conn, AVAHI_BUS_NAME, group_path, AVAHI_ENTRY_GROUP).await?;
+    group
+        .call_method(
+            "AddService",
+            &(
+                UNSPEC,
+                UNSPEC,
+                0u32,
+                name.as_str(),
+                SERVICE_TYPE,
+                "",
+                "",
+                port,
+                info.txt_records(),
+            ),
+        )
+        .await?;
+    group.call_method("Commit", &()).await?;
+
+    Ok(Advertisement { _conn: conn, name })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn txt_records_describe_server() {
+        let info = ServiceInfo {
+            tls: true,
+            rest: false,
+            token: true,
+        };
+        let txt: Vec<String> = info
+            .txt_records()
+            .into_iter()
+            .map(|r| String::from_utf8(r).unwrap())
+            .collect();
+        assert_eq!(txt[0], "txtvers=1");
+        assert!(txt.contains(&"tls=1".to_string()));
+        assert!(txt.contains(&"rest=0".to_string()));
+        assert!(txt.contains(&"auth=token".to_string()));
+    }
+
+    #[test]
+    fn only_non_loopback_is_lan() {
+        assert!(!is_lan(&"127.0.0.1:3814".parse().unwrap()));
+        assert!(!is_lan(&"[::1]:3814".parse().unwrap()));
+        assert!(is_lan(&"0.0.0.0:3814".parse().unwrap()));
+        assert!(is_lan(&"192.168.1.20:3814".parse().unwrap()));
+    }
+}
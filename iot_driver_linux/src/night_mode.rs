@@ -0,0 +1,393 @@
+//! Night mode: a color temperature and brightness schedule for the lighting.
+//!
+//! Configured under `[night_mode]` in `settings.toml`. Between `start` and
+//! `end` (local time) colors are shifted toward `temperature` and dimmed to
+//! `brightness`, fading in and out over `transition_minutes`. Screen sync
+//! applies it to every streamed color; the daemon applies it to the
+//! firmware lighting (see [`StaticNightMode`]).
+
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use monsgeek_keyboard::led::{LedMode, LedParams};
+use monsgeek_keyboard::{KeyboardInterface, RgbColor};
+
+const MINUTES_PER_DAY: f32 = 24.0 * 60.0;
+/// Color temperature of unshifted white.
+const NEUTRAL_KELVIN: u32 = 6500;
+/// How often the daemon re-evaluates the schedule.
+pub const STATIC_TICK: Duration = Duration::from_secs(60);
+
+/// A local wall-clock time, written `HH:MM`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay {
+    /// Minutes after midnight
+    pub minutes: u16,
+}
+
+impl TimeOfDay {
+    pub const fn new(hour: u16, minute: u16) -> Self {
+        Self {
+            minutes: hour * 60 + minute,
+        }
+    }
+}
+
+impl std::str::FromStr for TimeOfDay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (h, m) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| format!("expected HH:MM, got '{s}'"))?;
+        match (h.parse::<u16>(), m.parse::<u16>()) {
+            (Ok(h), Ok(m)) if h < 24 && m < 60 => Ok(Self::new(h, m)),
+            _ => Err(format!("invalid time '{s}'")),
+        }
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(t: TimeOfDay) -> Self {
+        t.to_string()
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.minutes / 60, self.minutes % 60)
+    }
+}
+
+/// The night mode schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NightMode {
+    pub enabled: bool,
+    /// When the night starts (fully in effect `transition_minutes` later)
+    pub start: TimeOfDay,
+    /// When the night ends (fading out `transition_minutes` before)
+    pub end: TimeOfDay,
+    /// Color temperature at night, in kelvin (1000-6500)
+    pub temperature: u32,
+    /// Brightness factor at night (0.0-1.0)
+    pub brightness: f32,
+    /// Length of the fade at either end of the night
+    pub transition_minutes: u32,
+}
+
+impl Default for NightMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: TimeOfDay::new(22, 0),
+            end: TimeOfDay::new(7, 0),
+            temperature: 3400,
+            brightness: 0.5,
+            transition_minutes: 30,
+        }
+    }
+}
+
+impl NightMode {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// How far into night mode the schedule is at `minute` after midnight:
+    /// 0.0 by day, 1.0 at night, in between while fading.
+    pub fn strength_at(&self, minute: f32) -> f32 {
+        if !self.enabled {
+            return 0.0;
+        }
+        let start = self.start.minutes as f32;
+        let len = (self.end.minutes as f32 - start).rem_euclid(MINUTES_PER_DAY);
+        let t = (minute - start).rem_euclid(MINUTES_PER_DAY);
+        if len == 0.0 || t >= len {
+            return 0.0;
+        }
+        let fade = self.transition_minutes as f32;
+        if fade == 0.0 {
+            return 1.0;
+        }
+        (t / fade).min((len - t) / fade).min(1.0)
+    }
+
+    /// The adjustment in effect at `minute` after midnight.
+    pub fn adjust_at(&self, minute: f32) -> NightAdjust {
+        let s = self.strength_at(minute);
+        if s == 0.0 {
+            return NightAdjust::NONE;
+        }
+        let white = kelvin_gain(self.temperature);
+        NightAdjust {
+            gain: white.map(|g| 1.0 + (g - 1.0) * s),
+            brightness: 1.0 + (self.brightness.clamp(0.0, 1.0) - 1.0) * s,
+        }
+    }
+
+    /// The adjustment in effect now (local time).
+    pub fn current(&self) -> NightAdjust {
+        if !self.enabled {
+            return NightAdjust::NONE;
+        }
+        self.adjust_at(local_minutes())
+    }
+}
+
+/// Per-channel gain and brightness factor to apply to a color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NightAdjust {
+    pub gain: [f32; 3],
+    pub brightness: f32,
+}
+
+impl NightAdjust {
+    /// No change (daytime).
+    pub const NONE: Self = Self {
+        gain: [1.0; 3],
+        brightness: 1.0,
+    };
+
+    pub fn is_none(&self) -> bool {
+        *self == Self::NONE
+    }
+
+    /// Shift and dim a color.
+    pub fn apply(&self, (r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+        let ch = |v: u8, i: usize| (v as f32 * self.gain[i] * self.brightness).round() as u8;
+        (ch(r, 0), ch(g, 1), ch(b, 2))
+    }
+
+    /// `params` with the color shifted and the firmware brightness scaled.
+    /// A lit keyboard stays at least at brightness 1.
+    pub fn apply_to_params(&self, params: &LedParams) -> LedParams {
+        let shift = |v: u8, i: usize| (v as f32 * self.gain[i]).round() as u8;
+        let c = params.color;
+        let brightness = (params.brightness as f32 * self.brightness).round() as u8;
+        LedParams {
+            color: RgbColor::new(shift(c.r, 0), shift(c.g, 1), shift(c.b, 2)),
+            brightness: brightness.max(params.brightness.min(1)),
+            ..params.clone()
+        }
+    }
+}
+
+/// Channel gains that turn neutral white into `kelvin` (Tanner Helland's
+/// blackbody fit, normalized so 6500K is 1.0 on every channel).
+pub fn kelvin_gain(kelvin: u32) -> [f32; 3] {
+    let rgb = |k: u32| {
+        let t = k as f32 / 100.0;
+        let r = if t <= 66.0 {
+            255.0
+        } else {
+            329.699 * (t - 60.0).powf(-0.133_204_76)
+        };
+        let g = if t <= 66.0 {
+            99.470_8 * t.ln() - 161.119_57
+        } else {
+            288.122_17 * (t - 60.0).powf(-0.075_514_85)
+        };
+        let b = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.517_73 * (t - 10.0).ln() - 305.044_8
+        };
+        [r, g, b].map(|v| v.clamp(0.0, 255.0))
+    };
+    let neutral = rgb(NEUTRAL_KELVIN);
+    let warm = rgb(kelvin.clamp(1000, NEUTRAL_KELVIN));
+    [0, 1, 2].map(|i| (warm[i] / neutral[i]).min(1.0))
+}
+
+/// Local time as minutes after midnight.
+fn local_minutes() -> f32 {
+    local_time().1
+}
+
+/// Local weekday (0 = Sunday) and minutes after midnight.
+pub(crate) fn local_time() -> (u8, f32) {
+    // SAFETY: localtime_r only writes the `tm` we pass it
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return (0, 0.0);
+        }
+        let minutes = tm.tm_hour as f32 * 60.0 + tm.tm_min as f32 + tm.tm_sec as f32 / 60.0;
+        (tm.tm_wday as u8, minutes)
+    }
+}
+
+/// Night mode for the firmware lighting.
+///
+/// Remembers the user's daytime LED settings when the night begins, writes
+/// the shifted and dimmed version while it lasts and puts the originals back
+/// in the morning. A lighting change made during the night becomes the new
+/// daytime setting. Screen sync is left alone; it adjusts its own colors.
+#[derive(Debug, Default)]
+pub struct StaticNightMode {
+    /// Daytime settings to restore
+    baseline: Option<LedParams>,
+    /// What we last wrote
+    applied: Option<LedParams>,
+}
+
+impl StaticNightMode {
+    /// Decide what to write given the keyboard's `current` settings; `None`
+    /// when nothing needs to change.
+    pub fn plan(&mut self, current: &LedParams, adjust: &NightAdjust) -> Option<LedParams> {
+        if self.applied.as_ref().is_some_and(|a| a != current) {
+            // Changed by the user since we wrote it
+            self.baseline = Some(current.clone());
+            self.applied = None;
+        }
+        if adjust.is_none() || current.mode == LedMode::ScreenSync {
+            let restore = self.baseline.take().filter(|_| self.applied.is_some());
+            self.applied = None;
+            return restore.filter(|base| base != current);
+        }
+        let want = adjust.apply_to_params(self.baseline.get_or_insert_with(|| current.clone()));
+        self.applied = Some(want.clone());
+        (want != *current).then_some(want)
+    }
+
+    /// Apply the schedule once.
+    pub fn tick(
+        &mut self,
+        keyboard: &KeyboardInterface,
+        adjust: &NightAdjust,
+    ) -> Result<(), String> {
+        let current = keyboard.get_led_params().map_err(|e| e.to_string())?;
+        if let Some(params) = self.plan(&current, adjust) {
+            keyboard
+                .set_led_params(&params)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// Daemon loop: apply the `settings.toml` schedule to the firmware lighting
+/// every [`STATIC_TICK`], re-reading the settings so edits apply without a
+/// restart. The keyboard is opened on demand and reopened after errors.
+pub fn run_static_schedule(open: impl Fn() -> Result<KeyboardInterface, String>) {
+    let mut night = StaticNightMode::default();
+    let mut keyboard = None;
+    loop {
+        let adjust = crate::settings::Settings::load().night_mode.current();
+        if !adjust.is_none() || night.baseline.is_some() {
+            if keyboard.is_none() {
+                keyboard = open()
+                    .map_err(|e| tracing::debug!("night mode: no keyboard: {e}"))
+                    .ok();
+            }
+            if let Some(kb) = &keyboard {
+                if let Err(e) = night.tick(kb, &adjust) {
+                    tracing::warn!("night mode: {e}");
+                    keyboard = None;
+                }
+            }
+        }
+        std::thread::sleep(STATIC_TICK);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule() -> NightMode {
+        NightMode {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn strength_fades_across_midnight() {
+        let night = schedule();
+        let at = |h: f32| night.strength_at(h * 60.0);
+        assert_eq!(at(12.0), 0.0);
+        assert_eq!(at(22.0), 0.0);
+        assert_eq!(at(22.25), 0.5);
+        assert_eq!(at(23.0), 1.0);
+        assert_eq!(at(3.0), 1.0);
+        assert_eq!(at(6.75), 0.5);
+        assert_eq!(at(7.0), 0.0);
+        assert_eq!(NightMode::default().strength_at(23.0 * 60.0), 0.0);
+    }
+
+    #[test]
+    fn warm_temperatures_cut_blue_and_dim() {
+        assert_eq!(kelvin_gain(6500), [1.0, 1.0, 1.0]);
+        let [r, g, b] = kelvin_gain(3000);
+        assert!(r == 1.0 && g < 1.0 && b < g, "{r} {g} {b}");
+
+        let adjust = schedule().adjust_at(0.0);
+        let (r, g, b) = adjust.apply((200, 200, 200));
+        assert_eq!(r, 100);
+        assert!(b < g && g < r);
+        assert_eq!(NightAdjust::NONE.apply((1, 2, 3)), (1, 2, 3));
+    }
+
+    #[test]
+    fn static_lighting_is_dimmed_and_restored() {
+        let day = LedParams {
+            brightness: 4,
+            ..Default::default()
+        };
+        let adjust = schedule().adjust_at(0.0);
+        let mut night = StaticNightMode::default();
+
+        let dimmed = night.plan(&day, &adjust).unwrap();
+        assert_eq!(dimmed.brightness, 2);
+        assert!(dimmed.color.b < dimmed.color.r);
+        assert_eq!(night.plan(&dimmed, &adjust), None);
+        assert_eq!(night.plan(&dimmed, &NightAdjust::NONE), Some(day.clone()));
+        assert_eq!(night.plan(&day, &NightAdjust::NONE), None);
+    }
+
+    #[test]
+    fn lighting_changed_at_night_becomes_the_day_setting() {
+        let adjust = schedule().adjust_at(0.0);
+        let mut night = StaticNightMode::default();
+        night.plan(&LedParams::default(), &adjust);
+
+        let chosen = LedParams {
+            mode: LedMode::Wave,
+            brightness: 2,
+            ..Default::default()
+        };
+        assert_eq!(night.plan(&chosen, &adjust).unwrap().brightness, 1);
+        let restored = night.plan(&LedParams::default(), &NightAdjust::NONE);
+        // The user changed it again; nothing to restore over their choice
+        assert_eq!(restored, None);
+    }
+
+    #[test]
+    fn times_parse_and_serialize() {
+        let night: NightMode = toml::from_str("enabled = true\nstart = \"21:30\"").unwrap();
+        assert_eq!(night.start, TimeOfDay::new(21, 30));
+        assert_eq!(night.end, TimeOfDay::new(7, 0));
+        assert!(toml::to_string(&night)
+            .unwrap()
+            .contains("start = \"21:30\""));
+        assert!("24:00".parse::<TimeOfDay>().is_err());
+        assert!("7".parse::<TimeOfDay>().is_err());
+    }
+}
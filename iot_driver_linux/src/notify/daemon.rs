@@ -11,6 +11,7 @@ use tokio::sync::Mutex;
 use tracing::{debug, info};
 
 use super::dbus::{NotifyInterface, SharedStore};
+use super::desktop::{self, DesktopNotifications};
 use super::state::{self, NotificationStore};
 use crate::anim::{self, AnimEngine, SharedSlotInfo, SlotEntry};
 use crate::effect::EffectLibrary;
@@ -19,16 +20,21 @@ use monsgeek_keyboard::VendorEvent;
 /// Run the notification daemon (blocking, standalone CLI entry point).
 ///
 /// Opens its own Ctrl-C handler. For TUI integration, use `run_with_cancel` instead.
+/// `watch_desktop` turns on desktop notification lighting regardless of
+/// `[desktop_notifications] enabled`.
 pub async fn run(
     kb: monsgeek_keyboard::KeyboardInterface,
     verbose: bool,
+    watch_desktop: bool,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let running = Arc::new(AtomicBool::new(true));
     let r = Arc::clone(&running);
     ctrlc::set_handler(move || r.store(false, Ordering::SeqCst)).ok();
     let slot_info = Arc::new(std::sync::Mutex::new(crate::anim::SlotInfo::default()));
     let log = super::log::DaemonLog::new(verbose);
-    run_with_cancel(Arc::new(kb), running, slot_info, log).await
+    let mut desktop = crate::settings::Settings::load().desktop_notifications;
+    desktop.enabled |= watch_desktop;
+    run_with_cancel(Arc::new(kb), running, slot_info, log, desktop).await
 }
 
 /// Tracks firmware animation slot allocation.
@@ -139,6 +145,7 @@ pub async fn run_with_cancel(
     running: Arc<AtomicBool>,
     slot_info: SharedSlotInfo,
     log: super::log::DaemonLog,
+    desktop: DesktopNotifications,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Load effect library
     let effects = EffectLibrary::load_default().map_err(|e| format!("load effects: {e}"))?;
@@ -184,6 +191,20 @@ pub async fn run_with_cancel(
         .await?;
 
     info!("D-Bus: org.monsgeek.Notify1 on session bus");
+
+    // Desktop notifications are posted through the same interface
+    if desktop.enabled {
+        let iface = conn
+            .object_server()
+            .interface::<_, NotifyInterface>("/org/monsgeek/Notify1")
+            .await?;
+        let log = log.clone();
+        tokio::spawn(async move {
+            if let Err(e) = desktop::watch(desktop, iface, log.clone()).await {
+                log.push(format!("desktop: watcher stopped: {e}"));
+            }
+        });
+    }
     info!("Render loop started");
 
     // Expiry/wave timer — only needed for TTL expiry and pending wave processing.
@@ -64,14 +64,10 @@ impl NotifyInterface {
             log,
         }
     }
-}
 
-#[interface(name = "org.monsgeek.Notify1")]
-impl NotifyInterface {
-    /// Post a notification. Returns notification ID.
-    ///
-    /// `vars` maps variable names to color values (e.g. {"color": "red"}).
-    async fn notify(
+    /// Post a notification to the store (the `Notify` method, also used by
+    /// the desktop notification watcher). Returns the notification ID.
+    pub async fn post(
         &self,
         source: &str,
         key: &str,
@@ -167,6 +163,25 @@ impl NotifyInterface {
 
         Ok(id)
     }
+}
+
+#[interface(name = "org.monsgeek.Notify1")]
+impl NotifyInterface {
+    /// Post a notification. Returns notification ID.
+    ///
+    /// `vars` maps variable names to color values (e.g. {"color": "red"}).
+    async fn notify(
+        &self,
+        source: &str,
+        key: &str,
+        effect_name: &str,
+        priority: i32,
+        ttl_ms: i32,
+        vars: BTreeMap<String, String>,
+    ) -> zbus::fdo::Result<u64> {
+        self.post(source, key, effect_name, priority, ttl_ms, vars)
+            .await
+    }
 
     /// Acknowledge (dismiss) a notification by ID.
     async fn acknowledge(&self, id: u64) -> zbus::fdo::Result<()> {
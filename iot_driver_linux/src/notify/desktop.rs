@@ -0,0 +1,259 @@
+//! Desktop notification lighting — react to `org.freedesktop.Notifications`.
+//!
+//! The notification daemon becomes a session bus monitor for `Notify` calls
+//! and posts a keyboard notification for the first matching per-application
+//! rule (`[desktop_notifications]` in `settings.toml`). Rules use the same
+//! key targets and effects as `iot_driver notify`; with a TTL the overlay
+//! expires on its own and the keyboard's lighting shows through again.
+
+use serde::{Deserialize, Serialize};
+
+/// Source name desktop notifications are posted under (`notify-ack --source`).
+pub const SOURCE: &str = "desktop";
+
+/// One application rule: which apps, and what to show for them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppRule {
+    /// Case-insensitive substrings of the app name or desktop entry;
+    /// `"*"` matches every app
+    pub apps: Vec<String>,
+    /// Key target, as for `iot_driver notify` (`Esc`, `frow`, `F1..F4`)
+    pub key: String,
+    /// Effect from `effects.toml`
+    pub effect: String,
+    /// Bound to the effect's `$color`; `None` keeps the effect default
+    pub color: Option<String>,
+    pub priority: i32,
+    /// Lifetime in ms (-1 = effect default, 0 = until acknowledged)
+    pub ttl_ms: i32,
+}
+
+impl Default for AppRule {
+    fn default() -> Self {
+        Self {
+            apps: Vec::new(),
+            key: "Esc".to_string(),
+            effect: "flash".to_string(),
+            color: None,
+            priority: 0,
+            ttl_ms: 5000,
+        }
+    }
+}
+
+impl AppRule {
+    fn new(apps: &[&str], key: &str, color: &str) -> Self {
+        Self {
+            apps: apps.iter().map(|a| a.to_string()).collect(),
+            key: key.to_string(),
+            color: Some(color.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the rule covers an app, by name or desktop entry.
+    pub fn matches(&self, app_name: &str, desktop_entry: Option<&str>) -> bool {
+        let candidates = [Some(app_name), desktop_entry];
+        self.apps.iter().any(|pattern| {
+            let pattern = pattern.to_lowercase();
+            pattern == "*"
+                || candidates
+                    .iter()
+                    .flatten()
+                    .any(|c| !c.is_empty() && c.to_lowercase().contains(&pattern))
+        })
+    }
+}
+
+/// `[desktop_notifications]` settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DesktopNotifications {
+    /// Watch desktop notifications while `notify-daemon` runs
+    pub enabled: bool,
+    /// Checked in order; the first match wins
+    pub rules: Vec<AppRule>,
+}
+
+impl Default for DesktopNotifications {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rules: vec![
+                AppRule::new(
+                    &[
+                        "telegram", "signal", "discord", "slack", "element", "whatsapp",
+                    ],
+                    "Esc",
+                    "cyan",
+                ),
+                AppRule::new(
+                    &["thunderbird", "evolution", "geary", "kmail", "mail"],
+                    "F1..F4",
+                    "yellow",
+                ),
+                AppRule::new(&["calendar", "korganizer", "reminder"], "F5..F8", "#FF8000"),
+            ],
+        }
+    }
+}
+
+impl DesktopNotifications {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The first rule matching an app, if any.
+    pub fn rule_for(&self, app_name: &str, desktop_entry: Option<&str>) -> Option<&AppRule> {
+        self.rules
+            .iter()
+            .find(|r| r.matches(app_name, desktop_entry))
+    }
+}
+
+/// Watch desktop notifications and post matching ones to the daemon.
+///
+/// Uses its own connection: a bus monitor can't send anything else.
+/// Returns when the connection closes or monitoring is refused.
+#[cfg(feature = "notify")]
+pub async fn watch(
+    config: DesktopNotifications,
+    iface: zbus::object_server::InterfaceRef<super::dbus::NotifyInterface>,
+    log: super::log::DaemonLog,
+) -> zbus::Result<()> {
+    use futures::StreamExt;
+    use std::collections::{BTreeMap, HashMap};
+    use zbus::zvariant::OwnedValue;
+
+    type NotifyArgs = (
+        String,
+        u32,
+        String,
+        String,
+        String,
+        Vec<String>,
+        HashMap<String, OwnedValue>,
+        i32,
+    );
+
+    let conn = zbus::connection::Builder::session()?.build().await?;
+    let rule = zbus::MatchRule::builder()
+        .msg_type(zbus::message::Type::MethodCall)
+        .interface("org.freedesktop.Notifications")?
+        .member("Notify")?
+        .build();
+    zbus::fdo::MonitoringProxy::new(&conn)
+        .await?
+        .become_monitor(&[rule], 0)
+        .await?;
+    log.push(format!(
+        "desktop: watching notifications ({} rules)",
+        config.rules.len()
+    ));
+
+    let mut stream = zbus::MessageStream::from(&conn);
+    while let Some(msg) = stream.next().await {
+        let Ok(msg) = msg else { continue };
+        if msg.header().member().map(|m| m.as_str()) != Some("Notify") {
+            continue;
+        }
+        let Ok((app_name, _, _, summary, _, _, hints, _)) = msg.body().deserialize::<NotifyArgs>()
+        else {
+            continue;
+        };
+        let desktop_entry = hints
+            .get("desktop-entry")
+            .and_then(|v| String::try_from(v.clone()).ok());
+
+        let Some(rule) = config.rule_for(&app_name, desktop_entry.as_deref()) else {
+            log.push(format!("desktop: {app_name}: no rule"));
+            continue;
+        };
+        let vars: BTreeMap<String, String> = rule
+            .color
+            .iter()
+            .map(|c| ("color".to_string(), c.clone()))
+            .collect();
+        let result = iface
+            .get()
+            .await
+            .post(
+                SOURCE,
+                &rule.key,
+                &rule.effect,
+                rule.priority,
+                rule.ttl_ms,
+                vars,
+            )
+            .await;
+        match result {
+            Ok(id) => log.push(format!(
+                "desktop: {app_name} \"{summary}\" → {} id={id}",
+                rule.key
+            )),
+            Err(e) => log.push(format!("desktop: {app_name}: {e}")),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let config = DesktopNotifications::default();
+        assert_eq!(
+            config.rule_for("Telegram Desktop", None).unwrap().key,
+            "Esc"
+        );
+        assert_eq!(
+            config
+                .rule_for("", Some("org.mozilla.Thunderbird"))
+                .unwrap()
+                .key,
+            "F1..F4"
+        );
+        assert_eq!(
+            config.rule_for("gnome-calendar", None).unwrap().key,
+            "F5..F8"
+        );
+        assert!(config.rule_for("Firefox", None).is_none());
+    }
+
+    #[test]
+    fn wildcard_matches_every_app() {
+        let rule = AppRule {
+            apps: vec!["*".into()],
+            ..Default::default()
+        };
+        assert!(rule.matches("anything", None));
+        assert!(!AppRule::default().matches("anything", None));
+    }
+
+    #[test]
+    fn rules_load_from_toml() {
+        let config: DesktopNotifications = toml::from_str(
+            r#"
+            enabled = true
+            [[rules]]
+            apps = ["slack"]
+            key = "frow"
+            color = "purple"
+            "#,
+        )
+        .unwrap();
+        assert!(config.enabled);
+        assert_eq!(config.rules.len(), 1);
+        let rule = &config.rules[0];
+        assert_eq!((rule.effect.as_str(), rule.ttl_ms), ("flash", 5000));
+        assert_eq!(
+            toml::from_str::<DesktopNotifications>("enabled = true")
+                .unwrap()
+                .rules,
+            DesktopNotifications::default().rules
+        );
+    }
+}
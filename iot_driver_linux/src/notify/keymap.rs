@@ -1,10 +1,14 @@
-//! Key name → matrix position lookup for the M1 V5 HE 16×6 LED grid.
+//! Key name → matrix position lookup for the 16×6 LED grid.
 //!
 //! The LED streaming protocol uses row-major positions: `pos = row * 16 + col`.
-//! The firmware's key name table (`M1_V5_HE_KEY_NAMES`) is column-major:
-//! `index = col * 6 + row`. This module bridges the two.
+//! Key matrix tables are column-major: `index = col * 6 + row`. This module
+//! bridges the two for the connected board's [`LedLayout`], which is the
+//! M1 V5 HE's until [`select_led_layout`] picks another.
 
-use crate::profile::M1_V5_HE_KEY_NAMES;
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+
+use crate::profile::{M1_V5_HE_KEY_NAMES, M1_V5_HE_LED_GAP_COLUMNS};
+use crate::protocol::hid;
 
 /// Matrix dimensions (must match led_stream.rs)
 pub const COLS: usize = 16;
@@ -22,35 +26,122 @@ pub struct KeyTarget {
     pub slots: Vec<usize>,
 }
 
-/// Physical LED grid column where an unaccounted gap exists per row.
-/// Keys at logical column >= this value need +1 to get the physical column.
-/// Derived from firmware's static_led_pos_tbl vs M1_V5_HE_KEY_NAMES layout.
-const PHYS_GAP_COL: [u8; ROWS] = [1, 1, 1, 1, 12, 9];
+/// Where a board's keys sit in the LED grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedLayout {
+    /// Key name per key matrix position (column-major); empty where there's no key
+    pub names: Vec<String>,
+    /// Per row, the first key matrix column that sits one LED column further
+    /// right (`COLS` for no gap). Read from the firmware's `static_led_pos_tbl`.
+    pub gap_cols: [u8; ROWS],
+}
 
-/// Return sorted row-major indices for all keys matching a predicate.
-///
-/// The predicate receives `(row_major_index, key_name)` for each non-empty key.
-fn keys_matching(pred: impl Fn(usize, &str) -> bool) -> Vec<usize> {
-    let mut result = Vec::new();
-    for (col_major_idx, &name) in M1_V5_HE_KEY_NAMES.iter().enumerate() {
-        if col_major_idx >= MATRIX_LEN || name.is_empty() {
-            continue;
+impl LedLayout {
+    /// Layout from a name table (`"?"` or empty for no key) and, if known,
+    /// the per-row gap columns. Rows without a gap column have no gap.
+    pub fn new<S: AsRef<str>>(names: impl IntoIterator<Item = S>, gap_cols: Option<&[u8]>) -> Self {
+        let names = names
+            .into_iter()
+            .map(|n| match n.as_ref() {
+                "?" => String::new(),
+                n => n.to_string(),
+            })
+            .collect();
+        let mut gaps = [COLS as u8; ROWS];
+        for (gap, &col) in gaps.iter_mut().zip(gap_cols.unwrap_or_default()) {
+            *gap = col;
         }
-        let col = col_major_idx / ROWS;
-        let row = col_major_idx % ROWS;
-        let row_major = pos_to_matrix_index(row as u8, col as u8);
-        if pred(row_major, name) {
-            result.push(row_major);
+        Self {
+            names,
+            gap_cols: gaps,
         }
     }
+
+    /// The M1 V5 HE, the board the built-in tables were read from.
+    pub fn m1_v5_he() -> Self {
+        Self::new(M1_V5_HE_KEY_NAMES, Some(&M1_V5_HE_LED_GAP_COLUMNS))
+    }
+
+    /// Row-major LED index of a key matrix (row, col); `None` for keys
+    /// outside the LED grid (matrices wider than 16 columns).
+    pub fn grid_index(&self, row: u8, col: u8) -> Option<usize> {
+        let gap = *self.gap_cols.get(row as usize)?;
+        let physical_col = col as usize + usize::from(col >= gap);
+        (physical_col < COLS).then_some(row as usize * COLS + physical_col)
+    }
+
+    /// (row, col) of a key name. Names are case-insensitive and also match
+    /// any name for the same HID key, so "Backspace" finds "Bksp".
+    pub fn key_pos(&self, name: &str) -> Option<(u8, u8)> {
+        self.keys()
+            .find(|&(_, _, n)| n.eq_ignore_ascii_case(name))
+            .or_else(|| self.keys().find(|&(_, _, n)| same_hid_key(n, name)))
+            .map(|(row, col, _)| (row, col))
+    }
+
+    /// (row, col, name) of every key, in key matrix order.
+    fn keys(&self) -> impl Iterator<Item = (u8, u8, &str)> {
+        self.names
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| !name.is_empty())
+            .map(|(i, name)| ((i % ROWS) as u8, (i / ROWS) as u8, name.as_str()))
+    }
+}
+
+/// Whether two key names are the same HID key ("Bksp" and "Backspace").
+fn same_hid_key(a: &str, b: &str) -> bool {
+    match (hid::key_code_from_name(a), hid::key_code_from_name(b)) {
+        (Some(a), Some(b)) => a != 0 && a == b,
+        _ => false,
+    }
+}
+
+/// Layout picked with [`select_led_layout`].
+static ACTIVE_LAYOUT: RwLock<Option<Arc<LedLayout>>> = RwLock::new(None);
+
+/// Use `layout` for every lookup here, and so for the LED frames built on
+/// them. Called when a keyboard is opened; one board at a time.
+pub fn select_led_layout(layout: LedLayout) {
+    *ACTIVE_LAYOUT
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(layout));
+}
+
+/// The selected layout, or the M1 V5 HE's.
+pub fn led_layout() -> Arc<LedLayout> {
+    static M1_V5_HE: OnceLock<Arc<LedLayout>> = OnceLock::new();
+    let active = ACTIVE_LAYOUT
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    active.unwrap_or_else(|| {
+        M1_V5_HE
+            .get_or_init(|| Arc::new(LedLayout::m1_v5_he()))
+            .clone()
+    })
+}
+
+/// Return sorted row-major indices for all keys matching a predicate.
+///
+/// The predicate receives `(row_major_index, key_name)` for each key in the LED grid.
+fn keys_matching(pred: impl Fn(usize, &str) -> bool) -> Vec<usize> {
+    let layout = led_layout();
+    let mut result: Vec<usize> = layout
+        .keys()
+        .filter_map(|(row, col, name)| {
+            let row_major = layout.grid_index(row, col)?;
+            pred(row_major, name).then_some(row_major)
+        })
+        .collect();
     result.sort_unstable();
     result
 }
 
 /// Convert a key name to its (row, col) position in the 16×6 LED grid.
 ///
-/// Key names are case-insensitive. Accepts the canonical names from
-/// `M1_V5_HE_KEY_NAMES` plus common aliases.
+/// Key names are case-insensitive. Accepts the names of the selected layout
+/// plus common aliases and other names for the same HID key.
 pub fn key_name_to_pos(name: &str) -> Option<(u8, u8)> {
     // Try aliases first
     let canonical = match name.to_ascii_lowercase().as_str() {
@@ -95,35 +186,14 @@ pub fn key_name_to_pos(name: &str) -> Option<(u8, u8)> {
         canonical
     };
 
-    // M1_V5_HE_KEY_NAMES is column-major: index = col * 6 + row
-    // Find the index, then convert to (row, col)
-    for (idx, &key_name) in M1_V5_HE_KEY_NAMES.iter().enumerate() {
-        if idx >= MATRIX_LEN {
-            break;
-        }
-        if key_name.is_empty() {
-            continue;
-        }
-        if key_name.eq_ignore_ascii_case(search) {
-            let col = idx / ROWS;
-            let row = idx % ROWS;
-            return Some((row as u8, col as u8));
-        }
-    }
-
-    None
+    led_layout().key_pos(search)
 }
 
 /// Convert a logical (row, col) from the key name table to the physical row-major
-/// matrix index for LED streaming. Accounts for per-row gap columns in the
-/// firmware's `static_led_pos_tbl`.
-pub fn pos_to_matrix_index(row: u8, col: u8) -> usize {
-    let physical_col = if col >= PHYS_GAP_COL[row as usize] {
-        col as usize + 1
-    } else {
-        col as usize
-    };
-    row as usize * COLS + physical_col
+/// matrix index for LED streaming. Accounts for the selected layout's per-row
+/// gap columns; `None` for keys outside the LED grid.
+pub fn pos_to_matrix_index(row: u8, col: u8) -> Option<usize> {
+    led_layout().grid_index(row, col)
 }
 
 /// Firmware's `static_led_pos_tbl` — strip_idx for each matrix position.
@@ -264,7 +334,8 @@ pub fn parse_key_target(s: &str) -> Result<KeyTarget, String> {
             "LShift", "RShift", "LCtrl", "RCtrl", "LAlt", "RAlt", "LWin", "Fn",
         ];
         return Ok(KeyTarget::from_sorted(keys_matching(|_, name| {
-            MODS.iter().any(|m| m.eq_ignore_ascii_case(name))
+            MODS.iter()
+                .any(|m| m.eq_ignore_ascii_case(name) || same_hid_key(m, name))
         })));
     }
 
@@ -321,8 +392,10 @@ pub fn parse_key_target(s: &str) -> Result<KeyTarget, String> {
         } else {
             (r_col, l_col)
         };
-        let phys_min = pos_to_matrix_index(l_row, min_col) % COLS;
-        let phys_max = pos_to_matrix_index(l_row, max_col) % COLS;
+        let phys_min =
+            pos_to_matrix_index(l_row, min_col).ok_or_else(|| format!("{left} has no LED"))? % COLS;
+        // A right end past the grid's edge covers the rest of the row
+        let phys_max = pos_to_matrix_index(l_row, max_col).map_or(COLS - 1, |i| i % COLS);
         let row = l_row as usize;
         return Ok(KeyTarget::from_sorted(keys_matching(|idx, _| {
             idx / COLS == row && {
@@ -344,11 +417,12 @@ pub fn parse_key_target(s: &str) -> Result<KeyTarget, String> {
             .trim()
             .parse()
             .map_err(|_| format!("invalid col: {col_s}"))?;
-        if (row as usize) < ROWS && (col as usize) < COLS {
-            return Ok(KeyTarget::from_sorted(vec![pos_to_matrix_index(row, col)]));
-        } else {
-            return Err(format!("position out of range: {row},{col}"));
-        }
+        return match pos_to_matrix_index(row, col) {
+            Some(idx) if (row as usize) < ROWS && (col as usize) < COLS => {
+                Ok(KeyTarget::from_sorted(vec![idx]))
+            }
+            _ => Err(format!("position out of range: {row},{col}")),
+        };
     }
 
     // Check for matrix index (#N)
@@ -365,7 +439,9 @@ pub fn parse_key_target(s: &str) -> Result<KeyTarget, String> {
 
     // Try key name
     if let Some((row, col)) = key_name_to_pos(s) {
-        return Ok(KeyTarget::from_sorted(vec![pos_to_matrix_index(row, col)]));
+        return pos_to_matrix_index(row, col)
+            .map(|idx| KeyTarget::from_sorted(vec![idx]))
+            .ok_or_else(|| format!("{s} has no LED"));
     }
 
     Err(format!("unknown key: {s}"))
@@ -393,8 +469,9 @@ fn parse_text_target(text: &str) -> Result<KeyTarget, String> {
         }
 
         if let Some(key_name) = char_to_key_name(ch) {
-            if let Some((row, col)) = key_name_to_pos(key_name) {
-                let idx = pos_to_matrix_index(row, col);
+            if let Some(idx) =
+                key_name_to_pos(key_name).and_then(|(row, col)| pos_to_matrix_index(row, col))
+            {
                 // Allow duplicates — repeated keys are split into timed sends
                 indices.push(idx);
                 slots.push(slot);
@@ -444,13 +521,13 @@ mod tests {
     #[test]
     fn test_matrix_index() {
         // Esc at (0,0) → col 0 < gap 1, no offset → index 0
-        assert_eq!(pos_to_matrix_index(0, 0), 0);
+        assert_eq!(pos_to_matrix_index(0, 0), Some(0));
         // F1 at (0,1) → col 1 >= gap 1, +1 → physical col 2 → index 2
-        assert_eq!(pos_to_matrix_index(0, 1), 2);
+        assert_eq!(pos_to_matrix_index(0, 1), Some(2));
         // Space at (5,6) → col 6 < gap 9, no offset → index 86
-        assert_eq!(pos_to_matrix_index(5, 6), 86);
+        assert_eq!(pos_to_matrix_index(5, 6), Some(86));
         // RAlt at (5,9) → col 9 >= gap 9, +1 → physical col 10 → index 90
-        assert_eq!(pos_to_matrix_index(5, 9), 90);
+        assert_eq!(pos_to_matrix_index(5, 9), Some(90));
     }
 
     #[test]
@@ -583,7 +660,7 @@ mod tests {
         // After space, slots jump
         let t_pos = target.indices.iter().position(|&idx| {
             // T key
-            key_name_to_pos("T").map(|(r, c)| pos_to_matrix_index(r, c)) == Some(idx)
+            key_name_to_pos("T").and_then(|(r, c)| pos_to_matrix_index(r, c)) == Some(idx)
         });
         assert!(t_pos.is_some());
         // T's slot should be 3 (after space at slot 2)
@@ -609,6 +686,31 @@ mod tests {
         assert_eq!(target.indices.len(), 3); // A, ;, B
     }
 
+    #[test]
+    fn layouts_from_other_boards() {
+        // A 60% board from the database: Esc in the backtick slot, no gaps,
+        // and a matrix wider than the LED grid
+        let mut names = vec![""; 128];
+        names[1] = "Esc";
+        names[8] = "Q";
+        names[85] = "Backspace";
+        names[127] = "Extra";
+        let layout = LedLayout::new(names, None);
+        assert_eq!(layout.key_pos("esc"), Some((1, 0)));
+        assert_eq!(layout.grid_index(2, 1), Some(33));
+        // "Bksp" is the same HID key
+        assert_eq!(layout.key_pos("Bksp"), Some((1, 14)));
+        assert_eq!(layout.grid_index(1, 14), Some(30));
+        // Column 21 doesn't wrap into the next row
+        assert_eq!(layout.key_pos("Extra"), Some((1, 21)));
+        assert_eq!(layout.grid_index(1, 21), None);
+
+        // Gap columns shift the rest of the row, up to the grid's edge
+        let m1 = LedLayout::m1_v5_he();
+        assert_eq!(m1.grid_index(0, 1), Some(2));
+        assert_eq!(m1.grid_index(0, 15), None);
+    }
+
     #[test]
     fn test_stagger_slots_sequential() {
         let target = parse_key_target("frow").unwrap();
@@ -7,12 +7,14 @@
 //! - 30 FPS render loop evaluates keyframe-based effects and sends RGB frames
 //!
 //! Effects are defined in `~/.config/monsgeek/effects.toml` using the keyframe
-//! engine in `crate::effect`.
+//! engine in `crate::effect`. Desktop notifications can be mirrored onto keys
+//! per application (`desktop`).
 
 #[cfg(feature = "notify")]
 pub mod daemon;
 #[cfg(feature = "notify")]
 pub mod dbus;
+pub mod desktop;
 pub mod keymap;
 #[cfg(feature = "notify")]
 pub mod log;
@@ -0,0 +1,421 @@
+//! Compare the vendor command streams of two captures (`iot_driver pcap diff`).
+//!
+//! Typical use: capture the Windows driver doing something and ours doing
+//! the same, then diff. Both captures are reduced to their vendor commands
+//! and responses (keyboard reports and input events are dropped, trailing
+//! zero padding is trimmed). The two streams are then aligned on command
+//! bytes, preferring identical packets. Aligned packets with the same
+//! command but different bytes are shown with the differing offsets and
+//! both decodes.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crossterm::style::Stylize;
+use monsgeek_transport::event_parser::report_id;
+use monsgeek_transport::protocol::cmd;
+use monsgeek_transport::{try_parse_command, try_parse_response};
+
+use super::usb_urb::extract_hid_data;
+use super::{for_each_packet, parse_usb_packet, Direction, UsbPacket};
+
+/// Larger alignments need too much memory; narrow them with `--ignore`.
+const MAX_CELLS: usize = 50_000_000;
+
+/// Whether a packet went to or came from the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Command,
+    Response,
+}
+
+/// One vendor packet from a capture.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    /// Seconds since the capture's first packet
+    pub ts: f64,
+    pub kind: MessageKind,
+    /// Report data starting at the command byte, trailing zeros trimmed
+    pub data: Vec<u8>,
+}
+
+impl Message {
+    pub fn new(ts: f64, kind: MessageKind, data: &[u8]) -> Self {
+        let len = data.iter().rposition(|&b| b != 0).map_or(1, |i| i + 1);
+        Self {
+            ts,
+            kind,
+            data: data[..len.min(data.len())].to_vec(),
+        }
+    }
+
+    pub fn cmd(&self) -> u8 {
+        self.data.first().copied().unwrap_or(0)
+    }
+
+    /// The command name and decoded fields, as `--file` prints them.
+    pub fn decode(&self) -> String {
+        match self.kind {
+            MessageKind::Command => format!("{:?}", try_parse_command(&self.data)),
+            MessageKind::Response => format!("{:?}", try_parse_response(&self.data)),
+        }
+    }
+}
+
+/// The vendor command or response in a USB packet, classified the same way
+/// as the analyzer does; `None` for everything else.
+pub fn vendor_message(ts: f64, packet: &UsbPacket) -> Option<Message> {
+    let data = extract_hid_data(packet).filter(|d| !d.is_empty())?;
+    let urb = packet.urb();
+    let kind = match packet {
+        UsbPacket::Control { setup, .. } => {
+            if setup.is_get_report() && urb.direction == Direction::In {
+                MessageKind::Response
+            } else if setup.is_set_report() {
+                MessageKind::Command
+            } else {
+                return None;
+            }
+        }
+        UsbPacket::Interrupt { .. } => {
+            let first = data[0];
+            let hid_report = (first == 0x00 && data.len() <= 8)
+                || (first == 0x03 && data.len() <= 4)
+                || first == 0x01;
+            if hid_report || first == report_id::USB_VENDOR_EVENT || first == report_id::MOUSE {
+                return None;
+            }
+            if first & 0x80 != 0 {
+                MessageKind::Response
+            } else {
+                MessageKind::Command
+            }
+        }
+        UsbPacket::Bulk { .. } => match urb.direction {
+            Direction::In => MessageKind::Response,
+            _ => MessageKind::Command,
+        },
+        UsbPacket::Other { .. } => return None,
+    };
+    Some(Message::new(ts, kind, data))
+}
+
+/// Which packets take part in the diff.
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// Also compare responses (commands only by default)
+    pub responses: bool,
+    /// Command bytes to leave out (polling, LED streaming, ...)
+    pub ignore: Vec<u8>,
+}
+
+impl DiffOptions {
+    fn keeps(&self, m: &Message) -> bool {
+        (self.responses || m.kind == MessageKind::Command) && !self.ignore.contains(&m.cmd())
+    }
+}
+
+/// Read the vendor packets of a capture that `options` keeps.
+pub fn read_messages(
+    path: &Path,
+    options: &DiffOptions,
+) -> Result<Vec<Message>, Box<dyn std::error::Error>> {
+    let mut messages = Vec::new();
+    for_each_packet(path, |ts, raw| {
+        if let Some(m) = parse_usb_packet(raw).and_then(|p| vendor_message(ts, &p)) {
+            if options.keeps(&m) {
+                messages.push(m);
+            }
+        }
+    })?;
+    Ok(messages)
+}
+
+/// One step of the alignment, as indices into the two streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOp {
+    Same(usize, usize),
+    /// Same command, different bytes
+    Changed(usize, usize),
+    OnlyA(usize),
+    OnlyB(usize),
+}
+
+fn score(a: &Message, b: &Message) -> u32 {
+    if a.kind != b.kind || a.cmd() != b.cmd() {
+        0
+    } else if a.data == b.data {
+        3
+    } else {
+        2
+    }
+}
+
+/// Align two streams: a longest-common-subsequence on command bytes, where
+/// identical packets weigh more than merely the same command. Common
+/// leading and trailing packets are matched first to keep the table small.
+pub fn align(a: &[Message], b: &[Message]) -> Result<Vec<DiffOp>, String> {
+    let prefix = a
+        .iter()
+        .zip(b)
+        .take_while(|(x, y)| score(x, y) == 3)
+        .count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| score(x, y) == 3)
+        .count();
+    let (ma, mb) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let (n, m) = (ma.len(), mb.len());
+    if (n + 1) * (m + 1) > MAX_CELLS {
+        return Err(format!(
+            "captures too different to align ({n} x {m} packets); \
+             leave out noisy commands with --ignore"
+        ));
+    }
+
+    // best[i][j]: best score aligning ma[i..] with mb[j..]
+    let width = m + 1;
+    let mut best = vec![0u32; (n + 1) * width];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            let s = score(&ma[i], &mb[j]);
+            let diag = if s > 0 {
+                best[(i + 1) * width + j + 1] + s
+            } else {
+                0
+            };
+            best[i * width + j] = diag
+                .max(best[(i + 1) * width + j])
+                .max(best[i * width + j + 1]);
+        }
+    }
+
+    let mut ops: Vec<DiffOp> = (0..prefix).map(|i| DiffOp::Same(i, i)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        let here = best[i * width + j];
+        if i < n && j < m {
+            let s = score(&ma[i], &mb[j]);
+            if s > 0 && here == best[(i + 1) * width + j + 1] + s {
+                let (ai, bj) = (prefix + i, prefix + j);
+                ops.push(if s == 3 {
+                    DiffOp::Same(ai, bj)
+                } else {
+                    DiffOp::Changed(ai, bj)
+                });
+                i += 1;
+                j += 1;
+                continue;
+            }
+        }
+        if i < n && (j == m || here == best[(i + 1) * width + j]) {
+            ops.push(DiffOp::OnlyA(prefix + i));
+            i += 1;
+        } else {
+            ops.push(DiffOp::OnlyB(prefix + j));
+            j += 1;
+        }
+    }
+    let (ta, tb) = (a.len() - suffix, b.len() - suffix);
+    ops.extend((0..suffix).map(|k| DiffOp::Same(ta + k, tb + k)));
+    Ok(ops)
+}
+
+/// Byte offsets where two packets differ (a missing byte counts).
+pub fn differing_bytes(a: &[u8], b: &[u8]) -> Vec<usize> {
+    (0..a.len().max(b.len()))
+        .filter(|&i| a.get(i) != b.get(i))
+        .collect()
+}
+
+fn hex(data: &[u8], highlight: &[usize]) -> String {
+    let mut out = String::new();
+    for (i, byte) in data.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        let b = format!("{byte:02x}");
+        if highlight.contains(&i) {
+            write!(out, "{}", b.red().bold()).ok();
+        } else {
+            out.push_str(&b);
+        }
+    }
+    out
+}
+
+fn label(m: &Message) -> String {
+    let dir = match m.kind {
+        MessageKind::Command => "CMD",
+        MessageKind::Response => "RSP",
+    };
+    format!("{dir} 0x{:02x} {:<22}", m.cmd(), cmd::name(m.cmd()))
+}
+
+/// Counts for the summary line.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DiffSummary {
+    pub same: usize,
+    pub changed: usize,
+    pub only_a: usize,
+    pub only_b: usize,
+}
+
+/// Render the alignment as text. Runs of identical packets are collapsed
+/// to a count unless `show_same`.
+pub fn render(a: &[Message], b: &[Message], ops: &[DiffOp], show_same: bool) -> String {
+    let mut out = String::new();
+    let mut hidden = 0;
+    let flush = |out: &mut String, hidden: &mut usize| {
+        if *hidden > 0 {
+            writeln!(out, "{}", format!("   ... {hidden} identical").dim()).ok();
+            *hidden = 0;
+        }
+    };
+    for op in ops {
+        match *op {
+            DiffOp::Same(..) if !show_same => hidden += 1,
+            DiffOp::Same(i, _) => {
+                writeln!(out, "  {} {}", label(&a[i]), hex(&a[i].data, &[])).ok();
+            }
+            DiffOp::Changed(i, j) => {
+                flush(&mut out, &mut hidden);
+                let diff = differing_bytes(&a[i].data, &b[j].data);
+                let offsets: Vec<String> = diff.iter().map(|o| o.to_string()).collect();
+                writeln!(
+                    out,
+                    "{} {}  (bytes {})",
+                    "~".yellow().bold(),
+                    label(&a[i]),
+                    offsets.join(",")
+                )
+                .ok();
+                writeln!(out, "    A {:>9.3}s  {}", a[i].ts, hex(&a[i].data, &diff)).ok();
+                writeln!(out, "    B {:>9.3}s  {}", b[j].ts, hex(&b[j].data, &diff)).ok();
+                let (da, db) = (a[i].decode(), b[j].decode());
+                if da != db {
+                    writeln!(out, "    A {da}").ok();
+                    writeln!(out, "    B {db}").ok();
+                }
+            }
+            DiffOp::OnlyA(i) => {
+                flush(&mut out, &mut hidden);
+                writeln!(
+                    out,
+                    "{} {} {}",
+                    "-".red().bold(),
+                    label(&a[i]),
+                    hex(&a[i].data, &[])
+                )
+                .ok();
+            }
+            DiffOp::OnlyB(j) => {
+                flush(&mut out, &mut hidden);
+                writeln!(
+                    out,
+                    "{} {} {}",
+                    "+".green().bold(),
+                    label(&b[j]),
+                    hex(&b[j].data, &[])
+                )
+                .ok();
+            }
+        }
+    }
+    flush(&mut out, &mut hidden);
+    out
+}
+
+pub fn summarize(ops: &[DiffOp]) -> DiffSummary {
+    let mut s = DiffSummary::default();
+    for op in ops {
+        match op {
+            DiffOp::Same(..) => s.same += 1,
+            DiffOp::Changed(..) => s.changed += 1,
+            DiffOp::OnlyA(_) => s.only_a += 1,
+            DiffOp::OnlyB(_) => s.only_b += 1,
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmds(packets: &[&[u8]]) -> Vec<Message> {
+        packets
+            .iter()
+            .map(|p| Message::new(0.0, MessageKind::Command, p))
+            .collect()
+    }
+
+    #[test]
+    fn trims_padding_but_keeps_the_command_byte() {
+        let m = Message::new(0.0, MessageKind::Command, &[0x07, 0x01, 0, 0, 0]);
+        assert_eq!(m.data, vec![0x07, 0x01]);
+        assert_eq!(
+            Message::new(0.0, MessageKind::Command, &[0, 0]).data,
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn aligns_changed_inserted_and_missing_packets() {
+        let a = cmds(&[&[0x8f], &[0x07, 0x04, 0x32], &[0x09, 0x01], &[0x87]]);
+        let b = cmds(&[&[0x8f], &[0x07, 0x04, 0x64], &[0x1b], &[0x87]]);
+        let ops = align(&a, &b).unwrap();
+        assert_eq!(
+            ops,
+            vec![
+                DiffOp::Same(0, 0),
+                DiffOp::Changed(1, 1),
+                DiffOp::OnlyA(2),
+                DiffOp::OnlyB(2),
+                DiffOp::Same(3, 3),
+            ]
+        );
+        assert_eq!(
+            summarize(&ops),
+            DiffSummary {
+                same: 2,
+                changed: 1,
+                only_a: 1,
+                only_b: 1
+            }
+        );
+    }
+
+    #[test]
+    fn prefers_identical_packets_over_the_same_command() {
+        // B repeats the command; the identical one is the match
+        let a = cmds(&[&[0x07, 0x02]]);
+        let b = cmds(&[&[0x07, 0x01], &[0x07, 0x02]]);
+        assert_eq!(
+            align(&a, &b).unwrap(),
+            vec![DiffOp::OnlyB(0), DiffOp::Same(0, 1)]
+        );
+    }
+
+    #[test]
+    fn reports_differing_offsets() {
+        assert_eq!(differing_bytes(&[1, 2, 3], &[1, 9, 3, 4]), vec![1, 3]);
+        let a = cmds(&[&[0x07, 0x04, 0x32]]);
+        let b = cmds(&[&[0x07, 0x04, 0x64]]);
+        let text = render(&a, &b, &align(&a, &b).unwrap(), false);
+        assert!(text.contains("(bytes 2)"));
+    }
+
+    #[test]
+    fn responses_and_ignored_commands_are_filtered() {
+        let options = DiffOptions {
+            responses: false,
+            ignore: vec![0x8f],
+        };
+        assert!(!options.keeps(&Message::new(0.0, MessageKind::Response, &[0x87])));
+        assert!(!options.keeps(&cmds(&[&[0x8f]])[0]));
+        assert!(options.keeps(&cmds(&[&[0x07]])[0]));
+    }
+}
@@ -0,0 +1,328 @@
+//! Wireshark Lua dissector generation (`iot_driver pcap dissector`).
+//!
+//! Command names, sub-command names and enum value tables are taken from
+//! `monsgeek_transport` when the dissector is generated, so regenerating it
+//! after a protocol change keeps Wireshark in step with the analyzer. Field
+//! layouts are keyed by the typed commands' `CMD` / `CMD_ECHO` bytes.
+
+use std::fmt::Write;
+
+use monsgeek_transport::protocol::{cmd, fw_transfer, magnetism, INPUT_REPORT_SIZE};
+use monsgeek_transport::{
+    DebounceResponse, EnterPairing, HidCommand, HidResponse, LedMode, LedParamsResponse,
+    PairingCmd, PollingRate, PollingRateResponse, ProfileResponse, SetCtrlByte, SetDebounce,
+    SetLedParams, SetMagnetismReport, SetPollingRate, SetProfile,
+};
+
+/// Value table a field is resolved against in Wireshark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Table {
+    Magnetism,
+    FwTransfer,
+    LedModes,
+    PollingRates,
+}
+
+impl Table {
+    const ALL: [Table; 4] = [
+        Table::Magnetism,
+        Table::FwTransfer,
+        Table::LedModes,
+        Table::PollingRates,
+    ];
+
+    fn lua_name(self) -> &'static str {
+        match self {
+            Table::Magnetism => "magnetism",
+            Table::FwTransfer => "fw_transfer",
+            Table::LedModes => "led_modes",
+            Table::PollingRates => "polling_rates",
+        }
+    }
+
+    /// Sub-commands read best in hex, enum values in decimal.
+    fn base(self) -> &'static str {
+        match self {
+            Table::Magnetism | Table::FwTransfer => "base.HEX",
+            Table::LedModes | Table::PollingRates => "base.DEC",
+        }
+    }
+
+    fn entries(self) -> Vec<(u8, String)> {
+        let named = |name: fn(u8) -> &'static str| -> Vec<(u8, String)> {
+            (0..=u8::MAX)
+                .filter(|&b| name(b) != "UNKNOWN")
+                .map(|b| (b, name(b).to_string()))
+                .collect()
+        };
+        match self {
+            Table::Magnetism => named(magnetism::name),
+            Table::FwTransfer => named(fw_transfer::name),
+            Table::LedModes => (0..=u8::MAX)
+                .filter_map(|b| LedMode::from_u8(b).map(|m| (b, m.name().to_string())))
+                .collect(),
+            Table::PollingRates => (0..=u8::MAX)
+                .filter_map(|b| {
+                    PollingRate::from_protocol(b).map(|r| (b, format!("{} Hz", r.to_hz())))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One decoded field of a report, offsets counted from the command byte.
+#[derive(Debug, Clone, Copy)]
+struct Field {
+    name: &'static str,
+    offset: usize,
+    /// 1, 2 or 4 bytes, little-endian
+    width: usize,
+    table: Option<Table>,
+}
+
+const fn field(name: &'static str, offset: usize, width: usize) -> Field {
+    Field {
+        name,
+        offset,
+        width,
+        table: None,
+    }
+}
+
+const fn lookup(name: &'static str, offset: usize, table: Table) -> Field {
+    Field {
+        name,
+        offset,
+        width: 1,
+        table: Some(table),
+    }
+}
+
+const LED_PARAMS: &[Field] = &[
+    lookup("mode", 1, Table::LedModes),
+    field("speed", 2, 1),
+    field("brightness", 3, 1),
+    field("option", 4, 1),
+    field("r", 5, 1),
+    field("g", 6, 1),
+    field("b", 7, 1),
+];
+const PROFILE: &[Field] = &[field("profile", 1, 1)];
+const DEBOUNCE: &[Field] = &[field("ms", 1, 1)];
+const POLLING_RATE: &[Field] = &[lookup("rate", 2, Table::PollingRates)];
+const MULTI_MAGNETISM: &[Field] = &[lookup("subcmd", 1, Table::Magnetism), field("page", 3, 1)];
+const MAGNETISM_REPORT: &[Field] = &[field("enabled", 1, 1)];
+const USB_VERSION: &[Field] = &[field("device_id", 1, 4), field("version", 7, 2)];
+const SCREEN_COLOR: &[Field] = &[field("r", 1, 1), field("g", 2, 1), field("b", 3, 1)];
+const CTRL_BYTE: &[Field] = &[field("value", 1, 1)];
+const MAGIC: &[Field] = &[field("magic", 1, 4)];
+const PAIRING: &[Field] = &[field("action", 1, 1), field("channel", 2, 1)];
+const ISP_PREPARE: &[Field] = &[field("param", 1, 1)];
+const FW_TRANSFER: &[Field] = &[
+    lookup("subcmd", 1, Table::FwTransfer),
+    field("chunks", 2, 2),
+];
+
+/// Known field layouts by command byte (commands and their response echoes).
+fn layouts() -> Vec<(u8, &'static [Field])> {
+    vec![
+        (SetLedParams::CMD, LED_PARAMS),
+        (LedParamsResponse::CMD_ECHO, LED_PARAMS),
+        (SetProfile::CMD, PROFILE),
+        (ProfileResponse::CMD_ECHO, PROFILE),
+        (SetDebounce::CMD, DEBOUNCE),
+        (DebounceResponse::CMD_ECHO, DEBOUNCE),
+        (SetPollingRate::CMD, POLLING_RATE),
+        (PollingRateResponse::CMD_ECHO, POLLING_RATE),
+        (SetMagnetismReport::CMD, MAGNETISM_REPORT),
+        (cmd::SET_MULTI_MAGNETISM, MULTI_MAGNETISM),
+        (cmd::GET_MULTI_MAGNETISM, MULTI_MAGNETISM),
+        (cmd::GET_USB_VERSION, USB_VERSION),
+        (cmd::SET_SCREEN_COLOR, SCREEN_COLOR),
+        (SetCtrlByte::CMD, CTRL_BYTE),
+        (EnterPairing::CMD, MAGIC),
+        (PairingCmd::CMD, PAIRING),
+        (cmd::ENTER_BOOTLOADER, MAGIC),
+        (cmd::ISP_PREPARE, ISP_PREPARE),
+        (cmd::FW_TRANSFER, FW_TRANSFER),
+    ]
+}
+
+/// Every command byte the transport crate has a name for.
+fn command_names() -> Vec<(u8, &'static str)> {
+    (0..=u8::MAX)
+        .map(|b| (b, cmd::name(b)))
+        .filter(|(_, name)| *name != "UNKNOWN")
+        .collect()
+}
+
+/// Lua identifier for a field, e.g. `set_ledparam_mode`.
+fn field_id(cmd_byte: u8, field: &Field) -> String {
+    format!("{}_{}", cmd::name(cmd_byte).to_lowercase(), field.name)
+}
+
+fn write_table(out: &mut String, name: &str, entries: &[(u8, String)]) {
+    writeln!(out, "local {name} = {{").ok();
+    for (value, label) in entries {
+        writeln!(out, "    [0x{value:02x}] = {label:?},").ok();
+    }
+    writeln!(out, "}}").ok();
+}
+
+/// Render the dissector as a Lua plugin.
+pub fn render() -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "-- MonsGeek/Akko vendor HID dissector, generated by `iot_driver pcap dissector`.\n\
+         -- Regenerate rather than edit; tables come from monsgeek_transport.\n\
+         --\n\
+         -- Install: copy to ~/.local/lib/wireshark/plugins/ and reload Lua plugins\n\
+         -- (Analyze > Reload Lua Plugins). Decodes vendor feature reports carried\n\
+         -- in USB control transfers; filter with `monsgeek`.\n\
+         -- GET_MULTI_MAGNETISM responses and firmware data chunks have no command\n\
+         -- byte and are labelled by whatever their first byte happens to be.\n\
+         \n\
+         local monsgeek = Proto(\"monsgeek\", \"MonsGeek/Akko vendor protocol\")\n"
+    )
+    .ok();
+
+    let commands: Vec<_> = command_names()
+        .into_iter()
+        .map(|(b, name)| (b, name.to_string()))
+        .collect();
+    write_table(&mut out, "commands", &commands);
+    for table in Table::ALL {
+        write_table(&mut out, table.lua_name(), &table.entries());
+    }
+
+    writeln!(
+        out,
+        "\nlocal f = monsgeek.fields\n\
+         f.cmd = ProtoField.uint8(\"monsgeek.cmd\", \"Command\", base.HEX, commands)\n\
+         f.direction = ProtoField.string(\"monsgeek.direction\", \"Direction\")\n\
+         f.payload = ProtoField.bytes(\"monsgeek.payload\", \"Payload\")"
+    )
+    .ok();
+    let layouts = layouts();
+    for (cmd_byte, fields) in &layouts {
+        for fd in fields.iter() {
+            let (base, table) = match fd.table {
+                Some(t) => (t.base(), format!(", {}", t.lua_name())),
+                None if fd.width == 1 => ("base.DEC", String::new()),
+                None => ("base.HEX", String::new()),
+            };
+            writeln!(
+                out,
+                "f.{} = ProtoField.uint{}(\"monsgeek.{}.{}\", \"{}\", {base}{table})",
+                field_id(*cmd_byte, fd),
+                fd.width * 8,
+                cmd::name(*cmd_byte).to_lowercase(),
+                fd.name,
+                fd.name
+            )
+            .ok();
+        }
+    }
+
+    writeln!(out, "\nlocal layouts = {{").ok();
+    for (cmd_byte, fields) in &layouts {
+        let entries: Vec<_> = fields
+            .iter()
+            .map(|fd| {
+                format!(
+                    "{{ f.{}, {}, {} }}",
+                    field_id(*cmd_byte, fd),
+                    fd.offset,
+                    fd.width
+                )
+            })
+            .collect();
+        writeln!(
+            out,
+            "    [0x{cmd_byte:02x}] = {{ {} }},",
+            entries.join(", ")
+        )
+        .ok();
+    }
+    writeln!(out, "}}").ok();
+
+    writeln!(
+        out,
+        "\nlocal transfer_type = Field.new(\"usb.transfer_type\")\n\
+         local endpoint_dir = Field.new(\"usb.endpoint_address.direction\")\n\
+         local data_fragment = Field.new(\"usb.data_fragment\")\n\
+         \n\
+         local URB_CONTROL = 2\n\
+         local REPORT_SIZE = {INPUT_REPORT_SIZE}\n\
+         \n\
+         function monsgeek.dissector(tvb, pinfo, tree)\n    \
+             local tt = transfer_type()\n    \
+             local frag = data_fragment()\n    \
+             if not tt or tt.value ~= URB_CONTROL or not frag then return end\n    \
+             local data = frag.range\n    \
+             if data:len() < 1 or data:len() > REPORT_SIZE + 1 then return end\n    \
+             local cmd = data(0, 1):uint()\n    \
+             local name = commands[cmd]\n    \
+             if not name then return end\n\
+             \n    \
+             local dir = endpoint_dir()\n    \
+             local label = (dir and dir.value == 1) and \"RSP\" or \"CMD\"\n    \
+             local subtree = tree:add(monsgeek, data, \"MonsGeek \" .. label .. \" \" .. name)\n    \
+             subtree:add(f.direction, label)\n    \
+             subtree:add(f.cmd, data(0, 1))\n    \
+             for _, fd in ipairs(layouts[cmd] or {{}}) do\n        \
+                 local field, offset, width = fd[1], fd[2], fd[3]\n        \
+                 if offset + width <= data:len() then\n            \
+                     subtree:add_le(field, data(offset, width))\n        \
+                 end\n    \
+             end\n    \
+             if data:len() > 1 then\n        \
+                 subtree:add(f.payload, data(1))\n    \
+             end\n    \
+             pinfo.cols.info:append(\" [\" .. label .. \" \" .. name .. \"]\")\n\
+         end\n\
+         \n\
+         register_postdissector(monsgeek)"
+    )
+    .ok();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn every_named_command_is_in_the_table() {
+        let lua = render();
+        for (b, name) in command_names() {
+            assert!(lua.contains(&format!("[0x{b:02x}] = \"{name}\"")), "{name}");
+        }
+        assert!(lua.contains("[0x00] = \"Off\""));
+        assert!(lua.contains("[0xc0] = \"START\""));
+        assert!(lua.trim_end().ends_with("register_postdissector(monsgeek)"));
+    }
+
+    #[test]
+    fn layouts_fit_the_report_and_have_unique_fields() {
+        let mut cmds = BTreeSet::new();
+        for (cmd_byte, fields) in layouts() {
+            assert_ne!(cmd::name(cmd_byte), "UNKNOWN", "0x{cmd_byte:02x}");
+            assert!(cmds.insert(cmd_byte), "0x{cmd_byte:02x} listed twice");
+            for fd in fields {
+                assert!(matches!(fd.width, 1 | 2 | 4), "{}", fd.name);
+                assert!(fd.offset + fd.width <= INPUT_REPORT_SIZE, "{}", fd.name);
+            }
+        }
+        let lua = render();
+        assert!(lua.contains(
+            "f.set_ledparam_mode = ProtoField.uint8(\"monsgeek.set_ledparam.mode\", \"mode\", base.DEC, led_modes)"
+        ));
+        assert!(lua.contains(
+            "f.get_usb_version_device_id = ProtoField.uint32(\"monsgeek.get_usb_version.device_id\""
+        ));
+        assert_eq!(lua.matches("f.set_ledparam_mode =").count(), 1);
+    }
+}
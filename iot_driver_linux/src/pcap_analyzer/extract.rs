@@ -0,0 +1,550 @@
+//! Reconstruct a device configuration from a capture (`iot_driver pcap extract`).
+//!
+//! Replays the SET commands the official driver sent, latest write winning,
+//! into one [`DeviceState`] per profile, so a setup made on Windows can be
+//! written out as a `config apply` file (or a `profile restore` backup when
+//! the capture touched several profiles). Userpic uploads come back as raw
+//! slot data for saving as images. What the config format can't hold (per-key
+//! trigger writes covering only some keys, on-board animations) is reported
+//! as notes rather than dropped silently.
+//!
+//! Command bytes are the RY5088 ones, as elsewhere in the analyzer.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use monsgeek_keyboard::led::LedParams;
+use monsgeek_keyboard::{
+    parse_macro_events, FeatureList, KeyboardOptions, PollingRate, Precision, SleepTimeSettings,
+    TriggerSettings,
+};
+use monsgeek_transport::protocol::{cmd, magnetism as mag_cmd};
+use monsgeek_transport::{try_parse_command, ParsedCommand};
+
+use super::diff::{read_messages, DiffOptions, Message, MessageKind};
+use crate::keyboard_config::{DeviceState, MacroWrite, ProfileBackup};
+use crate::keymap::{default_keycode, KeyMap, RawKeyMapData};
+
+/// Frame offset of the payload after the 7-byte header of paged writes
+/// (keymatrix, macro, magnetism, userpic).
+const PAGED_DATA: usize = 8;
+/// Bytes per page of macro and magnetism writes.
+const PAGE_SIZE: usize = 56;
+/// Bytes per userpic slot.
+const USERPIC_SIZE: usize = 384;
+
+/// Trigger tables written with two bytes per key, and their config names.
+const TRAVEL_TABLES: [(u8, &str); 6] = [
+    (mag_cmd::PRESS_TRAVEL, "actuation"),
+    (mag_cmd::LIFT_TRAVEL, "release"),
+    (mag_cmd::RT_PRESS, "rapid trigger press"),
+    (mag_cmd::RT_LIFT, "rapid trigger lift"),
+    (mag_cmd::BOTTOM_DEADZONE, "bottom deadzone"),
+    (mag_cmd::TOP_DEADZONE, "top deadzone"),
+];
+
+/// Everything recovered from a capture.
+#[derive(Default)]
+pub struct Extraction {
+    /// Per profile, only what the capture wrote
+    pub profiles: BTreeMap<u8, DeviceState>,
+    /// Userpic slot data (column-major RGB, as `userpic` uploads it)
+    pub userpics: BTreeMap<u8, Vec<u8>>,
+    /// What couldn't be carried over, for the user
+    pub notes: Vec<String>,
+}
+
+impl Extraction {
+    /// Whether anything importable was found.
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty() && self.userpics.is_empty()
+    }
+
+    /// Render as an importable file: a `config apply` file for a single
+    /// profile, a `profile restore` backup for several. Notes are added as
+    /// comments at the top.
+    pub fn to_toml(&self, source: &str) -> Result<String, String> {
+        let mut out = String::new();
+        if self.profiles.len() == 1 {
+            let (&profile, state) = self.profiles.iter().next().unwrap();
+            let header =
+                format!("# Keyboard config extracted from {source} by `iot_driver pcap extract`.");
+            out.push_str(&state.to_config_toml_with_header(profile, &header));
+        } else {
+            let mut backup = ProfileBackup {
+                device: Some(format!("extracted from {source}")),
+                ..Default::default()
+            };
+            for (&profile, state) in &self.profiles {
+                backup
+                    .push_state(profile, state)
+                    .map_err(|e| format!("profile {profile}: {e}"))?;
+            }
+            // Only what the capture wrote, as with a single profile: keys
+            // remapped outside it are left alone
+            for config in &mut backup.profiles {
+                config.reset_unlisted_remaps = false;
+            }
+            out.push_str(&backup.to_toml());
+        }
+        if self.notes.is_empty() {
+            return Ok(out);
+        }
+        let mut notes = String::from("# Not carried over:\n");
+        for note in &self.notes {
+            notes.push_str(&format!("#   {note}\n"));
+        }
+        notes.push('\n');
+        Ok(notes + &out)
+    }
+}
+
+/// Writes seen for one profile.
+#[derive(Default)]
+struct ProfileWrites {
+    state: DeviceState,
+    /// Base layer 0/1 key configs by (layer, key)
+    keys: BTreeMap<(u8, u8), [u8; 4]>,
+    /// Fn layer key configs by key
+    fn_keys: BTreeMap<u8, [u8; 4]>,
+    /// Trigger table bytes by sub-command; `None` where never written
+    triggers: BTreeMap<u8, Vec<Option<u8>>>,
+}
+
+/// Replays commands into per-profile writes.
+struct Extractor {
+    key_count: usize,
+    /// Active profile; unknown until a switch or a profile query
+    profile: Option<u8>,
+    assumed_profile: bool,
+    profiles: BTreeMap<u8, ProfileWrites>,
+    /// Macro pages by slot, with the profile active when the slot was written
+    macros: BTreeMap<u8, (u8, BTreeMap<u8, Vec<u8>>)>,
+    userpics: BTreeMap<u8, Vec<u8>>,
+    precision: Option<Precision>,
+    gif_packets: usize,
+}
+
+/// Byte `i` of a message; trailing zeros were trimmed off.
+fn byte(data: &[u8], i: usize) -> u8 {
+    data.get(i).copied().unwrap_or(0)
+}
+
+/// `len` bytes from `start`, zero-padded.
+fn bytes(data: &[u8], start: usize, len: usize) -> Vec<u8> {
+    (start..start + len).map(|i| byte(data, i)).collect()
+}
+
+impl Extractor {
+    fn new(key_count: usize) -> Self {
+        Self {
+            key_count,
+            profile: None,
+            assumed_profile: false,
+            profiles: BTreeMap::new(),
+            macros: BTreeMap::new(),
+            userpics: BTreeMap::new(),
+            precision: None,
+            gif_packets: 0,
+        }
+    }
+
+    fn active(&mut self) -> u8 {
+        *self.profile.get_or_insert_with(|| {
+            self.assumed_profile = true;
+            0
+        })
+    }
+
+    fn writes(&mut self, profile: u8) -> &mut ProfileWrites {
+        self.profiles.entry(profile).or_default()
+    }
+
+    fn current(&mut self) -> &mut ProfileWrites {
+        let profile = self.active();
+        self.writes(profile)
+    }
+
+    fn response(&mut self, d: &[u8]) {
+        match byte(d, 0) {
+            cmd::GET_PROFILE if self.profile.is_none() => self.profile = Some(byte(d, 1)),
+            cmd::GET_FEATURE_LIST => {
+                if let Some(p) = FeatureList::from_bytes(&d[1.min(d.len())..]).precision() {
+                    self.precision = Some(p);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn command(&mut self, d: &[u8]) {
+        match byte(d, 0) {
+            cmd::SET_PROFILE => self.profile = Some(byte(d, 1)),
+            cmd::SET_REPORT => {
+                if let Some(rate) = PollingRate::from_protocol(byte(d, 2)) {
+                    self.current().state.polling_rate = Some(rate);
+                }
+            }
+            cmd::SET_DEBOUNCE => self.current().state.debounce = Some(byte(d, 1)),
+            cmd::SET_LEDPARAM => {
+                if let ParsedCommand::SetLedParams(r) = try_parse_command(d) {
+                    self.current().state.led = Some(LedParams::from_transport_response(&r));
+                }
+            }
+            cmd::SET_SLEEPTIME => {
+                let u16_at = |i| u16::from_le_bytes([byte(d, i), byte(d, i + 1)]);
+                self.current().state.sleep = Some(SleepTimeSettings {
+                    idle_bt: u16_at(8),
+                    idle_24g: u16_at(10),
+                    deep_bt: u16_at(12),
+                    deep_24g: u16_at(14),
+                });
+            }
+            cmd::SET_KBOPTION => {
+                let options = KeyboardOptions::from_bytes(&bytes(d, 1, 8));
+                self.current().state.options = Some(options);
+            }
+            cmd::SET_KEYMATRIX => {
+                let (profile, key, enabled, layer) =
+                    (byte(d, 1), byte(d, 2), byte(d, 5), byte(d, 6));
+                let config = [byte(d, 8), byte(d, 9), byte(d, 10), byte(d, 11)];
+                let keys = &mut self.writes(profile).keys;
+                if enabled == 0 || config == [0; 4] {
+                    keys.remove(&(layer, key));
+                } else {
+                    keys.insert((layer, key), config);
+                }
+            }
+            // Only the Windows Fn layer (fn_sys 0), as `keymap` reads it
+            cmd::SET_FN if byte(d, 1) == 0 => {
+                let (profile, key) = (byte(d, 2), byte(d, 3));
+                let config = [byte(d, 8), byte(d, 9), byte(d, 10), byte(d, 11)];
+                self.writes(profile).fn_keys.insert(key, config);
+            }
+            cmd::SET_MACRO => {
+                let (slot, page, len) = (byte(d, 1), byte(d, 2), byte(d, 3) as usize);
+                let profile = self.active();
+                let (owner, pages) = self.macros.entry(slot).or_default();
+                if page == 0 {
+                    // A rewrite of the slot starts over
+                    pages.clear();
+                    *owner = profile;
+                }
+                pages.insert(page, bytes(d, PAGED_DATA, len.min(PAGE_SIZE)));
+            }
+            cmd::SET_MULTI_MAGNETISM => self.magnetism(d),
+            cmd::SET_USERPIC => {
+                let (slot, page, len) = (byte(d, 1), byte(d, 3) as usize, byte(d, 4) as usize);
+                let pic = self
+                    .userpics
+                    .entry(slot)
+                    .or_insert_with(|| vec![0; USERPIC_SIZE]);
+                for (i, b) in bytes(d, PAGED_DATA, len).into_iter().enumerate() {
+                    if let Some(p) = pic.get_mut(page * PAGE_SIZE + i) {
+                        *p = b;
+                    }
+                }
+            }
+            cmd::SET_USERGIF => self.gif_packets += 1,
+            _ => {}
+        }
+    }
+
+    /// SET_MULTI_MAGNETISM: `[sub_cmd, flag, page, commit, 0, 0, checksum]`
+    /// then data. Paged bulk writes (`flag` 1) carry the whole table, 56
+    /// bytes a page; simple writes (`flag` 0) one key, `page` being its index.
+    fn magnetism(&mut self, d: &[u8]) {
+        let (sub, flag, page) = (byte(d, 1), byte(d, 2), byte(d, 3) as usize);
+        let width = if sub == mag_cmd::KEY_MODE {
+            1
+        } else if TRAVEL_TABLES.iter().any(|&(s, _)| s == sub) {
+            2
+        } else {
+            return; // DKS and friends have no config representation
+        };
+        let len = self.key_count * width;
+        let (start, count) = match flag {
+            1 => (page * PAGE_SIZE, PAGE_SIZE),
+            0 => (page * width, width),
+            _ => return,
+        };
+        let table = self
+            .current()
+            .triggers
+            .entry(sub)
+            .or_insert_with(|| vec![None; len]);
+        for (i, b) in bytes(d, PAGED_DATA, count).into_iter().enumerate() {
+            if let Some(slot) = table.get_mut(start + i) {
+                *slot = Some(b);
+            }
+        }
+    }
+
+    fn finish(self) -> Extraction {
+        let mut ex = Extraction::default();
+        let key_count = self.key_count;
+        let precision = self.precision.unwrap_or_default();
+        let mut profiles = self.profiles;
+        for (slot, (owner, pages)) in self.macros {
+            let data: Vec<u8> = pages.into_values().flatten().collect();
+            let (repeat, events) = parse_macro_events(&data);
+            let events = events
+                .iter()
+                .map(|e| (e.keycode, e.is_down, e.delay_ms))
+                .collect();
+            profiles.entry(owner).or_default().state.macros.insert(
+                slot,
+                MacroWrite {
+                    slot,
+                    events,
+                    repeat,
+                },
+            );
+        }
+
+        let mut used_precision = false;
+        for (profile, mut w) in profiles {
+            if !w.keys.is_empty() || !w.fn_keys.is_empty() {
+                w.state.keymap = Some(keymap(key_count, &w.keys, &w.fn_keys));
+            }
+            let mut triggers = TriggerSettings::new(key_count);
+            let mut any = false;
+            for (&sub, table) in &w.triggers {
+                let name = TRAVEL_TABLES
+                    .iter()
+                    .find(|&&(s, _)| s == sub)
+                    .map_or("key mode", |&(_, n)| n);
+                let Some(raw) = table.iter().copied().collect::<Option<Vec<u8>>>() else {
+                    let keys = table.iter().filter(|b| b.is_some()).count()
+                        / if sub == mag_cmd::KEY_MODE { 1 } else { 2 };
+                    ex.notes.push(format!(
+                        "profile {profile}: per-key {name} for {keys} key(s) (a config sets every key)"
+                    ));
+                    continue;
+                };
+                any = true;
+                let values = TriggerSettings::decode_u16_values(&raw, key_count);
+                match sub {
+                    mag_cmd::KEY_MODE => triggers.key_modes = raw,
+                    mag_cmd::PRESS_TRAVEL => triggers.press_travel = values,
+                    mag_cmd::LIFT_TRAVEL => triggers.lift_travel = values,
+                    mag_cmd::RT_PRESS => triggers.rt_press = values,
+                    mag_cmd::RT_LIFT => triggers.rt_lift = values,
+                    mag_cmd::BOTTOM_DEADZONE => triggers.bottom_deadzone = values,
+                    _ => triggers.top_deadzone = values,
+                }
+            }
+            if any {
+                // Tables the capture didn't write stay as they are
+                for sub in [
+                    mag_cmd::PRESS_TRAVEL,
+                    mag_cmd::LIFT_TRAVEL,
+                    mag_cmd::RT_PRESS,
+                    mag_cmd::RT_LIFT,
+                    mag_cmd::BOTTOM_DEADZONE,
+                    mag_cmd::TOP_DEADZONE,
+                    mag_cmd::KEY_MODE,
+                ] {
+                    if w.triggers.get(&sub).is_none_or(|t| t.contains(&None)) {
+                        match sub {
+                            mag_cmd::KEY_MODE => triggers.key_modes.clear(),
+                            mag_cmd::PRESS_TRAVEL => triggers.press_travel.clear(),
+                            mag_cmd::LIFT_TRAVEL => triggers.lift_travel.clear(),
+                            mag_cmd::RT_PRESS => triggers.rt_press.clear(),
+                            mag_cmd::RT_LIFT => triggers.rt_lift.clear(),
+                            mag_cmd::BOTTOM_DEADZONE => triggers.bottom_deadzone.clear(),
+                            _ => triggers.top_deadzone.clear(),
+                        }
+                    }
+                }
+                w.state.triggers = Some(triggers);
+                used_precision = true;
+            }
+            w.state.precision = precision;
+            ex.profiles.insert(profile, w.state);
+        }
+
+        ex.userpics = self.userpics;
+        if self.assumed_profile {
+            ex.notes.push(
+                "the capture never switched or queried the profile; settings assumed for profile 0"
+                    .into(),
+            );
+        }
+        if used_precision && self.precision.is_none() {
+            ex.notes.push(format!(
+                "no feature list response; trigger travel assumed at {} precision",
+                precision.as_str()
+            ));
+        }
+        if self.gif_packets > 0 {
+            ex.notes.push(format!(
+                "on-board animation upload ({} SET_USERGIF packets)",
+                self.gif_packets
+            ));
+        }
+        ex
+    }
+}
+
+/// A keymap from key writes on top of the factory layout.
+fn keymap(
+    key_count: usize,
+    keys: &BTreeMap<(u8, u8), [u8; 4]>,
+    fn_keys: &BTreeMap<u8, [u8; 4]>,
+) -> KeyMap {
+    let factory = |_| -> Vec<u8> {
+        (0..key_count as u8)
+            .flat_map(|i| [0, 0, default_keycode(i), 0])
+            .collect()
+    };
+    let mut base = [factory(0), factory(1)];
+    let mut fn_layer = vec![0; key_count * 4];
+    for (&(layer, key), config) in keys {
+        if let Some(entry) = base
+            .get_mut(layer as usize)
+            .and_then(|b| b.get_mut(key as usize * 4..key as usize * 4 + 4))
+        {
+            entry.copy_from_slice(config);
+        }
+    }
+    for (&key, config) in fn_keys {
+        if let Some(entry) = fn_layer.get_mut(key as usize * 4..key as usize * 4 + 4) {
+            entry.copy_from_slice(config);
+        }
+    }
+    let [base0, base1] = base;
+    KeyMap::from_raw(&RawKeyMapData {
+        base0,
+        base1,
+        fn_layer: Some(fn_layer),
+        key_count,
+    })
+}
+
+/// Replay captured vendor packets for a board with `key_count` keys.
+pub fn extract_messages(messages: &[Message], key_count: usize) -> Extraction {
+    let mut ex = Extractor::new(key_count);
+    for m in messages {
+        match m.kind {
+            MessageKind::Command => ex.command(&m.data),
+            MessageKind::Response => ex.response(&m.data),
+        }
+    }
+    ex.finish()
+}
+
+/// Read a capture and reconstruct what the driver configured.
+pub fn extract_file(
+    path: &Path,
+    key_count: usize,
+) -> Result<Extraction, Box<dyn std::error::Error>> {
+    let options = DiffOptions {
+        responses: true,
+        ignore: Vec::new(),
+    };
+    Ok(extract_messages(&read_messages(path, &options)?, key_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard_config::KeyboardConfig;
+
+    const KEYS: usize = 98;
+
+    fn frame(cmd: u8, header: &[u8], payload: &[u8]) -> Message {
+        let mut data = vec![cmd];
+        data.extend_from_slice(header);
+        if !payload.is_empty() {
+            data.resize(PAGED_DATA, 0);
+            data.extend_from_slice(payload);
+        }
+        Message::new(0.0, MessageKind::Command, &data)
+    }
+
+    fn bulk_u16(sub: u8, value: u16) -> Vec<Message> {
+        let bytes: Vec<u8> = (0..KEYS).flat_map(|_| value.to_le_bytes()).collect();
+        let pages = bytes.len().div_ceil(PAGE_SIZE);
+        bytes
+            .chunks(PAGE_SIZE)
+            .enumerate()
+            .map(|(p, chunk)| {
+                let commit = (p == pages - 1) as u8;
+                frame(cmd::SET_MULTI_MAGNETISM, &[sub, 1, p as u8, commit], chunk)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn latest_settings_win_per_profile() {
+        let messages = vec![
+            Message::new(0.0, MessageKind::Response, &[cmd::GET_PROFILE, 1]),
+            frame(cmd::SET_DEBOUNCE, &[5], &[]),
+            frame(cmd::SET_DEBOUNCE, &[2], &[]),
+            frame(cmd::SET_REPORT, &[0, 0], &[]),
+            frame(cmd::SET_PROFILE, &[2], &[]),
+            // Caps -> Esc on layer 0 of profile 2
+            frame(cmd::SET_KEYMATRIX, &[2, 3, 0, 0, 1, 0], &[0, 0, 0x29, 0]),
+        ];
+        let ex = extract_messages(&messages, KEYS);
+        assert!(ex.notes.is_empty(), "{:?}", ex.notes);
+        assert_eq!(ex.profiles[&1].debounce, Some(2));
+        assert_eq!(ex.profiles[&1].polling_rate.map(|r| r.to_hz()), Some(8000));
+        let remaps: Vec<_> = ex.profiles[&2].keymap.as_ref().unwrap().remaps().collect();
+        assert_eq!(remaps.len(), 1);
+        assert_eq!(remaps[0].index, 3);
+
+        let backup = ProfileBackup::from_toml(&ex.to_toml("x.pcapng").unwrap()).unwrap();
+        assert_eq!(backup.profiles.len(), 2);
+    }
+
+    #[test]
+    fn reassembles_macros_and_bulk_triggers() {
+        let mut messages = vec![Message::new(
+            0.0,
+            MessageKind::Response,
+            &[cmd::GET_FEATURE_LIST, 0xAA, 1],
+        )];
+        // "a" down/up with 10ms delays, once
+        let data = [1, 0, 0x04, 0x80 | 10, 0x04, 10];
+        messages.push(frame(cmd::SET_MACRO, &[0, 0, 56, 1], &data));
+        messages.extend(bulk_u16(mag_cmd::PRESS_TRAVEL, 120));
+        // A single key's release point can't go into a config
+        messages.push(frame(
+            cmd::SET_MULTI_MAGNETISM,
+            &[mag_cmd::LIFT_TRAVEL, 0, 4, 1],
+            &50u16.to_le_bytes(),
+        ));
+        let ex = extract_messages(&messages, KEYS);
+        let state = &ex.profiles[&0];
+        assert_eq!(state.macros[&0].repeat, 1);
+        assert_eq!(state.macros[&0].events, vec![(4, true, 10), (4, false, 10)]);
+        let triggers = state.triggers.as_ref().unwrap();
+        assert_eq!(triggers.press_travel, vec![120; KEYS]);
+        assert!(triggers.lift_travel.is_empty());
+        assert_eq!(ex.notes.len(), 2, "{:?}", ex.notes);
+        assert!(ex.notes[0].contains("release for 1 key"));
+
+        let config = KeyboardConfig::from_toml(&ex.to_toml("x.pcapng").unwrap()).unwrap();
+        let expected = state.precision.raw_to_mm(120);
+        assert_eq!(config.triggers.actuation_mm, Some(expected));
+        assert_eq!(config.triggers.rapid_trigger, None);
+        assert_eq!(config.macros.len(), 1);
+    }
+
+    #[test]
+    fn userpic_pages_fill_the_slot() {
+        let messages = vec![
+            frame(cmd::SET_USERPIC, &[3, 0xFF, 0, 56, 0], &[0x11; 56]),
+            frame(cmd::SET_USERPIC, &[3, 0xFF, 1, 56, 0], &[0x22; 56]),
+            frame(cmd::SET_USERGIF, &[0, 1], &[]),
+        ];
+        let ex = extract_messages(&messages, KEYS);
+        let pic = &ex.userpics[&3];
+        assert_eq!(pic.len(), USERPIC_SIZE);
+        assert_eq!((pic[0], pic[56], pic[112]), (0x11, 0x22, 0));
+        assert!(ex.profiles.is_empty());
+        assert!(ex.notes[0].contains("SET_USERGIF"));
+    }
+}
@@ -0,0 +1,177 @@
+//! Parser regression fixtures from a capture (`iot_driver pcap fixtures`).
+//!
+//! Every distinct vendor command and response in a capture becomes a row of
+//! hex bytes plus what `try_parse_command` / `try_parse_response` make of it
+//! today, written out as a Rust integration test for `monsgeek-transport`.
+//! The parsed values don't implement `PartialEq`, so rows pin their `Debug`
+//! output; a parser change that alters a decode shows up as a failing row
+//! naming the packet.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use monsgeek_transport::protocol::{cmd, INPUT_REPORT_SIZE};
+use monsgeek_transport::{try_parse_command, try_parse_response};
+
+use super::diff::{Message, MessageKind};
+
+/// One captured packet and its expected decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    pub kind: MessageKind,
+    /// From the command byte, trailing zeros trimmed
+    pub data: Vec<u8>,
+    /// `Debug` output of the parse
+    pub expected: String,
+}
+
+/// Packets are parsed as the full report, zero-padded, the way the
+/// transport hands them over. The generated test pads the same way.
+fn padded(data: &[u8]) -> Vec<u8> {
+    let mut full = data.to_vec();
+    full.resize(full.len().max(INPUT_REPORT_SIZE), 0);
+    full
+}
+
+/// The decode a fixture pins.
+pub fn decode(kind: MessageKind, data: &[u8]) -> String {
+    let full = padded(data);
+    match kind {
+        MessageKind::Command => format!("{:?}", try_parse_command(&full)),
+        MessageKind::Response => format!("{:?}", try_parse_response(&full)),
+    }
+}
+
+/// Distinct packets in capture order, at most `max_per_cmd` per command
+/// byte and direction (0 = no limit) so LED streaming doesn't drown the rest.
+pub fn collect(messages: &[Message], max_per_cmd: usize) -> Vec<Fixture> {
+    let mut seen = BTreeSet::new();
+    let mut per_cmd: BTreeMap<(bool, u8), usize> = BTreeMap::new();
+    let mut out = Vec::new();
+    for m in messages {
+        let is_cmd = m.kind == MessageKind::Command;
+        if !seen.insert((is_cmd, m.data.clone())) {
+            continue;
+        }
+        let n = per_cmd.entry((is_cmd, m.cmd())).or_default();
+        if max_per_cmd > 0 && *n >= max_per_cmd {
+            continue;
+        }
+        *n += 1;
+        out.push(Fixture {
+            kind: m.kind,
+            data: m.data.clone(),
+            expected: decode(m.kind, &m.data),
+        });
+    }
+    out
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Render as a test file for `monsgeek-transport/tests/`.
+pub fn render(fixtures: &[Fixture], source: &str) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "//! Parser fixtures from {source}, generated by `iot_driver pcap fixtures`.\n\
+         //!\n\
+         //! Each row is a captured packet (from the command byte, trailing zeros\n\
+         //! trimmed) and the `Debug` output of its parse at generation time.\n\
+         //! Regenerate rather than edit when a decode changes on purpose.\n\
+         \n\
+         use monsgeek_transport::{{try_parse_command, try_parse_response}};\n\
+         \n\
+         /// Full report size the parsers see; rows are zero-padded to it.\n\
+         const REPORT: usize = {INPUT_REPORT_SIZE};\n\
+         \n\
+         fn bytes(hex: &str) -> Vec<u8> {{\n    \
+             let mut data: Vec<u8> = hex\n        \
+                 .split_whitespace()\n        \
+                 .map(|b| u8::from_str_radix(b, 16).unwrap())\n        \
+                 .collect();\n    \
+             data.resize(data.len().max(REPORT), 0);\n    \
+             data\n\
+         }}"
+    )
+    .ok();
+    for (kind, name, parse) in [
+        (MessageKind::Command, "COMMANDS", "try_parse_command"),
+        (MessageKind::Response, "RESPONSES", "try_parse_response"),
+    ] {
+        let rows: Vec<_> = fixtures.iter().filter(|f| f.kind == kind).collect();
+        writeln!(
+            out,
+            "\n#[rustfmt::skip]\nconst {name}: &[(&str, &str)] = &["
+        )
+        .ok();
+        for f in &rows {
+            writeln!(out, "    // 0x{:02x} {}", f.data[0], cmd::name(f.data[0])).ok();
+            writeln!(out, "    ({:?}, {:?}),", hex(&f.data), f.expected).ok();
+        }
+        writeln!(out, "];").ok();
+        let test = name.to_lowercase();
+        writeln!(
+            out,
+            "\n#[test]\n\
+             fn captured_{test}_parse_as_recorded() {{\n    \
+                 for (hex, expected) in {name} {{\n        \
+                     let parsed = format!(\"{{:?}}\", {parse}(&bytes(hex)));\n        \
+                     assert_eq!(parsed, *expected, \"packet {{hex}}\");\n    \
+                 }}\n\
+             }}"
+        )
+        .ok();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(data: &[u8]) -> Message {
+        Message::new(0.0, MessageKind::Command, data)
+    }
+
+    #[test]
+    fn keeps_distinct_packets_up_to_the_limit() {
+        let messages = vec![
+            c(&[cmd::SET_DEBOUNCE, 2]),
+            c(&[cmd::SET_DEBOUNCE, 2]),
+            c(&[cmd::SET_DEBOUNCE, 3]),
+            c(&[cmd::SET_DEBOUNCE, 4]),
+            Message::new(0.0, MessageKind::Response, &[cmd::SET_DEBOUNCE, 2]),
+        ];
+        let fixtures = collect(&messages, 2);
+        let data: Vec<_> = fixtures.iter().map(|f| (f.kind, f.data.clone())).collect();
+        assert_eq!(
+            data,
+            vec![
+                (MessageKind::Command, vec![cmd::SET_DEBOUNCE, 2]),
+                (MessageKind::Command, vec![cmd::SET_DEBOUNCE, 3]),
+                (MessageKind::Response, vec![cmd::SET_DEBOUNCE, 2]),
+            ]
+        );
+        assert_eq!(collect(&messages, 0).len(), 4);
+    }
+
+    #[test]
+    fn rows_hold_the_current_decode_as_a_literal() {
+        let fixtures = collect(&[c(&[cmd::SET_LEDPARAM, 1, 2, 4, 0, 255, 0, 0])], 0);
+        let expected = &fixtures[0].expected;
+        assert!(expected.starts_with("SetLedParams"), "{expected}");
+        assert_eq!(*expected, decode(MessageKind::Command, &fixtures[0].data));
+
+        let file = render(&fixtures, "capture.pcapng");
+        assert!(file.contains("(\"07 01 02 04 00 ff\", \""));
+        assert!(file.contains(&format!("{:?}", expected)));
+        assert!(file.contains("fn captured_commands_parse_as_recorded()"));
+        assert!(file.contains("const RESPONSES: &[(&str, &str)] = &[\n];"));
+    }
+}
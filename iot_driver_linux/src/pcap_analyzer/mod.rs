@@ -12,6 +12,12 @@
 //! analyzer.analyze_file("capture.pcapng")?;
 //! ```
 
+pub mod diff;
+pub mod dissector;
+pub mod extract;
+pub mod fixtures;
+pub mod replay;
+pub mod stats;
 mod usb_urb;
 
 // Re-export from monsgeek_transport for convenience
@@ -120,81 +126,16 @@ impl PcapAnalyzer {
 
     /// Analyze a pcapng file and print decoded packets
     pub fn analyze_file(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-        let file = File::open(path)?;
-        let mut reader = create_reader(65536, file)?;
-        let mut base_timestamp: Option<(u32, u32)> = None;
-        let mut packet_count = 0u64;
         let mut decoded_count = 0u64;
-        let mut last_incomplete_index = 0u64;
 
         // Statistics for verbose mode
         let mut stats = PacketStats::default();
 
-        loop {
-            // Extract data we need from the block before calling consume/refill
-            // to avoid lifetime issues with borrowed data
-            let result = reader.next();
-
-            match result {
-                Ok((offset, block)) => {
-                    // Extract packet info before calling consume
-                    let packet_info: Option<(u32, u32, Vec<u8>)> = match &block {
-                        // pcapng format: EnhancedPacket blocks
-                        PcapBlockOwned::NG(Block::EnhancedPacket(epb)) => {
-                            Some((epb.ts_high, epb.ts_low, epb.data.to_vec()))
-                        }
-                        // Legacy pcap format
-                        PcapBlockOwned::Legacy(lp) => {
-                            Some((lp.ts_sec, lp.ts_usec, lp.data.to_vec()))
-                        }
-                        // Skip other block types
-                        _ => None,
-                    };
-
-                    // Now we can consume since we've copied what we need
-                    reader.consume(offset);
-
-                    // Process the extracted data
-                    if let Some((ts_high, ts_low, data)) = packet_info {
-                        let ts = if let Some((base_high, base_low)) = base_timestamp {
-                            // For pcapng: ts_high/ts_low are a 64-bit timestamp
-                            // For legacy pcap: ts_sec/ts_usec
-                            let base_ts = ((base_high as u64) << 32) | (base_low as u64);
-                            let curr_ts = ((ts_high as u64) << 32) | (ts_low as u64);
-                            (curr_ts.saturating_sub(base_ts)) as f64 / 1_000_000.0
-                        } else {
-                            base_timestamp = Some((ts_high, ts_low));
-                            0.0
-                        };
-
-                        packet_count += 1;
-                        if self.process_packet_with_stats(ts, &data, &mut stats) {
-                            decoded_count += 1;
-                        }
-                    }
-                }
-                Err(PcapError::Eof) => break,
-                Err(PcapError::Incomplete(_)) => {
-                    // Need more data, try to refill buffer
-                    // Track last incomplete to avoid infinite loops on truncated files
-                    if last_incomplete_index == packet_count {
-                        eprintln!(
-                            "Warning: Could not read complete data block (file may be truncated)"
-                        );
-                        break;
-                    }
-                    last_incomplete_index = packet_count;
-                    // Map the error to avoid lifetime issues with PcapError<&[u8]>
-                    reader
-                        .refill()
-                        .map_err(|e| format!("Refill error: {:?}", e))?;
-                    continue;
-                }
-                Err(e) => {
-                    return Err(format!("PCAP parse error: {:?}", e).into());
-                }
+        let packet_count = for_each_packet(path, |ts, data| {
+            if self.process_packet_with_stats(ts, data, &mut stats) {
+                decoded_count += 1;
             }
-        }
+        })?;
 
         eprintln!(
             "\n--- Analyzed {} packets, {} decoded ---",
@@ -393,6 +334,84 @@ impl PcapAnalyzer {
     }
 }
 
+/// Call `on_packet` with the timestamp (seconds since the first packet) and
+/// raw data of every packet in a pcap or pcapng file. Returns the number of
+/// packets.
+pub fn for_each_packet(
+    path: &Path,
+    mut on_packet: impl FnMut(f64, &[u8]),
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let file = File::open(path)?;
+    let mut reader = create_reader(65536, file)?;
+    let mut base_timestamp: Option<(u32, u32)> = None;
+    let mut packet_count = 0u64;
+    let mut last_incomplete_index = 0u64;
+
+    loop {
+        // Extract data we need from the block before calling consume/refill
+        // to avoid lifetime issues with borrowed data
+        let result = reader.next();
+
+        match result {
+            Ok((offset, block)) => {
+                // Extract packet info before calling consume
+                let packet_info: Option<(u32, u32, Vec<u8>)> = match &block {
+                    // pcapng format: EnhancedPacket blocks
+                    PcapBlockOwned::NG(Block::EnhancedPacket(epb)) => {
+                        Some((epb.ts_high, epb.ts_low, epb.data.to_vec()))
+                    }
+                    // Legacy pcap format
+                    PcapBlockOwned::Legacy(lp) => Some((lp.ts_sec, lp.ts_usec, lp.data.to_vec())),
+                    // Skip other block types
+                    _ => None,
+                };
+
+                // Now we can consume since we've copied what we need
+                reader.consume(offset);
+
+                // Process the extracted data
+                if let Some((ts_high, ts_low, data)) = packet_info {
+                    let ts = if let Some((base_high, base_low)) = base_timestamp {
+                        // For pcapng: ts_high/ts_low are a 64-bit timestamp
+                        // For legacy pcap: ts_sec/ts_usec
+                        let base_ts = ((base_high as u64) << 32) | (base_low as u64);
+                        let curr_ts = ((ts_high as u64) << 32) | (ts_low as u64);
+                        (curr_ts.saturating_sub(base_ts)) as f64 / 1_000_000.0
+                    } else {
+                        base_timestamp = Some((ts_high, ts_low));
+                        0.0
+                    };
+
+                    packet_count += 1;
+                    on_packet(ts, &data);
+                }
+            }
+            Err(PcapError::Eof) => break,
+            Err(PcapError::Incomplete(_)) => {
+                // Need more data, try to refill buffer
+                // Track last incomplete to avoid infinite loops on truncated files
+                if last_incomplete_index == packet_count {
+                    eprintln!(
+                        "Warning: Could not read complete data block (file may be truncated)"
+                    );
+                    break;
+                }
+                last_incomplete_index = packet_count;
+                // Map the error to avoid lifetime issues with PcapError<&[u8]>
+                reader
+                    .refill()
+                    .map_err(|e| format!("Refill error: {:?}", e))?;
+                continue;
+            }
+            Err(e) => {
+                return Err(format!("PCAP parse error: {:?}", e).into());
+            }
+        }
+    }
+
+    Ok(packet_count)
+}
+
 /// CLI entry point for pcap analysis
 pub fn run_pcap_analysis(
     path: &Path,
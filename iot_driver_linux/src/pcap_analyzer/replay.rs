@@ -0,0 +1,220 @@
+//! Re-send a capture's command stream to a live device (`iot_driver pcap replay`).
+//!
+//! Each captured command is rebuilt through our own framing: the checksum
+//! byte is dropped and recomputed by `build_command`. A command counts as
+//! reproduced when one of our checksum types gives back the captured bytes
+//! exactly. Replies are compared with the reply the capture recorded for the
+//! same command.
+//!
+//! Commands that touch firmware or RF programming are never replayed, and
+//! nothing after them is either, since the rest of such a capture is
+//! bootloader traffic.
+
+use monsgeek_transport::protocol::{build_command, cmd, INPUT_REPORT_SIZE};
+use monsgeek_transport::ChecksumType;
+
+use crate::command_probe;
+
+use super::diff::{Message, MessageKind};
+
+/// Whether a command changes settings (gated behind confirmation).
+pub fn is_write(cmd_byte: u8) -> bool {
+    cmd_byte & 0x80 == 0
+}
+
+/// Commands that end the replay: everything after them is firmware traffic.
+fn ends_replay(cmd_byte: u8) -> bool {
+    matches!(
+        cmd_byte,
+        cmd::ENTER_BOOTLOADER | cmd::ISP_PREPARE | cmd::FW_TRANSFER | cmd::ENTER_PAIRING
+    )
+}
+
+/// How a captured command maps onto our framing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// `build_command` with this checksum reproduces the captured bytes
+    Checksum(ChecksumType),
+    /// No checksum of ours matches; the bytes are sent as captured
+    Verbatim,
+}
+
+impl Framing {
+    pub fn checksum(self) -> ChecksumType {
+        match self {
+            Framing::Checksum(c) => c,
+            Framing::Verbatim => ChecksumType::None,
+        }
+    }
+}
+
+/// Trailing-zero-trimmed report, the way captures are stored.
+fn trim(data: &[u8]) -> &[u8] {
+    let end = data.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    &data[..end]
+}
+
+/// Find the checksum type that rebuilds `captured` (from the command byte)
+/// and the payload to hand the transport for it.
+pub fn framing(captured: &[u8]) -> (Framing, Vec<u8>) {
+    let Some((&cmd_byte, rest)) = captured.split_first() else {
+        return (Framing::Verbatim, Vec::new());
+    };
+    for (checksum, at) in [(ChecksumType::Bit7, 6), (ChecksumType::Bit8, 7)] {
+        let mut data = rest.to_vec();
+        data.resize(data.len().max(INPUT_REPORT_SIZE - 1), 0);
+        data[at] = 0;
+        let built = build_command(cmd_byte, &data, checksum);
+        if trim(&built[1..]) == trim(captured) {
+            let len = trim(&data).len();
+            data.truncate(len);
+            return (Framing::Checksum(checksum), data);
+        }
+    }
+    (Framing::Verbatim, rest.to_vec())
+}
+
+/// One command to re-send.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayStep {
+    /// Seconds since the capture's first packet
+    pub ts: f64,
+    /// Captured bytes from the command byte, trailing zeros trimmed
+    pub command: Vec<u8>,
+    /// Captured reply, if one came before the next command
+    pub expected: Option<Vec<u8>>,
+    pub framing: Framing,
+    /// Payload after the command byte, checksum removed
+    pub payload: Vec<u8>,
+}
+
+impl ReplayStep {
+    pub fn cmd(&self) -> u8 {
+        self.command[0]
+    }
+}
+
+/// What a capture replays as.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayPlan {
+    pub steps: Vec<ReplayStep>,
+    /// Blocked command bytes left out, with the reason
+    pub blocked: Vec<(u8, &'static str)>,
+    /// Set when the capture goes on into firmware traffic at this command
+    pub stopped_at: Option<u8>,
+}
+
+/// Turn a capture's vendor messages into replay steps.
+///
+/// GET_CACHED_RESPONSE is dropped because the dongle transport sends its own
+/// flush; other blocked GET-range bytes are listed in `blocked`.
+pub fn plan(messages: &[Message]) -> ReplayPlan {
+    let mut plan = ReplayPlan::default();
+    for m in messages {
+        match m.kind {
+            MessageKind::Response => {
+                if let Some(step) = plan.steps.last_mut() {
+                    step.expected.get_or_insert_with(|| m.data.clone());
+                }
+            }
+            MessageKind::Command => {
+                let byte = m.cmd();
+                if ends_replay(byte) {
+                    plan.stopped_at = Some(byte);
+                    break;
+                }
+                if byte == cmd::GET_CACHED_RESPONSE {
+                    continue;
+                }
+                if let Some((_, why)) = command_probe::BLOCKLIST.iter().find(|(b, _)| *b == byte) {
+                    plan.blocked.push((byte, why));
+                    continue;
+                }
+                let (framing, payload) = framing(&m.data);
+                plan.steps.push(ReplayStep {
+                    ts: m.ts,
+                    command: m.data.clone(),
+                    expected: None,
+                    framing,
+                    payload,
+                });
+            }
+        }
+    }
+    plan
+}
+
+/// Offsets where two trimmed reports differ (the shorter one zero-extended).
+pub fn differing_offsets(a: &[u8], b: &[u8]) -> Vec<usize> {
+    (0..a.len().max(b.len()))
+        .filter(|&i| a.get(i).copied().unwrap_or(0) != b.get(i).copied().unwrap_or(0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(data: &[u8]) -> Message {
+        Message::new(0.0, MessageKind::Command, data)
+    }
+
+    fn r(data: &[u8]) -> Message {
+        Message::new(0.0, MessageKind::Response, data)
+    }
+
+    #[test]
+    fn framing_recovers_our_checksum() {
+        let bit7 = build_command(cmd::SET_DEBOUNCE, &[3], ChecksumType::Bit7);
+        let (f, payload) = framing(trim(&bit7[1..]));
+        assert_eq!(f, Framing::Checksum(ChecksumType::Bit7));
+        assert_eq!(payload, vec![3]);
+
+        let bit8 = build_command(
+            cmd::SET_LEDPARAM,
+            &[1, 2, 4, 0, 255, 0, 0],
+            ChecksumType::Bit8,
+        );
+        let (f, payload) = framing(trim(&bit8[1..]));
+        assert_eq!(f, Framing::Checksum(ChecksumType::Bit8));
+        // Trailing zeros are padding on the wire and come back trimmed
+        assert_eq!(payload, vec![1, 2, 4, 0, 255]);
+
+        // Wrong checksum byte: nothing of ours reproduces it
+        let (f, payload) = framing(&[cmd::SET_DEBOUNCE, 3, 0, 0, 0, 0, 0, 0x42]);
+        assert_eq!(f, Framing::Verbatim);
+        assert_eq!(payload, vec![3, 0, 0, 0, 0, 0, 0x42]);
+    }
+
+    #[test]
+    fn plan_pairs_replies_and_stops_at_firmware_traffic() {
+        let messages = vec![
+            c(&[cmd::GET_USB_VERSION]),
+            c(&[cmd::GET_CACHED_RESPONSE]),
+            r(&[cmd::GET_USB_VERSION, 1, 2]),
+            r(&[cmd::GET_USB_VERSION, 9]),
+            c(&[cmd::LED_STREAM, 0]),
+            c(&[cmd::SET_DEBOUNCE, 3]),
+            c(&[cmd::ISP_PREPARE, 0x3a]),
+            c(&[cmd::SET_PROFILE, 1]),
+        ];
+        let plan = plan(&messages);
+        let cmds: Vec<_> = plan.steps.iter().map(|s| s.cmd()).collect();
+        assert_eq!(cmds, vec![cmd::GET_USB_VERSION, cmd::SET_DEBOUNCE]);
+        assert_eq!(
+            plan.steps[0].expected,
+            Some(vec![cmd::GET_USB_VERSION, 1, 2])
+        );
+        assert_eq!(plan.steps[1].expected, None);
+        assert_eq!(plan.blocked.len(), 1);
+        assert_eq!(plan.stopped_at, Some(cmd::ISP_PREPARE));
+        assert!(is_write(cmd::SET_DEBOUNCE));
+        assert!(!is_write(cmd::GET_USB_VERSION));
+    }
+
+    #[test]
+    fn offsets_ignore_trailing_padding() {
+        assert_eq!(differing_offsets(&[1, 2, 3], &[1, 9, 3, 0]), vec![1]);
+        assert_eq!(differing_offsets(&[1, 2], &[1, 2, 5]), vec![2]);
+    }
+}
@@ -0,0 +1,449 @@
+//! Statistics and timeline of a capture's vendor traffic (`iot_driver pcap stats`).
+//!
+//! Pairs each command with the response that follows it, then reports per
+//! command: how often it was sent, how long the keyboard took to answer, and
+//! how long the driver waited since the previous command. Commands that are
+//! answered elsewhere in the capture but not this time count as timeouts, and
+//! an identical command sent after such a timeout as a retry. The timeline
+//! groups commands into bursts separated by idle gaps, which shows the
+//! official driver's pacing at a glance.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crossterm::style::Stylize;
+use monsgeek_transport::protocol::cmd;
+use serde::Serialize;
+
+use super::diff::{Message, MessageKind};
+
+/// Width of the longest histogram bar.
+const BAR_WIDTH: usize = 40;
+
+/// One command and what came of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exchange {
+    pub ts: f64,
+    pub cmd: u8,
+    pub data: Vec<u8>,
+    /// Seconds until the response, if one came before the next command
+    pub latency: Option<f64>,
+    /// Seconds since the previous command
+    pub gap: Option<f64>,
+}
+
+/// Pair commands with the first response before the next command.
+/// Responses without a pending command are ignored.
+pub fn exchanges(messages: &[Message]) -> Vec<Exchange> {
+    let mut out: Vec<Exchange> = Vec::new();
+    for m in messages {
+        match m.kind {
+            MessageKind::Command => {
+                let gap = out.last().map(|prev| m.ts - prev.ts);
+                out.push(Exchange {
+                    ts: m.ts,
+                    cmd: m.cmd(),
+                    data: m.data.clone(),
+                    latency: None,
+                    gap,
+                });
+            }
+            MessageKind::Response => {
+                if let Some(last) = out.last_mut().filter(|e| e.latency.is_none()) {
+                    last.latency = Some(m.ts - last.ts);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Summary of a set of durations, in milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Distribution {
+    pub count: usize,
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Distribution {
+    /// From durations in seconds; `None` when there are none.
+    pub fn from_seconds(samples: impl IntoIterator<Item = f64>) -> Option<Self> {
+        let mut ms: Vec<f64> = samples.into_iter().map(|s| s * 1000.0).collect();
+        if ms.is_empty() {
+            return None;
+        }
+        ms.sort_by(f64::total_cmp);
+        let at = |q: f64| ms[((ms.len() - 1) as f64 * q).round() as usize];
+        Some(Self {
+            count: ms.len(),
+            min_ms: ms[0],
+            median_ms: at(0.5),
+            p95_ms: at(0.95),
+            max_ms: ms[ms.len() - 1],
+        })
+    }
+}
+
+/// Everything about one command byte.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommandStats {
+    pub cmd: u8,
+    pub name: &'static str,
+    pub count: usize,
+    pub answered: usize,
+    pub timeouts: usize,
+    pub retries: usize,
+    /// Command to response
+    pub latency: Option<Distribution>,
+    /// Previous command to this one
+    pub gap: Option<Distribution>,
+}
+
+/// What went wrong with a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IncidentKind {
+    /// No response, though the command is answered elsewhere in the capture
+    Timeout,
+    /// The same bytes again right after a timeout
+    Retry,
+}
+
+/// Something that went wrong, for the event list.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Incident {
+    pub ts: f64,
+    pub cmd: u8,
+    pub kind: IncidentKind,
+}
+
+/// Commands close together in time.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Burst {
+    pub start: f64,
+    pub end: f64,
+    /// Idle time before the burst (none for the first)
+    pub idle_before: Option<f64>,
+    /// Command counts, in order of first appearance
+    pub commands: Vec<(u8, usize)>,
+}
+
+impl Burst {
+    pub fn len(&self) -> usize {
+        self.commands.iter().map(|&(_, n)| n).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    /// Seconds from the first to the last vendor packet
+    pub duration: f64,
+    pub commands: usize,
+    pub responses: usize,
+    /// Most frequent first
+    pub per_command: Vec<CommandStats>,
+    pub incidents: Vec<Incident>,
+    pub bursts: Vec<Burst>,
+}
+
+/// Build the report; commands further apart than `burst_gap` seconds start
+/// a new burst.
+pub fn report(messages: &[Message], burst_gap: f64) -> Report {
+    let ex = exchanges(messages);
+
+    // A command is expected to be answered if any instance of it was
+    let answered_cmds: Vec<u8> = ex
+        .iter()
+        .filter(|e| e.latency.is_some())
+        .map(|e| e.cmd)
+        .collect();
+    let mut incidents = Vec::new();
+    let mut timed_out = vec![false; ex.len()];
+    for (i, e) in ex.iter().enumerate() {
+        if e.latency.is_none() && answered_cmds.contains(&e.cmd) {
+            timed_out[i] = true;
+            incidents.push(Incident {
+                ts: e.ts,
+                cmd: e.cmd,
+                kind: IncidentKind::Timeout,
+            });
+        }
+        if i > 0 && timed_out[i - 1] && ex[i - 1].data == e.data {
+            incidents.push(Incident {
+                ts: e.ts,
+                cmd: e.cmd,
+                kind: IncidentKind::Retry,
+            });
+        }
+    }
+
+    let mut by_cmd: BTreeMap<u8, Vec<&Exchange>> = BTreeMap::new();
+    for e in &ex {
+        by_cmd.entry(e.cmd).or_default().push(e);
+    }
+    let count = |cmd: u8, kind: IncidentKind| {
+        incidents
+            .iter()
+            .filter(|i| i.cmd == cmd && i.kind == kind)
+            .count()
+    };
+    let mut per_command: Vec<CommandStats> = by_cmd
+        .into_iter()
+        .map(|(c, list)| CommandStats {
+            cmd: c,
+            name: cmd::name(c),
+            count: list.len(),
+            answered: list.iter().filter(|e| e.latency.is_some()).count(),
+            timeouts: count(c, IncidentKind::Timeout),
+            retries: count(c, IncidentKind::Retry),
+            latency: Distribution::from_seconds(list.iter().filter_map(|e| e.latency)),
+            gap: Distribution::from_seconds(list.iter().filter_map(|e| e.gap)),
+        })
+        .collect();
+    per_command.sort_by(|a, b| b.count.cmp(&a.count).then(a.cmd.cmp(&b.cmd)));
+
+    let mut bursts: Vec<Burst> = Vec::new();
+    for e in &ex {
+        match bursts.last_mut() {
+            Some(b) if e.ts - b.end <= burst_gap => {
+                b.end = e.ts;
+                match b.commands.iter_mut().find(|(c, _)| *c == e.cmd) {
+                    Some((_, n)) => *n += 1,
+                    None => b.commands.push((e.cmd, 1)),
+                }
+            }
+            last => {
+                let idle_before = last.map(|b| e.ts - b.end);
+                bursts.push(Burst {
+                    start: e.ts,
+                    end: e.ts,
+                    idle_before,
+                    commands: vec![(e.cmd, 1)],
+                });
+            }
+        }
+    }
+
+    let duration = match (messages.first(), messages.last()) {
+        (Some(first), Some(last)) => last.ts - first.ts,
+        _ => 0.0,
+    };
+    Report {
+        duration,
+        commands: ex.len(),
+        responses: messages
+            .iter()
+            .filter(|m| m.kind == MessageKind::Response)
+            .count(),
+        per_command,
+        incidents,
+        bursts,
+    }
+}
+
+fn dist(d: &Option<Distribution>) -> String {
+    match d {
+        Some(d) => format!(
+            "{:>7.1} {:>7.1} {:>7.1} {:>7.1}",
+            d.min_ms, d.median_ms, d.p95_ms, d.max_ms
+        ),
+        None => format!("{:>7} {:>7} {:>7} {:>7}", "-", "-", "-", "-"),
+    }
+}
+
+/// Render as text: histogram, timing table, incidents and, with
+/// `timeline`, one line per burst.
+pub fn render(report: &Report, timeline: bool) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{} commands, {} responses over {:.3}s",
+        report.commands, report.responses, report.duration
+    )
+    .ok();
+
+    writeln!(out, "\n{}", "Commands".bold()).ok();
+    let max = report.per_command.first().map_or(1, |c| c.count.max(1));
+    for c in &report.per_command {
+        let bar = "#".repeat((c.count * BAR_WIDTH).div_ceil(max));
+        writeln!(
+            out,
+            "  0x{:02x} {:<22} {:>6}  {}",
+            c.cmd,
+            c.name,
+            c.count,
+            bar.cyan()
+        )
+        .ok();
+    }
+
+    writeln!(out, "\n{}", "Timing (ms)".bold()).ok();
+    writeln!(
+        out,
+        "  {:<27} {:^31}   {:^31}",
+        "", "response: min  median  p95  max", "gap: min  median  p95  max"
+    )
+    .ok();
+    for c in &report.per_command {
+        writeln!(
+            out,
+            "  0x{:02x} {:<22} {}   {}",
+            c.cmd,
+            c.name,
+            dist(&c.latency),
+            dist(&c.gap)
+        )
+        .ok();
+    }
+
+    let (timeouts, retries) = report
+        .incidents
+        .iter()
+        .fold((0, 0), |(t, r), i| match i.kind {
+            IncidentKind::Timeout => (t + 1, r),
+            IncidentKind::Retry => (t, r + 1),
+        });
+    writeln!(
+        out,
+        "\n{} ({timeouts} timeouts, {retries} retries)",
+        "Incidents".bold()
+    )
+    .ok();
+    for i in &report.incidents {
+        let kind = match i.kind {
+            IncidentKind::Timeout => "timeout".yellow(),
+            IncidentKind::Retry => "retry".red(),
+        };
+        writeln!(
+            out,
+            "  {:>10.3}s  {kind:<7}  0x{:02x} {}",
+            i.ts,
+            i.cmd,
+            cmd::name(i.cmd)
+        )
+        .ok();
+    }
+
+    if timeline {
+        writeln!(
+            out,
+            "\n{} ({} bursts)",
+            "Timeline".bold(),
+            report.bursts.len()
+        )
+        .ok();
+        for b in &report.bursts {
+            if let Some(idle) = b.idle_before {
+                writeln!(out, "{}", format!("  {:>10}   idle {idle:.3}s", "").dim()).ok();
+            }
+            let cmds: Vec<String> = b
+                .commands
+                .iter()
+                .map(|&(c, n)| match n {
+                    1 => cmd::name(c).to_string(),
+                    n => format!("{}×{n}", cmd::name(c)),
+                })
+                .collect();
+            writeln!(
+                out,
+                "  {:>10.3}s  {:>4} cmds in {:>8.1}ms  {}",
+                b.start,
+                b.len(),
+                (b.end - b.start) * 1000.0,
+                cmds.join(" ")
+            )
+            .ok();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(ts: f64, data: &[u8]) -> Message {
+        Message::new(ts, MessageKind::Command, data)
+    }
+
+    fn r(ts: f64, data: &[u8]) -> Message {
+        Message::new(ts, MessageKind::Response, data)
+    }
+
+    #[test]
+    fn pairs_commands_with_the_next_response() {
+        let ex = exchanges(&[
+            r(0.0, &[0x8f]), // nothing pending
+            c(1.0, &[0x8f]),
+            r(1.002, &[0x8f, 1]),
+            r(1.003, &[0x8f, 1]), // already answered
+            c(1.010, &[0x07, 1]),
+            c(1.040, &[0x07, 2]),
+        ]);
+        assert_eq!(ex.len(), 3);
+        assert!((ex[0].latency.unwrap() - 0.002).abs() < 1e-9);
+        assert_eq!(ex[0].gap, None);
+        assert_eq!(ex[1].latency, None);
+        assert!((ex[2].gap.unwrap() - 0.030).abs() < 1e-9);
+    }
+
+    #[test]
+    fn unanswered_queries_are_timeouts_and_resends_retries() {
+        let report = report(
+            &[
+                c(0.0, &[0x8f]),
+                r(0.001, &[0x8f, 1]),
+                c(0.1, &[0x8f]),
+                c(0.3, &[0x8f]),
+                r(0.302, &[0x8f, 1]),
+                // Never answered anywhere: not a timeout
+                c(0.4, &[0x07, 1]),
+                c(0.5, &[0x07, 1]),
+            ],
+            0.05,
+        );
+        let kinds: Vec<_> = report.incidents.iter().map(|i| (i.ts, i.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![(0.1, IncidentKind::Timeout), (0.3, IncidentKind::Retry)]
+        );
+        let query = &report.per_command[0];
+        assert_eq!(query.cmd, 0x8f);
+        assert_eq!((query.count, query.answered), (3, 2));
+        assert_eq!((query.timeouts, query.retries), (1, 1));
+        assert_eq!(report.responses, 2);
+        assert!((report.duration - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bursts_split_on_idle_gaps() {
+        let report = report(
+            &[
+                c(0.0, &[0x65, 0]),
+                c(0.03, &[0x65, 1]),
+                c(0.06, &[0x07]),
+                c(1.0, &[0x8f]),
+            ],
+            0.05,
+        );
+        assert_eq!(report.bursts.len(), 2);
+        assert_eq!(report.bursts[0].commands, vec![(0x65, 2), (0x07, 1)]);
+        assert_eq!(report.bursts[0].len(), 3);
+        assert!((report.bursts[1].idle_before.unwrap() - 0.94).abs() < 1e-9);
+    }
+
+    #[test]
+    fn distribution_percentiles() {
+        let d = Distribution::from_seconds((1..=20).map(|i| i as f64 / 1000.0)).unwrap();
+        assert_eq!((d.count, d.min_ms, d.max_ms), (20, 1.0, 20.0));
+        assert_eq!((d.median_ms, d.p95_ms), (11.0, 19.0));
+        assert!(Distribution::from_seconds([]).is_none());
+    }
+}
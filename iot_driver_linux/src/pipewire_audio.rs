@@ -0,0 +1,631 @@
+//! Native PipeWire audio capture + node enumeration.
+//!
+//! Talks to the PipeWire daemon directly (no pulse server in between). Requires
+//! the libpipewire dev package at build time (e.g. `libpipewire-0.3-dev` on
+//! Debian/Ubuntu), the same one screen capture uses.
+//!
+//! [`Capture`] records from a [`CaptureTarget`]: the default sink's monitor,
+//! a named source, or a single application's playback stream (linked on its
+//! own, so other apps on the same output are left out). The default monitor
+//! is left untargeted, so the session manager moves it along when the default
+//! output changes.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pipewire::context::ContextBox;
+use pipewire::main_loop::MainLoopBox;
+use pipewire::spa::param::audio::{AudioFormat, AudioInfoRaw};
+use pipewire::spa::pod::serialize::PodSerializer;
+use pipewire::spa::pod::{Object, Pod, Value};
+use pipewire::spa::utils::Direction;
+use pipewire::stream::{StreamBox, StreamFlags, StreamState};
+use pipewire::types::ObjectType;
+
+/// Fixed capture rate; the FFT path assumes mono f32 at this rate.
+pub const SAMPLE_RATE: u32 = 44100;
+
+const APP_NAME: &str = "iot_driver";
+const STREAM_NAME: &str = "audio-reactive";
+
+/// How long to wait for the daemon to list its nodes or accept a stream.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// A stream with nothing playing into it delivers no buffers; after this long
+/// [`Capture::read`] hands out silence instead of blocking.
+const SILENCE_AFTER: Duration = Duration::from_millis(100);
+/// Main loop poll interval, so stop requests are seen promptly.
+const ITERATE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A capture source (input device or sink monitor).
+#[derive(Clone, Debug)]
+pub struct SourceEntry {
+    pub name: String,
+    pub description: String,
+    pub is_monitor: bool,
+}
+
+impl SourceEntry {
+    /// Human label: description, a `[monitor]` tag, and the raw node name.
+    pub fn label(&self) -> String {
+        let tag = if self.is_monitor { " [monitor]" } else { "" };
+        format!("{}{} ({})", self.description, tag, self.name)
+    }
+}
+
+/// An audio node from the registry, with the properties used for matching.
+#[derive(Clone, Debug)]
+struct Node {
+    /// `object.serial`, else the global id
+    serial: u32,
+    media_class: String,
+    name: String,
+    description: Option<String>,
+    application: Option<String>,
+    binary: Option<String>,
+    media: Option<String>,
+}
+
+/// Run the main loop until `done` is set, failing after [`CONNECT_TIMEOUT`].
+fn iterate_until(main_loop: &MainLoopBox, done: &Cell<bool>) -> Result<(), String> {
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+    let loop_ = main_loop.loop_();
+    while !done.get() {
+        if Instant::now() >= deadline {
+            return Err("PipeWire did not answer".into());
+        }
+        if loop_.iterate(ITERATE_INTERVAL) < 0 {
+            return Err("PipeWire main loop error".into());
+        }
+    }
+    Ok(())
+}
+
+/// List every node the daemon knows about (one registry roundtrip).
+fn list_nodes() -> Result<Vec<Node>, String> {
+    let main_loop =
+        MainLoopBox::new(None).map_err(|e| format!("Failed to create main loop: {e:?}"))?;
+    let context = ContextBox::new(main_loop.loop_(), None)
+        .map_err(|e| format!("Failed to create context: {e:?}"))?;
+    let core = context
+        .connect(None)
+        .map_err(|e| format!("Failed to connect to PipeWire: {e:?}"))?;
+    let registry = core
+        .get_registry()
+        .map_err(|e| format!("Failed to get PipeWire registry: {e:?}"))?;
+
+    let nodes = Rc::new(RefCell::new(Vec::new()));
+    let collect = nodes.clone();
+    let _registry_listener = registry
+        .add_listener_local()
+        .global(move |global| {
+            if global.type_ != ObjectType::Node {
+                return;
+            }
+            let Some(props) = global.props else {
+                return;
+            };
+            let get = |key: &str| props.get(key).map(str::to_string);
+            collect.borrow_mut().push(Node {
+                serial: props
+                    .get("object.serial")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(global.id),
+                media_class: get("media.class").unwrap_or_default(),
+                name: get("node.name").unwrap_or_default(),
+                description: get("node.description"),
+                application: get("application.name"),
+                binary: get("application.process.binary"),
+                media: get("media.name"),
+            });
+        })
+        .register();
+
+    // The registry has announced every existing global once the core answers
+    // a sync issued after binding it.
+    let pending = core
+        .sync(0)
+        .map_err(|e| format!("PipeWire sync failed: {e:?}"))?;
+    let done = Rc::new(Cell::new(false));
+    let done_flag = done.clone();
+    let _core_listener = core
+        .add_listener_local()
+        .done(move |id, seq| {
+            if id == pipewire::core::PW_ID_CORE && seq == pending {
+                done_flag.set(true);
+            }
+        })
+        .register();
+    iterate_until(&main_loop, &done)?;
+
+    let collected = nodes.borrow().clone();
+    Ok(collected)
+}
+
+/// List all capture sources (inputs + sink monitors).
+pub fn list_sources() -> Result<Vec<SourceEntry>, String> {
+    Ok(list_nodes()?
+        .into_iter()
+        .filter_map(|node| {
+            let is_monitor = match node.media_class.as_str() {
+                "Audio/Sink" => true,
+                class if class.starts_with("Audio/Source") => false,
+                _ => return None,
+            };
+            Some(SourceEntry {
+                description: node.description.unwrap_or_else(|| node.name.clone()),
+                name: node.name,
+                is_monitor,
+            })
+        })
+        .collect())
+}
+
+/// Resolve which source to capture from.
+///
+/// Exact match on name or description, else case-insensitive substring;
+/// errors list candidates on no/ambiguous match.
+pub fn resolve_source(requested: &str) -> Result<SourceEntry, String> {
+    let sources = list_sources()?;
+    if sources.is_empty() {
+        return Err("No PipeWire capture sources found".into());
+    }
+
+    if let Some(s) = sources
+        .iter()
+        .find(|s| s.name == requested || s.description == requested)
+    {
+        return Ok(s.clone());
+    }
+    let req_lower = requested.to_lowercase();
+    let matches: Vec<&SourceEntry> = sources
+        .iter()
+        .filter(|s| {
+            s.name.to_lowercase().contains(&req_lower)
+                || s.description.to_lowercase().contains(&req_lower)
+        })
+        .collect();
+    match matches.as_slice() {
+        [one] => Ok((*one).clone()),
+        [] => Err(format!(
+            "No capture source matches '{requested}'. Available sources:\n  - {}",
+            label_list(&sources)
+        )),
+        many => Err(format!(
+            "'{requested}' is ambiguous, matches {} sources:\n  - {}",
+            many.len(),
+            many.iter()
+                .map(|s| s.label())
+                .collect::<Vec<_>>()
+                .join("\n  - ")
+        )),
+    }
+}
+
+fn label_list(sources: &[SourceEntry]) -> String {
+    sources
+        .iter()
+        .map(SourceEntry::label)
+        .collect::<Vec<_>>()
+        .join("\n  - ")
+}
+
+/// An application's playback stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppStream {
+    /// Stream node serial (`object.serial`)
+    pub index: u32,
+    /// `application.name`, else the node name
+    pub application: String,
+    /// `application.process.binary`, if set
+    pub binary: Option<String>,
+    /// What is playing (`media.name`)
+    pub media: String,
+}
+
+impl AppStream {
+    /// Human label: application, what it plays, and the stream serial.
+    pub fn label(&self) -> String {
+        if self.media.is_empty() || self.media == self.application {
+            format!("{} (#{})", self.application, self.index)
+        } else {
+            format!("{} — {} (#{})", self.application, self.media, self.index)
+        }
+    }
+}
+
+/// List applications currently playing audio.
+pub fn list_app_streams() -> Result<Vec<AppStream>, String> {
+    Ok(list_nodes()?
+        .into_iter()
+        .filter(|node| node.media_class == "Stream/Output/Audio")
+        .map(|node| {
+            let application = node.application.unwrap_or(node.name);
+            AppStream {
+                index: node.serial,
+                media: node.media.unwrap_or_else(|| application.clone()),
+                application,
+                binary: node.binary,
+            }
+        })
+        .collect())
+}
+
+/// Pick the stream of the application matching `requested`.
+///
+/// Exact (case-insensitive) match on application or binary name, else
+/// substring of application, binary or media name. When the application
+/// has several streams the newest one wins; matches across different
+/// applications are ambiguous.
+pub fn match_app<'a>(streams: &'a [AppStream], requested: &str) -> Result<&'a AppStream, String> {
+    let req = requested.to_lowercase();
+    let exact: Vec<&AppStream> = streams
+        .iter()
+        .filter(|s| {
+            s.application.to_lowercase() == req
+                || s.binary.as_deref().map(str::to_lowercase).as_deref() == Some(req.as_str())
+        })
+        .collect();
+    let matches = if exact.is_empty() {
+        streams
+            .iter()
+            .filter(|s| {
+                [Some(&s.application), s.binary.as_ref(), Some(&s.media)]
+                    .into_iter()
+                    .flatten()
+                    .any(|field| field.to_lowercase().contains(&req))
+            })
+            .collect()
+    } else {
+        exact
+    };
+
+    let Some(first) = matches.first() else {
+        return Err(if streams.is_empty() {
+            format!("No application matching '{requested}': nothing is playing audio")
+        } else {
+            format!(
+                "No application matches '{requested}'. Playing:\n  - {}",
+                streams
+                    .iter()
+                    .map(AppStream::label)
+                    .collect::<Vec<_>>()
+                    .join("\n  - ")
+            )
+        });
+    };
+    if matches.iter().any(|s| s.application != first.application) {
+        return Err(format!(
+            "'{requested}' is ambiguous, matches {} streams:\n  - {}",
+            matches.len(),
+            matches
+                .iter()
+                .map(|s| s.label())
+                .collect::<Vec<_>>()
+                .join("\n  - ")
+        ));
+    }
+    Ok(matches.into_iter().max_by_key(|s| s.index).unwrap())
+}
+
+/// What to capture.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum CaptureTarget {
+    /// The default sink's monitor, following changes of the default sink
+    #[default]
+    DefaultMonitor,
+    /// A source by name or description (see [`resolve_source`])
+    Source(String),
+    /// One application's output (see [`match_app`])
+    App(String),
+}
+
+impl CaptureTarget {
+    /// Target from the `--device` / `--app` options; `app` wins.
+    pub fn new(device: Option<&str>, app: Option<&str>) -> Self {
+        match (app, device) {
+            (Some(app), _) => Self::App(app.to_string()),
+            (None, Some(device)) => Self::Source(device.to_string()),
+            (None, None) => Self::DefaultMonitor,
+        }
+    }
+}
+
+/// Where a [`CaptureTarget`] currently resolves to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Endpoint {
+    /// `target.object` (node name or serial); `None` follows the default
+    object: Option<String>,
+    /// Record what a sink plays rather than a source
+    capture_sink: bool,
+    label: String,
+}
+
+fn resolve(target: &CaptureTarget) -> Result<Endpoint, String> {
+    match target {
+        CaptureTarget::DefaultMonitor => Ok(Endpoint {
+            object: None,
+            capture_sink: true,
+            label: "Default output [monitor]".to_string(),
+        }),
+        CaptureTarget::Source(requested) => {
+            let source = resolve_source(requested)?;
+            Ok(Endpoint {
+                object: Some(source.name.clone()),
+                capture_sink: source.is_monitor,
+                label: source.label(),
+            })
+        }
+        CaptureTarget::App(requested) => {
+            let streams = list_app_streams()?;
+            let stream = match_app(&streams, requested)?;
+            Ok(Endpoint {
+                object: Some(stream.index.to_string()),
+                capture_sink: false,
+                label: format!("{} [application]", stream.label()),
+            })
+        }
+    }
+}
+
+/// Mono f32 @ [`SAMPLE_RATE`], the format the FFT path expects.
+fn format_pod() -> Result<Vec<u8>, String> {
+    let mut info = AudioInfoRaw::new();
+    info.set_format(AudioFormat::F32LE);
+    info.set_rate(SAMPLE_RATE);
+    info.set_channels(1);
+    let obj = Object {
+        type_: pipewire::spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+        id: pipewire::spa::param::ParamType::EnumFormat.as_raw(),
+        properties: info.into(),
+    };
+    PodSerializer::serialize(std::io::Cursor::new(Vec::new()), &Value::Object(obj))
+        .map(|(cursor, _)| cursor.into_inner())
+        .map_err(|e| format!("Failed to serialize audio format: {e:?}"))
+}
+
+/// A record stream on a resolved [`CaptureTarget`] (mono f32 @ [`SAMPLE_RATE`]).
+///
+/// The stream lives on its own PipeWire thread; buffers arrive over a channel.
+pub struct Capture {
+    target: CaptureTarget,
+    endpoint: Endpoint,
+    samples: mpsc::Receiver<Vec<u8>>,
+    /// Bytes received but not yet handed out
+    pending: Vec<u8>,
+    /// Cleared to stop the PipeWire thread
+    running: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Capture {
+    /// Resolve `target` and open a record stream on it.
+    pub fn open(target: &CaptureTarget) -> Result<Self, String> {
+        let endpoint = resolve(target)?;
+        let running = Arc::new(AtomicBool::new(true));
+        let (samples_tx, samples) = mpsc::channel();
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        let thread = {
+            let endpoint = endpoint.clone();
+            let running = running.clone();
+            thread::spawn(move || {
+                let error_tx = ready_tx.clone();
+                if let Err(e) = run_stream(&endpoint, &running, samples_tx, ready_tx) {
+                    let _ = error_tx.send(Err(e));
+                }
+            })
+        };
+
+        // Dropped (stopping the thread) if the stream doesn't come up
+        let capture = Self {
+            target: target.clone(),
+            endpoint,
+            samples,
+            pending: Vec::new(),
+            running,
+            thread: Some(thread),
+        };
+        ready_rx
+            .recv_timeout(CONNECT_TIMEOUT)
+            .unwrap_or_else(|_| Err(format!("Timed out connecting to {}", capture.label())))?;
+        Ok(capture)
+    }
+
+    /// Human label of what is being captured.
+    pub fn label(&self) -> &str {
+        &self.endpoint.label
+    }
+
+    /// Fill `buf` with samples (little-endian f32), blocking until it is full.
+    /// Nothing playing reads as silence. Errors once the stream is gone.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<(), String> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            if !self.pending.is_empty() {
+                let n = self.pending.len().min(buf.len() - filled);
+                buf[filled..filled + n].copy_from_slice(&self.pending[..n]);
+                self.pending.drain(..n);
+                filled += n;
+                continue;
+            }
+            match self.samples.recv_timeout(SILENCE_AFTER) {
+                Ok(chunk) => self.pending = chunk,
+                Err(RecvTimeoutError::Timeout) => self.pending.resize(buf.len() - filled, 0),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("PipeWire stream closed".into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the target now resolves elsewhere — the application restarted
+    /// its stream or stopped, or the device is gone — so the capture should be
+    /// reopened. The default monitor is moved by the session manager instead.
+    pub fn is_stale(&self) -> bool {
+        resolve(&self.target).as_ref() != Ok(&self.endpoint)
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Run a record stream on `endpoint` until `running` clears or the stream
+/// goes away. Reports the connection outcome on `ready`.
+fn run_stream(
+    endpoint: &Endpoint,
+    running: &AtomicBool,
+    samples: mpsc::Sender<Vec<u8>>,
+    ready: mpsc::Sender<Result<(), String>>,
+) -> Result<(), String> {
+    let main_loop =
+        MainLoopBox::new(None).map_err(|e| format!("Failed to create main loop: {e:?}"))?;
+    let context = ContextBox::new(main_loop.loop_(), None)
+        .map_err(|e| format!("Failed to create context: {e:?}"))?;
+    let core = context
+        .connect(None)
+        .map_err(|e| format!("Failed to connect to PipeWire: {e:?}"))?;
+
+    let mut props = pipewire::properties::properties! {
+        *pipewire::keys::MEDIA_TYPE => "Audio",
+        *pipewire::keys::MEDIA_CATEGORY => "Capture",
+        *pipewire::keys::MEDIA_ROLE => "Music",
+        *pipewire::keys::APP_NAME => APP_NAME,
+        // ~10ms quantum, so the visualizer advances smoothly
+        "node.latency" => format!("{}/{SAMPLE_RATE}", SAMPLE_RATE / 100),
+        "stream.capture.sink" => if endpoint.capture_sink { "true" } else { "false" },
+    };
+    if let Some(object) = &endpoint.object {
+        props.insert("target.object", object.as_str());
+        // An explicit target that disappears is re-resolved by the caller
+        // rather than silently moved to the default device.
+        props.insert("node.dont-reconnect", "true");
+    }
+
+    let stream = StreamBox::new(&core, STREAM_NAME, props)
+        .map_err(|e| format!("Failed to create stream: {e:?}"))?;
+
+    let closed = Rc::new(Cell::new(false));
+    let closed_flag = closed.clone();
+    let _listener = stream
+        .add_local_listener_with_user_data(())
+        .state_changed(move |_, _, _old, new| match new {
+            StreamState::Paused | StreamState::Streaming => {
+                let _ = ready.send(Ok(()));
+            }
+            StreamState::Error(e) => {
+                let _ = ready.send(Err(format!("PipeWire stream error: {e}")));
+                closed_flag.set(true);
+            }
+            StreamState::Unconnected => {
+                let _ = ready.send(Err("PipeWire stream disconnected".into()));
+                closed_flag.set(true);
+            }
+            StreamState::Connecting => {}
+        })
+        .process(move |stream, _| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+            let Some(data) = buffer.datas_mut().first_mut() else {
+                return;
+            };
+            let (offset, size) = (data.chunk().offset() as usize, data.chunk().size() as usize);
+            if let Some(bytes) = data.data() {
+                let start = offset.min(bytes.len());
+                let end = (start + size).min(bytes.len());
+                let _ = samples.send(bytes[start..end].to_vec());
+            }
+        })
+        .register()
+        .map_err(|e| format!("Failed to register listener: {e:?}"))?;
+
+    let format = format_pod()?;
+    let pod = Pod::from_bytes(&format).ok_or("Failed to create pod from bytes")?;
+    stream
+        .connect(
+            Direction::Input,
+            None,
+            StreamFlags::AUTOCONNECT | StreamFlags::MAP_BUFFERS,
+            &mut [pod],
+        )
+        .map_err(|e| format!("Failed to connect stream: {e:?}"))?;
+
+    let loop_ = main_loop.loop_();
+    while running.load(Ordering::SeqCst) && !closed.get() {
+        if loop_.iterate(ITERATE_INTERVAL) < 0 {
+            return Err("PipeWire main loop error".into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(index: u32, application: &str, binary: &str, media: &str) -> AppStream {
+        AppStream {
+            index,
+            application: application.to_string(),
+            binary: Some(binary.to_string()),
+            media: media.to_string(),
+        }
+    }
+
+    #[test]
+    fn match_app_prefers_exact_names_and_newest_stream() {
+        let streams = [
+            stream(40, "Firefox", "firefox", "YouTube"),
+            stream(52, "Firefox", "firefox", "Meet"),
+            stream(47, "Spotify", "spotify", "Song"),
+            stream(60, "Firefox Helper", "ffhelper", "Beep"),
+        ];
+        // Exact name beats the "Firefox Helper" substring match
+        assert_eq!(match_app(&streams, "firefox").unwrap().index, 52);
+        assert_eq!(match_app(&streams, "SPOTIFY").unwrap().index, 47);
+        // Substring of the media name
+        assert_eq!(match_app(&streams, "youtube").unwrap().index, 40);
+    }
+
+    #[test]
+    fn match_app_reports_missing_and_ambiguous() {
+        let streams = [
+            stream(1, "Firefox", "firefox", "Video"),
+            stream(2, "mpv", "mpv", "Video.mkv"),
+        ];
+        let err = match_app(&streams, "vlc").unwrap_err();
+        assert!(err.contains("Firefox — Video (#1)"), "{err}");
+        assert!(match_app(&streams, "video")
+            .unwrap_err()
+            .contains("ambiguous"));
+        assert!(match_app(&[], "mpv").unwrap_err().contains("nothing"));
+    }
+
+    #[test]
+    fn app_option_wins_over_device() {
+        assert_eq!(
+            CaptureTarget::new(Some("hdmi"), Some("mpv")),
+            CaptureTarget::App("mpv".into())
+        );
+        assert_eq!(
+            CaptureTarget::new(Some("hdmi"), None),
+            CaptureTarget::Source("hdmi".into())
+        );
+        assert_eq!(
+            CaptureTarget::new(None, None),
+            CaptureTarget::DefaultMonitor
+        );
+    }
+}
@@ -0,0 +1,645 @@
+//! Lighting plugins — third-party effects and event processors loaded from
+//! shared libraries in `~/.config/monsgeek/plugins/` (`iot_driver plugin`,
+//! `[plugins]` under the daemon).
+//!
+//! A plugin is a cdylib exporting one C function, `monsgeek_plugin_v1`,
+//! that returns a pointer to a static [`PluginVtable`]:
+//!
+//! ```c
+//! typedef struct {
+//!     uint32_t abi_version;       /* MONSGEEK_PLUGIN_ABI = 1 */
+//!     const char *name;           /* static, NUL-terminated */
+//!     uint32_t caps;              /* 1 = effect (render), 2 = events */
+//!     void *(*create)(const char *config_json);
+//!     void (*destroy)(void *state);
+//!     int32_t (*render)(void *state, uint64_t t_ms, uint8_t *rgb, size_t len);
+//!     void (*on_event)(void *state, const MonsgeekEvent *event);
+//! } MonsgeekPluginV1;
+//! ```
+//!
+//! `create` gets the plugin's `[plugins.config.<name>]` table as JSON (`{}`
+//! when absent) and returns its state, or NULL to refuse loading. `render`
+//! fills `len` bytes of RGB for the 16×6 row-major LED matrix (index
+//! `row * 16 + col`, as in `stream`) and returns 0, or non-zero on error.
+//! Either callback may be NULL when the matching capability is absent. All
+//! calls for one plugin come from a single thread.
+//!
+//! The ABI only grows by adding a new `monsgeek_plugin_vN` symbol; existing
+//! layouts never change.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::evdev::{keycode_to_hid, EventReader};
+use crate::led_stream::{apply_power_budget, send_full_frame};
+use crate::notify::keymap::{pos_to_matrix_index, MATRIX_LEN, ROWS};
+use monsgeek_keyboard::KeyboardInterface;
+
+/// ABI version of [`PluginVtable`].
+pub const ABI_VERSION: u32 = 1;
+/// Entry point every plugin exports.
+pub const ENTRY_SYMBOL: &[u8] = b"monsgeek_plugin_v1";
+
+/// Capability: `render` draws frames.
+pub const CAP_EFFECT: u32 = 1;
+/// Capability: `on_event` receives key events.
+pub const CAP_EVENTS: u32 = 2;
+
+/// Event kinds in [`PluginEvent::kind`].
+pub const EVENT_KEY_DOWN: u32 = 1;
+pub const EVENT_KEY_UP: u32 = 2;
+
+/// `MonsgeekPluginV1`: the table a plugin's entry point returns.
+#[repr(C)]
+pub struct PluginVtable {
+    pub abi_version: u32,
+    pub name: *const c_char,
+    pub caps: u32,
+    pub create: unsafe extern "C" fn(config_json: *const c_char) -> *mut c_void,
+    pub destroy: unsafe extern "C" fn(state: *mut c_void),
+    pub render: Option<
+        unsafe extern "C" fn(state: *mut c_void, t_ms: u64, rgb: *mut u8, len: usize) -> i32,
+    >,
+    pub on_event: Option<unsafe extern "C" fn(state: *mut c_void, event: *const PluginEvent)>,
+}
+
+/// `MonsgeekEvent`: one key event.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PluginEvent {
+    /// `EVENT_KEY_DOWN` or `EVENT_KEY_UP`
+    pub kind: u32,
+    /// HID usage code
+    pub hid: u8,
+    /// LED matrix index (row-major) of the key, or -1 when unknown
+    pub led: i16,
+    /// Milliseconds since the host started
+    pub t_ms: u64,
+}
+
+type EntryFn = unsafe extern "C" fn() -> *const PluginVtable;
+
+/// A loaded plugin instance.
+pub struct Plugin {
+    name: String,
+    path: PathBuf,
+    vtable: *const PluginVtable,
+    state: *mut c_void,
+    // Dropped after `state` is destroyed (see `Drop`)
+    _library: Option<libloading::Library>,
+}
+
+impl Plugin {
+    /// Load a plugin library and create its instance with its settings.
+    /// Returns `None` for a plugin listed in `disabled`.
+    pub fn load(path: &Path, settings: &PluginSettings) -> Result<Option<Self>, String> {
+        let fail = |e: String| format!("{}: {e}", path.display());
+        // SAFETY: loading a library runs its initializers; plugins in the
+        // plugins directory are trusted like any other installed code.
+        let library = unsafe { libloading::Library::new(path) }.map_err(|e| fail(e.to_string()))?;
+        let vtable = unsafe {
+            let entry = library
+                .get::<EntryFn>(ENTRY_SYMBOL)
+                .map_err(|e| fail(e.to_string()))?;
+            entry()
+        };
+        // SAFETY: the vtable comes from the library, which the plugin keeps loaded
+        let plugin = unsafe { Self::from_vtable(vtable, settings) }.map_err(fail)?;
+        Ok(plugin.map(|mut plugin| {
+            plugin.path = path.to_path_buf();
+            plugin._library = Some(library);
+            plugin
+        }))
+    }
+
+    /// Create an instance from a vtable (`None` if the plugin is disabled).
+    ///
+    /// # Safety
+    ///
+    /// `vtable` must be null or point to a valid [`PluginVtable`] whose
+    /// pointers stay valid for the lifetime of the returned plugin.
+    pub unsafe fn from_vtable(
+        vtable: *const PluginVtable,
+        settings: &PluginSettings,
+    ) -> Result<Option<Self>, String> {
+        let Some(table) = vtable.as_ref() else {
+            return Err("entry point returned no vtable".to_string());
+        };
+        if table.abi_version != ABI_VERSION {
+            return Err(format!(
+                "plugin ABI version {} (expected {ABI_VERSION})",
+                table.abi_version
+            ));
+        }
+        if table.name.is_null() {
+            return Err("plugin has no name".to_string());
+        }
+        let name = CStr::from_ptr(table.name).to_string_lossy().into_owned();
+        if settings.disabled.contains(&name) {
+            return Ok(None);
+        }
+        let config = CString::new(settings.config_for(&name).to_string()).unwrap_or_default();
+        let state = (table.create)(config.as_ptr());
+        if state.is_null() {
+            return Err(format!("plugin '{name}' refused to start"));
+        }
+        Ok(Some(Self {
+            name,
+            path: PathBuf::new(),
+            vtable,
+            state,
+            _library: None,
+        }))
+    }
+
+    fn table(&self) -> &PluginVtable {
+        // SAFETY: checked non-null in `from_vtable`, valid while loaded
+        unsafe { &*self.vtable }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_effect(&self) -> bool {
+        self.table().caps & CAP_EFFECT != 0 && self.table().render.is_some()
+    }
+
+    pub fn handles_events(&self) -> bool {
+        self.table().caps & CAP_EVENTS != 0 && self.table().on_event.is_some()
+    }
+
+    /// Capabilities as text, e.g. `effect, events`.
+    pub fn describe_caps(&self) -> String {
+        let caps: Vec<_> = [
+            (self.is_effect(), "effect"),
+            (self.handles_events(), "events"),
+        ]
+        .into_iter()
+        .filter_map(|(has, name)| has.then_some(name))
+        .collect();
+        if caps.is_empty() {
+            "none".to_string()
+        } else {
+            caps.join(", ")
+        }
+    }
+
+    /// Draw one frame for time `t`.
+    pub fn render(&mut self, t: Duration) -> Result<[(u8, u8, u8); MATRIX_LEN], String> {
+        let Some(render) = self.table().render.filter(|_| self.is_effect()) else {
+            return Err(format!("plugin '{}' is not an effect", self.name));
+        };
+        let mut rgb = [0u8; MATRIX_LEN * 3];
+        // SAFETY: the buffer is `len` bytes; the state came from `create`
+        let rc = unsafe {
+            render(
+                self.state,
+                t.as_millis() as u64,
+                rgb.as_mut_ptr(),
+                rgb.len(),
+            )
+        };
+        if rc != 0 {
+            return Err(format!("plugin '{}' render failed ({rc})", self.name));
+        }
+        let mut leds = [(0, 0, 0); MATRIX_LEN];
+        for (led, c) in leds.iter_mut().zip(rgb.chunks_exact(3)) {
+            *led = (c[0], c[1], c[2]);
+        }
+        Ok(leds)
+    }
+
+    /// Pass a key event on, if the plugin wants events.
+    pub fn on_event(&mut self, event: &PluginEvent) {
+        if let Some(on_event) = self.table().on_event.filter(|_| self.handles_events()) {
+            // SAFETY: the event outlives the call; the state came from `create`
+            unsafe { on_event(self.state, event) };
+        }
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        // SAFETY: `state` came from `create` and is destroyed exactly once,
+        // before the library is unloaded
+        unsafe { (self.table().destroy)(self.state) };
+    }
+}
+
+/// `[plugins]` settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginSettings {
+    /// Effect plugin `iot_driver daemon` streams to the LEDs (by name);
+    /// unset leaves the lighting alone and only runs event processors
+    pub effect: Option<String>,
+    /// Plugins not to load, by name
+    pub disabled: Vec<String>,
+    /// Frames per second for the effect (default 30)
+    pub fps: Option<u32>,
+    /// LED power budget in milliamps (0 = unlimited, default 400)
+    pub power_budget: Option<u32>,
+    /// Per-plugin settings, passed to the plugin as JSON
+    pub config: BTreeMap<String, serde_json::Value>,
+}
+
+impl PluginSettings {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_secs_f32(1.0 / self.fps.unwrap_or(30).clamp(1, 60) as f32)
+    }
+
+    pub fn power_budget(&self) -> u32 {
+        self.power_budget.unwrap_or(400)
+    }
+
+    fn config_for(&self, name: &str) -> serde_json::Value {
+        self.config
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| serde_json::json!({}))
+    }
+}
+
+/// `~/.config/monsgeek/plugins/`
+pub fn plugins_dir() -> PathBuf {
+    crate::effect::config_dir().join("plugins")
+}
+
+/// Shared libraries in `dir`, sorted by file name.
+pub fn scan(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().is_some_and(|e| e == "so"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Load every plugin in `dir` that isn't disabled. Returns the plugins and
+/// one message per library that failed to load.
+pub fn load_all(dir: &Path, settings: &PluginSettings) -> (Vec<Plugin>, Vec<String>) {
+    let mut plugins: Vec<Plugin> = Vec::new();
+    let mut errors = Vec::new();
+    for path in scan(dir) {
+        match Plugin::load(&path, settings) {
+            Ok(Some(p)) if plugins.iter().any(|q| q.name() == p.name()) => {
+                errors.push(format!(
+                    "{}: duplicate plugin '{}'",
+                    path.display(),
+                    p.name()
+                ));
+            }
+            Ok(Some(p)) => plugins.push(p),
+            Ok(None) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+    (plugins, errors)
+}
+
+/// Matrix LED index for each HID code of the factory layout.
+pub fn led_by_hid() -> HashMap<u8, usize> {
+    let mut map = HashMap::new();
+    for index in 0..MATRIX_LEN {
+        let code = crate::keymap::default_keycode(index as u8);
+        if code == 0 {
+            continue;
+        }
+        // Key matrix indices are column-major
+        let (row, col) = (index % ROWS, index / ROWS);
+        if let Some(led) = pos_to_matrix_index(row as u8, col as u8) {
+            map.entry(code).or_insert(led);
+        }
+    }
+    map
+}
+
+/// Run plugins (blocking) until `running` clears: key events from `reader`
+/// go to every event processor, and `effect` (an index into `plugins`), if
+/// any, is streamed to the LEDs. The stream is released on exit.
+pub fn run(
+    keyboard: &KeyboardInterface,
+    reader: &EventReader,
+    plugins: &mut [Plugin],
+    effect: Option<usize>,
+    settings: &PluginSettings,
+    running: Arc<AtomicBool>,
+) -> Result<(), String> {
+    let leds = led_by_hid();
+    let start = Instant::now();
+    let frame = settings.frame_interval();
+    let mut next_frame = Instant::now();
+    let mut result = Ok(());
+    while running.load(Ordering::SeqCst) {
+        let wait = next_frame.saturating_duration_since(Instant::now());
+        if reader.is_empty() {
+            std::thread::sleep(wait);
+        }
+        for ev in reader.poll(wait) {
+            if !ev.is_key_edge() {
+                continue;
+            }
+            let Some(hid) = keycode_to_hid(ev.code) else {
+                continue;
+            };
+            let event = PluginEvent {
+                kind: if ev.value == 1 {
+                    EVENT_KEY_DOWN
+                } else {
+                    EVENT_KEY_UP
+                },
+                hid,
+                led: leds.get(&hid).map_or(-1, |&i| i as i16),
+                t_ms: start.elapsed().as_millis() as u64,
+            };
+            for plugin in plugins.iter_mut() {
+                plugin.on_event(&event);
+            }
+        }
+
+        if Instant::now() < next_frame {
+            continue;
+        }
+        next_frame = Instant::now() + frame;
+        if let Some(plugin) = effect.and_then(|i| plugins.get_mut(i)) {
+            let sent = plugin.render(start.elapsed()).and_then(|mut leds| {
+                apply_power_budget(&mut leds, settings.power_budget());
+                send_full_frame(keyboard, &leds).map_err(|e| format!("send frame: {e}"))
+            });
+            if let Err(e) = sent {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+
+    if effect.is_some() {
+        keyboard.stream_led_release().ok();
+    }
+    result
+}
+
+/// Daemon loop: load the plugins directory once, then run them while any
+/// are loaded, re-reading `[plugins] effect` after errors and reconnects.
+/// The keyboard is opened on demand and reopened after errors.
+pub fn run_daemon_plugins(open: impl Fn() -> Result<KeyboardInterface, String>) {
+    const RETRY: Duration = Duration::from_secs(5);
+
+    let dir = plugins_dir();
+    let settings = crate::settings::Settings::load().plugins;
+    let (mut plugins, errors) = load_all(&dir, &settings);
+    for e in errors {
+        tracing::warn!("plugins: {e}");
+    }
+    if plugins.is_empty() {
+        tracing::debug!("plugins: none in {}", dir.display());
+        return;
+    }
+    for p in &plugins {
+        tracing::info!("plugins: loaded {} ({})", p.name(), p.describe_caps());
+    }
+
+    let running = Arc::new(AtomicBool::new(true));
+    loop {
+        let settings = crate::settings::Settings::load().plugins;
+        let effect = match settings.effect.as_deref() {
+            Some(name) => match plugins
+                .iter()
+                .position(|p| p.name() == name && p.is_effect())
+            {
+                Some(i) => Some(i),
+                None => {
+                    tracing::warn!("plugins: no effect plugin named '{name}'");
+                    None
+                }
+            },
+            None => None,
+        };
+        if effect.is_none() && !plugins.iter().any(Plugin::handles_events) {
+            std::thread::sleep(RETRY * 12);
+            continue;
+        }
+
+        let keyboard = match open() {
+            Ok(kb) => kb,
+            Err(e) => {
+                tracing::debug!("plugins: no keyboard: {e}");
+                std::thread::sleep(RETRY);
+                continue;
+            }
+        };
+        let effect = effect.filter(|_| {
+            let ok = crate::sysmon::supports_streaming(&keyboard);
+            if !ok {
+                tracing::warn!("plugins: firmware has no LED streaming patch");
+            }
+            ok
+        });
+        let reader = EventReader::open(keyboard.vid(), keyboard.pid());
+        if let Err(e) = run(
+            &keyboard,
+            &reader,
+            &mut plugins,
+            effect,
+            &settings,
+            Arc::clone(&running),
+        ) {
+            tracing::warn!("plugins: {e}");
+        }
+        std::thread::sleep(RETRY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    static EVENTS: AtomicUsize = AtomicUsize::new(0);
+    static DESTROYED: AtomicUsize = AtomicUsize::new(0);
+
+    struct State {
+        level: u8,
+    }
+
+    unsafe extern "C" fn create(config: *const c_char) -> *mut c_void {
+        let config = CStr::from_ptr(config).to_string_lossy();
+        let config: serde_json::Value = serde_json::from_str(&config).unwrap();
+        let level = config["level"].as_u64().unwrap_or(10) as u8;
+        Box::into_raw(Box::new(State { level })) as *mut c_void
+    }
+
+    unsafe extern "C" fn refuse(_: *const c_char) -> *mut c_void {
+        std::ptr::null_mut()
+    }
+
+    unsafe extern "C" fn destroy(state: *mut c_void) {
+        drop(Box::from_raw(state as *mut State));
+        DESTROYED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    unsafe extern "C" fn render(state: *mut c_void, t_ms: u64, rgb: *mut u8, len: usize) -> i32 {
+        let state = &*(state as *const State);
+        let rgb = std::slice::from_raw_parts_mut(rgb, len);
+        rgb[0] = state.level;
+        rgb[len - 1] = (t_ms / 1000) as u8;
+        0
+    }
+
+    unsafe extern "C" fn on_event(_: *mut c_void, event: *const PluginEvent) {
+        if (*event).kind == EVENT_KEY_DOWN {
+            EVENTS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn vtable(abi_version: u32) -> PluginVtable {
+        PluginVtable {
+            abi_version,
+            name: c"test".as_ptr(),
+            caps: CAP_EFFECT | CAP_EVENTS,
+            create,
+            destroy,
+            render: Some(render),
+            on_event: Some(on_event),
+        }
+    }
+
+    #[test]
+    fn renders_with_config_and_destroys_state() {
+        let table = vtable(ABI_VERSION);
+        let destroyed = DESTROYED.load(Ordering::SeqCst);
+        {
+            let settings: PluginSettings = toml::from_str("config.test.level = 200").unwrap();
+            let mut plugin = unsafe { Plugin::from_vtable(&table, &settings) }
+                .unwrap()
+                .unwrap();
+            assert_eq!(plugin.name(), "test");
+            assert_eq!(plugin.describe_caps(), "effect, events");
+            let leds = plugin.render(Duration::from_secs(3)).unwrap();
+            assert_eq!(leds[0], (200, 0, 0));
+            assert_eq!(leds[MATRIX_LEN - 1], (0, 0, 3));
+        }
+        assert!(DESTROYED.load(Ordering::SeqCst) > destroyed);
+    }
+
+    #[test]
+    fn events_reach_event_processors_only() {
+        let event = PluginEvent {
+            kind: EVENT_KEY_DOWN,
+            hid: 4,
+            led: 33,
+            t_ms: 0,
+        };
+        let mut table = vtable(ABI_VERSION);
+        let settings = PluginSettings::default();
+        let mut plugin = unsafe { Plugin::from_vtable(&table, &settings) }
+            .unwrap()
+            .unwrap();
+        let before = EVENTS.load(Ordering::SeqCst);
+        plugin.on_event(&event);
+        assert_eq!(EVENTS.load(Ordering::SeqCst), before + 1);
+        drop(plugin);
+
+        table.caps = CAP_EFFECT;
+        let plugin = unsafe { Plugin::from_vtable(&table, &settings) }
+            .unwrap()
+            .unwrap();
+        assert!(!plugin.handles_events());
+        assert_eq!(plugin.describe_caps(), "effect");
+    }
+
+    #[test]
+    fn rejects_wrong_abi_refusing_and_disabled_plugins() {
+        let settings = PluginSettings::default();
+        let table = vtable(ABI_VERSION + 1);
+        let err = unsafe { Plugin::from_vtable(&table, &settings) }
+            .err()
+            .unwrap();
+        assert!(err.contains("ABI version 2"), "{err}");
+        assert!(unsafe { Plugin::from_vtable(std::ptr::null(), &settings) }.is_err());
+
+        let table = PluginVtable {
+            create: refuse,
+            ..vtable(ABI_VERSION)
+        };
+        let err = unsafe { Plugin::from_vtable(&table, &settings) }
+            .err()
+            .unwrap();
+        assert!(err.contains("refused"), "{err}");
+
+        let disabled = PluginSettings {
+            disabled: vec!["test".to_string()],
+            ..Default::default()
+        };
+        let table = vtable(ABI_VERSION);
+        assert!(unsafe { Plugin::from_vtable(&table, &disabled) }
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn scan_finds_shared_libraries_and_reports_bad_ones() {
+        let dir = std::env::temp_dir().join(format!("monsgeek-plugins-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("libb.so"), b"not a library").unwrap();
+        std::fs::write(dir.join("a.so"), b"not a library").unwrap();
+        std::fs::write(dir.join("readme.txt"), b"").unwrap();
+        let found: Vec<_> = scan(&dir)
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(found, ["a.so", "libb.so"]);
+
+        let (plugins, errors) = load_all(&dir, &PluginSettings::default());
+        assert!(plugins.is_empty());
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("a.so"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn factory_layout_maps_hid_codes_to_leds() {
+        let leds = led_by_hid();
+        // Esc (HID 0x29) is the top-left LED
+        assert_eq!(leds.get(&0x29), Some(&0));
+        assert!(leds.len() > 60);
+    }
+
+    #[test]
+    fn settings_load_from_toml() {
+        let settings: PluginSettings = toml::from_str(
+            r#"
+            effect = "fire"
+            fps = 500
+            [config.fire]
+            speed = 2.5
+            "#,
+        )
+        .unwrap();
+        assert_eq!(settings.effect.as_deref(), Some("fire"));
+        assert_eq!(
+            settings.frame_interval(),
+            Duration::from_secs_f32(1.0 / 60.0)
+        );
+        assert_eq!(settings.config_for("fire")["speed"], 2.5);
+        assert_eq!(settings.config_for("other"), serde_json::json!({}));
+    }
+}
@@ -0,0 +1,323 @@
+//! Pomodoro timer lighting (`iot_driver timer`, D-Bus `StartTimer`).
+//!
+//! Work intervals alternate with breaks. During work the board fades from
+//! the start color to the end color (or fills up row by row); each phase
+//! change flashes in the new phase's color so it is hard to miss. Frames are
+//! streamed over the patch LED protocol.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::keyboard_config::parse_color;
+use crate::led_stream::{apply_power_budget, send_full_frame};
+use crate::notify::keymap::{COLS, MATRIX_LEN, ROWS};
+use monsgeek_keyboard::{KeyboardInterface, RgbColor};
+
+/// How long a phase change flashes.
+const FLASH_TIME: Duration = Duration::from_secs(4);
+/// Flash on/off period.
+const FLASH_PERIOD: Duration = Duration::from_millis(500);
+/// Brightness of the steady break color, so it reads as "resting".
+const BREAK_LEVEL: f32 = 0.35;
+/// Frame interval of the render loop.
+const FRAME: Duration = Duration::from_millis(100);
+
+/// How work progress is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimerStyle {
+    /// The whole board fades from the start color to the end color
+    #[default]
+    Fade,
+    /// Rows fill bottom to top; breaks drain them again
+    Rows,
+}
+
+/// Timer settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimerConfig {
+    pub work: Duration,
+    pub rest: Duration,
+    /// Work intervals before the timer finishes (0 = run until stopped)
+    pub cycles: u32,
+    pub style: TimerStyle,
+    /// Color at the start of a work interval
+    pub start: RgbColor,
+    /// Color at the end of a work interval
+    pub end: RgbColor,
+    /// Break color
+    pub rest_color: RgbColor,
+}
+
+impl Default for TimerConfig {
+    fn default() -> Self {
+        Self {
+            work: Duration::from_secs(25 * 60),
+            rest: Duration::from_secs(5 * 60),
+            cycles: 4,
+            style: TimerStyle::default(),
+            start: RgbColor::new(0, 255, 0),
+            end: RgbColor::new(255, 0, 0),
+            rest_color: RgbColor::new(0, 80, 255),
+        }
+    }
+}
+
+/// Parse `--colors` for the timer: `START,END,BREAK` as `#RRGGBB`.
+pub fn parse_colors(s: &str) -> Result<(RgbColor, RgbColor, RgbColor), String> {
+    let color = |c: &str| parse_color(c).ok_or_else(|| format!("invalid color '{c}'"));
+    match s.split(',').map(str::trim).collect::<Vec<_>>().as_slice() {
+        [start, end, rest] => Ok((color(start)?, color(end)?, color(rest)?)),
+        _ => Err(format!("expected START,END,BREAK colors, got '{s}'")),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Work,
+    Break,
+    /// All cycles completed
+    Done,
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Work => "work",
+            Phase::Break => "break",
+            Phase::Done => "done",
+        }
+    }
+}
+
+/// Where the timer is at some point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimerStatus {
+    pub phase: Phase,
+    /// Work interval number, from 1
+    pub cycle: u32,
+    /// Time into the current phase
+    pub elapsed: Duration,
+    /// Length of the current phase (zero when done)
+    pub length: Duration,
+}
+
+impl TimerStatus {
+    pub fn remaining(&self) -> Duration {
+        self.length.saturating_sub(self.elapsed)
+    }
+
+    /// Fraction of the phase elapsed, 0.0-1.0.
+    pub fn progress(&self) -> f32 {
+        if self.length.is_zero() {
+            return 1.0;
+        }
+        (self.elapsed.as_secs_f32() / self.length.as_secs_f32()).clamp(0.0, 1.0)
+    }
+
+    /// Whether the phase-change flash is still running.
+    pub fn flashing(&self) -> bool {
+        let changed = self.phase != Phase::Work || self.cycle > 1;
+        changed && self.elapsed < FLASH_TIME
+    }
+}
+
+fn blend(a: RgbColor, b: RgbColor, t: f32) -> RgbColor {
+    let mix = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * t).round() as u8;
+    RgbColor::new(mix(a.r, b.r), mix(a.g, b.g), mix(a.b, b.b))
+}
+
+fn scale(c: RgbColor, f: f32) -> (u8, u8, u8) {
+    let s = |v: u8| (v as f32 * f).round() as u8;
+    (s(c.r), s(c.g), s(c.b))
+}
+
+/// Light the bottom `level` (0.0-1.0) of the rows, the top one partially.
+fn fill_rows(level: f32, c: RgbColor) -> [(u8, u8, u8); MATRIX_LEN] {
+    let fill = level.clamp(0.0, 1.0) * ROWS as f32;
+    let mut leds = [(0, 0, 0); MATRIX_LEN];
+    for (i, led) in leds.iter_mut().enumerate() {
+        let from_bottom = ROWS - 1 - i / COLS;
+        *led = scale(c, (fill - from_bottom as f32).clamp(0.0, 1.0));
+    }
+    leds
+}
+
+impl TimerConfig {
+    /// Timer state `elapsed` after the start.
+    pub fn status_at(&self, elapsed: Duration) -> TimerStatus {
+        let work = self.work.max(Duration::from_secs(1));
+        let period = work + self.rest;
+        let n = (elapsed.as_secs_f64() / period.as_secs_f64()) as u32;
+        if self.cycles > 0 && n >= self.cycles {
+            // The last break is skipped: done as soon as the last work ends
+            let done_at = period * (self.cycles - 1) + work;
+            return TimerStatus {
+                phase: Phase::Done,
+                cycle: self.cycles,
+                elapsed: elapsed.saturating_sub(done_at),
+                length: Duration::ZERO,
+            };
+        }
+        let into = elapsed - period * n;
+        let (phase, elapsed, length) = if into < work {
+            (Phase::Work, into, work)
+        } else if self.cycles > 0 && n + 1 == self.cycles {
+            (Phase::Done, into - work, Duration::ZERO)
+        } else {
+            (Phase::Break, into - work, self.rest)
+        };
+        TimerStatus {
+            phase,
+            cycle: n + 1,
+            elapsed,
+            length,
+        }
+    }
+
+    /// Phase color at `status`, before flashing and row filling.
+    fn color(&self, status: &TimerStatus) -> RgbColor {
+        match status.phase {
+            Phase::Work => blend(self.start, self.end, status.progress()),
+            Phase::Break => self.rest_color,
+            Phase::Done => self.end,
+        }
+    }
+
+    /// One LED frame (row-major) for `status`.
+    pub fn render(&self, status: &TimerStatus) -> [(u8, u8, u8); MATRIX_LEN] {
+        let color = self.color(status);
+        if status.flashing() {
+            let on = (status.elapsed.as_millis() / FLASH_PERIOD.as_millis()).is_multiple_of(2);
+            let c = if on { color } else { RgbColor::new(0, 0, 0) };
+            return [(c.r, c.g, c.b); MATRIX_LEN];
+        }
+        match (status.phase, self.style) {
+            (Phase::Done, _) => [(0, 0, 0); MATRIX_LEN],
+            (Phase::Work, TimerStyle::Fade) => [(color.r, color.g, color.b); MATRIX_LEN],
+            (Phase::Work, TimerStyle::Rows) => fill_rows(status.progress(), color),
+            (Phase::Break, TimerStyle::Fade) => [scale(color, BREAK_LEVEL); MATRIX_LEN],
+            (Phase::Break, TimerStyle::Rows) => fill_rows(
+                1.0 - status.progress(),
+                blend(RgbColor::new(0, 0, 0), color, BREAK_LEVEL),
+            ),
+        }
+    }
+}
+
+/// Run the timer (blocking) from `started` until it finishes or `running`
+/// clears. `on_status` sees every rendered state, e.g. for a countdown line.
+///
+/// The keyboard must support patch LED streaming; the stream is released on
+/// exit.
+pub fn run_timer(
+    keyboard: &KeyboardInterface,
+    config: &TimerConfig,
+    started: Instant,
+    power_budget: u32,
+    running: Arc<AtomicBool>,
+    mut on_status: impl FnMut(&TimerStatus),
+) -> Result<(), String> {
+    let mut result = Ok(());
+    while running.load(Ordering::SeqCst) {
+        let status = config.status_at(started.elapsed());
+        if status.phase == Phase::Done && !status.flashing() {
+            break;
+        }
+        on_status(&status);
+
+        let mut leds = config.render(&status);
+        apply_power_budget(&mut leds, power_budget);
+        if let Err(e) = send_full_frame(keyboard, &leds) {
+            result = Err(format!("Failed to send LED frame: {e}"));
+            break;
+        }
+        std::thread::sleep(FRAME);
+    }
+
+    keyboard.stream_led_release().ok();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(cycles: u32) -> TimerConfig {
+        TimerConfig {
+            work: Duration::from_secs(100),
+            rest: Duration::from_secs(20),
+            cycles,
+            ..Default::default()
+        }
+    }
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn phases_alternate_and_finish_after_the_last_work() {
+        let c = config(2);
+        let at = |s| c.status_at(secs(s));
+        assert_eq!((at(0).phase, at(0).cycle), (Phase::Work, 1));
+        assert_eq!(at(50).progress(), 0.5);
+        assert_eq!(
+            (at(110).phase, at(110).remaining()),
+            (Phase::Break, secs(10))
+        );
+        assert_eq!((at(130).phase, at(130).cycle), (Phase::Work, 2));
+        assert_eq!((at(225).phase, at(225).elapsed), (Phase::Done, secs(5)));
+        assert_eq!((at(500).phase, at(500).elapsed), (Phase::Done, secs(280)));
+    }
+
+    #[test]
+    fn zero_cycles_runs_forever() {
+        let status = config(0).status_at(secs(120 * 50 + 10));
+        assert_eq!((status.phase, status.cycle), (Phase::Work, 51));
+    }
+
+    #[test]
+    fn only_phase_changes_flash() {
+        let c = config(0);
+        assert!(!c.status_at(secs(1)).flashing());
+        assert!(c.status_at(secs(101)).flashing());
+        assert!(!c.status_at(secs(110)).flashing());
+        assert!(c.status_at(secs(121)).flashing());
+    }
+
+    #[test]
+    fn work_fades_from_start_to_end() {
+        let c = config(0);
+        assert_eq!(c.render(&c.status_at(secs(10)))[0].1, 230);
+        assert_eq!(c.render(&c.status_at(secs(100) - FRAME))[50].0, 255);
+        // Flash frames alternate between the break color and dark
+        let on = c.render(&c.status_at(secs(100)));
+        let off = c.render(&c.status_at(secs(100) + FLASH_PERIOD));
+        assert_eq!(on[0], (0, 80, 255));
+        assert_eq!(off[0], (0, 0, 0));
+    }
+
+    #[test]
+    fn rows_fill_during_work_and_drain_during_break() {
+        let c = TimerConfig {
+            style: TimerStyle::Rows,
+            ..config(0)
+        };
+        let lit = |s| {
+            let leds = c.render(&c.status_at(secs(s)));
+            (0..ROWS).filter(|r| leds[r * COLS] != (0, 0, 0)).count()
+        };
+        assert_eq!(lit(0), 0);
+        assert_eq!(lit(50), ROWS / 2);
+        assert_eq!(lit(110), ROWS / 2);
+        assert_eq!(lit(119), 1);
+    }
+
+    #[test]
+    fn parses_three_colors() {
+        let (start, _, rest) = parse_colors("#00ff00,#ff0000, #0000FF").unwrap();
+        assert_eq!((start.g, rest.b), (255, 255));
+        assert!(parse_colors("#00ff00,#ff0000").is_err());
+    }
+}
@@ -3,7 +3,9 @@
 
 use super::traits::DeviceProfile;
 use super::types::TravelSettings;
-use crate::hal::constants::{KEY_COUNT_M1_V5, MATRIX_SIZE_M1_V5, VENDOR_ID};
+use crate::hal::constants::{
+    KEY_COUNT_M1_V5, KEY_COUNT_M3_V5, MATRIX_SIZE_M1_V5, MATRIX_SIZE_M3_V5, VENDOR_ID,
+};
 
 /// MonsGeek M1 V5 HE builtin profile
 pub struct M1V5HeProfile {
@@ -88,6 +90,10 @@ impl DeviceProfile for M1V5HeProfile {
         &M1_V5_HE_LED_MATRIX
     }
 
+    fn led_gap_columns(&self) -> Option<&[u8]> {
+        Some(&M1_V5_HE_LED_GAP_COLUMNS)
+    }
+
     fn matrix_key_name(&self, position: u8) -> &str {
         M1_V5_HE_KEY_NAMES
             .get(position as usize)
@@ -103,6 +109,10 @@ impl DeviceProfile for M1V5HeProfile {
         false
     }
 
+    fn has_knob(&self) -> bool {
+        true // Volume encoder at positions 90-92
+    }
+
     fn travel_settings(&self) -> Option<&TravelSettings> {
         Some(&self.travel_settings)
     }
@@ -236,6 +246,11 @@ pub const M1_V5_HE_LED_MATRIX: [u8; MATRIX_SIZE_M1_V5] = [
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // 116-125
 ];
 
+/// M1 V5 HE LED grid gaps: per row, the first matrix column that sits one LED
+/// column further right. Derived from the firmware's `static_led_pos_tbl`
+/// against `M1_V5_HE_KEY_NAMES`.
+pub const M1_V5_HE_LED_GAP_COLUMNS: [u8; 6] = [1, 1, 1, 1, 12, 9];
+
 /// Key names for M1 V5 HE matrix positions
 /// Derived from LED matrix HID codes using standard HID usage table
 /// Each name corresponds to the same index in M1_V5_HE_LED_MATRIX
@@ -358,6 +373,135 @@ pub const M1_V5_HE_KEY_NAMES: &[&str] = &[
     "", "", "", "", "", "", "", "", "", "", // 116-125
 ];
 
+/// MonsGeek M3 V5 HE builtin profile (TKL, 87 keys)
+///
+/// Reports the same PID as the M1 V5 HE, so it's only picked by device ID.
+pub struct M3V5HeProfile {
+    travel_settings: TravelSettings,
+}
+
+impl M3V5HeProfile {
+    pub fn new() -> Self {
+        Self {
+            travel_settings: TravelSettings::default(),
+        }
+    }
+}
+
+impl Default for M3V5HeProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeviceProfile for M3V5HeProfile {
+    fn id(&self) -> u32 {
+        2874 // Firmware-reported device ID (GET_USB_VERSION)
+    }
+
+    fn vid(&self) -> u16 {
+        VENDOR_ID
+    }
+
+    fn pid(&self) -> u16 {
+        0x5030
+    }
+
+    fn name(&self) -> &str {
+        "m3v5he"
+    }
+
+    fn display_name(&self) -> &str {
+        "MonsGeek M3 V5 HE"
+    }
+
+    fn company(&self) -> &str {
+        "MonsGeek"
+    }
+
+    fn key_count(&self) -> u8 {
+        KEY_COUNT_M3_V5
+    }
+
+    fn matrix_size(&self) -> usize {
+        MATRIX_SIZE_M3_V5
+    }
+
+    fn layer_count(&self) -> u8 {
+        16 // Same RY5088 firmware as the M1 V5 HE
+    }
+
+    fn led_matrix(&self) -> &[u8] {
+        &M3_V5_HE_LED_MATRIX
+    }
+
+    fn matrix_key_name(&self, position: u8) -> &str {
+        M3_V5_HE_KEY_NAMES
+            .get(position as usize)
+            .copied()
+            .unwrap_or("?")
+    }
+
+    fn has_magnetism(&self) -> bool {
+        true
+    }
+
+    fn travel_settings(&self) -> Option<&TravelSettings> {
+        Some(&self.travel_settings)
+    }
+
+    fn fn_layer_win(&self) -> u8 {
+        2
+    }
+
+    fn fn_layer_mac(&self) -> u8 {
+        2
+    }
+}
+
+/// M3 V5 HE LED matrix: position -> HID keycode
+/// Columns 0-9 match the M1 V5 HE; the right modifiers shift one column over
+/// and columns 13-15 hold the nav cluster. Column-major, 6 rows per column.
+pub const M3_V5_HE_LED_MATRIX: [u8; MATRIX_SIZE_M3_V5] = [
+    41, 53, 43, 57, 225, 224, // Col 0: Esc ` Tab Caps LShift LCtrl
+    58, 30, 20, 4, 0, 227, // Col 1: F1 1 Q A - LWin
+    59, 31, 26, 22, 29, 226, // Col 2: F2 2 W S Z LAlt
+    60, 32, 8, 7, 27, 0, // Col 3: F3 3 E D X
+    61, 33, 21, 9, 6, 0, // Col 4: F4 4 R F C
+    62, 34, 23, 10, 25, 0, // Col 5: F5 5 T G V
+    63, 35, 28, 11, 5, 44, // Col 6: F6 6 Y H B Space
+    64, 36, 24, 13, 17, 0, // Col 7: F7 7 U J N
+    65, 37, 12, 14, 16, 0, // Col 8: F8 8 I K M
+    66, 38, 18, 15, 54, 230, // Col 9: F9 9 O L , RAlt
+    67, 39, 19, 51, 55, 231, // Col 10: F10 0 P ; . RWin
+    68, 45, 47, 52, 56, 0, // Col 11: F11 - [ ' / (Fn - special)
+    69, 46, 48, 0, 229, 228, // Col 12: F12 = ] - RShift RCtrl
+    0, 42, 49, 40, 0, 80, // Col 13: - Backspace \ Enter - Left
+    70, 73, 76, 72, 82, 81, // Col 14: PrtSc Insert Delete Pause Up Down
+    71, 74, 77, 75, 78, 79, // Col 15: ScrLk Home End PgUp PgDn Right
+];
+
+/// Key names for M3 V5 HE matrix positions
+/// Each name corresponds to the same index in M3_V5_HE_LED_MATRIX
+pub const M3_V5_HE_KEY_NAMES: &[&str] = &[
+    "Esc", "`", "Tab", "Caps", "LShift", "LCtrl", // Col 0 (0-5)
+    "F1", "1", "Q", "A", "", "LWin", // Col 1 (6-11)
+    "F2", "2", "W", "S", "Z", "LAlt", // Col 2 (12-17)
+    "F3", "3", "E", "D", "X", "", // Col 3 (18-23)
+    "F4", "4", "R", "F", "C", "", // Col 4 (24-29)
+    "F5", "5", "T", "G", "V", "", // Col 5 (30-35)
+    "F6", "6", "Y", "H", "B", "Space", // Col 6 (36-41)
+    "F7", "7", "U", "J", "N", "", // Col 7 (42-47)
+    "F8", "8", "I", "K", "M", "", // Col 8 (48-53)
+    "F9", "9", "O", "L", ",", "RAlt", // Col 9 (54-59)
+    "F10", "0", "P", ";", ".", "RWin", // Col 10 (60-65)
+    "F11", "-", "[", "'", "/", "Fn", // Col 11 (66-71)
+    "F12", "=", "]", "", "RShift", "RCtrl", // Col 12 (72-77)
+    "", "Bksp", "\\", "Enter", "", "Left", // Col 13 (78-83)
+    "PrtSc", "Ins", "Del", "Pause", "Up", "Down", // Col 14 (84-89)
+    "ScrLk", "Home", "End", "PgUp", "PgDn", "Right", // Col 15 (90-95)
+];
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -402,6 +546,31 @@ mod tests {
         assert_eq!(active.len(), expected);
     }
 
+    #[test]
+    fn test_m3v5he_profile() {
+        let profile = M3V5HeProfile::new();
+        assert_eq!(profile.id(), 2874);
+        assert_eq!(profile.pid(), 0x5030);
+        assert_eq!(M3_V5_HE_KEY_NAMES.len(), MATRIX_SIZE_M3_V5);
+        assert_eq!(profile.matrix_size(), MATRIX_SIZE_M3_V5);
+
+        // 86 keys with a HID code, plus Fn
+        assert_eq!(profile.active_positions().len(), 86);
+        assert_eq!(profile.matrix_key_name(71), "Fn");
+
+        // Alpha block matches the M1 V5 HE; the nav cluster doesn't
+        assert_eq!(profile.matrix_key_name(14), "W");
+        assert_eq!(profile.hid_to_position(26), Some(14));
+        assert_eq!(profile.hid_to_position(76), Some(86)); // Delete
+        assert_eq!(profile.hid_to_position(79), Some(95)); // Right
+
+        // Every named key has a HID code, except Fn
+        for (pos, &hid) in M3_V5_HE_LED_MATRIX.iter().enumerate() {
+            let name = M3_V5_HE_KEY_NAMES[pos];
+            assert_eq!(hid != 0, !name.is_empty() && name != "Fn", "position {pos}");
+        }
+    }
+
     #[test]
     fn test_variant_profiles() {
         let wireless = M1V5HeProfile::wireless();
@@ -25,6 +25,9 @@ pub struct JsonProfile {
     pub layer_count: u8,
     pub led_matrix: Vec<u8>,
     pub matrix_key_names: Vec<String>,
+    /// Per LED grid row, the first matrix column that sits one LED column further right
+    #[serde(default)]
+    pub led_gap_columns: Option<Vec<u8>>,
     #[serde(default)]
     pub features: DeviceFeatures,
     #[serde(default)]
@@ -235,6 +238,10 @@ impl DeviceProfile for JsonProfileWrapper {
             .unwrap_or("?")
     }
 
+    fn led_gap_columns(&self) -> Option<&[u8]> {
+        self.profile.led_gap_columns.as_deref()
+    }
+
     fn has_magnetism(&self) -> bool {
         self.profile.features.magnetism
     }
@@ -7,7 +7,10 @@ pub mod registry;
 pub mod traits;
 pub mod types;
 
-pub use builtin::{M1V5HeProfile, M1_V5_HE_KEY_NAMES, M1_V5_HE_LED_MATRIX};
+pub use builtin::{
+    M1V5HeProfile, M3V5HeProfile, M1_V5_HE_KEY_NAMES, M1_V5_HE_LED_GAP_COLUMNS,
+    M1_V5_HE_LED_MATRIX, M3_V5_HE_KEY_NAMES, M3_V5_HE_LED_MATRIX,
+};
 pub use json::{JsonProfile, JsonProfileWrapper, LoadError};
 pub use registry::{profile_registry, ProfileRegistry};
 pub use traits::{DeviceProfile, DeviceProfileExt};
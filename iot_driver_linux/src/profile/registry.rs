@@ -1,14 +1,15 @@
 // Profile registry
 // Central registry for looking up device profiles by VID/PID
 
-use super::builtin::M1V5HeProfile;
+use super::builtin::{M1V5HeProfile, M3V5HeProfile};
 use super::json::{JsonProfileWrapper, LoadError};
 use super::traits::DeviceProfile;
-use crate::device_loader::{DeviceDatabase, JsonDeviceDefinition};
+use crate::device_loader::{user_device_files, DeviceDatabase, JsonDeviceDefinition};
 use std::collections::HashMap;
-use std::path::Path;
-use std::sync::Arc;
-use tracing::debug;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, warn};
 
 /// Registry for device profiles
 /// Provides lookup by VID/PID or device ID
@@ -18,8 +19,9 @@ pub struct ProfileRegistry {
     by_vid_pid: HashMap<(u16, u16), Vec<Arc<dyn DeviceProfile>>>,
     /// Profiles indexed by ID
     by_id: HashMap<u32, Arc<dyn DeviceProfile>>,
-    /// Device database loaded from JSON for feature lookup
-    device_db: Option<DeviceDatabase>,
+    /// Device database loaded from JSON for feature lookup. Swapped on
+    /// reload; see [`Self::reload_device_database`].
+    device_db: RwLock<Option<&'static DeviceDatabase>>,
 }
 
 impl ProfileRegistry {
@@ -28,7 +30,7 @@ impl ProfileRegistry {
         Self {
             by_vid_pid: HashMap::new(),
             by_id: HashMap::new(),
-            device_db: None,
+            device_db: RwLock::new(None),
         }
     }
 
@@ -47,27 +49,73 @@ impl ProfileRegistry {
         self.register(Arc::new(M1V5HeProfile::wireless())); // BT PID 0x503A
         self.register(Arc::new(M1V5HeProfile::dongle())); // 2.4GHz PID 0x5038
         self.register(Arc::new(M1V5HeProfile::wired())); // USB PID 0x5030
+
+        // M3 V5 HE shares PID 0x5030; registered after the M1 so VID/PID
+        // lookups without a device ID keep returning the M1
+        self.register(Arc::new(M3V5HeProfile::new()));
     }
 
-    /// Load the device database from default paths
+    /// Load the device database from default paths, plus the user's
+    /// definitions in `~/.config/monsgeek/devices`
     pub fn load_device_database(&mut self) {
-        match DeviceDatabase::load_default() {
-            Ok(db) => {
-                self.device_db = Some(db);
-            }
-            Err(e) => {
-                debug!("Device database not loaded: {}", e);
-            }
+        if let Err(e) = self.reload_device_database() {
+            debug!("Device database not loaded: {}", e);
         }
     }
 
+    /// Re-read the device database and the user definitions; returns the
+    /// new device count and version. On error the current database stays.
+    ///
+    /// Lookups hand out plain references, so a replaced database is leaked
+    /// rather than freed. Reloads only happen when the user edits a
+    /// definition, so that costs little.
+    pub fn reload_device_database(&self) -> Result<(usize, u32), String> {
+        let db = DeviceDatabase::load_with_user_dir()?;
+        let stats = (db.len(), db.version());
+        let db: &'static DeviceDatabase = Box::leak(Box::new(db));
+        *self.device_db.write().unwrap_or_else(|e| e.into_inner()) = Some(db);
+        Ok(stats)
+    }
+
+    /// Reload whenever a file in `dir` is added, removed or modified,
+    /// checking every `interval` on a background thread.
+    pub fn watch_device_dir(&'static self, dir: PathBuf, interval: Duration) {
+        let stamp = move || -> Vec<(PathBuf, Option<SystemTime>)> {
+            user_device_files(&dir)
+                .into_iter()
+                .map(|p| {
+                    let modified = std::fs::metadata(&p).and_then(|m| m.modified()).ok();
+                    (p, modified)
+                })
+                .collect()
+        };
+        std::thread::spawn(move || {
+            let mut last = stamp();
+            loop {
+                std::thread::sleep(interval);
+                let now = stamp();
+                if now == last {
+                    continue;
+                }
+                last = now;
+                match self.reload_device_database() {
+                    Ok((count, _)) => info!("Device definitions changed, reloaded {count} devices"),
+                    Err(e) => warn!("Device definitions changed but reload failed: {e}"),
+                }
+            }
+        });
+    }
+
+    fn db(&self) -> Option<&'static DeviceDatabase> {
+        *self.device_db.read().unwrap_or_else(|e| e.into_inner())
+    }
+
     /// Get device info from the database by VID/PID
     /// This provides access to device features even for devices without builtin profiles
     /// WARNING: Returns arbitrary first match if multiple devices share the same VID/PID.
     /// Prefer `get_device_info_by_id()` when device ID is available.
     pub fn get_device_info(&self, vid: u16, pid: u16) -> Option<&JsonDeviceDefinition> {
-        self.device_db
-            .as_ref()
+        self.db()
             .and_then(|db| db.find_by_vid_pid(vid, pid).into_iter().next())
     }
 
@@ -76,9 +124,7 @@ impl ProfileRegistry {
     /// Returns `None` for the few IDs shared by several products; pass the USB IDs to
     /// [`Self::get_device_info_by_id_and_usb`] to break those ties.
     pub fn get_device_info_by_id(&self, device_id: i32) -> Option<&JsonDeviceDefinition> {
-        self.device_db
-            .as_ref()
-            .and_then(|db| db.find_by_id(device_id))
+        self.db().and_then(|db| db.find_by_id(device_id))
     }
 
     /// Get device info by firmware device ID, disambiguated by the USB IDs it was
@@ -89,8 +135,7 @@ impl ProfileRegistry {
         vid: u16,
         pid: u16,
     ) -> Option<&JsonDeviceDefinition> {
-        self.device_db
-            .as_ref()
+        self.db()
             .and_then(|db| db.find_by_id_and_usb(device_id, vid, pid))
     }
 
@@ -101,8 +146,7 @@ impl ProfileRegistry {
         pid: u16,
         company: &str,
     ) -> Option<&JsonDeviceDefinition> {
-        self.device_db
-            .as_ref()
+        self.db()
             .and_then(|db| db.find_by_vid_pid_company(vid, pid, company))
     }
 
@@ -125,19 +169,22 @@ impl ProfileRegistry {
         pid: u16,
         device_id: i32,
     ) -> Option<&crate::device_loader::JsonDeviceMatrix> {
-        self.device_db
-            .as_ref()
-            .and_then(|db| db.get_matrix(vid, pid, device_id))
+        self.db().and_then(|db| db.get_matrix(vid, pid, device_id))
+    }
+
+    /// The loaded device database, if any
+    pub fn device_database(&self) -> Option<&'static DeviceDatabase> {
+        self.db()
     }
 
     /// Check if device database is loaded
     pub fn has_device_database(&self) -> bool {
-        self.device_db.is_some()
+        self.db().is_some()
     }
 
     /// Get device database stats
     pub fn device_database_stats(&self) -> Option<(usize, u32)> {
-        self.device_db.as_ref().map(|db| (db.len(), db.version()))
+        self.db().map(|db| (db.len(), db.version()))
     }
 
     /// Register a profile in the registry
@@ -197,6 +244,24 @@ impl ProfileRegistry {
         self.by_id.get(&id).cloned()
     }
 
+    /// Find the profile for a connected device.
+    ///
+    /// The firmware device ID decides when it's known: PIDs are shared across
+    /// boards (the M3 V5 HE answers on the M1 V5 HE's 0x5030), so a device ID
+    /// without a builtin profile gets none rather than another board's layout.
+    /// VID/PID is only used when the ID couldn't be read.
+    pub fn find_for_device(
+        &self,
+        device_id: Option<i32>,
+        vid: u16,
+        pid: u16,
+    ) -> Option<Arc<dyn DeviceProfile>> {
+        match device_id {
+            Some(id) => self.find_by_id(id as u32),
+            None => self.find_by_vid_pid(vid, pid),
+        }
+    }
+
     /// Check if a VID/PID is registered
     pub fn has_vid_pid(&self, vid: u16, pid: u16) -> bool {
         self.by_vid_pid.contains_key(&(vid, pid))
@@ -310,6 +375,26 @@ mod tests {
         assert_eq!(profile.pid(), 0x5030);
     }
 
+    #[test]
+    fn test_find_for_device() {
+        let registry = ProfileRegistry::with_builtins();
+
+        let m3 = registry
+            .find_for_device(Some(2874), 0x3151, 0x5030)
+            .unwrap();
+        assert_eq!(m3.display_name(), "MonsGeek M3 V5 HE");
+        assert_eq!(m3.matrix_size(), 96);
+
+        // Another board on the same PID doesn't borrow the M1 V5 HE layout
+        assert!(registry
+            .find_for_device(Some(2585), 0x3151, 0x5030)
+            .is_none());
+
+        // Without a device ID, VID/PID still finds the M1
+        let m1 = registry.find_for_device(None, 0x3151, 0x5030).unwrap();
+        assert_eq!(m1.id(), 2949);
+    }
+
     #[test]
     fn test_all_vid_pids() {
         let registry = ProfileRegistry::with_builtins();
@@ -54,6 +54,12 @@ pub trait DeviceProfile: Send + Sync {
     /// Get key name for a matrix position
     fn matrix_key_name(&self, position: u8) -> &str;
 
+    /// LED grid gaps: per row, the first matrix column that sits one LED
+    /// column further right (None if not known)
+    fn led_gap_columns(&self) -> Option<&[u8]> {
+        None
+    }
+
     /// Get all active matrix positions with their HID codes
     fn active_positions(&self) -> Vec<(usize, u8)> {
         self.led_matrix()
@@ -739,6 +739,24 @@ pub mod hid {
             }
         }
 
+        // Numpad keys as the device database names them (Numpad7, NumpadAdd)
+        if let Some(rest) = name_lower.strip_prefix("numpad") {
+            return match rest {
+                "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8" | "9" => {
+                    Some(0x59 + (rest.as_bytes()[0] - b'1'))
+                }
+                "0" => Some(0x62),
+                "divide" => Some(0x54),
+                "multiply" => Some(0x55),
+                "subtract" => Some(0x56),
+                "add" => Some(0x57),
+                "enter" => Some(0x58),
+                "decimal" => Some(0x63),
+                "equal" => Some(0x67),
+                _ => None,
+            };
+        }
+
         // Common aliases (matrix key names, abbreviations, etc.)
         match name_lower.as_str() {
             "esc" => Some(0x29),
@@ -751,8 +769,8 @@ pub mod hid {
             "rshf" => Some(0xE5),
             "lctl" => Some(0xE0),
             "rctl" => Some(0xE4),
-            "win" | "lwin" | "super" | "lsuper" | "cmd" | "lcmd" => Some(0xE3),
-            "rwin" | "rsuper" | "rcmd" => Some(0xE7),
+            "win" | "lwin" | "lmeta" | "super" | "lsuper" | "cmd" | "lcmd" => Some(0xE3),
+            "rwin" | "rmeta" | "rsuper" | "rcmd" => Some(0xE7),
             "printscreen" | "prtsc" => Some(0x46),
             "scrlk" => Some(0x47),
             "numlk" => Some(0x53),
@@ -760,7 +778,8 @@ pub mod hid {
             "spc" => Some(0x2C),
             "pgup" => Some(0x4B),
             "pgdn" | "pgdown" => Some(0x4E),
-            "nonusbs" | "intlbs" => Some(0x64),
+            "nonusbs" | "intlbs" | "intlbackslash" => Some(0x64),
+            "intlhash" => Some(0x32),
             "ent" => Some(0x28),
             "intlro" => Some(0x87),
             _ => None,
@@ -977,6 +996,16 @@ pub mod firmware_update {
                 Self::Dongle => "dongle",
             }
         }
+
+        /// Target for a device found in bootloader mode; RF modules are
+        /// updated through the dongle, not flashed directly.
+        pub fn from_boot_mode(kind: monsgeek_transport::BootModeKind) -> Option<Self> {
+            match kind {
+                monsgeek_transport::BootModeKind::Keyboard => Some(Self::Keyboard),
+                monsgeek_transport::BootModeKind::Dongle => Some(Self::Dongle),
+                monsgeek_transport::BootModeKind::Rf => None,
+            }
+        }
     }
 
     /// Firmware data chunk size
@@ -0,0 +1,327 @@
+//! QMK keymap import (`iot_driver keymap import-qmk`).
+//!
+//! Reads a QMK Configurator JSON export or a `keymap.c` and plans the
+//! keymatrix writes that carry the layout over. QMK lists a layer's keys in
+//! reading order (the `LAYOUT()` macro), so positions are matched to this
+//! keyboard's keys row by row, left to right (see
+//! [`keymap::reading_order`](crate::keymap::reading_order)).
+//!
+//! QMK layer 0 becomes the base layer and the layer the first `MO(n)` on
+//! layer 0 points at becomes the Fn layer. Everything else — other layers,
+//! layer-taps, mod-taps, tap dance, RGB and firmware keycodes — has no
+//! keymatrix equivalent and is reported instead of written.
+
+use serde::Deserialize;
+
+use crate::key_action::KeyAction;
+use crate::keyboard_config::ConfigError;
+use crate::keymap::{default_keycode, Layer};
+use crate::via_keymap::qmk_to_action;
+
+/// QMK keycode families the keymatrix can't express: (prefix, feature).
+const UNSUPPORTED: &[(&str, &str)] = &[
+    ("LT(", "layer-tap"),
+    ("MT(", "mod-tap"),
+    ("TG(", "layer toggle"),
+    ("TO(", "layer switch"),
+    ("TT(", "layer tap-toggle"),
+    ("DF(", "default layer switch"),
+    ("OSL(", "one-shot layer"),
+    ("OSM(", "one-shot modifier"),
+    ("LM(", "layer with modifiers"),
+    ("TD(", "tap dance"),
+    ("RGB_", "RGB control"),
+    ("RM_", "RGB control"),
+    ("UG_", "RGB control"),
+    ("BL_", "backlight control"),
+    ("QK_", "QMK firmware keycode"),
+    ("SH_", "swap hands"),
+    ("KC_GESC", "grave escape"),
+];
+
+/// The layers of a QMK keymap, as keycode strings.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct QmkKeymap {
+    #[serde(default)]
+    pub keyboard: Option<String>,
+    #[serde(default)]
+    pub keymap: Option<String>,
+    pub layers: Vec<Vec<String>>,
+}
+
+/// A keycode that was not carried over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skipped {
+    /// QMK layer and position
+    pub layer: usize,
+    pub position: usize,
+    pub keycode: String,
+    pub reason: String,
+}
+
+/// What importing a keymap onto this keyboard does.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportPlan {
+    /// QMK layer used as the Fn layer
+    pub fn_layer: Option<usize>,
+    /// Desired action per key, for the base and Fn layers
+    pub keys: Vec<(u8, Layer, KeyAction)>,
+    pub skipped: Vec<Skipped>,
+    /// QMK layers with no keymatrix equivalent
+    pub dropped_layers: Vec<usize>,
+    /// QMK positions beyond this keyboard's keys
+    pub extra_positions: usize,
+    /// Base-layer keys whose QMK keycode isn't this keyboard's legend; a
+    /// high share means the layouts don't line up.
+    pub legend_mismatches: usize,
+}
+
+impl QmkKeymap {
+    /// Parse a QMK JSON export, or a `keymap.c` when it isn't JSON.
+    pub fn parse(s: &str) -> Result<Self, ConfigError> {
+        if s.trim_start().starts_with('{') {
+            serde_json::from_str(s).map_err(|e| ConfigError::invalid("json", e.to_string()))
+        } else {
+            let layers = parse_keymap_c(s)?;
+            Ok(Self {
+                keyboard: None,
+                keymap: None,
+                layers,
+            })
+        }
+    }
+
+    /// The QMK layer the Fn key should open: the first `MO(n)` on layer 0.
+    pub fn fn_layer(&self) -> Option<usize> {
+        self.layers.first()?.iter().find_map(|k| {
+            let n = k.trim().strip_prefix("MO(")?.strip_suffix(')')?;
+            n.trim()
+                .parse()
+                .ok()
+                .filter(|&n| n > 0 && n < self.layers.len())
+        })
+    }
+
+    /// Plan the import onto keys at matrix indices `order` (reading order).
+    pub fn plan(&self, order: &[u8]) -> ImportPlan {
+        let fn_layer = self.fn_layer();
+        let mut plan = ImportPlan {
+            fn_layer,
+            ..Default::default()
+        };
+        for (l, keys) in self.layers.iter().enumerate() {
+            let layer = match l {
+                0 => Layer::Base,
+                _ if Some(l) == fn_layer => Layer::Fn,
+                _ => {
+                    plan.dropped_layers.push(l);
+                    continue;
+                }
+            };
+            plan.extra_positions = plan
+                .extra_positions
+                .max(keys.len().saturating_sub(order.len()));
+            for (position, (keycode, &index)) in keys.iter().zip(order).enumerate() {
+                match translate(keycode, index, layer, fn_layer) {
+                    Ok(action) => {
+                        if layer == Layer::Base && action != KeyAction::Key(default_keycode(index))
+                        {
+                            plan.legend_mismatches += 1;
+                        }
+                        plan.keys.push((index, layer, action));
+                    }
+                    Err(reason) => plan.skipped.push(Skipped {
+                        layer: l,
+                        position,
+                        keycode: keycode.clone(),
+                        reason,
+                    }),
+                }
+            }
+        }
+        plan
+    }
+}
+
+/// One QMK keycode as a key action on `layer`.
+fn translate(
+    keycode: &str,
+    index: u8,
+    layer: Layer,
+    fn_layer: Option<usize>,
+) -> Result<KeyAction, String> {
+    let keycode = keycode.trim();
+    if let Some(n) = keycode
+        .strip_prefix("MO(")
+        .and_then(|s| s.strip_suffix(')'))
+    {
+        return match n.trim().parse().ok() {
+            Some(n) if Some(n) == fn_layer => Ok(KeyAction::Fn),
+            _ => Err("momentary layer with no keymatrix equivalent".to_string()),
+        };
+    }
+    if let Some((_, feature)) = UNSUPPORTED.iter().find(|(p, _)| keycode.starts_with(p)) {
+        return Err(format!("{feature} not supported"));
+    }
+    if keycode.contains("_T(") {
+        return Err("mod-tap not supported".to_string());
+    }
+    qmk_to_action(keycode, index, layer)
+}
+
+/// Layers of a `keymap.c`: the arguments of each `LAYOUT*()` call, in order.
+pub fn parse_keymap_c(src: &str) -> Result<Vec<Vec<String>>, ConfigError> {
+    let src = strip_comments(src);
+    let start = src
+        .find("keymaps")
+        .ok_or_else(|| ConfigError::invalid("keymap.c", "no `keymaps` array found"))?;
+    let mut rest = &src[start..];
+    let mut layers = Vec::new();
+    while let Some(at) = rest.find("LAYOUT") {
+        let after = &rest[at..];
+        let Some(open) = after.find('(') else {
+            break;
+        };
+        // Only LAYOUT identifiers directly followed by their argument list
+        if !after[..open]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c.is_whitespace())
+        {
+            rest = &after[6..];
+            continue;
+        }
+        let (args, len) = call_args(&after[open + 1..]).ok_or_else(|| {
+            ConfigError::invalid(format!("layer {}", layers.len()), "unbalanced parentheses")
+        })?;
+        layers.push(args);
+        rest = &after[open + 1 + len..];
+    }
+    if layers.is_empty() {
+        return Err(ConfigError::invalid("keymap.c", "no LAYOUT() layers found"));
+    }
+    Ok(layers)
+}
+
+/// Split a call's arguments at top-level commas; `s` starts after the `(`.
+/// Returns the arguments and how many bytes the list took, `)` included.
+fn call_args(s: &str) -> Option<(Vec<String>, usize)> {
+    let mut depth = 0;
+    let mut args = Vec::new();
+    let mut current = String::new();
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                let last = current.trim();
+                if !last.is_empty() {
+                    args.push(last.to_string());
+                }
+                return Some((args, i + 1));
+            }
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        if !c.is_whitespace() {
+            current.push(c);
+        }
+    }
+    None
+}
+
+fn strip_comments(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut rest = src;
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix("//") {
+            rest = r.find('\n').map_or("", |n| &r[n..]);
+        } else if let Some(r) = rest.strip_prefix("/*") {
+            rest = r.find("*/").map_or("", |n| &r[n + 2..]);
+            out.push(' ');
+        } else {
+            let c = rest.chars().next().unwrap();
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYMAP_C: &str = r#"
+        #include QMK_KEYBOARD_H
+        /* layers */
+        const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+            [0] = LAYOUT_ansi( // base
+                KC_ESC,  KC_F1,   LCTL(KC_C),
+                KC_CAPS, MO(1),   LT(2, KC_SPC)
+            ),
+            [1] = LAYOUT_ansi(
+                _______, KC_MPLY, XXXXXXX,
+                _______, _______, RGB_TOG
+            ),
+            [2] = LAYOUT_ansi(KC_A, KC_B, KC_C, KC_D, KC_E, KC_F),
+        };
+    "#;
+
+    #[test]
+    fn parses_keymap_c_layers() {
+        let layers = parse_keymap_c(KEYMAP_C).unwrap();
+        assert_eq!(layers.len(), 3);
+        assert_eq!(
+            layers[0],
+            [
+                "KC_ESC",
+                "KC_F1",
+                "LCTL(KC_C)",
+                "KC_CAPS",
+                "MO(1)",
+                "LT(2,KC_SPC)"
+            ]
+        );
+        assert_eq!(layers[1][1], "KC_MPLY");
+        assert!(parse_keymap_c("int main() {}").is_err());
+    }
+
+    #[test]
+    fn plans_base_and_fn_layers_and_reports_the_rest() {
+        let keymap = QmkKeymap::parse(KEYMAP_C).unwrap();
+        assert_eq!(keymap.fn_layer(), Some(1));
+        // Esc, F1, F2 / Caps, A, S on a 75%
+        let plan = keymap.plan(&[0, 6, 12, 3, 9, 15]);
+
+        assert_eq!(plan.dropped_layers, vec![2]);
+        assert!(plan
+            .keys
+            .contains(&(12, Layer::Base, "Ctrl+C".parse().unwrap())));
+        assert!(plan.keys.contains(&(9, Layer::Base, KeyAction::Fn)));
+        assert!(plan
+            .keys
+            .contains(&(6, Layer::Fn, KeyAction::Consumer(0xCD))));
+        assert!(plan.keys.contains(&(0, Layer::Fn, KeyAction::Disabled)));
+
+        let skipped: Vec<_> = plan.skipped.iter().map(|s| s.keycode.as_str()).collect();
+        assert_eq!(skipped, ["LT(2,KC_SPC)", "RGB_TOG"]);
+        assert!(plan.skipped[0].reason.contains("layer-tap"));
+        // Ctrl+C and MO(1) replace the F2 and A legends
+        assert_eq!(plan.legend_mismatches, 2);
+    }
+
+    #[test]
+    fn parses_configurator_json() {
+        let json = r#"{"version": 1, "keyboard": "kbd/x", "keymap": "default",
+            "layout": "LAYOUT", "layers": [["KC_ESC", "MO(1)"], ["KC_TRNS", "KC_TRNS"]]}"#;
+        let keymap = QmkKeymap::parse(json).unwrap();
+        assert_eq!(keymap.keyboard.as_deref(), Some("kbd/x"));
+        let plan = keymap.plan(&[0]);
+        assert_eq!(plan.extra_positions, 1);
+        assert_eq!(plan.keys.len(), 2);
+    }
+}
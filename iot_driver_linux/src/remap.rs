@@ -0,0 +1,675 @@
+//! Software remapping layer over uinput.
+//!
+//! For remaps the firmware can't express: per-application remaps,
+//! multi-key sequences and layers beyond Fn. Configured under `[remap]` in
+//! `settings.toml` with the same key names and action syntax as
+//! `iot_driver remap` (`CapsLock`, `Ctrl+Shift+Esc`, ...). The
+//! daemon grabs the keyboard's evdev node, so the desktop no longer sees it
+//! directly, and replays the rewritten events through a virtual keyboard.
+//! The virtual keyboard reuses the keyboard's VID:PID, so other evdev
+//! readers (idle dimming, typing stats) find it when they open the
+//! keyboard's nodes. Needs read access to `/dev/input/event*` and write
+//! access to `/dev/uinput`.
+
+use std::collections::HashMap;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::evdev::{self, EventReader, EV_KEY, EV_SYN, KEY_REPEAT, SYN_REPORT};
+use crate::key_action::KeyAction;
+use crate::protocol::hid;
+use monsgeek_keyboard::KeyboardInterface;
+
+/// Name of the virtual keyboard, skipped when grabbing.
+pub const VIRTUAL_NAME: &str = "MonsGeek software remap";
+
+/// How long the daemon waits for input before checking for other work.
+const TICK: Duration = Duration::from_millis(250);
+/// How often the daemon re-reads `settings.toml`.
+const CONFIG_RELOAD: Duration = Duration::from_secs(5);
+
+// uinput ioctls (`'U'`)
+const UI_SET_EVBIT: libc::c_ulong = 0x4004_5564;
+const UI_SET_KEYBIT: libc::c_ulong = 0x4004_5565;
+const UI_DEV_SETUP: libc::c_ulong = 0x405c_5503;
+const UI_DEV_CREATE: libc::c_ulong = 0x5501;
+const UI_DEV_DESTROY: libc::c_ulong = 0x5502;
+const EV_REP: u16 = 0x14;
+const BUS_USB: u16 = 0x03;
+/// Key codes the virtual keyboard can send (`KEY_ESC` up to the media
+/// range).
+const MAX_KEY: u16 = 0xff;
+
+/// One action (`"Ctrl+C"`) or a sequence of them typed in order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Actions {
+    One(String),
+    Sequence(Vec<String>),
+}
+
+/// A remap of one key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyRemap {
+    /// Key name, as for `iot_driver remap` (`CapsLock`, `RAlt`, `F13`)
+    pub key: String,
+    /// What it sends: a key or combo held with the key, `Disabled`, or a
+    /// list typed once per press
+    pub to: Actions,
+    /// Only while one of these apps is focused (case-insensitive substrings
+    /// of the app id, see `watch-focus`); empty = everywhere
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub apps: Vec<String>,
+}
+
+/// A layer active while its key is held.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemapLayer {
+    /// Key that activates the layer while held; it sends nothing itself
+    pub hold: String,
+    /// Key name → action(s), as for [`KeyRemap::to`]. Keys not listed
+    /// fall through to the layers below.
+    pub keys: std::collections::BTreeMap<String, Actions>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub apps: Vec<String>,
+}
+
+/// `[remap]` settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemapConfig {
+    pub enabled: bool,
+    /// Checked in order; the first entry for a key that applies wins, so
+    /// put per-app entries first
+    pub keys: Vec<KeyRemap>,
+    pub layers: Vec<RemapLayer>,
+}
+
+impl RemapConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What a key produces, as evdev key codes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    /// Pressed in order with the key (modifiers first), released in reverse
+    Hold(Vec<u16>),
+    /// Chords typed one after another on press
+    Sequence(Vec<Vec<u16>>),
+    Disabled,
+}
+
+/// Evdev code of a key name.
+fn key_code(name: &str) -> Result<u16, String> {
+    hid::key_code_from_name(name)
+        .and_then(evdev::hid_to_keycode)
+        .ok_or_else(|| crate::keymap::unknown_key_message(name, crate::keymap::hid_key_names()))
+}
+
+/// Evdev codes for one action (modifiers first); `None` for `Disabled`.
+fn action_codes(action: &str) -> Result<Option<Vec<u16>>, String> {
+    let hid_code =
+        |h: u8| evdev::hid_to_keycode(h).ok_or_else(|| format!("{action:?} has no Linux key code"));
+    let (mods, key) = match action.parse::<KeyAction>().map_err(|e| e.to_string())? {
+        KeyAction::Disabled => return Ok(None),
+        KeyAction::Key(key) => (0, key),
+        KeyAction::Combo { mods, key } => (mods, key),
+        other => return Err(format!("{other} can't be sent by the software layer")),
+    };
+    let mut codes = (0..8)
+        .filter(|bit| mods & (1 << bit) != 0)
+        .map(|bit| hid_code(0xE0 + bit))
+        .collect::<Result<Vec<_>, _>>()?;
+    codes.push(hid_code(key)?);
+    Ok(Some(codes))
+}
+
+impl Output {
+    fn parse(actions: &Actions) -> Result<Self, String> {
+        match actions {
+            Actions::One(action) => Ok(match action_codes(action)? {
+                Some(codes) => Output::Hold(codes),
+                None => Output::Disabled,
+            }),
+            Actions::Sequence(actions) => {
+                let mut chords = Vec::new();
+                for action in actions {
+                    chords.extend(action_codes(action)?);
+                }
+                Ok(Output::Sequence(chords))
+            }
+        }
+    }
+}
+
+fn app_matches(apps: &[String], app_id: &str) -> bool {
+    let app_id = app_id.to_lowercase();
+    apps.is_empty()
+        || (!app_id.is_empty() && apps.iter().any(|a| app_id.contains(&a.to_lowercase())))
+}
+
+struct Mapping {
+    apps: Vec<String>,
+    output: Output,
+}
+
+struct Layer {
+    hold: u16,
+    apps: Vec<String>,
+    keys: HashMap<u16, Output>,
+}
+
+/// What a held physical key produced, so its release matches its press
+/// even if the app or layers changed in between.
+#[derive(Debug, Clone, PartialEq)]
+enum Held {
+    Keys(Vec<u16>),
+    Layer(usize),
+    Nothing,
+}
+
+/// Rewrites key events per a [`RemapConfig`].
+pub struct Remapper {
+    keys: HashMap<u16, Vec<Mapping>>,
+    layers: Vec<Layer>,
+    held: HashMap<u16, Held>,
+    /// Active layers, most recent last
+    active: Vec<usize>,
+}
+
+impl Remapper {
+    pub fn new(config: &RemapConfig) -> Result<Self, String> {
+        let mut keys: HashMap<u16, Vec<Mapping>> = HashMap::new();
+        for remap in &config.keys {
+            let output = Output::parse(&remap.to).map_err(|e| format!("{}: {e}", remap.key))?;
+            keys.entry(key_code(&remap.key)?)
+                .or_default()
+                .push(Mapping {
+                    apps: remap.apps.clone(),
+                    output,
+                });
+        }
+        let layers = config
+            .layers
+            .iter()
+            .map(|layer| {
+                let keys = layer
+                    .keys
+                    .iter()
+                    .map(|(key, to)| {
+                        let output = Output::parse(to).map_err(|e| format!("{key}: {e}"))?;
+                        Ok((key_code(key)?, output))
+                    })
+                    .collect::<Result<_, String>>()?;
+                Ok(Layer {
+                    hold: key_code(&layer.hold)?,
+                    apps: layer.apps.clone(),
+                    keys,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            keys,
+            layers,
+            held: HashMap::new(),
+            active: Vec::new(),
+        })
+    }
+
+    /// Whether any entry depends on the focused app.
+    pub fn needs_focus(&self) -> bool {
+        self.keys.values().flatten().any(|m| !m.apps.is_empty())
+            || self.layers.iter().any(|l| !l.apps.is_empty())
+    }
+
+    fn output_for(&self, code: u16, app_id: &str) -> Option<&Output> {
+        let from_layers = self
+            .active
+            .iter()
+            .rev()
+            .find_map(|&i| self.layers[i].keys.get(&code));
+        from_layers.or_else(|| {
+            self.keys
+                .get(&code)?
+                .iter()
+                .find(|m| app_matches(&m.apps, app_id))
+                .map(|m| &m.output)
+        })
+    }
+
+    /// Rewrite a key press (1) or release (0) of evdev `code` while
+    /// `app_id` is focused; returns the key events to send.
+    pub fn key(&mut self, code: u16, value: i32, app_id: &str) -> Vec<(u16, i32)> {
+        if value == 0 {
+            return match self.held.remove(&code) {
+                Some(Held::Keys(keys)) => keys.iter().rev().map(|&k| (k, 0)).collect(),
+                Some(Held::Layer(layer)) => {
+                    self.active.retain(|&l| l != layer);
+                    Vec::new()
+                }
+                Some(Held::Nothing) => Vec::new(),
+                // Pressed before we grabbed the keyboard
+                None => vec![(code, 0)],
+            };
+        }
+        if self.held.contains_key(&code) {
+            return Vec::new();
+        }
+        let layer = self
+            .layers
+            .iter()
+            .position(|l| l.hold == code && app_matches(&l.apps, app_id));
+        let (held, events) = if let Some(layer) = layer {
+            self.active.push(layer);
+            (Held::Layer(layer), Vec::new())
+        } else {
+            match self.output_for(code, app_id) {
+                None => (Held::Keys(vec![code]), vec![(code, 1)]),
+                Some(Output::Hold(keys)) => (
+                    Held::Keys(keys.clone()),
+                    keys.iter().map(|&k| (k, 1)).collect(),
+                ),
+                Some(Output::Sequence(chords)) => {
+                    let mut events = Vec::new();
+                    for chord in chords {
+                        events.extend(chord.iter().map(|&k| (k, 1)));
+                        events.extend(chord.iter().rev().map(|&k| (k, 0)));
+                    }
+                    (Held::Nothing, events)
+                }
+                Some(Output::Disabled) => (Held::Nothing, Vec::new()),
+            }
+        };
+        self.held.insert(code, held);
+        events
+    }
+
+    /// Release everything still held (before ungrabbing).
+    pub fn release_all(&mut self) -> Vec<(u16, i32)> {
+        self.active.clear();
+        self.held
+            .drain()
+            .flat_map(|(_, held)| match held {
+                Held::Keys(keys) => keys.into_iter().rev().map(|k| (k, 0)).collect(),
+                _ => Vec::new(),
+            })
+            .collect()
+    }
+}
+
+/// A uinput keyboard that replays the rewritten events.
+pub struct VirtualKeyboard {
+    fd: OwnedFd,
+}
+
+impl VirtualKeyboard {
+    /// Create the device with the physical keyboard's VID:PID.
+    pub fn create(vid: u16, pid: u16) -> Result<Self, String> {
+        Self::create_named(VIRTUAL_NAME, vid, pid)
+    }
+
+    /// Create the device under another name, for other layers that grab
+    /// the keyboard.
+    pub fn create_named(name: &str, vid: u16, pid: u16) -> Result<Self, String> {
+        let os_err = |what: &str| format!("{what} failed: {}", std::io::Error::last_os_error());
+        // SAFETY: plain syscalls on a descriptor we own; `setup` is plain data
+        unsafe {
+            let raw = libc::open(
+                c"/dev/uinput".as_ptr(),
+                libc::O_WRONLY | libc::O_NONBLOCK | libc::O_CLOEXEC,
+            );
+            if raw < 0 {
+                return Err(os_err("opening /dev/uinput"));
+            }
+            let fd = OwnedFd::from_raw_fd(raw);
+            for ev in [EV_KEY, EV_REP] {
+                if libc::ioctl(raw, UI_SET_EVBIT, ev as libc::c_int) < 0 {
+                    return Err(os_err("UI_SET_EVBIT"));
+                }
+            }
+            for code in 1..=MAX_KEY {
+                libc::ioctl(raw, UI_SET_KEYBIT, code as libc::c_int);
+            }
+            let mut setup: libc::uinput_setup = std::mem::zeroed();
+            setup.id = libc::input_id {
+                bustype: BUS_USB,
+                vendor: vid,
+                product: pid,
+                version: 1,
+            };
+            for (dst, &src) in setup.name.iter_mut().zip(name.as_bytes()) {
+                *dst = src as libc::c_char;
+            }
+            if libc::ioctl(raw, UI_DEV_SETUP, &setup) < 0 {
+                return Err(os_err("UI_DEV_SETUP"));
+            }
+            if libc::ioctl(raw, UI_DEV_CREATE) < 0 {
+                return Err(os_err("UI_DEV_CREATE"));
+            }
+            Ok(Self { fd })
+        }
+    }
+
+    fn write(&self, kind: u16, code: u16, value: i32) -> Result<(), String> {
+        // SAFETY: input_event is plain data; the kernel fills in the time
+        let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+        event.type_ = kind;
+        event.code = code;
+        event.value = value;
+        let size = std::mem::size_of::<libc::input_event>();
+        let n = unsafe {
+            libc::write(
+                self.fd.as_raw_fd(),
+                &event as *const libc::input_event as *const libc::c_void,
+                size,
+            )
+        };
+        if n != size as isize {
+            return Err(format!(
+                "uinput write failed: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Send key events, each in its own report.
+    pub fn send(&self, events: &[(u16, i32)]) -> Result<(), String> {
+        for &(code, value) in events {
+            self.write(EV_KEY, code, value)?;
+            self.write(EV_SYN, SYN_REPORT, 0)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for VirtualKeyboard {
+    fn drop(&mut self) {
+        // SAFETY: destroying the device created in `create`
+        unsafe {
+            libc::ioctl(self.fd.as_raw_fd(), UI_DEV_DESTROY);
+        }
+    }
+}
+
+/// Keep `focused` set to the focused app id (see [`crate::focus::watch`]).
+fn spawn_focus_watcher(focused: Arc<Mutex<String>>) {
+    std::thread::spawn(move || {
+        let result = crate::focus::watch(|window| {
+            *focused.lock().unwrap_or_else(|e| e.into_inner()) = window.app_id;
+        });
+        if let Err(e) = result {
+            tracing::warn!("remap: per-app remaps unavailable: {e}");
+        }
+    });
+}
+
+/// Log a setup error once (until it changes), prefixed with `what`.
+pub(crate) fn warn_once(last_error: &mut String, what: &str, e: String) {
+    if e != *last_error {
+        tracing::warn!("{what}: {e}");
+        *last_error = e;
+    }
+}
+
+/// Rewrites the key events of a keyboard grabbed by [`replay`].
+pub(crate) trait KeyRewrite {
+    /// Events to send for a press (1) or release (0) of evdev `code`.
+    fn key(&mut self, code: u16, value: i32) -> Vec<(u16, i32)>;
+    /// Releases for every key still held through the rewrite.
+    fn release_all(&mut self) -> Vec<(u16, i32)>;
+    /// Called every few seconds to pick up edits; false ends the session.
+    fn reload(&mut self) -> bool;
+}
+
+/// Grab the keyboard's nodes (those nobody else holds) and replay its key
+/// events through a virtual keyboard named `name`, rewritten by `rewrite`.
+///
+/// Runs until `stop` is set, the keyboard goes away or a reload declines,
+/// then releases the keys still held and ungrabs. Errors if the session
+/// can't start; `what` prefixes the log lines.
+pub(crate) fn replay(
+    vid: u16,
+    pid: u16,
+    name: &str,
+    what: &str,
+    rewrite: &mut impl KeyRewrite,
+    stop: &AtomicBool,
+) -> Result<(), String> {
+    let mut reader = EventReader::open_keyboard(vid, pid, name);
+    if reader.is_empty() {
+        return Err("no readable keyboard input node (not in the 'input' group?)".into());
+    }
+    let virtual_kb = VirtualKeyboard::create_named(name, vid, pid)
+        .map_err(|e| format!("{e} (no write access to /dev/uinput?)"))?;
+    reader.grab_available()?;
+    tracing::info!("{what}: grabbed {} input node(s)", reader.len());
+
+    let mut next_reload = Instant::now() + CONFIG_RELOAD;
+    while !stop.load(Ordering::Relaxed) {
+        for ev in reader.poll(TICK) {
+            if ev.kind != EV_KEY || ev.value == KEY_REPEAT {
+                continue;
+            }
+            if let Err(e) = virtual_kb.send(&rewrite.key(ev.code, ev.value)) {
+                tracing::warn!("{what}: {e}");
+            }
+        }
+        if Instant::now() < next_reload {
+            continue;
+        }
+        next_reload = Instant::now() + CONFIG_RELOAD;
+        if evdev::find_physical_nodes(vid, pid, name).is_empty() || !rewrite.reload() {
+            break;
+        }
+    }
+    let _ = virtual_kb.send(&rewrite.release_all());
+    // Dropping the reader ungrabs the keyboard
+    Ok(())
+}
+
+/// A [`Remapper`] session: remaps for the focused app and ends once the
+/// `[remap]` settings change.
+struct RemapSession<'a> {
+    remapper: Remapper,
+    config: RemapConfig,
+    focused: &'a Mutex<String>,
+}
+
+impl KeyRewrite for RemapSession<'_> {
+    fn key(&mut self, code: u16, value: i32) -> Vec<(u16, i32)> {
+        let app_id = self
+            .focused
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        self.remapper.key(code, value, &app_id)
+    }
+
+    fn release_all(&mut self) -> Vec<(u16, i32)> {
+        self.remapper.release_all()
+    }
+
+    fn reload(&mut self) -> bool {
+        crate::settings::Settings::load().remap == self.config
+    }
+}
+
+/// Daemon loop for `[remap]`: grab the keyboard while enabled and replay rewritten events, re-reading
+/// the settings so edits apply without a restart. `open` finds the
+/// keyboard (for its VID:PID); it is retried until one shows up.
+pub fn run_remap(open: impl Fn() -> Result<KeyboardInterface, String>) {
+    let focused = Arc::new(Mutex::new(String::new()));
+    let mut watching_focus = false;
+    let mut last_error = String::new();
+    let never = AtomicBool::new(false);
+    loop {
+        let config = crate::settings::Settings::load().remap;
+        if !config.enabled {
+            std::thread::sleep(CONFIG_RELOAD);
+            continue;
+        }
+        let remapper = match Remapper::new(&config) {
+            Ok(r) => r,
+            Err(e) => {
+                warn_once(&mut last_error, "remap", e);
+                std::thread::sleep(CONFIG_RELOAD);
+                continue;
+            }
+        };
+        if remapper.needs_focus() && !watching_focus {
+            spawn_focus_watcher(Arc::clone(&focused));
+            watching_focus = true;
+        }
+        let (vid, pid) = match open() {
+            Ok(kb) => (kb.vid(), kb.pid()),
+            Err(e) => {
+                tracing::debug!("remap: no keyboard: {e}");
+                std::thread::sleep(CONFIG_RELOAD);
+                continue;
+            }
+        };
+        let mut session = RemapSession {
+            remapper,
+            config,
+            focused: &focused,
+        };
+        match replay(vid, pid, VIRTUAL_NAME, "remap", &mut session, &never) {
+            Ok(()) => last_error.clear(),
+            Err(e) => {
+                warn_once(&mut last_error, "remap", e);
+                std::thread::sleep(CONFIG_RELOAD);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPS: u16 = 58;
+    const H: u16 = 35;
+    const J: u16 = 36;
+    const LEFT: u16 = 105;
+    const LCTRL: u16 = 29;
+    const LSHIFT: u16 = 42;
+    const RALT: u16 = 100;
+    const ESC: u16 = 1;
+    const C: u16 = 46;
+    const V: u16 = 47;
+    const F13: u16 = 183;
+
+    fn config(toml: &str) -> Remapper {
+        let config: RemapConfig = toml::from_str(toml).unwrap();
+        Remapper::new(&config).unwrap()
+    }
+
+    #[test]
+    fn parses_actions_into_key_codes() {
+        assert_eq!(action_codes("Esc").unwrap(), Some(vec![ESC]));
+        assert_eq!(
+            action_codes("Ctrl+Shift+Esc").unwrap(),
+            Some(vec![LCTRL, LSHIFT, ESC])
+        );
+        assert_eq!(action_codes("Disabled").unwrap(), None);
+        assert!(action_codes("Macro(1)").is_err());
+        assert!(key_code("NoSuchKey").is_err());
+        assert_eq!(key_code("CapsLock").unwrap(), CAPS);
+    }
+
+    #[test]
+    fn remaps_hold_and_pass_through() {
+        let mut remap = config(
+            r#"
+            [[keys]]
+            key = "RAlt"
+            to = "Ctrl+Shift+Esc"
+            [[keys]]
+            key = "CapsLock"
+            to = "Disabled"
+            "#,
+        );
+        assert_eq!(
+            remap.key(RALT, 1, ""),
+            vec![(LCTRL, 1), (LSHIFT, 1), (ESC, 1)]
+        );
+        assert_eq!(
+            remap.key(RALT, 0, ""),
+            vec![(ESC, 0), (LSHIFT, 0), (LCTRL, 0)]
+        );
+        assert_eq!(remap.key(CAPS, 1, ""), vec![]);
+        assert_eq!(remap.key(CAPS, 0, ""), vec![]);
+        assert_eq!(remap.key(H, 1, ""), vec![(H, 1)]);
+        assert_eq!(remap.key(H, 0, ""), vec![(H, 0)]);
+    }
+
+    #[test]
+    fn sequences_are_typed_on_press() {
+        let mut remap = config(
+            r#"
+            [[keys]]
+            key = "F13"
+            to = ["Ctrl+C", "Ctrl+V"]
+            "#,
+        );
+        assert_eq!(
+            remap.key(F13, 1, ""),
+            vec![
+                (LCTRL, 1),
+                (C, 1),
+                (C, 0),
+                (LCTRL, 0),
+                (LCTRL, 1),
+                (V, 1),
+                (V, 0),
+                (LCTRL, 0)
+            ]
+        );
+        assert_eq!(remap.key(F13, 0, ""), vec![]);
+    }
+
+    #[test]
+    fn per_app_entries_apply_only_while_focused() {
+        let mut remap = config(
+            r#"
+            [[keys]]
+            key = "H"
+            to = "Left"
+            apps = ["game"]
+            "#,
+        );
+        assert!(remap.needs_focus());
+        assert_eq!(remap.key(H, 1, "steam_game"), vec![(LEFT, 1)]);
+        // Released after focus moved: still releases what was pressed
+        assert_eq!(remap.key(H, 0, "firefox"), vec![(LEFT, 0)]);
+        assert_eq!(remap.key(H, 1, "firefox"), vec![(H, 1)]);
+    }
+
+    #[test]
+    fn layers_apply_while_their_key_is_held() {
+        let mut remap = config(
+            r#"
+            [[layers]]
+            hold = "CapsLock"
+            keys = { H = "Left" }
+            "#,
+        );
+        assert_eq!(remap.key(CAPS, 1, ""), vec![]);
+        assert_eq!(remap.key(H, 1, ""), vec![(LEFT, 1)]);
+        // Keys the layer doesn't list pass through
+        assert_eq!(remap.key(J, 1, ""), vec![(J, 1)]);
+        assert_eq!(remap.key(CAPS, 0, ""), vec![]);
+        assert_eq!(remap.key(H, 0, ""), vec![(LEFT, 0)]);
+        assert_eq!(remap.key(H, 1, ""), vec![(H, 1)]);
+        let mut released = remap.release_all();
+        released.sort();
+        assert_eq!(released, vec![(H, 0), (J, 0)]);
+    }
+}
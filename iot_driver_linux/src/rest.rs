@@ -0,0 +1,387 @@
+// REST/JSON façade for the driver server
+//
+// Served on the gRPC port under `/api/v1` when the server runs with `--rest`,
+// for browsers and scripts that struggle with gRPC-Web. Device listing shares
+// the gRPC service's device table; settings, triggers and battery go through
+// one lazily opened keyboard, like the LED streaming RPCs.
+// `GET /api/v1/openapi.json` describes every route.
+
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::Mutex as AsyncMutex;
+use tracing::info;
+
+use crate::commands::{open_keyboard, triggers::triggers_json, CmdCtx};
+use crate::grpc::{dj_dev, DjDev, DriverService};
+use iot_driver::cmd;
+use iot_driver::keyboard_config::{format_color, parse_color};
+use monsgeek_keyboard::{KeyboardError, KeyboardInterface, LedMode, PollingRate};
+
+/// Error reply: `{"error": "..."}` with a matching status code.
+struct ApiError(StatusCode, String);
+
+impl From<KeyboardError> for ApiError {
+    fn from(e: KeyboardError) -> Self {
+        let status = match e {
+            KeyboardError::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+            KeyboardError::NotSupported { .. } => StatusCode::NOT_IMPLEMENTED,
+            KeyboardError::Offline
+            | KeyboardError::Disconnected
+            | KeyboardError::Timeout { .. }
+            | KeyboardError::Busy { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self(status, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, json_body(&json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult = Result<Response, ApiError>;
+
+fn json_body(value: &Value) -> ([(header::HeaderName, &'static str); 1], String) {
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        value.to_string(),
+    )
+}
+
+fn ok(value: Value) -> ApiResult {
+    Ok(json_body(&value).into_response())
+}
+
+fn parse_body<T: DeserializeOwned>(body: &Bytes) -> Result<T, ApiError> {
+    serde_json::from_slice(body)
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("invalid JSON body: {e}")))
+}
+
+#[derive(Clone)]
+struct RestState {
+    service: Arc<DriverService>,
+    kb: Arc<AsyncMutex<Option<KeyboardInterface>>>,
+}
+
+impl RestState {
+    /// Run `f` on the keyboard, opening it first if needed. A device error
+    /// drops the handle so the next request reopens (e.g. after a replug).
+    async fn with_kb<T>(
+        &self,
+        f: impl FnOnce(&KeyboardInterface) -> Result<T, KeyboardError>,
+    ) -> Result<T, ApiError> {
+        let mut guard = self.kb.lock().await;
+        if guard.is_none() {
+            let kb = open_keyboard(&CmdCtx::default()).map_err(|e| {
+                ApiError(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("No keyboard found: {e}"),
+                )
+            })?;
+            *guard = Some(kb);
+        }
+        let kb = guard.as_ref().expect("opened above");
+        let result = f(kb);
+        if matches!(
+            result,
+            Err(KeyboardError::Disconnected | KeyboardError::Transport(_))
+        ) {
+            *guard = None;
+        }
+        result.map_err(ApiError::from)
+    }
+}
+
+/// Build the `/api/v1` routes.
+pub fn router(service: Arc<DriverService>) -> Router {
+    let state = RestState {
+        service,
+        kb: Arc::new(AsyncMutex::new(None)),
+    };
+    Router::new()
+        .route("/api/v1/openapi.json", get(openapi))
+        .route("/api/v1/devices", get(devices))
+        .route("/api/v1/battery", get(battery))
+        .route("/api/v1/settings", get(settings).patch(patch_settings))
+        .route("/api/v1/triggers", get(triggers).patch(patch_triggers))
+        .with_state(state)
+}
+
+fn device_json(dev: &DjDev) -> Option<Value> {
+    let (path, stable_id, id, vid, pid, battery, online, dongle) = match dev.oneof_dev.as_ref()? {
+        dj_dev::OneofDev::Dev(d) => (
+            &d.path,
+            &d.stable_id,
+            d.id,
+            d.vid,
+            d.pid,
+            d.battery,
+            d.is_online,
+            false,
+        ),
+        dj_dev::OneofDev::DangleCommonDev(d) => {
+            let (battery, online) = match d.keyboard.as_ref().and_then(|k| k.dangle_dev.as_ref()) {
+                Some(crate::grpc::dangle_status::DangleDev::Status(s)) => (s.battery, s.is_online),
+                _ => (0, false),
+            };
+            let id = d.keyboard_id as i32;
+            (
+                &d.path,
+                &d.stable_id,
+                id,
+                d.vid,
+                d.pid,
+                battery,
+                online,
+                true,
+            )
+        }
+    };
+    let name = iot_driver::devices::get_device_info_with_id(Some(id), vid as u16, pid as u16)
+        .map(|d| d.name);
+    Some(json!({
+        "id": stable_id,
+        "path": path,
+        "name": name,
+        "device_id": id,
+        "vid": vid,
+        "pid": pid,
+        "dongle": dongle,
+        "battery": battery,
+        "online": online,
+    }))
+}
+
+async fn devices(State(state): State<RestState>) -> ApiResult {
+    let list: Vec<Value> = state
+        .service
+        .scan_devices()
+        .await
+        .iter()
+        .filter_map(device_json)
+        .collect();
+    ok(json!(list))
+}
+
+async fn battery(State(state): State<RestState>) -> ApiResult {
+    let b = state.with_kb(|kb| kb.get_battery()).await?;
+    ok(json!({ "level": b.level, "online": b.online, "idle": b.idle }))
+}
+
+fn settings_json(kb: &KeyboardInterface) -> Result<Value, KeyboardError> {
+    let led = kb.get_led_params()?;
+    let mode = led.mode as u8;
+    Ok(json!({
+        "profile": kb.get_profile()?,
+        "polling_rate_hz": kb.get_polling_rate().ok().map(|r| r.to_hz()),
+        "led": {
+            "mode": mode,
+            "mode_name": cmd::led_mode_name(mode),
+            "brightness": led.brightness,
+            "speed": led.speed,
+            "color": format_color(led.color),
+        },
+    }))
+}
+
+async fn settings(State(state): State<RestState>) -> ApiResult {
+    ok(state.with_kb(settings_json).await?)
+}
+
+/// `PATCH /api/v1/settings` body; absent fields are left unchanged.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SettingsPatch {
+    profile: Option<u8>,
+    polling_rate_hz: Option<u16>,
+    led: Option<LedPatch>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LedPatch {
+    mode: Option<u8>,
+    brightness: Option<u8>,
+    speed: Option<u8>,
+    color: Option<String>,
+}
+
+async fn patch_settings(State(state): State<RestState>, body: Bytes) -> ApiResult {
+    let patch: SettingsPatch = parse_body(&body)?;
+    let bad = |msg: String| ApiError(StatusCode::BAD_REQUEST, msg);
+    let rate = patch
+        .polling_rate_hz
+        .map(|hz| {
+            PollingRate::from_hz(hz).ok_or_else(|| bad(format!("unsupported polling rate {hz}")))
+        })
+        .transpose()?;
+    let led_mode = patch
+        .led
+        .as_ref()
+        .and_then(|l| l.mode)
+        .map(|m| LedMode::from_u8(m).ok_or_else(|| bad(format!("unknown LED mode {m}"))))
+        .transpose()?;
+    let color = patch
+        .led
+        .as_ref()
+        .and_then(|l| l.color.as_deref())
+        .map(|c| {
+            parse_color(c).ok_or_else(|| bad(format!("invalid color '{c}', expected #RRGGBB")))
+        })
+        .transpose()?;
+
+    let value = state
+        .with_kb(|kb| {
+            if let Some(profile) = patch.profile {
+                kb.set_profile(profile)?;
+            }
+            if let Some(rate) = rate {
+                kb.set_polling_rate(rate)?;
+            }
+            if let Some(led) = &patch.led {
+                let mut params = kb.get_led_params()?;
+                params.mode = led_mode.unwrap_or(params.mode);
+                params.brightness = led.brightness.unwrap_or(params.brightness);
+                params.speed = led.speed.unwrap_or(params.speed);
+                params.color = color.unwrap_or(params.color);
+                kb.set_led_params(&params)?;
+            }
+            settings_json(kb)
+        })
+        .await?;
+    info!("REST: settings updated");
+    ok(value)
+}
+
+async fn triggers(State(state): State<RestState>) -> ApiResult {
+    ok(state.with_kb(triggers_json).await?)
+}
+
+/// `PATCH /api/v1/triggers` body, applied to every key.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggersPatch {
+    actuation_mm: Option<f32>,
+    release_mm: Option<f32>,
+    rapid_trigger: Option<bool>,
+    rt_sensitivity_mm: Option<f32>,
+}
+
+async fn patch_triggers(State(state): State<RestState>, body: Bytes) -> ApiResult {
+    let patch: TriggersPatch = parse_body(&body)?;
+    let value = state
+        .with_kb(|kb| {
+            let factor = kb.get_precision().unwrap_or_default().factor() as f32;
+            let raw = |mm: f32| (mm * factor) as u16;
+            if let Some(mm) = patch.actuation_mm {
+                kb.set_actuation_all_u16(raw(mm))?;
+            }
+            if let Some(mm) = patch.release_mm {
+                kb.set_release_all_u16(raw(mm))?;
+            }
+            if let Some(enable) = patch.rapid_trigger {
+                kb.set_rapid_trigger_all(enable)?;
+            }
+            if let Some(mm) = patch.rt_sensitivity_mm {
+                kb.set_rt_press_all_u16(raw(mm))?;
+                kb.set_rt_lift_all_u16(raw(mm))?;
+            }
+            triggers_json(kb)
+        })
+        .await?;
+    info!("REST: triggers updated");
+    ok(value)
+}
+
+async fn openapi() -> ApiResult {
+    ok(openapi_spec())
+}
+
+/// OpenAPI 3 description of the routes above.
+fn openapi_spec() -> Value {
+    let error = json!({ "$ref": "#/components/schemas/Error" });
+    let reply = |description: &str, schema: Value| {
+        json!({
+            "200": { "description": description, "content": { "application/json": { "schema": schema } } },
+            "default": { "description": "Error", "content": { "application/json": { "schema": error } } },
+        })
+    };
+    let body = |schema: &str| json!({ "required": true, "content": { "application/json": { "schema": { "$ref": format!("#/components/schemas/{schema}") } } } });
+    let schema = |name: &str| json!({ "$ref": format!("#/components/schemas/{name}") });
+    let number = json!({ "type": "number" });
+    let integer = json!({ "type": "integer" });
+    let mm_list = json!({ "type": "array", "items": number });
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "iot_driver REST API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": {
+            "/api/v1/devices": {
+                "get": { "summary": "List connected devices", "responses": reply("Devices", json!({ "type": "array", "items": schema("Device") })) }
+            },
+            "/api/v1/battery": {
+                "get": { "summary": "Battery status", "responses": reply("Battery", schema("Battery")) }
+            },
+            "/api/v1/settings": {
+                "get": { "summary": "Profile, polling rate and LED settings", "responses": reply("Settings", schema("Settings")) },
+                "patch": { "summary": "Change settings; absent fields are kept", "requestBody": body("SettingsPatch"), "responses": reply("Updated settings", schema("Settings")) }
+            },
+            "/api/v1/triggers": {
+                "get": { "summary": "Per-key trigger settings in mm", "responses": reply("Triggers", schema("Triggers")) },
+                "patch": { "summary": "Set triggers for all keys", "requestBody": body("TriggersPatch"), "responses": reply("Updated triggers", schema("Triggers")) }
+            },
+        },
+        "components": {
+            "schemas": {
+                "Error": { "type": "object", "properties": { "error": { "type": "string" } } },
+                "Device": { "type": "object", "properties": {
+                    "id": { "type": "string" }, "path": { "type": "string" },
+                    "name": { "type": "string", "nullable": true },
+                    "device_id": integer, "vid": integer, "pid": integer,
+                    "dongle": { "type": "boolean" }, "battery": integer, "online": { "type": "boolean" },
+                } },
+                "Battery": { "type": "object", "properties": {
+                    "level": integer, "online": { "type": "boolean" }, "idle": { "type": "boolean" },
+                } },
+                "Led": { "type": "object", "properties": {
+                    "mode": integer, "mode_name": { "type": "string" }, "brightness": integer,
+                    "speed": integer, "color": { "type": "string", "example": "#FF8800" },
+                } },
+                "Settings": { "type": "object", "properties": {
+                    "profile": integer, "polling_rate_hz": { "type": "integer", "nullable": true }, "led": schema("Led"),
+                } },
+                "SettingsPatch": { "type": "object", "properties": {
+                    "profile": integer, "polling_rate_hz": integer, "led": schema("LedPatch"),
+                } },
+                "LedPatch": { "type": "object", "properties": {
+                    "mode": integer, "brightness": integer, "speed": integer,
+                    "color": { "type": "string", "example": "#FF8800" },
+                } },
+                "Triggers": { "type": "object", "properties": {
+                    "firmware": { "type": "string" }, "precision": { "type": "string" }, "key_count": integer,
+                    "actuation_mm": mm_list, "release_mm": mm_list, "rt_press_mm": mm_list, "rt_release_mm": mm_list,
+                    "bottom_deadzone_mm": mm_list, "top_deadzone_mm": mm_list,
+                    "modes": { "type": "array", "items": integer },
+                } },
+                "TriggersPatch": { "type": "object", "properties": {
+                    "actuation_mm": number, "release_mm": number,
+                    "rapid_trigger": { "type": "boolean" }, "rt_sensitivity_mm": number,
+                } },
+            }
+        }
+    })
+}
@@ -0,0 +1,326 @@
+//! Time-of-day lighting schedule.
+//!
+//! Configured under `[schedule]` in `settings.toml` and run by the daemon:
+//! each `[[schedule.entries]]` maps a local time range (and optionally
+//! weekdays) to a profile and/or LED mode, brightness, speed and color. An
+//! entry is applied once when its range begins, so changes made by hand
+//! last until the next entry starts. Nothing is applied while the session
+//! is locked (see [`crate::lock_lighting`]).
+
+use serde::{Deserialize, Serialize};
+
+use crate::keyboard_config::parse_color;
+use crate::night_mode::{local_time, TimeOfDay, STATIC_TICK};
+use crate::protocol::cmd;
+use monsgeek_keyboard::led::LedParams;
+use monsgeek_keyboard::{KeyboardInterface, LedMode};
+
+const MINUTES_PER_DAY: f32 = 24.0 * 60.0;
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Weekday bitmask (bit 0 = Sunday) from names: `mon`..`sun` (or full
+/// names), `weekdays`, `weekend`. Empty means every day.
+pub fn parse_days(days: &[String]) -> Result<u8, String> {
+    if days.is_empty() {
+        return Ok(0x7F);
+    }
+    days.iter().try_fold(0u8, |mask, day| {
+        let day = day.trim().to_lowercase();
+        let bits = match day.as_str() {
+            "daily" | "all" => 0x7F,
+            "weekdays" => 0x3E,
+            "weekend" => 0x41,
+            _ => DAY_NAMES
+                .iter()
+                .position(|d| day.starts_with(d) && day.len() >= 3)
+                .map(|i| 1 << i)
+                .ok_or_else(|| format!("unknown day '{day}'"))?,
+        };
+        Ok(mask | bits)
+    })
+}
+
+/// Profile and LED settings to switch to; fields left out stay as they are.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LightingPreset {
+    /// Profile to switch to (0-3)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<u8>,
+    /// LED mode, as for `set-led`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// 0-100
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+    /// 0-100
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<u8>,
+    /// `#RRGGBB`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+impl LightingPreset {
+    /// Whether any LED field is set.
+    pub fn sets_leds(&self) -> bool {
+        self.mode.is_some()
+            || self.brightness.is_some()
+            || self.speed.is_some()
+            || self.color.is_some()
+    }
+
+    /// `current` with the preset's LED fields applied.
+    pub fn led_params(&self, current: &LedParams) -> Result<LedParams, String> {
+        let mut params = current.clone();
+        if let Some(mode) = &self.mode {
+            params.mode = cmd::LedMode::parse(mode)
+                .and_then(|m| LedMode::from_u8(m.as_u8()))
+                .ok_or_else(|| format!("unknown mode {mode:?}"))?;
+        }
+        if let Some(b) = self.brightness {
+            params.brightness = b.min(100);
+        }
+        if let Some(s) = self.speed {
+            params.speed = s.min(100);
+        }
+        if let Some(c) = &self.color {
+            params.color = parse_color(c).ok_or_else(|| format!("invalid color {c:?}"))?;
+        }
+        Ok(params)
+    }
+
+    /// Apply the preset to the keyboard: profile first, then lighting.
+    pub fn apply(&self, keyboard: &KeyboardInterface) -> Result<(), String> {
+        if let Some(profile) = self.profile {
+            if profile > 3 {
+                return Err(format!("profile {profile} out of range (0-3)"));
+            }
+            keyboard.set_profile(profile).map_err(|e| e.to_string())?;
+        }
+        if self.sets_leds() {
+            let current = keyboard.get_led_params().map_err(|e| e.to_string())?;
+            let params = self.led_params(&current)?;
+            if params != current {
+                keyboard
+                    .set_led_params(&params)
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One schedule entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    /// Days the range starts on (a range past midnight belongs to the day
+    /// it starts); empty = every day
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<String>,
+    pub start: TimeOfDay,
+    /// End of the range (exclusive); equal to `start` = all day
+    pub end: TimeOfDay,
+    #[serde(flatten)]
+    pub lighting: LightingPreset,
+}
+
+impl ScheduleEntry {
+    /// Whether the entry is in effect on `weekday` (0 = Sunday) at `minute`
+    /// after midnight.
+    pub fn active_at(&self, weekday: u8, minute: f32) -> Result<bool, String> {
+        let mask = parse_days(&self.days)?;
+        let start = self.start.minutes as f32;
+        let mut len = (self.end.minutes as f32 - start).rem_euclid(MINUTES_PER_DAY);
+        if len == 0.0 {
+            len = MINUTES_PER_DAY;
+        }
+        let t = (minute - start).rem_euclid(MINUTES_PER_DAY);
+        if t >= len {
+            return Ok(false);
+        }
+        // Started yesterday if the range wrapped past midnight
+        let day = if minute >= start {
+            weekday
+        } else {
+            (weekday + 6) % 7
+        };
+        Ok(mask & (1 << day) != 0)
+    }
+}
+
+/// `[schedule]` settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LightingSchedule {
+    pub enabled: bool,
+    /// Checked in order; the first active entry wins
+    pub entries: Vec<ScheduleEntry>,
+}
+
+impl LightingSchedule {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The entry in effect on `weekday` at `minute`, if any. Entries with
+    /// bad day names are skipped.
+    pub fn active_at(&self, weekday: u8, minute: f32) -> Option<&ScheduleEntry> {
+        if !self.enabled {
+            return None;
+        }
+        self.entries
+            .iter()
+            .find(|e| e.active_at(weekday, minute).unwrap_or(false))
+    }
+}
+
+/// Daemon loop: check the `settings.toml` schedule every [`STATIC_TICK`]
+/// and apply an entry when it becomes active (or is edited). The keyboard
+/// is opened on demand and reopened after errors.
+pub fn run_schedule(open: impl Fn() -> Result<KeyboardInterface, String>) {
+    let mut keyboard = None;
+    let mut applied: Option<ScheduleEntry> = None;
+    loop {
+        if crate::lock_lighting::is_locked() {
+            // Lock lighting owns the LEDs; catch up after unlock
+            std::thread::sleep(STATIC_TICK);
+            continue;
+        }
+        let schedule = crate::settings::Settings::load().schedule;
+        for entry in &schedule.entries {
+            if let Err(e) = parse_days(&entry.days) {
+                tracing::warn!("schedule: {e}");
+            }
+        }
+        let (weekday, minute) = local_time();
+        let active = schedule.active_at(weekday, minute).cloned();
+        if active.is_some() && active != applied {
+            if keyboard.is_none() {
+                keyboard = open()
+                    .map_err(|e| tracing::debug!("schedule: no keyboard: {e}"))
+                    .ok();
+            }
+            if let (Some(kb), Some(entry)) = (&keyboard, &active) {
+                match entry.lighting.apply(kb) {
+                    Ok(()) => {
+                        tracing::info!("schedule: applied {}-{}", entry.start, entry.end);
+                        applied = active;
+                    }
+                    Err(e) => {
+                        tracing::warn!("schedule: {e}");
+                        keyboard = None;
+                    }
+                }
+            }
+        } else if active.is_none() {
+            applied = None;
+        }
+        std::thread::sleep(STATIC_TICK);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(start: &str, end: &str, days: &[&str]) -> ScheduleEntry {
+        ScheduleEntry {
+            days: days.iter().map(|d| d.to_string()).collect(),
+            start: start.parse().unwrap(),
+            end: end.parse().unwrap(),
+            lighting: LightingPreset::default(),
+        }
+    }
+
+    const MON: u8 = 1;
+    const SAT: u8 = 6;
+
+    #[test]
+    fn parses_day_names_and_groups() {
+        assert_eq!(parse_days(&[]).unwrap(), 0x7F);
+        assert_eq!(parse_days(&["weekdays".into()]).unwrap(), 0b0111110);
+        assert_eq!(
+            parse_days(&["Sunday".into(), "sat".into()]).unwrap(),
+            0b1000001
+        );
+        assert!(parse_days(&["someday".into()]).is_err());
+    }
+
+    #[test]
+    fn ranges_wrap_midnight_and_belong_to_their_start_day() {
+        let night = entry("22:00", "07:00", &["fri"]);
+        let h = |h: f32| h * 60.0;
+        assert!(night.active_at(5, h(23.0)).unwrap());
+        assert!(night.active_at(SAT, h(3.0)).unwrap());
+        assert!(!night.active_at(SAT, h(8.0)).unwrap());
+        assert!(!night.active_at(SAT, h(23.0)).unwrap());
+
+        let all_day = entry("00:00", "00:00", &["weekend"]);
+        assert!(all_day.active_at(SAT, h(12.0)).unwrap());
+        assert!(!all_day.active_at(MON, h(12.0)).unwrap());
+    }
+
+    #[test]
+    fn first_active_entry_wins() {
+        let schedule = LightingSchedule {
+            enabled: true,
+            entries: vec![
+                entry("09:00", "17:00", &["weekdays"]),
+                entry("07:00", "22:00", &[]),
+            ],
+        };
+        let at = |day, hour: f32| schedule.active_at(day, hour * 60.0).map(|e| e.start);
+        assert_eq!(at(MON, 10.0), Some("09:00".parse().unwrap()));
+        assert_eq!(at(SAT, 10.0), Some("07:00".parse().unwrap()));
+        assert_eq!(at(MON, 23.0), None);
+        let off = LightingSchedule {
+            enabled: false,
+            ..schedule.clone()
+        };
+        assert!(off.active_at(MON, 600.0).is_none());
+    }
+
+    #[test]
+    fn presets_set_only_the_given_led_fields() {
+        let mut night = LightingPreset {
+            mode: Some("constant".into()),
+            brightness: Some(20),
+            color: Some("#FFFFFF".into()),
+            ..Default::default()
+        };
+        let current = LedParams {
+            mode: LedMode::Wave,
+            speed: 77,
+            ..Default::default()
+        };
+        let params = night.led_params(&current).unwrap();
+        assert_eq!(params.mode, LedMode::Constant);
+        assert_eq!((params.brightness, params.speed), (20, 77));
+
+        night.mode = Some("disco".into());
+        assert!(night.led_params(&current).is_err());
+    }
+
+    #[test]
+    fn loads_from_toml() {
+        let schedule: LightingSchedule = toml::from_str(
+            r#"
+            enabled = true
+            [[entries]]
+            start = "07:00"
+            end = "22:00"
+            mode = "rainbow"
+            brightness = 100
+            [[entries]]
+            days = ["weekdays"]
+            start = "22:00"
+            end = "07:00"
+            profile = 1
+            "#,
+        )
+        .unwrap();
+        assert_eq!(schedule.entries.len(), 2);
+        assert_eq!(schedule.entries[1].lighting.profile, Some(1));
+        assert_eq!(schedule.entries[0].lighting.brightness, Some(100));
+    }
+}
@@ -1,5 +1,6 @@
 // Screen Color Reactive LED Mode
-// Captures average screen color via PipeWire ScreenCast and streams to keyboard
+// Captures average screen color (PipeWire ScreenCast portal, or wlr-screencopy
+// on wlroots compositors) and streams it to the keyboard
 
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
@@ -7,10 +8,15 @@ use std::time::{Duration, Instant};
 
 use tracing::trace;
 
+use crate::led_stream::{apply_power_budget, send_full_frame};
+use crate::night_mode::NightMode;
+use crate::notify::keymap::MATRIX_LEN;
 use crate::protocol::{cmd, screen_color};
 use crate::screen_calib::{ColorCalibration, Region};
+use crate::screen_zones::ZoneMapping;
 use crate::settings::Settings;
-use monsgeek_keyboard::KeyboardInterface;
+use crate::wlr_screencopy;
+use monsgeek_keyboard::{DeviceCapabilities, KeyboardInterface};
 
 /// Screen color state shared between capture and main loop. Calibration/region/
 /// test-swatch are live-adjustable (the loop and PipeWire callback read them each
@@ -22,11 +28,17 @@ pub struct ScreenColorState {
     pub running: AtomicBool,
     /// Color transform applied before streaming.
     calibration: RwLock<ColorCalibration>,
+    /// Night-time color temperature/brightness, applied after calibration.
+    night_mode: NightMode,
     /// Sub-rectangle of the screen that drives the average.
     region: RwLock<Region>,
     /// When set, stream this fixed color instead of the screen average (used to
     /// tune calibration against a known target).
     test_swatch: Mutex<Option<(u8, u8, u8)>>,
+    /// When set, frames are also sampled per key zone (see `zone_colors`).
+    zones: RwLock<Option<ZoneMapping>>,
+    /// Latest raw per-key colors (row-major LED matrix), pre-calibration.
+    zone_colors: Mutex<[(u8, u8, u8); MATRIX_LEN]>,
 }
 
 impl Default for ScreenColorState {
@@ -35,8 +47,11 @@ impl Default for ScreenColorState {
             color: Mutex::new((0, 0, 0)),
             running: AtomicBool::new(false),
             calibration: RwLock::new(ColorCalibration::default()),
+            night_mode: NightMode::default(),
             region: RwLock::new(Region::default()),
             test_swatch: Mutex::new(None),
+            zones: RwLock::new(None),
+            zone_colors: Mutex::new([(0, 0, 0); MATRIX_LEN]),
         }
     }
 }
@@ -47,6 +62,7 @@ impl ScreenColorState {
         Self {
             calibration: RwLock::new(settings.screen_calibration),
             region: RwLock::new(settings.screen_region),
+            night_mode: settings.night_mode,
             ..Self::default()
         }
     }
@@ -90,6 +106,42 @@ impl ScreenColorState {
     pub fn set_test_swatch(&self, s: Option<(u8, u8, u8)>) {
         *self.test_swatch.lock().unwrap() = s;
     }
+
+    pub fn zone_mapping(&self) -> Option<ZoneMapping> {
+        *self.zones.read().unwrap()
+    }
+
+    pub fn set_zone_mapping(&self, m: Option<ZoneMapping>) {
+        *self.zones.write().unwrap() = m;
+    }
+
+    pub fn zone_colors(&self) -> [(u8, u8, u8); MATRIX_LEN] {
+        *self.zone_colors.lock().unwrap()
+    }
+}
+
+/// Sample one captured frame into `state`: the region average, plus per-key
+/// colors when a zone mapping is set. `to_buffer` maps a screen region onto
+/// the buffer as stored (identity unless rows are padded or flipped).
+fn sample_frame(
+    state: &ScreenColorState,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    is_bgra: bool,
+    to_buffer: impl Fn(Region) -> Region,
+) {
+    let region = state.region();
+    let (r, g, b) = compute_average_color(data, width, height, is_bgra, to_buffer(region));
+    state.set_color(r, g, b);
+
+    if let Some(mapping) = state.zone_mapping() {
+        let mut colors = [(0, 0, 0); MATRIX_LEN];
+        for (index, zone) in mapping.key_regions(region) {
+            colors[index] = compute_average_color(data, width, height, is_bgra, to_buffer(zone));
+        }
+        *state.zone_colors.lock().unwrap() = colors;
+    }
 }
 
 /// Grid size for sampling (16x16 = 256 samples instead of millions)
@@ -180,7 +232,10 @@ fn run_screen_color_loop(
         // A test swatch (calibration helper) overrides the live average so the
         // user can tune against a known target; calibration is always applied.
         let raw = state.test_swatch().unwrap_or_else(|| state.get_color());
-        let (r, g, b) = state.calibration().apply(raw);
+        let (r, g, b) = state
+            .night_mode
+            .current()
+            .apply(state.calibration().apply(raw));
 
         // Only send if color changed (reduces USB traffic)
         if (r, g, b) != last_color {
@@ -214,6 +269,45 @@ fn run_screen_color_loop(
     Ok(())
 }
 
+/// Run the per-key zone streaming loop: sends calibrated zone colors as full
+/// LED frames (patch LED streaming) until `running` clears.
+fn run_screen_zones_loop(
+    keyboard: &KeyboardInterface,
+    state: &Arc<ScreenColorState>,
+    running: Arc<AtomicBool>,
+    power_budget: u32,
+) -> Result<(), String> {
+    let update_interval = Duration::from_millis(screen_color::UPDATE_INTERVAL_MS);
+    let mut last_frame = None;
+
+    while running.load(Ordering::SeqCst) {
+        let frame_start = Instant::now();
+
+        let calibration = state.calibration();
+        let night = state.night_mode.current();
+        let mut leds = match state.test_swatch() {
+            Some(swatch) => [swatch; MATRIX_LEN],
+            None => state.zone_colors(),
+        };
+        for led in leds.iter_mut() {
+            *led = night.apply(calibration.apply(*led));
+        }
+        apply_power_budget(&mut leds, power_budget);
+
+        if last_frame != Some(leds) {
+            send_full_frame(keyboard, &leds)
+                .map_err(|e| format!("Failed to send LED frame: {e}"))?;
+            last_frame = Some(leds);
+        }
+
+        let elapsed = frame_start.elapsed();
+        if elapsed < update_interval {
+            std::thread::sleep(update_interval - elapsed);
+        }
+    }
+    Ok(())
+}
+
 /// Screen capture via PipeWire ScreenCast portal
 pub mod pipewire_capture {
     use super::*;
@@ -479,14 +573,7 @@ pub mod pipewire_capture {
                         if let Some(data) = datas[0].data() {
                             if width > 0 && height > 0 {
                                 // Assume BGRx format (common)
-                                let (r, g, b) = compute_average_color(
-                                    data,
-                                    width,
-                                    height,
-                                    true,
-                                    state_clone.region(),
-                                );
-                                state_clone.set_color(r, g, b);
+                                sample_frame(&state_clone, data, width, height, true, |r| r);
                             }
                         }
                     }
@@ -610,13 +697,167 @@ pub mod pipewire_capture {
     }
 }
 
+/// Screen capture via wlr-screencopy (Sway, Hyprland, niri, river, ...).
+///
+/// wlroots compositors without xdg-desktop-portal-wlr have no ScreenCast
+/// portal at all; screencopy needs no picker and no PipeWire, just the
+/// compositor socket.
+pub mod wlr_capture {
+    use super::*;
+    use crate::wlr_screencopy::Screencopy;
+
+    /// A running screencopy thread.
+    pub struct WlrCapture {
+        thread: Option<std::thread::JoinHandle<()>>,
+        /// Shared capture state; cleared to stop the capture thread.
+        state: Arc<ScreenColorState>,
+    }
+
+    impl WlrCapture {
+        /// Stop and join the capture thread (at most one frame timeout).
+        pub fn shutdown(mut self) {
+            self.state.stop();
+            if let Some(h) = self.thread.take() {
+                let _ = h.join();
+            }
+        }
+    }
+
+    /// Connect to the compositor and start copying frames at `fps`.
+    ///
+    /// The connection (and its shared-memory mapping) lives on the capture
+    /// thread; the connect result is reported back before this returns.
+    pub fn start_capture(state: Arc<ScreenColorState>, fps: u32) -> Result<WlrCapture, String> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let thread_state = state.clone();
+        let thread = std::thread::spawn(move || {
+            let mut screencopy = match Screencopy::connect() {
+                Ok(s) => s,
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    return;
+                }
+            };
+            thread_state.running.store(true, Ordering::SeqCst);
+            let _ = tx.send(Ok(()));
+
+            let interval = Duration::from_millis(1000 / fps.clamp(1, 60) as u64);
+            while thread_state.is_running() {
+                let frame_start = Instant::now();
+                match screencopy.capture() {
+                    Ok(frame) => sample_frame(
+                        &thread_state,
+                        frame.data,
+                        frame.padded_width(),
+                        frame.height,
+                        frame.is_bgra,
+                        |r| frame.buffer_region(r),
+                    ),
+                    Err(e) => {
+                        tracing::error!("wlr-screencopy capture error: {e}");
+                        break;
+                    }
+                }
+                let elapsed = frame_start.elapsed();
+                if elapsed < interval {
+                    std::thread::sleep(interval - elapsed);
+                }
+            }
+        });
+
+        match rx.recv() {
+            Ok(Ok(())) => Ok(WlrCapture {
+                thread: Some(thread),
+                state,
+            }),
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                Err(e)
+            }
+            Err(_) => Err("wlr-screencopy capture thread exited".to_string()),
+        }
+    }
+}
+
+/// Where screen frames come from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaptureBackend {
+    /// wlr-screencopy when the compositor offers it, else the portal
+    #[default]
+    Auto,
+    /// xdg-desktop-portal ScreenCast + PipeWire (GNOME, KDE, and wlroots
+    /// compositors running xdg-desktop-portal-wlr/-hyprland)
+    Portal,
+    /// wlr-screencopy protocol, straight from the compositor
+    Wlr,
+}
+
+impl CaptureBackend {
+    /// Pick a concrete backend. `Auto` prefers wlr-screencopy: it needs no
+    /// permission picker and works where no ScreenCast portal is installed.
+    pub fn resolve(self) -> CaptureBackend {
+        match self {
+            CaptureBackend::Auto if wlr_screencopy::is_available() => CaptureBackend::Wlr,
+            CaptureBackend::Auto => CaptureBackend::Portal,
+            backend => backend,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CaptureBackend::Auto => "auto",
+            CaptureBackend::Portal => "xdg-desktop-portal",
+            CaptureBackend::Wlr => "wlr-screencopy",
+        }
+    }
+}
+
+/// A live capture on either backend.
+pub enum ScreenCapture {
+    Portal(pipewire_capture::CaptureSession),
+    Wlr(wlr_capture::WlrCapture),
+}
+
+impl ScreenCapture {
+    /// Start capturing into `state` with `backend` (resolved if `Auto`).
+    pub async fn start(
+        backend: CaptureBackend,
+        state: Arc<ScreenColorState>,
+        fps: u32,
+    ) -> Result<Self, String> {
+        match backend.resolve() {
+            CaptureBackend::Wlr => wlr_capture::start_capture(state, fps).map(Self::Wlr),
+            _ => pipewire_capture::start_capture(state, fps)
+                .await
+                .map(Self::Portal),
+        }
+    }
+
+    /// Stop capturing. Portal sessions must be shut down on the runtime that
+    /// created them (see [`pipewire_capture::CaptureSession::shutdown`]).
+    pub async fn shutdown(self) {
+        match self {
+            ScreenCapture::Portal(session) => session.shutdown().await,
+            ScreenCapture::Wlr(capture) => capture.shutdown(),
+        }
+    }
+}
+
 /// Run screen color reactive mode (async entry point)
 pub async fn run_screen_color_mode(
     keyboard: &KeyboardInterface,
     running: Arc<AtomicBool>,
     fps: u32,
+    backend: CaptureBackend,
 ) -> Result<(), String> {
-    println!("Starting screen color mode ({fps}fps)...");
+    if DeviceCapabilities::lacks(keyboard.capabilities().has_screen) {
+        return Err("This keyboard has no screen sync mode".to_string());
+    }
+    let backend = backend.resolve();
+    println!(
+        "Starting screen color mode ({fps}fps, {})...",
+        backend.name()
+    );
 
     // Snapshot the current LED config so the previous mode + brightness/speed/
     // color can be restored on exit. Enter ScreenSync using those same values
@@ -639,11 +880,13 @@ pub async fn run_screen_color_mode(
     // Create shared state
     let state = Arc::new(ScreenColorState::from_settings(&Settings::load()));
 
-    // Start PipeWire capture (requests permission via portal)
-    println!("Requesting screen capture permission...");
-    let capture = pipewire_capture::start_capture(state.clone(), fps).await?;
+    // Start capture (the portal asks for permission the first time)
+    if backend == CaptureBackend::Portal {
+        println!("Requesting screen capture permission...");
+    }
+    let capture = ScreenCapture::start(backend, state.clone(), fps).await?;
 
-    // Give PipeWire time to start
+    // Give the capture time to deliver a first frame
     std::thread::sleep(Duration::from_millis(500));
 
     // Run the color streaming loop (blocking)
@@ -660,6 +903,44 @@ pub async fn run_screen_color_mode(
     result
 }
 
+/// Run ambient zone mode (async entry point): every key shows its own screen
+/// zone per `mapping`. The keyboard must support patch LED streaming; the
+/// stream is released on exit so the firmware effect comes back.
+pub async fn run_screen_zones_mode(
+    keyboard: &KeyboardInterface,
+    running: Arc<AtomicBool>,
+    fps: u32,
+    backend: CaptureBackend,
+    mapping: ZoneMapping,
+    power_budget: u32,
+) -> Result<(), String> {
+    let backend = backend.resolve();
+    println!(
+        "Starting screen zone mode ({fps}fps, {}, {:?} zones over {}x{} keys)...",
+        backend.name(),
+        mapping.mode,
+        mapping.columns,
+        mapping.rows
+    );
+
+    let state = Arc::new(ScreenColorState::from_settings(&Settings::load()));
+    state.set_zone_mapping(Some(mapping));
+
+    if backend == CaptureBackend::Portal {
+        println!("Requesting screen capture permission...");
+    }
+    let capture = ScreenCapture::start(backend, state.clone(), fps).await?;
+    std::thread::sleep(Duration::from_millis(500));
+
+    println!("Streaming screen zones to keyboard...");
+    let result = run_screen_zones_loop(keyboard, &state, running, power_budget);
+
+    capture.shutdown().await;
+    keyboard.stream_led_release().ok();
+    println!("Screen zone mode stopped");
+    result
+}
+
 /// Handle returned by [`spawn_for_tui`] — shared state plus the worker thread.
 pub struct TuiScreenCapture {
     pub(crate) state: Arc<ScreenColorState>,
@@ -690,9 +971,9 @@ impl TuiScreenCapture {
 }
 
 /// Start screen-reactive mode for the TUI: spawn a background thread that runs
-/// the portal negotiation + PipeWire capture + the keyboard streaming loop, all
-/// silently (no stdout). The LED mode is assumed to already be ScreenSync
-/// (set by the caller).
+/// the capture (auto-selected backend: wlr-screencopy, else portal + PipeWire)
+/// and the keyboard streaming loop, all silently (no stdout). The LED mode is
+/// assumed to already be ScreenSync (set by the caller).
 pub fn spawn_for_tui(keyboard: Arc<KeyboardInterface>, fps: u32) -> TuiScreenCapture {
     let state = Arc::new(ScreenColorState::from_settings(&Settings::load()));
     let running = Arc::new(AtomicBool::new(true));
@@ -713,8 +994,11 @@ pub fn spawn_for_tui(keyboard: Arc<KeyboardInterface>, fps: u32) -> TuiScreenCap
         // Portal negotiation only needs `block_on`; the returned session is held
         // (its background tasks run on the runtime's worker threads) so the
         // PipeWire stream stays valid during streaming.
-        let capture = match rt.block_on(pipewire_capture::start_capture(thread_state.clone(), fps))
-        {
+        let capture = match rt.block_on(ScreenCapture::start(
+            CaptureBackend::Auto,
+            thread_state.clone(),
+            fps,
+        )) {
             Ok(c) => c,
             Err(e) => {
                 tracing::error!("screen capture: {e}");
@@ -724,7 +1008,7 @@ pub fn spawn_for_tui(keyboard: Arc<KeyboardInterface>, fps: u32) -> TuiScreenCap
             }
         };
 
-        // Let PipeWire negotiate the format before streaming; bail fast if the
+        // Let the capture deliver a first frame before streaming; bail fast if the
         // user already left ScreenSync.
         if thread_running.load(Ordering::SeqCst) {
             std::thread::sleep(Duration::from_millis(300));
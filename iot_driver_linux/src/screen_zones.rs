@@ -0,0 +1,215 @@
+//! Screen-region to key-zone mapping for ambient screen sync.
+//!
+//! Instead of one averaged color, each key samples its own part of the
+//! capture region: a grid miniature of the screen, or ambilight-style edge
+//! zones where every key takes the color of the nearest screen edge. Pure
+//! geometry, like [`screen_calib`](crate::screen_calib), so it builds and
+//! unit-tests without the `screen-capture` feature.
+
+use serde::{Deserialize, Serialize};
+
+use crate::notify::keymap::{COLS, ROWS};
+use crate::screen_calib::Region;
+
+/// How keys are assigned screen zones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ZoneMode {
+    /// The region split into one cell per key: a miniature of the screen
+    Grid,
+    /// Each key samples the band along the screen edge nearest to it
+    #[default]
+    Edges,
+}
+
+/// A keyboard layout's zone mapping, saved per device in `settings.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZoneMapping {
+    pub mode: ZoneMode,
+    /// LED matrix columns the layout populates, from the left (16 max)
+    pub columns: usize,
+    /// LED matrix rows the layout populates, from the top (6 max)
+    pub rows: usize,
+    /// Edge band thickness as a fraction of the region (edges mode)
+    pub edge_depth: f32,
+}
+
+impl Default for ZoneMapping {
+    fn default() -> Self {
+        Self {
+            mode: ZoneMode::default(),
+            columns: COLS,
+            rows: ROWS,
+            edge_depth: 0.15,
+        }
+    }
+}
+
+impl ZoneMapping {
+    /// The screen area each key samples, as (row-major LED matrix index,
+    /// sub-rectangle of `region`).
+    pub fn key_regions(&self, region: Region) -> Vec<(usize, Region)> {
+        let region = region.sanitized();
+        let cols = self.columns.clamp(1, COLS);
+        let rows = self.rows.clamp(1, ROWS);
+        let depth = self.edge_depth.clamp(0.01, 0.5);
+
+        let mut out = Vec::with_capacity(cols * rows);
+        for row in 0..rows {
+            for col in 0..cols {
+                let (u0, u1) = (col as f32 / cols as f32, (col + 1) as f32 / cols as f32);
+                let (v0, v1) = (row as f32 / rows as f32, (row + 1) as f32 / rows as f32);
+                let zone = match self.mode {
+                    ZoneMode::Grid => (u0, v0, u1, v1),
+                    ZoneMode::Edges => match nearest_edge(row, col, rows, cols) {
+                        Edge::Top => (u0, 0.0, u1, depth),
+                        Edge::Bottom => (u0, 1.0 - depth, u1, 1.0),
+                        Edge::Left => (0.0, v0, depth, v1),
+                        Edge::Right => (1.0 - depth, v0, 1.0, v1),
+                    },
+                };
+                out.push((row * COLS + col, within(region, zone)));
+            }
+        }
+        out
+    }
+}
+
+/// Command-line changes to a saved mapping; `None` keeps the saved value.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ZoneOverrides {
+    pub mode: Option<ZoneMode>,
+    pub columns: Option<usize>,
+    pub rows: Option<usize>,
+    pub edge_depth: Option<f32>,
+}
+
+impl ZoneOverrides {
+    pub fn apply(&self, mapping: ZoneMapping) -> ZoneMapping {
+        ZoneMapping {
+            mode: self.mode.unwrap_or(mapping.mode),
+            columns: self.columns.unwrap_or(mapping.columns),
+            rows: self.rows.unwrap_or(mapping.rows),
+            edge_depth: self.edge_depth.unwrap_or(mapping.edge_depth),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+/// Edge closest to a key, measured in keys so the wide layout doesn't pull
+/// everything to the top and bottom. Ties go to the top/bottom edge.
+fn nearest_edge(row: usize, col: usize, rows: usize, cols: usize) -> Edge {
+    let (top, bottom) = (row, rows - 1 - row);
+    let (left, right) = (col, cols - 1 - col);
+    let vertical = if top <= bottom {
+        (top, Edge::Top)
+    } else {
+        (bottom, Edge::Bottom)
+    };
+    let horizontal = if left <= right {
+        (left, Edge::Left)
+    } else {
+        (right, Edge::Right)
+    };
+    if horizontal.0 < vertical.0 {
+        horizontal.1
+    } else {
+        vertical.1
+    }
+}
+
+/// `(left, top, right, bottom)` fractions of `region` as screen fractions.
+fn within(region: Region, (l, t, r, b): (f32, f32, f32, f32)) -> Region {
+    let (w, h) = (region.right - region.left, region.bottom - region.top);
+    Region {
+        left: region.left + l * w,
+        top: region.top + t * h,
+        right: region.left + r * w,
+        bottom: region.top + b * h,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone_of(zones: &[(usize, Region)], index: usize) -> Region {
+        zones.iter().find(|(i, _)| *i == index).unwrap().1
+    }
+
+    #[test]
+    fn grid_splits_the_region_per_key() {
+        let mapping = ZoneMapping {
+            mode: ZoneMode::Grid,
+            columns: 4,
+            rows: 2,
+            ..Default::default()
+        };
+        let zones = mapping.key_regions(Region {
+            left: 0.0,
+            top: 0.5,
+            right: 1.0,
+            bottom: 1.0,
+        });
+        assert_eq!(zones.len(), 8);
+        let last = zone_of(&zones, COLS + 3);
+        assert_eq!(
+            (last.left, last.top, last.right, last.bottom),
+            (0.75, 0.75, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn edges_follow_the_nearest_screen_edge() {
+        let zones = ZoneMapping::default().key_regions(Region::default());
+        // Top row samples the top band, bottom row the bottom band
+        assert_eq!(zone_of(&zones, 5).top, 0.0);
+        assert!(zone_of(&zones, 5).bottom <= 0.15 + f32::EPSILON);
+        assert_eq!(zone_of(&zones, (ROWS - 1) * COLS + 8).bottom, 1.0);
+        // Middle rows at the sides sample the side bands
+        let right = zone_of(&zones, 2 * COLS + COLS - 1);
+        assert!(right.left >= 0.85 - f32::EPSILON && right.right == 1.0);
+        assert_eq!(zone_of(&zones, 3 * COLS).left, 0.0);
+        // Interior keys fall back to the top/bottom edge
+        assert_eq!(zone_of(&zones, 2 * COLS + 7).top, 0.0);
+    }
+
+    #[test]
+    fn overrides_replace_only_given_fields() {
+        let saved = ZoneMapping {
+            columns: 15,
+            ..Default::default()
+        };
+        assert_eq!(ZoneOverrides::default().apply(saved), saved);
+        let mapping = ZoneOverrides {
+            mode: Some(ZoneMode::Grid),
+            ..Default::default()
+        }
+        .apply(saved);
+        assert_eq!((mapping.mode, mapping.columns), (ZoneMode::Grid, 15));
+    }
+
+    #[test]
+    fn mapping_round_trips_through_toml() {
+        let mapping = ZoneMapping {
+            mode: ZoneMode::Grid,
+            columns: 15,
+            ..Default::default()
+        };
+        let text = toml::to_string(&mapping).unwrap();
+        assert!(text.contains("mode = \"grid\""));
+        assert_eq!(toml::from_str::<ZoneMapping>(&text).unwrap(), mapping);
+        assert_eq!(
+            toml::from_str::<ZoneMapping>("columns = 14").unwrap().mode,
+            ZoneMode::Edges
+        );
+    }
+}
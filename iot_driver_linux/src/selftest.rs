@@ -0,0 +1,372 @@
+//! Protocol conformance report for `iot_driver selftest`.
+//!
+//! The suite queries every read command the keyboard supports, then checks
+//! a few writable settings by writing a neighbouring value, reading it back
+//! and writing the original again. Reports are keyed by device and firmware
+//! version so odd firmware behaviour can be compared across user reports.
+//! This module holds the report, the round-trip logic and the choice of test
+//! values; talking to the device lives in the `selftest` command.
+
+use std::fmt::{Debug, Display, Write as _};
+
+use monsgeek_keyboard::led::{LedParams, BRIGHTNESS_MAX};
+use monsgeek_keyboard::settings::{KeyboardOptions, SleepTimeSettings};
+use serde::Serialize;
+
+/// Result of one check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Pass,
+    Fail,
+    /// The device doesn't have this feature
+    Skip,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Pass => "PASS",
+            Outcome::Fail => "FAIL",
+            Outcome::Skip => "skip",
+        }
+    }
+}
+
+/// Which part of the suite a check belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Read-only query
+    Query,
+    /// Write, read back, restore
+    Roundtrip,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub stage: Stage,
+    pub name: String,
+    pub outcome: Outcome,
+    /// Decoded value, or what went wrong
+    pub detail: String,
+}
+
+/// Pass/fail/skip totals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub pass: usize,
+    pub fail: usize,
+    pub skip: usize,
+}
+
+/// Conformance report for one device and firmware version.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+    pub device: String,
+    pub vid: u16,
+    pub pid: u16,
+    pub device_id: Option<u32>,
+    /// Firmware version as reported by GET_REV (raw u16)
+    pub firmware: u16,
+    pub transport: String,
+    pub driver: String,
+    pub checks: Vec<Check>,
+    /// Settings left at the test value because restoring them failed
+    pub not_restored: Vec<String>,
+}
+
+impl Report {
+    pub fn new(device: &str, vid: u16, pid: u16, transport: &str) -> Self {
+        Self {
+            device: device.to_string(),
+            vid,
+            pid,
+            transport: transport.to_string(),
+            driver: env!("CARGO_PKG_VERSION").to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Record a query: `Ok` carries the decoded value, `Err` the error.
+    pub fn query<E: Display>(&mut self, name: &str, result: Result<String, E>) {
+        let (outcome, detail) = match result {
+            Ok(v) => (Outcome::Pass, v),
+            Err(e) => (Outcome::Fail, e.to_string()),
+        };
+        self.push(Stage::Query, name, outcome, detail);
+    }
+
+    /// Record a check that doesn't apply to this device.
+    pub fn skip(&mut self, stage: Stage, name: &str, why: &str) {
+        self.push(stage, name, Outcome::Skip, why.to_string());
+    }
+
+    fn push(&mut self, stage: Stage, name: &str, outcome: Outcome, detail: String) {
+        self.checks.push(Check {
+            stage,
+            name: name.to_string(),
+            outcome,
+            detail,
+        });
+    }
+
+    /// Write `alt(original)`, read it back, then restore the original.
+    ///
+    /// `same` decides whether a read-back matches what was written (some
+    /// fields aren't echoed by every firmware). A failed restore fails the
+    /// check and is listed in `not_restored`, whatever the read-back said.
+    pub fn roundtrip<T: Debug, E: Display>(
+        &mut self,
+        name: &str,
+        read: impl Fn() -> Result<T, E>,
+        write: impl Fn(&T) -> Result<(), E>,
+        alt: impl Fn(&T) -> T,
+        same: impl Fn(&T, &T) -> bool,
+    ) {
+        let original = match read() {
+            Ok(v) => v,
+            Err(e) => {
+                self.push(Stage::Roundtrip, name, Outcome::Fail, format!("read: {e}"));
+                return;
+            }
+        };
+        let test = alt(&original);
+        let (mut outcome, mut detail) = match write(&test).and_then(|()| read()) {
+            Ok(got) if same(&got, &test) => (Outcome::Pass, format!("{original:?} -> {test:?}")),
+            Ok(got) => (Outcome::Fail, format!("wrote {test:?}, read back {got:?}")),
+            Err(e) => (Outcome::Fail, format!("write {test:?}: {e}")),
+        };
+
+        let restored = write(&original).and_then(|()| read());
+        if !matches!(&restored, Ok(got) if same(got, &original)) {
+            let why = match restored {
+                Ok(got) => format!("reads {got:?}"),
+                Err(e) => e.to_string(),
+            };
+            outcome = Outcome::Fail;
+            let _ = write!(detail, "; NOT RESTORED to {original:?} ({why})");
+            self.not_restored.push(name.to_string());
+        }
+        self.push(Stage::Roundtrip, name, outcome, detail);
+    }
+
+    pub fn counts(&self) -> Counts {
+        let mut counts = Counts::default();
+        for check in &self.checks {
+            match check.outcome {
+                Outcome::Pass => counts.pass += 1,
+                Outcome::Fail => counts.fail += 1,
+                Outcome::Skip => counts.skip += 1,
+            }
+        }
+        counts
+    }
+
+    /// `v10.29`-style firmware version.
+    pub fn firmware_str(&self) -> String {
+        format!("v{}.{:02}", self.firmware / 100, self.firmware % 100)
+    }
+
+    /// File name for `--output`: one report per device and firmware version.
+    pub fn file_name(&self) -> String {
+        let slug: String = self
+            .device
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        let slug = slug.trim_matches('-');
+        format!("selftest-{slug}-{:04x}-fw{}.json", self.pid, self.firmware)
+    }
+
+    /// Plain-text report.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let id = self
+            .device_id
+            .map_or_else(|| "-".to_string(), |id| id.to_string());
+        let _ = writeln!(
+            out,
+            "{} ({:04x}:{:04x}, device id {id}) over {}",
+            self.device, self.vid, self.pid, self.transport
+        );
+        let _ = writeln!(
+            out,
+            "Firmware {} (raw {}), iot_driver {}",
+            self.firmware_str(),
+            self.firmware,
+            self.driver
+        );
+        for (stage, title) in [
+            (Stage::Query, "Queries"),
+            (Stage::Roundtrip, "Write / read back / restore"),
+        ] {
+            let _ = writeln!(out, "\n{title}:");
+            for c in self.checks.iter().filter(|c| c.stage == stage) {
+                let _ = writeln!(
+                    out,
+                    "  {:<4}  {:<18} {}",
+                    c.outcome.as_str(),
+                    c.name,
+                    c.detail
+                );
+            }
+        }
+        let n = self.counts();
+        let _ = writeln!(
+            out,
+            "\n{} passed, {} failed, {} skipped",
+            n.pass, n.fail, n.skip
+        );
+        if !self.not_restored.is_empty() {
+            let _ = writeln!(
+                out,
+                "WARNING: not restored: {} (set them back by hand)",
+                self.not_restored.join(", ")
+            );
+        }
+        out
+    }
+}
+
+/// A debounce value next to `ms` within the firmware's 0-50 range.
+pub fn alt_debounce(ms: u8) -> u8 {
+    if ms >= 50 {
+        49
+    } else {
+        ms + 1
+    }
+}
+
+/// The same lighting one brightness step away.
+pub fn alt_led(params: &LedParams) -> LedParams {
+    let mut test = params.clone();
+    test.brightness = if params.brightness >= BRIGHTNESS_MAX {
+        BRIGHTNESS_MAX - 1
+    } else {
+        params.brightness + 1
+    };
+    test
+}
+
+/// Fields every firmware reads back after SET_LEDPARAM.
+pub fn same_led(a: &LedParams, b: &LedParams) -> bool {
+    a.mode == b.mode && a.brightness == b.brightness && a.color == b.color
+}
+
+/// The Bluetooth idle timeout moved by a minute.
+pub fn alt_sleep(settings: &SleepTimeSettings) -> SleepTimeSettings {
+    let mut test = *settings;
+    test.idle_bt = if settings.idle_bt >= 60 {
+        settings.idle_bt - 60
+    } else {
+        settings.idle_bt + 60
+    };
+    test
+}
+
+/// Anti-mistouch toggled; the least noticeable option while it's applied.
+pub fn alt_options(options: &KeyboardOptions) -> KeyboardOptions {
+    let mut test = options.clone();
+    test.anti_mistouch = !options.anti_mistouch;
+    test
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn roundtrip_passes_and_restores() {
+        let value = Cell::new(5u8);
+        let mut report = Report::default();
+        report.roundtrip(
+            "debounce",
+            || Ok::<_, String>(value.get()),
+            |v| {
+                value.set(*v);
+                Ok(())
+            },
+            |v| alt_debounce(*v),
+            |a, b| a == b,
+        );
+        assert_eq!(report.checks[0].outcome, Outcome::Pass);
+        assert_eq!(value.get(), 5);
+        assert!(report.not_restored.is_empty());
+    }
+
+    #[test]
+    fn roundtrip_flags_ignored_writes_and_failed_restores() {
+        // Firmware that ignores the write: fails, but nothing to restore
+        let mut report = Report::default();
+        report.roundtrip(
+            "debounce",
+            || Ok::<_, String>(5u8),
+            |_| Ok(()),
+            |v| alt_debounce(*v),
+            |a, b| a == b,
+        );
+        assert_eq!(report.checks[0].outcome, Outcome::Fail);
+        assert!(report.checks[0].detail.contains("read back 5"));
+        assert!(report.not_restored.is_empty());
+
+        // Writes land but the restore write errors
+        let value = Cell::new(5u8);
+        let writes = Cell::new(0);
+        report.roundtrip(
+            "sleep",
+            || Ok::<_, String>(value.get()),
+            |v| {
+                writes.set(writes.get() + 1);
+                if writes.get() > 1 {
+                    return Err("timeout".to_string());
+                }
+                value.set(*v);
+                Ok(())
+            },
+            |v| alt_debounce(*v),
+            |a, b| a == b,
+        );
+        assert_eq!(report.checks[1].outcome, Outcome::Fail);
+        assert!(report.checks[1].detail.contains("NOT RESTORED"));
+        assert_eq!(report.not_restored, vec!["sleep".to_string()]);
+        assert_eq!(
+            report.counts(),
+            Counts {
+                pass: 0,
+                fail: 2,
+                skip: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_values_stay_in_range() {
+        assert_eq!(alt_debounce(0), 1);
+        assert_eq!(alt_debounce(50), 49);
+        let sleep = SleepTimeSettings {
+            idle_bt: 30,
+            ..SleepTimeSettings::default()
+        };
+        assert_eq!(alt_sleep(&sleep).idle_bt, 90);
+        assert_eq!(alt_sleep(&alt_sleep(&sleep)).idle_bt, 30);
+    }
+
+    #[test]
+    fn file_name_keys_device_and_firmware() {
+        let mut report = Report::new("MonsGeek M1 V5 HE", 0x3151, 0x5030, "wired");
+        report.firmware = 1029;
+        assert_eq!(report.firmware_str(), "v10.29");
+        assert_eq!(
+            report.file_name(),
+            "selftest-monsgeek-m1-v5-he-5030-fw1029.json"
+        );
+    }
+}
@@ -1,16 +1,31 @@
 //! Persistent host-side settings (`~/.config/monsgeek/settings.toml`).
 //!
 //! Small, user-facing knobs that should survive across runs and live alongside
-//! the effects library — the audio/screen visualizer refresh rates and the XDG
+//! the effects library — the audio/screen visualizer refresh rates, the XDG
 //! ScreenCast restore token (so screen-reactive mode does not re-prompt the
-//! desktop portal picker every time).
+//! desktop portal picker every time), the names given to onboard profiles and
+//! who may talk to the driver server (`[server]`).
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
+use crate::ambient::AmbientConfig;
+use crate::battery_warning::BatteryWarning;
 use crate::effect::config_dir;
+use crate::focus::AppProfiles;
+use crate::idle_dim::IdleDim;
+use crate::lock_lighting::LockLighting;
+use crate::night_mode::NightMode;
+use crate::notify::desktop::DesktopNotifications;
+use crate::plugin::PluginSettings;
+use crate::remap::RemapConfig;
+use crate::schedule::LightingSchedule;
 use crate::screen_calib::{ColorCalibration, Region};
+use crate::screen_zones::ZoneMapping;
+use crate::sysmon::SysmonConfig;
+use crate::telemetry::TelemetryConfig;
 
 /// Default visualizer refresh rate (Hz) for both audio and screen modes.
 pub const DEFAULT_RATE_HZ: u32 = 50;
@@ -19,6 +34,88 @@ fn default_rate() -> u32 {
     DEFAULT_RATE_HZ
 }
 
+/// Browser origins allowed by default: the official web configurators.
+pub const DEFAULT_ALLOWED_ORIGINS: &[&str] = &[
+    "https://app.monsgeek.com",
+    "https://web.monsgeek.com",
+    "https://web.akkogear.com",
+];
+
+fn default_allowed_origins() -> Vec<String> {
+    DEFAULT_ALLOWED_ORIGINS
+        .iter()
+        .map(|o| o.to_string())
+        .collect()
+}
+
+/// Access control for `iot_driver serve`/`daemon` (gRPC, REST and the event
+/// WebSocket).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerSettings {
+    /// Shared secret every request must carry as `Authorization: Bearer
+    /// <token>` (or `?token=` on the WebSocket). Unset or empty disables
+    /// authentication; the official web app cannot send a token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Origins allowed to make browser (CORS) requests; `"*"` allows any.
+    #[serde(default = "default_allowed_origins")]
+    pub allowed_origins: Vec<String>,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            token: None,
+            allowed_origins: default_allowed_origins(),
+        }
+    }
+}
+
+impl ServerSettings {
+    /// The configured token, if authentication is enabled.
+    pub fn token(&self) -> Option<&str> {
+        self.token
+            .as_deref()
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+    }
+
+    /// Whether `presented` matches the token (always true without one).
+    /// Compares in constant time so the token cannot be guessed byte by byte.
+    pub fn token_matches(&self, presented: Option<&str>) -> bool {
+        let Some(token) = self.token() else {
+            return true;
+        };
+        let Some(presented) = presented else {
+            return false;
+        };
+        presented.len() == token.len()
+            && presented
+                .bytes()
+                .zip(token.bytes())
+                .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+
+    /// Whether the allowlist is `"*"`.
+    pub fn any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|o| o.trim() == "*")
+    }
+
+    /// Whether browser requests from `origin` are allowed.
+    pub fn origin_allowed(&self, origin: &str) -> bool {
+        self.any_origin()
+            || self
+                .allowed_origins
+                .iter()
+                .any(|o| o.trim().trim_end_matches('/').eq_ignore_ascii_case(origin))
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 /// Persisted host settings. Missing fields fall back to defaults, so older or
 /// hand-edited files keep loading.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +135,55 @@ pub struct Settings {
     /// Screen-sync capture region (normalized fractions of the screen).
     #[serde(default)]
     pub screen_region: Region,
+    /// Screen-sync key-zone mapping per device name, for layouts that don't
+    /// fill the whole LED matrix or prefer the grid mode.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub screen_zones: BTreeMap<String, ZoneMapping>,
+    /// Color temperature/brightness schedule for screen sync and, under the
+    /// daemon, the firmware lighting.
+    #[serde(default, skip_serializing_if = "NightMode::is_default")]
+    pub night_mode: NightMode,
+    /// Fade the firmware lighting while the user is idle (daemon).
+    #[serde(default, skip_serializing_if = "IdleDim::is_default")]
+    pub idle_dim: IdleDim,
+    /// Lighting while the session is locked (daemon).
+    #[serde(default, skip_serializing_if = "LockLighting::is_default")]
+    pub lock_lighting: LockLighting,
+    /// Time-of-day profile and LED mode schedule (daemon).
+    #[serde(default, skip_serializing_if = "LightingSchedule::is_default")]
+    pub schedule: LightingSchedule,
+    /// Profile and lighting per focused application (daemon).
+    #[serde(default, skip_serializing_if = "AppProfiles::is_default")]
+    pub app_profiles: AppProfiles,
+    /// Software remapping layer over uinput (daemon).
+    #[serde(default, skip_serializing_if = "RemapConfig::is_default")]
+    pub remap: RemapConfig,
+    /// Low-battery warning on the LEDs (daemon).
+    #[serde(default, skip_serializing_if = "BatteryWarning::is_default")]
+    pub battery_warning: BatteryWarning,
+    /// External state shown as key zone colors while idle (daemon).
+    #[serde(default, skip_serializing_if = "AmbientConfig::is_default")]
+    pub ambient: AmbientConfig,
+    /// System monitor gauge run by the daemon.
+    #[serde(default, skip_serializing_if = "SysmonConfig::is_default")]
+    pub sysmon: SysmonConfig,
+    /// Per-application key flashes for desktop notifications, shown by
+    /// `notify-daemon`.
+    #[serde(default, skip_serializing_if = "DesktopNotifications::is_default")]
+    pub desktop_notifications: DesktopNotifications,
+    /// Value-to-key-zone mappings for `iot_driver telemetry`.
+    #[serde(default, skip_serializing_if = "TelemetryConfig::is_default")]
+    pub telemetry: TelemetryConfig,
+    /// Which plugins the daemon loads and runs, and their settings.
+    #[serde(default, skip_serializing_if = "PluginSettings::is_default")]
+    pub plugins: PluginSettings,
+    /// Local profile names per device name, indexed by profile; the firmware
+    /// has nowhere to store them. Empty strings mean unnamed.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profile_names: BTreeMap<String, Vec<String>>,
+    /// Server authentication and CORS origins.
+    #[serde(default, skip_serializing_if = "ServerSettings::is_default")]
+    pub server: ServerSettings,
 }
 
 impl Default for Settings {
@@ -48,6 +194,21 @@ impl Default for Settings {
             screencast_restore_token: None,
             screen_calibration: ColorCalibration::default(),
             screen_region: Region::default(),
+            screen_zones: BTreeMap::new(),
+            night_mode: NightMode::default(),
+            idle_dim: IdleDim::default(),
+            lock_lighting: LockLighting::default(),
+            schedule: LightingSchedule::default(),
+            app_profiles: AppProfiles::default(),
+            remap: RemapConfig::default(),
+            battery_warning: BatteryWarning::default(),
+            ambient: AmbientConfig::default(),
+            sysmon: SysmonConfig::default(),
+            desktop_notifications: DesktopNotifications::default(),
+            telemetry: TelemetryConfig::default(),
+            plugins: PluginSettings::default(),
+            profile_names: BTreeMap::new(),
+            server: ServerSettings::default(),
         }
     }
 }
@@ -84,6 +245,35 @@ impl Settings {
         std::fs::write(&path, content).map_err(|e| format!("write {}: {e}", path.display()))
     }
 
+    /// Local name of `profile` on `device`, if one was given.
+    pub fn profile_name(&self, device: &str, profile: u8) -> Option<&str> {
+        self.profile_names
+            .get(device)
+            .and_then(|names| names.get(profile as usize))
+            .map(String::as_str)
+            .filter(|n| !n.is_empty())
+    }
+
+    /// Name `profile` on `device`; an empty name clears it.
+    pub fn set_profile_name(&mut self, device: &str, profile: u8, name: &str) {
+        let names = self.profile_names.entry(device.to_string()).or_default();
+        if names.len() <= profile as usize {
+            names.resize(profile as usize + 1, String::new());
+        }
+        names[profile as usize] = name.trim().to_string();
+        while names.last().is_some_and(String::is_empty) {
+            names.pop();
+        }
+        if names.is_empty() {
+            self.profile_names.remove(device);
+        }
+    }
+
+    /// Zone mapping for `device`, or the full-matrix default.
+    pub fn zone_mapping(&self, device: &str) -> ZoneMapping {
+        self.screen_zones.get(device).copied().unwrap_or_default()
+    }
+
     /// Load, mutate, and save in one step; logs (does not propagate) save errors.
     pub fn update(f: impl FnOnce(&mut Settings)) {
         let mut s = Self::load();
@@ -93,3 +283,38 @@ impl Settings {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_names_set_and_clear() {
+        let mut s = Settings::default();
+        s.set_profile_name("M1 V5", 2, " Gaming ");
+        assert_eq!(s.profile_name("M1 V5", 2), Some("Gaming"));
+        assert_eq!(s.profile_name("M1 V5", 0), None);
+        assert_eq!(s.profile_name("Other", 2), None);
+        let text = toml::to_string(&s).unwrap();
+        let back: Settings = toml::from_str(&text).unwrap();
+        assert_eq!(back.profile_name("M1 V5", 2), Some("Gaming"));
+        s.set_profile_name("M1 V5", 2, "");
+        assert!(s.profile_names.is_empty());
+    }
+
+    #[test]
+    fn server_access_defaults_and_overrides() {
+        let s: Settings = toml::from_str("").unwrap();
+        assert!(s.server.token_matches(None));
+        assert!(s.server.origin_allowed("https://app.monsgeek.com"));
+        assert!(!s.server.origin_allowed("https://evil.example"));
+        assert!(!toml::to_string(&s).unwrap().contains("[server]"));
+
+        let s: Settings =
+            toml::from_str("[server]\ntoken = \"s3cret\"\nallowed_origins = [\"*\"]").unwrap();
+        assert!(s.server.token_matches(Some("s3cret")));
+        assert!(!s.server.token_matches(Some("s3creT")));
+        assert!(!s.server.token_matches(None));
+        assert!(s.server.origin_allowed("http://localhost:8080"));
+    }
+}
@@ -0,0 +1,181 @@
+//! WebRTC signaling mailbox.
+//!
+//! Pairs a remote client's SDP offer with the answer from the local peer
+//! that owns the keyboard, so a data channel can be set up through the
+//! driver server alone. Offers wait in a short queue until the local peer
+//! picks them up; SDPs are exchanged whole (ICE candidates gathered up
+//! front, no trickle).
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::sync::{oneshot, Notify};
+
+/// Offers waiting for the local peer at most.
+pub const MAX_PENDING: usize = 8;
+
+/// Why an offer or answer was not delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalError {
+    /// Too many offers are already waiting.
+    Busy,
+    /// No answer arrived in time.
+    Timeout,
+    /// The offer was withdrawn or never existed.
+    UnknownSession,
+}
+
+impl std::fmt::Display for SignalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Busy => "too many pending offers",
+            Self::Timeout => "no local peer answered",
+            Self::UnknownSession => "unknown or expired session",
+        })
+    }
+}
+
+impl std::error::Error for SignalError {}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    /// Offers not yet handed to the local peer, oldest first
+    queued: VecDeque<(u64, String)>,
+    /// Offers still waiting for an answer
+    waiting: HashMap<u64, oneshot::Sender<String>>,
+}
+
+/// Withdraws an offer when its waiter finishes or is dropped (the remote
+/// client disconnected mid-wait), so abandoned offers don't hold a slot.
+struct Withdraw<'a> {
+    inner: &'a Mutex<Inner>,
+    id: u64,
+}
+
+impl Drop for Withdraw<'_> {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.queued.retain(|(queued, _)| *queued != self.id);
+        inner.waiting.remove(&self.id);
+    }
+}
+
+/// Offer/answer exchange between remote clients and the local peer.
+#[derive(Default)]
+pub struct Signaling {
+    inner: Mutex<Inner>,
+    offered: Notify,
+}
+
+impl Signaling {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remote side: queue `sdp` and wait up to `timeout` for the answer.
+    pub async fn offer(&self, sdp: String, timeout: Duration) -> Result<String, SignalError> {
+        let (tx, rx) = oneshot::channel();
+        let id = {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.waiting.len() >= MAX_PENDING {
+                return Err(SignalError::Busy);
+            }
+            inner.next_id += 1;
+            let id = inner.next_id;
+            inner.queued.push_back((id, sdp));
+            inner.waiting.insert(id, tx);
+            id
+        };
+        let _withdraw = Withdraw {
+            inner: &self.inner,
+            id,
+        };
+        self.offered.notify_one();
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(sdp)) => Ok(sdp),
+            _ => Err(SignalError::Timeout),
+        }
+    }
+
+    /// Local peer: the next queued offer as (session id, SDP), waiting up
+    /// to `timeout` for one to arrive.
+    pub async fn next_offer(&self, timeout: Duration) -> Option<(u64, String)> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let offered = self.offered.notified();
+            if let Some(offer) = self.inner.lock().unwrap().queued.pop_front() {
+                return Some(offer);
+            }
+            if tokio::time::timeout_at(deadline, offered).await.is_err() {
+                return None;
+            }
+        }
+    }
+
+    /// Local peer: deliver the answer for session `id`.
+    pub fn answer(&self, id: u64, sdp: String) -> Result<(), SignalError> {
+        let tx = self.inner.lock().unwrap().waiting.remove(&id);
+        tx.ok_or(SignalError::UnknownSession)?
+            .send(sdp)
+            .map_err(|_| SignalError::UnknownSession)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn offer_is_answered_by_local_peer() {
+        let signaling = Arc::new(Signaling::new());
+        let remote = {
+            let signaling = Arc::clone(&signaling);
+            tokio::spawn(async move {
+                signaling
+                    .offer("v=0 offer".into(), Duration::from_secs(5))
+                    .await
+            })
+        };
+        let (id, sdp) = signaling.next_offer(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(sdp, "v=0 offer");
+        signaling.answer(id, "v=0 answer".into()).unwrap();
+        assert_eq!(remote.await.unwrap().unwrap(), "v=0 answer");
+        assert_eq!(
+            signaling.answer(id, "again".into()),
+            Err(SignalError::UnknownSession)
+        );
+    }
+
+    #[tokio::test]
+    async fn unanswered_offer_times_out_and_is_withdrawn() {
+        let signaling = Signaling::new();
+        let result = signaling
+            .offer("v=0".into(), Duration::from_millis(10))
+            .await;
+        assert_eq!(result, Err(SignalError::Timeout));
+        assert!(signaling
+            .next_offer(Duration::from_millis(10))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn dropped_offer_frees_its_slot() {
+        let signaling = Signaling::new();
+        for _ in 0..MAX_PENDING + 1 {
+            let offer = signaling.offer("v=0".into(), Duration::from_secs(60));
+            // Poll once so the offer is queued, then drop it mid-wait
+            let polled = tokio::time::timeout(Duration::from_millis(1), offer).await;
+            assert!(polled.is_err());
+        }
+        assert!(signaling.inner.lock().unwrap().waiting.is_empty());
+        assert!(signaling
+            .next_offer(Duration::from_millis(10))
+            .await
+            .is_none());
+    }
+}
@@ -0,0 +1,267 @@
+//! Hall-sensor health diagnostics from resting key depth.
+//!
+//! With magnetism reporting enabled and no keys pressed, a healthy switch
+//! stays silent (or reports 0). Failing sensors and misaligned magnets show up
+//! as jitter around a non-zero baseline, a baseline that creeps over time, or
+//! a reading that never returns to zero — all of which eventually cross the
+//! actuation point as phantom presses. This module only does the statistics;
+//! sampling the device lives in the `switch-health` command.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Limits above which a resting key is flagged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HealthThresholds {
+    /// Max standard deviation of resting depth (mm).
+    pub noise_mm: f32,
+    /// Max change between the early and late mean depth (mm).
+    pub drift_mm: f32,
+    /// Depth (mm) above which a resting reading counts as "not at rest".
+    pub stuck_mm: f32,
+    /// Fraction of the sampling window a key may spend above `stuck_mm`
+    /// before it is reported as stuck.
+    pub stuck_fraction: f32,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            noise_mm: 0.05,
+            drift_mm: 0.10,
+            stuck_mm: 0.10,
+            stuck_fraction: 0.5,
+        }
+    }
+}
+
+/// A problem detected on one switch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SwitchIssue {
+    /// Resting depth jitters more than `noise_mm`.
+    Noisy,
+    /// Resting baseline moved more than `drift_mm` during the window.
+    Drifting,
+    /// Reading stayed above `stuck_mm` for most of the window.
+    Stuck,
+}
+
+impl SwitchIssue {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SwitchIssue::Noisy => "noisy",
+            SwitchIssue::Drifting => "drifting",
+            SwitchIssue::Stuck => "stuck",
+        }
+    }
+}
+
+impl std::fmt::Display for SwitchIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Summary statistics for one key over the sampling window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyHealth {
+    pub key_index: u8,
+    /// Number of depth reports received for this key.
+    pub samples: usize,
+    pub mean_mm: f32,
+    pub stddev_mm: f32,
+    pub min_mm: f32,
+    pub max_mm: f32,
+    /// Mean of the last third of samples minus mean of the first third.
+    pub drift_mm: f32,
+    /// Fraction of the window spent above the stuck threshold (0..1).
+    pub stuck_fraction: f32,
+    pub issues: Vec<SwitchIssue>,
+}
+
+impl KeyHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+struct KeySamples {
+    /// (offset from start, depth mm), in arrival order.
+    samples: Vec<(Duration, f32)>,
+}
+
+/// Accumulates resting depth reports and evaluates them against thresholds.
+///
+/// The firmware only reports a key when its depth changes, so the last value
+/// seen is held until the next report (or the end of the window) when
+/// computing how long a key sat above the stuck threshold.
+#[derive(Debug, Default)]
+pub struct SwitchHealthMonitor {
+    keys: BTreeMap<u8, KeySamples>,
+}
+
+impl SwitchHealthMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one depth report at `at` (time since sampling started).
+    pub fn record(&mut self, key_index: u8, depth_mm: f32, at: Duration) {
+        self.keys
+            .entry(key_index)
+            .or_default()
+            .samples
+            .push((at, depth_mm));
+    }
+
+    /// Number of distinct keys that reported at least once.
+    pub fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Evaluate every key that reported during a window of length `window`.
+    ///
+    /// Keys are returned in matrix order; keys that never reported are
+    /// omitted (they are, by definition, quiet).
+    pub fn evaluate(&self, window: Duration, thresholds: &HealthThresholds) -> Vec<KeyHealth> {
+        self.keys
+            .iter()
+            .filter(|(_, s)| !s.samples.is_empty())
+            .map(|(&key_index, s)| evaluate_key(key_index, &s.samples, window, thresholds))
+            .collect()
+    }
+}
+
+fn mean(values: impl Iterator<Item = f32>) -> f32 {
+    let (sum, n) = values.fold((0.0f32, 0usize), |(s, n), v| (s + v, n + 1));
+    if n == 0 {
+        0.0
+    } else {
+        sum / n as f32
+    }
+}
+
+fn evaluate_key(
+    key_index: u8,
+    samples: &[(Duration, f32)],
+    window: Duration,
+    t: &HealthThresholds,
+) -> KeyHealth {
+    let n = samples.len();
+    let mean_mm = mean(samples.iter().map(|(_, d)| *d));
+    let variance = mean(samples.iter().map(|(_, d)| (d - mean_mm).powi(2)));
+    let stddev_mm = variance.sqrt();
+    let min_mm = samples.iter().map(|(_, d)| *d).fold(f32::MAX, f32::min);
+    let max_mm = samples.iter().map(|(_, d)| *d).fold(f32::MIN, f32::max);
+
+    // Drift needs enough samples for the thirds to mean anything.
+    let drift_mm = if n >= 6 {
+        let third = n / 3;
+        let early = mean(samples[..third].iter().map(|(_, d)| *d));
+        let late = mean(samples[n - third..].iter().map(|(_, d)| *d));
+        late - early
+    } else {
+        0.0
+    };
+
+    // Sample-and-hold: each reading lasts until the next one (or window end).
+    let mut above = Duration::ZERO;
+    for (i, &(at, depth)) in samples.iter().enumerate() {
+        let until = samples.get(i + 1).map(|(next, _)| *next).unwrap_or(window);
+        if depth > t.stuck_mm {
+            above += until.saturating_sub(at);
+        }
+    }
+    let stuck_fraction = if window.is_zero() {
+        0.0
+    } else {
+        (above.as_secs_f32() / window.as_secs_f32()).min(1.0)
+    };
+
+    let mut issues = Vec::new();
+    if n >= 2 && stddev_mm > t.noise_mm {
+        issues.push(SwitchIssue::Noisy);
+    }
+    if drift_mm.abs() > t.drift_mm {
+        issues.push(SwitchIssue::Drifting);
+    }
+    if stuck_fraction > t.stuck_fraction {
+        issues.push(SwitchIssue::Stuck);
+    }
+
+    KeyHealth {
+        key_index,
+        samples: n,
+        mean_mm,
+        stddev_mm,
+        min_mm,
+        max_mm,
+        drift_mm,
+        stuck_fraction,
+        issues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(v: u64) -> Duration {
+        Duration::from_millis(v)
+    }
+
+    #[test]
+    fn quiet_key_is_healthy() {
+        let mut m = SwitchHealthMonitor::new();
+        for i in 0..10 {
+            m.record(3, 0.0, ms(i * 100));
+        }
+        let r = m.evaluate(ms(1000), &HealthThresholds::default());
+        assert_eq!(r.len(), 1);
+        assert!(r[0].is_healthy());
+        assert_eq!(r[0].samples, 10);
+    }
+
+    #[test]
+    fn jitter_is_noisy() {
+        let mut m = SwitchHealthMonitor::new();
+        for i in 0..20 {
+            let d = if i % 2 == 0 { 0.3 } else { 0.0 };
+            m.record(7, d, ms(i * 10));
+        }
+        let r = m.evaluate(ms(400), &HealthThresholds::default());
+        assert!(r[0].issues.contains(&SwitchIssue::Noisy));
+        assert!(!r[0].issues.contains(&SwitchIssue::Stuck));
+    }
+
+    #[test]
+    fn creeping_baseline_is_drifting() {
+        let mut m = SwitchHealthMonitor::new();
+        for i in 0..30u64 {
+            m.record(1, i as f32 * 0.01, ms(i * 100));
+        }
+        let r = m.evaluate(ms(3000), &HealthThresholds::default());
+        assert!(r[0].drift_mm > 0.1);
+        assert!(r[0].issues.contains(&SwitchIssue::Drifting));
+    }
+
+    #[test]
+    fn held_nonzero_reading_is_stuck() {
+        // A single report that never returns to zero holds for the whole window.
+        let mut m = SwitchHealthMonitor::new();
+        m.record(9, 0.8, ms(0));
+        let r = m.evaluate(ms(5000), &HealthThresholds::default());
+        assert_eq!(r[0].issues, vec![SwitchIssue::Stuck]);
+        assert!((r[0].stuck_fraction - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn brief_excursion_is_not_stuck() {
+        let mut m = SwitchHealthMonitor::new();
+        m.record(2, 0.5, ms(0));
+        m.record(2, 0.0, ms(100));
+        let r = m.evaluate(ms(5000), &HealthThresholds::default());
+        assert!(!r[0].issues.contains(&SwitchIssue::Stuck));
+    }
+}
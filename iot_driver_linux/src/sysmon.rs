@@ -0,0 +1,410 @@
+//! System monitor gauge (`iot_driver sysmon`, `[sysmon]` under the daemon).
+//!
+//! Samples CPU load (`/proc/stat`), RAM usage (`/proc/meminfo`) or the CPU
+//! temperature (thermal zones / hwmon) a few times per second and renders it
+//! as lit columns or rows, green through yellow to red. Frames go out through
+//! the sparse overlay diff, so an idle gauge costs almost no USB traffic.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::led_stream::{apply_power_budget, send_overlay_diff};
+use crate::notify::keymap::{COLS, MATRIX_LEN, ROWS};
+use monsgeek_keyboard::KeyboardInterface;
+
+/// Resend every lit key this often, in case the keyboard slept and lost the
+/// overlay while the diff saw nothing to change.
+const FULL_REFRESH: Duration = Duration::from_secs(10);
+/// How often the daemon re-reads `settings.toml`.
+const CONFIG_RELOAD: Duration = Duration::from_secs(5);
+/// How long the daemon waits before retrying a keyboard without streaming.
+const RETRY: Duration = Duration::from_secs(60);
+
+/// What the gauge shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    /// CPU load across all cores
+    #[default]
+    Cpu,
+    /// Memory in use (total minus available)
+    Ram,
+    /// Hottest CPU temperature sensor, scaled over `temp_min..temp_max`
+    Temp,
+}
+
+/// How the level fills the board.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GaugeLayout {
+    /// Columns light up left to right
+    #[default]
+    Columns,
+    /// Rows light up bottom to top
+    Rows,
+}
+
+/// Gauge settings, `[sysmon]` in `settings.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SysmonConfig {
+    /// Run the gauge under `iot_driver daemon`
+    pub enabled: bool,
+    pub metric: Metric,
+    pub layout: GaugeLayout,
+    /// Update interval in milliseconds
+    pub interval_ms: u64,
+    /// LED power budget in milliamps (0 = unlimited)
+    pub power_budget: u32,
+    /// Temperature shown as an empty gauge (°C)
+    pub temp_min: f32,
+    /// Temperature shown as a full gauge (°C)
+    pub temp_max: f32,
+}
+
+impl Default for SysmonConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            metric: Metric::default(),
+            layout: GaugeLayout::default(),
+            interval_ms: 250,
+            power_budget: 400,
+            temp_min: 30.0,
+            temp_max: 90.0,
+        }
+    }
+}
+
+impl SysmonConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.clamp(50, 10_000))
+    }
+}
+
+/// `(busy, total)` jiffies from the aggregate `cpu` line of `/proc/stat`.
+/// iowait counts as idle.
+pub fn parse_cpu_times(stat: &str) -> Option<(u64, u64)> {
+    let line = stat.lines().find(|l| l.starts_with("cpu "))?;
+    let fields: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .map(|f| f.parse().ok())
+        .collect::<Option<_>>()?;
+    if fields.len() < 4 {
+        return None;
+    }
+    let total: u64 = fields.iter().take(8).sum();
+    let idle = fields[3] + fields.get(4).copied().unwrap_or(0);
+    Some((total - idle, total))
+}
+
+/// Fraction of memory in use from `/proc/meminfo`.
+pub fn parse_mem_usage(meminfo: &str) -> Option<f32> {
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|l| l.strip_prefix(name)?.strip_prefix(':'))?
+            .split_whitespace()
+            .next()?
+            .parse::<u64>()
+            .ok()
+    };
+    let total = field("MemTotal")?;
+    let available = field("MemAvailable")?;
+    (total > 0).then(|| (total.saturating_sub(available)) as f32 / total as f32)
+}
+
+/// Hottest CPU sensor in °C: thermal zones first, then the usual hwmon
+/// CPU drivers (AMD exposes no thermal zone).
+pub fn read_cpu_temperature() -> Option<f32> {
+    let read_milli = |p: &Path| {
+        std::fs::read_to_string(p)
+            .ok()?
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .map(|m| m as f32 / 1000.0)
+    };
+    let max = |temps: Vec<f32>| temps.into_iter().reduce(f32::max);
+
+    let zones = std::fs::read_dir("/sys/class/thermal").ok().map(|dir| {
+        dir.flatten()
+            .filter(|e| e.file_name().to_string_lossy().starts_with("thermal_zone"))
+            .filter_map(|e| read_milli(&e.path().join("temp")))
+            .collect::<Vec<_>>()
+    });
+    if let Some(t) = zones.and_then(max) {
+        return Some(t);
+    }
+
+    let dir = std::fs::read_dir("/sys/class/hwmon").ok()?;
+    let temps = dir
+        .flatten()
+        .filter(|e| {
+            let name = std::fs::read_to_string(e.path().join("name")).unwrap_or_default();
+            matches!(
+                name.trim(),
+                "coretemp" | "k10temp" | "zenpower" | "cpu_thermal"
+            )
+        })
+        .filter_map(|e| read_milli(&e.path().join("temp1_input")))
+        .collect();
+    max(temps)
+}
+
+/// Samples the configured metric as a 0.0-1.0 level.
+#[derive(Debug, Default)]
+pub struct MetricSampler {
+    prev_cpu: Option<(u64, u64)>,
+}
+
+impl MetricSampler {
+    /// CPU load since the previous call; `None` on the first call.
+    fn cpu_load(&mut self, times: (u64, u64)) -> Option<f32> {
+        let prev = self.prev_cpu.replace(times)?;
+        let busy = times.0.saturating_sub(prev.0);
+        let total = times.1.saturating_sub(prev.1);
+        (total > 0).then(|| busy as f32 / total as f32)
+    }
+
+    /// Current level, or `None` if the metric can't be read (yet).
+    pub fn sample(&mut self, config: &SysmonConfig) -> Option<f32> {
+        let level = match config.metric {
+            Metric::Cpu => {
+                let stat = std::fs::read_to_string("/proc/stat").ok()?;
+                self.cpu_load(parse_cpu_times(&stat)?)?
+            }
+            Metric::Ram => parse_mem_usage(&std::fs::read_to_string("/proc/meminfo").ok()?)?,
+            Metric::Temp => {
+                let span = (config.temp_max - config.temp_min).max(1.0);
+                (read_cpu_temperature()? - config.temp_min) / span
+            }
+        };
+        Some(level.clamp(0.0, 1.0))
+    }
+}
+
+/// Green at 0.0, yellow at 0.5, red at 1.0.
+pub fn level_color(t: f32) -> (u8, u8, u8) {
+    let t = t.clamp(0.0, 1.0);
+    let r = (t * 2.0).min(1.0);
+    let g = ((1.0 - t) * 2.0).min(1.0);
+    ((r * 255.0).round() as u8, (g * 255.0).round() as u8, 0)
+}
+
+/// One LED frame (row-major) with the gauge at `level`. Each column/row has
+/// the color of its position, and the leading one fades in with the
+/// fractional part.
+pub fn render_gauge(level: f32, layout: GaugeLayout) -> [(u8, u8, u8); MATRIX_LEN] {
+    let mut leds = [(0, 0, 0); MATRIX_LEN];
+    let cells = match layout {
+        GaugeLayout::Columns => COLS,
+        GaugeLayout::Rows => ROWS,
+    };
+    let fill = level.clamp(0.0, 1.0) * cells as f32;
+    for (i, led) in leds.iter_mut().enumerate() {
+        let (row, col) = (i / COLS, i % COLS);
+        let cell = match layout {
+            GaugeLayout::Columns => col,
+            GaugeLayout::Rows => ROWS - 1 - row,
+        };
+        let brightness = (fill - cell as f32).clamp(0.0, 1.0);
+        let (r, g, b) = level_color(cell as f32 / (cells - 1) as f32);
+        let s = |v: u8| (v as f32 * brightness).round() as u8;
+        *led = (s(r), s(g), s(b));
+    }
+    leds
+}
+
+/// A running gauge: sampler plus what is currently on the keyboard.
+pub struct Gauge {
+    pub config: SysmonConfig,
+    sampler: MetricSampler,
+    shown: [(u8, u8, u8); MATRIX_LEN],
+    last_full: Option<Instant>,
+}
+
+impl Gauge {
+    pub fn new(config: SysmonConfig) -> Self {
+        Self {
+            config,
+            sampler: MetricSampler::default(),
+            shown: [(0, 0, 0); MATRIX_LEN],
+            last_full: None,
+        }
+    }
+
+    /// Forget what the keyboard shows, so the next tick sends every key.
+    pub fn reset(&mut self) {
+        self.last_full = None;
+    }
+
+    /// Sample and send the changed keys. Returns the level, or `None` if the
+    /// metric couldn't be read this time.
+    pub fn tick(&mut self, kb: &KeyboardInterface) -> Result<Option<f32>, String> {
+        let Some(level) = self.sampler.sample(&self.config) else {
+            return Ok(None);
+        };
+        let mut leds = render_gauge(level, self.config.layout);
+        apply_power_budget(&mut leds, self.config.power_budget);
+
+        if self.last_full.is_none_or(|t| t.elapsed() >= FULL_REFRESH) {
+            let entries: Vec<_> = leds
+                .iter()
+                .enumerate()
+                .map(|(i, &(r, g, b))| (i as u8, r, g, b))
+                .collect();
+            kb.stream_led_sparse(&entries)
+                .map_err(|e| format!("send gauge: {e}"))?;
+            self.last_full = Some(Instant::now());
+        } else {
+            send_overlay_diff(kb, &self.shown, &leds).map_err(|e| format!("send gauge: {e}"))?;
+        }
+        self.shown = leds;
+        Ok(Some(level))
+    }
+}
+
+/// Whether the keyboard's firmware patch supports LED streaming.
+pub fn supports_streaming(kb: &KeyboardInterface) -> bool {
+    matches!(kb.get_patch_info(), Ok(Some(p)) if p.has_led_stream())
+}
+
+/// Daemon loop: run the gauge while `[sysmon]` is enabled in `settings.toml`,
+/// re-reading it every [`CONFIG_RELOAD`] so edits apply without a restart.
+/// The keyboard is opened on demand and reopened after errors.
+pub fn run_daemon_gauge(open: impl Fn() -> Result<KeyboardInterface, String>) {
+    let mut keyboard: Option<KeyboardInterface> = None;
+    let mut gauge = Gauge::new(SysmonConfig::default());
+    loop {
+        let config = crate::settings::Settings::load().sysmon;
+        if !config.enabled {
+            if let Some(kb) = keyboard.take() {
+                kb.stream_led_release().ok();
+            }
+            std::thread::sleep(CONFIG_RELOAD);
+            continue;
+        }
+
+        if keyboard.is_none() {
+            match open() {
+                Ok(kb) if supports_streaming(&kb) => {
+                    gauge.reset();
+                    keyboard = Some(kb);
+                }
+                Ok(_) => {
+                    tracing::warn!("sysmon: firmware has no LED streaming patch");
+                    std::thread::sleep(RETRY);
+                    continue;
+                }
+                Err(e) => {
+                    tracing::debug!("sysmon: no keyboard: {e}");
+                    std::thread::sleep(CONFIG_RELOAD);
+                    continue;
+                }
+            }
+        }
+        if gauge.config.metric != config.metric {
+            gauge = Gauge::new(config);
+        }
+        gauge.config = config;
+
+        let mut failed = false;
+        if let Some(kb) = &keyboard {
+            let until = Instant::now() + CONFIG_RELOAD;
+            while Instant::now() < until {
+                if let Err(e) = gauge.tick(kb) {
+                    tracing::warn!("sysmon: {e}");
+                    failed = true;
+                    break;
+                }
+                std::thread::sleep(config.interval());
+            }
+        }
+        if failed {
+            keyboard = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_load_is_busy_share_of_the_delta() {
+        let a = "cpu  100 0 100 700 100 0 0 0 0 0\ncpu0 1 2 3 4\n";
+        let b = "cpu  250 0 150 900 100 0 0 0 0 0\n";
+        assert_eq!(parse_cpu_times(a), Some((200, 1000)));
+        let mut sampler = MetricSampler::default();
+        assert_eq!(sampler.cpu_load(parse_cpu_times(a).unwrap()), None);
+        assert_eq!(sampler.cpu_load(parse_cpu_times(b).unwrap()), Some(0.5));
+        assert_eq!(parse_cpu_times("intr 5\n"), None);
+    }
+
+    #[test]
+    fn memory_usage_excludes_available() {
+        let meminfo = "MemTotal:       16000000 kB\nMemFree:         1000000 kB\nMemAvailable:    4000000 kB\n";
+        assert_eq!(parse_mem_usage(meminfo), Some(0.75));
+        assert_eq!(parse_mem_usage("MemTotal: 100 kB\n"), None);
+    }
+
+    #[test]
+    fn colors_run_green_yellow_red() {
+        assert_eq!(level_color(0.0), (0, 255, 0));
+        assert_eq!(level_color(0.5), (255, 255, 0));
+        assert_eq!(level_color(1.0), (255, 0, 0));
+    }
+
+    #[test]
+    fn columns_fill_left_to_right() {
+        let leds = render_gauge(0.5, GaugeLayout::Columns);
+        for row in 0..ROWS {
+            assert!(leds[row * COLS..row * COLS + COLS / 2]
+                .iter()
+                .all(|&c| c != (0, 0, 0)));
+            assert!(leds[row * COLS + COLS / 2..(row + 1) * COLS]
+                .iter()
+                .all(|&c| c == (0, 0, 0)));
+        }
+        assert_eq!(leds[0], (0, 255, 0));
+        assert!(render_gauge(0.0, GaugeLayout::Columns)
+            .iter()
+            .all(|&c| c == (0, 0, 0)));
+    }
+
+    #[test]
+    fn rows_fill_bottom_up() {
+        let leds = render_gauge(1.0 / ROWS as f32, GaugeLayout::Rows);
+        let lit: Vec<usize> = (0..MATRIX_LEN).filter(|&i| leds[i] != (0, 0, 0)).collect();
+        assert_eq!(lit, ((ROWS - 1) * COLS..MATRIX_LEN).collect::<Vec<_>>());
+        // Full gauge: top row is red
+        assert_eq!(render_gauge(1.0, GaugeLayout::Rows)[3], (255, 0, 0));
+    }
+
+    #[test]
+    fn config_round_trips_through_toml() {
+        let config = SysmonConfig {
+            enabled: true,
+            metric: Metric::Temp,
+            layout: GaugeLayout::Rows,
+            ..Default::default()
+        };
+        let text = toml::to_string(&config).unwrap();
+        assert!(text.contains("metric = \"temp\""));
+        assert_eq!(toml::from_str::<SysmonConfig>(&text).unwrap(), config);
+        assert_eq!(
+            toml::from_str::<SysmonConfig>("metric = \"ram\"")
+                .unwrap()
+                .interval_ms,
+            250
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! Minimal systemd service integration: `sd_notify` and socket activation.
+//!
+//! Both are plain environment/socket protocols, so they are spoken directly
+//! rather than through libsystemd. Outside systemd (no `NOTIFY_SOCKET`, no
+//! `LISTEN_FDS`) every function here is a no-op, so `iot_driver daemon` also
+//! runs fine from a terminal.
+
+use std::os::fd::{FromRawFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+
+/// First file descriptor passed by socket activation (`SD_LISTEN_FDS_START`).
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Send a state string (e.g. `READY=1`, `STATUS=...`) to the service manager.
+/// Returns `Ok(false)` when not running under a notify-type service.
+pub fn notify(state: &str) -> std::io::Result<bool> {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    let socket = UnixDatagram::unbound()?;
+    let path = path.to_string_lossy();
+    if let Some(name) = path.strip_prefix('@') {
+        // Abstract namespace socket
+        use std::os::linux::net::SocketAddrExt;
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+        socket.send_to_addr(state.as_bytes(), &addr)?;
+    } else {
+        socket.send_to(state.as_bytes(), path.as_ref())?;
+    }
+    Ok(true)
+}
+
+/// Like [`notify`], logging instead of failing; readiness reporting must never
+/// take the daemon down.
+pub fn notify_or_log(state: &str) {
+    if let Err(e) = notify(state) {
+        tracing::warn!("sd_notify({state}): {e}");
+    }
+}
+
+/// Half the watchdog interval requested via `WATCHDOG_USEC`, i.e. how often
+/// `WATCHDOG=1` should be sent. `None` when no watchdog is configured (or it
+/// is meant for another process).
+pub fn watchdog_interval() -> Option<Duration> {
+    parse_watchdog(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if pid.is_some_and(|p| p.parse() != Ok(own_pid)) {
+        return None;
+    }
+    let usec: u64 = usec?.parse().ok().filter(|&u| u > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+/// Number of sockets passed by socket activation, if they are for us.
+fn listen_fds_count(fds: Option<&str>, pid: Option<&str>, own_pid: u32) -> usize {
+    if pid.and_then(|p| p.parse().ok()) != Some(own_pid) {
+        return 0;
+    }
+    fds.and_then(|n| n.parse().ok()).unwrap_or(0)
+}
+
+/// Take the first socket-activated TCP listener, if systemd passed one.
+/// The activation variables are cleared so child processes don't inherit them.
+pub fn take_tcp_listener() -> std::io::Result<Option<std::net::TcpListener>> {
+    let count = listen_fds_count(
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::process::id(),
+    );
+    for var in ["LISTEN_FDS", "LISTEN_PID", "LISTEN_FDNAMES"] {
+        std::env::remove_var(var);
+    }
+    if count == 0 {
+        return Ok(None);
+    }
+    if count > 1 {
+        tracing::warn!("socket activation passed {count} sockets; using the first");
+    }
+    // SAFETY: systemd hands us ownership of fds LISTEN_FDS_START.. for the
+    // matching LISTEN_PID; each is taken exactly once, here.
+    let listener = unsafe { std::net::TcpListener::from_raw_fd(LISTEN_FDS_START) };
+    listener.set_nonblocking(true)?;
+    Ok(Some(listener))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listen_fds_require_matching_pid() {
+        assert_eq!(listen_fds_count(Some("1"), Some("42"), 42), 1);
+        assert_eq!(listen_fds_count(Some("1"), Some("41"), 42), 0);
+        assert_eq!(listen_fds_count(Some("1"), None, 42), 0);
+        assert_eq!(listen_fds_count(None, Some("42"), 42), 0);
+    }
+
+    #[test]
+    fn watchdog_pings_at_half_interval() {
+        assert_eq!(
+            parse_watchdog(Some("10000000"), None, 7),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(parse_watchdog(Some("10000000"), Some("8"), 7), None);
+        assert_eq!(parse_watchdog(Some("0"), None, 7), None);
+        assert_eq!(parse_watchdog(None, None, 7), None);
+    }
+}
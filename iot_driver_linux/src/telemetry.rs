@@ -0,0 +1,379 @@
+//! Game telemetry lighting (`iot_driver telemetry`).
+//!
+//! Games or community scripts send named values (health, ammo, RPM, ...)
+//! over UDP or write them to a file on tmpfs; `[[telemetry.mappings]]` in
+//! `settings.toml` turn each value into a bar or level on a key zone, with an
+//! optional pulse past a threshold (low health, redline). Frames go out over
+//! the patch LED streaming path; when the values go stale the keyboard's own
+//! lighting comes back.
+//!
+//! Input is `name=value` pairs separated by whitespace, `,` or `;`
+//! (`health=35 ammo=12`), or a flat JSON object (`{"health": 35}`).
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::keyboard_config::parse_color;
+use crate::notify::keymap::{parse_key_target, MATRIX_LEN};
+use monsgeek_keyboard::RgbColor;
+
+/// Alert pulse frequency.
+const PULSE_HZ: f32 = 2.0;
+/// Brightness at the bottom of an alert pulse.
+const PULSE_FLOOR: f32 = 0.15;
+
+/// Parse one telemetry message into `(name, value)` pairs. Names are
+/// lowercased; entries that aren't numbers are skipped.
+pub fn parse_values(msg: &str) -> Vec<(String, f32)> {
+    let msg = msg.trim();
+    if msg.starts_with('{') {
+        let Ok(serde_json::Value::Object(map)) = serde_json::from_str(msg) else {
+            return Vec::new();
+        };
+        return map
+            .into_iter()
+            .filter_map(|(k, v)| Some((k.to_lowercase(), v.as_f64()? as f32)))
+            .collect();
+    }
+    msg.split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .filter_map(|pair| {
+            let (name, value) = pair.split_once('=')?;
+            let value = value.trim().parse::<f32>().ok().filter(|v| v.is_finite())?;
+            Some((name.trim().to_lowercase(), value))
+        })
+        .collect()
+}
+
+/// How a value is drawn on its keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ZoneDisplay {
+    /// Keys light up in order (left to right) as the value rises
+    #[default]
+    Bar,
+    /// Every key shows the value's color from the low-high gradient
+    Level,
+}
+
+/// One value-to-zone mapping, `[[telemetry.mappings]]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mapping {
+    /// Value name as sent (case-insensitive)
+    pub value: String,
+    /// Key target, as for `iot_driver notify` (`numbers`, `frow`, `Q..P`)
+    pub keys: String,
+    /// Value shown as empty
+    pub min: f32,
+    /// Value shown as full
+    pub max: f32,
+    pub display: ZoneDisplay,
+    /// Color at `min` (`#RRGGBB`)
+    pub low: String,
+    /// Color at `max` (`#RRGGBB`)
+    pub high: String,
+    /// Pulse while the value is below this
+    pub pulse_below: Option<f32>,
+    /// Pulse while the value is above this
+    pub pulse_above: Option<f32>,
+}
+
+impl Default for Mapping {
+    fn default() -> Self {
+        Self {
+            value: String::new(),
+            keys: "all".to_string(),
+            min: 0.0,
+            max: 100.0,
+            display: ZoneDisplay::default(),
+            low: "#FF0000".to_string(),
+            high: "#00FF00".to_string(),
+            pulse_below: None,
+            pulse_above: None,
+        }
+    }
+}
+
+/// `[telemetry]` settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// A value not updated for this long is dropped; with none left the
+    /// keyboard's own lighting returns
+    pub timeout_ms: u64,
+    /// Drawn in order; later mappings win on shared keys
+    pub mappings: Vec<Mapping>,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 3000,
+            mappings: vec![
+                Mapping {
+                    value: "health".to_string(),
+                    keys: "numbers".to_string(),
+                    pulse_below: Some(25.0),
+                    ..Default::default()
+                },
+                Mapping {
+                    value: "ammo".to_string(),
+                    keys: "frow".to_string(),
+                    max: 30.0,
+                    low: "#FF4000".to_string(),
+                    high: "#FFD000".to_string(),
+                    pulse_below: Some(5.0),
+                    ..Default::default()
+                },
+                Mapping {
+                    value: "rpm".to_string(),
+                    keys: "Q..P".to_string(),
+                    max: 8000.0,
+                    low: "#00FF00".to_string(),
+                    high: "#FF0000".to_string(),
+                    pulse_above: Some(7500.0),
+                    ..Default::default()
+                },
+            ],
+        }
+    }
+}
+
+impl TelemetryConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms.max(100))
+    }
+
+    /// Resolve key targets and colors, failing on the first bad mapping.
+    pub fn zones(&self) -> Result<Vec<Zone>, String> {
+        self.mappings
+            .iter()
+            .map(|m| {
+                let target = parse_key_target(&m.keys)
+                    .map_err(|e| format!("telemetry mapping '{}': {e}", m.value))?;
+                let color = |c: &str| {
+                    parse_color(c).ok_or_else(|| {
+                        format!("telemetry mapping '{}': invalid color '{c}'", m.value)
+                    })
+                };
+                Ok(Zone {
+                    value: m.value.to_lowercase(),
+                    indices: target.indices,
+                    min: m.min,
+                    max: m.max,
+                    display: m.display,
+                    low: color(&m.low)?,
+                    high: color(&m.high)?,
+                    pulse_below: m.pulse_below,
+                    pulse_above: m.pulse_above,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A [`Mapping`] resolved to LED indices and colors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub value: String,
+    /// Row-major LED indices, in bar order
+    pub indices: Vec<usize>,
+    pub min: f32,
+    pub max: f32,
+    pub display: ZoneDisplay,
+    pub low: RgbColor,
+    pub high: RgbColor,
+    pub pulse_below: Option<f32>,
+    pub pulse_above: Option<f32>,
+}
+
+fn blend(a: RgbColor, b: RgbColor, t: f32) -> RgbColor {
+    let mix = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * t).round() as u8;
+    RgbColor::new(mix(a.r, b.r), mix(a.g, b.g), mix(a.b, b.b))
+}
+
+fn scale(c: RgbColor, f: f32) -> (u8, u8, u8) {
+    let s = |v: u8| (v as f32 * f).round() as u8;
+    (s(c.r), s(c.g), s(c.b))
+}
+
+impl Zone {
+    /// Fraction of the way from `min` to `max`, 0.0-1.0.
+    pub fn level(&self, value: f32) -> f32 {
+        let span = self.max - self.min;
+        if span == 0.0 {
+            return if value >= self.max { 1.0 } else { 0.0 };
+        }
+        ((value - self.min) / span).clamp(0.0, 1.0)
+    }
+
+    pub fn alerting(&self, value: f32) -> bool {
+        self.pulse_below.is_some_and(|t| value < t) || self.pulse_above.is_some_and(|t| value > t)
+    }
+
+    /// Draw `value` onto `leds`, `t` into the session (for the pulse).
+    pub fn draw(&self, value: f32, t: Duration, leds: &mut [(u8, u8, u8); MATRIX_LEN]) {
+        let level = self.level(value);
+        let color = blend(self.low, self.high, level);
+        let gain = if self.alerting(value) {
+            let wave = 0.5 + 0.5 * (std::f32::consts::TAU * PULSE_HZ * t.as_secs_f32()).cos();
+            PULSE_FLOOR + (1.0 - PULSE_FLOOR) * wave
+        } else {
+            1.0
+        };
+        let fill = level * self.indices.len() as f32;
+        for (n, &i) in self.indices.iter().enumerate() {
+            let Some(led) = leds.get_mut(i) else { continue };
+            let lit = match self.display {
+                ZoneDisplay::Level => 1.0,
+                // The leading key fades in with the fractional part
+                ZoneDisplay::Bar => (fill - n as f32).clamp(0.0, 1.0),
+            };
+            *led = scale(color, lit * gain);
+        }
+    }
+}
+
+/// Render every zone with a value into one frame (row-major); keys outside
+/// the zones stay dark.
+pub fn render(
+    zones: &[Zone],
+    values: &HashMap<String, f32>,
+    t: Duration,
+) -> [(u8, u8, u8); MATRIX_LEN] {
+    let mut leds = [(0, 0, 0); MATRIX_LEN];
+    for zone in zones {
+        if let Some(&v) = values.get(&zone.value) {
+            zone.draw(v, t, &mut leds);
+        }
+    }
+    leds
+}
+
+/// Latest values with their arrival times.
+#[derive(Debug, Default)]
+pub struct TelemetryState {
+    values: HashMap<String, (f32, Instant)>,
+}
+
+impl TelemetryState {
+    /// Record every value in a message; returns how many there were.
+    pub fn apply(&mut self, msg: &str, now: Instant) -> usize {
+        let values = parse_values(msg);
+        let n = values.len();
+        for (name, v) in values {
+            self.values.insert(name, (v, now));
+        }
+        n
+    }
+
+    /// Values updated within `timeout`, dropping older ones.
+    pub fn fresh(&mut self, now: Instant, timeout: Duration) -> HashMap<String, f32> {
+        self.values
+            .retain(|_, (_, at)| now.saturating_duration_since(*at) <= timeout);
+        self.values
+            .iter()
+            .map(|(k, (v, _))| (k.clone(), *v))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zones() -> Vec<Zone> {
+        TelemetryConfig::default().zones().unwrap()
+    }
+
+    #[test]
+    fn parses_pairs_and_json() {
+        assert_eq!(
+            parse_values("Health=35, ammo=12;rpm=6400.5\nbad=x junk"),
+            vec![
+                ("health".to_string(), 35.0),
+                ("ammo".to_string(), 12.0),
+                ("rpm".to_string(), 6400.5),
+            ]
+        );
+        let mut json = parse_values(r#"{"health": 80, "name": "x", "RPM": 1000}"#);
+        json.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            json,
+            vec![("health".to_string(), 80.0), ("rpm".to_string(), 1000.0)]
+        );
+        assert!(parse_values("{broken").is_empty());
+    }
+
+    #[test]
+    fn bar_fills_keys_in_order() {
+        let health = &zones()[0];
+        let mut leds = [(0, 0, 0); MATRIX_LEN];
+        health.draw(50.0, Duration::ZERO, &mut leds);
+        let lit = health.indices.iter().filter(|&&i| leds[i] != (0, 0, 0));
+        assert_eq!(lit.count(), health.indices.len() / 2);
+        assert_ne!(leds[health.indices[0]], (0, 0, 0));
+        assert_eq!(leds[*health.indices.last().unwrap()], (0, 0, 0));
+    }
+
+    #[test]
+    fn low_health_pulses() {
+        let health = &zones()[0];
+        let first = health.indices[0];
+        let at = |value, ms| {
+            let mut leds = [(0, 0, 0); MATRIX_LEN];
+            health.draw(value, Duration::from_millis(ms), &mut leds);
+            leds[first]
+        };
+        // Below the threshold: bright at the top of the pulse, dim halfway
+        assert!(at(10.0, 0).0 > 200);
+        assert!(at(10.0, 250).0 < 60);
+        // Above it: steady
+        assert_eq!(at(60.0, 0), at(60.0, 250));
+    }
+
+    #[test]
+    fn level_colors_every_key_and_later_zones_win() {
+        let mut config = TelemetryConfig::default();
+        config.mappings.push(Mapping {
+            value: "shield".to_string(),
+            keys: "1..3".to_string(),
+            display: ZoneDisplay::Level,
+            low: "#000000".to_string(),
+            high: "#0000FF".to_string(),
+            ..Default::default()
+        });
+        let zones = config.zones().unwrap();
+        let values = HashMap::from([("health".to_string(), 100.0), ("shield".to_string(), 100.0)]);
+        let leds = render(&zones, &values, Duration::ZERO);
+        let (shield, health) = (&zones[3], &zones[0]);
+        assert!(shield.indices.iter().all(|&i| leds[i] == (0, 0, 255)));
+        assert_eq!(leds[*health.indices.last().unwrap()], (0, 255, 0));
+    }
+
+    #[test]
+    fn stale_values_expire() {
+        let mut state = TelemetryState::default();
+        let start = Instant::now();
+        assert_eq!(state.apply("health=50", start), 1);
+        state.apply("ammo=3", start + Duration::from_secs(2));
+        let fresh = state.fresh(start + Duration::from_secs(4), Duration::from_secs(3));
+        assert_eq!(fresh, HashMap::from([("ammo".to_string(), 3.0)]));
+    }
+
+    #[test]
+    fn bad_mappings_are_reported() {
+        let mut config = TelemetryConfig::default();
+        config.mappings[1].keys = "nosuchkey".to_string();
+        assert!(config.zones().unwrap_err().contains("'ammo'"));
+        let mut config = TelemetryConfig::default();
+        config.mappings[0].high = "green".to_string();
+        assert!(config.zones().unwrap_err().contains("'green'"));
+    }
+}
@@ -0,0 +1,683 @@
+//! System tray applet (`iot_driver tray`).
+//!
+//! A StatusNotifierItem (KDE Plasma; GNOME with the AppIndicator extension)
+//! showing battery level and active profile, with a menu to switch profile,
+//! LED mode, brightness and trigger preset. Scrolling over the icon steps the
+//! brightness.
+//!
+//! The tray opens no HID device itself: everything goes through the daemon's
+//! `org.monsgeek.Keyboard1` interface (see [`crate::dbus`]), which D-Bus
+//! activation starts on first use. The menu is exported with the
+//! `com.canonical.dbusmenu` protocol the tray hosts expect.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Type, Value};
+use zbus::{fdo, interface};
+
+use crate::dbus::{BUS_NAME, OBJECT_PATH};
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+const WATCHER: &str = "org.kde.StatusNotifierWatcher";
+
+/// Refresh even without events, so changes made over gRPC/CLI show up.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Battery level (percent) at which the item asks for attention.
+const LOW_BATTERY: u8 = 15;
+
+/// Highest brightness level the daemon accepts.
+const MAX_BRIGHTNESS: u8 = 4;
+
+/// Keyboard state as last read from the daemon.
+#[derive(Debug, Clone, Default)]
+pub struct TrayState {
+    /// The daemon answered at all.
+    pub daemon: bool,
+    pub connected: bool,
+    pub name: String,
+    pub battery: Option<u8>,
+    pub charging: bool,
+    pub profile: Option<u8>,
+    pub led_mode: Option<u8>,
+    pub brightness: Option<u8>,
+    pub led_modes: Vec<(u8, String)>,
+    pub presets: Vec<(String, String)>,
+}
+
+impl TrayState {
+    async fn fetch(proxy: &zbus::Proxy<'_>) -> Self {
+        let Ok(connected) = proxy.get_property::<bool>("Connected").await else {
+            return Self::default();
+        };
+        let mut state = Self {
+            daemon: true,
+            connected,
+            ..Self::default()
+        };
+        if !connected {
+            return state;
+        }
+        state.name = proxy.get_property("Name").await.unwrap_or_default();
+        state.battery = proxy.get_property("BatteryLevel").await.ok();
+        state.charging = proxy.get_property("Charging").await.unwrap_or(false);
+        state.profile = proxy.get_property("Profile").await.ok();
+        state.led_mode = proxy.get_property("LedMode").await.ok();
+        state.brightness = proxy.get_property("LedBrightness").await.ok();
+        state.led_modes = call_list(proxy, "ListLedModes").await;
+        state.presets = call_list(proxy, "ListTriggerPresets").await;
+        state
+    }
+
+    /// One-line summary used as title, tooltip and menu header.
+    pub fn title(&self) -> String {
+        if !self.daemon {
+            return "MonsGeek: daemon not running".to_string();
+        }
+        if !self.connected {
+            return "MonsGeek: no keyboard".to_string();
+        }
+        let mut title = self.name.clone();
+        if let Some(level) = self.battery {
+            title.push_str(&format!(" · {level}%"));
+            if self.charging {
+                title.push_str(" (charging)");
+            }
+        }
+        if let Some(profile) = self.profile {
+            title.push_str(&format!(" · Profile {}", profile + 1));
+        }
+        title
+    }
+
+    fn low_battery(&self) -> bool {
+        !self.charging && self.battery.is_some_and(|b| b <= LOW_BATTERY)
+    }
+}
+
+async fn call_list<T>(proxy: &zbus::Proxy<'_>, method: &str) -> Vec<T>
+where
+    T: for<'de> Deserialize<'de> + Type,
+{
+    proxy
+        .call_method(method, &())
+        .await
+        .ok()
+        .and_then(|r| r.body().deserialize().ok())
+        .unwrap_or_default()
+}
+
+/// What a menu click asks for. Each submenu has its own id range so the
+/// clicked item id alone says what to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Profile(u8),
+    LedMode(u8),
+    Brightness(u8),
+    Preset(u8),
+    Quit,
+}
+
+const QUIT_ID: i32 = 2;
+const PROFILE_BASE: i32 = 100;
+const LED_BASE: i32 = 200;
+const BRIGHTNESS_BASE: i32 = 300;
+const PRESET_BASE: i32 = 400;
+const RANGE: i32 = 100;
+
+impl Action {
+    fn id(self) -> i32 {
+        match self {
+            Self::Profile(p) => PROFILE_BASE + p as i32,
+            Self::LedMode(m) => LED_BASE + m as i32,
+            Self::Brightness(b) => BRIGHTNESS_BASE + b as i32,
+            Self::Preset(i) => PRESET_BASE + i as i32,
+            Self::Quit => QUIT_ID,
+        }
+    }
+
+    fn from_id(id: i32) -> Option<Self> {
+        if id == QUIT_ID {
+            return Some(Self::Quit);
+        }
+        let value = u8::try_from(id % RANGE).ok()?;
+        match id - id % RANGE {
+            PROFILE_BASE => Some(Self::Profile(value)),
+            LED_BASE => Some(Self::LedMode(value)),
+            BRIGHTNESS_BASE => Some(Self::Brightness(value)),
+            PRESET_BASE => Some(Self::Preset(value)),
+            _ => None,
+        }
+    }
+}
+
+fn owned<'a>(value: impl Into<Value<'a>>) -> OwnedValue {
+    value
+        .into()
+        .try_to_owned()
+        .expect("menu properties hold no file descriptors")
+}
+
+/// A dbusmenu item and its children.
+#[derive(Debug, Clone)]
+struct MenuItem {
+    id: i32,
+    props: HashMap<String, OwnedValue>,
+    children: Vec<MenuItem>,
+}
+
+/// Wire form of a menu item: `(ia{sv}av)`.
+#[derive(Debug, Serialize, Deserialize, Type, Value, OwnedValue)]
+struct MenuLayout {
+    id: i32,
+    props: HashMap<String, OwnedValue>,
+    children: Vec<OwnedValue>,
+}
+
+impl MenuItem {
+    fn new(id: i32, label: &str) -> Self {
+        let mut props = HashMap::new();
+        props.insert("label".to_string(), owned(label));
+        Self {
+            id,
+            props,
+            children: Vec::new(),
+        }
+    }
+
+    fn separator(id: i32) -> Self {
+        let mut props = HashMap::new();
+        props.insert("type".to_string(), owned("separator"));
+        Self {
+            id,
+            props,
+            children: Vec::new(),
+        }
+    }
+
+    fn radio(action: Action, label: &str, checked: bool) -> Self {
+        let mut item = Self::new(action.id(), label);
+        item.props.insert("toggle-type".to_string(), owned("radio"));
+        item.props
+            .insert("toggle-state".to_string(), owned(i32::from(checked)));
+        item
+    }
+
+    fn enabled(mut self, enabled: bool) -> Self {
+        if !enabled {
+            self.props.insert("enabled".to_string(), owned(false));
+        }
+        self
+    }
+
+    fn submenu(mut self, children: Vec<MenuItem>) -> Self {
+        self.props
+            .insert("children-display".to_string(), owned("submenu"));
+        self.children = children;
+        self
+    }
+
+    fn find(&self, id: i32) -> Option<&MenuItem> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|c| c.find(id))
+    }
+
+    /// Layout down to `depth` levels of children (-1 = all).
+    fn layout(&self, depth: i32) -> MenuLayout {
+        let children = if depth == 0 {
+            Vec::new()
+        } else {
+            self.children
+                .iter()
+                .map(|c| owned(c.layout(depth - 1)))
+                .collect()
+        };
+        MenuLayout {
+            id: self.id,
+            props: self.props.clone(),
+            children,
+        }
+    }
+}
+
+/// Build the menu for `state`.
+fn build_menu(state: &TrayState) -> MenuItem {
+    let on = state.connected;
+    let profiles = (0..4u8)
+        .map(|p| {
+            MenuItem::radio(
+                Action::Profile(p),
+                &format!("Profile {}", p + 1),
+                state.profile == Some(p),
+            )
+        })
+        .collect();
+    let modes = state
+        .led_modes
+        .iter()
+        .map(|(m, name)| MenuItem::radio(Action::LedMode(*m), name, state.led_mode == Some(*m)))
+        .collect();
+    let levels = (0..=MAX_BRIGHTNESS)
+        .map(|b| {
+            MenuItem::radio(
+                Action::Brightness(b),
+                &b.to_string(),
+                state.brightness == Some(b),
+            )
+        })
+        .collect();
+    let presets = state
+        .presets
+        .iter()
+        .enumerate()
+        .map(|(i, (name, desc))| {
+            MenuItem::new(Action::Preset(i as u8).id(), &format!("{name} ({desc})"))
+        })
+        .collect();
+
+    MenuItem::new(0, "").submenu(vec![
+        MenuItem::new(1, &state.title()).enabled(false),
+        MenuItem::separator(3),
+        MenuItem::new(10, "Profile").submenu(profiles).enabled(on),
+        MenuItem::new(11, "LED Mode").submenu(modes).enabled(on),
+        MenuItem::new(12, "Brightness").submenu(levels).enabled(on),
+        MenuItem::new(13, "Trigger Preset")
+            .submenu(presets)
+            .enabled(on),
+        MenuItem::separator(4),
+        MenuItem::new(QUIT_ID, "Quit"),
+    ])
+}
+
+/// SNI tooltip: icon name, icon pixmaps `(width, height, ARGB32)`, title, text.
+type ToolTip = (String, Vec<(i32, i32, Vec<u8>)>, String, String);
+
+/// `org.kde.StatusNotifierItem` implementation.
+struct StatusItem {
+    state: Arc<Mutex<TrayState>>,
+    actions: mpsc::UnboundedSender<Action>,
+}
+
+#[interface(name = "org.kde.StatusNotifierItem")]
+impl StatusItem {
+    #[zbus(property)]
+    fn category(&self) -> String {
+        "Hardware".to_string()
+    }
+
+    #[zbus(property)]
+    fn id(&self) -> String {
+        "monsgeek-tray".to_string()
+    }
+
+    #[zbus(property)]
+    fn title(&self) -> String {
+        self.state.lock().unwrap().title()
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> String {
+        if self.state.lock().unwrap().low_battery() {
+            "NeedsAttention".to_string()
+        } else {
+            "Active".to_string()
+        }
+    }
+
+    #[zbus(property)]
+    fn window_id(&self) -> i32 {
+        0
+    }
+
+    #[zbus(property)]
+    fn icon_name(&self) -> String {
+        "input-keyboard".to_string()
+    }
+
+    #[zbus(property)]
+    fn attention_icon_name(&self) -> String {
+        "battery-caution".to_string()
+    }
+
+    #[zbus(property)]
+    fn tool_tip(&self) -> ToolTip {
+        let title = self.state.lock().unwrap().title();
+        (
+            "input-keyboard".to_string(),
+            Vec::new(),
+            title,
+            String::new(),
+        )
+    }
+
+    /// Left click opens the menu rather than calling `Activate`.
+    #[zbus(property)]
+    fn item_is_menu(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn menu(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(MENU_PATH).expect("valid object path")
+    }
+
+    fn activate(&self, _x: i32, _y: i32) {}
+
+    fn secondary_activate(&self, _x: i32, _y: i32) {}
+
+    fn context_menu(&self, _x: i32, _y: i32) {}
+
+    /// Wheel over the icon steps the brightness.
+    fn scroll(&self, delta: i32, orientation: &str) {
+        if orientation != "vertical" || delta == 0 {
+            return;
+        }
+        let Some(current) = self.state.lock().unwrap().brightness else {
+            return;
+        };
+        let level = if delta > 0 {
+            (current + 1).min(MAX_BRIGHTNESS)
+        } else {
+            current.saturating_sub(1)
+        };
+        if level != current {
+            let _ = self.actions.send(Action::Brightness(level));
+        }
+    }
+
+    #[zbus(signal)]
+    async fn new_title(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn new_tool_tip(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn new_status(emitter: &SignalEmitter<'_>, status: &str) -> zbus::Result<()>;
+}
+
+/// `com.canonical.dbusmenu` implementation.
+struct Menu {
+    state: Arc<Mutex<TrayState>>,
+    actions: mpsc::UnboundedSender<Action>,
+    revision: AtomicU32,
+}
+
+impl Menu {
+    fn tree(&self) -> MenuItem {
+        build_menu(&self.state.lock().unwrap())
+    }
+
+    fn clicked(&self, id: i32, event: &str) {
+        if event != "clicked" {
+            return;
+        }
+        if let Some(action) = Action::from_id(id) {
+            let _ = self.actions.send(action);
+        }
+    }
+}
+
+#[interface(name = "com.canonical.dbusmenu")]
+impl Menu {
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[zbus(property)]
+    fn text_direction(&self) -> String {
+        "ltr".to_string()
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> String {
+        "normal".to_string()
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(out_args("revision", "layout"))]
+    fn get_layout(
+        &self,
+        parent_id: i32,
+        recursion_depth: i32,
+        _property_names: Vec<String>,
+    ) -> fdo::Result<(u32, MenuLayout)> {
+        let tree = self.tree();
+        let parent = tree
+            .find(parent_id)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("no menu item {parent_id}")))?;
+        Ok((
+            self.revision.load(Ordering::Relaxed),
+            parent.layout(recursion_depth),
+        ))
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        _property_names: Vec<String>,
+    ) -> Vec<(i32, HashMap<String, OwnedValue>)> {
+        let tree = self.tree();
+        ids.iter()
+            .filter_map(|&id| tree.find(id))
+            .map(|item| (item.id, item.props.clone()))
+            .collect()
+    }
+
+    fn get_property(&self, id: i32, name: &str) -> fdo::Result<OwnedValue> {
+        self.tree()
+            .find(id)
+            .and_then(|item| item.props.get(name))
+            .map(|v| {
+                v.try_clone()
+                    .expect("menu properties hold no file descriptors")
+            })
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("no property {name} on item {id}")))
+    }
+
+    fn event(&self, id: i32, event_id: &str, _data: OwnedValue, _timestamp: u32) {
+        self.clicked(id, event_id);
+    }
+
+    fn event_group(&self, events: Vec<(i32, String, OwnedValue, u32)>) -> Vec<i32> {
+        for (id, event_id, _, _) in &events {
+            self.clicked(*id, event_id);
+        }
+        Vec::new()
+    }
+
+    fn about_to_show(&self, _id: i32) -> bool {
+        false
+    }
+
+    #[zbus(out_args("updates_needed", "id_errors"))]
+    fn about_to_show_group(&self, _ids: Vec<i32>) -> (Vec<i32>, Vec<i32>) {
+        (Vec::new(), Vec::new())
+    }
+
+    #[zbus(signal)]
+    async fn layout_updated(
+        emitter: &SignalEmitter<'_>,
+        revision: u32,
+        parent: i32,
+    ) -> zbus::Result<()>;
+}
+
+/// Register the item with the tray host.
+async fn register(conn: &zbus::Connection, service: &str) -> zbus::Result<()> {
+    conn.call_method(
+        Some(WATCHER),
+        "/StatusNotifierWatcher",
+        Some(WATCHER),
+        "RegisterStatusNotifierItem",
+        &(service,),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn apply(proxy: &zbus::Proxy<'_>, state: &TrayState, action: Action) -> zbus::Result<()> {
+    match action {
+        Action::Profile(p) => proxy.set_property("Profile", p).await?,
+        Action::LedMode(m) => proxy.set_property("LedMode", m).await?,
+        Action::Brightness(b) => proxy.set_property("LedBrightness", b).await?,
+        Action::Preset(i) => {
+            if let Some((name, _)) = state.presets.get(i as usize) {
+                proxy
+                    .call_method("ApplyTriggerPreset", &(name.as_str(),))
+                    .await?;
+            }
+        }
+        Action::Quit => {}
+    }
+    Ok(())
+}
+
+/// Run the tray applet until Quit is chosen.
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let conn = zbus::Connection::session().await?;
+    // Uncached: settings also change over gRPC and the CLI, which send no
+    // PropertiesChanged.
+    let proxy = zbus::proxy::Builder::<zbus::Proxy<'_>>::new(&conn)
+        .destination(BUS_NAME)?
+        .path(OBJECT_PATH)?
+        .interface(BUS_NAME)?
+        .cache_properties(zbus::proxy::CacheProperties::No)
+        .build()
+        .await?;
+
+    let state = Arc::new(Mutex::new(TrayState::fetch(&proxy).await));
+    let (tx, mut actions) = mpsc::unbounded_channel();
+    let server = conn.object_server();
+    server
+        .at(
+            ITEM_PATH,
+            StatusItem {
+                state: Arc::clone(&state),
+                actions: tx.clone(),
+            },
+        )
+        .await?;
+    server
+        .at(
+            MENU_PATH,
+            Menu {
+                state: Arc::clone(&state),
+                actions: tx,
+                revision: AtomicU32::new(1),
+            },
+        )
+        .await?;
+
+    let service = format!("org.kde.StatusNotifierItem-{}-1", std::process::id());
+    conn.request_name(service.as_str()).await?;
+    register(&conn, &service)
+        .await
+        .map_err(|e| format!("no system tray found ({WATCHER}): {e}"))?;
+
+    let item = server.interface::<_, StatusItem>(ITEM_PATH).await?;
+    let menu = server.interface::<_, Menu>(MENU_PATH).await?;
+    let mut events = proxy.receive_signal("Event").await?;
+    // Re-register when the tray host restarts (e.g. plasmashell).
+    let mut hosts = fdo::DBusProxy::new(&conn)
+        .await?
+        .receive_name_owner_changed_with_args(&[(0, WATCHER)])
+        .await?;
+    let mut poll = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = poll.tick() => {}
+            Some(_) = events.next() => {}
+            Some(_) = hosts.next() => {
+                if let Err(e) = register(&conn, &service).await {
+                    tracing::debug!("tray: re-register: {e}");
+                }
+            }
+            Some(action) = actions.recv() => {
+                if action == Action::Quit {
+                    break;
+                }
+                let current = state.lock().unwrap().clone();
+                if let Err(e) = apply(&proxy, &current, action).await {
+                    tracing::warn!("tray: {action:?}: {e}");
+                }
+            }
+        }
+
+        let fresh = TrayState::fetch(&proxy).await;
+        let low = fresh.low_battery();
+        *state.lock().unwrap() = fresh;
+        let emitter = item.signal_emitter();
+        let status = if low { "NeedsAttention" } else { "Active" };
+        let revision = menu.get().await.revision.fetch_add(1, Ordering::Relaxed) + 1;
+        let sent = StatusItem::new_title(emitter)
+            .await
+            .and(StatusItem::new_tool_tip(emitter).await)
+            .and(StatusItem::new_status(emitter, status).await)
+            .and(Menu::layout_updated(menu.signal_emitter(), revision, 0).await);
+        if let Err(e) = sent {
+            tracing::debug!("tray: update signals: {e}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menu_ids_round_trip() {
+        for action in [
+            Action::Profile(3),
+            Action::LedMode(24),
+            Action::Brightness(0),
+            Action::Preset(1),
+            Action::Quit,
+        ] {
+            assert_eq!(Action::from_id(action.id()), Some(action));
+        }
+        assert_eq!(Action::from_id(10), None);
+        assert_eq!(Action::from_id(-1), None);
+    }
+
+    #[test]
+    fn title_summarizes_state() {
+        let mut state = TrayState::default();
+        assert_eq!(state.title(), "MonsGeek: daemon not running");
+        state.daemon = true;
+        assert_eq!(state.title(), "MonsGeek: no keyboard");
+        state.connected = true;
+        state.name = "M1 V5".to_string();
+        state.battery = Some(12);
+        state.profile = Some(0);
+        assert_eq!(state.title(), "M1 V5 · 12% · Profile 1");
+        assert!(state.low_battery());
+        state.charging = true;
+        assert!(!state.low_battery());
+    }
+
+    #[test]
+    fn menu_disables_submenus_without_keyboard() {
+        let menu = build_menu(&TrayState::default());
+        let profile = menu.find(10).unwrap();
+        assert!(profile.props.contains_key("enabled"));
+        assert!(menu.find(Action::Profile(0).id()).is_some());
+        assert_eq!(menu.layout(1).children.len(), 8);
+    }
+}
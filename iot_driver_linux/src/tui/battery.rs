@@ -0,0 +1,222 @@
+// Battery Graph — level over time for the active wireless board.
+//
+// Opened with `B`. Every battery reading the TUI takes is added to the shared
+// history file (see `crate::battery_history`), so the graph also covers
+// earlier sessions. Charge start/stop points are drawn as vertical markers and
+// the estimate extrapolates the current discharge run.
+
+use std::time::Duration;
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::battery_history::BatteryHistory;
+
+use super::App;
+
+/// Time spans the graph can show, cycled with `t`.
+const SPANS: [(Duration, &str); 3] = [
+    (Duration::from_secs(24 * 3600), "24h"),
+    (Duration::from_secs(3 * 24 * 3600), "3d"),
+    (Duration::from_secs(14 * 24 * 3600), "14d"),
+];
+
+/// Open state of the battery graph.
+#[derive(Default)]
+pub(super) struct BatteryGraph {
+    span: usize,
+}
+
+impl App {
+    pub(super) fn open_battery_graph(&mut self) {
+        if !self.is_wireless {
+            self.status_msg = "Battery graph needs a wireless keyboard".to_string();
+            return;
+        }
+        self.battery_graph = Some(BatteryGraph::default());
+        self.refresh_battery();
+    }
+
+    /// Add the current reading to the history.
+    pub(super) fn record_battery(&mut self) {
+        if let Some(info) = self.battery.as_ref().filter(|b| b.online) {
+            self.battery_history.record(info.level, info.charging);
+        }
+    }
+}
+
+pub(super) fn handle_key(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('B') => app.battery_graph = None,
+        KeyCode::Char('t') => {
+            if let Some(g) = app.battery_graph.as_mut() {
+                g.span = (g.span + 1) % SPANS.len();
+            }
+        }
+        KeyCode::Char('r') => app.refresh_battery(),
+        _ => {}
+    }
+}
+
+fn format_duration(d: Duration) -> String {
+    let mins = d.as_secs() / 60;
+    match (mins / (24 * 60), mins / 60 % 24, mins % 60) {
+        (0, 0, m) => format!("{m}m"),
+        (0, h, m) => format!("{h}h {m:02}m"),
+        (days, h, _) => format!("{days}d {h}h"),
+    }
+}
+
+/// Summary line under the graph: level, state and estimate.
+fn summary(app: &App, history: &BatteryHistory) -> Line<'static> {
+    let dim = Style::default().fg(Color::DarkGray);
+    let mut spans = Vec::new();
+    match app.battery.as_ref() {
+        Some(b) => {
+            spans.push(Span::styled(
+                format!("{}%", b.level),
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            ));
+            if b.charging {
+                spans.push(Span::styled(
+                    "  charging",
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+        }
+        None => spans.push(Span::styled("level unknown", dim)),
+    }
+    match (history.time_remaining(), history.discharge_rate()) {
+        (Some(left), Some(rate)) => {
+            spans.push(Span::raw(format!("  ~{} left", format_duration(left))));
+            spans.push(Span::styled(format!(" ({rate:.1}%/h)"), dim));
+        }
+        _ => spans.push(Span::styled(
+            "  no estimate yet (needs a 1% drop off charge)",
+            dim,
+        )),
+    }
+    spans.push(Span::styled(
+        format!("  {} readings", history.samples().len()),
+        dim,
+    ));
+    Line::from(spans)
+}
+
+pub(super) fn render_battery_graph(f: &mut Frame, app: &App, area: Rect) {
+    let Some(graph) = app.battery_graph.as_ref() else {
+        return;
+    };
+    let w = 100.min(area.width.saturating_sub(4));
+    let h = 22.min(area.height.saturating_sub(2));
+    let popup = Rect::new(
+        area.x + (area.width.saturating_sub(w)) / 2,
+        area.y + (area.height.saturating_sub(h)) / 2,
+        w,
+        h,
+    );
+    f.render_widget(Clear, popup);
+
+    let (span, span_name) = SPANS[graph.span];
+    let history = &app.battery_history;
+    let block = Block::default()
+        .title(format!(" Battery — {} ", history.device()))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(popup);
+    f.render_widget(block, popup);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(6),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+
+    // X axis is hours relative to the newest reading (0 = now).
+    let now = history.samples().last().map_or(0, |s| s.at);
+    let hours = |at: u64| -(now.saturating_sub(at) as f64) / 3600.0;
+    let x_min = -span.as_secs_f64() / 3600.0;
+    let visible = |at: u64| hours(at) >= x_min;
+
+    let levels: Vec<(f64, f64)> = history
+        .samples()
+        .iter()
+        .filter(|s| visible(s.at))
+        .map(|s| (hours(s.at), s.level as f64))
+        .collect();
+    let events = history.charge_events();
+    let plugged: Vec<Vec<(f64, f64)>> = events
+        .iter()
+        .filter(|e| visible(e.at))
+        .map(|e| (0..=20).map(|i| (hours(e.at), i as f64 * 5.0)).collect())
+        .collect();
+
+    let mut datasets: Vec<Dataset> = events
+        .iter()
+        .filter(|e| visible(e.at))
+        .zip(&plugged)
+        .map(|(e, line)| {
+            let color = if e.started {
+                Color::Yellow
+            } else {
+                Color::DarkGray
+            };
+            Dataset::default()
+                .marker(symbols::Marker::Dot)
+                .graph_type(GraphType::Scatter)
+                .style(Style::default().fg(color))
+                .data(line)
+        })
+        .collect();
+    datasets.push(
+        Dataset::default()
+            .name("level")
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Green))
+            .data(&levels),
+    );
+
+    let chart = Chart::new(datasets)
+        .x_axis(
+            Axis::default()
+                .title("hours ago")
+                .style(Style::default().fg(Color::Gray))
+                .bounds([x_min, 0.0])
+                .labels(vec![
+                    Span::raw(format!("{:.0}", x_min)),
+                    Span::raw(format!("{:.0}", x_min / 2.0)),
+                    Span::raw("now"),
+                ]),
+        )
+        .y_axis(
+            Axis::default()
+                .title("%")
+                .style(Style::default().fg(Color::Gray))
+                .bounds([0.0, 100.0])
+                .labels(vec![Span::raw("0"), Span::raw("50"), Span::raw("100")]),
+        );
+    if levels.is_empty() {
+        f.render_widget(
+            Paragraph::new("No readings in this span yet — they are recorded while the TUI runs")
+                .style(Style::default().fg(Color::DarkGray))
+                .alignment(Alignment::Center),
+            chunks[0],
+        );
+    } else {
+        f.render_widget(chart, chunks[0]);
+    }
+
+    f.render_widget(Paragraph::new(summary(app, history)), chunks[1]);
+    f.render_widget(
+        Paragraph::new(format!(
+            "span {span_name}  │  yellow: plugged in, gray: unplugged  │  t: span  r: refresh  Esc: close"
+        ))
+        .style(Style::default().fg(Color::DarkGray)),
+        chunks[2],
+    );
+}
@@ -0,0 +1,575 @@
+// Firmware Update — check, fetch, validate and rehearse an update.
+//
+// Opened with `F`. Shows the running firmware and the vendor server's latest,
+// downloads or opens a firmware file, validates it and then walks through the
+// update steps `iot_driver firmware dry-run` would print, one confirmation per
+// phase. It is a dry run only: no boot-mode or transfer command is sent, and
+// real flashing stays with `iot_driver firmware flash`. `b` saves every
+// profile to the automatic backup directory, the same backup `firmware flash`
+// takes before entering the bootloader. The transfer gauge is fed the flash
+// engine's `FlashEvent`s, so it reads the same as the real thing.
+
+use std::path::{Path, PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::firmware::{dry_run_usb, DryRunCommand, DryRunResult, FirmwareFile};
+use crate::flash::{FlashEvent, FlashPhase, FlashProgress};
+use crate::keyboard_config::auto_backup;
+use crate::protocol::firmware_update;
+
+use super::shared::{AsyncResult, LoadState};
+use super::App;
+
+/// Chunks "sent" per UI tick while rehearsing the transfer.
+const CHUNKS_PER_TICK: usize = 16;
+
+/// One confirmable phase of the update.
+#[derive(Clone, Copy, PartialEq)]
+enum Phase {
+    EnterBoot,
+    WaitBootloader,
+    StartTransfer,
+    Transfer,
+    Complete,
+}
+
+const PHASES: [Phase; 5] = [
+    Phase::EnterBoot,
+    Phase::WaitBootloader,
+    Phase::StartTransfer,
+    Phase::Transfer,
+    Phase::Complete,
+];
+
+impl Phase {
+    /// The dry-run commands belonging to this phase.
+    fn matches(self, cmd: &DryRunCommand) -> bool {
+        matches!(
+            (self, cmd),
+            (Self::EnterBoot, DryRunCommand::EnterBootMode { .. })
+                | (Self::WaitBootloader, DryRunCommand::WaitReconnect { .. })
+                | (Self::StartTransfer, DryRunCommand::StartTransfer { .. })
+                | (Self::Transfer, DryRunCommand::DataChunk { .. })
+                | (Self::Complete, DryRunCommand::CompleteTransfer { .. })
+        )
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::EnterBoot => "Enter bootloader",
+            Self::WaitBootloader => "Wait for bootloader device",
+            Self::StartTransfer => "Start transfer",
+            Self::Transfer => "Send firmware data",
+            Self::Complete => "Complete and verify",
+        }
+    }
+}
+
+/// Where the walkthrough is.
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    /// Firmware info, server check, file selection.
+    Overview,
+    /// Waiting for `y` before running `PHASES[n]`.
+    Confirm(usize),
+    /// Rehearsing the transfer.
+    Transferring,
+    Done,
+}
+
+/// Open state of the firmware update screen.
+pub(super) struct FirmwareUpdate {
+    stage: Stage,
+    /// Path prompt for opening a file.
+    prompt: Option<String>,
+    downloading: bool,
+    file: Option<FirmwareFile>,
+    /// Validation error of `file`, if any.
+    invalid: Option<String>,
+    plan: Option<DryRunResult>,
+    /// Rehearsed transfer progress, as the flash engine would report it.
+    progress: FlashEvent,
+    backing_up: bool,
+    /// Settings backup file written this session, or why it failed.
+    backup: Option<Result<PathBuf, String>>,
+}
+
+impl FirmwareUpdate {
+    fn new() -> Self {
+        Self {
+            stage: Stage::Overview,
+            prompt: None,
+            downloading: false,
+            file: None,
+            invalid: None,
+            plan: None,
+            progress: FlashEvent::default(),
+            backing_up: false,
+            backup: None,
+        }
+    }
+
+    fn ready(&self) -> bool {
+        self.plan.is_some() && self.invalid.is_none()
+    }
+}
+
+/// Default download name for a server firmware, in the working directory.
+fn download_name(device_id: u32, version: Option<u16>) -> String {
+    match version {
+        Some(v) => format!("firmware-{device_id}-v{v:X}.bin"),
+        None => format!("firmware-{device_id}.bin"),
+    }
+}
+
+impl App {
+    pub(super) fn open_firmware_update(&mut self) {
+        if !self.connected {
+            self.status_msg = "Connect a keyboard first".to_string();
+            return;
+        }
+        self.firmware_update = Some(FirmwareUpdate::new());
+        if self.firmware_check.is_none() {
+            self.check_firmware();
+        }
+    }
+
+    /// Take a loaded firmware file: validate it and build the dry-run plan.
+    pub(super) fn on_firmware_file(&mut self, result: Result<FirmwareFile, String>) {
+        let current = format!("v{:X}", self.info.version);
+        let device_id = (self.info.device_id != 0).then_some(self.info.device_id);
+        let Some(fu) = self.firmware_update.as_mut() else {
+            return;
+        };
+        fu.downloading = false;
+        fu.stage = Stage::Overview;
+        match result {
+            Ok(file) => {
+                fu.invalid = file.validate().err().map(|e| e.to_string());
+                self.status_msg = match &fu.invalid {
+                    None => format!("{} is valid; Enter starts the dry run", file.filename),
+                    Some(e) => format!("{} failed validation: {e}", file.filename),
+                };
+                fu.plan = Some(dry_run_usb(&file, Some(current), device_id));
+                fu.file = Some(file);
+            }
+            Err(e) => {
+                fu.file = None;
+                fu.plan = None;
+                fu.invalid = None;
+                self.status_msg = e;
+            }
+        }
+    }
+
+    fn download_firmware_update(&mut self) {
+        let Some((path, manifest)) = self
+            .firmware_check
+            .as_ref()
+            .filter(|c| c.has_update)
+            .and_then(|c| Some((c.download_path.clone()?, c.manifest.clone())))
+        else {
+            self.status_msg = "No update to download (c checks the server)".to_string();
+            return;
+        };
+        let Some(fu) = self.firmware_update.as_mut() else {
+            return;
+        };
+        if fu.downloading {
+            return;
+        }
+        fu.downloading = true;
+        let output = download_name(
+            self.info.device_id,
+            self.firmware_check.as_ref().and_then(|c| c.server_version),
+        );
+        self.status_msg = format!("Downloading firmware to {output}...");
+        let tx = self.gen_sender();
+        tokio::spawn(async move {
+            let result =
+                match crate::firmware_api::download_firmware(&path, &manifest, &output).await {
+                    Ok(_) => FirmwareFile::load(&output).map_err(|e| format!("{output}: {e}")),
+                    Err(e) => Err(format!("Download failed: {e}")),
+                };
+            tx.send(AsyncResult::FirmwareFile(result));
+        });
+    }
+
+    /// Save every profile to the automatic backup directory in the
+    /// background; the keyboard briefly switches through its profiles.
+    fn backup_settings(&mut self) {
+        let (Some(keyboard), Some(fu)) = (self.keyboard.clone(), self.firmware_update.as_mut())
+        else {
+            return;
+        };
+        if fu.backing_up {
+            return;
+        }
+        fu.backing_up = true;
+        self.status_msg = "Backing up settings...".to_string();
+        let tx = self.gen_sender();
+        tokio::spawn(async move {
+            let result = auto_backup(&keyboard, "pre-flash", |_| {}).map_err(|e| e.to_string());
+            tx.send(AsyncResult::SettingsBackup(result));
+        });
+    }
+
+    pub(super) fn on_settings_backup(&mut self, result: Result<PathBuf, String>) {
+        let Some(fu) = self.firmware_update.as_mut() else {
+            return;
+        };
+        fu.backing_up = false;
+        self.status_msg = match &result {
+            Ok(path) => format!("Settings backed up to {}", path.display()),
+            Err(e) => format!("Backup failed: {e}"),
+        };
+        fu.backup = Some(result);
+    }
+
+    fn open_firmware_file(&mut self, path: &str) {
+        if path.is_empty() {
+            return;
+        }
+        let result = FirmwareFile::load(Path::new(path)).map_err(|e| format!("{path}: {e}"));
+        self.on_firmware_file(result);
+    }
+
+    /// Advance the rehearsed transfer; called every tick.
+    pub(super) fn firmware_update_tick(&mut self) {
+        let Some(fu) = self.firmware_update.as_mut() else {
+            return;
+        };
+        if fu.stage != Stage::Transferring {
+            return;
+        }
+        let total = fu.progress.total_chunks;
+        let sent = (fu.progress.chunk + CHUNKS_PER_TICK).min(total);
+        fu.progress.on_chunk(sent, total);
+        if sent >= total {
+            fu.stage = Stage::Confirm(PHASES.len() - 1);
+        }
+    }
+}
+
+/// Chunks the transfer phase sends (USB data after the bootloader offset).
+fn chunk_count(file: &FirmwareFile) -> usize {
+    file.usb_data()
+        .unwrap_or(&file.data)
+        .len()
+        .div_ceil(firmware_update::CHUNK_SIZE)
+}
+
+pub(super) fn handle_key(app: &mut App, key: KeyEvent) {
+    let Some(fu) = app.firmware_update.as_mut() else {
+        return;
+    };
+
+    if let Some(input) = fu.prompt.as_mut() {
+        match key.code {
+            KeyCode::Esc => fu.prompt = None,
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Enter => {
+                let path = input.trim().to_string();
+                fu.prompt = None;
+                app.open_firmware_file(&path);
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    match (fu.stage, key.code) {
+        (Stage::Overview, KeyCode::Esc | KeyCode::Char('F')) => app.firmware_update = None,
+        (Stage::Overview, KeyCode::Char('c')) => {
+            app.firmware_check = None;
+            app.check_firmware();
+        }
+        (Stage::Overview, KeyCode::Char('d')) => app.download_firmware_update(),
+        (Stage::Overview, KeyCode::Char('o')) => fu.prompt = Some(String::new()),
+        (Stage::Overview, KeyCode::Char('b')) => app.backup_settings(),
+        (Stage::Overview, KeyCode::Enter) if fu.ready() => {
+            fu.stage = Stage::Confirm(0);
+            fu.progress = FlashEvent::default();
+            app.status_msg = "Dry run: confirm each step with y".to_string();
+        }
+        (Stage::Overview, KeyCode::Enter) => {
+            app.status_msg = "Open or download a valid firmware file first".to_string();
+        }
+        (Stage::Confirm(n), KeyCode::Char('y')) => {
+            fu.stage = match PHASES[n] {
+                Phase::StartTransfer => {
+                    let chunks = fu.file.as_ref().map_or(0, chunk_count);
+                    let size = fu.file.as_ref().map_or(0, |f| f.size);
+                    let start = FlashPhase::StartingTransfer { chunks, size };
+                    fu.progress.on_phase(&start);
+                    Stage::Transferring
+                }
+                _ if n + 1 < PHASES.len() => Stage::Confirm(n + 1),
+                _ => {
+                    app.status_msg = "Dry run complete - device unchanged".to_string();
+                    Stage::Done
+                }
+            };
+        }
+        (Stage::Confirm(_) | Stage::Transferring, KeyCode::Esc | KeyCode::Char('n')) => {
+            fu.stage = Stage::Overview;
+            app.status_msg = "Dry run aborted - device unchanged".to_string();
+        }
+        (Stage::Done, KeyCode::Esc | KeyCode::Enter) => fu.stage = Stage::Overview,
+        _ => {}
+    }
+}
+
+/// State of one phase in the step list.
+fn phase_marker(stage: Stage, index: usize) -> (&'static str, Style) {
+    let done = Style::default().fg(Color::Green);
+    let current = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let pending = Style::default().fg(Color::DarkGray);
+    let transfer = PHASES
+        .iter()
+        .position(|&p| p == Phase::Transfer)
+        .unwrap_or(0);
+    let position = match stage {
+        Stage::Overview => return ("  ", pending),
+        Stage::Confirm(n) => n,
+        Stage::Transferring => transfer,
+        Stage::Done => PHASES.len(),
+    };
+    match index.cmp(&position) {
+        std::cmp::Ordering::Less => ("✓ ", done),
+        std::cmp::Ordering::Equal => ("▶ ", current),
+        std::cmp::Ordering::Greater => ("  ", pending),
+    }
+}
+
+fn overview_lines(app: &App, fu: &FirmwareUpdate) -> Vec<Line<'static>> {
+    let label = |s: &str| Span::styled(format!("{s:<12}"), Style::default().fg(Color::Gray));
+    let mut lines = vec![Line::from(vec![
+        label("Current"),
+        Span::styled(
+            format!("v{:X}", app.info.version),
+            Style::default().fg(Color::Cyan),
+        ),
+        Span::styled(
+            format!("  device ID {}", app.info.device_id),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ])];
+
+    let server = match (app.loading.firmware_check, app.firmware_check.as_ref()) {
+        (LoadState::Loading, _) => Span::styled(
+            format!("{} checking...", app.spinner_char()),
+            Style::default().fg(Color::Yellow),
+        ),
+        (_, Some(check)) => {
+            let color = if check.has_update {
+                Color::Green
+            } else {
+                Color::Gray
+            };
+            let version = check
+                .server_version
+                .map(|v| format!("v{v:X}  "))
+                .unwrap_or_default();
+            Span::styled(
+                format!("{version}{}", check.message),
+                Style::default().fg(color),
+            )
+        }
+        _ => Span::styled("not checked", Style::default().fg(Color::DarkGray)),
+    };
+    lines.push(Line::from(vec![label("Server"), server]));
+
+    let file = match (&fu.file, fu.downloading) {
+        (_, true) => Span::styled(
+            format!("{} downloading...", app.spinner_char()),
+            Style::default().fg(Color::Yellow),
+        ),
+        (Some(file), _) => Span::raw(format!(
+            "{}  {} firmware, {} KB, checksum 0x{:08X}",
+            file.filename,
+            file.firmware_type,
+            file.size / 1024,
+            file.checksum
+        )),
+        (None, _) => Span::styled(
+            "none (d: download, o: open)",
+            Style::default().fg(Color::DarkGray),
+        ),
+    };
+    lines.push(Line::from(vec![label("File"), file]));
+
+    if fu.file.is_some() {
+        let status = match &fu.invalid {
+            None => Span::styled("valid", Style::default().fg(Color::Green)),
+            Some(e) => Span::styled(format!("INVALID - {e}"), Style::default().fg(Color::Red)),
+        };
+        lines.push(Line::from(vec![label("Validation"), status]));
+    }
+    let backup = match (&fu.backup, fu.backing_up) {
+        (_, true) => Span::styled(
+            format!("{} reading profiles...", app.spinner_char()),
+            Style::default().fg(Color::Yellow),
+        ),
+        (Some(Ok(path)), _) => Span::styled(
+            format!("{}  (iot_driver profile restore <file>)", path.display()),
+            Style::default().fg(Color::Green),
+        ),
+        (Some(Err(e)), _) => Span::styled(format!("FAILED - {e}"), Style::default().fg(Color::Red)),
+        (None, _) => Span::styled(
+            "none yet (b: back up all profiles)",
+            Style::default().fg(Color::DarkGray),
+        ),
+    };
+    lines.push(Line::from(vec![label("Backup"), backup]));
+    if let Some(plan) = &fu.plan {
+        lines.push(Line::from(vec![
+            label("Plan"),
+            Span::raw(format!(
+                "{} commands, ~{:.0}s transfer",
+                plan.commands.len(),
+                plan.estimated_time_secs
+            )),
+        ]));
+    }
+    lines
+}
+
+pub(super) fn render_firmware_update(f: &mut Frame, app: &App, area: Rect) {
+    let Some(fu) = app.firmware_update.as_ref() else {
+        return;
+    };
+    let w = 90.min(area.width.saturating_sub(4));
+    let h = 22.min(area.height.saturating_sub(2));
+    let popup = Rect::new(
+        area.x + (area.width.saturating_sub(w)) / 2,
+        area.y + (area.height.saturating_sub(h)) / 2,
+        w,
+        h,
+    );
+    f.render_widget(Clear, popup);
+
+    let block = Block::default()
+        .title(" Firmware Update — DRY RUN, device is never written ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(popup);
+    f.render_widget(block, popup);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(6),
+            Constraint::Min(7),
+            Constraint::Length(1),
+            Constraint::Length(2),
+        ])
+        .split(inner);
+
+    f.render_widget(Paragraph::new(overview_lines(app, fu)), chunks[0]);
+
+    // Step list with the commands each phase would send.
+    let mut steps = Vec::new();
+    for (i, &phase) in PHASES.iter().enumerate() {
+        let (marker, style) = phase_marker(fu.stage, i);
+        let detail = fu
+            .plan
+            .as_ref()
+            .map(|plan| {
+                let cmds: Vec<_> = plan.commands.iter().filter(|c| phase.matches(c)).collect();
+                match cmds.as_slice() {
+                    [] => String::new(),
+                    [one] => one.display(),
+                    [first, ..] => format!("{} chunks, first: {}", cmds.len(), first.display()),
+                }
+            })
+            .unwrap_or_default();
+        steps.push(Line::from(vec![
+            Span::styled(format!("{marker}{}. {:<28}", i + 1, phase.title()), style),
+            Span::styled(detail, Style::default().fg(Color::DarkGray)),
+        ]));
+    }
+    f.render_widget(
+        Paragraph::new(steps)
+            .block(Block::default().borders(Borders::TOP).title(" Steps "))
+            .wrap(Wrap { trim: true }),
+        chunks[1],
+    );
+
+    let progress = &fu.progress;
+    let (ratio, label) = match fu.stage {
+        Stage::Overview => (0.0, "not started".to_string()),
+        _ => (
+            f64::from(progress.percent) / 100.0,
+            format!(
+                "{}/{} chunks, {} retries (simulated)",
+                progress.chunk, progress.total_chunks, progress.retries
+            ),
+        ),
+    };
+    f.render_widget(
+        Gauge::default()
+            .gauge_style(Style::default().fg(Color::Green))
+            .ratio(ratio.clamp(0.0, 1.0))
+            .label(label),
+        chunks[2],
+    );
+
+    let hint = if let Some(input) = &fu.prompt {
+        Line::from(vec![
+            Span::styled("Open file: ", Style::default().fg(Color::Yellow)),
+            Span::raw(format!("{input}_")),
+            Span::styled(
+                "  Enter: load  Esc: cancel",
+                Style::default().fg(Color::DarkGray),
+            ),
+        ])
+    } else {
+        let text = match fu.stage {
+            Stage::Overview => {
+                "c: check server  d: download update  o: open file  b: back up settings  Enter: start dry run  Esc: close"
+                    .to_string()
+            }
+            Stage::Confirm(n) => format!(
+                "Step {}: {} — y: run it (simulated)  n/Esc: abort",
+                n + 1,
+                PHASES[n].title()
+            ),
+            Stage::Transferring => "Sending chunks (simulated)... Esc: abort".to_string(),
+            Stage::Done => {
+                "DRY RUN COMPLETE - DEVICE UNCHANGED. To flash for real: iot_driver firmware flash <file>  (Enter: back)"
+                    .to_string()
+            }
+        };
+        Line::styled(text, Style::default().fg(Color::DarkGray))
+    };
+    f.render_widget(Paragraph::new(hint).wrap(Wrap { trim: true }), chunks[3]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_dry_run_command_belongs_to_a_phase() {
+        let mut data = vec![0u8; 5000];
+        data[0] = 1;
+        let file =
+            FirmwareFile::from_data(data, "fw.bin".into(), crate::firmware::FirmwareType::Usb);
+        let plan = dry_run_usb(&file, None, None);
+        for cmd in &plan.commands {
+            assert_eq!(PHASES.iter().filter(|p| p.matches(cmd)).count(), 1);
+        }
+        let chunks = PHASES
+            .iter()
+            .find(|&&p| p == Phase::Transfer)
+            .map(|p| plan.commands.iter().filter(|c| p.matches(c)).count());
+        assert_eq!(chunks, Some(chunk_count(&file)));
+    }
+}
@@ -89,6 +89,31 @@ pub(crate) const TUI_KEYBINDS: &[Keybind] = &[
         description: "Switch profile 1-4",
         context: KeyContext::Global,
     },
+    Keybind {
+        keys: "P",
+        description: "Profile manager",
+        context: KeyContext::Global,
+    },
+    Keybind {
+        keys: "F",
+        description: "Firmware update (dry run)",
+        context: KeyContext::Global,
+    },
+    Keybind {
+        keys: "B",
+        description: "Battery graph",
+        context: KeyContext::Global,
+    },
+    Keybind {
+        keys: "< / >",
+        description: "Switch keyboard",
+        context: KeyContext::Global,
+    },
+    Keybind {
+        keys: "Mouse",
+        description: "Click to select, drag/wheel to adjust",
+        context: KeyContext::Global,
+    },
     Keybind {
         keys: "PgUp/PgDn",
         description: "Fast scroll (15 items)",
@@ -108,7 +133,7 @@ pub(crate) const TUI_KEYBINDS: &[Keybind] = &[
     // Depth tab
     Keybind {
         keys: "v",
-        description: "Toggle visualization mode",
+        description: "Cycle bar chart / time series / heatmap",
         context: KeyContext::Depth,
     },
     Keybind {
@@ -124,7 +149,27 @@ pub(crate) const TUI_KEYBINDS: &[Keybind] = &[
     // Triggers tab
     Keybind {
         keys: "v",
-        description: "Toggle list/layout view",
+        description: "Cycle list / layout / trigger grid",
+        context: KeyContext::Triggers,
+    },
+    Keybind {
+        keys: "Space / a",
+        description: "Grid: select key / all",
+        context: KeyContext::Triggers,
+    },
+    Keybind {
+        keys: "Tab",
+        description: "Grid: act / rel / RT press / RT lift",
+        context: KeyContext::Triggers,
+    },
+    Keybind {
+        keys: "+ / - ([ / ])",
+        description: "Grid: adjust selection (coarse)",
+        context: KeyContext::Triggers,
+    },
+    Keybind {
+        keys: "y / p",
+        description: "Grid: copy key / paste to selection",
         context: KeyContext::Triggers,
     },
     Keybind {
@@ -0,0 +1,294 @@
+// Keyboard Manager — every board the TUI has open, and which one the tabs show.
+//
+// Each responsive device found at connect time is opened once and kept, so a
+// keyboard + numpad (or two boards) can be switched between without a rescan.
+// Only the active board drives the tabs and the event stream; the others are
+// polled for the header strip.
+
+use std::sync::Arc;
+
+use ratatui::{prelude::*, widgets::*};
+
+use crate::cmd;
+use crate::device_loader::PollingRateSupport;
+use crate::devices;
+use monsgeek_keyboard::KeyboardInterface;
+use monsgeek_transport::{FlowControlTransport, Transport};
+
+use super::shared::{resolve_polling_rate, transport_type_name};
+use super::App;
+
+/// An opened board with everything the TUI derives from its identity.
+pub(super) struct OpenKeyboard {
+    pub keyboard: Arc<KeyboardInterface>,
+    pub hid_path: String,
+    pub vid: u16,
+    pub pid: u16,
+    pub device_name: String,
+    pub transport_name: &'static str,
+    pub key_count: u8,
+    pub display_key_count: u8,
+    pub has_sidelight: bool,
+    pub has_magnetism: bool,
+    pub matrix_size: usize,
+    pub matrix_key_names: Vec<String>,
+    pub is_wireless: bool,
+    pub polling_rate_support: PollingRateSupport,
+    pub polling_rates: &'static [u16],
+    /// Last header poll; unused for the active board, which has live values.
+    pub status: BoardStatus,
+}
+
+/// Header summary of a background board.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BoardStatus {
+    pub profile: Option<u8>,
+    pub battery: Option<u8>,
+    /// The last poll got no answer (asleep, out of range or unplugged).
+    pub offline: bool,
+}
+
+impl OpenKeyboard {
+    /// Identify a freshly opened transport and build its keyboard interface.
+    pub fn open(transport: Arc<dyn Transport>) -> Self {
+        let transport_info = transport.device_info().clone();
+        let vid = transport_info.vid;
+        let pid = transport_info.pid;
+
+        let flow_transport = Arc::new(FlowControlTransport::new(transport));
+
+        // Query device_id for accurate DB lookup (shared PIDs are ambiguous without it)
+        let device_id = flow_transport
+            .query_command(
+                cmd::GET_USB_VERSION,
+                &[],
+                monsgeek_transport::ChecksumType::Bit7,
+            )
+            .ok()
+            .filter(|r| r.len() >= 5 && r[0] == cmd::GET_USB_VERSION)
+            .map(|r| u32::from_le_bytes([r[1], r[2], r[3], r[4]]) as i32);
+
+        let db_key_count = devices::key_count_with_id(device_id, vid, pid);
+        let has_magnetism = devices::has_magnetism_with_id(device_id, vid, pid);
+        let device_info = devices::get_device_info_with_id(device_id, vid, pid);
+        let has_sidelight = device_info
+            .as_ref()
+            .map(|d| d.has_sidelight)
+            .unwrap_or(false);
+        let device_name = device_info
+            .as_ref()
+            .map(|d| d.display_name.clone())
+            .or_else(|| transport_info.product_name.clone())
+            .unwrap_or_else(|| format!("Device {vid:04x}:{pid:04x}"));
+
+        let protocol = monsgeek_transport::protocol::ProtocolFamily::detect(
+            device_info.as_ref().map(|d| d.name.as_str()),
+            pid,
+        );
+
+        // Try matrix database for key names and matrix size
+        let registry = crate::profile_registry();
+        let matrix_db: Option<&crate::device_loader::JsonDeviceMatrix> =
+            device_id.and_then(|id| registry.get_device_matrix(vid, pid, id));
+        let (mut key_count, display_key_count) = super::resolve_key_counts(db_key_count, matrix_db);
+        let profile = registry.find_for_device(device_id, vid, pid);
+        if let Some(p) = &profile {
+            key_count = key_count.max(p.key_count());
+        }
+        crate::keymap::select_board_layout(device_id, matrix_db);
+        crate::keymap::select_led_layout(device_id, vid, pid);
+
+        let mut kb = KeyboardInterface::new(flow_transport, key_count, has_magnetism, protocol);
+        let (polling_rate_support, polling_rates) =
+            resolve_polling_rate(device_id, vid, pid, transport_info.transport_type);
+        kb.set_polling_rates(polling_rates.to_vec());
+        kb.set_capabilities(devices::capabilities_with_id(device_id, vid, pid));
+
+        // Resolve key names: prefer builtin profile, fall back to matrix database.
+        if let Some(p) = profile {
+            let names: Vec<String> = (0..p.matrix_size())
+                .map(|i| p.matrix_key_name(i as u8).to_string())
+                .collect();
+            kb.set_matrix_key_names(names);
+        } else if let Some(matrix) = matrix_db {
+            let size = matrix.matrix_size();
+            let names: Vec<String> = (0..size)
+                .map(|i| matrix.key_name(i).unwrap_or("").to_string())
+                .collect();
+            kb.set_matrix_key_names(names);
+        }
+
+        // Set non-analog positions from matrix database (encoder/GPIO keys).
+        if let Some(matrix) = matrix_db {
+            if let Some(positions) = &matrix.non_analog_positions {
+                kb.set_non_analog_positions(positions.clone());
+            }
+        }
+
+        let matrix_size = kb.matrix_size();
+        let matrix_key_names: Vec<String> = (0..matrix_size)
+            .map(|i| kb.matrix_key_name(i).to_string())
+            .collect();
+
+        let keyboard = Arc::new(kb);
+        let is_wireless = keyboard.is_wireless();
+
+        Self {
+            keyboard,
+            hid_path: transport_info.device_path,
+            vid,
+            pid,
+            device_name,
+            transport_name: transport_type_name(transport_info.transport_type),
+            key_count,
+            display_key_count,
+            has_sidelight,
+            has_magnetism,
+            matrix_size,
+            matrix_key_names,
+            is_wireless,
+            polling_rate_support,
+            polling_rates,
+            status: BoardStatus::default(),
+        }
+    }
+}
+
+/// The open boards and which one is active.
+#[derive(Default)]
+pub(super) struct KeyboardManager {
+    boards: Vec<OpenKeyboard>,
+    active: usize,
+}
+
+impl KeyboardManager {
+    pub fn len(&self) -> usize {
+        self.boards.len()
+    }
+
+    pub fn boards(&self) -> &[OpenKeyboard] {
+        &self.boards
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn active(&self) -> Option<&OpenKeyboard> {
+        self.boards.get(self.active)
+    }
+
+    pub fn position(&self, hid_path: &str) -> Option<usize> {
+        self.boards.iter().position(|b| b.hid_path == hid_path)
+    }
+
+    /// Add a board, replacing one already open at the same HID path.
+    pub fn insert(&mut self, board: OpenKeyboard) -> usize {
+        match self.position(&board.hid_path) {
+            Some(i) => {
+                self.boards[i] = board;
+                i
+            }
+            None => {
+                self.boards.push(board);
+                self.boards.len() - 1
+            }
+        }
+    }
+
+    pub fn set_active(&mut self, index: usize) {
+        if index < self.boards.len() {
+            self.active = index;
+        }
+    }
+
+    /// The board after (or before) the active one, wrapping.
+    pub fn neighbor(&self, forward: bool) -> Option<usize> {
+        let n = self.boards.len();
+        (n > 1).then(|| {
+            if forward {
+                (self.active + 1) % n
+            } else {
+                (self.active + n - 1) % n
+            }
+        })
+    }
+
+    pub fn set_status(&mut self, hid_path: &str, status: BoardStatus) {
+        if let Some(i) = self.position(hid_path) {
+            self.boards[i].status = status;
+        }
+    }
+}
+
+/// Query the header summary of one board.
+pub(super) fn poll_status(kb: &KeyboardInterface) -> BoardStatus {
+    let profile = kb.get_profile().ok();
+    let battery = if kb.is_wireless() {
+        kb.get_battery().ok().map(|b| b.level)
+    } else {
+        None
+    };
+    BoardStatus {
+        profile,
+        battery,
+        offline: profile.is_none(),
+    }
+}
+
+/// One-line strip naming every open board; the active one is highlighted and
+/// shows the live profile/battery instead of the last poll.
+pub(super) fn render_board_strip(f: &mut Frame, app: &App, area: Rect) {
+    let mut spans = Vec::new();
+    for (i, board) in app.keyboards.boards().iter().enumerate() {
+        let active = i == app.keyboards.active_index();
+        let status = if active {
+            BoardStatus {
+                profile: Some(app.info.profile),
+                battery: app.battery.as_ref().map(|b| b.level),
+                offline: false,
+            }
+        } else {
+            board.status.clone()
+        };
+        let mut text = format!(
+            " {} {} ({})",
+            i + 1,
+            board.device_name,
+            board.transport_name
+        );
+        if status.offline {
+            text.push_str(" offline");
+        } else {
+            if let Some(p) = status.profile {
+                text.push_str(&format!(" P{}", p + 1));
+            }
+            if let Some(level) = status.battery {
+                text.push_str(&format!(" {level}%"));
+            }
+        }
+        text.push(' ');
+        let style = if active {
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Cyan)
+                .add_modifier(Modifier::BOLD)
+        } else if status.offline {
+            Style::default().fg(Color::DarkGray)
+        } else {
+            Style::default().fg(Color::Cyan)
+        };
+        if i > 0 {
+            spans.push(Span::raw("│"));
+        }
+        spans.push(Span::styled(text, style));
+    }
+    spans.push(Span::styled(
+        "   < >: switch keyboard",
+        Style::default().fg(Color::DarkGray),
+    ));
+    f.render_widget(
+        Paragraph::new(Line::from(spans)).alignment(Alignment::Center),
+        area,
+    );
+}
@@ -1,12 +1,21 @@
 // MonsGeek M1 V5 HE TUI Application
 // Real-time monitoring and settings configuration
 
+mod battery;
+mod firmware;
 mod help;
+mod keyboards;
+mod mouse;
+mod profiles;
 mod shared;
 mod tabs;
 mod widgets;
 
+use battery::{render_battery_graph, BatteryGraph};
+use firmware::{render_firmware_update, FirmwareUpdate};
 use help::render_help_popup;
+use keyboards::{KeyboardManager, OpenKeyboard};
+use profiles::{render_profile_manager, ProfileManager};
 use shared::*;
 
 use tabs::audio::AudioTabState;
@@ -17,7 +26,8 @@ use tabs::remaps::{
     text_preview_from_events, BindingEditor, BindingField, BindingType, RemapFocus, RemapLayerView,
 };
 
-use tabs::key_mapping::{KeyMappingFilter, KeyMappingView, KmSort};
+use tabs::key_mapping::{KeyGridArea, KeyMappingFilter, KeyMappingView, KmSort};
+use tabs::trigger_grid::TriggerGrid;
 use tabs::triggers::{render_trigger_edit_modal, TriggerEditModal};
 
 #[cfg(feature = "notify")]
@@ -26,10 +36,7 @@ use crate::effect::{default_effects_path, EffectLibrary};
 use tabs::notify::{handle_notify_input, render_notify, NotifyFocus, NotifyTabState};
 
 use crossterm::{
-    event::{
-        DisableMouseCapture, EnableMouseCapture, Event, EventStream, KeyCode, KeyEventKind,
-        MouseButton, MouseEventKind,
-    },
+    event::{DisableMouseCapture, EnableMouseCapture, Event, EventStream, KeyCode, KeyEventKind},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
@@ -45,20 +52,20 @@ use tokio::sync::{broadcast, mpsc};
 use tui_scrollview::ScrollViewState;
 
 // Use shared library
+use crate::battery_history::BatteryHistory;
 use crate::firmware_api::FirmwareCheckResult;
 use crate::hid::BatteryInfo;
 use crate::key_action::KeyAction;
 use crate::keymap::{self, KeyEntry, KeyRow, Layer};
 use crate::power_supply::find_hid_battery_power_supply;
-use crate::{cmd, devices, FirmwareSettings, TriggerSettings};
+use crate::{devices, FirmwareSettings, TriggerSettings};
 use monsgeek_transport::protocol::matrix;
 
 // Keyboard abstraction layer - using async interface directly
 use monsgeek_keyboard::{
-    led::speed_to_wire, DksCombo, KeyboardInterface, Precision, SleepTimeSettings,
-    TimestampedEvent, VendorEvent,
+    DksCombo, KeyboardInterface, Precision, SleepTimeSettings, TimestampedEvent, VendorEvent,
 };
-use monsgeek_transport::{FlowControlTransport, HidDiscovery, Transport};
+use monsgeek_transport::{HidDiscovery, Transport};
 
 /// Resolve `(array_dim, display_count)` for a device.
 ///
@@ -135,6 +142,8 @@ struct App {
     key_mapping_filter: KeyMappingFilter,
     key_mapping_filter_open: bool,
     key_mapping_filter_field: usize,
+    trigger_grid: TriggerGrid,
+    profile_manager: Option<ProfileManager>,
     // Macro data (loaded alongside remaps or on editor open)
     macros: Vec<MacroSlot>,
     // Key depth visualization
@@ -145,6 +154,7 @@ struct App {
     depth_cursor: usize,                      // Cursor for key selection
     max_observed_depth: f32,                  // Max depth observed during session (for bar scaling)
     depth_last_update: Vec<Instant>,          // Last update time per key (for stale detection)
+    depth_peaks: Vec<(f32, Instant)>,         // Per-key peak depth and when it was hit (heatmap)
     // Patch info (custom firmware capabilities)
     patch_info: Option<PatchInfoData>,
     // Dongle patch info (custom dongle firmware capabilities)
@@ -155,6 +165,8 @@ struct App {
     rf_info: Option<monsgeek_transport::RfInfo>,
     // Battery status (for 2.4GHz dongle)
     battery: Option<BatteryInfo>,
+    battery_history: BatteryHistory,
+    battery_graph: Option<BatteryGraph>,
     battery_source: Option<BatterySource>,
     last_battery_check: Instant,
     is_wireless: bool,
@@ -173,6 +185,9 @@ struct App {
     device_picker_selected: usize,
     // Keyboard interface (async, wrapped in Arc for spawning tasks)
     keyboard: Option<Arc<KeyboardInterface>>,
+    // Every open board; `keyboard` is the active one's interface
+    keyboards: KeyboardManager,
+    last_board_poll: Instant,
     // Event receiver for low-latency EP2 notifications (with timestamps)
     event_rx: Option<broadcast::Receiver<TimestampedEvent>>,
     loading: LoadingStates,
@@ -185,9 +200,13 @@ struct App {
     hex_target: HexColorTarget,
     // Firmware check result
     firmware_check: Option<FirmwareCheckResult>,
+    firmware_update: Option<FirmwareUpdate>,
     // Mouse hit areas (updated during render via interior mutability)
     tab_bar_area: StdCell<Rect>,
     content_area: StdCell<Rect>,
+    key_grid_area: StdCell<Option<KeyGridArea>>,
+    // Last column of an in-progress left-button drag
+    drag_column: Option<u16>,
     // Scroll view state for content area
     scroll_state: ScrollViewState,
     // Trigger edit modal
@@ -253,6 +272,8 @@ impl App {
             key_mapping_filter: KeyMappingFilter::default(),
             key_mapping_filter_open: false,
             key_mapping_filter_field: 0,
+            trigger_grid: TriggerGrid::default(),
+            profile_manager: None,
             macros: Vec::new(),
             // Key depth visualization
             depth_view_mode: DepthViewMode::default(),
@@ -262,6 +283,7 @@ impl App {
             depth_cursor: 0,
             max_observed_depth: 0.1, // Will grow as keys are pressed
             depth_last_update: Vec::new(),
+            depth_peaks: Vec::new(),
             // Patch info
             patch_info: None,
             dongle_patch_info: None,
@@ -271,6 +293,8 @@ impl App {
             rf_info: None,
             // Battery status
             battery: None,
+            battery_history: BatteryHistory::default(),
+            battery_graph: None,
             battery_source: None,
             last_battery_check: Instant::now(),
             is_wireless: false,
@@ -284,6 +308,8 @@ impl App {
             device_picker_selected: 0,
             // Keyboard interface (wrapped in Arc for spawning tasks)
             keyboard: None,
+            keyboards: KeyboardManager::default(),
+            last_board_poll: Instant::now(),
             // Event receiver (subscribed on connect)
             event_rx: None,
             loading: LoadingStates::default(),
@@ -295,9 +321,12 @@ impl App {
             hex_target: HexColorTarget::default(),
             // Firmware check
             firmware_check: None,
+            firmware_update: None,
             // Mouse hit areas (updated during render)
             tab_bar_area: StdCell::new(Rect::default()),
             content_area: StdCell::new(Rect::default()),
+            key_grid_area: StdCell::new(None),
+            drag_column: None,
             // Scroll view state
             scroll_state: ScrollViewState::new(),
             // Trigger edit modal
@@ -328,64 +357,29 @@ impl App {
     }
 
     fn connect(&mut self) -> Result<(), String> {
-        // Bump generation so in-flight async results from the old device are discarded
-        self.device_generation += 1;
+        use monsgeek_transport::DeviceDiscovery;
 
         // Use async device discovery with smart probing
         let discovery = HidDiscovery::new();
+        let resolve_name = |device_id: Option<u32>, vid: u16, pid: u16| -> Option<String> {
+            devices::get_device_info_with_id(device_id.map(|id| id as i32), vid, pid)
+                .map(|info| info.display_name)
+        };
+        let labeled = discovery
+            .list_labeled_devices(resolve_name)
+            .map_err(|e| format!("Discovery failed: {e}"))?;
 
         // If --device was specified, use the resolve logic; otherwise auto-select
         let transport = if let Some(ref selector) = self.device_selector {
-            use monsgeek_transport::DeviceDiscovery;
-            let resolve_name = |device_id: Option<u32>, vid: u16, pid: u16| -> Option<String> {
-                devices::get_device_info_with_id(device_id.map(|id| id as i32), vid, pid)
-                    .map(|info| info.display_name)
-            };
-            let labeled = discovery
-                .list_labeled_devices(resolve_name)
-                .map_err(|e| format!("Discovery failed: {e}"))?;
             if labeled.is_empty() {
                 return Err("No supported device found".into());
             }
-            // Try index
-            if let Ok(idx) = selector.parse::<usize>() {
-                let (p, _) = labeled
-                    .into_iter()
-                    .find(|(_, l)| l.index == idx)
-                    .ok_or_else(|| format!("Device index {idx} out of range"))?;
-                discovery
-                    .open_device(&p.device)
-                    .map_err(|e| format!("Failed to open device: {e}"))?
-            } else {
-                // Try transport name
-                let matches: Vec<_> = labeled
-                    .iter()
-                    .filter(|(_, l)| l.transport_name == selector.as_str())
-                    .collect();
-                if matches.len() == 1 {
-                    discovery
-                        .open_device(&matches[0].0.device)
-                        .map_err(|e| format!("Failed to open device: {e}"))?
-                } else if matches.len() > 1 {
-                    return Err(format!(
-                        "Ambiguous --device '{selector}': {} matches",
-                        matches.len()
-                    ));
-                } else {
-                    // Try path match
-                    let path_matches: Vec<_> = labeled
-                        .iter()
-                        .filter(|(_, l)| l.hid_path.contains(selector.as_str()))
-                        .collect();
-                    if path_matches.len() == 1 {
-                        discovery
-                            .open_device(&path_matches[0].0.device)
-                            .map_err(|e| format!("Failed to open device: {e}"))?
-                    } else {
-                        return Err(format!("No device matches '{selector}'"));
-                    }
-                }
-            }
+            let labels: Vec<_> = labeled.iter().map(|(_, l)| l.clone()).collect();
+            let pos =
+                monsgeek_transport::select_device(&labels, selector).map_err(|e| e.to_string())?;
+            discovery
+                .open_device(&labeled[pos].0.device)
+                .map_err(|e| format!("Failed to open device: {e}"))?
         } else {
             // Default: auto-select best device (TUI picks first, doesn't error on multiple)
             discovery
@@ -393,100 +387,69 @@ impl App {
                 .map_err(|e| format!("Failed to open device: {e}"))?
         };
 
-        let transport_info = transport.device_info().clone();
-        let vid = transport_info.vid;
-        let pid = transport_info.pid;
-
-        let flow_transport = Arc::new(FlowControlTransport::new(transport));
-
-        // Query device_id for accurate DB lookup (shared PIDs are ambiguous without it)
-        let device_id = flow_transport
-            .query_command(
-                cmd::GET_USB_VERSION,
-                &[],
-                monsgeek_transport::ChecksumType::Bit7,
-            )
-            .ok()
-            .filter(|r| r.len() >= 5 && r[0] == cmd::GET_USB_VERSION)
-            .map(|r| u32::from_le_bytes([r[1], r[2], r[3], r[4]]) as i32);
-
-        let db_key_count = devices::key_count_with_id(device_id, vid, pid);
-        let has_magnetism = devices::has_magnetism_with_id(device_id, vid, pid);
-        let device_info = devices::get_device_info_with_id(device_id, vid, pid);
-        let has_sidelight = device_info
-            .as_ref()
-            .map(|d| d.has_sidelight)
-            .unwrap_or(false);
-        let device_name = device_info
-            .as_ref()
-            .map(|d| d.display_name.clone())
-            .or_else(|| transport_info.product_name.clone())
-            .unwrap_or_else(|| format!("Device {vid:04x}:{pid:04x}"));
-
-        let protocol = monsgeek_transport::protocol::ProtocolFamily::detect(
-            device_info.as_ref().map(|d| d.name.as_str()),
-            pid,
-        );
-
-        // Try matrix database for key names and matrix size
-        let registry = crate::profile_registry();
-        let matrix_db: Option<&crate::device_loader::JsonDeviceMatrix> =
-            device_id.and_then(|id| registry.get_device_matrix(vid, pid, id));
-        let (key_count, display_key_count) = resolve_key_counts(db_key_count, matrix_db);
-
-        let mut kb = KeyboardInterface::new(flow_transport, key_count, has_magnetism, protocol);
-        let (polling_rate_support, polling_rates) =
-            resolve_polling_rate(device_id, vid, pid, transport_info.transport_type);
-        kb.set_polling_rates(polling_rates.to_vec());
-
-        // Resolve key names: prefer builtin profile, fall back to matrix database.
-        let profile = device_id
-            .and_then(|id| registry.find_by_id(id as u32))
-            .or_else(|| registry.find_by_vid_pid(vid, pid));
-        if let Some(p) = profile {
-            let names: Vec<String> = (0..p.matrix_size())
-                .map(|i| p.matrix_key_name(i as u8).to_string())
-                .collect();
-            kb.set_matrix_key_names(names);
-        } else if let Some(matrix) = matrix_db {
-            let size = matrix.matrix_size();
-            let names: Vec<String> = (0..size)
-                .map(|i| matrix.key_name(i).unwrap_or("").to_string())
-                .collect();
-            kb.set_matrix_key_names(names);
-        }
+        // Start over: boards from an earlier connect may be gone.
+        self.keyboards = KeyboardManager::default();
+        let primary = self.keyboards.insert(OpenKeyboard::open(transport));
 
-        // Set non-analog positions from matrix database (encoder/GPIO keys).
-        if let Some(matrix) = matrix_db {
-            if let Some(positions) = &matrix.non_analog_positions {
-                kb.set_non_analog_positions(positions.clone());
+        // Open the other responsive boards too, for switching and the header strip.
+        for (probed, label) in &labeled {
+            if !probed.responsive || self.keyboards.position(&label.hid_path).is_some() {
+                continue;
+            }
+            match discovery.open_device(&probed.device) {
+                Ok(transport) => {
+                    self.keyboards.insert(OpenKeyboard::open(transport));
+                }
+                Err(e) => tracing::warn!("tui: could not open {}: {e}", label.hid_path),
             }
         }
 
-        let matrix_size = kb.matrix_size();
-        let matrix_key_names: Vec<String> = (0..matrix_size)
-            .map(|i| kb.matrix_key_name(i).to_string())
-            .collect();
+        self.activate_keyboard(primary);
+        Ok(())
+    }
 
-        let keyboard = Arc::new(kb);
-        let is_wireless = keyboard.is_wireless();
+    /// Make board `index` of the manager the one the tabs show. Per-device state
+    /// is reset; callers load what they need afterwards.
+    fn activate_keyboard(&mut self, index: usize) {
+        // Bump generation so in-flight async results from the old device are discarded
+        self.device_generation += 1;
+
+        // Depth reports follow the active board.
+        if self.depth_monitoring {
+            if let Some(ref keyboard) = self.keyboard {
+                let _ = keyboard.stop_magnetism_report();
+            }
+        }
+
+        self.keyboards.set_active(index);
+        let Some(board) = self.keyboards.active() else {
+            return;
+        };
+        let keyboard = board.keyboard.clone();
 
         // Subscribe to low-latency event notifications
         self.event_rx = keyboard.subscribe_events();
 
-        self.device_name = device_name;
-        self.transport_name = transport_type_name(transport_info.transport_type);
-        self.key_count = key_count;
-        self.display_key_count = display_key_count;
-        self.has_sidelight = has_sidelight;
-        self.has_magnetism = has_magnetism;
-        self.matrix_size = matrix_size;
-        self.matrix_key_names = matrix_key_names;
-        self.is_wireless = is_wireless;
-        self.polling_rate_support = polling_rate_support;
-        self.polling_rates = polling_rates;
+        self.device_name = board.device_name.clone();
+        self.transport_name = board.transport_name;
+        self.key_count = board.key_count;
+        self.display_key_count = board.display_key_count;
+        self.has_sidelight = board.has_sidelight;
+        self.has_magnetism = board.has_magnetism;
+        self.matrix_size = board.matrix_size;
+        self.matrix_key_names = board.matrix_key_names.clone();
+        self.is_wireless = board.is_wireless;
+        self.polling_rate_support = board.polling_rate_support;
+        self.polling_rates = board.polling_rates;
+        let (vid, pid) = (board.vid, board.pid);
         self.keyboard = Some(keyboard);
 
+        if self.depth_monitoring {
+            if let Some(ref keyboard) = self.keyboard {
+                let _ = keyboard.start_magnetism_report();
+            }
+        }
+
         // Initialize key depths array based on actual key count
         self.key_depths = vec![0.0; self.key_count as usize];
         // Initialize depth history for time series
@@ -494,11 +457,17 @@ impl App {
             vec![VecDeque::with_capacity(DEPTH_HISTORY_LEN); self.key_count as usize];
         // Initialize last update times (set to past so they don't show as active)
         self.depth_last_update = vec![Instant::now(); self.key_count as usize];
+        self.depth_peaks = vec![(0.0, Instant::now()); self.key_count as usize];
         self.active_keys.clear();
         self.selected_keys.clear();
+        self.depth_cursor = 0;
 
         // Detect battery source (kernel power_supply if eBPF loaded, else vendor)
+        self.battery = None;
+        self.battery_source = None;
+        self.battery_history = BatteryHistory::default();
         if self.is_wireless {
+            self.battery_history = BatteryHistory::load(&self.device_name);
             self.battery_source = if let Some(path) = find_hid_battery_power_supply(vid, pid) {
                 Some(BatterySource::Kernel(path))
             } else {
@@ -508,7 +477,25 @@ impl App {
 
         self.connected = true;
 
+        // Reset loading states so tabs re-fetch
+        self.loading = LoadingStates::default();
+        self.info = FirmwareSettings::default();
+        self.triggers = None;
+        self.remaps.clear();
+        self.key_rows.clear();
+        self.trigger_grid = TriggerGrid::default();
+        self.profile_manager = None;
+        self.battery_graph = None;
+        self.patch_info = None;
+        self.dongle_patch_info = None;
+        self.firmware_check = None;
+        self.firmware_update = None;
+        self.anim_snapshot = None;
+
         // Load dongle info (instant dongle-local queries)
+        self.dongle_info = None;
+        self.dongle_status = None;
+        self.rf_info = None;
         if self.is_wireless {
             if let Some(ref keyboard) = self.keyboard {
                 let transport = keyboard.transport();
@@ -531,8 +518,37 @@ impl App {
         } else {
             self.status_msg = format!("Connected to {}", self.device_name);
         }
+    }
 
-        Ok(())
+    /// Switch the tabs to the next (or previous) open board.
+    fn switch_keyboard(&mut self, forward: bool) {
+        let Some(index) = self.keyboards.neighbor(forward) else {
+            self.status_msg = "Only one keyboard connected".to_string();
+            return;
+        };
+        self.activate_keyboard(index);
+        self.load_device_info();
+        self.load_options();
+        self.auto_load_tab();
+        self.poll_keyboards();
+    }
+
+    /// Refresh the header strip's profile/battery for the background boards.
+    fn poll_keyboards(&mut self) {
+        self.last_board_poll = Instant::now();
+        let active = self.keyboards.active_index();
+        for (i, board) in self.keyboards.boards().iter().enumerate() {
+            if i == active {
+                continue;
+            }
+            let keyboard = board.keyboard.clone();
+            let hid_path = board.hid_path.clone();
+            let tx = self.gen_sender();
+            tokio::spawn(async move {
+                let status = keyboards::poll_status(&keyboard);
+                tx.send(AsyncResult::BoardStatus(hid_path, status));
+            });
+        }
     }
 
     /// Scan for devices and populate the device picker list.
@@ -554,13 +570,11 @@ impl App {
         }
     }
 
-    /// Connect to the device currently selected in the device picker.
+    /// Connect to the device currently selected in the device picker. A board
+    /// that is already open is switched to rather than reopened.
     fn connect_to_picked_device(&mut self) {
         use monsgeek_transport::DeviceDiscovery;
 
-        // Bump generation so in-flight async results from the old device are discarded
-        self.device_generation += 1;
-
         if self.device_picker_items.is_empty() {
             return;
         }
@@ -569,147 +583,24 @@ impl App {
             .min(self.device_picker_items.len() - 1);
         let (probed, label) = &self.device_picker_items[idx];
 
-        let discovery = HidDiscovery::new();
-        match discovery.open_device(&probed.device) {
-            Ok(transport) => {
-                // Set device_selector to the index so connect() logic is bypassed
-                self.device_selector = Some(label.index.to_string());
-                self.show_device_picker = false;
-
-                // Now do the full connection setup with this transport
-                let transport_info = transport.device_info().clone();
-                let vid = transport_info.vid;
-                let pid = transport_info.pid;
-                let flow_transport = Arc::new(FlowControlTransport::new(transport));
-
-                let device_id = flow_transport
-                    .query_command(
-                        cmd::GET_USB_VERSION,
-                        &[],
-                        monsgeek_transport::ChecksumType::Bit7,
-                    )
-                    .ok()
-                    .filter(|r| r.len() >= 5 && r[0] == cmd::GET_USB_VERSION)
-                    .map(|r| u32::from_le_bytes([r[1], r[2], r[3], r[4]]) as i32);
-
-                let db_key_count = devices::key_count_with_id(device_id, vid, pid);
-                let has_magnetism = devices::has_magnetism_with_id(device_id, vid, pid);
-                let device_info = devices::get_device_info_with_id(device_id, vid, pid);
-                let has_sidelight = device_info
-                    .as_ref()
-                    .map(|d| d.has_sidelight)
-                    .unwrap_or(false);
-                let device_name = device_info
-                    .as_ref()
-                    .map(|d| d.display_name.clone())
-                    .or_else(|| transport_info.product_name.clone())
-                    .unwrap_or_else(|| format!("Device {vid:04x}:{pid:04x}"));
-
-                let protocol = monsgeek_transport::protocol::ProtocolFamily::detect(
-                    device_info.as_ref().map(|d| d.name.as_str()),
-                    pid,
-                );
-
-                let registry = crate::profile_registry();
-                let matrix_db = device_id.and_then(|id| registry.get_device_matrix(vid, pid, id));
-                let (key_count, display_key_count) = resolve_key_counts(db_key_count, matrix_db);
-
-                let mut kb =
-                    KeyboardInterface::new(flow_transport, key_count, has_magnetism, protocol);
-                let (polling_rate_support, polling_rates) =
-                    resolve_polling_rate(device_id, vid, pid, transport_info.transport_type);
-                kb.set_polling_rates(polling_rates.to_vec());
-
-                let profile = device_id
-                    .and_then(|id| registry.find_by_id(id as u32))
-                    .or_else(|| registry.find_by_vid_pid(vid, pid));
-                if let Some(p) = profile {
-                    let names: Vec<String> = (0..p.matrix_size())
-                        .map(|i| p.matrix_key_name(i as u8).to_string())
-                        .collect();
-                    kb.set_matrix_key_names(names);
-                } else if let Some(matrix) = matrix_db {
-                    let size = matrix.matrix_size();
-                    let names: Vec<String> = (0..size)
-                        .map(|i| matrix.key_name(i).unwrap_or("").to_string())
-                        .collect();
-                    kb.set_matrix_key_names(names);
-                }
-
-                if let Some(matrix) = matrix_db {
-                    if let Some(positions) = &matrix.non_analog_positions {
-                        kb.set_non_analog_positions(positions.clone());
-                    }
-                }
-
-                let matrix_size = kb.matrix_size();
-                let matrix_key_names: Vec<String> = (0..matrix_size)
-                    .map(|i| kb.matrix_key_name(i).to_string())
-                    .collect();
-
-                let keyboard = Arc::new(kb);
-                let is_wireless = keyboard.is_wireless();
-
-                self.event_rx = keyboard.subscribe_events();
-                self.device_name = device_name;
-                self.transport_name = transport_type_name(transport_info.transport_type);
-                self.key_count = key_count;
-                self.display_key_count = display_key_count;
-                self.has_sidelight = has_sidelight;
-                self.has_magnetism = has_magnetism;
-                self.matrix_size = matrix_size;
-                self.matrix_key_names = matrix_key_names;
-                self.is_wireless = is_wireless;
-                self.polling_rate_support = polling_rate_support;
-                self.polling_rates = polling_rates;
-                self.keyboard = Some(keyboard);
-
-                self.key_depths = vec![0.0; self.key_count as usize];
-                self.depth_history =
-                    vec![VecDeque::with_capacity(DEPTH_HISTORY_LEN); self.key_count as usize];
-                self.depth_last_update = vec![Instant::now(); self.key_count as usize];
-                self.active_keys.clear();
-                self.selected_keys.clear();
-
-                if self.is_wireless {
-                    self.battery_source =
-                        if let Some(path) = find_hid_battery_power_supply(vid, pid) {
-                            Some(BatterySource::Kernel(path))
-                        } else {
-                            Some(BatterySource::Vendor)
-                        };
-                }
-
-                self.connected = true;
-
-                if self.is_wireless {
-                    if let Some(ref keyboard) = self.keyboard {
-                        let transport = keyboard.transport();
-                        self.dongle_info = transport.query_dongle_info().ok().flatten();
-                        self.dongle_status = transport.query_dongle_status().ok().flatten();
-                        self.rf_info = transport.query_rf_info().ok().flatten();
-                    }
-                    self.refresh_battery();
+        let index = match self.keyboards.position(&label.hid_path) {
+            Some(index) => index,
+            None => match HidDiscovery::new().open_device(&probed.device) {
+                Ok(transport) => self.keyboards.insert(OpenKeyboard::open(transport)),
+                Err(e) => {
+                    self.status_msg = format!("Failed to open device: {e}");
+                    self.show_device_picker = false;
+                    return;
                 }
+            },
+        };
+        // Set device_selector to the index so a reconnect picks the same device
+        self.device_selector = Some(label.index.to_string());
+        self.show_device_picker = false;
 
-                // Reset loading states so tabs re-fetch
-                self.loading = LoadingStates::default();
-                self.info = FirmwareSettings::default();
-                self.triggers = None;
-                self.remaps.clear();
-                self.patch_info = None;
-                self.dongle_patch_info = None;
-                self.firmware_check = None;
-                self.anim_snapshot = None;
-
-                self.status_msg = format!("Connected to {}", self.device_name);
-                self.load_device_info();
-            }
-            Err(e) => {
-                self.status_msg = format!("Failed to open device: {e}");
-                self.show_device_picker = false;
-            }
-        }
+        self.activate_keyboard(index);
+        self.load_device_info();
+        self.auto_load_tab();
     }
     /// Load remaps (tab 5) from device — reads key matrix for both layers
     fn load_remaps(&mut self) {
@@ -1017,6 +908,12 @@ impl App {
                 self.loading.macros = LoadState::Error;
                 self.status_msg = "Failed to load macros".to_string();
             }
+            AsyncResult::FirmwareFile(result) => self.on_firmware_file(result),
+            AsyncResult::SettingsBackup(result) => self.on_settings_backup(result),
+            AsyncResult::BoardStatus(hid_path, status) => {
+                self.keyboards.set_status(&hid_path, status);
+            }
+            AsyncResult::Profiles(result, outcome) => self.on_profiles_loaded(result, outcome),
             AsyncResult::SetComplete(field, Ok(())) => {
                 self.status_msg = format!("{field} updated");
                 // Reload remaps after remap operations
@@ -1034,6 +931,7 @@ impl App {
             // Battery is read synchronously via feature report, not used currently
             AsyncResult::Battery(Ok(info)) => {
                 self.battery = Some(info);
+                self.record_battery();
             }
             AsyncResult::Battery(Err(e)) => {
                 self.status_msg = format!("Battery read failed: {e}");
@@ -1293,6 +1191,21 @@ pub async fn run(device_selector: Option<String>) -> io::Result<()> {
                         continue;
                     }
 
+                    if app.profile_manager.is_some() {
+                        profiles::handle_key(&mut app, key);
+                        continue;
+                    }
+
+                    if app.battery_graph.is_some() {
+                        battery::handle_key(&mut app, key);
+                        continue;
+                    }
+
+                    if app.firmware_update.is_some() {
+                        firmware::handle_key(&mut app, key);
+                        continue;
+                    }
+
                     // Device picker popup handling
                     if app.show_device_picker {
                         match key.code {
@@ -1567,6 +1480,8 @@ pub async fn run(device_selector: Option<String>) -> io::Result<()> {
                         }
                     }
 
+                    let in_trigger_grid =
+                        app.tab == 2 && app.key_mapping_view == KeyMappingView::Triggers;
                     match key.code {
                         // Help toggle
                         KeyCode::Char('?') | KeyCode::F(1) => {
@@ -1583,6 +1498,9 @@ pub async fn run(device_selector: Option<String>) -> io::Result<()> {
                             #[cfg(not(feature = "notify"))]
                             break;
                         }
+                        KeyCode::Tab | KeyCode::BackTab if in_trigger_grid => {
+                            app.trigger_grid.cycle_field(key.code == KeyCode::Tab);
+                        }
                         // Tab/BackTab: navigate within current tab
                         KeyCode::Tab | KeyCode::BackTab => {
                             #[cfg(feature = "notify")]
@@ -1593,17 +1511,9 @@ pub async fn run(device_selector: Option<String>) -> io::Result<()> {
                         }
                         KeyCode::Up | KeyCode::Char('k') => {
                             if app.tab == 1 && app.depth_view_mode == DepthViewMode::BarChart {
-                                let row_starts = [0, 15, 30, 43, 56];
-                                if let Some(row) = row_starts.iter().rposition(|&s| s <= app.depth_cursor) {
-                                    if row > 0 {
-                                        let col = app.depth_cursor - row_starts[row];
-                                        let prev_row_start = row_starts[row - 1];
-                                        let prev_row_size = row_starts[row] - prev_row_start;
-                                        app.depth_cursor = prev_row_start + col.min(prev_row_size - 1);
-                                    }
-                                }
+                                app.move_depth_cursor(0, -1);
                             } else if app.tab == 2 {
-                                if app.key_mapping_view == KeyMappingView::Layout {
+                                if app.key_mapping_view.is_grid() {
                                     tabs::key_mapping::layout_move(&mut app, 0, -1);
                                 } else if app.key_mapping_selected > 0 {
                                     app.key_mapping_selected -= 1;
@@ -1624,17 +1534,9 @@ pub async fn run(device_selector: Option<String>) -> io::Result<()> {
                         }
                         KeyCode::Down | KeyCode::Char('j') => {
                             if app.tab == 1 && app.depth_view_mode == DepthViewMode::BarChart {
-                                let row_starts = [0, 15, 30, 43, 56, 66];
-                                if let Some(row) = row_starts.iter().rposition(|&s| s <= app.depth_cursor) {
-                                    if row < 4 {
-                                        let col = app.depth_cursor - row_starts[row];
-                                        let next_row_start = row_starts[row + 1];
-                                        let next_row_size = row_starts[row + 2] - next_row_start;
-                                        app.depth_cursor = next_row_start + col.min(next_row_size - 1);
-                                    }
-                                }
+                                app.move_depth_cursor(0, 1);
                             } else if app.tab == 2 {
-                                if app.key_mapping_view == KeyMappingView::Layout {
+                                if app.key_mapping_view.is_grid() {
                                     tabs::key_mapping::layout_move(&mut app, 0, 1);
                                 } else if app.key_mapping_selected + 1
                                     < tabs::key_mapping::visible_indices(&app).len()
@@ -1657,64 +1559,12 @@ pub async fn run(device_selector: Option<String>) -> io::Result<()> {
                         }
                         KeyCode::Left | KeyCode::Char('h') => {
                             if app.tab == 1 && app.depth_view_mode == DepthViewMode::BarChart {
-                                if app.depth_cursor > 0 {
-                                    app.depth_cursor -= 1;
-                                }
-                            } else if app.tab == 2 && app.key_mapping_view == KeyMappingView::Layout {
+                                app.move_depth_cursor(-1, 0);
+                            } else if app.tab == 2 && app.key_mapping_view.is_grid() {
                                 tabs::key_mapping::layout_move(&mut app, -1, 0);
                             } else if app.tab == 0 {
                                 let coarse = key.modifiers.contains(KeyModifiers::SHIFT);
-                                match app.info_tags.get(app.selected).copied().unwrap_or(InfoTag::ReadOnly) {
-                                    InfoTag::Profile => app.set_profile(PROFILE_SPINNER.decrement_u8(app.info.profile, coarse)),
-                                    InfoTag::Debounce => app.set_debounce(DEBOUNCE_SPINNER.decrement_u8(app.info.debounce, coarse)),
-                                    InfoTag::PollingRate => app.cycle_polling_rate(1), // higher index = lower rate
-                                    InfoTag::LedMode => app.set_led_mode(app.info.led_mode.saturating_sub(1)),
-                                    InfoTag::LedBrightness => app.set_brightness(BRIGHTNESS_SPINNER.decrement_u8(app.info.led_brightness, coarse)),
-                                    InfoTag::LedSpeed => {
-                                        let current = speed_to_wire(app.info.led_speed);
-                                        app.set_speed(SPEED_SPINNER.decrement_u8(current, coarse));
-                                    }
-                                    InfoTag::LedRed => { let r = RGB_SPINNER.decrement_u8(app.info.led_r, coarse); app.set_color(r, app.info.led_g, app.info.led_b); }
-                                    InfoTag::LedGreen => { let g = RGB_SPINNER.decrement_u8(app.info.led_g, coarse); app.set_color(app.info.led_r, g, app.info.led_b); }
-                                    InfoTag::LedBlue => { let b = RGB_SPINNER.decrement_u8(app.info.led_b, coarse); app.set_color(app.info.led_r, app.info.led_g, b); }
-                                    InfoTag::LedDazzle => { app.toggle_dazzle(); tabs::audio::reapply_if_active(&mut app); }
-                                    InfoTag::SideMode => app.set_side_mode(app.info.side_mode.saturating_sub(1)),
-                                    InfoTag::SideBrightness => app.set_side_brightness(BRIGHTNESS_SPINNER.decrement_u8(app.info.side_brightness, coarse)),
-                                    InfoTag::SideSpeed => {
-                                        let current = speed_to_wire(app.info.side_speed);
-                                        app.set_side_speed(SPEED_SPINNER.decrement_u8(current, coarse));
-                                    }
-                                    InfoTag::SideRed => { let r = RGB_SPINNER.decrement_u8(app.info.side_r, coarse); app.set_side_color(r, app.info.side_g, app.info.side_b); }
-                                    InfoTag::SideGreen => { let g = RGB_SPINNER.decrement_u8(app.info.side_g, coarse); app.set_side_color(app.info.side_r, g, app.info.side_b); }
-                                    InfoTag::SideBlue => { let b = RGB_SPINNER.decrement_u8(app.info.side_b, coarse); app.set_side_color(app.info.side_r, app.info.side_g, b); }
-                                    InfoTag::SideDazzle => app.toggle_side_dazzle(),
-                                    InfoTag::FnLayer => { if let Some(ref opts) = app.options.clone() { app.set_fn_layer(FN_LAYER_SPINNER.decrement_u8(opts.fn_layer, coarse)); } }
-                                    InfoTag::WasdSwap => app.toggle_wasd_swap(),
-                                    InfoTag::AntiMistouch => app.toggle_anti_mistouch(),
-                                    InfoTag::RtStability => { if let Some(ref opts) = app.options.clone() { app.set_rt_stability(RT_STABILITY_SPINNER.decrement_u8(opts.rt_stability, coarse)); } }
-                                    InfoTag::SleepIdleBt => { let step = if coarse { SLEEP_TIME_SPINNER.step_coarse } else { SLEEP_TIME_SPINNER.step } as i32; app.update_sleep_time(SleepField::IdleBt, -step); }
-                                    InfoTag::SleepIdle24g => { let step = if coarse { SLEEP_TIME_SPINNER.step_coarse } else { SLEEP_TIME_SPINNER.step } as i32; app.update_sleep_time(SleepField::Idle24g, -step); }
-                                    InfoTag::SleepDeepBt => { let step = if coarse { SLEEP_TIME_SPINNER.step_coarse } else { SLEEP_TIME_SPINNER.step } as i32; app.update_sleep_time(SleepField::DeepBt, -step); }
-                                    InfoTag::SleepDeep24g => { let step = if coarse { SLEEP_TIME_SPINNER.step_coarse } else { SLEEP_TIME_SPINNER.step } as i32; app.update_sleep_time(SleepField::Deep24g, -step); }
-                                    InfoTag::AudioDevice => tabs::audio::cycle_device(&mut app, -1),
-                                    InfoTag::AudioVizStyle => tabs::audio::cycle_style(&mut app, -1),
-                                    InfoTag::AudioRate => tabs::audio::cycle_rate(&mut app, -1),
-                                    InfoTag::ScreenRate => tabs::screen::cycle_rate(&mut app, -1),
-                                    InfoTag::ScreenRegion => tabs::screen::cycle_region(&mut app, -1),
-                                    InfoTag::ScreenTestSwatch => tabs::screen::cycle_test_swatch(&mut app, -1),
-                                    InfoTag::ScreenGainR => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::GainR, -1, coarse),
-                                    InfoTag::ScreenGainG => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::GainG, -1, coarse),
-                                    InfoTag::ScreenGainB => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::GainB, -1, coarse),
-                                    InfoTag::ScreenGammaR => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::GammaR, -1, coarse),
-                                    InfoTag::ScreenGammaG => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::GammaG, -1, coarse),
-                                    InfoTag::ScreenGammaB => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::GammaB, -1, coarse),
-                                    InfoTag::ScreenSaturation => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::Saturation, -1, coarse),
-                                    InfoTag::UserPicLayer => {
-                                        let n = (app.userpic_layer + 3) % 4;
-                                        app.set_userpic_layer(n);
-                                    }
-                                    _ => {}
-                                }
+                                tabs::device_info::adjust_info_row(&mut app, false, coarse);
                             }
                             #[cfg(feature = "notify")]
                             if app.tab == 3 {
@@ -1722,66 +1572,13 @@ pub async fn run(device_selector: Option<String>) -> io::Result<()> {
                             }
                         }
                         KeyCode::Right | KeyCode::Char('l') => {
-                            if app.tab == 2 && app.key_mapping_view == KeyMappingView::Layout {
+                            if app.tab == 2 && app.key_mapping_view.is_grid() {
                                 tabs::key_mapping::layout_move(&mut app, 1, 0);
                             } else if app.tab == 1 && app.depth_view_mode == DepthViewMode::BarChart {
-                                let max_key = app.key_depths.len().min(66).saturating_sub(1);
-                                if app.depth_cursor < max_key {
-                                    app.depth_cursor += 1;
-                                }
+                                app.move_depth_cursor(1, 0);
                             } else if app.tab == 0 {
                                 let coarse = key.modifiers.contains(KeyModifiers::SHIFT);
-                                match app.info_tags.get(app.selected).copied().unwrap_or(InfoTag::ReadOnly) {
-                                    InfoTag::Profile => app.set_profile(PROFILE_SPINNER.increment_u8(app.info.profile, coarse)),
-                                    InfoTag::Debounce => app.set_debounce(DEBOUNCE_SPINNER.increment_u8(app.info.debounce, coarse)),
-                                    InfoTag::PollingRate => app.cycle_polling_rate(-1), // lower index = higher rate
-                                    InfoTag::LedMode => app.set_led_mode((app.info.led_mode + 1).min(cmd::LED_MODE_MAX)),
-                                    InfoTag::LedBrightness => app.set_brightness(BRIGHTNESS_SPINNER.increment_u8(app.info.led_brightness, coarse)),
-                                    InfoTag::LedSpeed => {
-                                        let current = speed_to_wire(app.info.led_speed);
-                                        app.set_speed(SPEED_SPINNER.increment_u8(current, coarse));
-                                    }
-                                    InfoTag::LedRed => { let r = RGB_SPINNER.increment_u8(app.info.led_r, coarse); app.set_color(r, app.info.led_g, app.info.led_b); }
-                                    InfoTag::LedGreen => { let g = RGB_SPINNER.increment_u8(app.info.led_g, coarse); app.set_color(app.info.led_r, g, app.info.led_b); }
-                                    InfoTag::LedBlue => { let b = RGB_SPINNER.increment_u8(app.info.led_b, coarse); app.set_color(app.info.led_r, app.info.led_g, b); }
-                                    InfoTag::LedDazzle => { app.toggle_dazzle(); tabs::audio::reapply_if_active(&mut app); }
-                                    InfoTag::SideMode => app.set_side_mode((app.info.side_mode + 1).min(cmd::LED_MODE_MAX)),
-                                    InfoTag::SideBrightness => app.set_side_brightness(BRIGHTNESS_SPINNER.increment_u8(app.info.side_brightness, coarse)),
-                                    InfoTag::SideSpeed => {
-                                        let current = speed_to_wire(app.info.side_speed);
-                                        app.set_side_speed(SPEED_SPINNER.increment_u8(current, coarse));
-                                    }
-                                    InfoTag::SideRed => { let r = RGB_SPINNER.increment_u8(app.info.side_r, coarse); app.set_side_color(r, app.info.side_g, app.info.side_b); }
-                                    InfoTag::SideGreen => { let g = RGB_SPINNER.increment_u8(app.info.side_g, coarse); app.set_side_color(app.info.side_r, g, app.info.side_b); }
-                                    InfoTag::SideBlue => { let b = RGB_SPINNER.increment_u8(app.info.side_b, coarse); app.set_side_color(app.info.side_r, app.info.side_g, b); }
-                                    InfoTag::SideDazzle => app.toggle_side_dazzle(),
-                                    InfoTag::FnLayer => { if let Some(ref opts) = app.options.clone() { app.set_fn_layer(FN_LAYER_SPINNER.increment_u8(opts.fn_layer, coarse)); } }
-                                    InfoTag::WasdSwap => app.toggle_wasd_swap(),
-                                    InfoTag::AntiMistouch => app.toggle_anti_mistouch(),
-                                    InfoTag::RtStability => { if let Some(ref opts) = app.options.clone() { app.set_rt_stability(RT_STABILITY_SPINNER.increment_u8(opts.rt_stability, coarse)); } }
-                                    InfoTag::SleepIdleBt => { let step = if coarse { SLEEP_TIME_SPINNER.step_coarse } else { SLEEP_TIME_SPINNER.step } as i32; app.update_sleep_time(SleepField::IdleBt, step); }
-                                    InfoTag::SleepIdle24g => { let step = if coarse { SLEEP_TIME_SPINNER.step_coarse } else { SLEEP_TIME_SPINNER.step } as i32; app.update_sleep_time(SleepField::Idle24g, step); }
-                                    InfoTag::SleepDeepBt => { let step = if coarse { SLEEP_TIME_SPINNER.step_coarse } else { SLEEP_TIME_SPINNER.step } as i32; app.update_sleep_time(SleepField::DeepBt, step); }
-                                    InfoTag::SleepDeep24g => { let step = if coarse { SLEEP_TIME_SPINNER.step_coarse } else { SLEEP_TIME_SPINNER.step } as i32; app.update_sleep_time(SleepField::Deep24g, step); }
-                                    InfoTag::AudioDevice => tabs::audio::cycle_device(&mut app, 1),
-                                    InfoTag::AudioVizStyle => tabs::audio::cycle_style(&mut app, 1),
-                                    InfoTag::AudioRate => tabs::audio::cycle_rate(&mut app, 1),
-                                    InfoTag::ScreenRate => tabs::screen::cycle_rate(&mut app, 1),
-                                    InfoTag::ScreenRegion => tabs::screen::cycle_region(&mut app, 1),
-                                    InfoTag::ScreenTestSwatch => tabs::screen::cycle_test_swatch(&mut app, 1),
-                                    InfoTag::ScreenGainR => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::GainR, 1, coarse),
-                                    InfoTag::ScreenGainG => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::GainG, 1, coarse),
-                                    InfoTag::ScreenGainB => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::GainB, 1, coarse),
-                                    InfoTag::ScreenGammaR => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::GammaR, 1, coarse),
-                                    InfoTag::ScreenGammaG => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::GammaG, 1, coarse),
-                                    InfoTag::ScreenGammaB => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::GammaB, 1, coarse),
-                                    InfoTag::ScreenSaturation => tabs::screen::adjust_calibration(&mut app, tabs::screen::CalField::Saturation, 1, coarse),
-                                    InfoTag::UserPicLayer => {
-                                        let n = (app.userpic_layer + 1) % 4;
-                                        app.set_userpic_layer(n);
-                                    }
-                                    _ => {}
-                                }
+                                tabs::device_info::adjust_info_row(&mut app, true, coarse);
                             }
                             #[cfg(feature = "notify")]
                             if app.tab == 3 {
@@ -1876,6 +1673,11 @@ pub async fn run(device_selector: Option<String>) -> io::Result<()> {
                             app.scan_device_picker();
                             app.show_device_picker = true;
                         }
+                        KeyCode::Char('P') => app.open_profile_manager(),
+                        KeyCode::Char('B') => app.open_battery_graph(),
+                        KeyCode::Char('F') => app.open_firmware_update(),
+                        KeyCode::Char('<') => app.switch_keyboard(false),
+                        KeyCode::Char('>') => app.switch_keyboard(true),
                         KeyCode::Char('1') if key.modifiers.contains(KeyModifiers::CONTROL) => app.set_profile(0),
                         KeyCode::Char('2') if key.modifiers.contains(KeyModifiers::CONTROL) => app.set_profile(1),
                         KeyCode::Char('3') if key.modifiers.contains(KeyModifiers::CONTROL) => app.set_profile(2),
@@ -1896,10 +1698,7 @@ pub async fn run(device_selector: Option<String>) -> io::Result<()> {
                         KeyCode::Char('p') if app.tab == 0 => app.apply_per_key_color(),
                         KeyCode::Char('v') if app.tab == 1 => app.toggle_depth_view(),
                         KeyCode::Char('v') if app.tab == 2 => {
-                            app.key_mapping_view = match app.key_mapping_view {
-                                KeyMappingView::List => KeyMappingView::Layout,
-                                KeyMappingView::Layout => KeyMappingView::List,
-                            };
+                            app.key_mapping_view = app.key_mapping_view.cycle();
                         }
                         KeyCode::Char('s') if app.tab == 2 => {
                             app.key_mapping_sort = app.key_mapping_sort.cycle();
@@ -1913,6 +1712,17 @@ pub async fn run(device_selector: Option<String>) -> io::Result<()> {
                             }
                         }
                         KeyCode::Char('g') if app.tab == 2 => app.open_trigger_edit_global(),
+                        // Trigger grid: select keys, copy/paste values, step the focused field.
+                        KeyCode::Char(' ') if in_trigger_grid => tabs::trigger_grid::toggle_selected(&mut app),
+                        KeyCode::Char('a') if in_trigger_grid => tabs::trigger_grid::select_all(&mut app),
+                        KeyCode::Char('y') if in_trigger_grid => tabs::trigger_grid::copy(&mut app),
+                        KeyCode::Char('p') if in_trigger_grid => tabs::trigger_grid::paste(&mut app),
+                        KeyCode::Char('+') | KeyCode::Char('=') if in_trigger_grid => {
+                            tabs::trigger_grid::adjust(&mut app, true, false);
+                        }
+                        KeyCode::Char('-') if in_trigger_grid => tabs::trigger_grid::adjust(&mut app, false, false),
+                        KeyCode::Char(']') if in_trigger_grid => tabs::trigger_grid::adjust(&mut app, true, true),
+                        KeyCode::Char('[') if in_trigger_grid => tabs::trigger_grid::adjust(&mut app, false, true),
                         KeyCode::Char('x') if app.tab == 1 => app.clear_depth_data(),
                         KeyCode::Char(' ') if app.tab == 1 => {
                             if app.depth_view_mode == DepthViewMode::BarChart {
@@ -1933,68 +1743,7 @@ pub async fn run(device_selector: Option<String>) -> io::Result<()> {
                         _ => {}
                     }
                 } else if let Some(Ok(Event::Mouse(mouse))) = maybe_event {
-                    // Handle mouse events
-                    let pos = Position::new(mouse.column, mouse.row);
-                    let tab_bar = app.tab_bar_area.get();
-                    let content = app.content_area.get();
-
-                    match mouse.kind {
-                    MouseEventKind::Down(MouseButton::Left) => {
-                        // Check if click is in tab bar area
-                        if tab_bar.contains(pos) {
-                            // Calculate which tab was clicked
-                            // Tabs render with border (1 char), then " Tab1 │ Tab2 │ ..."
-                            let tab_names = app.tab_names();
-                            let inner_x = mouse.column.saturating_sub(tab_bar.x + 1); // Account for border
-                            let mut tab_pos = 1u16; // Initial padding
-                            for (i, name) in tab_names.iter().enumerate() {
-                                let tab_width = name.len() as u16;
-                                if inner_x >= tab_pos && inner_x < tab_pos + tab_width {
-                                    let old_tab = app.tab;
-                                    app.tab = i;
-                                    app.selected = 0;
-                                    app.trigger_scroll = 0;
-                                    app.scroll_state = ScrollViewState::new();
-                                    app.auto_load_tab();
-                                    if old_tab != app.tab {
-                                        app.status_msg = format!("Switched to tab {}", i);
-                                    }
-                                    break;
-                                }
-                                tab_pos += tab_width + 3; // Tab width + " │ " separator
-                            }
-                        }
-
-                        // Check if click is in content area
-                        if content.contains(pos) {
-                            // Row within content area (accounting for any border)
-                            let content_row = (mouse.row.saturating_sub(content.y + 1)) as usize;
-                            match app.tab {
-                                0 => {
-                                    // Device Info - items in the list
-                                    if content_row < app.info_tags.len() {
-                                        app.selected = content_row;
-                                    }
-                                }
-                                3 => {
-                                    // Remaps tab - select remap entry
-                                    let filtered = app.filtered_remaps();
-                                    if content_row < filtered.len() {
-                                        app.remap_selected = content_row;
-                                    }
-                                }
-                                _ => {}
-                            }
-                        }
-                    }
-                    MouseEventKind::ScrollUp if content.contains(pos) => {
-                        app.scroll_state.scroll_up();
-                    }
-                    MouseEventKind::ScrollDown if content.contains(pos) => {
-                        app.scroll_state.scroll_down();
-                    }
-                    _ => {}
-                    }
+                    mouse::handle_mouse(&mut app, mouse);
                 } else if let Some(Ok(Event::Resize(_, _))) = maybe_event {
                     // Resize is handled automatically by ratatui on next draw
                 }
@@ -2107,6 +1856,14 @@ pub async fn run(device_selector: Option<String>) -> io::Result<()> {
                 // Poll animation engine status (on notify tab, or when overlay active)
                 app.poll_anim_status();
 
+                // Rehearsed firmware transfer progress
+                app.firmware_update_tick();
+
+                // Keep the header strip current for the other open boards
+                if app.keyboards.len() > 1 && app.last_board_poll.elapsed() >= Duration::from_secs(10) {
+                    app.poll_keyboards();
+                }
+
                 // Refresh battery every 30 seconds for wireless devices
                 if app.is_wireless && app.last_battery_check.elapsed() >= Duration::from_secs(30) {
                     let was_idle = app.battery.as_ref().map(|b| b.idle).unwrap_or(false);
@@ -2177,7 +1934,11 @@ fn ui(f: &mut Frame, app: &mut App) {
                 .add_modifier(Modifier::BOLD),
         )
         .alignment(Alignment::Center);
-    f.render_widget(title, chunks[0]);
+    if app.keyboards.len() > 1 {
+        keyboards::render_board_strip(f, app, chunks[0]);
+    } else {
+        f.render_widget(title, chunks[0]);
+    }
 
     // Tabs, with a right-aligned navigation hint.
     let tab_hint = format!(" Alt+1-{}: switch tabs ", app.tab_count());
@@ -2208,6 +1969,7 @@ fn ui(f: &mut Frame, app: &mut App) {
     // Store areas for mouse hit testing (using interior mutability)
     app.tab_bar_area.set(tab_row[0]);
     app.content_area.set(chunks[2]);
+    app.key_grid_area.set(None);
 
     // Content based on tab
     match app.tab {
@@ -2296,6 +2058,17 @@ fn ui(f: &mut Frame, app: &mut App) {
         render_device_picker(f, app, f.area());
     }
 
+    // Profile manager popup (renders on top)
+    if app.profile_manager.is_some() {
+        render_profile_manager(f, app, f.area());
+    }
+    if app.battery_graph.is_some() {
+        render_battery_graph(f, app, f.area());
+    }
+    if app.firmware_update.is_some() {
+        render_firmware_update(f, app, f.area());
+    }
+
     // Trigger edit modal (renders on top)
     if app.trigger_edit_modal.is_some() {
         render_trigger_edit_modal(f, app, f.area());
@@ -0,0 +1,202 @@
+// Mouse input — clicks, drags and the wheel.
+//
+// Clicks switch tabs, pick list rows and put the cursor on a key in the layout
+// and trigger grid views. Horizontal drags act as sliders: each column moved
+// is one step on the selected Info row or the trigger edit modal's field (the
+// same steps as ←/→). In the trigger grid a drag paints keys into the
+// selection. The wheel adjusts whatever a drag would, or scrolls otherwise.
+
+use crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::Position;
+use tui_scrollview::ScrollViewState;
+
+use super::tabs::device_info::{adjust_info_row, InfoTag};
+use super::tabs::{key_mapping, trigger_grid};
+use super::App;
+
+/// Most steps one drag event applies, so a fast flick does not flood the
+/// keyboard with commands.
+const MAX_DRAG_STEPS: u16 = 8;
+
+impl App {
+    /// Whether the selected Info row has a value the wheel and drags can step.
+    fn info_row_adjustable(&self) -> bool {
+        !matches!(
+            self.info_tags.get(self.selected).copied(),
+            None | Some(
+                InfoTag::ReadOnly
+                    | InfoTag::Separator
+                    | InfoTag::Device
+                    | InfoTag::FirmwareCheck
+                    | InfoTag::LedColorHex
+                    | InfoTag::SideColorHex
+            )
+        )
+    }
+
+    /// Step the slider under the pointer: the trigger modal's field, else the
+    /// selected Info row. Returns false when there is nothing to step.
+    fn step_slider(&mut self, forward: bool, coarse: bool) -> bool {
+        if let Some(modal) = self.trigger_edit_modal.as_mut() {
+            if forward {
+                modal.increment_current(coarse);
+            } else {
+                modal.decrement_current(coarse);
+            }
+            return true;
+        }
+        if self.tab == 0 && self.info_row_adjustable() {
+            adjust_info_row(self, forward, coarse);
+            return true;
+        }
+        false
+    }
+
+    fn click_tab_bar(&mut self, column: u16) {
+        // Tabs render with border (1 char), then " Tab1 │ Tab2 │ ..."
+        let tab_bar = self.tab_bar_area.get();
+        let inner_x = column.saturating_sub(tab_bar.x + 1);
+        let mut tab_pos = 1u16; // Initial padding
+        for (i, name) in self.tab_names().iter().enumerate() {
+            let tab_width = name.len() as u16;
+            if inner_x >= tab_pos && inner_x < tab_pos + tab_width {
+                let old_tab = self.tab;
+                self.tab = i;
+                self.selected = 0;
+                self.trigger_scroll = 0;
+                self.scroll_state = ScrollViewState::new();
+                self.auto_load_tab();
+                if old_tab != self.tab {
+                    self.status_msg = format!("Switched to tab {i}");
+                }
+                break;
+            }
+            tab_pos += tab_width + 3; // Tab width + " │ " separator
+        }
+    }
+
+    /// List row under the pointer (content area has a one-line border).
+    fn content_row(&self, row: u16) -> usize {
+        row.saturating_sub(self.content_area.get().y + 1) as usize
+    }
+
+    /// Matrix key under the pointer in a layout or trigger grid view.
+    fn key_under(&self, pos: Position) -> Option<u8> {
+        self.key_grid_area.get().and_then(|g| g.key_at(pos))
+    }
+
+    fn click_key(&mut self, index: u8, toggle: bool) {
+        if !key_mapping::select_key(self, index) {
+            return;
+        }
+        if toggle && self.key_mapping_view == super::KeyMappingView::Triggers {
+            trigger_grid::toggle_selected(self);
+        }
+    }
+}
+
+pub(super) fn handle_mouse(app: &mut App, mouse: MouseEvent) {
+    let pos = Position::new(mouse.column, mouse.row);
+    let content = app.content_area.get();
+    let coarse = mouse.modifiers.contains(KeyModifiers::SHIFT);
+    let in_grid = app.tab == 2 && app.key_mapping_view.is_grid();
+
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            app.drag_column = Some(mouse.column);
+            if app.trigger_edit_modal.is_some() {
+                return;
+            }
+            if app.tab_bar_area.get().contains(pos) {
+                app.click_tab_bar(mouse.column);
+            }
+            if !content.contains(pos) {
+                return;
+            }
+            if let Some(key) = app.key_under(pos) {
+                let toggle = mouse.modifiers.contains(KeyModifiers::CONTROL);
+                app.click_key(key, toggle);
+                return;
+            }
+            let content_row = app.content_row(mouse.row);
+            match app.tab {
+                0 => {
+                    // Device Info - items in the list
+                    if content_row < app.info_tags.len() {
+                        app.selected = content_row;
+                    }
+                }
+                3 => {
+                    // Remaps tab - select remap entry
+                    let filtered = app.filtered_remaps();
+                    if content_row < filtered.len() {
+                        app.remap_selected = content_row;
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Right click toggles a key in the trigger grid selection.
+        MouseEventKind::Down(MouseButton::Right) if in_grid => {
+            if let Some(key) = app.key_under(pos) {
+                app.click_key(key, true);
+            }
+        }
+        MouseEventKind::Drag(MouseButton::Left) => {
+            let last = app.drag_column.replace(mouse.column);
+            if in_grid && app.trigger_edit_modal.is_none() {
+                // Paint keys into the trigger grid selection.
+                if let Some(key) = app.key_under(pos) {
+                    if app.key_mapping_view == super::KeyMappingView::Triggers
+                        && key_mapping::select_key(app, key)
+                    {
+                        app.trigger_grid.selection.insert(key);
+                    }
+                }
+                return;
+            }
+            let Some(last) = last else {
+                return;
+            };
+            let forward = mouse.column > last;
+            let steps = mouse.column.abs_diff(last).min(MAX_DRAG_STEPS);
+            for _ in 0..steps {
+                if !app.step_slider(forward, coarse) {
+                    break;
+                }
+            }
+        }
+        MouseEventKind::Up(MouseButton::Left) => app.drag_column = None,
+        MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+            let forward = mouse.kind == MouseEventKind::ScrollUp;
+            if app.trigger_edit_modal.is_some() {
+                app.step_slider(forward, coarse);
+                return;
+            }
+            if !content.contains(pos) {
+                return;
+            }
+            if in_grid && app.key_mapping_view == super::KeyMappingView::Triggers {
+                if let Some(key) = app.key_under(pos) {
+                    // Same as +/-: the selection if any, else the key under the wheel.
+                    key_mapping::select_key(app, key);
+                    trigger_grid::adjust(app, forward, coarse);
+                    return;
+                }
+            }
+            // Wheel over the selected Info row steps its value.
+            if app.tab == 0
+                && app.content_row(mouse.row) == app.selected
+                && app.step_slider(forward, coarse)
+            {
+                return;
+            }
+            if forward {
+                app.scroll_state.scroll_up();
+            } else {
+                app.scroll_state.scroll_down();
+            }
+        }
+        _ => {}
+    }
+}
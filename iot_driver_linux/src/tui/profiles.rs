@@ -0,0 +1,492 @@
+// Profile Manager — the four onboard profiles side by side.
+//
+// Opened with `P`. Reads every profile through the same snapshot as
+// `iot_driver profile backup` (switching to each and back), so export writes a
+// file `profile restore` accepts and copy/import converge through the
+// `config apply` plan. Names are local metadata kept in `settings.toml`.
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::keyboard_config::{
+    read_backup, restore_calibration, DeviceState, KeyboardConfig, ProfileBackup, TriggersSection,
+    PROFILE_COUNT,
+};
+use crate::settings::Settings;
+use monsgeek_keyboard::KeyboardInterface;
+
+use super::shared::{AsyncResult, LoadState};
+use super::App;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Default file for export and import, relative to the working directory.
+const DEFAULT_FILE: &str = "monsgeek-profiles.toml";
+
+/// What an open text prompt is for.
+#[derive(Clone, Copy, PartialEq)]
+enum Prompt {
+    Rename,
+    Export,
+    Import,
+}
+
+impl Prompt {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Rename => "Name",
+            Self::Export => "Export to",
+            Self::Import => "Import from",
+        }
+    }
+}
+
+pub(super) struct ProfileManager {
+    selected: u8,
+    /// Device name the local profile names are stored under.
+    device: String,
+    names: Vec<String>,
+    snapshot: Option<ProfileBackup>,
+    /// Snapshot read, or a copy/import in progress.
+    state: LoadState,
+    /// Source profile while picking a copy target.
+    copy_from: Option<u8>,
+    prompt: Option<(Prompt, String)>,
+}
+
+impl ProfileManager {
+    fn config(&self, profile: u8) -> Option<&KeyboardConfig> {
+        self.snapshot
+            .as_ref()?
+            .profiles
+            .iter()
+            .find(|c| c.profile == Some(profile))
+    }
+
+    fn name(&self, profile: u8) -> &str {
+        self.names.get(profile as usize).map_or("", String::as_str)
+    }
+
+    fn busy(&self) -> bool {
+        self.state == LoadState::Loading
+    }
+}
+
+/// Read all profiles, as `profile backup` does.
+fn read_snapshot(kb: &KeyboardInterface) -> Result<ProfileBackup, String> {
+    read_backup(kb, |_| {}).map_err(|e| e.to_string())
+}
+
+/// Converge `profile` to `config`; returns the number of changes written.
+fn converge(
+    kb: &KeyboardInterface,
+    config: &KeyboardConfig,
+    profile: u8,
+) -> Result<usize, BoxError> {
+    kb.with_profile(profile, |kb| {
+        let state = DeviceState::read(kb, config)?;
+        let plan = config.plan(&state)?;
+        plan.apply(kb, profile)?;
+        Ok(plan.changes.len())
+    })
+}
+
+impl App {
+    pub(super) fn open_profile_manager(&mut self) {
+        let Some(keyboard) = self.keyboard.as_ref() else {
+            self.status_msg = "No keyboard connected".to_string();
+            return;
+        };
+        let device = keyboard.device_name();
+        let settings = Settings::load();
+        let names = (0..PROFILE_COUNT)
+            .map(|p| settings.profile_name(&device, p).unwrap_or("").to_string())
+            .collect();
+        self.profile_manager = Some(ProfileManager {
+            selected: self.info.profile.min(PROFILE_COUNT - 1),
+            device,
+            names,
+            snapshot: None,
+            state: LoadState::NotLoaded,
+            copy_from: None,
+            prompt: None,
+        });
+        self.load_profile_snapshot();
+    }
+
+    /// Read every profile in the background; the keyboard briefly switches
+    /// through them and returns to the active one.
+    fn load_profile_snapshot(&mut self) {
+        let (Some(keyboard), Some(pm)) = (self.keyboard.clone(), self.profile_manager.as_mut())
+        else {
+            return;
+        };
+        pm.state = LoadState::Loading;
+        self.status_msg = "Reading profiles...".to_string();
+        let tx = self.gen_sender();
+        tokio::spawn(async move {
+            tx.send(AsyncResult::Profiles(read_snapshot(&keyboard), None));
+        });
+    }
+
+    /// Take a fresh snapshot; `outcome` reports the write that preceded it.
+    pub(super) fn on_profiles_loaded(
+        &mut self,
+        result: Result<ProfileBackup, String>,
+        outcome: Option<String>,
+    ) {
+        let Some(pm) = self.profile_manager.as_mut() else {
+            return;
+        };
+        match result {
+            Ok(snapshot) => {
+                pm.snapshot = Some(snapshot);
+                pm.state = LoadState::Loaded;
+                self.status_msg = outcome.unwrap_or_else(|| "Profiles loaded".to_string());
+            }
+            Err(e) => {
+                pm.state = LoadState::Error;
+                self.status_msg = match outcome {
+                    Some(o) => format!("{o}; re-reading profiles failed: {e}"),
+                    None => format!("Failed to read profiles: {e}"),
+                };
+            }
+        }
+    }
+
+    /// Write `configs` (each to its own `profile`) and a backup's
+    /// `calibration` (if any), then re-read the snapshot.
+    fn converge_profiles(
+        &mut self,
+        configs: Vec<KeyboardConfig>,
+        calibration: Vec<u16>,
+        what: String,
+    ) {
+        let (Some(keyboard), Some(pm)) = (self.keyboard.clone(), self.profile_manager.as_mut())
+        else {
+            return;
+        };
+        pm.state = LoadState::Loading;
+        self.status_msg = format!("{what}...");
+        let tx = self.gen_sender();
+        tokio::spawn(async move {
+            let mut changes = 0;
+            let mut outcome = None;
+            for config in &configs {
+                let profile = config.profile.unwrap_or_default();
+                match converge(&keyboard, config, profile) {
+                    Ok(n) => changes += n,
+                    Err(e) => {
+                        outcome = Some(format!("{what} failed on profile {}: {e}", profile + 1));
+                        break;
+                    }
+                }
+            }
+            if outcome.is_none() {
+                match restore_calibration(&keyboard, &calibration, false) {
+                    Ok(0) => {}
+                    Ok(_) => changes += 1,
+                    Err(e) => outcome = Some(format!("{what} failed on calibration: {e}")),
+                }
+            }
+            let outcome = outcome.unwrap_or_else(|| format!("{what}: {changes} change(s)"));
+            tx.send(AsyncResult::Profiles(
+                read_snapshot(&keyboard),
+                Some(outcome),
+            ));
+        });
+    }
+
+    fn copy_profile(&mut self, from: u8, to: u8) {
+        let Some(pm) = self.profile_manager.as_ref() else {
+            return;
+        };
+        let Some(mut config) = pm.config(from).cloned() else {
+            self.status_msg = "Profiles not loaded yet".to_string();
+            return;
+        };
+        config.profile = Some(to);
+        self.converge_profiles(
+            vec![config],
+            Vec::new(),
+            format!("Copy profile {} to {}", from + 1, to + 1),
+        );
+    }
+
+    fn export_profiles(&mut self, path: &str) {
+        let Some(pm) = self.profile_manager.as_ref() else {
+            return;
+        };
+        let Some(snapshot) = pm.snapshot.as_ref() else {
+            self.status_msg = "Profiles not loaded yet".to_string();
+            return;
+        };
+        self.status_msg = match std::fs::write(path, snapshot.to_toml()) {
+            Ok(()) => format!("Exported {} profiles to {path}", snapshot.profiles.len()),
+            Err(e) => format!("Failed to write {path}: {e}"),
+        };
+    }
+
+    fn import_profiles(&mut self, path: &str) {
+        let backup = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| ProfileBackup::from_toml(&text).map_err(|e| e.to_string()));
+        match backup {
+            Ok(backup) => {
+                let n = backup.profiles.len();
+                self.converge_profiles(
+                    backup.profiles,
+                    backup.calibration,
+                    format!("Import of {n} profile(s)"),
+                );
+            }
+            Err(e) => self.status_msg = format!("{path}: {e}"),
+        }
+    }
+
+    fn rename_profile(&mut self, profile: u8, name: &str) {
+        let Some(pm) = self.profile_manager.as_mut() else {
+            return;
+        };
+        let mut settings = Settings::load();
+        settings.set_profile_name(&pm.device, profile, name);
+        if let Err(e) = settings.save() {
+            self.status_msg = format!("Failed to save name: {e}");
+            return;
+        }
+        if let Some(slot) = pm.names.get_mut(profile as usize) {
+            *slot = name.trim().to_string();
+        }
+        self.status_msg = format!("Profile {} renamed", profile + 1);
+    }
+}
+
+/// Key handling while the profile manager is open.
+pub(super) fn handle_key(app: &mut App, key: KeyEvent) {
+    let Some(pm) = app.profile_manager.as_mut() else {
+        return;
+    };
+
+    if let Some((prompt, input)) = pm.prompt.as_mut() {
+        match key.code {
+            KeyCode::Esc => pm.prompt = None,
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Enter => {
+                let (prompt, input) = (*prompt, input.clone());
+                let profile = pm.selected;
+                pm.prompt = None;
+                match prompt {
+                    Prompt::Rename => app.rename_profile(profile, &input),
+                    Prompt::Export => app.export_profiles(input.trim()),
+                    Prompt::Import => app.import_profiles(input.trim()),
+                }
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    match key.code {
+        KeyCode::Esc if pm.copy_from.is_some() => {
+            pm.copy_from = None;
+            app.status_msg = "Copy cancelled".to_string();
+        }
+        KeyCode::Esc | KeyCode::Char('P') => app.profile_manager = None,
+        KeyCode::Up | KeyCode::Char('k') => pm.selected = pm.selected.saturating_sub(1),
+        KeyCode::Down | KeyCode::Char('j') => {
+            pm.selected = (pm.selected + 1).min(PROFILE_COUNT - 1);
+        }
+        KeyCode::Enter => match pm.copy_from.take() {
+            Some(from) if from == pm.selected => {
+                app.status_msg = "Pick a different profile to copy to".to_string();
+            }
+            Some(from) => {
+                let to = pm.selected;
+                app.copy_profile(from, to);
+            }
+            None => {
+                let profile = pm.selected;
+                app.set_profile(profile);
+            }
+        },
+        KeyCode::Char('n') => {
+            let name = pm.name(pm.selected).to_string();
+            pm.prompt = Some((Prompt::Rename, name));
+        }
+        KeyCode::Char('c') if !pm.busy() && pm.snapshot.is_some() => {
+            pm.copy_from = Some(pm.selected);
+            app.status_msg = format!(
+                "Copy profile {}: pick the target and press Enter (Esc cancels)",
+                pm.selected + 1
+            );
+        }
+        KeyCode::Char('e') if pm.snapshot.is_some() => {
+            pm.prompt = Some((Prompt::Export, DEFAULT_FILE.to_string()));
+        }
+        KeyCode::Char('i') if !pm.busy() => {
+            pm.prompt = Some((Prompt::Import, DEFAULT_FILE.to_string()));
+        }
+        KeyCode::Char('r') if !pm.busy() => app.load_profile_snapshot(),
+        _ => {}
+    }
+}
+
+/// A comparison table column: header and the value for one profile, given
+/// its triggers as bulk values.
+type Column = (
+    &'static str,
+    fn(&KeyboardConfig, &TriggersSection) -> String,
+);
+
+const COLUMNS: [Column; 7] = [
+    ("LED", |c, _| {
+        c.led.mode.clone().unwrap_or_else(|| "-".into())
+    }),
+    ("Bright", |c, _| opt(c.led.brightness)),
+    ("Color", |c, _| match (c.led.dazzle, &c.led.color) {
+        (Some(true), _) => "dazzle".into(),
+        (_, Some(color)) => color.clone(),
+        _ => "-".into(),
+    }),
+    ("Act/Rel mm", |_, t| {
+        let mm = |v: Option<f64>| v.map_or("mixed".into(), |v| format!("{v:.2}"));
+        format!("{}/{}", mm(t.actuation_mm), mm(t.release_mm))
+    }),
+    ("RT", |_, t| match t.rapid_trigger {
+        Some(true) => t.rt_press_mm.map_or("on".into(), |v| format!("on {v:.2}")),
+        Some(false) => "off".into(),
+        None => "mixed".into(),
+    }),
+    ("Remaps", |c, _| c.remap.len().to_string()),
+    ("Macros", |c, _| c.macros.len().to_string()),
+];
+
+fn opt(v: Option<u8>) -> String {
+    v.map_or("-".into(), |v| v.to_string())
+}
+
+/// Centered popup with one row per profile. Values that are not the same on
+/// all four profiles are highlighted, so what sets them apart stands out.
+pub(super) fn render_profile_manager(f: &mut Frame, app: &App, area: Rect) {
+    let Some(pm) = app.profile_manager.as_ref() else {
+        return;
+    };
+    let w = 100.min(area.width.saturating_sub(4));
+    let h = 14.min(area.height.saturating_sub(2));
+    let popup = Rect::new(
+        area.x + (area.width.saturating_sub(w)) / 2,
+        area.y + (area.height.saturating_sub(h)) / 2,
+        w,
+        h,
+    );
+    f.render_widget(Clear, popup);
+
+    let block = Block::default()
+        .title(" Profiles ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(popup);
+    f.render_widget(block, popup);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(6), Constraint::Length(2)])
+        .split(inner);
+
+    let cells: Vec<Option<Vec<String>>> = (0..PROFILE_COUNT)
+        .map(|p| {
+            pm.config(p).map(|c| {
+                let triggers = c
+                    .trigger_table
+                    .as_ref()
+                    .map_or_else(|| c.triggers.clone(), |t| t.to_bulk(app.precision));
+                COLUMNS.iter().map(|(_, get)| get(c, &triggers)).collect()
+            })
+        })
+        .collect();
+    let differs = |col: usize| {
+        let mut values = cells.iter().flatten().map(|row| &row[col]);
+        let first = values.next();
+        values.any(|v| Some(v) != first)
+    };
+
+    let header = Row::new(
+        ["", "Profile"]
+            .into_iter()
+            .chain(COLUMNS.iter().map(|(h, _)| *h)),
+    )
+    .style(Style::default().fg(Color::DarkGray));
+    let rows = (0..PROFILE_COUNT).map(|p| {
+        let marker = match (pm.copy_from, p == app.info.profile) {
+            (Some(src), _) if src == p => "⇒",
+            (_, true) => "▶",
+            _ => " ",
+        };
+        let name = match pm.name(p) {
+            "" => format!("{}", p + 1),
+            n => format!("{} {n}", p + 1),
+        };
+        let mut row = vec![Cell::from(marker), Cell::from(name)];
+        match &cells[p as usize] {
+            Some(values) => row.extend(values.iter().enumerate().map(|(col, v)| {
+                let style = if differs(col) {
+                    Style::default().fg(Color::Yellow)
+                } else {
+                    Style::default()
+                };
+                Cell::from(v.clone()).style(style)
+            })),
+            None => row.push(Cell::from(match pm.state {
+                LoadState::Error => "(read failed)",
+                _ => "...",
+            })),
+        }
+        let style = if p == pm.selected {
+            Style::default().bg(Color::Blue).fg(Color::White)
+        } else {
+            Style::default()
+        };
+        Row::new(row).style(style)
+    });
+    let widths = [
+        Constraint::Length(2),
+        Constraint::Length(18),
+        Constraint::Length(16),
+        Constraint::Length(6),
+        Constraint::Length(8),
+        Constraint::Length(11),
+        Constraint::Length(8),
+        Constraint::Length(7),
+        Constraint::Length(7),
+    ];
+    f.render_widget(Table::new(rows, widths).header(header), chunks[0]);
+
+    let footer = match &pm.prompt {
+        Some((prompt, input)) => Line::from(vec![
+            Span::styled(format!("{}: ", prompt.label()), Style::default().fg(Color::Cyan)),
+            Span::raw(format!("{input}_")),
+            Span::styled("   Enter ok  Esc cancel", Style::default().fg(Color::DarkGray)),
+        ]),
+        None if pm.copy_from.is_some() => Line::from(Span::styled(
+            "↑↓ pick target   Enter copy here   Esc cancel",
+            Style::default().fg(Color::Yellow),
+        )),
+        None => Line::from(Span::styled(
+            "↑↓ select  Enter switch  n rename  c copy to…  e export  i import  r reload  Esc close",
+            Style::default().fg(Color::DarkGray),
+        )),
+    };
+    let busy = if pm.busy() { "  working..." } else { "" };
+    f.render_widget(
+        Paragraph::new(vec![
+            Line::from(Span::styled(
+                format!("Names are stored locally; export/import use `profile backup` files{busy}"),
+                Style::default().fg(Color::DarkGray),
+            )),
+            footer,
+        ]),
+        chunks[1],
+    );
+}
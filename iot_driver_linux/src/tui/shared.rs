@@ -100,6 +100,7 @@ pub(crate) enum DepthViewMode {
     #[default]
     BarChart, // Bar chart of all active keys
     TimeSeries, // Time series graph of selected keys
+    Heatmap,    // Physical layout colored by depth, with peak hold
 }
 
 /// Configuration for a spinner (numeric value with left/right adjustment)
@@ -302,12 +303,24 @@ pub(crate) enum AsyncResult {
     PatchInfo(Result<PatchInfoData, String>),
     DonglePatchInfo(Result<PatchInfoData, String>),
     FirmwareCheck(FirmwareCheckResult),
+    /// Downloaded firmware, loaded for the update screen
+    FirmwareFile(Result<crate::firmware::FirmwareFile, String>),
+    /// Settings backup taken from the update screen, with the file written
+    SettingsBackup(Result<std::path::PathBuf, String>),
     // Other tab results
     Triggers(Result<TriggerSettings, String>),
     Options(Result<KbOptions, String>),
     Remaps(Result<Vec<KeyEntry>, String>),
     KeyRows(Result<Vec<KeyRow>, String>),
     Macros(Result<Vec<MacroSlot>, String>),
+    /// Header poll of a background board, by HID path
+    BoardStatus(String, super::keyboards::BoardStatus),
+    /// Profile manager snapshot of all onboard profiles, with the outcome of
+    /// the copy/import that preceded it
+    Profiles(
+        Result<crate::keyboard_config::ProfileBackup, String>,
+        Option<String>,
+    ),
     // Battery status (from keyboard API)
     Battery(Result<BatteryInfo, String>),
     // Operation completion (for set operations)
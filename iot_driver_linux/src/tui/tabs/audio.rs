@@ -18,7 +18,7 @@ use ratatui::Frame;
 
 use super::super::App;
 use crate::audio_reactive::{run_viz_loop, AudioCapture, AudioConfig};
-use crate::pulse::{self, SourceEntry};
+use crate::pipewire_audio::{self, SourceEntry};
 
 const MUSIC_BARS: u8 = 22;
 const MUSIC_PATTERNS: u8 = 20;
@@ -118,7 +118,7 @@ pub(in crate::tui) fn ensure_sources_loaded(app: &mut App) {
     if app.audio.sources.is_some() {
         return;
     }
-    match pulse::list_sources() {
+    match pipewire_audio::list_sources() {
         Ok(list) => {
             app.audio.selected = list
                 .iter()
@@ -250,6 +250,8 @@ fn start(app: &mut App, led_mode: u8) {
         sensitivity: 1.0,
         update_hz: app.audio.update_hz,
         device: Some(source.name.clone()),
+        app: None,
+        spectrum_bands: 0,
     };
 
     let capture = match AudioCapture::start(config.clone()) {
@@ -5,11 +5,17 @@
 use ratatui::{prelude::*, widgets::*};
 use std::time::{Duration, Instant};
 
-use monsgeek_keyboard::{led::speed_from_wire, VendorEvent};
+use monsgeek_keyboard::{led::speed_from_wire, RgbColor, VendorEvent};
 
 use super::super::shared::{DepthViewMode, DEPTH_HISTORY_LEN};
 use super::super::App;
 
+/// How long the heatmap keeps a key's peak depth marker.
+const PEAK_HOLD: Duration = Duration::from_millis(1500);
+
+/// Depth (mm) at the hot end of the heatmap color scale.
+const HEATMAP_FULL_SCALE_MM: f32 = 4.0;
+
 // ============================================================================
 // Rendering
 // ============================================================================
@@ -28,6 +34,7 @@ pub(in crate::tui) fn render_depth_monitor(f: &mut Frame, app: &mut App, area: R
     let mode_str = match app.depth_view_mode {
         DepthViewMode::BarChart => "Bar Chart",
         DepthViewMode::TimeSeries => "Time Series",
+        DepthViewMode::Heatmap => "Heatmap",
     };
     let status_text = if app.depth_monitoring {
         vec![
@@ -73,13 +80,15 @@ pub(in crate::tui) fn render_depth_monitor(f: &mut Frame, app: &mut App, area: R
     match app.depth_view_mode {
         DepthViewMode::BarChart => render_depth_bar_chart(f, app, inner[1]),
         DepthViewMode::TimeSeries => render_depth_time_series(f, app, inner[1]),
+        DepthViewMode::Heatmap => render_depth_heatmap(f, app, inner[1]),
     }
 
     // Help bar
     let help_text = if app.depth_monitoring {
         match app.depth_view_mode {
             DepthViewMode::BarChart => "m:Stop  v:TimeSeries  ↑↓←→:Navigate  Space:Select  x:Clear",
-            DepthViewMode::TimeSeries => "m:Stop  v:BarChart  Space:Deselect  x:Clear",
+            DepthViewMode::TimeSeries => "m:Stop  v:Heatmap  Space:Deselect  x:Clear",
+            DepthViewMode::Heatmap => "m:Stop  v:BarChart  x:Clear peaks",
         }
     } else {
         "m:Start monitoring  v:Switch view"
@@ -311,6 +320,64 @@ fn render_depth_time_series(f: &mut Frame, app: &mut App, area: Rect) {
     f.render_widget(chart, area);
 }
 
+/// Physical layout with each key filled by its current depth (blue = shallow,
+/// red = bottomed out) and a ▲ marker holding the recent peak.
+fn render_depth_heatmap(f: &mut Frame, app: &mut App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title(format!(
+        "Depth Heatmap (0-{HEATMAP_FULL_SCALE_MM:.0}mm, ▲ peak held {:.1}s)",
+        PEAK_HOLD.as_secs_f32()
+    ));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    // Matrix is column-major: index = col * 6 + row.
+    let (key_w, key_h) = (6u16, 3u16);
+    let now = Instant::now();
+    for (i, &depth) in app.key_depths.iter().enumerate() {
+        let label = get_key_label(app, i);
+        if label.is_empty() || label == "?" {
+            continue;
+        }
+        let x = inner.x + (i / 6) as u16 * key_w;
+        let y = inner.y + (i % 6) as u16 * key_h;
+        if x + key_w > inner.x + inner.width || y + key_h > inner.y + inner.height {
+            continue;
+        }
+
+        let style = if depth > 0.01 {
+            let frac = (depth / HEATMAP_FULL_SCALE_MM).clamp(0.0, 1.0);
+            let c = RgbColor::from_hsv(240.0 * (1.0 - frac), 1.0, 1.0);
+            let fg = if frac > 0.2 && frac < 0.75 {
+                Color::Black
+            } else {
+                Color::White
+            };
+            Style::default().bg(Color::Rgb(c.r, c.g, c.b)).fg(fg)
+        } else {
+            Style::default().bg(Color::Rgb(30, 30, 30)).fg(Color::Gray)
+        };
+        let peak = app
+            .depth_peaks
+            .get(i)
+            .filter(|&&(peak, at)| peak > 0.01 && now.duration_since(at) < PEAK_HOLD)
+            .map_or(String::new(), |&(peak, _)| format!("▲{peak:.2}"));
+        let name: String = label.chars().take(key_w as usize - 1).collect();
+        let lines = vec![
+            Line::from(name),
+            Line::from(if depth > 0.01 {
+                format!("{depth:.2}")
+            } else {
+                String::new()
+            }),
+            Line::from(Span::styled(peak, Style::default().fg(Color::Yellow))),
+        ];
+        let cell = Paragraph::new(lines)
+            .style(style)
+            .alignment(Alignment::Center);
+        f.render_widget(cell, Rect::new(x, y, key_w - 1, key_h));
+    }
+}
+
 /// Get key label for display - use device profile matrix key names
 pub(in crate::tui) fn get_key_label(app: &App, index: usize) -> String {
     app.matrix_key_names
@@ -521,6 +588,13 @@ impl App {
                 self.depth_last_update[key_index] = Instant::now();
             }
 
+            // Peak hold for the heatmap: raise, or restart once the hold expired
+            if let Some(peak) = self.depth_peaks.get_mut(key_index) {
+                if depth_mm >= peak.0 || peak.1.elapsed() >= PEAK_HOLD {
+                    *peak = (depth_mm, Instant::now());
+                }
+            }
+
             // Track max observed depth for bar chart scaling
             if depth_mm > self.max_observed_depth {
                 self.max_observed_depth = depth_mm;
@@ -554,7 +628,8 @@ impl App {
     pub(in crate::tui) fn toggle_depth_view(&mut self) {
         self.depth_view_mode = match self.depth_view_mode {
             DepthViewMode::BarChart => DepthViewMode::TimeSeries,
-            DepthViewMode::TimeSeries => DepthViewMode::BarChart,
+            DepthViewMode::TimeSeries => DepthViewMode::Heatmap,
+            DepthViewMode::Heatmap => DepthViewMode::BarChart,
         };
         self.status_msg = format!("Depth view: {:?}", self.depth_view_mode);
     }
@@ -569,6 +644,29 @@ impl App {
         }
     }
 
+    /// Move the bar chart cursor one step on the matrix grid (`dcol`/`drow` in
+    /// {-1,0,1}; index = col * 6 + row), skipping positions without a key so
+    /// small boards like numpads never land on an empty slot.
+    pub(in crate::tui) fn move_depth_cursor(&mut self, dcol: i32, drow: i32) {
+        let cur = self.depth_cursor as i32;
+        let (col, row) = (cur / 6, cur % 6);
+        for step in 1.. {
+            let (c, r) = (col + dcol * step, row + drow * step);
+            if c < 0 || !(0..6).contains(&r) {
+                break;
+            }
+            let target = (c * 6 + r) as usize;
+            if target >= self.key_depths.len() {
+                break;
+            }
+            let label = get_key_label(self, target);
+            if !label.is_empty() && label != "?" {
+                self.depth_cursor = target;
+                return;
+            }
+        }
+    }
+
     /// Clear depth history and active keys
     pub(in crate::tui) fn clear_depth_data(&mut self) {
         for history in &mut self.depth_history {
@@ -578,6 +676,9 @@ impl App {
         for depth in &mut self.key_depths {
             *depth = 0.0;
         }
+        for peak in &mut self.depth_peaks {
+            peak.0 = 0.0;
+        }
         self.status_msg = "Depth data cleared".to_string();
     }
 }
@@ -14,7 +14,11 @@ use monsgeek_keyboard::{
 };
 use monsgeek_transport::Transport;
 
-use crate::tui::shared::{AsyncResult, BatterySource, LoadState, PatchInfoData, SleepField};
+use crate::tui::shared::{
+    AsyncResult, BatterySource, LoadState, PatchInfoData, SleepField, BRIGHTNESS_SPINNER,
+    DEBOUNCE_SPINNER, FN_LAYER_SPINNER, PROFILE_SPINNER, RGB_SPINNER, RT_STABILITY_SPINNER,
+    SLEEP_TIME_SPINNER, SPEED_SPINNER,
+};
 use crate::tui::App;
 
 /// Which color is being edited with hex input
@@ -304,6 +308,7 @@ impl App {
             Some(BatterySource::Kernel(path)) => {
                 // Read from kernel power_supply sysfs (synchronous, fast)
                 self.battery = read_kernel_battery(path);
+                self.record_battery();
             }
             Some(BatterySource::Vendor) => {
                 // Query battery via keyboard API (async)
@@ -355,6 +360,7 @@ impl App {
                     server_version: None,
                     has_update: false,
                     download_path: None,
+                    manifest: Default::default(),
                     message: format!("Check failed: {e}"),
                 },
             };
@@ -763,6 +769,288 @@ pub(in crate::tui) fn parse_hex_color(s: &str) -> Option<(u8, u8, u8)> {
     Some((r, g, b))
 }
 
+/// Step the value on the selected Info row: `←`/`→`, mouse wheel and drag.
+pub(in crate::tui) fn adjust_info_row(app: &mut App, forward: bool, coarse: bool) {
+    let tag = app
+        .info_tags
+        .get(app.selected)
+        .copied()
+        .unwrap_or(InfoTag::ReadOnly);
+    if forward {
+        match tag {
+            InfoTag::Profile => {
+                app.set_profile(PROFILE_SPINNER.increment_u8(app.info.profile, coarse))
+            }
+            InfoTag::Debounce => {
+                app.set_debounce(DEBOUNCE_SPINNER.increment_u8(app.info.debounce, coarse))
+            }
+            InfoTag::PollingRate => app.cycle_polling_rate(-1), // lower index = higher rate
+            InfoTag::LedMode => app.set_led_mode((app.info.led_mode + 1).min(cmd::LED_MODE_MAX)),
+            InfoTag::LedBrightness => {
+                app.set_brightness(BRIGHTNESS_SPINNER.increment_u8(app.info.led_brightness, coarse))
+            }
+            InfoTag::LedSpeed => {
+                let current = speed_to_wire(app.info.led_speed);
+                app.set_speed(SPEED_SPINNER.increment_u8(current, coarse));
+            }
+            InfoTag::LedRed => {
+                let r = RGB_SPINNER.increment_u8(app.info.led_r, coarse);
+                app.set_color(r, app.info.led_g, app.info.led_b);
+            }
+            InfoTag::LedGreen => {
+                let g = RGB_SPINNER.increment_u8(app.info.led_g, coarse);
+                app.set_color(app.info.led_r, g, app.info.led_b);
+            }
+            InfoTag::LedBlue => {
+                let b = RGB_SPINNER.increment_u8(app.info.led_b, coarse);
+                app.set_color(app.info.led_r, app.info.led_g, b);
+            }
+            InfoTag::LedDazzle => {
+                app.toggle_dazzle();
+                super::audio::reapply_if_active(app);
+            }
+            InfoTag::SideMode => app.set_side_mode((app.info.side_mode + 1).min(cmd::LED_MODE_MAX)),
+            InfoTag::SideBrightness => app.set_side_brightness(
+                BRIGHTNESS_SPINNER.increment_u8(app.info.side_brightness, coarse),
+            ),
+            InfoTag::SideSpeed => {
+                let current = speed_to_wire(app.info.side_speed);
+                app.set_side_speed(SPEED_SPINNER.increment_u8(current, coarse));
+            }
+            InfoTag::SideRed => {
+                let r = RGB_SPINNER.increment_u8(app.info.side_r, coarse);
+                app.set_side_color(r, app.info.side_g, app.info.side_b);
+            }
+            InfoTag::SideGreen => {
+                let g = RGB_SPINNER.increment_u8(app.info.side_g, coarse);
+                app.set_side_color(app.info.side_r, g, app.info.side_b);
+            }
+            InfoTag::SideBlue => {
+                let b = RGB_SPINNER.increment_u8(app.info.side_b, coarse);
+                app.set_side_color(app.info.side_r, app.info.side_g, b);
+            }
+            InfoTag::SideDazzle => app.toggle_side_dazzle(),
+            InfoTag::FnLayer => {
+                if let Some(ref opts) = app.options.clone() {
+                    app.set_fn_layer(FN_LAYER_SPINNER.increment_u8(opts.fn_layer, coarse));
+                }
+            }
+            InfoTag::WasdSwap => app.toggle_wasd_swap(),
+            InfoTag::AntiMistouch => app.toggle_anti_mistouch(),
+            InfoTag::RtStability => {
+                if let Some(ref opts) = app.options.clone() {
+                    app.set_rt_stability(
+                        RT_STABILITY_SPINNER.increment_u8(opts.rt_stability, coarse),
+                    );
+                }
+            }
+            InfoTag::SleepIdleBt => {
+                let step = if coarse {
+                    SLEEP_TIME_SPINNER.step_coarse
+                } else {
+                    SLEEP_TIME_SPINNER.step
+                } as i32;
+                app.update_sleep_time(SleepField::IdleBt, step);
+            }
+            InfoTag::SleepIdle24g => {
+                let step = if coarse {
+                    SLEEP_TIME_SPINNER.step_coarse
+                } else {
+                    SLEEP_TIME_SPINNER.step
+                } as i32;
+                app.update_sleep_time(SleepField::Idle24g, step);
+            }
+            InfoTag::SleepDeepBt => {
+                let step = if coarse {
+                    SLEEP_TIME_SPINNER.step_coarse
+                } else {
+                    SLEEP_TIME_SPINNER.step
+                } as i32;
+                app.update_sleep_time(SleepField::DeepBt, step);
+            }
+            InfoTag::SleepDeep24g => {
+                let step = if coarse {
+                    SLEEP_TIME_SPINNER.step_coarse
+                } else {
+                    SLEEP_TIME_SPINNER.step
+                } as i32;
+                app.update_sleep_time(SleepField::Deep24g, step);
+            }
+            InfoTag::AudioDevice => super::audio::cycle_device(app, 1),
+            InfoTag::AudioVizStyle => super::audio::cycle_style(app, 1),
+            InfoTag::AudioRate => super::audio::cycle_rate(app, 1),
+            InfoTag::ScreenRate => super::screen::cycle_rate(app, 1),
+            InfoTag::ScreenRegion => super::screen::cycle_region(app, 1),
+            InfoTag::ScreenTestSwatch => super::screen::cycle_test_swatch(app, 1),
+            InfoTag::ScreenGainR => {
+                super::screen::adjust_calibration(app, super::screen::CalField::GainR, 1, coarse)
+            }
+            InfoTag::ScreenGainG => {
+                super::screen::adjust_calibration(app, super::screen::CalField::GainG, 1, coarse)
+            }
+            InfoTag::ScreenGainB => {
+                super::screen::adjust_calibration(app, super::screen::CalField::GainB, 1, coarse)
+            }
+            InfoTag::ScreenGammaR => {
+                super::screen::adjust_calibration(app, super::screen::CalField::GammaR, 1, coarse)
+            }
+            InfoTag::ScreenGammaG => {
+                super::screen::adjust_calibration(app, super::screen::CalField::GammaG, 1, coarse)
+            }
+            InfoTag::ScreenGammaB => {
+                super::screen::adjust_calibration(app, super::screen::CalField::GammaB, 1, coarse)
+            }
+            InfoTag::ScreenSaturation => super::screen::adjust_calibration(
+                app,
+                super::screen::CalField::Saturation,
+                1,
+                coarse,
+            ),
+            InfoTag::UserPicLayer => {
+                let n = (app.userpic_layer + 1) % 4;
+                app.set_userpic_layer(n);
+            }
+            _ => {}
+        }
+    } else {
+        match tag {
+            InfoTag::Profile => {
+                app.set_profile(PROFILE_SPINNER.decrement_u8(app.info.profile, coarse))
+            }
+            InfoTag::Debounce => {
+                app.set_debounce(DEBOUNCE_SPINNER.decrement_u8(app.info.debounce, coarse))
+            }
+            InfoTag::PollingRate => app.cycle_polling_rate(1), // higher index = lower rate
+            InfoTag::LedMode => app.set_led_mode(app.info.led_mode.saturating_sub(1)),
+            InfoTag::LedBrightness => {
+                app.set_brightness(BRIGHTNESS_SPINNER.decrement_u8(app.info.led_brightness, coarse))
+            }
+            InfoTag::LedSpeed => {
+                let current = speed_to_wire(app.info.led_speed);
+                app.set_speed(SPEED_SPINNER.decrement_u8(current, coarse));
+            }
+            InfoTag::LedRed => {
+                let r = RGB_SPINNER.decrement_u8(app.info.led_r, coarse);
+                app.set_color(r, app.info.led_g, app.info.led_b);
+            }
+            InfoTag::LedGreen => {
+                let g = RGB_SPINNER.decrement_u8(app.info.led_g, coarse);
+                app.set_color(app.info.led_r, g, app.info.led_b);
+            }
+            InfoTag::LedBlue => {
+                let b = RGB_SPINNER.decrement_u8(app.info.led_b, coarse);
+                app.set_color(app.info.led_r, app.info.led_g, b);
+            }
+            InfoTag::LedDazzle => {
+                app.toggle_dazzle();
+                super::audio::reapply_if_active(app);
+            }
+            InfoTag::SideMode => app.set_side_mode(app.info.side_mode.saturating_sub(1)),
+            InfoTag::SideBrightness => app.set_side_brightness(
+                BRIGHTNESS_SPINNER.decrement_u8(app.info.side_brightness, coarse),
+            ),
+            InfoTag::SideSpeed => {
+                let current = speed_to_wire(app.info.side_speed);
+                app.set_side_speed(SPEED_SPINNER.decrement_u8(current, coarse));
+            }
+            InfoTag::SideRed => {
+                let r = RGB_SPINNER.decrement_u8(app.info.side_r, coarse);
+                app.set_side_color(r, app.info.side_g, app.info.side_b);
+            }
+            InfoTag::SideGreen => {
+                let g = RGB_SPINNER.decrement_u8(app.info.side_g, coarse);
+                app.set_side_color(app.info.side_r, g, app.info.side_b);
+            }
+            InfoTag::SideBlue => {
+                let b = RGB_SPINNER.decrement_u8(app.info.side_b, coarse);
+                app.set_side_color(app.info.side_r, app.info.side_g, b);
+            }
+            InfoTag::SideDazzle => app.toggle_side_dazzle(),
+            InfoTag::FnLayer => {
+                if let Some(ref opts) = app.options.clone() {
+                    app.set_fn_layer(FN_LAYER_SPINNER.decrement_u8(opts.fn_layer, coarse));
+                }
+            }
+            InfoTag::WasdSwap => app.toggle_wasd_swap(),
+            InfoTag::AntiMistouch => app.toggle_anti_mistouch(),
+            InfoTag::RtStability => {
+                if let Some(ref opts) = app.options.clone() {
+                    app.set_rt_stability(
+                        RT_STABILITY_SPINNER.decrement_u8(opts.rt_stability, coarse),
+                    );
+                }
+            }
+            InfoTag::SleepIdleBt => {
+                let step = if coarse {
+                    SLEEP_TIME_SPINNER.step_coarse
+                } else {
+                    SLEEP_TIME_SPINNER.step
+                } as i32;
+                app.update_sleep_time(SleepField::IdleBt, -step);
+            }
+            InfoTag::SleepIdle24g => {
+                let step = if coarse {
+                    SLEEP_TIME_SPINNER.step_coarse
+                } else {
+                    SLEEP_TIME_SPINNER.step
+                } as i32;
+                app.update_sleep_time(SleepField::Idle24g, -step);
+            }
+            InfoTag::SleepDeepBt => {
+                let step = if coarse {
+                    SLEEP_TIME_SPINNER.step_coarse
+                } else {
+                    SLEEP_TIME_SPINNER.step
+                } as i32;
+                app.update_sleep_time(SleepField::DeepBt, -step);
+            }
+            InfoTag::SleepDeep24g => {
+                let step = if coarse {
+                    SLEEP_TIME_SPINNER.step_coarse
+                } else {
+                    SLEEP_TIME_SPINNER.step
+                } as i32;
+                app.update_sleep_time(SleepField::Deep24g, -step);
+            }
+            InfoTag::AudioDevice => super::audio::cycle_device(app, -1),
+            InfoTag::AudioVizStyle => super::audio::cycle_style(app, -1),
+            InfoTag::AudioRate => super::audio::cycle_rate(app, -1),
+            InfoTag::ScreenRate => super::screen::cycle_rate(app, -1),
+            InfoTag::ScreenRegion => super::screen::cycle_region(app, -1),
+            InfoTag::ScreenTestSwatch => super::screen::cycle_test_swatch(app, -1),
+            InfoTag::ScreenGainR => {
+                super::screen::adjust_calibration(app, super::screen::CalField::GainR, -1, coarse)
+            }
+            InfoTag::ScreenGainG => {
+                super::screen::adjust_calibration(app, super::screen::CalField::GainG, -1, coarse)
+            }
+            InfoTag::ScreenGainB => {
+                super::screen::adjust_calibration(app, super::screen::CalField::GainB, -1, coarse)
+            }
+            InfoTag::ScreenGammaR => {
+                super::screen::adjust_calibration(app, super::screen::CalField::GammaR, -1, coarse)
+            }
+            InfoTag::ScreenGammaG => {
+                super::screen::adjust_calibration(app, super::screen::CalField::GammaG, -1, coarse)
+            }
+            InfoTag::ScreenGammaB => {
+                super::screen::adjust_calibration(app, super::screen::CalField::GammaB, -1, coarse)
+            }
+            InfoTag::ScreenSaturation => super::screen::adjust_calibration(
+                app,
+                super::screen::CalField::Saturation,
+                -1,
+                coarse,
+            ),
+            InfoTag::UserPicLayer => {
+                let n = (app.userpic_layer + 3) % 4;
+                app.set_userpic_layer(n);
+            }
+            _ => {}
+        }
+    }
+}
+
 pub(in crate::tui) fn render_device_info(f: &mut Frame, app: &mut App, area: Rect) {
     // Enumerate capture sources once (for the audio-reactive rows below).
     super::audio::ensure_sources_loaded(app);
@@ -15,12 +15,29 @@ use monsgeek_transport::protocol::matrix;
 use super::super::shared::LoadState;
 use super::super::App;
 
-/// List vs. keyboard-layout presentation for the Key Mapping tab.
+/// Presentation for the Key Mapping tab: list, keyboard layout, or the
+/// editable trigger grid.
 #[derive(Clone, Copy, PartialEq, Default)]
 pub(in crate::tui) enum KeyMappingView {
     #[default]
     List,
     Layout,
+    Triggers,
+}
+
+impl KeyMappingView {
+    pub fn cycle(self) -> Self {
+        match self {
+            Self::List => Self::Layout,
+            Self::Layout => Self::Triggers,
+            Self::Triggers => Self::List,
+        }
+    }
+
+    /// Whether arrows move over the keyboard grid rather than down a list.
+    pub fn is_grid(self) -> bool {
+        self != Self::List
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -290,6 +307,7 @@ pub(in crate::tui) fn render_key_mapping(f: &mut Frame, app: &mut App, area: Rec
     match app.key_mapping_view {
         KeyMappingView::Layout => render_key_mapping_layout(f, app, area),
         KeyMappingView::List => render_key_mapping_list(f, app, area),
+        KeyMappingView::Triggers => super::trigger_grid::render_trigger_grid(f, app, area),
     }
 }
 
@@ -459,7 +477,7 @@ fn render_key_mapping_layout(f: &mut Frame, app: &mut App, area: Rect) {
         .split(area);
 
     let block = Block::default().borders(Borders::ALL).title(format!(
-        "Key Mapping — layout  [{}/{} keys]  (v: list  ←↑↓→: move  Enter: edit  f: filter  s: sort  g: global)",
+        "Key Mapping — layout  [{}/{} keys]  (v: trigger grid  ←↑↓→: move  Enter: edit  f: filter  s: sort  g: global)",
         visible.len(),
         app.key_rows.len(),
     ));
@@ -467,6 +485,11 @@ fn render_key_mapping_layout(f: &mut Frame, app: &mut App, area: Rect) {
     f.render_widget(block, chunks[0]);
 
     let (key_w, key_h) = (5u16, 2u16);
+    app.key_grid_area.set(Some(KeyGridArea {
+        area: inner,
+        key_w,
+        key_h,
+    }));
     for r in &app.key_rows {
         if r.position.is_empty() || r.position == "?" {
             continue;
@@ -541,6 +564,42 @@ fn render_key_mapping_layout(f: &mut Frame, app: &mut App, area: Rect) {
     );
 }
 
+/// Screen geometry of a rendered key grid (layout and trigger grid views),
+/// kept for mouse hit testing. Keys sit at column `index / 6`, row `index % 6`.
+#[derive(Debug, Clone, Copy)]
+pub(in crate::tui) struct KeyGridArea {
+    pub area: Rect,
+    pub key_w: u16,
+    pub key_h: u16,
+}
+
+impl KeyGridArea {
+    /// Matrix index of the cell under `pos`.
+    pub fn key_at(&self, pos: Position) -> Option<u8> {
+        if !self.area.contains(pos) {
+            return None;
+        }
+        let col = (pos.x - self.area.x) / self.key_w;
+        let row = (pos.y - self.area.y) / self.key_h;
+        (row < 6).then(|| (col * 6 + row) as u8)
+    }
+}
+
+/// Put the layout cursor on matrix key `index`; false if it is filtered out.
+pub(in crate::tui) fn select_key(app: &mut App, index: u8) -> bool {
+    let visible = visible_indices(app);
+    match visible
+        .iter()
+        .position(|&ri| app.key_rows[ri].index == index)
+    {
+        Some(vi) => {
+            app.key_mapping_selected = vi;
+            true
+        }
+        None => false,
+    }
+}
+
 /// Move the layout selection one grid step; `dcol`/`drow` in {-1,0,1}. Snaps to
 /// the nearest visible key in that direction (same column for up/down, same row
 /// for left/right).
@@ -8,6 +8,7 @@ pub(super) mod key_mapping;
 pub(super) mod notify;
 pub(super) mod remaps;
 pub(super) mod screen;
+pub(super) mod trigger_grid;
 pub(super) mod triggers;
 
 /// True if `led_mode` is a host-driven reactive mode (music visualizer or screen
@@ -552,7 +552,9 @@ impl App {
             let log = crate::notify::log::DaemonLog::new(false);
             self.notify.daemon_log = Some(log.clone());
             let handle = tokio::spawn(async move {
-                let result = crate::notify::daemon::run_with_cancel(kb, running, labels, log).await;
+                let desktop = crate::settings::Settings::load().desktop_notifications;
+                let result =
+                    crate::notify::daemon::run_with_cancel(kb, running, labels, log, desktop).await;
                 tx.send(AsyncResult::NotifyDaemonStopped(
                     result.map_err(|e| e.to_string()),
                 ));
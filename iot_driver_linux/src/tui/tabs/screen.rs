@@ -324,6 +324,10 @@ fn start(app: &mut App) {
         app.screen.error = Some("No keyboard connected".to_string());
         return;
     };
+    if monsgeek_keyboard::DeviceCapabilities::lacks(keyboard.capabilities().has_screen) {
+        app.screen.error = Some("This keyboard has no screen sync mode".to_string());
+        return;
+    }
 
     // The keyboard is already in ScreenSync mode here — set either by the user
     // picking it in the LED mode row (which sent the user's brightness/color via
@@ -0,0 +1,373 @@
+// Trigger Grid — per-key actuation / release / RT values drawn over the layout.
+//
+// The third Key Mapping view. Edits go straight to the magnetism tables: the
+// local `TriggerSettings` copy is changed, then each touched table is written
+// whole through the per-key bulk setters, so a 60-key paste is one transfer
+// per table instead of one per key.
+
+use std::collections::BTreeSet;
+
+use ratatui::{prelude::*, widgets::*};
+
+use crate::TriggerSettings;
+use monsgeek_keyboard::{KeyboardError, KeyboardInterface, ModeByte};
+
+use super::super::shared::SpinnerConfig;
+use super::super::App;
+use super::key_mapping::{visible_indices, KeyGridArea};
+use super::triggers::TriggerField;
+
+/// Magnetism table shown and adjusted by the grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(in crate::tui) enum GridField {
+    Actuation,
+    Release,
+    RtPress,
+    RtLift,
+}
+
+impl GridField {
+    /// Tab order; also the clipboard layout.
+    const ALL: [Self; 4] = [Self::Actuation, Self::Release, Self::RtPress, Self::RtLift];
+
+    fn trigger_field(self) -> TriggerField {
+        match self {
+            Self::Actuation => TriggerField::Actuation,
+            Self::Release => TriggerField::Release,
+            Self::RtPress => TriggerField::RtPress,
+            Self::RtLift => TriggerField::RtLift,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        self.trigger_field().label()
+    }
+
+    fn spinner(self) -> Option<SpinnerConfig> {
+        self.trigger_field().spinner_config()
+    }
+
+    fn is_rt(self) -> bool {
+        matches!(self, Self::RtPress | Self::RtLift)
+    }
+
+    fn values(self, t: &TriggerSettings) -> &[u16] {
+        match self {
+            Self::Actuation => &t.press_travel,
+            Self::Release => &t.lift_travel,
+            Self::RtPress => &t.rt_press,
+            Self::RtLift => &t.rt_lift,
+        }
+    }
+
+    fn values_mut(self, t: &mut TriggerSettings) -> &mut [u16] {
+        match self {
+            Self::Actuation => &mut t.press_travel,
+            Self::Release => &mut t.lift_travel,
+            Self::RtPress => &mut t.rt_press,
+            Self::RtLift => &mut t.rt_lift,
+        }
+    }
+
+    fn write(self, kb: &KeyboardInterface, t: &TriggerSettings) -> Result<(), KeyboardError> {
+        let values = self.values(t);
+        match self {
+            Self::Actuation => kb.set_actuation_per_key_u16(values),
+            Self::Release => kb.set_release_per_key_u16(values),
+            Self::RtPress => kb.set_rt_press_per_key_u16(values),
+            Self::RtLift => kb.set_rt_lift_per_key_u16(values),
+        }
+    }
+}
+
+/// Grid editor state: focused table, selected keys and the copied values.
+#[derive(Default)]
+pub(in crate::tui) struct TriggerGrid {
+    field: usize,
+    /// Selected matrix indices; edits apply to these, or to the cursor key when empty.
+    pub selection: BTreeSet<u8>,
+    /// One key's values, in [`GridField::ALL`] order (raw u16).
+    clipboard: Option<[u16; 4]>,
+}
+
+impl TriggerGrid {
+    fn current(&self) -> GridField {
+        GridField::ALL[self.field]
+    }
+
+    pub fn cycle_field(&mut self, forward: bool) {
+        let n = GridField::ALL.len();
+        self.field = if forward {
+            (self.field + 1) % n
+        } else {
+            (self.field + n - 1) % n
+        };
+    }
+}
+
+/// Matrix index of the key under the cursor.
+fn cursor_key(app: &App) -> Option<u8> {
+    visible_indices(app)
+        .get(app.key_mapping_selected)
+        .map(|&ri| app.key_rows[ri].index)
+}
+
+/// Keys an edit applies to: the selection, else the cursor key.
+fn targets(app: &App) -> Vec<u8> {
+    if app.trigger_grid.selection.is_empty() {
+        cursor_key(app).into_iter().collect()
+    } else {
+        app.trigger_grid.selection.iter().copied().collect()
+    }
+}
+
+/// Space: add or remove the cursor key from the selection.
+pub(in crate::tui) fn toggle_selected(app: &mut App) {
+    let Some(key) = cursor_key(app) else {
+        return;
+    };
+    let sel = &mut app.trigger_grid.selection;
+    if !sel.remove(&key) {
+        sel.insert(key);
+    }
+    app.status_msg = format!("{} key(s) selected", sel.len());
+}
+
+/// `a`: select every key passing the filter, or clear when they already are.
+pub(in crate::tui) fn select_all(app: &mut App) {
+    let keys: BTreeSet<u8> = visible_indices(app)
+        .into_iter()
+        .map(|ri| &app.key_rows[ri])
+        .filter(|r| !r.position.is_empty() && r.position != "?")
+        .map(|r| r.index)
+        .collect();
+    let sel = &mut app.trigger_grid.selection;
+    if keys.is_subset(sel) {
+        sel.clear();
+    } else {
+        sel.extend(keys);
+    }
+    app.status_msg = format!("{} key(s) selected", sel.len());
+}
+
+/// `y`: copy the cursor key's four values.
+pub(in crate::tui) fn copy(app: &mut App) {
+    let (Some(key), Some(t)) = (cursor_key(app), app.triggers.as_ref()) else {
+        return;
+    };
+    let i = key as usize;
+    let mut values = [0u16; 4];
+    for (v, field) in values.iter_mut().zip(GridField::ALL) {
+        *v = field.values(t).get(i).copied().unwrap_or(0);
+    }
+    app.trigger_grid.clipboard = Some(values);
+    app.status_msg = format!("Copied trigger values of {}", key_label(app, key));
+}
+
+/// `p`: paste the copied values onto the targets.
+pub(in crate::tui) fn paste(app: &mut App) {
+    let Some(values) = app.trigger_grid.clipboard else {
+        app.status_msg = "Nothing copied (y copies the key under the cursor)".to_string();
+        return;
+    };
+    let keys = targets(app);
+    let Some(t) = app.triggers.as_mut() else {
+        app.status_msg = "No trigger data loaded".to_string();
+        return;
+    };
+    for (field, v) in GridField::ALL.into_iter().zip(values) {
+        let table = field.values_mut(t);
+        for &k in &keys {
+            if let Some(slot) = table.get_mut(k as usize) {
+                *slot = v;
+            }
+        }
+    }
+    apply(app, &GridField::ALL, keys.len());
+}
+
+/// `+`/`-` (`]`/`[` coarse): step the focused field on the targets.
+pub(in crate::tui) fn adjust(app: &mut App, up: bool, coarse: bool) {
+    let field = app.trigger_grid.current();
+    let Some(spinner) = field.spinner() else {
+        return;
+    };
+    let keys = targets(app);
+    let factor = app.precision.factor() as f32;
+    let Some(t) = app.triggers.as_mut() else {
+        app.status_msg = "No trigger data loaded".to_string();
+        return;
+    };
+    let table = field.values_mut(t);
+    for &k in &keys {
+        if let Some(slot) = table.get_mut(k as usize) {
+            let mm = *slot as f32 / factor;
+            let mm = if up {
+                spinner.increment(mm, coarse)
+            } else {
+                spinner.decrement(mm, coarse)
+            };
+            *slot = (mm * factor).round() as u16;
+        }
+    }
+    apply(app, &[field], keys.len());
+}
+
+/// Write the edited tables to the device and mirror them into the key rows.
+/// On failure the tables are reloaded so the grid shows what the device holds.
+fn apply(app: &mut App, fields: &[GridField], count: usize) {
+    let (Some(keyboard), Some(t)) = (app.keyboard.clone(), app.triggers.as_ref()) else {
+        app.status_msg = "No keyboard connected".to_string();
+        return;
+    };
+    let errors: Vec<String> = fields
+        .iter()
+        .filter_map(|f| {
+            f.write(&keyboard, t)
+                .err()
+                .map(|e| format!("{}: {e}", f.label()))
+        })
+        .collect();
+    if !errors.is_empty() {
+        app.status_msg = format!("Errors: {}", errors.join(", "));
+        app.load_triggers();
+        return;
+    }
+    for r in &mut app.key_rows {
+        let i = r.index as usize;
+        let get = |v: &[u16], old: u16| v.get(i).copied().unwrap_or(old);
+        r.actuation = get(&t.press_travel, r.actuation);
+        r.release = get(&t.lift_travel, r.release);
+        r.rt_press = get(&t.rt_press, r.rt_press);
+        r.rt_lift = get(&t.rt_lift, r.rt_lift);
+    }
+    let names: Vec<&str> = fields.iter().map(|f| f.label()).collect();
+    app.status_msg = format!("Applied {} to {count} key(s)", names.join("/"));
+}
+
+fn key_label(app: &App, key: u8) -> &'static str {
+    app.key_rows
+        .iter()
+        .find(|r| r.index == key)
+        .map_or("?", |r| r.position)
+}
+
+/// Layout-shaped grid of the focused field's value per key. Cursor key in blue,
+/// selected keys in magenta; RT values are dimmed on keys with RT off.
+pub(in crate::tui) fn render_trigger_grid(f: &mut Frame, app: &mut App, area: Rect) {
+    let visible = visible_indices(app);
+    let cursor = cursor_key(app);
+    let grid = &app.trigger_grid;
+    let field = grid.current();
+    let factor = app.precision.factor() as f32;
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(8), Constraint::Length(4)])
+        .split(area);
+
+    let block = Block::default().borders(Borders::ALL).title(format!(
+        "Key Mapping — trigger grid  [{} selected]  (v: list  Tab: field  Space: select  a: all  y/p: copy/paste  +/-: adjust  [/]: coarse)",
+        grid.selection.len(),
+    ));
+    let inner = block.inner(chunks[0]);
+    f.render_widget(block, chunks[0]);
+
+    let Some(t) = app.triggers.as_ref() else {
+        f.render_widget(Paragraph::new("Loading trigger settings..."), inner);
+        return;
+    };
+
+    let visible_set: BTreeSet<u8> = visible.iter().map(|&ri| app.key_rows[ri].index).collect();
+    let (key_w, key_h) = (6u16, 3u16);
+    app.key_grid_area.set(Some(KeyGridArea {
+        area: inner,
+        key_w,
+        key_h,
+    }));
+    for r in &app.key_rows {
+        if r.position.is_empty() || r.position == "?" {
+            continue;
+        }
+        let col = r.index as u16 / 6;
+        let row = r.index as u16 % 6;
+        let x = inner.x + col * key_w;
+        let y = inner.y + row * key_h;
+        if x + key_w > inner.x + inner.width || y + 2 > inner.y + inner.height {
+            continue;
+        }
+        let i = r.index as usize;
+        let raw = field.values(t).get(i).copied().unwrap_or(0);
+        let rt_on = ModeByte::from_u8(t.key_modes.get(i).copied().unwrap_or(0)).rapid_trigger;
+
+        let style = if Some(r.index) == cursor {
+            Style::default()
+                .bg(Color::Blue)
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD)
+        } else if grid.selection.contains(&r.index) {
+            Style::default().bg(Color::Magenta).fg(Color::Black)
+        } else if !visible_set.contains(&r.index) || (field.is_rt() && !rt_on) {
+            Style::default().fg(Color::DarkGray)
+        } else {
+            Style::default().fg(Color::Cyan)
+        };
+        let name: String = r.position.chars().take(5).collect();
+        let lines = vec![
+            Line::from(name),
+            Line::from(format!("{:.2}", raw as f32 / factor)),
+        ];
+        f.render_widget(
+            Paragraph::new(lines)
+                .style(style)
+                .alignment(Alignment::Center),
+            Rect::new(x, y, key_w - 1, 2),
+        );
+    }
+
+    // Field tabs, then the cursor key's full values and the clipboard.
+    let mut tabs = vec![Span::raw(" Field: ")];
+    for (n, gf) in GridField::ALL.into_iter().enumerate() {
+        let style = if n == grid.field {
+            Style::default().fg(Color::Black).bg(Color::Cyan)
+        } else {
+            Style::default().fg(Color::Cyan)
+        };
+        tabs.push(Span::styled(format!(" {} ", gf.label()), style));
+        tabs.push(Span::raw(" "));
+    }
+    let fmt = |v: [u16; 4]| {
+        format!(
+            "act {:.2} / rel {:.2} / RT {:.2}↓ {:.2}↑ mm",
+            v[0] as f32 / factor,
+            v[1] as f32 / factor,
+            v[2] as f32 / factor,
+            v[3] as f32 / factor,
+        )
+    };
+    let cursor_line = match cursor {
+        Some(key) => {
+            let mut v = [0u16; 4];
+            for (slot, gf) in v.iter_mut().zip(GridField::ALL) {
+                *slot = gf.values(t).get(key as usize).copied().unwrap_or(0);
+            }
+            format!(" {}: {}", key_label(app, key), fmt(v))
+        }
+        None => " (no matching key)".to_string(),
+    };
+    let clip = grid.clipboard.map_or_else(|| "(empty)".to_string(), fmt);
+    let lines = vec![
+        Line::from(tabs),
+        Line::from(vec![
+            Span::styled(cursor_line, Style::default().fg(Color::Green)),
+            Span::styled(
+                format!("   clipboard: {clip}"),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]),
+    ];
+    f.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL)),
+        chunks[1],
+    );
+}
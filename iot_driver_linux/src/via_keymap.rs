@@ -0,0 +1,488 @@
+//! VIA/QMK-style keymap files (`iot_driver keymap export/import`).
+//!
+//! Layouts are stored the way VIA backs them up: a `layers` array of QMK
+//! keycode names, one list per layer, so they can be shared and edited with
+//! QMK tooling. There is no physical layout definition to order keys by, so
+//! position `n` in a layer is matrix index `n` (see `iot_driver keys`).
+//! Layer 0 and 1 are the two base layers, layer 2 the Fn layer.
+//!
+//! ```json
+//! {
+//!   "name": "my layout",
+//!   "vendorProductId": 825577520,
+//!   "layers": [
+//!     ["KC_ESC", "KC_GRV", "KC_TAB", "KC_ESC", "KC_LSFT", "KC_LCTL", ...],
+//!     ["KC_TRNS", ...],
+//!     ["KC_TRNS", "KC_TRNS", "KC_TRNS", "KC_TRNS", "KC_TRNS", "KC_TRNS", "KC_MPLY", ...]
+//!   ]
+//! }
+//! ```
+//!
+//! Keycodes: basic `KC_*` keys (short and long QMK names), media keys,
+//! `KC_BTN1`-`KC_BTN5`, modifier wrappers such as `LCTL(KC_C)`, `MO(2)` for
+//! the Fn key and `MACRO(n)`. `KC_TRNS` falls through to the base layer
+//! (on layer 0 it means the factory default); `KC_NO` disables the key,
+//! which on the overlay layers is the same as `KC_TRNS`. Actions QMK has no
+//! name for are written as `MG(0x...)`, the raw 4-byte key config, so
+//! exports always round-trip.
+
+use serde::{Deserialize, Serialize};
+
+use crate::key_action::{mods, KeyAction};
+use crate::keyboard_config::ConfigError;
+use crate::keymap::{default_keycode, KeyMap, Layer};
+use monsgeek_transport::protocol::matrix;
+
+/// Basic keys with fixed names: (HID code, short name, long name).
+const NAMED_KEYS: &[(u8, &str, &str)] = &[
+    (0x28, "KC_ENT", "KC_ENTER"),
+    (0x29, "KC_ESC", "KC_ESCAPE"),
+    (0x2A, "KC_BSPC", "KC_BACKSPACE"),
+    (0x2B, "KC_TAB", "KC_TAB"),
+    (0x2C, "KC_SPC", "KC_SPACE"),
+    (0x2D, "KC_MINS", "KC_MINUS"),
+    (0x2E, "KC_EQL", "KC_EQUAL"),
+    (0x2F, "KC_LBRC", "KC_LEFT_BRACKET"),
+    (0x30, "KC_RBRC", "KC_RIGHT_BRACKET"),
+    (0x31, "KC_BSLS", "KC_BACKSLASH"),
+    (0x32, "KC_NUHS", "KC_NONUS_HASH"),
+    (0x33, "KC_SCLN", "KC_SEMICOLON"),
+    (0x34, "KC_QUOT", "KC_QUOTE"),
+    (0x35, "KC_GRV", "KC_GRAVE"),
+    (0x36, "KC_COMM", "KC_COMMA"),
+    (0x37, "KC_DOT", "KC_DOT"),
+    (0x38, "KC_SLSH", "KC_SLASH"),
+    (0x39, "KC_CAPS", "KC_CAPS_LOCK"),
+    (0x46, "KC_PSCR", "KC_PRINT_SCREEN"),
+    (0x47, "KC_SCRL", "KC_SCROLL_LOCK"),
+    (0x48, "KC_PAUS", "KC_PAUSE"),
+    (0x49, "KC_INS", "KC_INSERT"),
+    (0x4A, "KC_HOME", "KC_HOME"),
+    (0x4B, "KC_PGUP", "KC_PAGE_UP"),
+    (0x4C, "KC_DEL", "KC_DELETE"),
+    (0x4D, "KC_END", "KC_END"),
+    (0x4E, "KC_PGDN", "KC_PAGE_DOWN"),
+    (0x4F, "KC_RGHT", "KC_RIGHT"),
+    (0x50, "KC_LEFT", "KC_LEFT"),
+    (0x51, "KC_DOWN", "KC_DOWN"),
+    (0x52, "KC_UP", "KC_UP"),
+    (0x53, "KC_NUM", "KC_NUM_LOCK"),
+    (0x54, "KC_PSLS", "KC_KP_SLASH"),
+    (0x55, "KC_PAST", "KC_KP_ASTERISK"),
+    (0x56, "KC_PMNS", "KC_KP_MINUS"),
+    (0x57, "KC_PPLS", "KC_KP_PLUS"),
+    (0x58, "KC_PENT", "KC_KP_ENTER"),
+    (0x63, "KC_PDOT", "KC_KP_DOT"),
+    (0x64, "KC_NUBS", "KC_NONUS_BACKSLASH"),
+    (0x65, "KC_APP", "KC_APPLICATION"),
+    (0x67, "KC_PEQL", "KC_KP_EQUAL"),
+    (0x87, "KC_INT1", "KC_INTERNATIONAL_1"),
+    (0x88, "KC_INT2", "KC_INTERNATIONAL_2"),
+    (0x89, "KC_INT3", "KC_INTERNATIONAL_3"),
+    (0x90, "KC_LNG1", "KC_LANGUAGE_1"),
+    (0x91, "KC_LNG2", "KC_LANGUAGE_2"),
+    (0xE0, "KC_LCTL", "KC_LEFT_CTRL"),
+    (0xE1, "KC_LSFT", "KC_LEFT_SHIFT"),
+    (0xE2, "KC_LALT", "KC_LEFT_ALT"),
+    (0xE3, "KC_LGUI", "KC_LEFT_GUI"),
+    (0xE4, "KC_RCTL", "KC_RIGHT_CTRL"),
+    (0xE5, "KC_RSFT", "KC_RIGHT_SHIFT"),
+    (0xE6, "KC_RALT", "KC_RIGHT_ALT"),
+    (0xE7, "KC_RGUI", "KC_RIGHT_GUI"),
+];
+
+/// Older QMK names still found in shared keymaps.
+const KEY_ALIASES: &[(&str, u8)] = &[
+    ("KC_BSPACE", 0x2A),
+    ("KC_SLCK", 0x47),
+    ("KC_NLCK", 0x53),
+    ("KC_CLCK", 0x39),
+    ("KC_LCMD", 0xE3),
+    ("KC_LWIN", 0xE3),
+    ("KC_RCMD", 0xE7),
+    ("KC_RWIN", 0xE7),
+    ("KC_LOPT", 0xE2),
+    ("KC_ROPT", 0xE6),
+    ("KC_ALGR", 0xE6),
+];
+
+/// Consumer-page keys: (usage, short name, long name).
+const MEDIA_KEYS: &[(u16, &str, &str)] = &[
+    (0x00E2, "KC_MUTE", "KC_AUDIO_MUTE"),
+    (0x00E9, "KC_VOLU", "KC_AUDIO_VOL_UP"),
+    (0x00EA, "KC_VOLD", "KC_AUDIO_VOL_DOWN"),
+    (0x00B5, "KC_MNXT", "KC_MEDIA_NEXT_TRACK"),
+    (0x00B6, "KC_MPRV", "KC_MEDIA_PREV_TRACK"),
+    (0x00B7, "KC_MSTP", "KC_MEDIA_STOP"),
+    (0x00CD, "KC_MPLY", "KC_MEDIA_PLAY_PAUSE"),
+    (0x018A, "KC_MAIL", "KC_MAIL"),
+    (0x0192, "KC_CALC", "KC_CALCULATOR"),
+    (0x0194, "KC_MYCM", "KC_MY_COMPUTER"),
+    (0x0221, "KC_WSCH", "KC_WWW_SEARCH"),
+    (0x0223, "KC_WHOM", "KC_WWW_HOME"),
+    (0x006F, "KC_BRIU", "KC_BRIGHTNESS_UP"),
+    (0x0070, "KC_BRID", "KC_BRIGHTNESS_DOWN"),
+];
+
+/// Modifier wrappers in export order: (bit, name, aliases).
+const MOD_WRAPPERS: &[(u8, &str, &[&str])] = &[
+    (mods::LCTRL, "LCTL", &["C"]),
+    (mods::LSHIFT, "LSFT", &["S"]),
+    (mods::LALT, "LALT", &["A", "LOPT"]),
+    (mods::LGUI, "LGUI", &["G", "LCMD", "LWIN"]),
+    (mods::RCTRL, "RCTL", &[]),
+    (mods::RSHIFT, "RSFT", &[]),
+    (mods::RALT, "RALT", &["ALGR", "ROPT"]),
+    (mods::RGUI, "RGUI", &["RCMD", "RWIN"]),
+];
+
+/// Index of the Fn layer in `layers`, the target of `MO()`.
+const FN_LAYER: usize = 2;
+
+/// QMK name of a basic HID keycode.
+fn basic_name(code: u8) -> Option<String> {
+    match code {
+        0x04..=0x1D => Some(format!("KC_{}", (b'A' + code - 0x04) as char)),
+        0x1E..=0x26 => Some(format!("KC_{}", code - 0x1D)),
+        0x27 => Some("KC_0".to_string()),
+        0x3A..=0x45 => Some(format!("KC_F{}", code - 0x39)),
+        0x68..=0x73 => Some(format!("KC_F{}", code - 0x68 + 13)),
+        0x59..=0x61 => Some(format!("KC_P{}", code - 0x58)),
+        0x62 => Some("KC_P0".to_string()),
+        _ => NAMED_KEYS
+            .iter()
+            .find(|(c, ..)| *c == code)
+            .map(|(_, short, _)| short.to_string()),
+    }
+}
+
+/// Basic HID keycode from a QMK name.
+fn parse_basic(name: &str) -> Option<u8> {
+    if let Some((code, ..)) = NAMED_KEYS
+        .iter()
+        .find(|(_, short, long)| *short == name || *long == name)
+    {
+        return Some(*code);
+    }
+    if let Some((_, code)) = KEY_ALIASES.iter().find(|(alias, _)| *alias == name) {
+        return Some(*code);
+    }
+    let rest = name.strip_prefix("KC_")?;
+    match rest.as_bytes() {
+        [c @ b'A'..=b'Z'] => return Some(0x04 + (c - b'A')),
+        [b'0'] => return Some(0x27),
+        [c @ b'1'..=b'9'] => return Some(0x1E + (c - b'1')),
+        _ => {}
+    }
+    let keypad = rest.strip_prefix("KP_").or_else(|| rest.strip_prefix('P'));
+    if let Some(n) = keypad.and_then(|d| d.parse::<u8>().ok()) {
+        return match n {
+            0 => Some(0x62),
+            1..=9 => Some(0x58 + n),
+            _ => None,
+        };
+    }
+    match rest.strip_prefix('F')?.parse::<u8>().ok()? {
+        n @ 1..=12 => Some(0x39 + n),
+        n @ 13..=24 => Some(0x68 + n - 13),
+        _ => None,
+    }
+}
+
+/// QMK keycode for an action on `layer`; zero configs are `KC_NO` on the
+/// base layer and `KC_TRNS` on the overlays.
+pub fn action_to_qmk(action: &KeyAction, layer: Layer) -> String {
+    match *action {
+        KeyAction::Disabled => match layer {
+            Layer::Base => "KC_NO".to_string(),
+            Layer::Layer1 | Layer::Fn => "KC_TRNS".to_string(),
+        },
+        KeyAction::Key(code) => basic_name(code).unwrap_or_else(|| raw(action)),
+        KeyAction::Combo { mods, key } => match basic_name(key) {
+            Some(mut name) => {
+                for &(bit, wrapper, _) in MOD_WRAPPERS.iter().rev() {
+                    if mods & bit != 0 {
+                        name = format!("{wrapper}({name})");
+                    }
+                }
+                name
+            }
+            None => raw(action),
+        },
+        KeyAction::Consumer(usage) => MEDIA_KEYS
+            .iter()
+            .find(|(u, ..)| *u == usage)
+            .map(|(_, short, _)| short.to_string())
+            .unwrap_or_else(|| raw(action)),
+        KeyAction::Mouse(btn @ 1..=5) => format!("KC_BTN{btn}"),
+        KeyAction::Fn => format!("MO({FN_LAYER})"),
+        KeyAction::Macro { index, kind: 0 } => format!("MACRO({index})"),
+        _ => raw(action),
+    }
+}
+
+/// `MG(0x...)`: the raw key config, for actions QMK has no keycode for.
+fn raw(action: &KeyAction) -> String {
+    format!("MG(0x{:08X})", u32::from_be_bytes(action.to_config_bytes()))
+}
+
+/// Parse a QMK keycode for matrix position `index` on `layer`.
+pub fn qmk_to_action(name: &str, index: u8, layer: Layer) -> Result<KeyAction, String> {
+    let name = name.trim();
+    match name {
+        "KC_NO" | "XXXXXXX" => return Ok(KeyAction::Disabled),
+        "KC_TRNS" | "KC_TRANSPARENT" | "_______" => {
+            return Ok(match (layer, default_keycode(index)) {
+                (Layer::Base, code) if code != 0 => KeyAction::Key(code),
+                _ => KeyAction::Disabled,
+            });
+        }
+        _ => {}
+    }
+    if let Some(code) = parse_basic(name) {
+        return Ok(KeyAction::Key(code));
+    }
+    if let Some((usage, ..)) = MEDIA_KEYS
+        .iter()
+        .find(|(_, short, long)| *short == name || *long == name)
+    {
+        return Ok(KeyAction::Consumer(*usage));
+    }
+    if let Some(btn) = name
+        .strip_prefix("KC_BTN")
+        .or_else(|| name.strip_prefix("MS_BTN"))
+        .and_then(|n| n.parse::<u8>().ok())
+        .filter(|n| (1..=5).contains(n))
+    {
+        return Ok(KeyAction::Mouse(btn));
+    }
+
+    let Some((func, arg)) = name.strip_suffix(')').and_then(|s| s.split_once('(')) else {
+        return Err(format!("unknown keycode \"{name}\""));
+    };
+    match func {
+        "MO" => match arg.trim().parse::<usize>() {
+            Ok(FN_LAYER) => Ok(KeyAction::Fn),
+            _ => Err(format!(
+                "{name}: only MO({FN_LAYER}) (the Fn key) exists on this keyboard"
+            )),
+        },
+        "MACRO" | "M" => arg
+            .trim()
+            .parse::<u8>()
+            .map(|index| KeyAction::Macro { index, kind: 0 })
+            .map_err(|_| format!("{name}: invalid macro index")),
+        "MG" => {
+            let hex = arg.trim().trim_start_matches("0x");
+            u32::from_str_radix(hex, 16)
+                .map(|v| KeyAction::from_config_bytes(v.to_be_bytes()))
+                .map_err(|_| format!("{name}: expected MG(0x<8 hex digits>)"))
+        }
+        _ => {
+            let Some(&(bit, ..)) = MOD_WRAPPERS
+                .iter()
+                .find(|(_, w, aliases)| *w == func || aliases.contains(&func))
+            else {
+                return Err(format!("unsupported keycode \"{name}\""));
+            };
+            match qmk_to_action(arg, index, Layer::Layer1)? {
+                KeyAction::Key(key) => Ok(KeyAction::Combo { mods: bit, key }),
+                KeyAction::Combo { mods, key } => Ok(KeyAction::Combo {
+                    mods: mods | bit,
+                    key,
+                }),
+                _ => Err(format!("{name}: modifiers only wrap basic keys")),
+            }
+        }
+    }
+}
+
+/// A keymap file: VIA's backup format restricted to what the vendor
+/// keymatrix can hold. Other VIA/QMK fields (`macros`, `keyboard`,
+/// `layout`, ...) are ignored on import.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ViaKeymap {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// `(vid << 16) | pid` of the keyboard the layout was made for
+    #[serde(
+        rename = "vendorProductId",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub vendor_product_id: Option<u32>,
+    pub layers: Vec<Vec<String>>,
+}
+
+impl ViaKeymap {
+    /// Export `keymap` for positions `0..key_count`.
+    pub fn from_keymap(keymap: &KeyMap, key_count: usize) -> Self {
+        let layers = Layer::ALL
+            .iter()
+            .map(|&layer| {
+                (0..key_count)
+                    .map(|i| {
+                        let action = keymap
+                            .get(i as u8, layer)
+                            .map(|e| e.action)
+                            .unwrap_or(KeyAction::Disabled);
+                        action_to_qmk(&action, layer)
+                    })
+                    .collect()
+            })
+            .collect();
+        Self {
+            name: None,
+            vendor_product_id: None,
+            layers,
+        }
+    }
+
+    /// Parse and validate a keymap file; every keycode is checked here.
+    pub fn from_json(s: &str) -> Result<Self, ConfigError> {
+        let file: Self =
+            serde_json::from_str(s).map_err(|e| ConfigError::invalid("json", e.to_string()))?;
+        file.actions()?;
+        Ok(file)
+    }
+
+    /// Render as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default() + "\n"
+    }
+
+    /// The action for every position in the file as `(index, layer, action)`.
+    /// Empty matrix slots are skipped.
+    pub fn actions(&self) -> Result<Vec<(u8, Layer, KeyAction)>, ConfigError> {
+        if self.layers.len() > Layer::ALL.len() {
+            return Err(ConfigError::invalid(
+                "layers",
+                format!(
+                    "{} layers, the keyboard has {}",
+                    self.layers.len(),
+                    Layer::ALL.len()
+                ),
+            ));
+        }
+        let mut out = Vec::new();
+        for (l, (keys, &layer)) in self.layers.iter().zip(Layer::ALL.iter()).enumerate() {
+            if keys.len() > u8::MAX as usize + 1 {
+                return Err(ConfigError::invalid(
+                    format!("layers[{l}]"),
+                    "too many positions",
+                ));
+            }
+            for (i, name) in keys.iter().enumerate() {
+                if matrix::key_name(i as u8) == "?" {
+                    continue;
+                }
+                let action = qmk_to_action(name, i as u8, layer)
+                    .map_err(|reason| ConfigError::invalid(format!("layers[{l}][{i}]"), reason))?;
+                out.push((i as u8, layer, action));
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// VIA's `vendorProductId` for a device.
+pub fn vendor_product_id(vid: u16, pid: u16) -> u32 {
+    (vid as u32) << 16 | pid as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap::RawKeyMapData;
+
+    #[test]
+    fn basic_keycodes_round_trip() {
+        for code in (0x04..=0x67u8).chain(0x68..=0x73).chain(0xE0..=0xE7) {
+            if let Some(name) = basic_name(code) {
+                assert_eq!(parse_basic(&name), Some(code), "{name}");
+            }
+        }
+        assert_eq!(parse_basic("KC_ESCAPE"), Some(0x29));
+        assert_eq!(parse_basic("KC_F13"), Some(0x68));
+        assert_eq!(parse_basic("KC_KP_5"), Some(0x5D));
+        assert_eq!(parse_basic("KC_LWIN"), Some(0xE3));
+    }
+
+    #[test]
+    fn parses_wrappers_layers_and_macros() {
+        let parse = |s| qmk_to_action(s, 3, Layer::Base).unwrap();
+        assert_eq!(
+            parse("LCTL(LSFT(KC_C))"),
+            KeyAction::Combo {
+                mods: mods::LCTRL | mods::LSHIFT,
+                key: 0x06
+            }
+        );
+        assert_eq!(parse("MO(2)"), KeyAction::Fn);
+        assert_eq!(parse("MACRO(4)"), KeyAction::Macro { index: 4, kind: 0 });
+        assert_eq!(parse("KC_MPLY"), KeyAction::Consumer(0xCD));
+        assert_eq!(parse("KC_BTN2"), KeyAction::Mouse(2));
+        assert!(qmk_to_action("MO(1)", 3, Layer::Base).is_err());
+        assert!(qmk_to_action("LT(1,KC_A)", 3, Layer::Base).is_err());
+    }
+
+    #[test]
+    fn transparent_depends_on_layer() {
+        // Index 3 is Caps
+        assert_eq!(
+            qmk_to_action("KC_TRNS", 3, Layer::Base).unwrap(),
+            KeyAction::Key(0x39)
+        );
+        assert_eq!(
+            qmk_to_action("_______", 3, Layer::Fn).unwrap(),
+            KeyAction::Disabled
+        );
+        assert_eq!(action_to_qmk(&KeyAction::Disabled, Layer::Base), "KC_NO");
+        assert_eq!(action_to_qmk(&KeyAction::Disabled, Layer::Fn), "KC_TRNS");
+    }
+
+    #[test]
+    fn unnamed_actions_export_raw_and_round_trip() {
+        let action: KeyAction = KeyAction::from_config_bytes([13, 1, 0, 0]);
+        let name = action_to_qmk(&action, Layer::Fn);
+        assert_eq!(name, "MG(0x0D010000)");
+        assert_eq!(qmk_to_action(&name, 0, Layer::Fn).unwrap(), action);
+    }
+
+    #[test]
+    fn keymap_export_import_round_trips() {
+        let key_count = 96;
+        let mut base0 = Vec::new();
+        for i in 0..key_count as u8 {
+            base0.extend_from_slice(&[0, 0, default_keycode(i), 0]);
+        }
+        base0[3 * 4 + 2] = 0x29; // Caps -> Esc
+        let mut fn_layer = vec![0u8; key_count * 4];
+        fn_layer[6 * 4..6 * 4 + 4].copy_from_slice(&[3, 0, 0xCD, 0]); // Fn+F1 -> Play
+        let map = KeyMap::from_raw(&RawKeyMapData {
+            base0,
+            base1: vec![0; key_count * 4],
+            fn_layer: Some(fn_layer),
+            key_count,
+        });
+
+        let mut file = ViaKeymap::from_keymap(&map, key_count);
+        file.vendor_product_id = Some(vendor_product_id(0x3151, 0x5030));
+        assert_eq!(file.layers[0][3], "KC_ESC");
+        assert_eq!(file.layers[2][6], "KC_MPLY");
+
+        let back = ViaKeymap::from_json(&file.to_json()).unwrap();
+        assert_eq!(back, file);
+        let actions = back.actions().unwrap();
+        assert!(actions.contains(&(3, Layer::Base, KeyAction::Key(0x29))));
+        assert!(actions.contains(&(6, Layer::Fn, KeyAction::Consumer(0xCD))));
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let err = ViaKeymap::from_json(r#"{"layers": [["KC_ESC", "KC_WAT"]]}"#).unwrap_err();
+        assert!(err.to_string().contains("layers[0][1]"), "{err}");
+        let four = r#"{"layers": [[], [], [], []]}"#;
+        assert!(ViaKeymap::from_json(four).is_err());
+    }
+}
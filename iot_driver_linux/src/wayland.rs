@@ -0,0 +1,404 @@
+//! Minimal Wayland wire protocol client.
+//!
+//! The few protocols we use (wlr-screencopy for screen sync,
+//! wlr-foreign-toplevel for focus switching) only need a handful of
+//! messages, so they are spoken directly over the compositor socket instead
+//! of pulling in a Wayland client stack.
+
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// `wl_display` is always object 1.
+pub(crate) const DISPLAY: u32 = 1;
+
+/// The compositor socket from `WAYLAND_DISPLAY` (absolute, or relative to
+/// `XDG_RUNTIME_DIR`); `None` outside a Wayland session.
+pub fn socket_path(display: Option<&str>, runtime_dir: Option<&str>) -> Option<PathBuf> {
+    let display = display.filter(|d| !d.is_empty())?;
+    if display.starts_with('/') {
+        return Some(PathBuf::from(display));
+    }
+    Some(PathBuf::from(runtime_dir?).join(display))
+}
+
+/// An outgoing request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Request {
+    object: u32,
+    opcode: u16,
+    args: Vec<u8>,
+}
+
+impl Request {
+    pub(crate) fn new(object: u32, opcode: u16) -> Self {
+        Self {
+            object,
+            opcode,
+            args: Vec::new(),
+        }
+    }
+
+    pub(crate) fn uint(mut self, v: u32) -> Self {
+        self.args.extend_from_slice(&v.to_ne_bytes());
+        self
+    }
+
+    pub(crate) fn int(self, v: i32) -> Self {
+        self.uint(v as u32)
+    }
+
+    pub(crate) fn string(mut self, s: &str) -> Self {
+        let len = s.len() + 1;
+        self = self.uint(len as u32);
+        self.args.extend_from_slice(s.as_bytes());
+        self.args.resize(self.args.len() + 1 + pad(len), 0);
+        self
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let size = (8 + self.args.len()) as u32;
+        let mut out = Vec::with_capacity(size as usize);
+        out.extend_from_slice(&self.object.to_ne_bytes());
+        out.extend_from_slice(&((size << 16) | self.opcode as u32).to_ne_bytes());
+        out.extend_from_slice(&self.args);
+        out
+    }
+}
+
+/// Padding after `len` bytes to the next 32-bit boundary.
+pub(crate) fn pad(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+/// An incoming event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Event {
+    pub(crate) object: u32,
+    pub(crate) opcode: u16,
+    pub(crate) args: Vec<u8>,
+}
+
+impl Event {
+    /// Split one event off the front of `buf`, if it holds a whole one.
+    pub(crate) fn parse(buf: &[u8]) -> Option<(Event, usize)> {
+        let header = buf.get(..8)?;
+        let object = u32::from_ne_bytes(header[..4].try_into().unwrap());
+        let word = u32::from_ne_bytes(header[4..].try_into().unwrap());
+        let size = (word >> 16) as usize;
+        let args = buf.get(8..size.max(8))?.to_vec();
+        Some((
+            Event {
+                object,
+                opcode: word as u16,
+                args,
+            },
+            size.max(8),
+        ))
+    }
+
+    pub(crate) fn args(&self) -> Args<'_> {
+        Args {
+            data: &self.args,
+            pos: 0,
+        }
+    }
+}
+
+/// Reader over an event's arguments.
+pub(crate) struct Args<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Args<'_> {
+    pub(crate) fn uint(&mut self) -> Result<u32, String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + 4)
+            .ok_or("truncated Wayland event")?;
+        self.pos += 4;
+        Ok(u32::from_ne_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String, String> {
+        let len = self.uint()? as usize;
+        if len == 0 {
+            return Ok(String::new());
+        }
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or("truncated Wayland event")?;
+        self.pos += len + pad(len);
+        Ok(String::from_utf8_lossy(&bytes[..len - 1]).into_owned())
+    }
+
+    /// A `wl_array` of 32-bit values.
+    pub(crate) fn uint_array(&mut self) -> Result<Vec<u32>, String> {
+        let len = self.uint()? as usize;
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or("truncated Wayland event")?;
+        self.pos += len + pad(len);
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|c| u32::from_ne_bytes(c.try_into().unwrap()))
+            .collect())
+    }
+}
+
+/// The compositor connection.
+pub(crate) struct Wire {
+    stream: UnixStream,
+    buf: Vec<u8>,
+    next_id: u32,
+}
+
+impl Wire {
+    pub(crate) fn new_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    pub(crate) fn send(&mut self, req: Request) -> Result<(), String> {
+        self.stream
+            .write_all(&req.encode())
+            .map_err(|e| format!("Wayland write failed: {e}"))
+    }
+
+    /// Send a request carrying `fd` (SCM_RIGHTS).
+    pub(crate) fn send_with_fd(&mut self, req: Request, fd: RawFd) -> Result<(), String> {
+        let bytes = req.encode();
+        let mut iov = libc::iovec {
+            iov_base: bytes.as_ptr() as *mut libc::c_void,
+            iov_len: bytes.len(),
+        };
+        // SAFETY: CMSG_SPACE only computes a size
+        let space = unsafe { libc::CMSG_SPACE(size_of::<RawFd>() as u32) } as usize;
+        // u64 storage keeps the cmsghdr aligned
+        let mut control = vec![0u64; space.div_ceil(8)];
+        // SAFETY: msghdr is plain data; every pointer set below outlives sendmsg
+        let sent = unsafe {
+            let mut msg: libc::msghdr = std::mem::zeroed();
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            msg.msg_controllen = space as _;
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(size_of::<RawFd>() as u32) as _;
+            std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+            libc::sendmsg(self.stream.as_raw_fd(), &msg, libc::MSG_NOSIGNAL)
+        };
+        match sent {
+            n if n < 0 => Err(format!(
+                "Wayland write failed: {}",
+                io::Error::last_os_error()
+            )),
+            n if n as usize != bytes.len() => Err("Wayland write truncated".into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Next event, blocking up to the read timeout. Protocol errors become
+    /// `Err`.
+    pub(crate) fn next_event(&mut self) -> Result<Event, String> {
+        loop {
+            if let Some((event, len)) = Event::parse(&self.buf) {
+                self.buf.drain(..len);
+                if event.object == DISPLAY && event.opcode == 0 {
+                    let mut args = event.args();
+                    let (object, code) = (args.uint()?, args.uint()?);
+                    let message = args.string()?;
+                    return Err(format!(
+                        "Wayland protocol error on object {object} (code {code}): {message}"
+                    ));
+                }
+                return Ok(event);
+            }
+            let mut chunk = [0u8; 4096];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err("compositor closed the connection".into()),
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Err("timed out waiting for the compositor".into());
+                }
+                Err(e) => return Err(format!("Wayland read failed: {e}")),
+            }
+        }
+    }
+
+    /// Send `wl_display.sync` and hand every event until it is done to
+    /// `on_event`.
+    pub(crate) fn roundtrip(
+        &mut self,
+        mut on_event: impl FnMut(&Event) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let callback = self.new_id();
+        self.send(Request::new(DISPLAY, 0).uint(callback))?;
+        loop {
+            let event = self.next_event()?;
+            if event.object == callback {
+                return Ok(());
+            }
+            on_event(&event)?;
+        }
+    }
+}
+
+impl Wire {
+    /// Connect to the compositor from `WAYLAND_DISPLAY`; reads give up after
+    /// `timeout` (`None` blocks).
+    pub(crate) fn connect(timeout: Option<Duration>) -> Result<Self, String> {
+        let path = socket_path(
+            std::env::var("WAYLAND_DISPLAY").ok().as_deref(),
+            std::env::var("XDG_RUNTIME_DIR").ok().as_deref(),
+        )
+        .ok_or("not a Wayland session (WAYLAND_DISPLAY unset)")?;
+        let stream = UnixStream::connect(&path)
+            .map_err(|e| format!("Failed to connect to {}: {e}", path.display()))?;
+        stream
+            .set_read_timeout(timeout)
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            stream,
+            buf: Vec::new(),
+            next_id: DISPLAY,
+        })
+    }
+
+    /// Get the registry and the globals it announces.
+    pub(crate) fn registry(&mut self) -> Result<Registry, String> {
+        let id = self.new_id();
+        self.send(Request::new(DISPLAY, 1).uint(id))?;
+        let mut globals = Vec::new();
+        self.roundtrip(|event| {
+            if event.object == id && event.opcode == 0 {
+                let mut args = event.args();
+                globals.push((args.uint()?, args.string()?, args.uint()?));
+            }
+            Ok(())
+        })?;
+        Ok(Registry { id, globals })
+    }
+}
+
+/// `wl_registry` and its globals as (name, interface, version).
+pub(crate) struct Registry {
+    id: u32,
+    globals: Vec<(u32, String, u32)>,
+}
+
+impl Registry {
+    /// Name and version of the global offering `interface`.
+    pub(crate) fn find(&self, interface: &str) -> Option<(u32, u32)> {
+        self.globals
+            .iter()
+            .find(|(_, name, _)| name == interface)
+            .map(|(global, _, version)| (*global, *version))
+    }
+
+    /// Bind a global, returning the new object id.
+    pub(crate) fn bind(
+        &self,
+        wire: &mut Wire,
+        global: u32,
+        interface: &str,
+        version: u32,
+    ) -> Result<u32, String> {
+        let id = wire.new_id();
+        wire.send(
+            Request::new(self.id, 0)
+                .uint(global)
+                .string(interface)
+                .uint(version)
+                .uint(id),
+        )
+        .map(|()| id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_registry_bind() {
+        let bytes = Request::new(2, 0)
+            .uint(7)
+            .string("wl_shm")
+            .uint(1)
+            .uint(5)
+            .encode();
+        // header + name + (len + "wl_shm\0" + pad) + version + id
+        assert_eq!(bytes.len(), 8 + 4 + 4 + 8 + 4 + 4);
+        assert_eq!(
+            u32::from_ne_bytes(bytes[4..8].try_into().unwrap()),
+            32 << 16
+        );
+        assert_eq!(u32::from_ne_bytes(bytes[8..12].try_into().unwrap()), 7);
+        assert_eq!(&bytes[16..23], b"wl_shm\0");
+        assert_eq!(u32::from_ne_bytes(bytes[28..32].try_into().unwrap()), 5);
+    }
+
+    #[test]
+    fn parses_global_events_and_waits_for_whole_messages() {
+        let mut global = Request::new(2, 0)
+            .uint(3)
+            .string("zwlr_screencopy_manager_v1")
+            .uint(3)
+            .encode();
+        assert_eq!(Event::parse(&global[..10]), None);
+
+        global.extend_from_slice(&Request::new(9, 2).encode());
+        let (event, len) = Event::parse(&global).unwrap();
+        assert_eq!((event.object, event.opcode), (2, 0));
+        let mut args = event.args();
+        assert_eq!(args.uint().unwrap(), 3);
+        assert_eq!(args.string().unwrap(), "zwlr_screencopy_manager_v1");
+        assert_eq!(args.uint().unwrap(), 3);
+        assert!(args.uint().is_err());
+
+        let (next, _) = Event::parse(&global[len..]).unwrap();
+        assert_eq!((next.object, next.opcode, next.args.len()), (9, 2, 0));
+    }
+
+    #[test]
+    fn socket_path_follows_wayland_display() {
+        assert_eq!(
+            socket_path(Some("wayland-1"), Some("/run/user/1000")),
+            Some(PathBuf::from("/run/user/1000/wayland-1"))
+        );
+        assert_eq!(
+            socket_path(Some("/tmp/wl"), None),
+            Some(PathBuf::from("/tmp/wl"))
+        );
+        assert_eq!(socket_path(None, Some("/run/user/1000")), None);
+        assert_eq!(socket_path(Some("wayland-0"), None), None);
+    }
+
+    #[test]
+    fn reads_uint_arrays() {
+        // state event: [activated, fullscreen], then a trailing uint
+        let event = Request::new(0xff00_0001, 4)
+            .uint(8)
+            .uint(2)
+            .uint(3)
+            .uint(9)
+            .encode();
+        let (event, _) = Event::parse(&event).unwrap();
+        let mut args = event.args();
+        assert_eq!(args.uint_array().unwrap(), vec![2, 3]);
+        assert_eq!(args.uint().unwrap(), 9);
+    }
+}
@@ -0,0 +1,126 @@
+// WebRTC signaling endpoint
+//
+// `iot_driver serve --webrtc` lets a remote client set up a WebRTC data
+// channel to the local keyboard without third-party signaling: the client
+// POSTs its offer to `/webrtc/offer` and gets the answer in the reply. The
+// local peer that owns the keyboard long-polls `GET /webrtc/offer` and posts
+// answers to `/webrtc/answer/{id}`; those two routes only accept loopback
+// clients, so nobody else can pick up offers. Offers go through the server's
+// token check like every other request.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::extract::{Path, Request, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use serde::Deserialize;
+use serde_json::json;
+use tonic::transport::server::TcpConnectInfo;
+use tracing::info;
+
+use iot_driver::signaling::{SignalError, Signaling};
+
+/// How long a remote offer waits for the local peer's answer.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long the local peer's `GET /webrtc/offer` waits for an offer.
+const POLL_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// Session description as sent by `RTCPeerConnection`.
+#[derive(Deserialize)]
+struct Description {
+    #[serde(rename = "type")]
+    kind: String,
+    sdp: String,
+}
+
+fn reply(status: StatusCode, value: serde_json::Value) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/json")],
+        value.to_string(),
+    )
+        .into_response()
+}
+
+fn error(status: StatusCode, msg: impl std::fmt::Display) -> Response {
+    reply(status, json!({ "error": msg.to_string() }))
+}
+
+/// The SDP of a `kind` description in `body`, or why it is unusable.
+fn description(body: &Bytes, kind: &str) -> Result<String, String> {
+    match serde_json::from_slice::<Description>(body) {
+        Ok(d) if d.kind == kind => Ok(d.sdp),
+        Ok(d) => Err(format!("expected type \"{kind}\", got \"{}\"", d.kind)),
+        Err(e) => Err(format!("invalid JSON body: {e}")),
+    }
+}
+
+fn is_local(req: &Request) -> bool {
+    req.extensions()
+        .get::<TcpConnectInfo>()
+        .and_then(|info| info.remote_addr())
+        .is_some_and(|addr| addr.ip().is_loopback())
+}
+
+/// Build the `/webrtc` routes.
+pub fn router() -> Router {
+    Router::new()
+        .route("/webrtc/offer", post(offer).get(next_offer))
+        .route("/webrtc/answer/:id", post(answer))
+        .with_state(Arc::new(Signaling::new()))
+}
+
+async fn offer(State(signaling): State<Arc<Signaling>>, body: Bytes) -> Response {
+    let sdp = match description(&body, "offer") {
+        Ok(sdp) => sdp,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+    info!("WebRTC: offer received, waiting for the local peer");
+    match signaling.offer(sdp, ANSWER_TIMEOUT).await {
+        Ok(sdp) => reply(StatusCode::OK, json!({ "type": "answer", "sdp": sdp })),
+        Err(e @ SignalError::Busy) => error(StatusCode::TOO_MANY_REQUESTS, e),
+        Err(e) => error(StatusCode::GATEWAY_TIMEOUT, e),
+    }
+}
+
+async fn next_offer(State(signaling): State<Arc<Signaling>>, req: Request) -> Response {
+    if !is_local(&req) {
+        return error(StatusCode::FORBIDDEN, "local peer only");
+    }
+    match signaling.next_offer(POLL_TIMEOUT).await {
+        Some((id, sdp)) => reply(
+            StatusCode::OK,
+            json!({ "id": id, "type": "offer", "sdp": sdp }),
+        ),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+async fn answer(
+    State(signaling): State<Arc<Signaling>>,
+    Path(id): Path<u64>,
+    req: Request,
+) -> Response {
+    if !is_local(&req) {
+        return error(StatusCode::FORBIDDEN, "local peer only");
+    }
+    let body = match axum::body::to_bytes(req.into_body(), 64 * 1024).await {
+        Ok(body) => body,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+    let sdp = match description(&body, "answer") {
+        Ok(sdp) => sdp,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+    match signaling.answer(id, sdp) {
+        Ok(()) => {
+            info!("WebRTC: answered session {id}");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => error(StatusCode::NOT_FOUND, e),
+    }
+}
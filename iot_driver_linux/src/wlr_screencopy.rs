@@ -0,0 +1,350 @@
+//! Minimal Wayland client for `wlr-screencopy-unstable-v1`.
+//!
+//! wlroots-based compositors (Sway, Hyprland, niri, river, …) expose
+//! `zwlr_screencopy_manager_v1`, which copies an output into a shared-memory
+//! buffer without going through the xdg-desktop-portal picker or PipeWire.
+//! Screen sync only needs the occasional frame to average, so this speaks
+//! the handful of wire messages involved directly (see [`crate::wayland`]).
+
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::Duration;
+
+use crate::screen_calib::Region;
+use crate::wayland::{Request, Wire};
+
+/// Newest `zwlr_screencopy_manager_v1` version we speak (adds `buffer_done`).
+const MANAGER_VERSION: u32 = 3;
+/// How long to wait for the compositor before giving up on a request.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// `wl_shm` formats we can read: (format, memory order is BGRA).
+const SHM_FORMATS: &[(u32, bool)] = &[
+    (0, true),            // ARGB8888
+    (1, true),            // XRGB8888
+    (0x3432_4241, false), // ABGR8888
+    (0x3432_4258, false), // XBGR8888
+];
+
+/// Whether the compositor offers wlr-screencopy.
+pub fn is_available() -> bool {
+    Screencopy::connect().is_ok()
+}
+
+/// Buffer layout announced by the compositor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BufferParams {
+    format: u32,
+    width: u32,
+    height: u32,
+    stride: u32,
+}
+
+/// A `wl_buffer` backed by a memfd mapped into our address space.
+struct ShmBuffer {
+    params: BufferParams,
+    pool: u32,
+    buffer: u32,
+    map: *mut u8,
+    len: usize,
+    _fd: OwnedFd,
+}
+
+impl ShmBuffer {
+    fn create(wire: &mut Wire, shm: u32, params: BufferParams) -> Result<Self, String> {
+        let len = params.stride as usize * params.height as usize;
+        // SAFETY: plain syscalls; results are checked before use
+        let (fd, map) = unsafe {
+            let raw = libc::memfd_create(c"iot_driver-screencopy".as_ptr(), libc::MFD_CLOEXEC);
+            if raw < 0 {
+                return Err(format!(
+                    "memfd_create failed: {}",
+                    io::Error::last_os_error()
+                ));
+            }
+            let fd = OwnedFd::from_raw_fd(raw);
+            if libc::ftruncate(raw, len as libc::off_t) < 0 {
+                return Err(format!("ftruncate failed: {}", io::Error::last_os_error()));
+            }
+            let map = libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                raw,
+                0,
+            );
+            if map == libc::MAP_FAILED {
+                return Err(format!("mmap failed: {}", io::Error::last_os_error()));
+            }
+            (fd, map as *mut u8)
+        };
+
+        let pool = wire.new_id();
+        // wl_shm.create_pool(id, fd, size)
+        wire.send_with_fd(
+            Request::new(shm, 0).uint(pool).int(len as i32),
+            fd.as_raw_fd(),
+        )?;
+        let buffer = wire.new_id();
+        // wl_shm_pool.create_buffer(id, offset, width, height, stride, format)
+        wire.send(
+            Request::new(pool, 0)
+                .uint(buffer)
+                .int(0)
+                .int(params.width as i32)
+                .int(params.height as i32)
+                .int(params.stride as i32)
+                .uint(params.format),
+        )?;
+        Ok(Self {
+            params,
+            pool,
+            buffer,
+            map,
+            len,
+            _fd: fd,
+        })
+    }
+
+    /// Destroy the buffer and pool on the compositor side.
+    fn release(&self, wire: &mut Wire) {
+        let _ = wire.send(Request::new(self.buffer, 0)); // wl_buffer.destroy
+        let _ = wire.send(Request::new(self.pool, 1)); // wl_shm_pool.destroy
+    }
+
+    fn data(&self) -> &[u8] {
+        // SAFETY: `map` is a live mapping of `len` bytes until drop
+        unsafe { std::slice::from_raw_parts(self.map, self.len) }
+    }
+}
+
+impl Drop for ShmBuffer {
+    fn drop(&mut self) {
+        // SAFETY: unmapping the region mapped in `create`
+        unsafe {
+            libc::munmap(self.map as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// One captured frame, 4 bytes per pixel.
+pub struct Frame<'a> {
+    pub data: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+    /// Bytes per row (at least `width * 4`)
+    pub stride: u32,
+    /// Pixels are B, G, R, x in memory (else R, G, B, x)
+    pub is_bgra: bool,
+    /// Rows are stored bottom-up
+    pub y_invert: bool,
+}
+
+impl Frame<'_> {
+    /// Width in pixels including row padding, for code that assumes tightly
+    /// packed rows.
+    pub fn padded_width(&self) -> u32 {
+        self.stride / 4
+    }
+
+    /// `region` (relative to the visible screen) mapped onto the buffer as
+    /// stored: scaled into the padded row and flipped when bottom-up.
+    pub fn buffer_region(&self, region: Region) -> Region {
+        let scale = self.width as f32 / self.padded_width().max(1) as f32;
+        let (top, bottom) = if self.y_invert {
+            (1.0 - region.bottom, 1.0 - region.top)
+        } else {
+            (region.top, region.bottom)
+        };
+        Region {
+            left: region.left * scale,
+            top,
+            right: region.right * scale,
+            bottom,
+        }
+    }
+}
+
+/// A screencopy session on the first output.
+pub struct Screencopy {
+    wire: Wire,
+    shm: u32,
+    output: u32,
+    manager: u32,
+    manager_version: u32,
+    buffer: Option<ShmBuffer>,
+}
+
+impl Screencopy {
+    /// Connect to the compositor and bind `wl_shm`, the first `wl_output`
+    /// and the screencopy manager. Fails outside Wayland or when the
+    /// compositor doesn't offer wlr-screencopy (GNOME, KDE).
+    pub fn connect() -> Result<Self, String> {
+        let mut wire = Wire::connect(Some(TIMEOUT))?;
+        let registry = wire.registry()?;
+        let (manager_global, manager_version) = registry
+            .find("zwlr_screencopy_manager_v1")
+            .ok_or("compositor does not support wlr-screencopy")?;
+        let (shm_global, _) = registry.find("wl_shm").ok_or("compositor has no wl_shm")?;
+        let (output_global, _) = registry.find("wl_output").ok_or("no outputs")?;
+
+        let manager_version = manager_version.min(MANAGER_VERSION);
+        let manager = registry.bind(
+            &mut wire,
+            manager_global,
+            "zwlr_screencopy_manager_v1",
+            manager_version,
+        )?;
+        let shm = registry.bind(&mut wire, shm_global, "wl_shm", 1)?;
+        let output = registry.bind(&mut wire, output_global, "wl_output", 1)?;
+        wire.roundtrip(|_| Ok(()))?;
+
+        Ok(Self {
+            wire,
+            shm,
+            output,
+            manager,
+            manager_version,
+            buffer: None,
+        })
+    }
+
+    /// Copy the current contents of the output.
+    pub fn capture(&mut self) -> Result<Frame<'_>, String> {
+        let frame = self.wire.new_id();
+        // capture_output(frame, overlay_cursor = 0, output)
+        self.wire.send(
+            Request::new(self.manager, 0)
+                .uint(frame)
+                .int(0)
+                .uint(self.output),
+        )?;
+
+        let mut params = None;
+        let mut flags = 0;
+        let result = loop {
+            let event = match self.wire.next_event() {
+                Ok(e) => e,
+                Err(e) => break Err(e),
+            };
+            if event.object != frame {
+                continue;
+            }
+            let mut args = event.args();
+            match event.opcode {
+                // buffer(format, width, height, stride): one per shm option
+                0 => {
+                    let p = BufferParams {
+                        format: args.uint()?,
+                        width: args.uint()?,
+                        height: args.uint()?,
+                        stride: args.uint()?,
+                    };
+                    if params.is_none() && bgra_order(p.format).is_some() {
+                        params = Some(p);
+                    }
+                    // Before v3 the single buffer event is the cue to copy
+                    if self.manager_version < 3 {
+                        if let Err(e) = self.copy(frame, params) {
+                            break Err(e);
+                        }
+                    }
+                }
+                1 => flags = args.uint()?,
+                2 => break Ok(()),
+                3 => break Err("compositor failed to copy the output".into()),
+                // buffer_done: all buffer options announced
+                6 => {
+                    if let Err(e) = self.copy(frame, params) {
+                        break Err(e);
+                    }
+                }
+                _ => {}
+            }
+        };
+        let _ = self.wire.send(Request::new(frame, 1)); // frame.destroy
+        result?;
+
+        let buffer = self.buffer.as_ref().ok_or("no frame was copied")?;
+        let p = buffer.params;
+        Ok(Frame {
+            data: buffer.data(),
+            width: p.width,
+            height: p.height,
+            stride: p.stride,
+            is_bgra: bgra_order(p.format).unwrap_or(true),
+            y_invert: flags & 1 != 0,
+        })
+    }
+
+    /// Ask for the frame to be copied into a buffer matching `params`.
+    fn copy(&mut self, frame: u32, params: Option<BufferParams>) -> Result<(), String> {
+        let params = params.ok_or("compositor offers no supported shm pixel format")?;
+        if self.buffer.as_ref().map(|b| b.params) != Some(params) {
+            if let Some(old) = self.buffer.take() {
+                old.release(&mut self.wire);
+            }
+            self.buffer = Some(ShmBuffer::create(&mut self.wire, self.shm, params)?);
+        }
+        let buffer = self.buffer.as_ref().unwrap().buffer;
+        self.wire.send(Request::new(frame, 0).uint(buffer)) // frame.copy
+    }
+}
+
+impl Drop for Screencopy {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            buffer.release(&mut self.wire);
+        }
+        let _ = self.wire.send(Request::new(self.manager, 2)); // manager.destroy
+    }
+}
+
+/// Memory order of a supported shm format: `Some(true)` for B, G, R.
+fn bgra_order(format: u32) -> Option<bool> {
+    SHM_FORMATS
+        .iter()
+        .find(|(f, _)| *f == format)
+        .map(|&(_, bgra)| bgra)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_regions_onto_padded_and_inverted_buffers() {
+        let data = [];
+        let frame = Frame {
+            data: &data,
+            width: 300,
+            height: 100,
+            stride: 400 * 4,
+            is_bgra: true,
+            y_invert: true,
+        };
+        let region = Region {
+            left: 0.0,
+            top: 0.0,
+            right: 1.0,
+            bottom: 0.25,
+        };
+        assert_eq!(
+            frame.buffer_region(region),
+            Region {
+                left: 0.0,
+                top: 0.75,
+                right: 0.75,
+                bottom: 1.0,
+            }
+        );
+    }
+
+    #[test]
+    fn knows_pixel_order_of_shm_formats() {
+        assert_eq!(bgra_order(1), Some(true));
+        assert_eq!(bgra_order(0x3432_4258), Some(false));
+        assert_eq!(bgra_order(0x3033_5258), None); // XRGB2101010
+    }
+}
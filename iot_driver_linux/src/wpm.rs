@@ -0,0 +1,268 @@
+//! Typing speed meter lighting (`iot_driver wpm`).
+//!
+//! Key presses from evdev feed a [`WpmMeter`]: presses in a sliding window,
+//! five per word, smoothed so the display glides instead of jumping on every
+//! keystroke. The speed is shown either as one color across the board or as
+//! a bar filling the function row, streamed over the patch LED protocol.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::evdev::{keycode_to_hid, monotonic_now, EventReader};
+use crate::keyboard_config::parse_color;
+use crate::led_stream::{apply_power_budget, send_full_frame};
+use crate::notify::keymap::{COLS, MATRIX_LEN};
+use monsgeek_keyboard::{KeyboardInterface, RgbColor};
+
+/// Keystrokes per word, the usual typing-test convention.
+const CHARS_PER_WORD: f32 = 5.0;
+/// Time constant of the display smoothing.
+const SMOOTHING: Duration = Duration::from_millis(600);
+/// Frame interval of the render loop (~30 FPS).
+const FRAME: Duration = Duration::from_millis(33);
+
+/// Whether an evdev key code counts as a keystroke: any keyboard-page key
+/// except the modifiers, which are held rather than typed.
+pub fn is_keystroke(code: u16) -> bool {
+    keycode_to_hid(code).is_some_and(|hid| !(0xE0..=0xE7).contains(&hid))
+}
+
+/// Words per minute over a sliding window of keystrokes.
+pub struct WpmMeter {
+    window: Duration,
+    presses: VecDeque<Duration>,
+    smoothed: f32,
+}
+
+impl WpmMeter {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window: window.max(Duration::from_secs(1)),
+            presses: VecDeque::new(),
+            smoothed: 0.0,
+        }
+    }
+
+    /// Record a keystroke at monotonic time `t`.
+    pub fn press(&mut self, t: Duration) {
+        self.presses.push_back(t);
+    }
+
+    /// Unsmoothed speed at `now`, dropping presses that left the window.
+    pub fn wpm(&mut self, now: Duration) -> f32 {
+        while self
+            .presses
+            .front()
+            .is_some_and(|&t| now.saturating_sub(t) > self.window)
+        {
+            self.presses.pop_front();
+        }
+        self.presses.len() as f32 / CHARS_PER_WORD * 60.0 / self.window.as_secs_f32()
+    }
+
+    /// Smoothed speed at `now`, `dt` after the previous update.
+    pub fn update(&mut self, now: Duration, dt: Duration) -> f32 {
+        let target = self.wpm(now);
+        let k = 1.0 - (-dt.as_secs_f32() / SMOOTHING.as_secs_f32()).exp();
+        self.smoothed += (target - self.smoothed) * k;
+        self.smoothed
+    }
+}
+
+/// How the speed is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WpmDisplay {
+    /// The whole board in one color from the slow-fast gradient
+    #[default]
+    Gradient,
+    /// A bar filling the function row left to right, everything else dark
+    Bar,
+}
+
+/// WPM meter settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WpmStyle {
+    pub display: WpmDisplay,
+    /// Color at 0 WPM
+    pub slow: RgbColor,
+    /// Color at `max_wpm` and above
+    pub fast: RgbColor,
+    /// Speed that fills the gradient/bar
+    pub max_wpm: f32,
+}
+
+impl Default for WpmStyle {
+    fn default() -> Self {
+        Self {
+            display: WpmDisplay::default(),
+            slow: RgbColor::new(0, 255, 0),
+            fast: RgbColor::new(255, 0, 0),
+            max_wpm: 120.0,
+        }
+    }
+}
+
+/// Parse `--colors` for the WPM meter: `SLOW,FAST` as `#RRGGBB`.
+pub fn parse_colors(s: &str) -> Result<(RgbColor, RgbColor), String> {
+    let color = |c: &str| parse_color(c).ok_or_else(|| format!("invalid color '{c}'"));
+    match s.split(',').map(str::trim).collect::<Vec<_>>().as_slice() {
+        [slow, fast] => Ok((color(slow)?, color(fast)?)),
+        _ => Err(format!("expected SLOW,FAST colors, got '{s}'")),
+    }
+}
+
+impl WpmStyle {
+    /// Gradient color at `t` (0.0 slow - 1.0 fast). Blends in RGB, then
+    /// restores brightness so green to red passes through yellow, not olive.
+    pub fn color_at(&self, t: f32) -> RgbColor {
+        let t = t.clamp(0.0, 1.0);
+        let (a, b) = (self.slow, self.fast);
+        let mix = |x: u8, y: u8| x as f32 + (y as f32 - x as f32) * t;
+        let (r, g, bl) = (mix(a.r, b.r), mix(a.g, b.g), mix(a.b, b.b));
+        let peak = |c: RgbColor| c.r.max(c.g).max(c.b) as f32;
+        let want = peak(a) + (peak(b) - peak(a)) * t;
+        let have = r.max(g).max(bl);
+        let gain = if have > 0.0 { want / have } else { 0.0 };
+        let ch = |v: f32| (v * gain).round().clamp(0.0, 255.0) as u8;
+        RgbColor::new(ch(r), ch(g), ch(bl))
+    }
+
+    /// One LED frame (row-major) for the given speed.
+    pub fn render(&self, wpm: f32) -> [(u8, u8, u8); MATRIX_LEN] {
+        let t = (wpm / self.max_wpm.max(1.0)).clamp(0.0, 1.0);
+        match self.display {
+            WpmDisplay::Gradient => {
+                let c = self.color_at(t);
+                [(c.r, c.g, c.b); MATRIX_LEN]
+            }
+            WpmDisplay::Bar => {
+                let mut leds = [(0, 0, 0); MATRIX_LEN];
+                let fill = t * COLS as f32;
+                for (col, led) in leds.iter_mut().take(COLS).enumerate() {
+                    // The leading key fades in with the fractional part
+                    let level = (fill - col as f32).clamp(0.0, 1.0);
+                    let c = self.color_at(col as f32 / (COLS - 1) as f32);
+                    let s = |v: u8| (v as f32 * level).round() as u8;
+                    *led = (s(c.r), s(c.g), s(c.b));
+                }
+                leds
+            }
+        }
+    }
+}
+
+/// Run the WPM meter (blocking) until `running` clears.
+///
+/// `reader` must be open on the keyboard's evdev nodes. The keyboard must
+/// support patch LED streaming; the stream is released on exit.
+pub fn run_wpm(
+    keyboard: &KeyboardInterface,
+    reader: &EventReader,
+    style: &WpmStyle,
+    window: Duration,
+    power_budget: u32,
+    running: Arc<AtomicBool>,
+) -> Result<(), String> {
+    let mut meter = WpmMeter::new(window);
+    let mut last = monotonic_now();
+    let mut result = Ok(());
+    while running.load(Ordering::SeqCst) {
+        for ev in reader.poll(FRAME) {
+            if ev.is_key_edge() && ev.value == 1 && is_keystroke(ev.code) {
+                meter.press(ev.time);
+            }
+        }
+        let now = monotonic_now();
+        let wpm = meter.update(now, now.saturating_sub(last));
+        last = now;
+
+        let mut leds = style.render(wpm);
+        apply_power_budget(&mut leds, power_budget);
+        if let Err(e) = send_full_frame(keyboard, &leds) {
+            result = Err(format!("Failed to send LED frame: {e}"));
+            break;
+        }
+        print!("\r{wpm:5.0} WPM  ");
+        std::io::Write::flush(&mut std::io::stdout()).ok();
+    }
+
+    keyboard.stream_led_release().ok();
+    println!("\nWPM meter stopped");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: f32) -> Duration {
+        Duration::from_secs_f32(s)
+    }
+
+    #[test]
+    fn counts_five_keystrokes_per_word_over_the_window() {
+        let mut meter = WpmMeter::new(secs(6.0));
+        // 50 presses in 6 seconds: 10 words in a tenth of a minute
+        for i in 0..50 {
+            meter.press(secs(i as f32 * 0.12));
+        }
+        assert!((meter.wpm(secs(6.0)) - 100.0).abs() < 0.01);
+        // Ten seconds later they have all left the window
+        assert_eq!(meter.wpm(secs(16.0)), 0.0);
+    }
+
+    #[test]
+    fn smoothing_approaches_the_target() {
+        let mut meter = WpmMeter::new(secs(6.0));
+        for i in 0..50 {
+            meter.press(secs(i as f32 * 0.01));
+        }
+        let first = meter.update(secs(1.0), FRAME);
+        assert!(first > 0.0 && first < 20.0, "{first}");
+        let mut wpm = first;
+        for i in 1..60 {
+            wpm = meter.update(secs(1.0) + FRAME * i, FRAME);
+        }
+        assert!((wpm - 100.0).abs() < 5.0, "{wpm}");
+    }
+
+    #[test]
+    fn modifiers_are_not_keystrokes() {
+        assert!(is_keystroke(30)); // A
+        assert!(is_keystroke(57)); // Space
+        assert!(!is_keystroke(42)); // Left Shift
+        assert!(!is_keystroke(29)); // Left Ctrl
+    }
+
+    #[test]
+    fn gradient_keeps_brightness_through_the_middle() {
+        let style = WpmStyle::default();
+        assert_eq!(style.color_at(0.0), RgbColor::new(0, 255, 0));
+        assert_eq!(style.color_at(1.0), RgbColor::new(255, 0, 0));
+        assert_eq!(style.color_at(0.5), RgbColor::new(255, 255, 0));
+        assert_eq!(style.render(500.0)[40], (255, 0, 0));
+    }
+
+    #[test]
+    fn bar_fills_the_function_row() {
+        let style = WpmStyle {
+            display: WpmDisplay::Bar,
+            ..Default::default()
+        };
+        let leds = style.render(style.max_wpm * 0.25);
+        let lit: Vec<_> = leds.iter().map(|&c| c != (0, 0, 0)).collect();
+        assert!(lit[..COLS / 4].iter().all(|&l| l));
+        assert!(!lit[COLS / 4..].iter().any(|&l| l));
+        assert_eq!(leds[0], (0, 255, 0));
+        assert!(style.render(0.0).iter().all(|&c| c == (0, 0, 0)));
+    }
+
+    #[test]
+    fn parses_slow_and_fast_colors() {
+        let (slow, fast) = parse_colors("#0000ff, FF00FF").unwrap();
+        assert_eq!((slow.b, fast.r), (255, 255));
+        assert!(parse_colors("#00ff00").is_err());
+    }
+}
@@ -0,0 +1,193 @@
+// WebSocket event stream
+//
+// `iot_driver serve --events ADDR` streams keyboard events as JSON text
+// messages on `ws://ADDR/events`, one object per event (see `event_json`),
+// for dashboards and OBS overlays. `?depth=1` also switches on key depth
+// reports while the client is connected. Runs on its own listener because
+// the gRPC server does not support HTTP/1.1 upgrades. The server token (if
+// set) is accepted as a bearer header or `?token=`, since browsers cannot
+// set WebSocket headers; browser clients must come from an allowed origin.
+
+use std::sync::Arc;
+
+use axum::extract::ws::rejection::WebSocketUpgradeRejection;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+use crate::grpc::DriverService;
+use iot_driver::settings::ServerSettings;
+use monsgeek_transport::VendorEvent;
+
+#[derive(Clone)]
+struct WsState {
+    service: Arc<DriverService>,
+    access: Arc<ServerSettings>,
+}
+
+/// Serve `/events` on `listener` until the task is aborted.
+pub async fn serve(
+    service: Arc<DriverService>,
+    listener: tokio::net::TcpListener,
+    access: ServerSettings,
+) {
+    let state = WsState {
+        service,
+        access: Arc::new(access),
+    };
+    let app = Router::new()
+        .route("/events", get(events))
+        .with_state(state);
+    if let Err(e) = axum::serve(listener, app).await {
+        warn!("WebSocket server stopped: {e}");
+    }
+}
+
+fn query_param<'a>(uri: &'a Uri, name: &str) -> Option<&'a str> {
+    uri.query()?
+        .split('&')
+        .find_map(|p| p.strip_prefix(name)?.strip_prefix('='))
+}
+
+async fn events(
+    State(state): State<WsState>,
+    headers: HeaderMap,
+    uri: Uri,
+    ws: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+) -> Response {
+    if let Some(origin) = headers.get(header::ORIGIN).and_then(|o| o.to_str().ok()) {
+        if !state.access.origin_allowed(origin) {
+            warn!("WebSocket: rejected origin {origin}");
+            return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+        }
+    }
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if !state
+        .access
+        .token_matches(bearer.or(query_param(&uri, "token")))
+    {
+        return (StatusCode::UNAUTHORIZED, "Missing or wrong token").into_response();
+    }
+    let ws = match ws {
+        Ok(ws) => ws,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let depth = matches!(query_param(&uri, "depth"), Some("1" | "true"));
+    let service = state.service;
+    ws.on_upgrade(move |socket| session(service, socket, depth))
+}
+
+/// Forward events to one client until it closes or the connection drops.
+/// Pings are answered by the WebSocket layer; other client messages are
+/// ignored.
+async fn session(service: Arc<DriverService>, mut socket: WebSocket, depth: bool) {
+    info!("WebSocket client connected (key depth: {depth})");
+    // Open whatever is plugged in so its events are polled.
+    service.scan_devices().await;
+    let mut sub = service.subscribe_events();
+    if depth {
+        service.set_key_depth_reporting(true).await;
+    }
+
+    loop {
+        tokio::select! {
+            ev = sub.rx.recv() => match ev {
+                Ok(ev) => {
+                    if !depth && matches!(ev.event, VendorEvent::KeyDepth { .. }) {
+                        continue;
+                    }
+                    let Some(msg) = event_json(&ev.device, &ev.event) else {
+                        continue;
+                    };
+                    if socket.send(Message::Text(msg.to_string())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(n)) => debug!("WebSocket client lagged by {n} events"),
+                Err(RecvError::Closed) => break,
+            },
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+
+    if depth {
+        service.set_key_depth_reporting(false).await;
+    }
+    info!("WebSocket client disconnected");
+}
+
+/// JSON message for a vendor event from the device at `path`, or `None` for
+/// reports that are not useful to dashboards (mouse, raw, acks).
+///
+/// Every message has a `type` (same names as the D-Bus `Event` signal, plus
+/// `key-depth`) and the `device` path; other fields depend on the type.
+pub fn event_json(path: &str, event: &VendorEvent) -> Option<Value> {
+    let (kind, fields) = match *event {
+        VendorEvent::KeyDepth {
+            key_index,
+            depth_raw,
+        } => (
+            "key-depth",
+            json!({ "key": key_index, "depth_raw": depth_raw }),
+        ),
+        VendorEvent::Wake => ("wake", json!({})),
+        VendorEvent::Sleep => ("sleep", json!({})),
+        VendorEvent::DeepSleep => ("deep-sleep", json!({})),
+        VendorEvent::ProfileChange { profile } => ("profile", json!({ "profile": profile })),
+        VendorEvent::LedEffectMode { effect_id } => ("led-mode", json!({ "mode": effect_id })),
+        VendorEvent::LedEffectSpeed { speed } => ("led-speed", json!({ "speed": speed })),
+        VendorEvent::BrightnessLevel { level } => ("brightness", json!({ "level": level })),
+        VendorEvent::LedColor { color } => ("led-color", json!({ "color": color })),
+        VendorEvent::WinLockToggle { locked } => ("win-lock", json!({ "locked": locked })),
+        VendorEvent::WasdSwapToggle { swapped } => ("wasd-swap", json!({ "swapped": swapped })),
+        VendorEvent::BacklightToggle => ("backlight-toggle", json!({})),
+        VendorEvent::FnLayerToggle { layer } => ("fn-layer", json!({ "layer": layer })),
+        VendorEvent::DialModeToggle => ("dial-mode", json!({})),
+        VendorEvent::BatteryStatus {
+            level,
+            charging,
+            online,
+        } => (
+            "battery",
+            json!({ "level": level, "charging": charging, "online": online }),
+        ),
+        _ => return None,
+    };
+    let mut msg = json!({ "type": kind, "device": path });
+    if let (Some(msg), Value::Object(fields)) = (msg.as_object_mut(), fields) {
+        msg.extend(fields);
+    }
+    Some(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_messages() {
+        let msg = event_json("dev", &VendorEvent::ProfileChange { profile: 2 }).unwrap();
+        assert_eq!(
+            msg,
+            json!({ "type": "profile", "device": "dev", "profile": 2 })
+        );
+        let depth = VendorEvent::KeyDepth {
+            key_index: 7,
+            depth_raw: 120,
+        };
+        assert_eq!(event_json("dev", &depth).unwrap()["depth_raw"], 120);
+        assert!(event_json("dev", &VendorEvent::Unknown(vec![1])).is_none());
+    }
+}
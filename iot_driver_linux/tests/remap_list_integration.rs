@@ -81,6 +81,17 @@ fn hid_key_code_abbreviations() {
     assert_eq!(hid::key_code_from_name("RAlt"), Some(0xE6));
 }
 
+#[test]
+fn hid_key_code_database_names() {
+    assert_eq!(hid::key_code_from_name("Numpad7"), Some(0x5F));
+    assert_eq!(hid::key_code_from_name("Numpad0"), Some(0x62));
+    assert_eq!(hid::key_code_from_name("NumpadAdd"), Some(0x57));
+    assert_eq!(hid::key_code_from_name("NumpadEnter"), Some(0x58));
+    assert_eq!(hid::key_code_from_name("LMeta"), Some(0xE3));
+    assert_eq!(hid::key_code_from_name("IntlBackslash"), Some(0x64));
+    assert_eq!(hid::key_code_from_name("NumpadSomething"), None);
+}
+
 // ── Byte-pattern detection ──
 
 #[test]